@@ -1,5 +1,8 @@
 #!/bin/sh
 
+# Compare with lua and python, see `diatom/benches/suite.rs` for benchmarks of the interpreter
+# itself with criterion
+
 dir=$(CDPATH= cd -- "$(dirname -- "$0")" && pwd)
 cd $dir
 
@@ -20,3 +23,11 @@ bench -n diatom "${diatom} fibonacci/fib.dm" -n lua "lua fibonacci/fib.lua" -n p
 # bench loop
 echo "\n${font}Benchmark plain loop...${normal}\n"
 bench -n diatom "${diatom} loop/loop.dm" -n lua "lua loop/loop.lua" -n python "python3 loop/loop.py"
+
+# bench integer keyed table
+echo "\n${font}Benchmark integer keyed table...${normal}\n"
+bench -n diatom "${diatom} table/table.dm" -n lua "lua table/table.lua" -n python "python3 table/table.py"
+
+# bench string building
+echo "\n${font}Benchmark string building...${normal}\n"
+bench -n diatom "${diatom} string/string.dm" -n lua "lua string/string.lua" -n python "python3 string/string.py"
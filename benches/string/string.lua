@@ -0,0 +1,5 @@
+local s = {}
+for i = 1, 1000000 do
+    s[#s + 1] = "piece"
+end
+print(#table.concat(s))
@@ -0,0 +1,14 @@
+t = {}
+i = 0
+while i < 10000000 do
+    t[i] = i
+    i = i + 1
+end
+
+sum = 0
+i = 0
+while i < 10000000 do
+    sum = sum + t[i]
+    i = i + 1
+end
+print(sum)
@@ -0,0 +1,12 @@
+t = {}
+i = 0
+while i < 10000000:
+    t[i] = i
+    i = i + 1
+
+sum = 0
+i = 0
+while i < 10000000:
+    sum = sum + t[i]
+    i = i + 1
+print(sum)
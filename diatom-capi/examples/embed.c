@@ -0,0 +1,45 @@
+/*
+ * Embed diatom in a C program
+ *
+ * cargo build --release -p diatom-capi
+ * cc diatom-capi/examples/embed.c -Idiatom-capi/include -Ltarget/release -ldiatom_capi -o embed
+ */
+#include <stdio.h>
+
+#include "diatom.h"
+
+static bool square(const DiatomValue *args, size_t len, DiatomValue *ret, void *user_data) {
+    int *calls = user_data;
+    *calls += 1;
+    if (len != 1 || args[0].kind != DIATOM_KIND_INT) {
+        ret->kind = DIATOM_KIND_STR;
+        ret->string = "square expects an integer";
+        return false;
+    }
+    ret->kind = DIATOM_KIND_INT;
+    ret->integer = args[0].integer * args[0].integer;
+    return true;
+}
+
+int main(void) {
+    DiatomInterpreter *interpreter = diatom_new();
+    int calls = 0;
+    diatom_register_fn(interpreter, "square", square, &calls);
+
+    DiatomValue name = {.kind = DIATOM_KIND_STR, .string = "C"};
+    diatom_set_global(interpreter, "name", &name);
+
+    if (!diatom_exec(interpreter, "print('Hello from', name)\nx = square(12)", "<embed>")) {
+        fprintf(stderr, "%s\n", diatom_last_error(interpreter));
+        return 1;
+    }
+    DiatomValue x;
+    diatom_get_global(interpreter, "x", &x);
+    printf("\nx = %lld, square is called %d time(s)\n", (long long)x.integer, calls);
+
+    if (!diatom_exec(interpreter, "square('12')", "<embed>")) {
+        fprintf(stderr, "%s\n", diatom_last_error(interpreter));
+    }
+    diatom_free(interpreter);
+    return 0;
+}
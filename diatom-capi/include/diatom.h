@@ -0,0 +1,143 @@
+#ifndef DIATOM_H
+#define DIATOM_H
+
+/* Generated by cbindgen from diatom-capi/src/lib.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Kind of a value passed between host and interpreter
+ */
+typedef enum DiatomKind {
+  DIATOM_KIND_UNIT,
+  DIATOM_KIND_BOOL,
+  DIATOM_KIND_INT,
+  DIATOM_KIND_FLOAT,
+  DIATOM_KIND_STR,
+  /**
+   * Any other value, e.g. a table or a function, which can not be read by host
+   */
+  DIATOM_KIND_OBJECT,
+} DiatomKind;
+
+/**
+ * An interpreter printing to standard output
+ */
+typedef struct DiatomInterpreter DiatomInterpreter;
+
+/**
+ * A value passed between host and interpreter, only the field of its kind is meaningful
+ *
+ * Strings are always owned by the side that created them. A string given to the host is valid
+ * until the call it is passed to returns, or until the next call on the interpreter for
+ * `diatom_get_global`. A string given by the host is copied before the call returns, and a null
+ * `string` of kind `Str` is reported as an error.
+ */
+typedef struct DiatomValue {
+  enum DiatomKind kind;
+  bool boolean;
+  int64_t integer;
+  double real;
+  const char *string;
+} DiatomValue;
+
+/**
+ * Function implemented by host
+ *
+ * Called with arguments and a return value initialized as `Unit`. Return `false` to raise an
+ * error, whose message is the return value if it is a string. A string returned is copied once
+ * the function returns, and thus may point to a buffer of `user_data`, while the host keeps its
+ * ownership. Returning a null string raises an error. Strings in `args` are only valid during the
+ * call.
+ */
+typedef bool (*DiatomFunction)(const struct DiatomValue *args,
+                               size_t len,
+                               struct DiatomValue *ret,
+                               void *user_data);
+
+/**
+ * Create an interpreter with the standard library loaded
+ *
+ * Free it with `diatom_free`.
+ */
+struct DiatomInterpreter *diatom_new(void);
+
+/**
+ * Free an interpreter created by `diatom_new`, do nothing if it is null
+ *
+ * # Safety
+ *
+ * `interpreter` must be created by `diatom_new` and not freed yet.
+ */
+void diatom_free(struct DiatomInterpreter *interpreter);
+
+/**
+ * Error of the last call on `interpreter`, null if it succeeded
+ *
+ * # Safety
+ *
+ * `interpreter` must be created by `diatom_new`.
+ */
+const char *diatom_last_error(const struct DiatomInterpreter *interpreter);
+
+/**
+ * Execute `code` read from `source`, a name shown in error messages
+ *
+ * Return `false` if code fails to compile or run, get the rendered error with
+ * `diatom_last_error`.
+ *
+ * # Safety
+ *
+ * `interpreter` must be created by `diatom_new`, `code` and `source` must be valid strings.
+ */
+bool diatom_exec(struct DiatomInterpreter *interpreter, const char *code, const char *source);
+
+/**
+ * Declare a function implemented by host as global `name`
+ *
+ * `user_data` is passed to every call of `function`.
+ *
+ * # Safety
+ *
+ * `interpreter` must be created by `diatom_new` and `name` must be a valid string. `user_data`
+ * must stay valid as long as the function may be called.
+ */
+bool diatom_register_fn(struct DiatomInterpreter *interpreter,
+                        const char *name,
+                        DiatomFunction function,
+                        void *user_data);
+
+/**
+ * Read global variable `name` into `value`
+ *
+ * Return `false` if it is not defined. A string read is valid until the next call on
+ * `interpreter`.
+ *
+ * # Safety
+ *
+ * `interpreter` must be created by `diatom_new`, `name` must be a valid string and `value` must
+ * be a valid pointer.
+ */
+bool diatom_get_global(struct DiatomInterpreter *interpreter,
+                       const char *name,
+                       struct DiatomValue *value);
+
+/**
+ * Set global variable `name` to `value`
+ *
+ * Return `false` if value is of kind `Object`, which can only be created by scripts.
+ *
+ * # Safety
+ *
+ * `interpreter` must be created by `diatom_new`, `name` must be a valid string and `value` must
+ * be a valid pointer, whose `string` is null or a valid string if it is of kind `Str`.
+ */
+bool diatom_set_global(struct DiatomInterpreter *interpreter,
+                       const char *name,
+                       const struct DiatomValue *value);
+
+#endif  /* DIATOM_H */
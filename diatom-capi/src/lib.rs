@@ -0,0 +1,551 @@
+//! # C API of Diatom
+//!
+//! Functions exported with C linkage for hosts written in C, C++ or any language with a C
+//! foreign function interface, e.g. `ctypes` of Python. The header is generated by `cbindgen`
+//! into `include/diatom.h` when this crate is built.
+//!
+//! ```c
+//! #include "diatom.h"
+//!
+//! DiatomInterpreter *interpreter = diatom_new();
+//! if (!diatom_exec(interpreter, "x = 1 + 1", "<main>")) {
+//!     fprintf(stderr, "%s\n", diatom_last_error(interpreter));
+//! }
+//! DiatomValue x;
+//! diatom_get_global(interpreter, "x", &x);
+//! diatom_free(interpreter);
+//! ```
+//!
+//! Strings are nul terminated and encoded in UTF-8. Unless stated otherwise, strings returned
+//! by the interpreter are owned by it and valid until the next call on the same interpreter.
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    io, ptr,
+};
+
+use diatom::{ffi, Interpreter};
+
+/// An interpreter printing to standard output
+pub struct DiatomInterpreter {
+    interpreter: Interpreter<io::Stdout>,
+    error: Option<CString>,
+    string: Option<CString>,
+}
+
+impl DiatomInterpreter {
+    fn result<T>(&mut self, result: Result<T, String>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.error = None;
+                Some(value)
+            }
+            Err(err) => {
+                self.error = Some(to_c_string(err));
+                None
+            }
+        }
+    }
+}
+
+/// Kind of a value passed between host and interpreter
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiatomKind {
+    Unit,
+    Bool,
+    Int,
+    Float,
+    Str,
+    /// Any other value, e.g. a table or a function, which can not be read by host
+    Object,
+}
+
+/// A value passed between host and interpreter, only the field of its kind is meaningful
+///
+/// Strings are always owned by the side that created them. A string given to the host is valid
+/// until the call it is passed to returns, or until the next call on the interpreter for
+/// `diatom_get_global`. A string given by the host is copied before the call returns, and a null
+/// `string` of kind `Str` is reported as an error.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DiatomValue {
+    pub kind: DiatomKind,
+    pub boolean: bool,
+    pub integer: i64,
+    pub real: f64,
+    pub string: *const c_char,
+}
+
+impl DiatomValue {
+    fn new(kind: DiatomKind) -> Self {
+        Self {
+            kind,
+            boolean: false,
+            integer: 0,
+            real: 0.0,
+            string: ptr::null(),
+        }
+    }
+}
+
+/// Function implemented by host
+///
+/// Called with arguments and a return value initialized as `Unit`. Return `false` to raise an
+/// error, whose message is the return value if it is a string. A string returned is copied once
+/// the function returns, and thus may point to a buffer of `user_data`, while the host keeps its
+/// ownership. Returning a null string raises an error. Strings in `args` are only valid during the
+/// call.
+pub type DiatomFunction = extern "C" fn(
+    args: *const DiatomValue,
+    len: usize,
+    ret: *mut DiatomValue,
+    user_data: *mut c_void,
+) -> bool;
+
+/// Pointer given to `diatom_register_fn`, host is responsible for synchronizing access to it
+struct UserData(*mut c_void);
+
+impl UserData {
+    /// Called as a method so that closures capture the wrapper instead of the pointer
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+/// Nul characters can not be represented in C strings and are dropped
+fn to_c_string(s: String) -> CString {
+    CString::new(s).unwrap_or_else(|err| {
+        let mut s = err.into_vec();
+        s.retain(|c| *c != 0);
+        CString::new(s).unwrap()
+    })
+}
+
+/// Null pointers are reported as an error rather than dereferenced
+///
+/// # Safety
+///
+/// `s` must be null or a valid nul terminated string.
+unsafe fn to_str<'a>(s: *const c_char) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err("String is a null pointer".to_string());
+    }
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map_err(|_| "String is not valid UTF-8".to_string())
+}
+
+/// Create an interpreter with the standard library loaded
+///
+/// Free it with `diatom_free`.
+#[no_mangle]
+pub extern "C" fn diatom_new() -> *mut DiatomInterpreter {
+    Box::into_raw(Box::new(DiatomInterpreter {
+        interpreter: Interpreter::new(io::stdout()),
+        error: None,
+        string: None,
+    }))
+}
+
+/// Free an interpreter created by `diatom_new`, do nothing if it is null
+///
+/// # Safety
+///
+/// `interpreter` must be created by `diatom_new` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn diatom_free(interpreter: *mut DiatomInterpreter) {
+    if !interpreter.is_null() {
+        drop(unsafe { Box::from_raw(interpreter) });
+    }
+}
+
+/// Error of the last call on `interpreter`, null if it succeeded
+///
+/// # Safety
+///
+/// `interpreter` must be created by `diatom_new`.
+#[no_mangle]
+pub unsafe extern "C" fn diatom_last_error(interpreter: *const DiatomInterpreter) -> *const c_char {
+    let interpreter = unsafe { &*interpreter };
+    interpreter
+        .error
+        .as_ref()
+        .map_or(ptr::null(), |err| err.as_ptr())
+}
+
+/// Execute `code` read from `source`, a name shown in error messages
+///
+/// Return `false` if code fails to compile or run, get the rendered error with
+/// `diatom_last_error`.
+///
+/// # Safety
+///
+/// `interpreter` must be created by `diatom_new`, `code` and `source` must be valid strings.
+#[no_mangle]
+pub unsafe extern "C" fn diatom_exec(
+    interpreter: *mut DiatomInterpreter,
+    code: *const c_char,
+    source: *const c_char,
+) -> bool {
+    let interpreter = unsafe { &mut *interpreter };
+    let result = unsafe { to_str(code) }.and_then(|code| {
+        let source = unsafe { to_str(source) }?;
+        interpreter.interpreter.exec(code, source, true)
+    });
+    interpreter.result(result).is_some()
+}
+
+/// Declare a function implemented by host as global `name`
+///
+/// `user_data` is passed to every call of `function`.
+///
+/// # Safety
+///
+/// `interpreter` must be created by `diatom_new` and `name` must be a valid string. `user_data`
+/// must stay valid as long as the function may be called.
+#[no_mangle]
+pub unsafe extern "C" fn diatom_register_fn(
+    interpreter: *mut DiatomInterpreter,
+    name: *const c_char,
+    function: DiatomFunction,
+    user_data: *mut c_void,
+) -> bool {
+    let interpreter = unsafe { &mut *interpreter };
+    let name = match unsafe { to_str(name) } {
+        Ok(name) => name.to_string(),
+        Err(err) => return interpreter.result::<()>(Err(err)).is_some(),
+    };
+    let user_data = UserData(user_data);
+    let fn_name = name.clone();
+    interpreter
+        .interpreter
+        .impl_extern_function(name, move |state, parameters, _| {
+            // Keep strings alive until function returns
+            let mut strings = vec![];
+            let args: Vec<_> = parameters
+                .iter()
+                .map(|value| match value {
+                    ffi::DiatomValue::Str(sid) => {
+                        let s = state.get_string_by_id(*sid).unwrap().to_string();
+                        let s = to_c_string(s);
+                        let value = DiatomValue {
+                            string: s.as_ptr(),
+                            ..DiatomValue::new(DiatomKind::Str)
+                        };
+                        strings.push(s);
+                        value
+                    }
+                    value => from_value(value),
+                })
+                .collect();
+            let mut ret = DiatomValue::new(DiatomKind::Unit);
+            let ok = function(args.as_ptr(), args.len(), &mut ret, user_data.get());
+            if !ok {
+                return Err(match ret.kind {
+                    DiatomKind::Str if !ret.string.is_null() => {
+                        unsafe { CStr::from_ptr(ret.string) }
+                            .to_string_lossy()
+                            .into_owned()
+                    }
+                    _ => format!("External function `{fn_name}` failed"),
+                });
+            }
+            Ok(match ret.kind {
+                DiatomKind::Str => {
+                    let s = unsafe { to_str(ret.string) }?.to_string();
+                    ffi::DiatomValue::Str(state.create_str(s))
+                }
+                _ => into_value(&ret)?,
+            })
+        });
+    interpreter.result(Ok(())).is_some()
+}
+
+/// Read global variable `name` into `value`
+///
+/// Return `false` if it is not defined. A string read is valid until the next call on
+/// `interpreter`.
+///
+/// # Safety
+///
+/// `interpreter` must be created by `diatom_new`, `name` must be a valid string and `value` must
+/// be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn diatom_get_global(
+    interpreter: *mut DiatomInterpreter,
+    name: *const c_char,
+    value: *mut DiatomValue,
+) -> bool {
+    let interpreter = unsafe { &mut *interpreter };
+    let result =
+        unsafe { to_str(name) }.and_then(|name| match interpreter.interpreter.get_global(name) {
+            Some(ffi::DiatomValue::Str(_)) => {
+                let s = interpreter.interpreter.get_global_as::<String>(name)?;
+                let s = interpreter.string.insert(to_c_string(s));
+                Ok(DiatomValue {
+                    string: s.as_ptr(),
+                    ..DiatomValue::new(DiatomKind::Str)
+                })
+            }
+            Some(global) => Ok(from_value(&global)),
+            None => Err(format!("Variable `{name}` is not defined")),
+        });
+    match interpreter.result(result) {
+        Some(result) => {
+            unsafe { *value = result };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Set global variable `name` to `value`
+///
+/// Return `false` if value is of kind `Object`, which can only be created by scripts.
+///
+/// # Safety
+///
+/// `interpreter` must be created by `diatom_new`, `name` must be a valid string and `value` must
+/// be a valid pointer, whose `string` is null or a valid string if it is of kind `Str`.
+#[no_mangle]
+pub unsafe extern "C" fn diatom_set_global(
+    interpreter: *mut DiatomInterpreter,
+    name: *const c_char,
+    value: *const DiatomValue,
+) -> bool {
+    let interpreter = unsafe { &mut *interpreter };
+    let value = unsafe { &*value };
+    let result = unsafe { to_str(name) }.and_then(|name| {
+        match value.kind {
+            DiatomKind::Str => {
+                let s = unsafe { to_str(value.string) }?.to_string();
+                interpreter.interpreter.set_global(name, s);
+            }
+            _ => {
+                let value = into_value(value)?;
+                interpreter.interpreter.set_global(name, value);
+            }
+        }
+        Ok(())
+    });
+    interpreter.result(result).is_some()
+}
+
+/// Convert a value that is not a string
+fn from_value(value: &ffi::DiatomValue) -> DiatomValue {
+    match value {
+        ffi::DiatomValue::Unit => DiatomValue::new(DiatomKind::Unit),
+        ffi::DiatomValue::Bool(b) => DiatomValue {
+            boolean: *b,
+            ..DiatomValue::new(DiatomKind::Bool)
+        },
+        ffi::DiatomValue::Int(i) => DiatomValue {
+            integer: *i,
+            ..DiatomValue::new(DiatomKind::Int)
+        },
+        ffi::DiatomValue::Float(f) => DiatomValue {
+            real: *f,
+            ..DiatomValue::new(DiatomKind::Float)
+        },
+        ffi::DiatomValue::Str(_) | ffi::DiatomValue::Ref(_) => DiatomValue::new(DiatomKind::Object),
+    }
+}
+
+/// Convert a value that is not a string
+fn into_value(value: &DiatomValue) -> Result<ffi::DiatomValue, String> {
+    match value.kind {
+        DiatomKind::Unit => Ok(ffi::DiatomValue::Unit),
+        DiatomKind::Bool => Ok(ffi::DiatomValue::Bool(value.boolean)),
+        DiatomKind::Int => Ok(ffi::DiatomValue::Int(value.integer)),
+        DiatomKind::Float => Ok(ffi::DiatomValue::Float(value.real)),
+        DiatomKind::Str => unreachable!("Strings are converted by caller"),
+        DiatomKind::Object => Err("Host can not create a value of kind `Object`".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    extern "C" fn add(
+        args: *const DiatomValue,
+        len: usize,
+        ret: *mut DiatomValue,
+        user_data: *mut c_void,
+    ) -> bool {
+        let args = unsafe { std::slice::from_raw_parts(args, len) };
+        let ret = unsafe { &mut *ret };
+        let calls = unsafe { &mut *(user_data as *mut i64) };
+        *calls += 1;
+        if len != 2 || args.iter().any(|arg| arg.kind != DiatomKind::Int) {
+            ret.kind = DiatomKind::Str;
+            ret.string = c"add expects 2 integers".as_ptr();
+            return false;
+        }
+        ret.kind = DiatomKind::Int;
+        ret.integer = args[0].integer + args[1].integer;
+        true
+    }
+
+    extern "C" fn greet(
+        args: *const DiatomValue,
+        len: usize,
+        ret: *mut DiatomValue,
+        _: *mut c_void,
+    ) -> bool {
+        let args = unsafe { std::slice::from_raw_parts(args, len) };
+        let name = unsafe { CStr::from_ptr(args[0].string) }.to_str().unwrap();
+        let ret = unsafe { &mut *ret };
+        ret.kind = DiatomKind::Bool;
+        ret.boolean = name == "diatom";
+        true
+    }
+
+    extern "C" fn null_str(
+        _: *const DiatomValue,
+        _: usize,
+        ret: *mut DiatomValue,
+        _: *mut c_void,
+    ) -> bool {
+        unsafe { (*ret).kind = DiatomKind::Str };
+        true
+    }
+
+    #[test]
+    fn test_exec() {
+        unsafe {
+            let interpreter = diatom_new();
+            assert!(diatom_exec(
+                interpreter,
+                c("x = 1 + 1").as_ptr(),
+                c("<test>").as_ptr()
+            ));
+            assert!(diatom_last_error(interpreter).is_null());
+            assert!(!diatom_exec(
+                interpreter,
+                c("y = z").as_ptr(),
+                c("<test>").as_ptr()
+            ));
+            let err = CStr::from_ptr(diatom_last_error(interpreter));
+            assert!(err.to_str().unwrap().contains("<test>"), "{err:?}");
+
+            let mut value = DiatomValue::new(DiatomKind::Unit);
+            assert!(diatom_get_global(interpreter, c("x").as_ptr(), &mut value));
+            assert_eq!(value.kind, DiatomKind::Int);
+            assert_eq!(value.integer, 2);
+            assert!(!diatom_get_global(interpreter, c("y").as_ptr(), &mut value));
+            diatom_free(interpreter);
+        }
+    }
+
+    #[test]
+    fn test_globals() {
+        unsafe {
+            let interpreter = diatom_new();
+            let name = c("name");
+            let value = DiatomValue {
+                string: name.as_ptr(),
+                ..DiatomValue::new(DiatomKind::Str)
+            };
+            assert!(diatom_set_global(interpreter, c("s").as_ptr(), &value));
+            let value = DiatomValue {
+                real: 1.5,
+                ..DiatomValue::new(DiatomKind::Float)
+            };
+            assert!(diatom_set_global(interpreter, c("f").as_ptr(), &value));
+            assert!(!diatom_set_global(
+                interpreter,
+                c("o").as_ptr(),
+                &DiatomValue::new(DiatomKind::Object)
+            ));
+            let code = c("s = s + '!'\nf = f * 2\nt = {}");
+            assert!(diatom_exec(
+                interpreter,
+                code.as_ptr(),
+                c("<test>").as_ptr()
+            ));
+
+            let mut value = DiatomValue::new(DiatomKind::Unit);
+            assert!(diatom_get_global(interpreter, c("s").as_ptr(), &mut value));
+            assert_eq!(value.kind, DiatomKind::Str);
+            assert_eq!(CStr::from_ptr(value.string).to_str().unwrap(), "name!");
+            assert!(diatom_get_global(interpreter, c("f").as_ptr(), &mut value));
+            assert_eq!(value.real, 3.0);
+            assert!(diatom_get_global(interpreter, c("t").as_ptr(), &mut value));
+            assert_eq!(value.kind, DiatomKind::Object);
+            diatom_free(interpreter);
+        }
+    }
+
+    #[test]
+    fn test_register_fn() {
+        unsafe {
+            let interpreter = diatom_new();
+            let mut calls = 0i64;
+            let calls_ptr = &mut calls as *mut i64 as *mut c_void;
+            assert!(diatom_register_fn(
+                interpreter,
+                c("add").as_ptr(),
+                add,
+                calls_ptr
+            ));
+            assert!(diatom_register_fn(
+                interpreter,
+                c("greet").as_ptr(),
+                greet,
+                ptr::null_mut()
+            ));
+            let code = c("x = add(1, 2)\ny = greet('diatom')");
+            assert!(diatom_exec(
+                interpreter,
+                code.as_ptr(),
+                c("<test>").as_ptr()
+            ));
+
+            let mut value = DiatomValue::new(DiatomKind::Unit);
+            assert!(diatom_get_global(interpreter, c("x").as_ptr(), &mut value));
+            assert_eq!(value.integer, 3);
+            assert!(diatom_get_global(interpreter, c("y").as_ptr(), &mut value));
+            assert!(value.boolean);
+
+            assert!(!diatom_exec(
+                interpreter,
+                c("add(1)").as_ptr(),
+                c("<test>").as_ptr()
+            ));
+            let err = CStr::from_ptr(diatom_last_error(interpreter));
+            assert!(
+                err.to_str().unwrap().contains("add expects 2 integers"),
+                "{err:?}"
+            );
+            assert_eq!(calls, 2);
+
+            assert!(diatom_register_fn(
+                interpreter,
+                c("null_str").as_ptr(),
+                null_str,
+                ptr::null_mut()
+            ));
+            assert!(!diatom_exec(
+                interpreter,
+                c("null_str()").as_ptr(),
+                c("<test>").as_ptr()
+            ));
+            let err = CStr::from_ptr(diatom_last_error(interpreter));
+            assert!(
+                err.to_str().unwrap().contains("String is a null pointer"),
+                "{err:?}"
+            );
+            let value = DiatomValue::new(DiatomKind::Str);
+            assert!(!diatom_set_global(interpreter, c("s").as_ptr(), &value));
+            diatom_free(interpreter);
+        }
+    }
+}
@@ -3,8 +3,8 @@ use nu_ansi_term::{Color, Style};
 use reedline::{Highlighter, StyledText};
 use regex::{self, Regex};
 
-const KEYWORDS: [&str; 23] = [
-    "until", "end", "if", "then", "else", "elsif", "in", "for", "do", "return", "break",
+const KEYWORDS: [&str; 24] = [
+    "until", "end", "if", "then", "else", "elsif", "in", "for", "do", "return", "defer", "break",
     "continue", "loop", "def", "begin", "and", "or", "not", "fn", "is", "import", "from", "as",
 ];
 
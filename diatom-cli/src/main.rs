@@ -1,8 +1,14 @@
-use crossterm::tty::IsTty;
-use diatom::Interpreter;
-use std::{fs, io, path::PathBuf};
+use diatom::{
+    ast::Ast, benchmark, bundle::Bundle, lint::LintConfig, manifest::Manifest, testing,
+    Interpreter, RenderOptions, ScriptArgs, WarningOptions, ENV_CAPABILITY,
+};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
-use clap::{ColorChoice, Parser};
+use clap::{ColorChoice, Parser, Subcommand, ValueEnum};
 
 mod cli;
 pub use cli::Cli;
@@ -17,48 +23,570 @@ pub use cli::Cli;
 
 {all-args}{after-help}
 ")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    #[arg(long, default_value_t = ColorChoice::Auto)]
+    #[arg(long, default_value_t = ColorChoice::Auto, global = true)]
     color: ColorChoice,
     #[arg(short, long)]
     /// Show decompiled bytecode instead of execution
     inspect: bool,
+    #[arg(long, global = true)]
+    /// Allow scripts to spawn subprocesses
+    allow_process: bool,
+    #[arg(long, global = true)]
+    /// Feed `input()` from a file instead of stdin
+    input: Option<PathBuf>,
+    #[arg(short = 'W', global = true, value_name = "WARNING")]
+    /// Report warnings as `allow`, `warn` or `deny`, or suppress one by `no-<name or code>`
+    warnings: Vec<String>,
+    #[arg(long, global = true)]
+    /// Only declare variables at top level, and disallow reading undefined ones
+    strict: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
     /// File to be executed, using REPL mode if leaving empty
     path: Option<PathBuf>,
+    /// Arguments passed to the script, read by `os.args()` after `import std.os`
+    #[arg(last = true)]
+    script_args: Vec<String>,
 }
 
-fn main() {
-    let args = Args::parse();
+#[derive(Subcommand)]
+enum Command {
+    /// Execute a file
+    Run {
+        path: PathBuf,
+        /// Arguments passed to the script, read by `os.args()` after `import std.os`
+        #[arg(last = true)]
+        script_args: Vec<String>,
+    },
+    /// Start an interactive console
+    Repl {
+        #[arg(short, long)]
+        /// Show decompiled bytecode instead of execution
+        inspect: bool,
+    },
+    /// Report syntax and compile errors and lint warnings without execution
+    Check {
+        paths: Vec<PathBuf>,
+        #[arg(long, value_name = "KIND")]
+        /// Print an intermediate representation of each file before checking it
+        emit: Option<Emit>,
+    },
+    /// Show decompiled bytecode of a file
+    Disasm { path: PathBuf },
+    /// Check that a script compiles and pack its source and files it imports into a bundle, which
+    /// is compiled and run as a script
+    Bundle {
+        path: PathBuf,
+        #[arg(short, long)]
+        /// Path of the bundle, the script with extension `dmb` by default
+        output: Option<PathBuf>,
+    },
+    /// Format files in place
+    Fmt {
+        paths: Vec<PathBuf>,
+        #[arg(long)]
+        /// Report files not formatted instead of writing them
+        check: bool,
+    },
+    /// Generate API docs of files from their `---` doc comments
+    Doc {
+        paths: Vec<PathBuf>,
+        #[arg(long)]
+        /// Generate an HTML page instead of markdown
+        html: bool,
+        #[arg(short, long)]
+        /// File to write docs to, stdout by default
+        output: Option<PathBuf>,
+    },
+    /// Run test cases in `*_test.dm` files
+    Test {
+        /// Test files or directories to search, the current directory by default
+        paths: Vec<PathBuf>,
+    },
+    /// Run benchmarks in `*_bench.dm` files
+    Bench {
+        /// Benchmark files or directories to search, the current directory by default
+        paths: Vec<PathBuf>,
+        #[arg(long)]
+        /// Save results as JSON, which can be used as baseline later
+        save: Option<PathBuf>,
+        #[arg(long)]
+        /// Compare results with a JSON file saved by `--save`
+        baseline: Option<PathBuf>,
+        #[arg(long, requires = "baseline")]
+        /// Fail if any benchmark is slower than baseline by more than this percentage
+        threshold: Option<f64>,
+    },
+}
 
-    let color = match args.color {
-        ColorChoice::Auto => io::stdout().is_tty(),
-        ColorChoice::Always => true,
-        ColorChoice::Never => false,
+#[derive(Clone, Copy, ValueEnum)]
+enum Emit {
+    /// Syntax tree used by the compiler
+    Ast,
+}
+
+/// Manifest of the project containing `dir`, if any
+fn find_manifest(dir: &Path) -> Result<Option<Manifest>, ExitCode> {
+    let Some(path) = Manifest::find(dir) else {
+        return Ok(None);
     };
-    let mut interpreter = if color {
-        Interpreter::with_color(io::stdout())
+    match Manifest::load(&path) {
+        Ok(manifest) => Ok(Some(manifest)),
+        Err(err) => {
+            eprintln!("Error: {err}");
+            Err(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, ExitCode> {
+    fs::read_to_string(path).map_err(|err| {
+        eprintln!("Error: Can not read `{}`: {err}", path.display());
+        ExitCode::FAILURE
+    })
+}
+
+/// Print warnings of the last successful call, errors already include them
+fn emit_warnings(interpreter: &Interpreter<io::Stdout>) {
+    if interpreter.warning_count() > 0 {
+        let _ = interpreter.emit_diagnostics(io::stderr());
+    }
+}
+
+fn run(mut interpreter: Interpreter<io::Stdout>, path: &Path, script_args: &[String]) -> ExitCode {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("Error: Can not read `{}`: {err}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    interpreter.set_context(ScriptArgs(script_args.to_vec()));
+    interpreter.enable_capability(ENV_CAPABILITY);
+    let result = if Bundle::is_bundle(&bytes) {
+        Bundle::from_bytes(&bytes)
+            .map_err(|err| format!("Error: Can not read `{}`: {err}\n", path.display()))
+            .and_then(|bundle| interpreter.exec_bundle(&bundle))
     } else {
-        Interpreter::new(io::stdout())
+        match String::from_utf8(bytes) {
+            Ok(code) => interpreter.exec(code, path.as_os_str(), false),
+            Err(err) => Err(format!("Error: Can not read `{}`: {err}\n", path.display())),
+        }
     };
+    match result {
+        Ok(_) => {
+            emit_warnings(&interpreter);
+            ExitCode::SUCCESS
+        }
+        Err(s) => {
+            eprint!("{s}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    match (&args.path, args.inspect) {
-        (None, inspect) => {
-            let mut console = Cli::new(interpreter);
-            console.run(inspect);
+fn check(
+    mut interpreter: Interpreter<io::Stdout>,
+    paths: &[PathBuf],
+    emit: Option<Emit>,
+) -> ExitCode {
+    interpreter.set_lint(Some(LintConfig::default()));
+    let mut result = ExitCode::SUCCESS;
+    for path in paths {
+        let code = match read_file(path) {
+            Ok(code) => code,
+            Err(code) => {
+                result = code;
+                continue;
+            }
+        };
+        if let Some(Emit::Ast) = emit {
+            print!("{}", Ast::parse(&code));
+        }
+        match interpreter.check(code, path.as_os_str(), false) {
+            Ok(()) => emit_warnings(&interpreter),
+            Err(s) => {
+                eprint!("{s}");
+                result = ExitCode::FAILURE;
+            }
+        }
+    }
+    result
+}
+
+fn disasm(mut interpreter: Interpreter<io::Stdout>, path: &Path) -> ExitCode {
+    let code = match read_file(path) {
+        Ok(code) => code,
+        Err(code) => return code,
+    };
+    match interpreter.decompile(code, path.as_os_str(), false) {
+        Ok(s) => {
+            print!("{s}");
+            ExitCode::SUCCESS
+        }
+        Err(s) => {
+            eprint!("{s}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn bundle(
+    mut interpreter: Interpreter<io::Stdout>,
+    path: &Path,
+    output: Option<&Path>,
+) -> ExitCode {
+    let code = match read_file(path) {
+        Ok(code) => code,
+        Err(code) => return code,
+    };
+    let bundle = match interpreter.bundle(&code, path) {
+        Ok(bundle) => bundle,
+        Err(s) => {
+            eprint!("{s}");
+            return ExitCode::FAILURE;
+        }
+    };
+    emit_warnings(&interpreter);
+    let output = output.map_or_else(|| path.with_extension("dmb"), Path::to_path_buf);
+    match fs::write(&output, bundle.to_bytes()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: Can not write `{}`: {err}", output.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn fmt(paths: &[PathBuf], check: bool) -> ExitCode {
+    let mut result = ExitCode::SUCCESS;
+    for path in paths {
+        let code = match read_file(path) {
+            Ok(code) => code,
+            Err(code) => {
+                result = code;
+                continue;
+            }
+        };
+        let formatted = match diatom::format::format(&code) {
+            Ok(formatted) => formatted,
+            Err(s) => {
+                eprintln!("Error: Can not format `{}`:", path.display());
+                eprint!("{s}");
+                result = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        if formatted == code {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", path.display());
+            result = ExitCode::FAILURE;
+        } else if let Err(err) = fs::write(path, formatted) {
+            eprintln!("Error: Can not write `{}`: {err}", path.display());
+            result = ExitCode::FAILURE;
+        }
+    }
+    result
+}
+
+fn doc(paths: &[PathBuf], html: bool, output: Option<&Path>) -> ExitCode {
+    let mut sections = vec![];
+    for path in paths {
+        let code = match read_file(path) {
+            Ok(code) => code,
+            Err(code) => return code,
+        };
+        let docs = diatom::doc::extract(&code);
+        let title = path.file_stem().unwrap_or_default().to_string_lossy();
+        sections.push(if html {
+            docs.to_html(&title)
+        } else {
+            docs.to_markdown(&title)
+        });
+    }
+    let docs = if html {
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"></head>\n<body>\n{}</body>\n</html>\n",
+            sections.concat()
+        )
+    } else {
+        sections.join("\n")
+    };
+    match output {
+        Some(output) => {
+            if let Err(err) = fs::write(output, docs) {
+                eprintln!("Error: Can not write `{}`: {err}", output.display());
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{docs}"),
+    }
+    ExitCode::SUCCESS
+}
+
+fn test(new_interpreter: impl Fn() -> Interpreter<io::Stdout>, paths: &[PathBuf]) -> ExitCode {
+    let default = [PathBuf::from(".")];
+    let paths: &[PathBuf] = if paths.is_empty() { &default } else { paths };
+    let mut files = vec![];
+    for path in paths {
+        match testing::discover(path) {
+            Ok(found) => files.extend(found),
+            Err(err) => {
+                eprintln!("Error: Can not search `{}`: {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let (mut passed, mut failures) = (0, vec![]);
+    for path in files {
+        let code = match fs::read_to_string(&path) {
+            Ok(code) => code,
+            Err(err) => {
+                failures.push((
+                    path.display().to_string(),
+                    format!("Can not read file: {err}\n"),
+                ));
+                continue;
+            }
+        };
+        println!("\nrunning {}", path.display());
+        // Each file gets a fresh interpreter so that files can not affect each other
+        let mut interpreter = new_interpreter();
+        match testing::run_tests(&mut interpreter, code, path.as_os_str(), false) {
+            Ok(results) => {
+                for result in results {
+                    let name = format!("{}::{}", path.display(), result.name);
+                    match result.error {
+                        None => {
+                            println!("test {name} ... ok");
+                            passed += 1;
+                        }
+                        Some(error) => {
+                            println!("test {name} ... FAILED");
+                            failures.push((name, error));
+                        }
+                    }
+                }
+            }
+            Err(error) => {
+                let name = path.display().to_string();
+                println!("test {name} ... FAILED");
+                failures.push((name, error));
+            }
         }
-        (Some(path), false) => {
-            let code = fs::read_to_string(path).expect("Error: File can not be read!");
-            match interpreter.exec(code, path.as_os_str(), false) {
-                Ok(_) => (),
-                Err(s) => print!("{s}"),
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, error) in &failures {
+            println!("\n---- {name} ----\n{error}");
+        }
+    }
+    let status = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!(
+        "\ntest result: {status}. {passed} passed; {} failed",
+        failures.len()
+    );
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn bench(
+    new_interpreter: impl Fn() -> Interpreter<io::Stdout>,
+    paths: &[PathBuf],
+    save: Option<&Path>,
+    baseline: Option<&Path>,
+    threshold: Option<f64>,
+) -> ExitCode {
+    let baseline = match baseline {
+        Some(path) => {
+            let json = match read_file(path) {
+                Ok(json) => json,
+                Err(code) => return code,
             };
+            match benchmark::from_json(&json) {
+                Ok(baseline) => Some(baseline),
+                Err(err) => {
+                    eprintln!("Error: Invalid baseline `{}`: {err}", path.display());
+                    return ExitCode::FAILURE;
+                }
+            }
         }
-        (Some(path), true) => {
-            let code = fs::read_to_string(path).expect("Error: File can not be read!");
-            let result = match interpreter.decompile(code, path.as_os_str(), false) {
-                Ok(s) | Err(s) => s,
+        None => None,
+    };
+
+    let default = [PathBuf::from(".")];
+    let paths: &[PathBuf] = if paths.is_empty() { &default } else { paths };
+    let mut results = vec![];
+    for path in paths {
+        let files = match benchmark::discover(path) {
+            Ok(files) => files,
+            Err(err) => {
+                eprintln!("Error: Can not search `{}`: {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+        };
+        for path in files {
+            let code = match read_file(&path) {
+                Ok(code) => code,
+                Err(code) => return code,
+            };
+            // Each file gets a fresh interpreter so that files can not affect each other
+            let mut interpreter = new_interpreter();
+            match benchmark::run_benches(&mut interpreter, code, path.as_os_str(), false) {
+                Ok(stats) => {
+                    stats.iter().for_each(|stats| println!("{stats}"));
+                    results.extend(stats);
+                }
+                Err(s) => {
+                    eprint!("{s}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    }
+
+    let mut result = ExitCode::SUCCESS;
+    if let Some(baseline) = baseline {
+        println!();
+        for comparison in benchmark::compare(&results, &baseline) {
+            let Some(change) = comparison.change() else {
+                println!("{}: no baseline", comparison.name);
+                continue;
             };
-            print!("{result}");
+            let percent = change * 100.0;
+            let regressed = threshold.is_some_and(|threshold| percent > threshold);
+            println!(
+                "{}: {} -> {} ({percent:+.1}%){}",
+                comparison.name,
+                benchmark::format_duration(comparison.baseline.unwrap()),
+                benchmark::format_duration(comparison.mean),
+                if regressed { " REGRESSED" } else { "" }
+            );
+            if regressed {
+                result = ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(path) = save {
+        if let Err(err) = fs::write(path, benchmark::to_json(&results)) {
+            eprintln!("Error: Can not write `{}`: {err}", path.display());
+            result = ExitCode::FAILURE;
+        }
+    }
+    result
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let color = match args.color {
+        ColorChoice::Auto => diatom::ColorChoice::Auto,
+        ColorChoice::Always => diatom::ColorChoice::Always,
+        ColorChoice::Never => diatom::ColorChoice::Never,
+    };
+    let mut warnings = WarningOptions::default();
+    for flag in &args.warnings {
+        warnings = match warnings.with_flag(flag) {
+            Ok(warnings) => warnings,
+            Err(err) => {
+                eprintln!("Error: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+    }
+    // Projects are found from the script run, or the current directory
+    let script = match (&args.command, &args.path) {
+        (
+            Some(
+                Command::Run { path, .. } | Command::Disasm { path } | Command::Bundle { path, .. },
+            ),
+            _,
+        )
+        | (None, Some(path)) => path.parent(),
+        _ => None,
+    };
+    let dir = match script {
+        Some(dir) => dir.to_path_buf(),
+        None => env::current_dir().unwrap_or_default(),
+    };
+    let manifest = match find_manifest(&dir) {
+        Ok(manifest) => manifest,
+        Err(code) => return code,
+    };
+    let width = crossterm::terminal::size()
+        .ok()
+        .map(|(columns, _)| columns as usize);
+    let new_interpreter = || {
+        let mut interpreter = Interpreter::new(io::stdout());
+        interpreter.set_render_options(RenderOptions::default().color(color).width(width));
+        interpreter.set_warning_options(warnings.clone());
+        interpreter.set_strict(args.strict);
+        if let Some(manifest) = &manifest {
+            interpreter
+                .with_manifest(manifest)
+                .unwrap_or_else(|err| panic!("Error: Module directories can not be read: {err}"));
+        }
+        interpreter.with_env_search_path();
+        if args.allow_process {
+            interpreter.enable_capability("process");
+        }
+        if let Some(path) = &args.input {
+            let file = fs::File::open(path).expect("Error: Input file can not be read!");
+            interpreter.replace_input(Box::new(io::BufReader::new(file)));
+        }
+        interpreter
+    };
+    let interpreter = new_interpreter();
+
+    match (args.command, &args.path, args.inspect) {
+        (Some(Command::Run { path, script_args }), _, _) => run(interpreter, &path, &script_args),
+        (Some(Command::Repl { inspect }), _, _) | (None, None, inspect) => {
+            let mut console = Cli::new(interpreter);
+            console.run(inspect);
+            ExitCode::SUCCESS
+        }
+        (Some(Command::Check { paths, emit }), _, _) => check(interpreter, &paths, emit),
+        (Some(Command::Disasm { path }), _, _) => disasm(interpreter, &path),
+        (Some(Command::Bundle { path, output }), _, _) => {
+            bundle(interpreter, &path, output.as_deref())
         }
+        (Some(Command::Fmt { paths, check }), _, _) => fmt(&paths, check),
+        (
+            Some(Command::Doc {
+                paths,
+                html,
+                output,
+            }),
+            _,
+            _,
+        ) => doc(&paths, html, output.as_deref()),
+        (Some(Command::Test { paths }), _, _) => test(new_interpreter, &paths),
+        (
+            Some(Command::Bench {
+                paths,
+                save,
+                baseline,
+                threshold,
+            }),
+            _,
+            _,
+        ) => bench(
+            new_interpreter,
+            &paths,
+            save.as_deref(),
+            baseline.as_deref(),
+            threshold,
+        ),
+        (None, Some(path), false) => run(interpreter, path, &args.script_args),
+        (None, Some(path), true) => disasm(interpreter, path),
     }
 }
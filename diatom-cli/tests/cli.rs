@@ -0,0 +1,72 @@
+use std::{env, fs, process::Command};
+
+/// Run `diatom check <args>` on `code`, return whether it succeeded and its stderr
+fn check(name: &str, code: &str, args: &[&str]) -> (bool, String) {
+    let path = env::temp_dir().join(format!("diatom_cli_{name}_{}.dm", std::process::id()));
+    fs::write(&path, code).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_diatom"))
+        .args(["check", "--color", "never"])
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn test_check_lint() {
+    let (ok, stderr) = check("lint", "def f x =\n    y = x\n    x\nend\n", &[]);
+    assert!(ok, "{stderr}");
+    assert!(stderr.contains("warning[W0000]"), "{stderr}");
+    assert!(stderr.contains("Variable `y` is never used"), "{stderr}");
+
+    let (ok, stderr) = check("clean", "def f x = x end\n", &[]);
+    assert!(ok, "{stderr}");
+    assert!(stderr.is_empty(), "{stderr}");
+}
+
+#[test]
+fn test_warning_flags() {
+    let code = "def f x =\n    y = x\n    x\nend\n";
+    let (ok, stderr) = check("suppress", code, &["-W", "no-unused"]);
+    assert!(ok, "{stderr}");
+    assert!(stderr.is_empty(), "{stderr}");
+    let (ok, stderr) = check("suppress_code", code, &["-W", "no-W0000"]);
+    assert!(ok, "{stderr}");
+    assert!(stderr.is_empty(), "{stderr}");
+
+    let (ok, stderr) = check("deny", code, &["-W", "deny"]);
+    assert!(!ok, "{stderr}");
+    assert!(stderr.contains("error[W0000]"), "{stderr}");
+    assert!(
+        stderr.contains("Warning `unused-variable` is reported as error"),
+        "{stderr}"
+    );
+    let (ok, stderr) = check("deny_suppressed", code, &["-W", "deny", "-W", "no-unused"]);
+    assert!(ok, "{stderr}");
+
+    let (ok, stderr) = check("allow", code, &["-W", "allow"]);
+    assert!(ok, "{stderr}");
+    assert!(stderr.is_empty(), "{stderr}");
+}
+
+#[test]
+fn test_check_constant_conditions() {
+    let code = "x = 1\nif 1 > 2 then x end\nx == x\nuntil 1 < 2 do x end\n";
+    let (ok, stderr) = check("constant", code, &[]);
+    assert!(ok, "{stderr}");
+    for expected in [
+        "warning[W0007]",
+        "It is folded to `false` at compile time",
+        "warning[W0008]",
+        "It is always `true`",
+        "warning[W0009]",
+        "Condition of `until` is folded to `true` at compile time",
+    ] {
+        assert!(stderr.contains(expected), "{expected}\n{stderr}");
+    }
+}
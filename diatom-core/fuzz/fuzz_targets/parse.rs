@@ -0,0 +1,43 @@
+//! Lexer, parser and compiler must not panic or hang on any input
+//!
+//! Run with `cargo fuzz run parse` in `diatom-core`.
+#![no_main]
+
+use diatom_core::{
+    ast::{walk_stmt, Ast, Stmt, Visitor},
+    syntax::SyntaxTree,
+    Interpreter,
+};
+use diatom_std_core::StdLibCore;
+use libfuzzer_sys::fuzz_target;
+
+/// Whether any statement failed to parse
+struct HasErrorStmt(bool);
+
+impl Visitor for HasErrorStmt {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.0 |= matches!(stmt, Stmt::Error);
+        walk_stmt(self, stmt);
+    }
+}
+
+/// Statements that failed to parse must be reported, and compiling must not panic
+fn check(source: &str) {
+    let ast = Ast::parse(source);
+    let mut error_stmt = HasErrorStmt(false);
+    ast.visit(&mut error_stmt);
+    assert!(!error_stmt.0 || ast.has_errors());
+    let _ = Interpreter::<_, StdLibCore>::new(Vec::<u8>::new()).check(source, "<fuzz>", true);
+}
+
+fuzz_target!(|source: &str| {
+    let tree = SyntaxTree::parse(source);
+    // Every byte belongs to exactly one token
+    let text: String = tree
+        .root()
+        .tokens()
+        .map(|token| &source[token.range.clone()])
+        .collect();
+    assert_eq!(text, source);
+    check(source);
+});
@@ -0,0 +1,53 @@
+//! Parsing a document after an edit must give the same result as parsing it from scratch
+//!
+//! Run with `cargo fuzz run reparse` in `diatom-core`.
+#![no_main]
+
+use diatom_core::{
+    ast::{walk_stmt, Ast, Stmt, Visitor},
+    syntax::{Document, SyntaxTree, TextEdit},
+    Interpreter,
+};
+use diatom_std_core::StdLibCore;
+use libfuzzer_sys::fuzz_target;
+
+/// Whether any statement failed to parse
+struct HasErrorStmt(bool);
+
+impl Visitor for HasErrorStmt {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.0 |= matches!(stmt, Stmt::Error);
+        walk_stmt(self, stmt);
+    }
+}
+
+/// Statements that failed to parse must be reported, and compiling must not panic
+fn check(source: &str) {
+    let ast = Ast::parse(source);
+    let mut error_stmt = HasErrorStmt(false);
+    ast.visit(&mut error_stmt);
+    assert!(!error_stmt.0 || ast.has_errors());
+    let _ = Interpreter::<_, StdLibCore>::new(Vec::<u8>::new()).check(source, "<fuzz>", true);
+}
+
+fuzz_target!(|input: (&str, u16, u16, &str)| {
+    let (source, start, len, text) = input;
+    let mut document = Document::new(source);
+    let start = start as usize % (source.len() + 1);
+    let end = (start + len as usize).min(source.len());
+    if !source.is_char_boundary(start) || !source.is_char_boundary(end) {
+        return;
+    }
+    document.edit(TextEdit {
+        range: start..end,
+        text: text.to_string(),
+    });
+    let source = document.source();
+    let tree = SyntaxTree::parse(source);
+    assert_eq!(document.has_errors(), tree.has_errors());
+    assert_eq!(
+        document.syntax_tree().root().dump(source),
+        tree.root().dump(source)
+    );
+    check(source);
+});
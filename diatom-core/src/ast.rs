@@ -0,0 +1,277 @@
+//! Syntax tree used by the compiler, for analysis tools
+//!
+//! Only available with feature `unstable-ast`, this API may change in any release. Unlike
+//! [`crate::syntax`], nodes keep names and values of literals, but whitespace and comments are
+//! dropped. Implement [`Visitor`] or [`MutVisitor`] to walk a tree.
+//!
+//! # Example
+//! ```
+//! use diatom_core::ast::{walk_expr, Ast, Expr, Visitor};
+//!
+//! struct Names(Vec<String>);
+//!
+//! impl Visitor for Names {
+//!     fn visit_expr(&mut self, expr: &Expr) {
+//!         if let Expr::Id { name, .. } = expr {
+//!             self.0.push(name.to_string());
+//!         }
+//!         walk_expr(self, expr);
+//!     }
+//! }
+//!
+//! let ast = Ast::parse("x = f(y)");
+//! assert!(!ast.has_errors());
+//! let mut names = Names(vec![]);
+//! ast.visit(&mut names);
+//! assert_eq!(names.0, ["x", "f", "y"]);
+//! assert_eq!(
+//!     ast.to_string(),
+//!     "\
+//! Expr@0..8
+//!   Infix(Assign)@0..8
+//!     Id(x)@0..1
+//!     Call@4..8
+//!       Id(f)@4..5
+//!       Id(y)@6..7
+//! "
+//! );
+//! ```
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{
+    file_manager::FileManager,
+    frontend::{Parser, Resolver},
+};
+pub use crate::{
+    file_manager::Loc,
+    frontend::parser::{
+        ast::{Const, Expr, ImportItem, OpInfix, OpPostfix, OpPrefix, Stmt},
+        visit::{walk_expr, walk_expr_mut, walk_stmt, walk_stmt_mut, MutVisitor, Visitor},
+    },
+    frontend::resolver::{Binding, NameUse},
+    frontend::Symbol,
+};
+
+/// Top level statements of a source file
+#[derive(Clone, Debug)]
+pub struct Ast {
+    body: Vec<Stmt>,
+    has_errors: bool,
+}
+
+impl Ast {
+    /// Parse `source`, imports are not resolved
+    ///
+    /// Parts that can not be parsed are `Stmt::Error` or `Expr::Error`.
+    pub fn parse(source: &str) -> Self {
+        let mut file_manager = FileManager::new();
+        let fid = Parser::new(&mut file_manager, &[])
+            .skip_imports()
+            .keep_macros()
+            .parse_file_phony("<source>", source);
+        Self {
+            body: file_manager.get_ast(fid).as_ref().clone(),
+            has_errors: file_manager.error_count() > 0,
+        }
+    }
+
+    pub fn body(&self) -> &[Stmt] {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut Vec<Stmt> {
+        &mut self.body
+    }
+
+    /// Whether source has any syntax error
+    pub fn has_errors(&self) -> bool {
+        self.has_errors
+    }
+
+    /// Bind every name read to where it is defined, in source order
+    ///
+    /// Names not defined in source, like those of the prelude, are global without a
+    /// definition. Names read before they are defined are left out.
+    ///
+    /// ```
+    /// use diatom_core::ast::{Ast, Binding};
+    ///
+    /// let ast = Ast::parse("x = 1\ndef f y = fn = x + y end\nprintln(f(x))");
+    /// let uses: Vec<_> = ast
+    ///     .resolve()
+    ///     .into_iter()
+    ///     .map(|name| (name.name, name.binding, name.definition.map(|loc| loc.start)))
+    ///     .collect();
+    /// assert_eq!(
+    ///     uses,
+    ///     [
+    ///         ("x".to_string(), Binding::Global, Some(0)),
+    ///         ("y".to_string(), Binding::Upvalue, Some(12)),
+    ///         ("println".to_string(), Binding::Global, None),
+    ///         ("f".to_string(), Binding::Global, Some(10)),
+    ///         ("x".to_string(), Binding::Global, Some(0)),
+    ///     ]
+    /// );
+    /// ```
+    pub fn resolve(&self) -> Vec<NameUse> {
+        Resolver::new(|_| false).resolve(&self.body).0
+    }
+
+    /// Visit all statements in order
+    pub fn visit(&self, visitor: &mut impl Visitor) {
+        self.body.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+    }
+
+    /// Visit all statements in order, allowing them to be modified
+    pub fn visit_mut(&mut self, visitor: &mut impl MutVisitor) {
+        self.body
+            .iter_mut()
+            .for_each(|stmt| visitor.visit_stmt(stmt));
+    }
+}
+
+/// One node per line, children are indented under their parent
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer::default();
+        self.visit(&mut printer);
+        f.write_str(&printer.out)
+    }
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn line(&mut self, label: impl fmt::Display, loc: Option<&Loc>) {
+        let indent = "  ".repeat(self.depth);
+        match loc {
+            Some(loc) => self.out += &format!("{indent}{label}@{}..{}\n", loc.start, loc.end),
+            None => self.out += &format!("{indent}{label}\n"),
+        }
+    }
+
+    fn nested(&mut self, f: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        f(self);
+        self.depth -= 1;
+    }
+}
+
+fn names(parameters: &[(Symbol, Loc)]) -> String {
+    let names: Vec<_> = parameters.iter().map(|(name, _)| name.as_str()).collect();
+    names.join(", ")
+}
+
+impl Visitor for Printer {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        let label = match stmt {
+            Stmt::Expr { .. } => "Expr".to_string(),
+            Stmt::Continue { .. } => "Continue".to_string(),
+            Stmt::Break { .. } => "Break".to_string(),
+            Stmt::Return { .. } => "Return".to_string(),
+            Stmt::Defer { .. } => "Defer".to_string(),
+            Stmt::Loop { .. } => "Loop".to_string(),
+            Stmt::For { .. } => "For".to_string(),
+            Stmt::Def { parameters, .. } => format!("Def({})", names(parameters)),
+            Stmt::Enum { name, variants, .. } => {
+                let variants: Vec<_> = variants.iter().map(|v| v.name.to_string()).collect();
+                format!("Enum({name}: {})", variants.join(", "))
+            }
+            Stmt::Interface { name, methods, .. } => {
+                format!("Interface({name}: {})", names(methods))
+            }
+            Stmt::Macro {
+                name, parameters, ..
+            } => format!("Macro({name}: {})", names(parameters)),
+            Stmt::Import { module, items, .. } => {
+                self.line(format!("Import({})", module.join(".")), stmt.loc());
+                self.nested(|printer| {
+                    for item in items {
+                        let label = match &item.alias {
+                            Some(alias) => format!("Item({} as {alias})", item.path.join(".")),
+                            None => format!("Item({})", item.path.join(".")),
+                        };
+                        printer.line(label, Some(&item.loc));
+                    }
+                });
+                return;
+            }
+            Stmt::Error => "Error".to_string(),
+        };
+        self.line(label, stmt.loc());
+        self.nested(|printer| walk_stmt(printer, stmt));
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        let label = match expr {
+            Expr::Block { .. } => "Block".to_string(),
+            Expr::If {
+                conditional,
+                default,
+                ..
+            } => {
+                self.line("If", expr.loc());
+                self.nested(|printer| {
+                    for (condition, body) in conditional {
+                        printer.line("Branch", None);
+                        printer.nested(|printer| {
+                            printer.visit_expr(condition);
+                            body.iter().for_each(|stmt| printer.visit_stmt(stmt));
+                        });
+                    }
+                    if let Some(default) = default {
+                        printer.line("Else", None);
+                        printer.nested(|printer| {
+                            default.iter().for_each(|stmt| printer.visit_stmt(stmt));
+                        });
+                    }
+                });
+                return;
+            }
+            Expr::Prefix { op, .. } => format!("Prefix({op:?})"),
+            Expr::Call { .. } => "Call".to_string(),
+            Expr::Index { .. } => "Index".to_string(),
+            Expr::Infix { op, .. } => format!("Infix({op:?})"),
+            Expr::OpenRange { .. } => "OpenRange".to_string(),
+            Expr::Spread { .. } => "Spread".to_string(),
+            Expr::Fn { parameters, .. } => format!("Fn({})", names(parameters)),
+            Expr::Id { name, .. } => format!("Id({name})"),
+            Expr::Parentheses { .. } => "Parentheses".to_string(),
+            Expr::Const {
+                value: Const::Table(items),
+                ..
+            } => {
+                self.line("Table", expr.loc());
+                self.nested(|printer| {
+                    for (key, value, loc) in items {
+                        match key {
+                            Some(key) => {
+                                printer.line(format!("Entry({key})"), Some(loc));
+                                printer.nested(|printer| printer.visit_expr(value));
+                            }
+                            None => printer.visit_expr(value),
+                        }
+                    }
+                });
+                return;
+            }
+            Expr::Const {
+                value: Const::List(_),
+                ..
+            } => "List".to_string(),
+            Expr::Const { value, .. } => format!("{value:?}"),
+            Expr::Error => "Error".to_string(),
+        };
+        self.line(label, expr.loc());
+        self.nested(|printer| walk_expr(printer, expr));
+    }
+}
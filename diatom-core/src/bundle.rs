@@ -0,0 +1,170 @@
+//! A script and files it imports packed into one file, made by `Interpreter::bundle` and run by
+//! `Interpreter::exec_bundle`
+//!
+//! Imports of a bundle are resolved exactly as they were when it was made, with the same search
+//! paths and packages, but files are read from the bundle instead of the file system. Modules of
+//! extensions, e.g. the standard library, are not bundled since they are part of interpreter.
+//!
+//! A bundle holds source, not byte code. Byte code refers to the heap and extensions of the
+//! interpreter that compiled it and imported modules are compiled together with the importing
+//! code, thus a bundle is compiled again each time it is run, by the interpreter running it. It
+//! is compiled once when it is made as well, so that compile errors are reported before it is
+//! shipped.
+//!
+//! A bundle starts with line `diatom-bundle <length>` followed by the version of its format,
+//! after which each entry is `<key> <length>` and a value of that length on the next line. A
+//! bundle of another version is rejected by `Bundle::from_bytes`.
+use crate::compat::{
+    io,
+    path::{Path, PathBuf},
+    AHashMap,
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::host::FileSystem;
+
+/// First line of a bundle, followed by a version
+const MAGIC: &str = "diatom-bundle";
+const VERSION: u32 = 1;
+
+/// Source of a script and files it imports
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bundle {
+    /// Path of the script run, which is also in `files`
+    pub main: PathBuf,
+    /// Search paths used to resolve imports
+    pub search_path: Vec<PathBuf>,
+    /// Name and module directory of packages used to resolve imports
+    pub packages: Vec<(String, PathBuf)>,
+    /// Path and content of the script and each file it imports directly or indirectly
+    pub files: Vec<(PathBuf, String)>,
+}
+
+fn write_entry(out: &mut Vec<u8>, key: &str, value: &str) {
+    out.extend_from_slice(format!("{key} {}\n", value.len()).as_bytes());
+    out.extend_from_slice(value.as_bytes());
+    out.push(b'\n');
+}
+
+/// Entries of a bundle, each of which is `<key> <length>\n<value>\n`
+struct Entries<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Entries<'a> {
+    fn next(&mut self) -> Result<Option<(&'a str, &'a str)>, String> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+        let invalid = || "Bundle is corrupted".to_string();
+        let line_end = self
+            .bytes
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(invalid)?;
+        let line = core::str::from_utf8(&self.bytes[..line_end]).map_err(|_| invalid())?;
+        let (key, len) = line.split_once(' ').ok_or_else(invalid)?;
+        let len: usize = len.parse().map_err(|_| invalid())?;
+        let rest = &self.bytes[line_end + 1..];
+        if rest.len() <= len || rest[len] != b'\n' {
+            return Err(invalid());
+        }
+        let value = core::str::from_utf8(&rest[..len]).map_err(|_| invalid())?;
+        self.bytes = &rest[len + 1..];
+        Ok(Some((key, value)))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<&'a str, String> {
+        match self.next()? {
+            Some((key, value)) if key == expected => Ok(value),
+            _ => Err(format!("Bundle is corrupted, expect `{expected}`")),
+        }
+    }
+}
+
+impl Bundle {
+    /// Whether `bytes` starts as a bundle
+    pub fn is_bundle(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC.as_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        write_entry(&mut out, MAGIC, &VERSION.to_string());
+        write_entry(&mut out, "main", &self.main.to_string_lossy());
+        for path in &self.search_path {
+            write_entry(&mut out, "search-path", &path.to_string_lossy());
+        }
+        for (name, path) in &self.packages {
+            write_entry(&mut out, "package", name);
+            write_entry(&mut out, "package-path", &path.to_string_lossy());
+        }
+        for (path, content) in &self.files {
+            write_entry(&mut out, "file", &path.to_string_lossy());
+            write_entry(&mut out, "content", content);
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut entries = Entries { bytes };
+        let version = entries
+            .expect(MAGIC)
+            .map_err(|_| "Not a bundle".to_string())?;
+        if version != VERSION.to_string() {
+            return Err(format!(
+                "Bundle of version {version} is not supported, expect version {VERSION}"
+            ));
+        }
+        let mut bundle = Bundle {
+            main: entries.expect("main")?.into(),
+            ..Default::default()
+        };
+        while let Some((key, value)) = entries.next()? {
+            match key {
+                "search-path" => bundle.search_path.push(value.into()),
+                "package" => {
+                    let path = entries.expect("package-path")?;
+                    bundle.packages.push((value.to_string(), path.into()));
+                }
+                "file" => {
+                    let content = entries.expect("content")?;
+                    bundle.files.push((value.into(), content.to_string()));
+                }
+                _ => return Err(format!("Bundle is corrupted, unknown entry `{key}`")),
+            }
+        }
+        Ok(bundle)
+    }
+
+    /// Content of the script run
+    pub fn main_script(&self) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|(path, _)| *path == self.main)
+            .map(|(_, content)| content.as_str())
+    }
+}
+
+/// Files of a bundle, read when its imports are resolved
+pub(crate) struct BundleFiles(AHashMap<PathBuf, String>);
+
+impl BundleFiles {
+    pub fn new(bundle: &Bundle) -> Self {
+        Self(bundle.files.iter().cloned().collect())
+    }
+}
+
+impl FileSystem for BundleFiles {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.0
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+}
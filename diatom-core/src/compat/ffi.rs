@@ -0,0 +1,132 @@
+//! Names of sources without `std`, which are always UTF-8
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::String,
+};
+use core::{borrow::Borrow, fmt, ops::Deref};
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct OsStr(str);
+
+impl OsStr {
+    pub fn new<S: AsRef<OsStr> + ?Sized>(s: &S) -> &OsStr {
+        s.as_ref()
+    }
+
+    pub(super) fn from_inner(s: &str) -> &OsStr {
+        // SAFETY: `OsStr` is a transparent wrapper of `str`
+        unsafe { &*(s as *const str as *const OsStr) }
+    }
+
+    pub fn to_str(&self) -> Option<&str> {
+        Some(&self.0)
+    }
+
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.0)
+    }
+
+    pub fn to_os_string(&self) -> OsString {
+        OsString(self.0.into())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(super) fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for OsStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl AsRef<OsStr> for OsStr {
+    fn as_ref(&self) -> &OsStr {
+        self
+    }
+}
+
+impl AsRef<OsStr> for str {
+    fn as_ref(&self) -> &OsStr {
+        OsStr::from_inner(self)
+    }
+}
+
+impl AsRef<OsStr> for String {
+    fn as_ref(&self) -> &OsStr {
+        OsStr::from_inner(self)
+    }
+}
+
+impl ToOwned for OsStr {
+    type Owned = OsString;
+
+    fn to_owned(&self) -> OsString {
+        self.to_os_string()
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OsString(String);
+
+impl OsString {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn as_os_str(&self) -> &OsStr {
+        OsStr::from_inner(&self.0)
+    }
+
+    pub fn into_string(self) -> Result<String, OsString> {
+        Ok(self.0)
+    }
+}
+
+impl fmt::Debug for OsString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl Deref for OsString {
+    type Target = OsStr;
+
+    fn deref(&self) -> &OsStr {
+        self.as_os_str()
+    }
+}
+
+impl Borrow<OsStr> for OsString {
+    fn borrow(&self) -> &OsStr {
+        self.as_os_str()
+    }
+}
+
+impl AsRef<OsStr> for OsString {
+    fn as_ref(&self) -> &OsStr {
+        self.as_os_str()
+    }
+}
+
+impl From<String> for OsString {
+    fn from(s: String) -> Self {
+        Self(s)
+    }
+}
+
+impl<T: AsRef<OsStr> + ?Sized> From<&T> for OsString {
+    fn from(s: &T) -> Self {
+        s.as_ref().to_os_string()
+    }
+}
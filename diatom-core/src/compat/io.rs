@@ -0,0 +1,160 @@
+//! Output and input of the interpreter without `std`
+//!
+//! Only what the interpreter uses is provided: output is written with [`Write`] and input of
+//! `input()` is read line by line with [`BufRead`].
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    NotFound,
+    InvalidData,
+    Unsupported,
+    Other,
+}
+
+impl ErrorKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::NotFound => "entity not found",
+            ErrorKind::InvalidData => "invalid data",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::Other => "other error",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    message: Option<String>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: Some(message.into()),
+        }
+    }
+
+    pub fn other(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Other, message)
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            message: None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => f.write_str(message),
+            None => f.write_str(self.kind.as_str()),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Where output of scripts is written
+pub trait Write {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Used by `write!` and `writeln!`
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> Result<()> {
+        struct Adapter<'a, W: ?Sized> {
+            writer: &'a mut W,
+            error: Option<Error>,
+        }
+
+        impl<W: Write + ?Sized> fmt::Write for Adapter<'_, W> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.writer.write_all(s.as_bytes()).map_err(|err| {
+                    self.error = Some(err);
+                    fmt::Error
+                })
+            }
+        }
+
+        let mut adapter = Adapter {
+            writer: self,
+            error: None,
+        };
+        fmt::write(&mut adapter, args).map_err(|_| {
+            adapter
+                .error
+                .unwrap_or_else(|| Error::other("Formatter error"))
+        })
+    }
+}
+
+impl Write for Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_all(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+impl<W: Write + ?Sized> Write for Box<W> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_all(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+/// Where `input()` reads lines from
+pub trait BufRead {
+    /// Append a line including its line break to `buf`, return the number of bytes read, which
+    /// is zero at the end of input
+    fn read_line(&mut self, buf: &mut String) -> Result<usize>;
+}
+
+impl BufRead for &[u8] {
+    fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        let len = self
+            .iter()
+            .position(|c| *c == b'\n')
+            .map_or(self.len(), |i| i + 1);
+        let line = core::str::from_utf8(&self[..len])
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Input is not valid UTF-8"))?;
+        buf.push_str(line);
+        *self = &self[len..];
+        Ok(len)
+    }
+}
+
+impl<R: BufRead + ?Sized> BufRead for Box<R> {
+    fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        (**self).read_line(buf)
+    }
+}
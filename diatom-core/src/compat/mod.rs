@@ -0,0 +1,63 @@
+//! Parts of the standard library of Rust used by the interpreter
+//!
+//! With feature `std` they are re-exported from `std`. Without it, minimal versions built on
+//! `alloc` take their place, so that the rest of the crate is written once for both.
+use alloc::boxed::Box;
+
+#[cfg(not(feature = "std"))]
+pub mod ffi;
+#[cfg(not(feature = "std"))]
+pub mod io;
+#[cfg(not(feature = "std"))]
+pub mod path;
+
+#[cfg(feature = "std")]
+pub use std::{ffi, io, path};
+
+#[cfg(feature = "std")]
+pub use ahash::{AHashMap, AHashSet};
+
+#[cfg(not(feature = "std"))]
+pub type AHashMap<K, V> = hashbrown::HashMap<K, V, ahash::RandomState>;
+#[cfg(not(feature = "std"))]
+pub type AHashSet<K> = hashbrown::HashSet<K, ahash::RandomState>;
+
+/// Float functions that need the math library of the platform
+pub mod float {
+    #[cfg(feature = "std")]
+    pub fn floor(x: f64) -> f64 {
+        x.floor()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn powf(x: f64, y: f64) -> f64 {
+        x.powf(y)
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub use libm::{floor, pow as powf};
+}
+
+/// Run `f` and catch its panic, which is never caught without `std`
+pub fn catch_unwind<R>(f: impl FnOnce() -> R) -> Result<R, Box<dyn core::any::Any + Send>> {
+    #[cfg(feature = "std")]
+    {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        Ok(f())
+    }
+}
+
+/// Absolute form of `path` on the file system, `path` itself without `std`
+pub fn canonicalize(path: &path::Path) -> io::Result<path::PathBuf> {
+    #[cfg(feature = "std")]
+    {
+        path.canonicalize()
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        Ok(path.to_path_buf())
+    }
+}
@@ -0,0 +1,252 @@
+//! Paths of files without `std`, whose parts are separated by `/`
+//!
+//! They are only compared and joined, it is up to the file system supplied by host to interpret
+//! them.
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::String,
+};
+use core::{borrow::Borrow, fmt, ops::Deref};
+
+use super::ffi::{OsStr, OsString};
+
+const SEPARATOR: char = '/';
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Path(OsStr);
+
+impl Path {
+    pub fn new<S: AsRef<OsStr> + ?Sized>(s: &S) -> &Path {
+        // SAFETY: `Path` is a transparent wrapper of `OsStr`
+        unsafe { &*(s.as_ref() as *const OsStr as *const Path) }
+    }
+
+    fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn as_os_str(&self) -> &OsStr {
+        &self.0
+    }
+
+    pub fn to_str(&self) -> Option<&str> {
+        Some(self.as_str())
+    }
+
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.as_str())
+    }
+
+    pub fn to_path_buf(&self) -> PathBuf {
+        PathBuf(self.as_str().into())
+    }
+
+    pub fn display(&self) -> Display<'_> {
+        Display(self)
+    }
+
+    pub fn is_absolute(&self) -> bool {
+        self.as_str().starts_with(SEPARATOR)
+    }
+
+    /// Path without its last part, `None` for the root or an empty path
+    pub fn parent(&self) -> Option<&Path> {
+        let s = self.as_str().trim_end_matches(SEPARATOR);
+        if s.is_empty() {
+            return None;
+        }
+        Some(match s.rfind(SEPARATOR) {
+            Some(0) => Path::new("/"),
+            Some(i) => Path::new(&s[..i]),
+            None => Path::new(""),
+        })
+    }
+
+    pub fn file_name(&self) -> Option<&OsStr> {
+        let name = self.as_str().rsplit(SEPARATOR).next()?;
+        (!name.is_empty() && name != "..").then(|| OsStr::new(name))
+    }
+
+    pub fn extension(&self) -> Option<&OsStr> {
+        let name = self.file_name()?.as_str();
+        match name.rfind('.') {
+            Some(0) | None => None,
+            Some(i) => Some(OsStr::new(&name[i + 1..])),
+        }
+    }
+
+    /// Non-empty parts of the path
+    pub fn iter(&self) -> impl Iterator<Item = &OsStr> {
+        self.as_str()
+            .split(SEPARATOR)
+            .filter(|part| !part.is_empty())
+            .map(OsStr::new)
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        let mut joined = self.to_path_buf();
+        joined.push(path);
+        joined
+    }
+}
+
+impl fmt::Debug for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+pub struct Display<'a>(&'a Path);
+
+impl fmt::Display for Display<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.as_str())
+    }
+}
+
+impl AsRef<Path> for Path {
+    fn as_ref(&self) -> &Path {
+        self
+    }
+}
+
+impl AsRef<Path> for OsStr {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<Path> for OsString {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<Path> for str {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<Path> for String {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<OsStr> for Path {
+    fn as_ref(&self) -> &OsStr {
+        &self.0
+    }
+}
+
+impl ToOwned for Path {
+    type Owned = PathBuf;
+
+    fn to_owned(&self) -> PathBuf {
+        self.to_path_buf()
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PathBuf(String);
+
+impl PathBuf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn as_path(&self) -> &Path {
+        Path::new(self.0.as_str())
+    }
+
+    /// Append `path`, which replaces the whole path if it is absolute
+    pub fn push(&mut self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            self.0.clear();
+        } else if !self.0.is_empty() && !self.0.ends_with(SEPARATOR) {
+            self.0.push(SEPARATOR);
+        }
+        self.0.push_str(path.as_str());
+    }
+
+    /// Replace the extension of the last part, return `false` if there is no last part
+    pub fn set_extension(&mut self, extension: impl AsRef<OsStr>) -> bool {
+        if self.file_name().is_none() {
+            return false;
+        }
+        if let Some(old) = self.extension() {
+            let len = self.0.len() - old.len() - 1;
+            self.0.truncate(len);
+        }
+        let extension = extension.as_ref().as_str();
+        if !extension.is_empty() {
+            self.0.push('.');
+            self.0.push_str(extension);
+        }
+        true
+    }
+
+    pub fn into_os_string(self) -> OsString {
+        OsString::from(self.0)
+    }
+}
+
+impl fmt::Debug for PathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_path(), f)
+    }
+}
+
+impl Deref for PathBuf {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl Borrow<Path> for PathBuf {
+    fn borrow(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl AsRef<Path> for PathBuf {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl AsRef<OsStr> for PathBuf {
+    fn as_ref(&self) -> &OsStr {
+        self.as_path().as_os_str()
+    }
+}
+
+impl From<String> for PathBuf {
+    fn from(s: String) -> Self {
+        Self(s)
+    }
+}
+
+impl From<OsString> for PathBuf {
+    fn from(s: OsString) -> Self {
+        // Names are always UTF-8 without `std`
+        Self(s.into_string().unwrap_or_default())
+    }
+}
+
+impl From<PathBuf> for OsString {
+    fn from(path: PathBuf) -> Self {
+        path.into_os_string()
+    }
+}
+
+impl<T: AsRef<OsStr> + ?Sized> From<&T> for PathBuf {
+    fn from(s: &T) -> Self {
+        Self(s.as_ref().as_str().into())
+    }
+}
@@ -0,0 +1,253 @@
+//! API documentation from doc comments, which start with `---`
+//!
+//! Doc comments on the lines right before a top level `def`, `enum`, `interface`, `macro` or
+//! assignment document it. Those at the start of a file followed by a blank line document the
+//! module. Functions, enums, interfaces and macros are listed even if they have no doc comment,
+//! assigned variables only if they have one.
+//!
+//! # Example
+//! ```
+//! use diatom_core::doc::{extract, ItemKind};
+//!
+//! let source = "--- Geometry\n\n--- Area of a circle\ndef area r = 3.14 * r * r end";
+//! let docs = extract(source);
+//! assert_eq!(docs.doc.as_deref(), Some("Geometry"));
+//! let area = docs.get("area").unwrap();
+//! assert_eq!(area.kind, ItemKind::Function);
+//! assert_eq!(area.signature, "def area r");
+//! assert_eq!(area.doc, "Area of a circle");
+//! assert!(docs.to_markdown("geometry").contains("## `def area r`"));
+//! ```
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{fmt::Write, ops::Range};
+
+use crate::{
+    file_manager::FileManager,
+    frontend::{
+        parser::ast::{Expr, OpInfix, Stmt},
+        Lexer, Parser,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+    Function,
+    Enum,
+    Interface,
+    Macro,
+    Variable,
+}
+
+/// A documented item of a module
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemDoc {
+    pub kind: ItemKind,
+    /// Name as written, e.g. `Point.new` for a method defined by `def Point.new`
+    pub name: String,
+    /// How the item is declared, e.g. `def area r` or `enum Shape`
+    pub signature: String,
+    /// Text of its doc comments without `---`, lines are joined by `\n`
+    pub doc: String,
+    /// Byte range of the item in source
+    pub range: Range<usize>,
+    /// Line of the item, starting from 1
+    pub line: usize,
+}
+
+/// Documentation of a module
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleDoc {
+    /// Doc comments at the start of the module
+    pub doc: Option<String>,
+    /// Items in source order
+    pub items: Vec<ItemDoc>,
+}
+
+/// Collect doc comments of `source`, imports are not resolved
+///
+/// Items of statements with syntax errors are skipped.
+pub fn extract(source: &str) -> ModuleDoc {
+    let mut file_manager = FileManager::new();
+    let mut parser = Parser::new(&mut file_manager, &[])
+        .skip_imports()
+        .keep_macros();
+    let fid = parser.parse_file_phony("<source>", source);
+    let stream = Lexer::lex(&mut file_manager, fid);
+    let ast = file_manager.get_ast(fid);
+
+    let line_starts: Vec<usize> = core::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|start| *start <= offset) - 1;
+
+    // Text of lines which have nothing but a doc comment
+    let mut doc_lines = BTreeMap::new();
+    for loc in stream.comments() {
+        let comment = &source[loc.start..loc.end];
+        let line = line_of(loc.start);
+        let before = &source[line_starts[line]..loc.start];
+        if Lexer::is_doc_comment(comment) && before.trim().is_empty() {
+            let text = &comment[3..];
+            let text = text.strip_prefix(' ').unwrap_or(text);
+            doc_lines.insert(line, text.trim_end().to_string());
+        }
+    }
+    // Doc comments ending on the line before `line`
+    let doc_before = |line: usize| {
+        let mut start = line;
+        while start > 0 && doc_lines.contains_key(&(start - 1)) {
+            start -= 1;
+        }
+        let lines: Vec<&str> = (start..line).map(|i| doc_lines[&i].as_str()).collect();
+        lines.join("\n")
+    };
+
+    let mut items = vec![];
+    for stmt in ast.iter() {
+        let Some(loc) = stmt.loc() else {
+            continue;
+        };
+        let text = |range: Range<usize>| source[range].to_string();
+        let (kind, name, signature) = match stmt {
+            Stmt::Def {
+                variable,
+                parameters,
+                ..
+            } => {
+                let Some(loc) = variable.loc() else {
+                    continue;
+                };
+                let name = text(loc.start..loc.end);
+                let signature = core::iter::once(format!("def {name}"))
+                    .chain(parameters.iter().map(|(name, _)| name.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                (ItemKind::Function, name, signature)
+            }
+            Stmt::Enum { name, .. } => (ItemKind::Enum, name.to_string(), format!("enum {name}")),
+            Stmt::Interface { name, .. } => (
+                ItemKind::Interface,
+                name.to_string(),
+                format!("interface {name}"),
+            ),
+            Stmt::Macro {
+                name, parameters, ..
+            } => {
+                let parameters: Vec<_> = parameters.iter().map(|(name, _)| name.as_str()).collect();
+                let signature = format!("macro {name}({})", parameters.join(", "));
+                (ItemKind::Macro, name.to_string(), signature)
+            }
+            Stmt::Expr {
+                expr:
+                    Expr::Infix {
+                        op: OpInfix::Assign,
+                        lhs,
+                        ..
+                    },
+                ..
+            } => match lhs.as_ref() {
+                Expr::Id { name, .. } => (ItemKind::Variable, name.to_string(), name.to_string()),
+                _ => continue,
+            },
+            _ => continue,
+        };
+        let line = line_of(loc.start);
+        let doc = doc_before(line);
+        if kind == ItemKind::Variable && doc.is_empty() {
+            continue;
+        }
+        items.push(ItemDoc {
+            kind,
+            name,
+            signature,
+            doc,
+            range: loc.start..loc.end,
+            line: line + 1,
+        });
+    }
+
+    // Doc comments at the start of the module not attached to an item
+    let first = usize::from(source.starts_with("#!"));
+    let mut end = first;
+    while doc_lines.contains_key(&end) {
+        end += 1;
+    }
+    let attached = items.iter().any(|item| item.line - 1 == end);
+    let doc = (end > first && !attached).then(|| {
+        let lines: Vec<&str> = (first..end).map(|i| doc_lines[&i].as_str()).collect();
+        lines.join("\n")
+    });
+    ModuleDoc { doc, items }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Paragraphs of a doc, which are separated by blank lines
+fn paragraphs(doc: &str) -> impl Iterator<Item = &str> {
+    doc.split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+}
+
+impl ModuleDoc {
+    /// Item named `name`, e.g. to show its doc when hovering a name
+    pub fn get(&self, name: &str) -> Option<&ItemDoc> {
+        self.items.iter().find(|item| item.name == name)
+    }
+
+    /// Item whose source contains byte `offset`
+    pub fn item_at(&self, offset: usize) -> Option<&ItemDoc> {
+        self.items.iter().find(|item| item.range.contains(&offset))
+    }
+
+    /// Markdown with `title` as heading and a section for each item
+    ///
+    /// Docs are copied as they are, thus they can use markdown.
+    pub fn to_markdown(&self, title: &str) -> String {
+        let mut out = format!("# {title}\n");
+        if let Some(doc) = &self.doc {
+            write!(out, "\n{doc}\n").unwrap();
+        }
+        for item in &self.items {
+            write!(out, "\n## `{}`\n", item.signature).unwrap();
+            if !item.doc.is_empty() {
+                write!(out, "\n{}\n", item.doc).unwrap();
+            }
+        }
+        out
+    }
+
+    /// HTML fragment with `title` as heading and a section for each item
+    ///
+    /// Docs are escaped and split into paragraphs by blank lines.
+    pub fn to_html(&self, title: &str) -> String {
+        let mut out = format!("<h1>{}</h1>\n", escape_html(title));
+        for paragraph in self.doc.iter().flat_map(|doc| paragraphs(doc)) {
+            writeln!(out, "<p>{}</p>", escape_html(paragraph)).unwrap();
+        }
+        for item in &self.items {
+            writeln!(
+                out,
+                "<h2 id=\"{}\"><code>{}</code></h2>",
+                escape_html(&item.name),
+                escape_html(&item.signature)
+            )
+            .unwrap();
+            for paragraph in paragraphs(&item.doc) {
+                writeln!(out, "<p>{}</p>", escape_html(paragraph)).unwrap();
+            }
+        }
+        out
+    }
+}
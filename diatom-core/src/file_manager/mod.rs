@@ -1,45 +1,341 @@
-use ahash::{AHashMap, AHashSet};
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use codespan_reporting::term::termcolor::{Ansi, NoColor, WriteColor};
 use codespan_reporting::{
     diagnostic::{self, Severity},
-    files::SimpleFiles,
-    term::{self, termcolor::Buffer, Chars},
+    files::Files,
+    term::{self, Chars},
 };
-use std::{collections::BTreeMap, ffi::OsString, io::Write, sync::Arc};
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::{env, io::IsTerminal};
 
-use crate::frontend::parser::ast::Stmt;
+use crate::compat::{
+    ffi::OsString,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    AHashMap, AHashSet,
+};
+use crate::frontend::{
+    parser::{ast::Stmt, ParseCache},
+    Symbol, SymbolTable,
+};
+use crate::host::{default_file_system, FileSystem, ModuleLoader};
 
 pub type Diagnostic = diagnostic::Diagnostic<usize>;
 
 mod util;
-pub use util::Loc;
-use util::{PathShow, SharedFile};
+pub use util::{display_width, Loc, SharedFile, TAB_WIDTH};
+use util::{PathShow, SourceFiles};
+
+/// When to color diagnostics with ANSI escape codes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    Always,
+    #[default]
+    Never,
+    /// Color if stdout is a terminal and `NO_COLOR` is not set, never without `std`
+    Auto,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            #[cfg(feature = "std")]
+            ColorChoice::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            #[cfg(not(feature = "std"))]
+            ColorChoice::Auto => false,
+        }
+    }
+}
+
+/// How diagnostics are rendered
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub color: ColorChoice,
+    /// Wrap messages and notes at word boundaries to fit this many columns
+    ///
+    /// Source snippets are never wrapped. No wrapping if `None`.
+    pub width: Option<usize>,
+}
+
+impl RenderOptions {
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn width(mut self, width: Option<usize>) -> Self {
+        self.width = width;
+        self
+    }
+}
+
+/// Where a span of generated code comes from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceMapping {
+    /// Byte range in the generated file
+    pub generated: Range<usize>,
+    /// Path of the original file
+    pub path: String,
+    /// Line of the start of the span in the original file, starts from 1
+    pub line: usize,
+    /// Column of the start of the span in the original file, starts from 1
+    pub column: usize,
+}
+
+/// Map spans of a generated file back to the files it is generated from
+///
+/// Text in a mapped span is assumed to be copied from the original, so positions inside it are
+/// counted from the start of the span.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    mappings: Vec<SourceMapping>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map bytes `generated` of the generated file to `line` and `column` of `path`
+    pub fn map(
+        mut self,
+        generated: Range<usize>,
+        path: impl Into<String>,
+        line: usize,
+        column: usize,
+    ) -> Self {
+        self.mappings.push(SourceMapping {
+            generated,
+            path: path.into(),
+            line,
+            column,
+        });
+        self
+    }
+
+    /// The smallest span containing `byte`, the last one added if several are equal
+    pub fn lookup(&self, byte: usize) -> Option<&SourceMapping> {
+        self.mappings
+            .iter()
+            .rev()
+            .filter(|mapping| mapping.generated.contains(&byte))
+            .min_by_key(|mapping| mapping.generated.len())
+    }
+
+    pub fn mappings(&self) -> &[SourceMapping] {
+        &self.mappings
+    }
+}
+
+/// How warnings are reported
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WarningLevel {
+    /// Warnings are dropped
+    Allow,
+    #[default]
+    Warn,
+    /// Warnings are reported as errors
+    Deny,
+}
+
+/// Which warnings are reported and how
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WarningOptions {
+    pub level: WarningLevel,
+    suppressed: Vec<String>,
+}
+
+impl WarningOptions {
+    pub fn level(mut self, level: WarningLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Never report warnings matching `name`
+    ///
+    /// `name` is either a code like `W0000` or a warning name like `unused-variable`. A name
+    /// also matches warnings it is a prefix of up to a `-`, so `unused` suppresses
+    /// `unused-variable`.
+    pub fn suppress(mut self, name: impl Into<String>) -> Self {
+        self.suppressed.push(name.into());
+        self
+    }
+
+    /// Apply a command line flag: `allow`, `warn`, `deny` or `no-<name>` to suppress `<name>`
+    pub fn with_flag(self, flag: &str) -> Result<Self, String> {
+        match flag {
+            "allow" => Ok(self.level(WarningLevel::Allow)),
+            "warn" => Ok(self.level(WarningLevel::Warn)),
+            "deny" => Ok(self.level(WarningLevel::Deny)),
+            _ => match flag.strip_prefix("no-") {
+                Some(name) if !name.is_empty() => Ok(self.suppress(name)),
+                _ => Err(format!(
+                    "Invalid warning flag `{flag}`, expected `allow`, `warn`, `deny` or `no-<name>`"
+                )),
+            },
+        }
+    }
+
+    pub fn is_suppressed(&self, warning: &Warning) -> bool {
+        let code = warning.diagnostic.code.as_deref();
+        self.suppressed.iter().any(|suppressed| {
+            code == Some(suppressed.as_str())
+                || warning
+                    .name
+                    .strip_prefix(suppressed.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+        })
+    }
+}
+
+/// A warning diagnostic and the name it can be suppressed by
+pub struct Warning {
+    /// Name in kebab case, e.g. `unused-variable`
+    pub name: &'static str,
+    pub diagnostic: Diagnostic,
+}
+
+/// Columns taken by `  = ` before notes, assuming line numbers of at most 3 digits
+const NOTE_INDENT: usize = 8;
+
+/// Wrap each line of `text` at spaces so that lines fit `width` columns where possible
+fn wrap(text: &str, width: usize) -> String {
+    let width = width.max(1);
+    let mut wrapped = String::new();
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            wrapped.push('\n');
+        }
+        let mut column = 0;
+        for word in line.split(' ') {
+            let len = display_width(word);
+            if column > 0 && column + 1 + len > width {
+                wrapped.push('\n');
+                column = 0;
+            } else if column > 0 {
+                wrapped.push(' ');
+                column += 1;
+            }
+            wrapped.push_str(word);
+            column += len;
+        }
+    }
+    wrapped
+}
 
 /// Manage and display diagnoses and opened files
+#[derive(Clone)]
 pub struct FileManager {
-    files: SimpleFiles<PathShow, SharedFile>,
+    files: SourceFiles,
     file_map: AHashMap<PathShow, usize>,
     ast_map: BTreeMap<usize, Arc<Vec<Stmt>>>,
+    parse_cache: BTreeMap<usize, ParseCache>,
     diagnoses: Vec<Diagnostic>,
     extensions: AHashSet<String>,
     error_count: usize,
+    warning_count: usize,
+    warning_options: WarningOptions,
+    source_maps: AHashMap<PathShow, SourceMap>,
     has_eof_error: bool,
     has_non_eof_error: bool,
+    symbols: SymbolTable,
+    file_system: Arc<dyn FileSystem>,
+    module_loader: Option<Arc<dyn ModuleLoader>>,
 }
 
 impl FileManager {
     pub fn new() -> Self {
         Self {
-            files: SimpleFiles::new(),
-            file_map: AHashMap::new(),
+            files: SourceFiles::default(),
+            file_map: AHashMap::default(),
             ast_map: BTreeMap::new(),
+            parse_cache: BTreeMap::new(),
             diagnoses: vec![],
-            extensions: AHashSet::new(),
+            extensions: AHashSet::default(),
             error_count: 0,
+            warning_count: 0,
+            warning_options: WarningOptions::default(),
+            source_maps: AHashMap::default(),
             has_eof_error: false,
             has_non_eof_error: false,
+            symbols: SymbolTable::default(),
+            file_system: default_file_system(),
+            module_loader: None,
         }
     }
 
+    /// Symbol of identifier `name`, shared by all files
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        self.symbols.intern(name)
+    }
+
+    /// A file manager with the same files and options but nothing parsed or reported, used
+    /// to parse files on other threads
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            files: self.files.clone(),
+            file_map: self.file_map.clone(),
+            extensions: self.extensions.clone(),
+            warning_options: self.warning_options.clone(),
+            source_maps: self.source_maps.clone(),
+            file_system: self.file_system.clone(),
+            module_loader: self.module_loader.clone(),
+            ..Self::new()
+        }
+    }
+
+    /// Take syntax trees and diagnostics of a fork, files added to the fork are ignored
+    pub(crate) fn join(&mut self, fork: Self) {
+        self.ast_map.extend(fork.ast_map);
+        self.parse_cache.extend(fork.parse_cache);
+        self.diagnoses.extend(fork.diagnoses);
+        self.error_count += fork.error_count;
+        self.warning_count += fork.warning_count;
+        self.has_eof_error |= fork.has_eof_error;
+        self.has_non_eof_error |= fork.has_non_eof_error;
+        self.symbols.join(fork.symbols);
+    }
+
+    /// Read a file from the file system supplied by host
+    pub fn read_file(&self, path: &Path) -> io::Result<String> {
+        self.file_system.read_to_string(path)
+    }
+
+    pub fn file_system(&self) -> &Arc<dyn FileSystem> {
+        &self.file_system
+    }
+
+    pub fn set_file_system(&mut self, file_system: Arc<dyn FileSystem>) -> Arc<dyn FileSystem> {
+        core::mem::replace(&mut self.file_system, file_system)
+    }
+
+    /// Source of module `name` from the loader supplied by host
+    pub fn load_module(&self, name: &str) -> Option<String> {
+        self.module_loader.as_ref()?.load(name)
+    }
+
+    pub fn module_loader(&self) -> Option<&Arc<dyn ModuleLoader>> {
+        self.module_loader.as_ref()
+    }
+
+    pub fn set_module_loader(
+        &mut self,
+        loader: Option<Arc<dyn ModuleLoader>>,
+    ) -> Option<Arc<dyn ModuleLoader>> {
+        core::mem::replace(&mut self.module_loader, loader)
+    }
+
     pub fn new_ext(&mut self, name: String) -> bool {
         self.extensions.insert(name)
     }
@@ -50,12 +346,7 @@ impl FileManager {
 
     pub fn add_file(&mut self, path: impl Into<OsString>, file: String) -> usize {
         let path = PathShow::from(path.into());
-        let fid = self.files.add(
-            path.clone(),
-            SharedFile {
-                file: Arc::new(file),
-            },
-        );
+        let fid = self.files.add(path.clone(), SharedFile::new(file));
         self.file_map.insert(path, fid);
         fid
     }
@@ -65,11 +356,38 @@ impl FileManager {
     }
 
     pub fn get_file(&self, fid: usize) -> Arc<String> {
-        self.files.get(fid).unwrap().source().file.clone()
+        self.files.get(fid).unwrap().file.clone()
+    }
+
+    pub fn get_shared_file(&self, fid: usize) -> &SharedFile {
+        self.files.get(fid).unwrap()
+    }
+
+    /// Replace content of a file, its syntax tree is outdated until it is parsed again
+    pub fn replace_file(&mut self, fid: usize, file: String) {
+        self.files.replace(fid, SharedFile::new(file));
+    }
+
+    /// Path of a file as it is added
+    pub fn file_path(&self, fid: usize) -> Option<PathBuf> {
+        self.files
+            .name(fid)
+            .ok()
+            .map(|path| PathBuf::from(path.as_os_str()))
+    }
+
+    /// Drop content, syntax tree and source map of a file no longer needed, its id stays valid
+    pub fn release_file(&mut self, fid: usize) {
+        self.files.replace(fid, SharedFile::new(""));
+        self.ast_map.remove(&fid);
+        self.parse_cache.remove(&fid);
+        if let Ok(path) = self.files.name(fid) {
+            self.source_maps.remove(&path);
+        }
     }
 
     pub fn set_ast(&mut self, fid: usize, ast: Vec<Stmt>) {
-        assert!(self.files.get(fid).is_ok());
+        assert!(self.files.get(fid).is_some());
         self.ast_map.insert(fid, Arc::new(ast));
     }
 
@@ -77,14 +395,36 @@ impl FileManager {
         self.ast_map.get(&fid).unwrap().clone()
     }
 
+    pub fn set_parse_cache(&mut self, fid: usize, cache: ParseCache) {
+        self.parse_cache.insert(fid, cache);
+    }
+
+    pub fn get_parse_cache(&self, fid: usize) -> Option<&ParseCache> {
+        self.parse_cache.get(&fid)
+    }
+
+    pub fn take_parse_cache(&mut self, fid: usize) -> Option<ParseCache> {
+        self.parse_cache.remove(&fid)
+    }
+
     pub fn input_can_continue(&self) -> bool {
         self.has_eof_error && !self.has_non_eof_error
     }
 
-    pub fn add_diagnostic(&mut self, diag: Diagnostic, is_eof: bool) {
+    /// Labels of calls made by host are replaced by a note as they have no source.
+    pub fn add_diagnostic(&mut self, mut diag: Diagnostic, is_eof: bool) {
         use Severity::*;
+        if diag
+            .labels
+            .iter()
+            .any(|label| label.file_id == Loc::HOST.fid)
+        {
+            diag.labels.retain(|label| label.file_id != Loc::HOST.fid);
+            diag.notes.push("Called by host".to_string());
+        }
         match diag.severity {
             Error => self.error_count += 1,
+            Warning => self.warning_count += 1,
             _ => unreachable!(),
         }
         if is_eof {
@@ -98,36 +438,115 @@ impl FileManager {
         self.diagnoses.push(diag)
     }
 
+    /// Report a warning according to warning options
+    pub fn add_warning(&mut self, warning: Warning) {
+        if self.warning_options.is_suppressed(&warning) {
+            return;
+        }
+        let Warning { name, diagnostic } = warning;
+        match self.warning_options.level {
+            WarningLevel::Allow => (),
+            WarningLevel::Warn => self.add_diagnostic(diagnostic, false),
+            WarningLevel::Deny => {
+                let mut diagnostic = diagnostic.with_notes(vec![format!(
+                    "Warning `{name}` is reported as error since warnings are denied"
+                )]);
+                diagnostic.severity = Severity::Error;
+                self.add_diagnostic(diagnostic, false)
+            }
+        }
+    }
+
+    pub fn set_warning_options(&mut self, options: WarningOptions) -> WarningOptions {
+        core::mem::replace(&mut self.warning_options, options)
+    }
+
+    pub fn warning_options(&self) -> &WarningOptions {
+        &self.warning_options
+    }
+
+    /// Set source map of files named `path`, remove it if `None`, return the old one
+    pub fn set_source_map(
+        &mut self,
+        path: impl Into<OsString>,
+        map: Option<SourceMap>,
+    ) -> Option<SourceMap> {
+        let path = PathShow::from(path.into());
+        match map {
+            Some(map) => self.source_maps.insert(path, map),
+            None => self.source_maps.remove(&path),
+        }
+    }
+
+    /// Path, line and column in the original file of `byte` in a generated file
+    fn original_position(&self, fid: usize, byte: usize) -> Option<(String, usize, usize)> {
+        let mapping = self
+            .source_maps
+            .get(&self.files.name(fid).ok()?)?
+            .lookup(byte)?;
+        let file = self.files.get(fid)?;
+        let (start_line, start_column) = file.line_col(mapping.generated.start);
+        let (line, column) = file.line_col(byte);
+        let column = if line == start_line {
+            mapping.column + column - start_column
+        } else {
+            column
+        };
+        Some((
+            mapping.path.clone(),
+            mapping.line + line - start_line,
+            column,
+        ))
+    }
+
+    /// The first error, warnings are skipped
+    pub fn first_error(&self) -> Option<&Diagnostic> {
+        self.diagnoses
+            .iter()
+            .find(|diagnostic| diagnostic.severity >= Severity::Error)
+    }
+
     pub fn error_count(&self) -> usize {
         self.error_count
     }
 
+    /// Number of errors and warnings
+    pub fn diagnostic_count(&self) -> usize {
+        self.diagnoses.len()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.warning_count
+    }
+
     pub fn clear_diagnoses(&mut self) {
         self.diagnoses.clear();
         self.error_count = 0;
+        self.warning_count = 0;
         self.has_eof_error = false;
         self.has_non_eof_error = false;
     }
 
     /// Render error to string
-    pub fn render(&self, color: bool) -> String {
-        let mut writer = if color {
-            Buffer::ansi()
-        } else {
-            Buffer::no_color()
-        };
-        let config = codespan_reporting::term::Config {
-            chars: Chars::ascii(),
-            ..Default::default()
-        };
-        for diagnostic in &self.diagnoses {
-            let r = term::emit(&mut writer, &config, &self.files, diagnostic);
-            if let Err(r) = r {
-                let _ = writeln!(writer, "{r:?}");
-                let _ = writeln!(writer, "{diagnostic:?}");
-            }
+    /// Path, line and column number of the start of `loc`, both numbers start from 1
+    ///
+    /// Locations in generated files are translated with their source maps.
+    pub fn locate(&self, loc: &Loc) -> Option<(String, usize, usize)> {
+        if let Some(position) = self.original_position(loc.fid, loc.start) {
+            return Some(position);
         }
-        String::from_utf8(writer.into_inner()).unwrap_or_else(|_| {
+        let line = self.files.line_index(loc.fid, loc.start).ok()?;
+        let column = self.files.column_number(loc.fid, line, loc.start).ok()?;
+        let path = self.files.name(loc.fid).ok()?.to_string();
+        Some((path, line + 1, column))
+    }
+
+    /// Render diagnoses to a string
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let mut buffer = vec![];
+        // Writing to a `Vec` never fails
+        let _ = self.emit(&mut buffer, options);
+        String::from_utf8(buffer).unwrap_or_else(|_| {
             format!(
                 "{}:{}: Internal error(Invalid utf8 buffer)",
                 file!(),
@@ -135,4 +554,82 @@ impl FileManager {
             )
         })
     }
+
+    /// Write diagnoses to `writer`
+    #[cfg(feature = "std")]
+    pub fn emit(&self, writer: impl Write, options: &RenderOptions) -> io::Result<()> {
+        if options.color.enabled() {
+            self.emit_to(&mut Ansi::new(writer), options)
+        } else {
+            self.emit_to(&mut NoColor::new(writer), options)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn emit_to(&self, writer: &mut dyn WriteColor, options: &RenderOptions) -> io::Result<()> {
+        for diagnostic in self.prepare(options) {
+            match term::emit_to_write_style(writer, &render_config(), &self.files, &diagnostic) {
+                Ok(()) => (),
+                Err(codespan_reporting::files::Error::Io(err)) => return Err(err),
+                Err(r) => {
+                    writeln!(writer, "{r:?}")?;
+                    writeln!(writer, "{diagnostic:?}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write diagnoses to `writer`, never colored without `std`
+    #[cfg(not(feature = "std"))]
+    pub fn emit(&self, mut writer: impl Write, options: &RenderOptions) -> io::Result<()> {
+        for diagnostic in self.prepare(options) {
+            match term::emit_into_string(&render_config(), &self.files, &diagnostic) {
+                Ok(rendered) => writer.write_all(rendered.as_bytes())?,
+                Err(r) => {
+                    writeln!(writer, "{r:?}")?;
+                    writeln!(writer, "{diagnostic:?}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Diagnoses with notes of their origins, wrapped to fit the width in `options`
+    fn prepare<'a>(&'a self, options: &'a RenderOptions) -> impl Iterator<Item = Diagnostic> + 'a {
+        self.diagnoses.iter().map(|diagnostic| {
+            let mut diagnostic = diagnostic.clone();
+            let origins: Vec<_> = diagnostic
+                .labels
+                .iter()
+                .filter_map(|label| self.original_position(label.file_id, label.range.start))
+                .map(|(path, line, column)| format!("Generated from `{path}:{line}:{column}`"))
+                .collect();
+            for note in origins {
+                if !diagnostic.notes.contains(&note) {
+                    diagnostic.notes.push(note);
+                }
+            }
+            if let Some(width) = options.width {
+                // Header is `error[E0000]: `
+                let header = format!("{:?}", diagnostic.severity).len()
+                    + diagnostic.code.as_ref().map_or(0, |code| code.len() + 2)
+                    + 2;
+                diagnostic.message = wrap(&diagnostic.message, width.saturating_sub(header));
+                diagnostic
+                    .notes
+                    .iter_mut()
+                    .for_each(|note| *note = wrap(note, width.saturating_sub(NOTE_INDENT)));
+            }
+            diagnostic
+        })
+    }
+}
+
+fn render_config() -> term::Config {
+    term::Config {
+        chars: Chars::ascii(),
+        tab_width: TAB_WIDTH,
+        ..Default::default()
+    }
 }
@@ -1,12 +1,18 @@
-use std::{
-    ffi::OsString,
+use crate::compat::ffi::{OsStr, OsString};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::{
     fmt::Display,
     ops::{Add, Range},
-    sync::Arc,
 };
 
-#[derive(Clone)]
-#[cfg_attr(test, derive(Debug))]
+use codespan_reporting::files::{Error, Files};
+use unicode_width::UnicodeWidthChar;
+
+/// Columns a tab advances to the next multiple of, same as the diagnostic printer
+pub const TAB_WIDTH: usize = 4;
+
+/// A span of a file, `start` and `end` are UTF-8 byte offsets
+#[derive(Clone, Debug)]
 pub struct Loc {
     pub start: usize,
     pub end: usize,
@@ -19,6 +25,25 @@ impl From<Loc> for Range<usize> {
     }
 }
 
+impl Loc {
+    /// Location of calls made by host, which is not part of any file
+    pub const HOST: Loc = Loc {
+        start: 0,
+        end: 0,
+        fid: usize::MAX,
+    };
+
+    pub fn is_host(&self) -> bool {
+        self.fid == Self::HOST.fid
+    }
+
+    /// Move by `delta` bytes
+    pub fn shift(&mut self, delta: isize) {
+        self.start = self.start.wrapping_add_signed(delta);
+        self.end = self.end.wrapping_add_signed(delta);
+    }
+}
+
 impl Add<Loc> for Loc {
     type Output = Self;
     fn add(self, rhs: Loc) -> Self::Output {
@@ -37,7 +62,7 @@ pub struct PathShow {
 }
 
 impl Display for PathShow {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         if let Some(s) = self.path.to_str() {
             write!(f, "{s}")
         } else {
@@ -46,6 +71,12 @@ impl Display for PathShow {
     }
 }
 
+impl PathShow {
+    pub fn as_os_str(&self) -> &OsStr {
+        &self.path
+    }
+}
+
 impl From<OsString> for PathShow {
     fn from(value: OsString) -> Self {
         PathShow {
@@ -54,8 +85,108 @@ impl From<OsString> for PathShow {
     }
 }
 
+/// Content of a source file
+///
+/// Locations are byte offsets, which can be converted to characters, lines and columns here.
+/// Lines and columns start from 1. Offsets inside a multi-byte character are treated as the
+/// start of that character.
+#[derive(Clone)]
 pub struct SharedFile {
     pub file: Arc<String>,
+    /// Byte offset of the start of each line
+    line_starts: Arc<Vec<usize>>,
+}
+
+impl SharedFile {
+    pub fn new(file: impl Into<String>) -> Self {
+        let file = file.into();
+        let line_starts = core::iter::once(0)
+            .chain(file.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            file: Arc::new(file),
+            line_starts: Arc::new(line_starts),
+        }
+    }
+
+    /// Move `byte` back to the start of the character it is in
+    fn floor_char_boundary(&self, byte: usize) -> usize {
+        let mut byte = byte.min(self.file.len());
+        while !self.file.is_char_boundary(byte) {
+            byte -= 1;
+        }
+        byte
+    }
+
+    /// Line containing `byte`
+    pub fn line(&self, byte: usize) -> usize {
+        match self.line_starts.binary_search(&byte) {
+            Ok(line) => line + 1,
+            Err(next) => next,
+        }
+    }
+
+    /// Byte offset of the start of `line`, `None` if there is no such line
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        line.checked_sub(1)
+            .and_then(|line| self.line_starts.get(line))
+            .copied()
+    }
+
+    /// Number of characters before `byte`
+    pub fn byte_to_char(&self, byte: usize) -> usize {
+        self.file[..self.floor_char_boundary(byte)].chars().count()
+    }
+
+    /// Byte offset of the `char`th character, `None` if the file is shorter
+    ///
+    /// The length of the file is returned for the character after the last one.
+    pub fn char_to_byte(&self, char: usize) -> Option<usize> {
+        self.file
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(core::iter::once(self.file.len()))
+            .nth(char)
+    }
+
+    /// Line and column of `byte`, where column counts characters
+    pub fn line_col(&self, byte: usize) -> (usize, usize) {
+        let byte = self.floor_char_boundary(byte);
+        let line = self.line(byte);
+        let start = self.line_starts[line - 1];
+        (line, self.file[start..byte].chars().count() + 1)
+    }
+
+    /// Byte offset of `line` and `column`, inverse of `line_col`
+    ///
+    /// Return `None` if the position is not in the file. The end of a line is a valid position.
+    pub fn line_col_to_byte(&self, line: usize, column: usize) -> Option<usize> {
+        let start = self.line_start(line)?;
+        let end = self.line_start(line + 1).unwrap_or(self.file.len());
+        self.file[start..end]
+            .char_indices()
+            .map(|(i, _)| start + i)
+            .chain(core::iter::once(end))
+            .nth(column.checked_sub(1)?)
+    }
+
+    /// Column of `byte` as displayed in a terminal
+    ///
+    /// Wide characters such as CJK take 2 columns, combining marks take none and tabs advance
+    /// to the next multiple of `TAB_WIDTH`.
+    pub fn display_column(&self, byte: usize) -> usize {
+        let byte = self.floor_char_boundary(byte);
+        let start = self.line_starts[self.line(byte) - 1];
+        display_width(&self.file[start..byte]) + 1
+    }
+}
+
+/// Width of `text` as displayed in a terminal, `text` is assumed to start at a line start
+pub fn display_width(text: &str) -> usize {
+    text.chars().fold(0, |width, c| match c {
+        '\t' => width + TAB_WIDTH - width % TAB_WIDTH,
+        c => width + c.width().unwrap_or(0),
+    })
 }
 
 impl AsRef<str> for SharedFile {
@@ -63,3 +194,61 @@ impl AsRef<str> for SharedFile {
         self.file.as_str()
     }
 }
+
+/// Opened files, content of a file can be replaced while keeping its id
+#[derive(Clone, Default)]
+pub struct SourceFiles {
+    files: Vec<(PathShow, SharedFile)>,
+}
+
+impl SourceFiles {
+    pub fn add(&mut self, name: PathShow, file: SharedFile) -> usize {
+        self.files.push((name, file));
+        self.files.len() - 1
+    }
+
+    pub fn get(&self, fid: usize) -> Option<&SharedFile> {
+        self.files.get(fid).map(|(_, file)| file)
+    }
+
+    pub fn replace(&mut self, fid: usize, file: SharedFile) {
+        self.files[fid].1 = file;
+    }
+}
+
+impl<'a> Files<'a> for SourceFiles {
+    type FileId = usize;
+    type Name = PathShow;
+    type Source = &'a str;
+
+    fn name(&'a self, fid: usize) -> Result<PathShow, Error> {
+        self.files
+            .get(fid)
+            .map(|(name, _)| name.clone())
+            .ok_or(Error::FileMissing)
+    }
+
+    fn source(&'a self, fid: usize) -> Result<&'a str, Error> {
+        self.get(fid)
+            .map(|file| file.file.as_str())
+            .ok_or(Error::FileMissing)
+    }
+
+    fn line_index(&'a self, fid: usize, byte_index: usize) -> Result<usize, Error> {
+        let file = self.get(fid).ok_or(Error::FileMissing)?;
+        Ok(file.line(byte_index) - 1)
+    }
+
+    fn line_range(&'a self, fid: usize, line_index: usize) -> Result<Range<usize>, Error> {
+        let file = self.get(fid).ok_or(Error::FileMissing)?;
+        let line_start = |line: usize| match line.cmp(&file.line_starts.len()) {
+            core::cmp::Ordering::Less => Ok(file.line_starts[line]),
+            core::cmp::Ordering::Equal => Ok(file.file.len()),
+            core::cmp::Ordering::Greater => Err(Error::LineTooLarge {
+                given: line,
+                max: file.line_starts.len() - 1,
+            }),
+        };
+        Ok(line_start(line_index)?..line_start(line_index + 1)?)
+    }
+}
@@ -0,0 +1,526 @@
+//! Source formatter built on the syntax tree
+use crate::{
+    file_manager::{display_width, FileManager, Loc, RenderOptions},
+    frontend::{
+        parser::ast::{Const, Expr, OpInfix, OpPrefix, Stmt},
+        Lexer, Parser,
+    },
+};
+use alloc::{format, string::String, vec::Vec};
+
+const INDENT: &str = "    ";
+
+/// Calls, lists and tables longer than this are split into one item per line
+pub const MAX_WIDTH: usize = 100;
+
+/// Pretty-print `source` in canonical form
+///
+/// * Each statement takes its own line and blocks are indented by 4 spaces.
+/// * Infix operators are surrounded by spaces except `.`, `::` and `..`.
+/// * Calls, lists and tables exceeding `MAX_WIDTH` are split into one item per line.
+/// * Comments are kept. A comment inside an expression is moved before its statement.
+/// * Consecutive blank lines are merged and literals keep their spelling.
+///
+/// Imported modules are not resolved. Rendered diagnostics are returned if `source` has syntax
+/// errors.
+pub fn format(source: &str) -> Result<String, String> {
+    let mut file_manager = FileManager::new();
+    let mut parser = Parser::new(&mut file_manager, &[])
+        .skip_imports()
+        .keep_macros();
+    let fid = parser.parse_file_phony("<source>", source);
+    if file_manager.error_count() > 0 {
+        return Err(file_manager.render(&RenderOptions::default()));
+    }
+    let comments = Lexer::lex(&mut file_manager, fid).comments().to_vec();
+    let ast = file_manager.get_ast(fid);
+
+    let mut formatter = Formatter {
+        source,
+        comments: &comments,
+        next_comment: 0,
+        last_end: 0,
+        first_in_block: true,
+        block_end: usize::MAX,
+        indent: 0,
+        column: 0,
+        out: String::new(),
+    };
+    if source.starts_with("#!") {
+        let shebang = source.lines().next().unwrap_or_default();
+        formatter.out.push_str(shebang.trim_end());
+        formatter.out.push('\n');
+        formatter.last_end = shebang.len();
+        formatter.first_in_block = false;
+    }
+    ast.iter().for_each(|stmt| formatter.stmt(stmt));
+    formatter.comments_before(usize::MAX);
+    Ok(formatter.out)
+}
+
+fn stmt_loc(stmt: &Stmt) -> &Loc {
+    match stmt {
+        Stmt::Expr { loc, .. }
+        | Stmt::Continue { loc }
+        | Stmt::Break { loc }
+        | Stmt::Return { loc, .. }
+        | Stmt::Defer { loc, .. }
+        | Stmt::Loop { loc, .. }
+        | Stmt::For { loc, .. }
+        | Stmt::Def { loc, .. }
+        | Stmt::Enum { loc, .. }
+        | Stmt::Interface { loc, .. }
+        | Stmt::Macro { loc, .. }
+        | Stmt::Import { loc, .. } => loc,
+        Stmt::Error => unreachable!(),
+    }
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    comments: &'a [Loc],
+    /// Index of the first comment not written yet
+    next_comment: usize,
+    /// End of the last statement or comment written
+    last_end: usize,
+    /// Suppress blank line at the start of a block
+    first_in_block: bool,
+    /// End of the innermost block, comments after it belong to the enclosing statement
+    block_end: usize,
+    indent: usize,
+    /// Column where `out` starts
+    column: usize,
+    out: String,
+}
+
+impl<'a> Formatter<'a> {
+    /// A formatter to write a part of current line, used to measure width before writing
+    fn child(&self) -> Self {
+        Self {
+            source: self.source,
+            comments: self.comments,
+            next_comment: self.next_comment,
+            last_end: self.last_end,
+            first_in_block: false,
+            block_end: self.block_end,
+            indent: self.indent,
+            column: self.column(),
+            out: String::new(),
+        }
+    }
+
+    fn adopt(&mut self, child: Self) {
+        self.out.push_str(&child.out);
+        self.next_comment = child.next_comment;
+        self.last_end = child.last_end;
+    }
+
+    fn column(&self) -> usize {
+        match self.out.rfind('\n') {
+            Some(i) => display_width(&self.out[i + 1..]),
+            None => self.column + display_width(&self.out),
+        }
+    }
+
+    fn begin_line(&mut self) {
+        (0..self.indent).for_each(|_| self.out.push_str(INDENT));
+    }
+
+    fn blank_line(&mut self, start: usize) {
+        let newlines = self
+            .source
+            .get(self.last_end..start)
+            .map_or(0, |s| s.matches('\n').count());
+        if !self.first_in_block && newlines > 1 {
+            self.out.push('\n');
+        }
+    }
+
+    fn comment_line(&mut self, comment: &Loc) {
+        self.begin_line();
+        self.out
+            .push_str(self.source[comment.start..comment.end].trim_end());
+        self.out.push('\n');
+    }
+
+    /// Write comments before `pos` on their own lines
+    fn comments_before(&mut self, pos: usize) {
+        let comments = self.comments;
+        while let Some(comment) = comments.get(self.next_comment).filter(|c| c.start < pos) {
+            self.blank_line(comment.start);
+            self.comment_line(comment);
+            self.last_end = comment.end;
+            self.first_in_block = false;
+            self.next_comment += 1;
+        }
+    }
+
+    /// Append a comment starting on the same line as `end`
+    fn trailing_comment(&mut self, end: usize) {
+        let comments = self.comments;
+        if let Some(comment) = comments
+            .get(self.next_comment)
+            .filter(|c| c.start >= end && c.start < self.block_end)
+            .filter(|c| !self.source[end..c.start].contains('\n'))
+        {
+            self.out.push(' ');
+            self.out
+                .push_str(self.source[comment.start..comment.end].trim_end());
+            self.last_end = comment.end;
+            self.next_comment += 1;
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let loc = stmt_loc(stmt);
+        self.comments_before(loc.start);
+        self.blank_line(loc.start);
+
+        let mut child = self.child();
+        child.column = self.indent * INDENT.len();
+        child.stmt_content(stmt);
+        // Comments left inside the statement can not be placed, move them before it
+        let comments = self.comments;
+        while let Some(comment) = comments
+            .get(child.next_comment)
+            .filter(|c| c.start < loc.end)
+        {
+            self.comment_line(comment);
+            child.next_comment += 1;
+        }
+        self.begin_line();
+        self.adopt(child);
+
+        self.last_end = loc.end;
+        self.first_in_block = false;
+        self.trailing_comment(loc.end);
+        self.out.push('\n');
+    }
+
+    /// Write a block body on new lines and begin the line of its closing keyword
+    fn body(&mut self, body: &[Stmt], end: usize) {
+        self.out.push('\n');
+        self.indent += 1;
+        self.first_in_block = true;
+        let block_end = core::mem::replace(&mut self.block_end, end);
+        body.iter().for_each(|stmt| self.stmt(stmt));
+        self.comments_before(end);
+        self.block_end = block_end;
+        self.indent -= 1;
+        self.first_in_block = false;
+        self.begin_line();
+    }
+
+    fn stmt_content(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr { expr, .. } => self.expr(expr),
+            Stmt::Continue { .. } => self.out.push_str("continue"),
+            Stmt::Break { .. } => self.out.push_str("break"),
+            Stmt::Return { value, .. } => {
+                self.out.push_str("return");
+                if let Some(value) = value {
+                    self.out.push(' ');
+                    self.expr(value);
+                }
+            }
+            Stmt::Defer { body, .. } => {
+                self.out.push_str("defer ");
+                self.expr(body);
+            }
+            Stmt::Loop {
+                loc,
+                condition,
+                body,
+            } => {
+                match condition {
+                    Some(condition) => {
+                        self.out.push_str("until ");
+                        self.expr(condition);
+                        self.out.push_str(" do");
+                    }
+                    None => self.out.push_str("loop"),
+                }
+                self.body(body, loc.end);
+                self.out.push_str("end");
+            }
+            Stmt::For {
+                loc,
+                loop_variable,
+                iterator,
+                body,
+            } => {
+                self.out.push_str("for ");
+                self.expr(loop_variable);
+                self.out.push_str(" in ");
+                self.expr(iterator);
+                self.out.push_str(" do");
+                self.body(body, loc.end);
+                self.out.push_str("end");
+            }
+            Stmt::Def {
+                loc,
+                decorators,
+                variable,
+                parameters,
+                body,
+            } => {
+                for decorator in decorators {
+                    self.out.push('@');
+                    self.expr(decorator);
+                    self.out.push('\n');
+                    self.begin_line();
+                }
+                self.out.push_str("def ");
+                self.expr(variable);
+                for (name, _) in parameters {
+                    self.out.push(' ');
+                    self.out.push_str(name);
+                }
+                self.out.push_str(" =");
+                self.body(body, loc.end);
+                self.out.push_str("end");
+            }
+            Stmt::Enum { name, variants, .. } => {
+                self.out.push_str("enum ");
+                self.out.push_str(name);
+                self.indent += 1;
+                for variant in variants {
+                    self.out.push('\n');
+                    self.begin_line();
+                    self.out.push_str(&variant.name);
+                    if let Some(fields) = &variant.fields {
+                        let fields: Vec<&str> = fields.iter().map(|(name, _)| &**name).collect();
+                        self.out.push_str(&format!("({})", fields.join(", ")));
+                    }
+                }
+                self.indent -= 1;
+                self.out.push('\n');
+                self.begin_line();
+                self.out.push_str("end");
+            }
+            Stmt::Interface { name, methods, .. } => {
+                self.out.push_str("interface ");
+                self.out.push_str(name);
+                self.indent += 1;
+                for (method, _) in methods {
+                    self.out.push('\n');
+                    self.begin_line();
+                    self.out.push_str(method);
+                }
+                self.indent -= 1;
+                self.out.push('\n');
+                self.begin_line();
+                self.out.push_str("end");
+            }
+            Stmt::Macro {
+                loc,
+                name,
+                parameters,
+                body,
+                ..
+            } => {
+                let parameters: Vec<&str> = parameters.iter().map(|(name, _)| &**name).collect();
+                self.out
+                    .push_str(&format!("macro {name}({})", parameters.join(", ")));
+                self.body(body, loc.end);
+                self.out.push_str("end");
+            }
+            Stmt::Import {
+                module,
+                items,
+                direct_import_mod,
+                ..
+            } => {
+                let items = items
+                    .iter()
+                    .map(|item| match &item.alias {
+                        Some(alias) => format!("{} as {alias}", item.path.join(".")),
+                        None => item.path.join("."),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if *direct_import_mod {
+                    self.out.push_str(&format!("import {items}"));
+                } else {
+                    self.out
+                        .push_str(&format!("import {{{items}}} from {}", module.join(".")));
+                }
+            }
+            Stmt::Error => unreachable!(),
+        }
+    }
+
+    /// Write items separated by `, ` on the current line if they fit, otherwise one per line
+    fn seq<T>(&mut self, open: &str, items: &[T], close: &str, item: impl Fn(&mut Self, &T)) {
+        let mut flat = self.child();
+        flat.out.push_str(open);
+        for (i, x) in items.iter().enumerate() {
+            if i > 0 {
+                flat.out.push_str(", ");
+            }
+            item(&mut flat, x);
+        }
+        flat.out.push_str(close);
+        if items.is_empty()
+            || flat.out.contains('\n')
+            || self.column() + display_width(&flat.out) <= MAX_WIDTH
+        {
+            self.adopt(flat);
+            return;
+        }
+        self.out.push_str(open);
+        self.indent += 1;
+        for x in items {
+            self.out.push('\n');
+            self.begin_line();
+            item(self, x);
+            self.out.push(',');
+        }
+        self.indent -= 1;
+        self.out.push('\n');
+        self.begin_line();
+        self.out.push_str(close);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Block { loc, body } => {
+                self.out.push_str("begin");
+                self.body(body, loc.end);
+                self.out.push_str("end");
+            }
+            Expr::If {
+                loc,
+                conditional,
+                default,
+            } => {
+                for (i, (condition, body)) in conditional.iter().enumerate() {
+                    self.out.push_str(if i == 0 { "if " } else { "elsif " });
+                    self.expr(condition);
+                    self.out.push_str(" then");
+                    let end = match (conditional.get(i + 1), default) {
+                        (Some((next, _)), _) => next.get_loc().start,
+                        (None, Some(default)) => {
+                            default.first().map_or(loc.end, |stmt| stmt_loc(stmt).start)
+                        }
+                        (None, None) => loc.end,
+                    };
+                    self.body(body, end);
+                }
+                if let Some(default) = default {
+                    self.out.push_str("else");
+                    self.body(default, loc.end);
+                }
+                self.out.push_str("end");
+            }
+            Expr::Prefix { op, rhs, .. } => {
+                self.out.push_str(match (op, rhs.as_ref()) {
+                    (OpPrefix::Not, _) => "not ",
+                    // `--` starts a comment
+                    (OpPrefix::Neg, Expr::Prefix { .. }) => "- ",
+                    (OpPrefix::Neg, _) => "-",
+                });
+                self.expr(rhs);
+            }
+            Expr::Call {
+                lhs, parameters, ..
+            } => {
+                self.expr(lhs);
+                self.seq("(", parameters, ")", Self::expr);
+            }
+            Expr::Index { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.out.push('[');
+                self.expr(rhs);
+                self.out.push(']');
+            }
+            Expr::Infix {
+                op: OpInfix::SafeIndex,
+                lhs,
+                rhs,
+                ..
+            } => {
+                self.expr(lhs);
+                self.out.push_str("?.[");
+                self.expr(rhs);
+                self.out.push(']');
+            }
+            Expr::Infix { op, lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.out.push_str(match op {
+                    OpInfix::Assign => " = ",
+                    OpInfix::Pipe => " |> ",
+                    OpInfix::Range => "..",
+                    OpInfix::Or => " or ",
+                    OpInfix::And => " and ",
+                    OpInfix::Eq => " == ",
+                    OpInfix::Ne => " <> ",
+                    OpInfix::Le => " <= ",
+                    OpInfix::Lt => " < ",
+                    OpInfix::Ge => " >= ",
+                    OpInfix::Gt => " > ",
+                    OpInfix::Plus => " + ",
+                    OpInfix::Minus => " - ",
+                    OpInfix::Mul => " * ",
+                    OpInfix::Div => " / ",
+                    OpInfix::DivFloor => " // ",
+                    OpInfix::Rem => " % ",
+                    OpInfix::Exp => " ** ",
+                    OpInfix::Comma => ", ",
+                    OpInfix::Member => ".",
+                    OpInfix::SafeMember => "?.",
+                    OpInfix::SafeIndex => unreachable!(),
+                    OpInfix::Coalesce => " ?? ",
+                    OpInfix::DoubleColon => "::",
+                    OpInfix::LArrow => " <- ",
+                    OpInfix::Is => " is ",
+                    OpInfix::In => " in ",
+                    OpInfix::NotIn => " not in ",
+                });
+                self.expr(rhs);
+            }
+            Expr::OpenRange { lhs, .. } => {
+                self.expr(lhs);
+                self.out.push_str("..");
+            }
+            Expr::Spread { value, .. } => {
+                self.expr(value);
+                self.out.push_str("...");
+            }
+            Expr::Fn { body, .. } if expr.is_placeholder_fn() => self.expr(body),
+            Expr::Fn {
+                parameters, body, ..
+            } => {
+                self.out.push_str("fn");
+                for (name, _) in parameters {
+                    self.out.push(' ');
+                    self.out.push_str(name);
+                }
+                self.out.push_str(" = ");
+                self.expr(body);
+            }
+            Expr::Id { name, .. } => self.out.push_str(name),
+            Expr::Parentheses { content, .. } => {
+                self.out.push('(');
+                self.expr(content);
+                self.out.push(')');
+            }
+            Expr::Const { loc, value } => match value {
+                Const::Unit => self.out.push_str("()"),
+                Const::Int(_) | Const::Float(_) | Const::Str(_) => {
+                    self.out.push_str(&self.source[loc.start..loc.end])
+                }
+                Const::Bool(b) => self.out.push_str(if *b { "true" } else { "false" }),
+                Const::List(items) => self.seq("[", items, "]", Self::expr),
+                Const::Table(items) => self.seq("{", items, "}", |f, (key, value, _)| {
+                    if let Some(key) = key {
+                        f.out.push_str(key);
+                        f.out.push_str(" = ");
+                    }
+                    f.expr(value);
+                }),
+            },
+            Expr::Error => unreachable!(),
+        }
+    }
+}
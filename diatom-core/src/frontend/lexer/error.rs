@@ -1,6 +1,14 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+};
 use codespan_reporting::diagnostic::Label;
 
-use crate::file_manager::{Diagnostic, Loc};
+use crate::{
+    file_manager::{Diagnostic, Loc, Warning},
+    gc::format_float,
+};
 
 /// Error Code used by `Lexer`
 #[cfg_attr(test, derive(Debug))]
@@ -12,11 +20,32 @@ pub enum ErrorCode {
     /// E0003 Parse float error
     ParseFloatError(String),
     /// E0004 Invalid Escape Sequence
-    InvalidEscapeSequence,
+    ///
+    /// Parameters:
+    /// - 1 The escape sequence, including `\`
+    /// - 2 Where the escape sequence is
+    InvalidEscapeSequence(String, Loc),
     /// E0005 Open quotation
     OpenQuote,
     /// E0006 Invalid operator
     InvalidOp(char),
+    /// E0007 Character not allowed in identifiers
+    ///
+    /// Parameters:
+    /// - 1 The character
+    /// - 2 Where the character is
+    InvalidIdentChar(char, Loc),
+    /// E0008 Digit separator `_` is not between two digits
+    ///
+    /// Parameters:
+    /// - 1 Where the separators are
+    MisplacedSeparator(Loc),
+    /// E0009 No digit in a part of number literal that requires one
+    ///
+    /// Parameters:
+    /// - 1 Which part, e.g. "in exponent"
+    /// - 2 Where the part is
+    MissingDigits(String, Loc),
 }
 
 pub fn to_diagnostic(error: ErrorCode, loc: Loc) -> (Diagnostic, bool) {
@@ -34,10 +63,20 @@ pub fn to_diagnostic(error: ErrorCode, loc: Loc) -> (Diagnostic, bool) {
             .with_code("E0003")
             .with_message(s)
             .with_labels(vec![Label::primary(loc.fid, loc)]),
-        ErrorCode::InvalidEscapeSequence => Diagnostic::error()
+        ErrorCode::InvalidEscapeSequence(escape, at) => Diagnostic::error()
             .with_code("E0004")
-            .with_message("Invalid escape sequence in string literal")
-            .with_labels(vec![Label::primary(loc.fid, loc)]),
+            .with_message(format!(
+                "Invalid escape sequence `{escape}` in string literal"
+            ))
+            .with_labels(vec![
+                Label::primary(at.fid, at),
+                Label::secondary(loc.fid, loc),
+            ])
+            .with_notes(vec![
+                "Supported escapes are `\\\\`, `\\\"`, `\\'`, `\\0`, `\\a`, `\\b`, `\\e`, `\\f`, `\\n`, `\\r`, \
+                 `\\t`, `\\v`, `\\xHH`, `\\uHHHH` and `\\UHHHHHHHH`, where `H` is a hex digit"
+                    .to_string(),
+            ]),
         ErrorCode::OpenQuote => Diagnostic::error()
             .with_code("E0005")
             .with_message("String literal is not terminated")
@@ -46,6 +85,92 @@ pub fn to_diagnostic(error: ErrorCode, loc: Loc) -> (Diagnostic, bool) {
             .with_code("E0006")
             .with_message(format!("Invalid operator `{c}`"))
             .with_labels(vec![Label::primary(loc.fid, loc)]),
+        ErrorCode::InvalidIdentChar(c, at) => Diagnostic::error()
+            .with_code("E0007")
+            .with_message(format!(
+                "Invalid character `{c}` (U+{:04X}) in identifier",
+                c as u32
+            ))
+            .with_labels(vec![
+                Label::primary(at.fid, at),
+                Label::secondary(loc.fid, loc),
+            ])
+            .with_notes(vec![
+                "Identifiers start with a letter or `_`, followed by letters, marks, digits or `_`"
+                    .to_string(),
+            ]),
+        ErrorCode::MisplacedSeparator(at) => Diagnostic::error()
+            .with_code("E0008")
+            .with_message("Digit separator `_` must be between two digits")
+            .with_labels(vec![
+                Label::primary(at.fid, at),
+                Label::secondary(loc.fid, loc),
+            ])
+            .with_notes(vec![
+                "Separate digits with single underscores, e.g. `1_000_000` or `0xff_ff`"
+                    .to_string(),
+            ]),
+        ErrorCode::MissingDigits(part, at) => Diagnostic::error()
+            .with_code("E0009")
+            .with_message(format!("Expected digits {part} of number literal"))
+            .with_labels(vec![
+                Label::primary(at.fid, at),
+                Label::secondary(loc.fid, loc),
+            ]),
     };
     (diag, eof)
 }
+
+/// Warning code used by `Lexer`
+///
+/// W1000 - W1499
+pub enum WarningCode {
+    /// W1000 Float literal is too large or too small to be represented
+    FloatOutOfRange(f64),
+    /// W1001 Identifier looks the same as another one
+    ///
+    /// Parameters:
+    /// - 1 Identifier
+    /// - 2 The other identifier
+    /// - 3 Where the other identifier first appears
+    ConfusableIdent(String, String, Loc),
+    /// W1002 Alias of a keyword is used
+    ///
+    /// Parameters:
+    /// - 1 Alias
+    /// - 2 Keyword
+    KeywordAlias(String, String),
+}
+
+pub fn to_warning(warning: WarningCode, loc: Loc) -> Warning {
+    match warning {
+        WarningCode::FloatOutOfRange(f) => Warning {
+            name: "float-out-of-range",
+            diagnostic: Diagnostic::warning()
+                .with_code("W1000")
+                .with_message("Float literal is out of range")
+                .with_labels(vec![Label::primary(loc.fid, loc)
+                    .with_message(format!("This evaluates to `{}`", format_float(f)))]),
+        },
+        WarningCode::ConfusableIdent(name, other, other_loc) => Warning {
+            name: "confusable-identifier",
+            diagnostic: Diagnostic::warning()
+                .with_code("W1001")
+                .with_message(format!("Identifier `{name}` is confusable with `{other}`"))
+                .with_labels(vec![
+                    Label::primary(loc.fid, loc),
+                    Label::secondary(other_loc.fid, other_loc)
+                        .with_message(format!("`{other}` is used here")),
+                ]),
+        },
+        WarningCode::KeywordAlias(alias, keyword) => Warning {
+            name: "keyword-alias",
+            diagnostic: Diagnostic::warning()
+                .with_code("W1002")
+                .with_message(format!("`{alias}` is an alias of keyword `{keyword}`"))
+                .with_labels(vec![
+                    Label::primary(loc.fid, loc).with_message(format!("Use `{keyword}` instead"))
+                ]),
+        },
+    }
+}
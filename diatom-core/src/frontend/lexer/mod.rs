@@ -1,19 +1,36 @@
 mod error;
 mod token;
 
-use lazy_static::lazy_static;
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::ops::Range;
+
+use crate::compat::{AHashMap, AHashSet};
+use once_cell::race::OnceBox;
 use regex::Regex;
 pub use token::{Keyword, Operator, Token};
+use unicode_ident::{is_xid_continue, is_xid_start};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+use unicode_security::skeleton;
 
 use crate::file_manager::{FileManager, Loc};
 
-use self::error::{to_diagnostic, ErrorCode};
+use self::error::{to_diagnostic, to_warning, ErrorCode, WarningCode};
 
-use super::util::{FileIterator, TokenIterator};
+use super::util::FileIterator;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct TokenStream {
     tokens: Vec<(Token, Loc)>,
+    /// Comments are not tokens but are kept for syntax highlighting
+    comments: Vec<Loc>,
+    /// Input that can not be lexed
+    invalid: Vec<Loc>,
 }
 
 impl TokenStream {
@@ -21,8 +38,65 @@ impl TokenStream {
         self.tokens.push(token);
     }
 
-    pub fn iter(&self) -> TokenIterator {
-        TokenIterator::new(&self.tokens)
+    pub fn tokens(&self) -> &[(Token, Loc)] {
+        &self.tokens
+    }
+
+    pub fn comments(&self) -> &[Loc] {
+        &self.comments
+    }
+
+    pub fn invalid(&self) -> &[Loc] {
+        &self.invalid
+    }
+}
+
+/// Whether the next token follows a member access
+fn after_member(tokens: &[(Token, Loc)]) -> bool {
+    matches!(
+        tokens.last(),
+        Some((Token::Op(Operator::Member | Operator::SafeMember), _))
+    )
+}
+
+/// Extra names recognized as keywords, e.g. `function` for `def`
+///
+/// Uses of an alias are reported by warning `W1002`, which suggests the keyword instead.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeywordAliases {
+    aliases: BTreeMap<String, String>,
+}
+
+impl KeywordAliases {
+    /// Recognize `alias` as `keyword`
+    ///
+    /// Return `Err` if `keyword` is not a keyword, or `alias` is a keyword or not an
+    /// identifier.
+    pub fn alias(mut self, alias: impl Into<String>, keyword: &str) -> Result<Self, String> {
+        let alias: String = alias.into().nfc().collect();
+        if Lexer::keyword(keyword).is_none() {
+            return Err(format!("`{keyword}` is not a keyword"));
+        }
+        if Lexer::keyword(&alias).is_some() {
+            return Err(format!("Keyword `{alias}` can not be an alias"));
+        }
+        let mut chars = alias.chars();
+        let is_ident =
+            chars.next().is_some_and(|c| c == '_' || is_xid_start(c)) && chars.all(is_xid_continue);
+        if !is_ident {
+            return Err(format!("Alias `{alias}` is not an identifier"));
+        }
+        self.aliases.insert(alias, keyword.to_string());
+        Ok(self)
+    }
+
+    /// Keyword recognized for `alias`
+    pub fn get(&self, alias: &str) -> Option<&str> {
+        self.aliases.get(alias).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
     }
 }
 
@@ -34,17 +108,181 @@ impl TokenStream {
 pub struct Lexer;
 
 impl Lexer {
+    /// Whether a comment is a doc comment, which starts with `---` but not `----`
+    ///
+    /// Doc comments are comments as well, they document the item after them, see `doc`.
+    pub fn is_doc_comment(comment: &str) -> bool {
+        comment
+            .strip_prefix("---")
+            .is_some_and(|rest| !rest.starts_with('-'))
+    }
+
     pub fn lex(file_manager: &mut FileManager, fid: usize) -> TokenStream {
+        Self::lex_with_aliases(file_manager, fid, &KeywordAliases::default())
+    }
+
+    /// Lex a file, recognizing `aliases` as keywords
+    pub fn lex_with_aliases(
+        file_manager: &mut FileManager,
+        fid: usize,
+        aliases: &KeywordAliases,
+    ) -> TokenStream {
         let mut token_stream = TokenStream::default();
+        Self::lex_from(file_manager, fid, 0, aliases, &mut token_stream, |_, _| {
+            false
+        });
+        Self::check_tokens(file_manager, fid, &token_stream, aliases);
+        token_stream
+    }
+
+    /// Lex from byte offset `start` until the first token, `previous` is the token before
+    ///
+    /// Comments right after the token are lexed as well. Return tokens lexed and the offset
+    /// where lexing stopped, which is the end of file if there is no more token.
+    pub fn lex_next(
+        file_manager: &mut FileManager,
+        fid: usize,
+        start: usize,
+        previous: Option<(Token, Loc)>,
+    ) -> (TokenStream, usize) {
+        let mut stream = TokenStream::default();
+        stream.tokens.extend(previous);
+        let seeded = stream.tokens.len();
+        let mut stop = None;
+        Self::lex_from(
+            file_manager,
+            fid,
+            start,
+            &KeywordAliases::default(),
+            &mut stream,
+            |offset, tokens| {
+                if tokens.len() > seeded {
+                    stop = Some(offset);
+                }
+                stop.is_some()
+            },
+        );
+        stream.tokens.drain(..seeded);
+        let stop = stop.unwrap_or_else(|| file_manager.get_file(fid).len());
+        (stream, stop)
+    }
+
+    /// Lex a file again after `edit` of its old content is replaced by `new_len` bytes
+    ///
+    /// Lexing starts from the tokens near the edit and stops once a token starts where a
+    /// token of `old` starts, after which tokens of `old` are reused. Everything between the
+    /// first and the last invalid token of `old` is always lexed again so that lexer errors are
+    /// reported again.
+    ///
+    /// # Return
+    /// * New token stream
+    /// * Range of tokens of `old` replaced
+    /// * Range of tokens in the new stream replacing them
+    pub fn relex(
+        file_manager: &mut FileManager,
+        fid: usize,
+        old: &TokenStream,
+        edit: Range<usize>,
+        new_len: usize,
+        aliases: &KeywordAliases,
+    ) -> (TokenStream, Range<usize>, Range<usize>) {
+        let delta = new_len as isize - edit.len() as isize;
+        let spans = || {
+            old.tokens
+                .iter()
+                .map(|(_, loc)| loc)
+                .chain(&old.comments)
+                .chain(&old.invalid)
+        };
+        let start = spans()
+            // Lexer looks ahead at most 2 characters after a token
+            .filter(|loc| loc.start < edit.start && loc.end + 2 >= edit.start)
+            .map(|loc| loc.start)
+            .chain(old.invalid.first().map(|loc| loc.start))
+            .fold(edit.start, usize::min);
+        let barrier = old
+            .invalid
+            .last()
+            .map(|loc| loc.end)
+            .map_or(edit.end, |end| end.max(edit.end));
+
+        let mut stream = TokenStream::default();
+        let keep = |loc: &Loc| loc.start < start;
+        stream.tokens = old
+            .tokens
+            .iter()
+            .take_while(|(_, loc)| keep(loc))
+            .cloned()
+            .collect();
+        stream.comments = old
+            .comments
+            .iter()
+            .filter(|loc| keep(loc))
+            .cloned()
+            .collect();
+        let first = stream.tokens.len();
+
+        let mut resync = None;
+        Self::lex_from(
+            file_manager,
+            fid,
+            start,
+            aliases,
+            &mut stream,
+            |offset, tokens| {
+                let old_offset = offset as isize - delta;
+                if old_offset < barrier as isize {
+                    return false;
+                }
+                // Numbers are lexed differently after `.`
+                resync = old
+                    .tokens
+                    .binary_search_by_key(&(old_offset as usize), |(_, loc)| loc.start)
+                    .ok()
+                    .filter(|i| after_member(&old.tokens[..*i]) == after_member(tokens));
+                resync.is_some()
+            },
+        );
+        let last = stream.tokens.len();
+        let resync = resync.unwrap_or(old.tokens.len());
+        if let Some((_, resync_loc)) = old.tokens.get(resync) {
+            let shift = |loc: &Loc| {
+                let mut loc = loc.clone();
+                loc.shift(delta);
+                loc
+            };
+            stream.tokens.extend(
+                old.tokens[resync..]
+                    .iter()
+                    .map(|(token, loc)| (token.clone(), shift(loc))),
+            );
+            stream.comments.extend(
+                old.comments
+                    .iter()
+                    .filter(|loc| loc.start > resync_loc.start)
+                    .map(shift),
+            );
+        }
+        Self::check_tokens(file_manager, fid, &stream, aliases);
+        (stream, first..resync, first..last)
+    }
+
+    /// Lex from byte offset `start` until end of file or `stop` returns true for the offset
+    /// where a token starts and tokens lexed so far
+    /// where a token starts
+    fn lex_from(
+        file_manager: &mut FileManager,
+        fid: usize,
+        start: usize,
+        aliases: &KeywordAliases,
+        token_stream: &mut TokenStream,
+        mut stop: impl FnMut(usize, &[(Token, Loc)]) -> bool,
+    ) {
         let file = file_manager.get_file(fid);
-        let mut iter = FileIterator::new(file.as_ref(), fid);
+        let mut iter = FileIterator::at(file.as_ref(), fid, start);
         // Ignore shebang (#!...) at the beginning of the file
-        if let (Some('#'), Some('!')) = iter.peek2() {
-            loop {
-                if let Some('\n') = iter.next() {
-                    break;
-                }
-            }
+        if let (0, Some('#'), Some('!')) = (start, iter.peek2().0, iter.peek2().1) {
+            while !matches!(iter.next(), Some('\n') | None) {}
         }
         // Start consuming characters
         loop {
@@ -52,6 +290,7 @@ impl Lexer {
             match iter.peek2() {
                 (Some('-'), Some('-')) => {
                     // Ignore comment
+                    let start = iter.offset();
                     iter.next();
                     iter.next();
                     loop {
@@ -59,7 +298,6 @@ impl Lexer {
                         match c {
                             Some(c) => {
                                 if c == '\n' {
-                                    iter.next();
                                     break;
                                 };
                             }
@@ -67,11 +305,24 @@ impl Lexer {
                         }
                         iter.next();
                     }
+                    token_stream.comments.push(Loc {
+                        start,
+                        end: iter.offset(),
+                        fid,
+                    });
+                    iter.next();
                     continue;
                 }
+                (Some(c), _) if !c.is_whitespace() && stop(iter.offset(), &token_stream.tokens) => {
+                    break
+                }
                 (Some(c), next) => {
                     let result = match (c, next) {
-                        (c, _) if c.is_ascii_digit() => Some(Self::consume_num(&mut iter)),
+                        (c, _) if c.is_ascii_digit() => {
+                            // `t.0.1` is a member of a member rather than a float
+                            let int_only = after_member(&token_stream.tokens);
+                            Some(Self::consume_num(&mut iter, int_only, file_manager))
+                        }
                         ('"' | '\'', _) => Some(Self::consume_string(&mut iter)),
                         (c, _) if c.is_whitespace() => {
                             iter.next();
@@ -82,17 +333,18 @@ impl Lexer {
                                 && !c.is_ascii_digit()
                                 && !c.is_whitespace() =>
                         {
-                            Some(Self::consume_id_or_key(&mut iter))
+                            Some(Self::consume_id_or_key(&mut iter, aliases, file_manager))
                         }
                         (c, _) if c.is_ascii_punctuation() && c != '_' => {
                             Some(Self::consume_op(&mut iter))
                         }
-                        _ => Some(Self::consume_id_or_key(&mut iter)),
+                        _ => Some(Self::consume_id_or_key(&mut iter, aliases, file_manager)),
                     };
                     if let Some(result) = result {
                         match result {
                             Ok(x) => token_stream.push(x),
                             Err((error, loc)) => {
+                                token_stream.invalid.push(loc.clone());
                                 let diag = to_diagnostic(error, loc);
                                 file_manager.add_diagnostic(diag.0, diag.1);
                             }
@@ -104,11 +356,89 @@ impl Lexer {
                 }
             }
         }
-        token_stream
+    }
+
+    /// Report warnings of lexed tokens
+    fn check_tokens(
+        file_manager: &mut FileManager,
+        fid: usize,
+        token_stream: &TokenStream,
+        aliases: &KeywordAliases,
+    ) {
+        let file = file_manager.get_file(fid);
+        for (token, loc) in &token_stream.tokens {
+            match token {
+                Token::Float(f) if Self::out_of_range(*f, &file[loc.start..loc.end]) => {
+                    file_manager
+                        .add_warning(to_warning(WarningCode::FloatOutOfRange(*f), loc.clone()));
+                }
+                Token::Key(_) | Token::Op(_) if !aliases.is_empty() => {
+                    let name: String = file[loc.start..loc.end].nfc().collect();
+                    if let Some(keyword) = aliases.get(&name) {
+                        file_manager.add_warning(to_warning(
+                            WarningCode::KeywordAlias(name.clone(), keyword.to_string()),
+                            loc.clone(),
+                        ));
+                    }
+                }
+                _ => (),
+            }
+        }
+        Self::check_confusables(file_manager, token_stream);
+    }
+
+    /// Warn about different identifiers that look the same
+    ///
+    /// Only identifiers with non-ASCII characters are checked, since `l` and `I` are
+    /// intentionally different names in most code.
+    fn check_confusables(file_manager: &mut FileManager, token_stream: &TokenStream) {
+        let mut skeletons: AHashMap<String, (&str, &Loc)> = AHashMap::default();
+        let mut warned = AHashSet::default();
+        for (token, loc) in &token_stream.tokens {
+            let Token::Id(name) = token else {
+                continue;
+            };
+            let skeleton: String = skeleton(name).collect();
+            match skeletons.get(&skeleton) {
+                Some((other, other_loc)) => {
+                    if *other != name.as_str()
+                        && !(name.is_ascii() && other.is_ascii())
+                        && warned.insert(name.as_str())
+                    {
+                        file_manager.add_warning(to_warning(
+                            WarningCode::ConfusableIdent(
+                                name.to_string(),
+                                other.to_string(),
+                                (*other_loc).clone(),
+                            ),
+                            loc.clone(),
+                        ));
+                    }
+                }
+                None => {
+                    skeletons.insert(skeleton, (name, loc));
+                }
+            }
+        }
+    }
+
+    /// Whether float `literal` overflows to infinity or underflows to zero
+    fn out_of_range(f: f64, literal: &str) -> bool {
+        let mantissa = literal.split(['e', 'E']).next().unwrap_or_default();
+        f.is_infinite() || (f == 0.0 && mantissa.chars().any(|c| matches!(c, '1'..='9')))
     }
 
     /// Consume numeric types, aka int & float.
-    fn consume_num(iter: &mut FileIterator) -> Result<(Token, Loc), (ErrorCode, Loc)> {
+    ///
+    /// Digits may be separated by single underscores. A decimal point must be followed by a
+    /// digit, so `1.method()` and `1..5` are not floats. No fraction or exponent is consumed if
+    /// `int_only`. A misplaced separator is reported to `file_manager` while the number is still
+    /// consumed, thus it is the only error reported.
+    fn consume_num(
+        iter: &mut FileIterator,
+        int_only: bool,
+        file_manager: &mut FileManager,
+    ) -> Result<(Token, Loc), (ErrorCode, Loc)> {
         fn consume_int(s: &str) -> Result<i64, ErrorCode> {
             let mut i: i64 = 0;
             if s.starts_with("0x") || s.starts_with("0X") {
@@ -244,22 +574,15 @@ impl Lexer {
         }
         let start = iter.offset();
 
-        lazy_static!(
-            static ref RE: Regex =
-            Regex::new("^(([0][Xx][_0-9a-fA-F]+)|([0][Bb][_0-1]+)|([0][Oo][_0-7]+)|([0-9][_0-9]*(\\.[_0-9]+){0,1}([Ee][\\+\\-]{0,1}[0-9_]*){0, 1}))")
-                .unwrap();
-            static ref RE_INT: Regex = Regex::new("^(([0][Xx][_0-9a-fA-F]+)|([0][Bb][_0-1]+)|([0][Oo][_0-7]+)|([0-9][_0-9]*))$").unwrap();
-        );
+        static RE: OnceBox<Regex> = OnceBox::new();
+        static RE_INT: OnceBox<Regex> = OnceBox::new();
+        let re = RE.get_or_init(|| {
+            Box::new(Regex::new("^(([0][Xx][_0-9a-fA-F]*)|([0][Bb][_0-1]*)|([0][Oo][_0-7]*)|([0-9][_0-9]*(\\.[0-9][_0-9]*){0,1}([Ee][\\+\\-]{0,1}[0-9_]*){0, 1}))")
+                .unwrap())
+        });
 
         let s = iter.as_str();
-        let num = if let Some(m) = RE.find(s) {
-            let s = s[..m.end()].to_string();
-            let s = s.replace('_', "");
-            (0..m.end()).for_each(|_| {
-                iter.next();
-            });
-            s
-        } else {
+        let Some(m) = re.find(s) else {
             let mut error_s = String::new();
             loop {
                 match iter.peek() {
@@ -278,14 +601,82 @@ impl Lexer {
                 },
             ));
         };
+        let literal = &s[..m.end()];
+        let radix = match literal.get(..2) {
+            Some("0x" | "0X") => 16,
+            Some("0b" | "0B") => 2,
+            Some("0o" | "0O") => 8,
+            _ => 10,
+        };
+        let literal = if int_only && radix == 10 {
+            &literal[..literal.find(['.', 'e', 'E']).unwrap_or(literal.len())]
+        } else {
+            literal
+        };
+        literal.chars().for_each(|_| {
+            iter.next();
+        });
         let end = iter.offset();
         let loc = Loc {
             start,
             end,
             fid: iter.fid(),
         };
+        let at = |range: Range<usize>| Loc {
+            start: start + range.start,
+            end: start + range.end,
+            fid: loc.fid,
+        };
+
+        if radix != 10 && literal.len() == 2 {
+            return Err((
+                ErrorCode::MissingDigits(format!("after `{literal}`"), at(0..2)),
+                loc,
+            ));
+        }
+        if let Some(exponent) = literal.find(['e', 'E']).filter(|_| radix == 10) {
+            if !literal[exponent..].contains(|c: char| c.is_ascii_digit()) {
+                return Err((
+                    ErrorCode::MissingDigits(
+                        "in exponent".to_string(),
+                        at(exponent..literal.len()),
+                    ),
+                    loc,
+                ));
+            }
+        }
+        // Underscores must be between 2 digits
+        let bytes = literal.as_bytes();
+        let is_digit = |i: Option<usize>| {
+            i.and_then(|i| bytes.get(i))
+                .is_some_and(|c| (*c as char).is_digit(radix))
+        };
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] != b'_' {
+                i += 1;
+                continue;
+            }
+            let run = i..i + bytes[i..].iter().take_while(|c| **c == b'_').count();
+            if run.len() > 1 || !is_digit(i.checked_sub(1)) || !is_digit(Some(run.end)) {
+                let (diag, is_eof) =
+                    to_diagnostic(ErrorCode::MisplacedSeparator(at(run)), loc.clone());
+                file_manager.add_diagnostic(diag, is_eof);
+                break;
+            }
+            i = run.end;
+        }
+        let num = literal.replace('_', "");
 
-        let float_flag = !RE_INT.is_match(&num);
+        let re_int = RE_INT.get_or_init(|| {
+            Box::new(
+                Regex::new(
+                    "^(([0][Xx][_0-9a-fA-F]+)|([0][Bb][_0-1]+)|([0][Oo][_0-7]+)|([0-9][_0-9]*))$",
+                )
+                .unwrap(),
+            )
+        });
+        let float_flag = !re_int.is_match(&num);
 
         if float_flag {
             let float = num.parse::<f64>();
@@ -306,9 +697,17 @@ impl Lexer {
     }
 
     /// Consume keyword or Identifier
-    fn consume_id_or_key(iter: &mut FileIterator) -> Result<(Token, Loc), (ErrorCode, Loc)> {
+    ///
+    /// Identifiers follow UAX#31, that is a letter or `_` followed by letters, marks, digits or
+    /// `_`, and are normalized to NFC.
+    fn consume_id_or_key(
+        iter: &mut FileIterator,
+        aliases: &KeywordAliases,
+        file_manager: &mut FileManager,
+    ) -> Result<(Token, Loc), (ErrorCode, Loc)> {
         let mut name = String::new();
         let start = iter.offset();
+        let mut invalid = None;
         if let Some('$') = iter.peek() {
             name.push('$');
             iter.next();
@@ -319,7 +718,26 @@ impl Lexer {
                 Some(' ' | '\r' | '\n' | '\t' | '!'..='/' | ':'..='@' | '['..='`' | '{'..='~') => {
                     break
                 }
-                Some(c) => name.push(c),
+                Some(c) if c.is_whitespace() => break,
+                Some(c) => {
+                    let valid = if name.is_empty() || name == "$" {
+                        is_xid_start(c)
+                    } else {
+                        is_xid_continue(c)
+                    };
+                    if !valid && invalid.is_none() {
+                        let offset = iter.offset();
+                        invalid = Some((
+                            c,
+                            Loc {
+                                start: offset,
+                                end: offset + c.len_utf8(),
+                                fid: iter.fid(),
+                            },
+                        ));
+                    }
+                    name.push(c)
+                }
                 None => break,
             }
             iter.next();
@@ -329,37 +747,60 @@ impl Lexer {
             end: iter.offset(),
             fid: iter.fid(),
         };
-        match name.as_str() {
-            "and" => Ok((Token::Op(Operator::And), loc)),
-            "or" => Ok((Token::Op(Operator::Or), loc)),
-            "not" => Ok((Token::Op(Operator::Not), loc)),
-            "true" => Ok((Token::Key(Keyword::True), loc)),
-            "false" => Ok((Token::Key(Keyword::False), loc)),
-            "do" => Ok((Token::Key(Keyword::Do), loc)),
-            "until" => Ok((Token::Key(Keyword::Until), loc)),
-            "end" => Ok((Token::Key(Keyword::End), loc)),
-            "if" => Ok((Token::Key(Keyword::If), loc)),
-            "then" => Ok((Token::Key(Keyword::Then), loc)),
-            "else" => Ok((Token::Key(Keyword::Else), loc)),
-            "elsif" => Ok((Token::Key(Keyword::Elsif), loc)),
-            "in" => Ok((Token::Key(Keyword::In), loc)),
-            "for" => Ok((Token::Key(Keyword::For), loc)),
-            "return" => Ok((Token::Key(Keyword::Return), loc)),
-            "continue" => Ok((Token::Key(Keyword::Continue), loc)),
-            "break" => Ok((Token::Key(Keyword::Break), loc)),
-            "loop" => Ok((Token::Key(Keyword::Loop), loc)),
-            "def" => Ok((Token::Key(Keyword::Def), loc)),
-            "fn" => Ok((Token::Key(Keyword::Fn), loc)),
-            "begin" => Ok((Token::Key(Keyword::Begin), loc)),
-            "import" => Ok((Token::Key(Keyword::Import), loc)),
-            "from" => Ok((Token::Key(Keyword::From), loc)),
-            "as" => Ok((Token::Key(Keyword::As), loc)),
-            "is" => Ok((Token::Op(Operator::Is), loc)),
-            _ => Ok((Token::Id(name), loc)),
+        if let Some((c, at)) = invalid {
+            return Err((ErrorCode::InvalidIdentChar(c, at), loc));
         }
+        let name = if is_nfc(&name) {
+            name
+        } else {
+            name.nfc().collect()
+        };
+        let token = Self::keyword(&name)
+            .or_else(|| aliases.get(&name).and_then(Self::keyword))
+            .unwrap_or_else(|| Token::Id(file_manager.intern(&name)));
+        Ok((token, loc))
+    }
+
+    /// Token of keyword or word operator `name`
+    fn keyword(name: &str) -> Option<Token> {
+        let token = match name {
+            "and" => Token::Op(Operator::And),
+            "or" => Token::Op(Operator::Or),
+            "not" => Token::Op(Operator::Not),
+            "true" => Token::Key(Keyword::True),
+            "false" => Token::Key(Keyword::False),
+            "do" => Token::Key(Keyword::Do),
+            "until" => Token::Key(Keyword::Until),
+            "end" => Token::Key(Keyword::End),
+            "if" => Token::Key(Keyword::If),
+            "then" => Token::Key(Keyword::Then),
+            "else" => Token::Key(Keyword::Else),
+            "elsif" => Token::Key(Keyword::Elsif),
+            "in" => Token::Key(Keyword::In),
+            "for" => Token::Key(Keyword::For),
+            "return" => Token::Key(Keyword::Return),
+            "defer" => Token::Key(Keyword::Defer),
+            "continue" => Token::Key(Keyword::Continue),
+            "break" => Token::Key(Keyword::Break),
+            "loop" => Token::Key(Keyword::Loop),
+            "def" => Token::Key(Keyword::Def),
+            "fn" => Token::Key(Keyword::Fn),
+            "begin" => Token::Key(Keyword::Begin),
+            "import" => Token::Key(Keyword::Import),
+            "from" => Token::Key(Keyword::From),
+            "as" => Token::Key(Keyword::As),
+            "is" => Token::Op(Operator::Is),
+            _ => return None,
+        };
+        Some(token)
     }
 
     /// Consume string token
+    ///
+    /// Supported escapes are `\\`, `\"`, `\'`, `\0` (null), `\a` (bell), `\b` (backspace),
+    /// `\e` (escape), `\f` (form feed), `\n`, `\r`, `\t`, `\v` (vertical tab), and `\xHH`,
+    /// `\uHHHH` and `\UHHHHHHHH` for code points in hex. Since strings are UTF-8 text, `\xHH`
+    /// is code point `U+00HH` rather than a raw byte.
     fn consume_string(iter: &mut FileIterator) -> Result<(Token, Loc), (ErrorCode, Loc)> {
         fn consume_escape(iter: &mut FileIterator) -> Result<char, ()> {
             /// Consume a hex escape sequence with n character exactly
@@ -380,18 +821,23 @@ impl Lexer {
                     None => Err(()),
                 }
             }
-            let c = iter.next();
-            let c = match c {
-                Some(c) => c,
-                None => unreachable!(),
+            // Backslash at end of file, reported as an unterminated string
+            let Some(c) = iter.next() else {
+                return Err(());
             };
             match c {
                 '\\' => Ok('\\'),
-                't' => Ok('\t'),
-                'n' => Ok('\n'),
-                'r' => Ok('\r'),
                 '\"' => Ok('\"'),
                 '\'' => Ok('\''),
+                '0' => Ok('\0'),
+                'a' => Ok('\x07'),
+                'b' => Ok('\x08'),
+                'e' => Ok('\x1b'),
+                'f' => Ok('\x0c'),
+                'n' => Ok('\n'),
+                'r' => Ok('\r'),
+                't' => Ok('\t'),
+                'v' => Ok('\x0b'),
                 'x' => consume_hex_escape(iter, 2),
                 'u' => consume_hex_escape(iter, 4),
                 'U' => consume_hex_escape(iter, 8),
@@ -401,7 +847,7 @@ impl Lexer {
         let start = iter.offset();
         let start_char = iter.next();
         let mut result = String::new();
-        let mut invalid = false;
+        let mut invalid = None;
 
         let is_single_quote = match start_char {
             Some('"') => false,
@@ -409,37 +855,26 @@ impl Lexer {
             _ => unreachable!(),
         };
         loop {
+            let escape_start = iter.offset();
+            let rest = iter.as_str();
             let c = iter.next();
             match c {
                 Some(c) => match c {
                     '\\' => match consume_escape(iter) {
                         Ok(c) => result.push(c),
-                        Err(()) => invalid = true,
-                    },
-                    '\'' if is_single_quote => {
-                        let loc = Loc {
-                            start,
-                            end: iter.offset(),
-                            fid: iter.fid(),
-                        };
-                        if !invalid {
-                            return Ok((Token::Str(result), loc));
-                        } else {
-                            return Err((ErrorCode::InvalidEscapeSequence, loc));
-                        }
-                    }
-                    '"' if !is_single_quote => {
-                        let loc = Loc {
-                            start,
-                            end: iter.offset(),
-                            fid: iter.fid(),
-                        };
-                        if !invalid {
-                            return Ok((Token::Str(result), loc));
-                        } else {
-                            return Err((ErrorCode::InvalidEscapeSequence, loc));
+                        Err(()) if invalid.is_none() => {
+                            let escape = rest[..iter.offset() - escape_start].to_string();
+                            let at = Loc {
+                                start: escape_start,
+                                end: iter.offset(),
+                                fid: iter.fid(),
+                            };
+                            invalid = Some((escape, at));
                         }
-                    }
+                        Err(()) => (),
+                    },
+                    '\'' if is_single_quote => break,
+                    '"' if !is_single_quote => break,
                     c => result.push(c),
                 },
                 None => {
@@ -454,6 +889,15 @@ impl Lexer {
                 }
             }
         }
+        let loc = Loc {
+            start,
+            end: iter.offset(),
+            fid: iter.fid(),
+        };
+        match invalid {
+            None => Ok((Token::Str(result), loc)),
+            Some((escape, at)) => Err((ErrorCode::InvalidEscapeSequence(escape, at), loc)),
+        }
     }
 
     /// Consume operators
@@ -473,7 +917,16 @@ impl Lexer {
                 Ok((Token::Op(Operator::DivFloor), consume_next_2_char(iter)))
             }
             (Some('*'), Some('*')) => Ok((Token::Op(Operator::Exp), consume_next_2_char(iter))),
-            (Some('.'), Some('.')) => Ok((Token::Op(Operator::Range), consume_next_2_char(iter))),
+            (Some('.'), Some('.')) => {
+                let mut loc = consume_next_2_char(iter);
+                if iter.peek() == Some('.') {
+                    iter.next();
+                    loc.end = iter.offset();
+                    Ok((Token::Op(Operator::Spread), loc))
+                } else {
+                    Ok((Token::Op(Operator::Range), loc))
+                }
+            }
             (Some('>'), Some('=')) => Ok((Token::Op(Operator::Ge), consume_next_2_char(iter))),
             (Some('<'), Some('=')) => Ok((Token::Op(Operator::Le), consume_next_2_char(iter))),
             (Some('='), Some('=')) => Ok((Token::Op(Operator::Eq), consume_next_2_char(iter))),
@@ -482,6 +935,13 @@ impl Lexer {
                 Ok((Token::Op(Operator::DoubleColon), consume_next_2_char(iter)))
             }
             (Some('<'), Some('-')) => Ok((Token::Op(Operator::LArrow), consume_next_2_char(iter))),
+            (Some('?'), Some('.')) => {
+                Ok((Token::Op(Operator::SafeMember), consume_next_2_char(iter)))
+            }
+            (Some('?'), Some('?')) => {
+                Ok((Token::Op(Operator::Coalesce), consume_next_2_char(iter)))
+            }
+            (Some('|'), Some('>')) => Ok((Token::Op(Operator::Pipe), consume_next_2_char(iter))),
             (Some(c), _) => {
                 let start = iter.offset();
                 iter.next();
@@ -521,15 +981,17 @@ impl Lexer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::file_manager::RenderOptions;
 
     #[test]
     fn test_consume_int() {
         fn test_helper(s: &str, i: i64, should_fail: bool) {
-            let mut iter = FileIterator::new(s, 0);
-            let result = Lexer::consume_num(&mut iter);
+            let mut iter = FileIterator::at(s, 0, 0);
+            let mut file_manager = FileManager::new();
+            let result = Lexer::consume_num(&mut iter, false, &mut file_manager);
             if should_fail {
                 assert!(
-                    result.is_err(),
+                    result.is_err() || file_manager.error_count() > 0,
                     "Expected parse success! source = {s}, result = {result:?}"
                 );
             } else if let Ok((Token::Integer(j), _)) = result {
@@ -545,8 +1007,10 @@ mod tests {
         test_helper("1_23", 123, false);
         test_helper("0xf_f", 0xff, false);
         test_helper("0Xff", 0xff, false);
-        test_helper("0b1__00011", 0b100011, false);
-        test_helper("0o776_610_", 0o776610, false);
+        test_helper("0b1__00011", 0, true);
+        test_helper("0o776_610_", 0, true);
+        test_helper("0x_ff", 0, true);
+        test_helper("0x", 0, true);
         test_helper("999+3", 999, false);
         test_helper("9_223_372_036_854_775_808", 0, true); // Overflow i64
         test_helper(
@@ -569,22 +1033,26 @@ mod tests {
             false,
         ); // Overflow i64
         test_helper("0xffabcde", 0xffabcde, false);
-        test_helper("0O999", 0, false);
+        test_helper("0O999", 0, true);
         test_helper("123y", 123, false);
         // This must not be parsed as a float as '123..int()' is ambiguous
         // It can be '123. ' then call 'int()' on a float
         // Also may be a range from '123' to 'int()'
         test_helper("123.", 123, false);
+        test_helper("1.max(2)", 1, false);
+        test_helper("1._2", 1, false);
+        test_helper("1..5", 1, false);
     }
 
     #[test]
     fn test_consume_float() {
         fn test_helper(s: &str, i: f64, should_fail: bool) {
-            let mut iter = FileIterator::new(s, 0);
-            let result = Lexer::consume_num(&mut iter);
+            let mut iter = FileIterator::at(s, 0, 0);
+            let mut file_manager = FileManager::new();
+            let result = Lexer::consume_num(&mut iter, false, &mut file_manager);
             if should_fail {
                 assert!(
-                    result.is_err(),
+                    result.is_err() || file_manager.error_count() > 0,
                     "Expected parse success! source = {s}, result = {result:?}"
                 );
             } else if let Ok((Token::Float(j), _)) = result {
@@ -596,16 +1064,73 @@ mod tests {
 
         test_helper("123e14", 123e14, false);
         test_helper("123.0.", 123.0, false);
-        test_helper("1_23e_14", 123e14, false);
+        test_helper("1_23e1_4", 123e14, false);
         test_helper("123E-14", 123e-14, false);
-        test_helper("0.01__2", 0.012, false);
+        test_helper("0.01_2", 0.012, false);
         test_helper("123e1y", 1230., false);
+        test_helper("1_23e_14", 0., true);
+        test_helper("0.01__2", 0., true);
+        test_helper("1_.5", 0., true);
+        test_helper("1.5_", 0., true);
+        test_helper("1.5_e3", 0., true);
+        test_helper("1e", 0., true);
+        test_helper("1e+_", 0., true);
+    }
+
+    #[test]
+    fn test_number_diagnostics() {
+        fn error(s: &str) -> (ErrorCode, Range<usize>) {
+            let mut iter = FileIterator::at(s, 0, 0);
+            let (error, loc) =
+                Lexer::consume_num(&mut iter, false, &mut FileManager::new()).unwrap_err();
+            (error, loc.start..loc.end)
+        }
+        // Number is still lexed after a misplaced separator, which is the only error
+        for (code, token) in [("1__000_ + 1", "int(1000)"), ("y = 1__2", "int(12)")] {
+            let mut file_manager = FileManager::new();
+            let fid = file_manager.add_file("<test>", code.to_string());
+            let tokens = Lexer::lex(&mut file_manager, fid);
+            let tokens: Vec<_> = tokens
+                .tokens()
+                .iter()
+                .map(|(token, _)| format!("{token:?}"))
+                .collect();
+            assert_eq!(file_manager.error_count(), 1, "{code}");
+            let rendered = file_manager.render(&RenderOptions::default());
+            assert!(rendered.contains("E0008"), "{rendered}");
+            assert!(!rendered.contains("E1001"), "{rendered}");
+            assert!(tokens.iter().any(|t| t == token), "{code}: {tokens:?}");
+        }
+        match error("2.5e-") {
+            (ErrorCode::MissingDigits(part, at), _) => {
+                assert_eq!((part.as_str(), at.start..at.end), ("in exponent", 3..5));
+            }
+            result => panic!("Expected missing digits, result = {result:?}"),
+        }
+        match error("0bx") {
+            (ErrorCode::MissingDigits(part, at), _) => {
+                assert_eq!((part.as_str(), at.start..at.end), ("after `0b`", 0..2));
+            }
+            result => panic!("Expected missing digits, result = {result:?}"),
+        }
+
+        // Numbers after `.` are members, not floats
+        let mut file_manager = FileManager::new();
+        let fid = file_manager.add_file("<test>", "t.0.1 + 1.5".to_string());
+        let tokens: Vec<_> = Lexer::lex(&mut file_manager, fid)
+            .tokens()
+            .iter()
+            .map(|(token, _)| format!("{token:?}"))
+            .collect();
+        assert_eq!(file_manager.error_count(), 0);
+        assert_eq!(tokens.len(), 7, "{tokens:?}");
+        assert!(tokens[6].contains("1.5"), "{tokens:?}");
     }
 
     #[test]
     fn test_consume_string() {
         fn test_helper(s: &str, i: &str, should_fail: bool) {
-            let mut iter = FileIterator::new(s, 0);
+            let mut iter = FileIterator::at(s, 0, 0);
             let result = Lexer::consume_string(&mut iter);
             if should_fail {
                 assert!(
@@ -636,18 +1161,39 @@ mod tests {
         test_helper("'\\UdFFf'", "", true);
         test_helper("'\\uDfff'", "", true);
         test_helper("'", "", true);
+        test_helper(
+            r#"'\0\a\b\e\f\v\xff\\'"#,
+            "\0\x07\x08\x1b\x0c\x0b\u{ff}\\",
+            false,
+        );
+
+        // Only the first invalid escape is reported
+        let mut iter = FileIterator::at(r#"'ab\q\z\x1'"#, 0, 0);
+        match Lexer::consume_string(&mut iter) {
+            Err((ErrorCode::InvalidEscapeSequence(escape, at), loc)) => {
+                assert_eq!(escape, "\\q");
+                assert_eq!((at.start, at.end), (3, 5));
+                assert_eq!((loc.start, loc.end), (0, 11));
+            }
+            result => panic!("Expected invalid escape, result = {result:?}"),
+        }
+        let mut iter = FileIterator::at(r#""\u12G4""#, 0, 0);
+        match Lexer::consume_string(&mut iter) {
+            Err((ErrorCode::InvalidEscapeSequence(escape, _), _)) => assert_eq!(escape, "\\u12"),
+            result => panic!("Expected invalid escape, result = {result:?}"),
+        }
     }
 
     fn test_str(code: &str, should_fail: bool) {
         let mut file_manager = FileManager::new();
         let fid = file_manager.add_file("<test>", code.to_string());
         let token_stream = Lexer::lex(&mut file_manager, fid);
-        for token in token_stream.iter() {
+        for (token, _) in token_stream.tokens() {
             println!("{token:?}");
         }
 
         if !should_fail && file_manager.error_count() > 0 {
-            println!("{}", file_manager.render(false));
+            println!("{}", file_manager.render(&RenderOptions::default()));
         }
         if should_fail {
             assert!(file_manager.error_count() > 0);
@@ -666,7 +1212,7 @@ mod tests {
                 end end
             ß = fac(5) å = ß**3//0x11f fac 
             set = {'s', 'ma\u00E9', 65e52, 0b00110}; dict = {}.insert(('key', 98)) 
-            🐶🐱 <> "Doa\x09 and cat'?'" $x $y $acb1.0
+            犬猫 <> "Doa\x09 and cat'?'" $x $y $acb1.0
             "#;
         test_str(code, false);
     }
@@ -682,4 +1228,65 @@ mod tests {
         let code = "$";
         test_str(code, true);
     }
+
+    #[test]
+    fn test_identifier() {
+        fn lex(code: &str) -> (Vec<Token>, FileManager) {
+            let mut file_manager = FileManager::new();
+            let fid = file_manager.add_file("<test>", code.to_string());
+            let token_stream = Lexer::lex(&mut file_manager, fid);
+            let tokens = token_stream
+                .tokens()
+                .iter()
+                .map(|(token, _)| token.clone())
+                .collect();
+            (tokens, file_manager)
+        }
+
+        test_str("café naïve_1 $λ x\u{0301} ℕ", false);
+        for code in ["🐶 = 1", "a🐱 = 1", "x\u{0301}\u{200B}", "\u{0301}x", "$🐶"] {
+            test_str(code, true);
+        }
+        let (_, file_manager) = lex("cat🐱dog = 1");
+        let rendered = file_manager.render(&RenderOptions::default());
+        assert!(
+            rendered.contains("Invalid character `🐱` (U+1F431)"),
+            "{rendered}"
+        );
+
+        // Composed and decomposed forms are the same identifier
+        let (tokens, _) = lex("caf\u{00E9} cafe\u{0301}");
+        assert!(matches!(&tokens[..], [Token::Id(a), Token::Id(b)] if a == b && a == "café"));
+
+        // Latin `a` and Cyrillic `а`
+        let (_, file_manager) = lex("a = 1\n\u{0430} = 2\n\u{0430}");
+        assert_eq!(file_manager.warning_count(), 1);
+        let rendered = file_manager.render(&RenderOptions::default());
+        assert!(rendered.contains("W1001"), "{rendered}");
+        let (_, file_manager) = lex("l = 1\nI = 2");
+        assert_eq!(file_manager.warning_count(), 0);
+    }
+
+    #[test]
+    fn test_interned_ids() {
+        let mut file_manager = FileManager::new();
+        let fid = file_manager.add_file("<a>", "x = y\nx".to_string());
+        let first = Lexer::lex(&mut file_manager, fid);
+        let fid = file_manager.add_file("<b>", "y".to_string());
+        let second = Lexer::lex(&mut file_manager, fid);
+        let ids: Vec<_> = first
+            .tokens()
+            .iter()
+            .chain(second.tokens())
+            .filter_map(|(token, _)| match token {
+                Token::Id(name) => Some(name.as_ptr()),
+                _ => None,
+            })
+            .collect();
+        // Same name shares one allocation, across files as well
+        assert_eq!(ids.len(), 4);
+        assert_eq!(ids[0], ids[2]);
+        assert_eq!(ids[1], ids[3]);
+        assert_ne!(ids[0], ids[1]);
+    }
 }
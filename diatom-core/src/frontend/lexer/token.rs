@@ -1,11 +1,14 @@
-use std::fmt::{Debug, Display};
+use alloc::string::String;
+use core::fmt::{Debug, Display};
+
+use crate::frontend::Symbol;
 
 #[derive(Clone)]
 pub enum Token {
     Str(String),
     Integer(i64),
     Float(f64),
-    Id(String),
+    Id(Symbol),
     Key(Keyword),
     Op(Operator),
 }
@@ -36,6 +39,8 @@ pub enum Keyword {
     For,
     /// return
     Return,
+    /// defer
+    Defer,
     /// break
     Break,
     /// continue
@@ -75,6 +80,8 @@ pub enum Operator {
     Rem,
     /// ".."
     Range,
+    /// "..."
+    Spread,
     /// is
     Is,
     /// "and"
@@ -101,8 +108,14 @@ pub enum Operator {
     Comma,
     /// "."
     Member,
+    /// "?."
+    SafeMember,
+    /// "??"
+    Coalesce,
     /// "|"
     BitOr,
+    /// "|>"
+    Pipe,
     /// "("
     LPar,
     /// ")"
@@ -126,7 +139,7 @@ pub enum Operator {
 }
 
 impl Display for Token {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Token::Str(s) => write!(f, "str({s})"),
             Token::Integer(i) => write!(f, "int({i})"),
@@ -139,13 +152,13 @@ impl Display for Token {
 }
 
 impl Debug for Token {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         Display::fmt(&self, f)
     }
 }
 
 impl Display for Keyword {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let name = match self {
             Keyword::True => "true",
             Keyword::False => "false",
@@ -159,6 +172,7 @@ impl Display for Keyword {
             Keyword::In => "in",
             Keyword::For => "for",
             Keyword::Return => "return",
+            Keyword::Defer => "defer",
             Keyword::Break => "break",
             Keyword::Continue => "continue",
             Keyword::Loop => "loop",
@@ -174,13 +188,13 @@ impl Display for Keyword {
 }
 
 impl Debug for Keyword {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         Display::fmt(&self, f)
     }
 }
 
 impl Display for Operator {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let name = match self {
             Operator::Plus => "+",
             Operator::Minus => "-",
@@ -190,6 +204,7 @@ impl Display for Operator {
             Operator::Div => "/",
             Operator::Rem => "%",
             Operator::Range => "..",
+            Operator::Spread => "...",
             Operator::Is => "is",
             Operator::And => "and",
             Operator::Or => "or",
@@ -203,7 +218,10 @@ impl Display for Operator {
             Operator::Assign => "=",
             Operator::Comma => ",",
             Operator::Member => ".",
+            Operator::SafeMember => "?.",
+            Operator::Coalesce => "??",
             Operator::BitOr => "|",
+            Operator::Pipe => "|>",
             Operator::LPar => "(",
             Operator::RPar => ")",
             Operator::LBrk => "[",
@@ -220,7 +238,7 @@ impl Display for Operator {
 }
 
 impl Debug for Operator {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         Display::fmt(&self, f)
     }
 }
@@ -1,5 +1,10 @@
 mod lexer;
 pub mod parser;
+pub mod resolver;
+mod symbol;
 mod util;
-pub use lexer::{Keyword, Lexer, Operator, Token};
+pub use lexer::{Keyword, KeywordAliases, Lexer, Operator, Token, TokenStream};
 pub use parser::Parser;
+pub use resolver::Resolver;
+pub use symbol::{Symbol, SymbolTable};
+pub(crate) use util::closest_name;
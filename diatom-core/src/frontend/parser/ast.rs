@@ -1,13 +1,30 @@
-use crate::file_manager::Loc;
+use crate::{file_manager::Loc, frontend::Symbol};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
 
-#[derive(Clone)]
+use super::visit::{walk_expr_mut, walk_stmt_mut, MutVisitor};
+
+#[derive(Clone, Debug)]
 pub struct ImportItem {
     pub loc: Loc,
     pub alias: Option<String>,
     pub path: Vec<String>,
 }
 
-#[derive(Clone)]
+/// A variant of an enum, `fields` is `None` if it is written without parentheses
+#[derive(Clone, Debug)]
+pub struct Variant {
+    pub loc: Loc,
+    pub name: Symbol,
+    pub fields: Option<Vec<(Symbol, Loc)>>,
+}
+
+#[derive(Clone, Debug)]
 pub enum Stmt {
     Expr {
         loc: Loc,
@@ -24,6 +41,14 @@ pub enum Stmt {
         loc: Loc,
         value: Option<Expr>,
     },
+    /// Run `body` when the enclosing function exits, either by returning or by an error
+    ///
+    /// Variables of the enclosing function are copied when `defer` runs, while those of outer
+    /// functions are shared.
+    Defer {
+        loc: Loc,
+        body: Expr,
+    },
     /// loop
     Loop {
         loc: Loc,
@@ -40,23 +65,354 @@ pub enum Stmt {
     /// Define a function
     Def {
         loc: Loc,
+        /// Expressions after `@` before `def`, outermost first, see `lower_decorators`
+        decorators: Vec<Expr>,
         variable: Box<Expr>,
-        parameters: Vec<(String, Loc)>,
+        parameters: Vec<(Symbol, Loc)>,
+        body: Vec<Stmt>,
+    },
+    /// Declare variants of an enum, see `lower_enum`
+    Enum {
+        loc: Loc,
+        name: Symbol,
+        name_loc: Loc,
+        variants: Vec<Variant>,
+    },
+    /// Declare an interface by names of methods it requires, see `lower_interface`
+    Interface {
+        loc: Loc,
+        name: Symbol,
+        name_loc: Loc,
+        methods: Vec<(Symbol, Loc)>,
+    },
+    /// Define a macro, whose calls are expanded by the parser, see `macros`
+    Macro {
+        loc: Loc,
+        name: Symbol,
+        name_loc: Loc,
+        parameters: Vec<(Symbol, Loc)>,
         body: Vec<Stmt>,
     },
     /// Import module
     Import {
         loc: Loc,
         fid: usize,
+        /// Path of the imported module
+        module: Vec<String>,
         items: Vec<ImportItem>,
         direct_import_mod: bool,
     },
     Error,
 }
 
-#[derive(Clone, Copy)]
+impl Stmt {
+    /// Move all locations by `delta` bytes
+    pub fn shift(&mut self, delta: isize) {
+        Shift(delta).visit_stmt(self);
+    }
+
+    /// Location of the statement, `None` if it can not be parsed
+    pub fn loc(&self) -> Option<&Loc> {
+        match self {
+            Stmt::Expr { loc, .. }
+            | Stmt::Continue { loc }
+            | Stmt::Break { loc }
+            | Stmt::Return { loc, .. }
+            | Stmt::Defer { loc, .. }
+            | Stmt::Loop { loc, .. }
+            | Stmt::For { loc, .. }
+            | Stmt::Def { loc, .. }
+            | Stmt::Enum { loc, .. }
+            | Stmt::Interface { loc, .. }
+            | Stmt::Macro { loc, .. }
+            | Stmt::Import { loc, .. } => Some(loc),
+            Stmt::Error => None,
+        }
+    }
+}
+
+/// Lower decorators of `def` into an assignment of the decorated function to `variable`
+///
+/// `@a @b(x) def f ... end` assigns `a(b(x)(f))` to `f` after `f` is defined, thus the
+/// decorator closest to `def` is applied first. None if there is no decorator.
+pub fn lower_decorators(loc: &Loc, variable: &Expr, decorators: &[Expr]) -> Option<Stmt> {
+    if decorators.is_empty() {
+        return None;
+    }
+    // Read the function by `::` rather than `.`, which binds it to the table
+    let function = match variable {
+        Expr::Infix {
+            loc,
+            op: OpInfix::Member,
+            lhs,
+            rhs,
+        } => Expr::Infix {
+            loc: loc.clone(),
+            op: OpInfix::DoubleColon,
+            lhs: lhs.clone(),
+            rhs: rhs.clone(),
+        },
+        variable => variable.clone(),
+    };
+    let decorated = decorators
+        .iter()
+        .rev()
+        .fold(function, |function, decorator| Expr::Call {
+            loc: decorator.get_loc().clone(),
+            lhs: Box::new(decorator.clone()),
+            parameters: vec![function],
+        });
+    Some(Stmt::Expr {
+        loc: loc.clone(),
+        expr: Expr::Infix {
+            loc: loc.clone(),
+            op: OpInfix::Assign,
+            lhs: Box::new(variable.clone()),
+            rhs: Box::new(decorated),
+        },
+    })
+}
+
+/// Lower `enum` into assignments of its variants to attributes of table `name`
+///
+/// A variant is a table tagged by its full name in `__variant`, e.g. `Shape::Circle`, with the
+/// enum as its meta table. A
+/// variant with fields is created by a constructor taking them, otherwise it is a single shared
+/// table. Table `name` is created first if `create` is true, otherwise variants are added to it.
+pub fn lower_enum(loc: &Loc, name: &Symbol, variants: &[Variant], create: bool) -> Vec<Stmt> {
+    let id = |loc: &Loc, name: &Symbol| Expr::Id {
+        loc: loc.clone(),
+        name: name.clone(),
+    };
+    let mut stmts = vec![];
+    if create {
+        stmts.push(Stmt::Expr {
+            loc: loc.clone(),
+            expr: Expr::Infix {
+                loc: loc.clone(),
+                op: OpInfix::Assign,
+                lhs: Box::new(id(loc, name)),
+                rhs: Box::new(Expr::Const {
+                    loc: loc.clone(),
+                    value: Const::Table(vec![]),
+                }),
+            },
+        });
+    }
+    for variant in variants {
+        let loc = &variant.loc;
+        let mut entries = vec![(
+            Some("__variant".to_string()),
+            Expr::Const {
+                loc: loc.clone(),
+                value: Const::Str(format!("{name}::{}", variant.name)),
+            },
+            loc.clone(),
+        )];
+        for (field, field_loc) in variant.fields.iter().flatten() {
+            entries.push((
+                Some(field.to_string()),
+                id(field_loc, field),
+                field_loc.clone(),
+            ));
+        }
+        let value = Expr::Infix {
+            loc: loc.clone(),
+            op: OpInfix::LArrow,
+            lhs: Box::new(Expr::Const {
+                loc: loc.clone(),
+                value: Const::Table(entries),
+            }),
+            rhs: Box::new(id(loc, name)),
+        };
+        let value = match &variant.fields {
+            Some(fields) => Expr::Fn {
+                loc: loc.clone(),
+                parameters: fields.clone(),
+                body: Box::new(value),
+            },
+            None => value,
+        };
+        stmts.push(Stmt::Expr {
+            loc: loc.clone(),
+            expr: Expr::Infix {
+                loc: loc.clone(),
+                op: OpInfix::Assign,
+                lhs: Box::new(Expr::Infix {
+                    loc: loc.clone(),
+                    op: OpInfix::Member,
+                    lhs: Box::new(id(loc, name)),
+                    rhs: Box::new(id(loc, &variant.name)),
+                }),
+                rhs: Box::new(value),
+            },
+        });
+    }
+    stmts
+}
+
+/// Lower `interface` into an assignment of table `{__methods = [...]}` to `name`
+pub fn lower_interface(loc: &Loc, name: &Symbol, methods: &[(Symbol, Loc)]) -> Stmt {
+    let methods = methods
+        .iter()
+        .map(|(method, loc)| Expr::Const {
+            loc: loc.clone(),
+            value: Const::Str(method.to_string()),
+        })
+        .collect();
+    let table = vec![(
+        Some("__methods".to_string()),
+        Expr::Const {
+            loc: loc.clone(),
+            value: Const::List(methods),
+        },
+        loc.clone(),
+    )];
+    Stmt::Expr {
+        loc: loc.clone(),
+        expr: Expr::Infix {
+            loc: loc.clone(),
+            op: OpInfix::Assign,
+            lhs: Box::new(Expr::Id {
+                loc: loc.clone(),
+                name: name.clone(),
+            }),
+            rhs: Box::new(Expr::Const {
+                loc: loc.clone(),
+                value: Const::Table(table),
+            }),
+        },
+    }
+}
+
+/// Lower `super.m(...)` in method `def T.f self ... end` into `table.m(self, ...)`
+///
+/// `table` is a variable assigned with `T` before the method is defined, and replaces `T` in
+/// `variable`. The code generator reads `m` of a receiver named by [`is_super`] from its meta
+/// table without binding, thus `super` is the parent of the class defining the method rather
+/// than that of the receiver. Return the assignment of `table` with the lowered variable and
+/// body, or `None` if `def` is not a method with parameters or does not call `super`.
+pub fn lower_super(
+    loc: &Loc,
+    variable: &Expr,
+    parameters: &[(Symbol, Loc)],
+    body: &[Stmt],
+    table: &Symbol,
+) -> Option<(Stmt, Expr, Vec<Stmt>)> {
+    let Expr::Infix {
+        loc: variable_loc,
+        op: OpInfix::Member,
+        lhs: class,
+        rhs: method,
+    } = variable
+    else {
+        return None;
+    };
+    let mut lowering = SuperLowering {
+        table,
+        receiver: &parameters.first()?.0,
+        lowered: false,
+    };
+    let mut body = body.to_vec();
+    body.iter_mut().for_each(|stmt| lowering.visit_stmt(stmt));
+    if !lowering.lowered {
+        return None;
+    }
+    let table = || {
+        Box::new(Expr::Id {
+            loc: class.get_loc(),
+            name: table.clone(),
+        })
+    };
+    let assignment = Stmt::Expr {
+        loc: loc.clone(),
+        expr: Expr::Infix {
+            loc: loc.clone(),
+            op: OpInfix::Assign,
+            lhs: table(),
+            rhs: class.clone(),
+        },
+    };
+    let variable = Expr::Infix {
+        loc: variable_loc.clone(),
+        op: OpInfix::Member,
+        lhs: table(),
+        rhs: method.clone(),
+    };
+    Some((assignment, variable, body))
+}
+
+/// Whether `name` is a variable assigned by [`lower_super`]
+pub fn is_super(name: &str) -> bool {
+    name.starts_with("#super")
+}
+
+struct SuperLowering<'a> {
+    table: &'a Symbol,
+    receiver: &'a Symbol,
+    lowered: bool,
+}
+
+impl MutVisitor for SuperLowering<'_> {
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            // Nested methods have their own `super`
+            Stmt::Def { variable, .. }
+                if matches!(
+                    variable.as_ref(),
+                    Expr::Infix {
+                        op: OpInfix::Member,
+                        ..
+                    }
+                ) => {}
+            stmt => walk_stmt_mut(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        if let Expr::Call {
+            lhs, parameters, ..
+        } = expr
+        {
+            if let Expr::Infix {
+                op: OpInfix::Member,
+                lhs: receiver,
+                rhs,
+                ..
+            } = lhs.as_mut()
+            {
+                if let (Expr::Id { loc, name }, Expr::Id { .. }) = (receiver.as_mut(), rhs.as_ref())
+                {
+                    if name == "super" {
+                        *name = self.table.clone();
+                        let receiver = Expr::Id {
+                            loc: loc.clone(),
+                            name: self.receiver.clone(),
+                        };
+                        parameters.insert(0, receiver);
+                        self.lowered = true;
+                    }
+                }
+            }
+        }
+        walk_expr_mut(self, expr);
+    }
+}
+
+/// Move all locations by some bytes
+struct Shift(isize);
+
+impl MutVisitor for Shift {
+    fn visit_loc(&mut self, loc: &mut Loc) {
+        loc.shift(self.0);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OpInfix {
     Assign,
+    /// `lhs |> f(...)` is `f(lhs, ...)`
+    Pipe,
     Range,
     Or,
     And,
@@ -75,24 +431,32 @@ pub enum OpInfix {
     Exp,
     Comma,
     Member,
+    SafeMember,
+    /// `lhs?.[rhs]`
+    SafeIndex,
+    Coalesce,
     DoubleColon,
     LArrow,
     Is,
+    /// `lhs in rhs` tests whether collection `rhs` contains `lhs`
+    In,
+    /// `lhs not in rhs`
+    NotIn,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OpPrefix {
     Not,
     Neg,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OpPostfix {
     Index,
     Call,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Expr {
     Block {
         loc: Loc,
@@ -130,12 +494,12 @@ pub enum Expr {
     },
     Fn {
         loc: Loc,
-        parameters: Vec<(String, Loc)>,
+        parameters: Vec<(Symbol, Loc)>,
         body: Box<Expr>,
     },
     Id {
         loc: Loc,
-        name: String,
+        name: Symbol,
     },
     Parentheses {
         loc: Loc,
@@ -145,30 +509,61 @@ pub enum Expr {
         loc: Loc,
         value: Const,
     },
+    /// `value...` in a list or table literal
+    Spread {
+        loc: Loc,
+        value: Box<Expr>,
+    },
     Error,
 }
 
 impl Expr {
+    /// Location of the expression, `None` if it can not be parsed
+    pub fn loc(&self) -> Option<&Loc> {
+        match self {
+            Expr::Block { loc, .. }
+            | Expr::If { loc, .. }
+            | Expr::Prefix { loc, .. }
+            | Expr::Call { loc, .. }
+            | Expr::Index { loc, .. }
+            | Expr::Infix { loc, .. }
+            | Expr::OpenRange { loc, .. }
+            | Expr::Fn { loc, .. }
+            | Expr::Id { loc, .. }
+            | Expr::Parentheses { loc, .. }
+            | Expr::Const { loc, .. }
+            | Expr::Spread { loc, .. } => Some(loc),
+            Expr::Error => None,
+        }
+    }
+
+    /// Location of the expression
+    ///
+    /// # Panics
+    /// Panics if it is `Expr::Error`.
     pub fn get_loc(&self) -> Loc {
+        self.loc()
+            .expect("Error expression has no location")
+            .clone()
+    }
+
+    /// Function made of an argument using placeholder `_`, e.g. `_ > 3` in `filter(_ > 3)`
+    ///
+    /// Its parameter has the location of the whole function, as it is not written in source.
+    pub fn is_placeholder_fn(&self) -> bool {
         match self {
-            Expr::Block { loc, .. } => loc,
-            Expr::If { loc, .. } => loc,
-            Expr::Prefix { loc, .. } => loc,
-            Expr::Call { loc, .. } => loc,
-            Expr::Index { loc, .. } => loc,
-            Expr::Infix { loc, .. } => loc,
-            Expr::Fn { loc, .. } => loc,
-            Expr::Id { loc, .. } => loc,
-            Expr::Parentheses { loc, .. } => loc,
-            Expr::Const { loc, .. } => loc,
-            Expr::Error => unreachable!(),
-            Expr::OpenRange { loc, .. } => loc,
+            Expr::Fn {
+                loc, parameters, ..
+            } => matches!(
+                parameters.as_slice(),
+                [(name, param)] if name == "_" && (param.start, param.end) == (loc.start, loc.end)
+            ),
+            _ => false,
         }
-        .clone()
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Const {
     Unit,
     Int(i64),
@@ -176,5 +571,6 @@ pub enum Const {
     Str(String),
     Bool(bool),
     List(Vec<Expr>),
-    Table(Vec<(String, Expr, Loc)>),
+    /// Entries of `key = value`, key is `None` if value is `Expr::Spread`
+    Table(Vec<(Option<String>, Expr, Loc)>),
 }
@@ -1,4 +1,8 @@
-use crate::{file_manager::Loc, frontend::Token};
+use crate::{
+    file_manager::Loc,
+    frontend::{Keyword, Token},
+};
+use alloc::string::String;
 
 /// Error code for `Parser`
 ///
@@ -27,4 +31,44 @@ pub enum ErrorCode {
     DuplicateKey(Loc, String),
     /// E1008 Invalid import
     InvalidImport,
+    /// E1009 Block is not closed by `end`
+    ///
+    /// Parameters:
+    /// - 1 Keyword starting the block
+    /// - 2 Token closing an outer block instead (None if eof is met)
+    MissingEnd(Keyword, Option<Loc>),
+    /// E1010 Statements or expressions nested deeper than `MAX_NESTING_DEPTH`
+    NestingTooDeep,
+    /// E1011 Assign to an expression which is not a variable, index or member
+    ///
+    /// Parameters:
+    /// - 1 Description of the expression, e.g. "a literal"
+    InvalidAssignTarget(&'static str),
+    /// E1012 Number of values does not match number of targets in parallel assignment
+    ///
+    /// Parameters:
+    /// - 1 Number of targets
+    /// - 2 Number of values
+    AssignCountMismatch(usize, usize),
+    /// E1013 Number of arguments does not match number of parameters of a macro
+    ///
+    /// Parameters:
+    /// - 1 Name of the macro where it is defined
+    /// - 2 Name of the macro
+    /// - 3 Number of parameters
+    /// - 4 Number of arguments
+    MacroArgumentCount(Loc, String, usize, usize),
+    /// E1014 Macros expanded inside one another deeper than `MAX_EXPANSION_DEPTH`
+    ///
+    /// Parameters:
+    /// - 1 Name of the macro
+    MacroExpansionTooDeep(String),
+}
+
+/// Warning code for `Parser`
+///
+/// Warning code `W1500` to `W1999` is reserved.
+pub enum WarningCode {
+    /// W1500 Condition of `if`, `elsif` or `until` is wrapped in parentheses
+    UnnecessaryParentheses,
 }
@@ -0,0 +1,294 @@
+//! Expansion of macros defined by `macro name(parameter, ...) ... end`
+//!
+//! A call of a macro after its definition, in the same block or a nested one, is replaced by a
+//! `begin ... end` block of the body of the macro, in which each parameter is replaced by the
+//! expression passed to it. An argument is thus evaluated each time the parameter is read, e.g.
+//! `times(3, println('hi'))` prints three lines with
+//!
+//! ```text
+//! macro times(n, body)
+//!     for _ in 0..n do body end
+//! end
+//! ```
+//!
+//! Names the body binds, i.e. variables assigned, loop variables, functions defined by `def` and
+//! parameters of functions, are local to each expansion: they are renamed so that they never
+//! clash with names of the caller. Other names are looked up where the macro is called. To assign
+//! a variable of the caller, pass it as an argument.
+use crate::compat::{AHashMap, AHashSet};
+use alloc::{format, string::ToString, vec, vec::Vec};
+
+use crate::{file_manager::Loc, frontend::Symbol};
+
+use super::{
+    ast::{Expr, OpInfix, Stmt},
+    error::ErrorCode,
+    visit::{walk_expr, walk_expr_mut, walk_stmt, walk_stmt_mut, MutVisitor, Visitor},
+    Parser, MAX_EXPANSION_DEPTH,
+};
+
+struct Macro {
+    name: Symbol,
+    name_loc: Loc,
+    parameters: Vec<(Symbol, Loc)>,
+    body: Vec<Stmt>,
+}
+
+struct Expander {
+    /// Macros in scope, the innermost last
+    macros: Vec<Macro>,
+    /// Expansions made, which tells names local to different expansions apart
+    expansions: usize,
+    /// Expansions being visited, one inside another
+    depth: usize,
+    errors: Vec<(ErrorCode, Loc)>,
+}
+
+impl Parser<'_> {
+    /// Expand calls of macros in top level statements of a file
+    pub(super) fn expand_macros(&mut self, stmts: &mut [Stmt]) {
+        let mut expander = Expander {
+            macros: vec![],
+            expansions: 0,
+            depth: 0,
+            errors: vec![],
+        };
+        for stmt in stmts {
+            expander.visit_stmt(stmt);
+            self.stmt_errors = 0;
+            for (error, loc) in expander.errors.drain(..) {
+                self.add_diagnostic(error, loc);
+            }
+        }
+        self.stmt_errors = 0;
+    }
+}
+
+impl Expander {
+    /// Expansion of `expr` if it calls a macro in scope
+    fn expand(&mut self, expr: &Expr) -> Option<Expr> {
+        let Expr::Call {
+            loc,
+            lhs,
+            parameters: arguments,
+        } = expr
+        else {
+            return None;
+        };
+        let Expr::Id { name, .. } = lhs.as_ref() else {
+            return None;
+        };
+        let found = self.macros.iter().rev().find(|found| found.name == *name)?;
+        if arguments.len() != found.parameters.len() {
+            self.errors.push((
+                ErrorCode::MacroArgumentCount(
+                    found.name_loc.clone(),
+                    name.to_string(),
+                    found.parameters.len(),
+                    arguments.len(),
+                ),
+                loc.clone(),
+            ));
+            return Some(Expr::Error);
+        }
+        if self.depth >= MAX_EXPANSION_DEPTH {
+            self.errors.push((
+                ErrorCode::MacroExpansionTooDeep(name.to_string()),
+                loc.clone(),
+            ));
+            return Some(Expr::Error);
+        }
+
+        self.expansions += 1;
+        let mut locals = Locals::default();
+        found.body.iter().for_each(|stmt| locals.visit_stmt(stmt));
+        let arguments: AHashMap<_, _> = found
+            .parameters
+            .iter()
+            .map(|(parameter, _)| parameter.clone())
+            .zip(arguments.iter().cloned())
+            .collect();
+        let renamed = locals
+            .0
+            .into_iter()
+            .filter(|local| !arguments.contains_key(local))
+            .map(|local| {
+                let unique = Symbol::from(format!("{local}@{}", self.expansions));
+                (local, unique)
+            })
+            .collect();
+        let mut substitute = Substitute { arguments, renamed };
+        let mut body = found.body.clone();
+        body.iter_mut().for_each(|stmt| substitute.visit_stmt(stmt));
+        Some(Expr::Block {
+            loc: loc.clone(),
+            body,
+        })
+    }
+}
+
+impl MutVisitor for Expander {
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        if let Stmt::Macro {
+            name,
+            name_loc,
+            parameters,
+            body,
+            ..
+        } = stmt
+        {
+            self.macros.push(Macro {
+                name: name.clone(),
+                name_loc: name_loc.clone(),
+                parameters: parameters.clone(),
+                body: body.clone(),
+            });
+            return;
+        }
+        // Macros defined in a nested block are not seen after it
+        let scope = self.macros.len();
+        walk_stmt_mut(self, stmt);
+        self.macros.truncate(scope);
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        if let Some(expansion) = self.expand(expr) {
+            *expr = expansion;
+            self.depth += 1;
+            walk_expr_mut(self, expr);
+            self.depth -= 1;
+            return;
+        }
+        let scope = self.macros.len();
+        match expr {
+            // Nor are macros defined in a branch seen in the following ones
+            Expr::If {
+                conditional,
+                default,
+                ..
+            } => {
+                for (condition, body) in conditional {
+                    self.visit_expr(condition);
+                    body.iter_mut().for_each(|stmt| self.visit_stmt(stmt));
+                    self.macros.truncate(scope);
+                }
+                default
+                    .iter_mut()
+                    .flatten()
+                    .for_each(|stmt| self.visit_stmt(stmt));
+            }
+            expr => walk_expr_mut(self, expr),
+        }
+        self.macros.truncate(scope);
+    }
+}
+
+/// Names bound in the body of a macro
+#[derive(Default)]
+struct Locals(AHashSet<Symbol>);
+
+impl Locals {
+    fn targets(&mut self, target: &Expr) {
+        match target {
+            Expr::Id { name, .. } => {
+                self.0.insert(name.clone());
+            }
+            Expr::Infix {
+                op: OpInfix::Comma,
+                lhs,
+                rhs,
+                ..
+            } => {
+                self.targets(lhs);
+                self.targets(rhs);
+            }
+            Expr::Parentheses { content, .. } => self.targets(content),
+            _ => (),
+        }
+    }
+}
+
+impl Visitor for Locals {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::For { loop_variable, .. } => self.targets(loop_variable),
+            Stmt::Def {
+                variable,
+                parameters,
+                ..
+            } => {
+                self.targets(variable);
+                self.0
+                    .extend(parameters.iter().map(|(name, _)| name.clone()));
+            }
+            _ => (),
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Infix {
+                op: OpInfix::Assign,
+                lhs,
+                ..
+            } => self.targets(lhs),
+            Expr::Fn { parameters, .. } => {
+                self.0
+                    .extend(parameters.iter().map(|(name, _)| name.clone()));
+            }
+            _ => (),
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// Replace parameters of a macro by arguments and rename names local to an expansion
+struct Substitute {
+    arguments: AHashMap<Symbol, Expr>,
+    renamed: AHashMap<Symbol, Symbol>,
+}
+
+impl Substitute {
+    fn rename_parameters(&self, parameters: &mut [(Symbol, Loc)]) {
+        for (name, _) in parameters {
+            if let Some(unique) = self.renamed.get(name) {
+                *name = unique.clone();
+            }
+        }
+    }
+}
+
+impl MutVisitor for Substitute {
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        if let Stmt::Def { parameters, .. } = stmt {
+            self.rename_parameters(parameters);
+        }
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Id { name, .. } => {
+                if let Some(argument) = self.arguments.get(name) {
+                    *expr = argument.clone();
+                } else if let Some(unique) = self.renamed.get(name) {
+                    *name = unique.clone();
+                }
+            }
+            // Right hand side is the name of an attribute
+            Expr::Infix {
+                op: OpInfix::Member | OpInfix::SafeMember | OpInfix::DoubleColon,
+                lhs,
+                ..
+            } => self.visit_expr(lhs),
+            Expr::Fn {
+                parameters, body, ..
+            } => {
+                self.rename_parameters(parameters);
+                self.visit_expr(body);
+            }
+            expr => walk_expr_mut(self, expr),
+        }
+    }
+}
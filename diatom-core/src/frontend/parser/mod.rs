@@ -1,49 +1,172 @@
 pub mod ast;
 mod error;
+mod macros;
 mod path_resolver;
 #[cfg(test)]
 mod tests;
+pub mod visit;
 
-use crate::file_manager::{Diagnostic, FileManager, Loc};
+use crate::file_manager::{display_width, Diagnostic, FileManager, Loc, Warning};
 use crate::frontend::parser::ast::ImportItem;
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
 
-use self::{error::ErrorCode, path_resolver::try_get_mod};
+use self::{
+    error::{ErrorCode, WarningCode},
+    path_resolver::{try_get_mod, try_load_mod},
+    visit::{walk_stmt_mut, MutVisitor},
+};
 
 use super::{
-    lexer::{Keyword, Operator, Token},
+    lexer::{Keyword, KeywordAliases, Operator, Token, TokenStream},
     util::TokenIterator,
-    Lexer,
+    Lexer, Symbol,
 };
 
-use ast::{Const, Expr, OpInfix, OpPostfix, OpPrefix, Stmt};
+use crate::compat::{ffi::OsString, io, path::PathBuf};
+use ast::{Const, Expr, OpInfix, OpPostfix, OpPrefix, Stmt, Variant};
 use codespan_reporting::diagnostic::Label;
-use std::collections::BTreeMap;
-use std::{ffi::OsString, mem::Discriminant, path::PathBuf};
+use core::{mem::Discriminant, ops::Range};
 
+/// Left and right binding power of infix operators, the higher the tighter
+///
+/// An operator is right associative if its right binding power is lower than its left.
 const fn precedence_infix(op: OpInfix) -> (u16, u16) {
     use OpInfix::*;
     match op {
         Comma => (1, 2),
         Assign => (4, 3),
-        Range => (5, 6),
-        Or => (7, 8),
-        And => (9, 10),
-        Eq | Ne | Le | Lt | Gt | Ge | Is => (11, 12),
-        Plus | Minus => (13, 14),
-        Mul | Div | DivFloor | Rem => (15, 16),
-        Exp => (17, 18),
-        LArrow => (21, 22),
-        DoubleColon => (23, 24),
-        Member => (23, 24),
+        Pipe => (5, 6),
+        Range => (7, 8),
+        Or => (9, 10),
+        And => (11, 12),
+        Eq | Ne | Le | Lt | Gt | Ge | Is | In | NotIn => (13, 14),
+        Coalesce => (16, 15),
+        Plus | Minus => (17, 18),
+        Mul | Div | DivFloor | Rem => (19, 20),
+        Exp => (23, 22),
+        LArrow => (25, 26),
+        DoubleColon => (27, 28),
+        Member | SafeMember => (27, 28),
+        SafeIndex => (precedence_postfix(), precedence_postfix()),
     }
 }
 
+/// `-` and `not` bind looser than `**` but tighter than `*`, so `-2 ** 2` is `-(2 ** 2)`
 const fn precedence_prefix() -> u16 {
-    19
+    21
 }
 
 const fn precedence_postfix() -> u16 {
-    20
+    24
+}
+
+/// `...` spreads a whole item of a list or table, `[a + b...]` spreads `a + b`
+const fn precedence_spread() -> u16 {
+    3
+}
+
+/// Where an operator is written relative to its operands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fixity {
+    Prefix,
+    Infix,
+    Postfix,
+}
+
+/// Which way operators of the same precedence group, prefix operators are right associative
+/// and postfix operators are left associative
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+/// Precedence of an operator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OperatorPrecedence {
+    /// `(...)` for calls, `[...]` for indexing and `?.[...]` for nil-safe indexing
+    pub symbol: &'static str,
+    pub fixity: Fixity,
+    /// Operators of higher precedence bind tighter
+    pub precedence: u16,
+    pub associativity: Associativity,
+}
+
+/// Precedence of every operator, from the loosest to the tightest binding
+///
+/// Operators of the same precedence are in the order they are defined.
+pub fn precedence_table() -> Vec<OperatorPrecedence> {
+    use OpInfix::*;
+    let infix = [
+        (Comma, ","),
+        (Assign, "="),
+        (Pipe, "|>"),
+        (Range, ".."),
+        (Or, "or"),
+        (And, "and"),
+        (Eq, "=="),
+        (Ne, "<>"),
+        (Le, "<="),
+        (Lt, "<"),
+        (Ge, ">="),
+        (Gt, ">"),
+        (Is, "is"),
+        (In, "in"),
+        (NotIn, "not in"),
+        (Plus, "+"),
+        (Minus, "-"),
+        (Mul, "*"),
+        (Div, "/"),
+        (DivFloor, "//"),
+        (Rem, "%"),
+        (Coalesce, "??"),
+        (Exp, "**"),
+        (LArrow, "<-"),
+        (DoubleColon, "::"),
+        (Member, "."),
+        (SafeMember, "?."),
+    ]
+    .map(|(op, symbol)| {
+        let (left, right) = precedence_infix(op);
+        OperatorPrecedence {
+            symbol,
+            fixity: Fixity::Infix,
+            precedence: left,
+            associativity: if right < left {
+                Associativity::Right
+            } else {
+                Associativity::Left
+            },
+        }
+    });
+    let prefix = ["-", "not"].map(|symbol| OperatorPrecedence {
+        symbol,
+        fixity: Fixity::Prefix,
+        precedence: precedence_prefix(),
+        associativity: Associativity::Right,
+    });
+    let postfix = [
+        ("...", precedence_spread()),
+        ("(...)", precedence_postfix()),
+        ("[...]", precedence_postfix()),
+        ("?.[...]", precedence_postfix()),
+    ]
+    .map(|(symbol, precedence)| OperatorPrecedence {
+        symbol,
+        fixity: Fixity::Postfix,
+        precedence,
+        associativity: Associativity::Left,
+    });
+    let mut table: Vec<_> = infix.into_iter().chain(prefix).chain(postfix).collect();
+    table.sort_by_key(|op| op.precedence);
+    table
 }
 
 /// A pattern match all possible start of an expression
@@ -53,7 +176,7 @@ macro_rules! expr_start_pattern {
             | Token::Op(Operator::LBrc)
             | Token::Op(Operator::LBrk)
             | Token::Op(Operator::LPar)
-            | Token::Op(Operator::Minus)
+            | Token::Op(Operator::Minus | Operator::Not)
             | Token::Id(_)
             | Token::Integer(_)
             | Token::Float(_)
@@ -66,8 +189,237 @@ pub struct Parser<'a> {
     file_manager: &'a mut FileManager,
     relative_path: Option<PathBuf>,
     search_path: &'a [PathBuf],
+    /// Name and module directory of packages, see `packages`
+    packages: &'a [(String, PathBuf)],
     import_stack: BTreeMap<usize, Option<Loc>>,
     fid: usize,
+    resolve_imports: bool,
+    /// Keep tokens of parsed files for `reparse`
+    incremental: bool,
+    /// Do not expand calls of macros, see `keep_macros`
+    keep_macros: bool,
+    keyword_aliases: KeywordAliases,
+    /// Blocks waiting for `end`, innermost last
+    blocks: Vec<OpenBlock>,
+    /// Errors reported for the statement being parsed
+    stmt_errors: usize,
+    /// Statements and expressions being parsed, one inside another
+    depth: usize,
+    /// Nesting reached `MAX_NESTING_DEPTH` and the rest of file is skipped
+    too_deep: bool,
+}
+
+/// Replace `range` of a file, in bytes, by `text`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// Top level statements changed by `Parser::reparse`
+///
+/// Statements not in these ranges are reused, with locations moved if they follow the edit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reparse {
+    /// Indices of statements of the old syntax tree that were removed
+    pub removed: Range<usize>,
+    /// Indices of statements of the new syntax tree that were parsed again
+    pub inserted: Range<usize>,
+}
+
+/// Tokens and top level statements of a file kept by an incremental parser
+#[derive(Clone)]
+pub struct ParseCache {
+    tokens: TokenStream,
+    stmts: Vec<StmtSpan>,
+    relative_path: Option<PathBuf>,
+}
+
+impl ParseCache {
+    pub fn tokens(&self) -> &TokenStream {
+        &self.tokens
+    }
+
+    /// Byte range of each top level statement
+    pub fn statements(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.stmts.iter().filter_map(|stmt| {
+            let first = self.tokens.tokens().get(stmt.tokens.start)?;
+            let last = self.tokens.tokens().get(stmt.tokens.end.checked_sub(1)?)?;
+            Some(first.1.start..last.1.end)
+        })
+    }
+}
+
+/// Tokens of a top level statement
+#[derive(Clone)]
+struct StmtSpan {
+    tokens: Range<usize>,
+    /// No diagnostic is reported while parsing it, thus it can be reused
+    clean: bool,
+}
+
+/// A block waiting for its `end`
+struct OpenBlock {
+    keyword: Keyword,
+    loc: Loc,
+    /// Indentation of the line where the block starts
+    indent: usize,
+}
+
+/// Errors after this many in one statement are considered cascaded and not reported
+const MAX_STMT_ERRORS: usize = 3;
+
+/// Statements and expressions nested deeper than this are rejected instead of overflowing stack
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+/// Macros expanded inside one another deeper than this are considered recursive
+pub const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// Whether `tokens` have a definition of macro, i.e. `macro` before a name
+fn defines_macro(tokens: &[(Token, Loc)]) -> bool {
+    tokens.windows(2).any(|pair| {
+        matches!((&pair[0].0, &pair[1].0), (Token::Id(name), Token::Id(_)) if name == "macro")
+    })
+}
+
+/// Whether parsing should resume at this token after an error
+///
+/// Statement keywords and keywords closing a block are never skipped. `then` and `do` are only
+/// synchronized on when looking for a closing token, since they belong to an enclosing `if`,
+/// `for` or `until`.
+fn is_sync_token(token: &Token, closing: bool) -> bool {
+    use Keyword::*;
+    match token {
+        Token::Key(
+            Def | Defer | For | Loop | Until | Import | Return | Break | Continue | End | Else
+            | Elsif,
+        ) => true,
+        Token::Key(Then | Do) => closing,
+        _ => false,
+    }
+}
+
+/// Items of a top level comma separated expression, in order
+fn flatten_comma(expr: Expr, items: &mut Vec<Expr>) {
+    match expr {
+        Expr::Infix {
+            op: OpInfix::Comma,
+            lhs,
+            rhs,
+            ..
+        } => {
+            flatten_comma(*lhs, items);
+            flatten_comma(*rhs, items);
+        }
+        expr => items.push(expr),
+    }
+}
+
+/// Join items with commas, nested to the left as parsed
+fn join_comma(items: Vec<Expr>) -> Expr {
+    items
+        .into_iter()
+        .reduce(|lhs, rhs| join(lhs, OpInfix::Comma, rhs))
+        .unwrap_or(Expr::Error)
+}
+
+/// Infix expression spanning both operands, either of which may be an error
+fn join(lhs: Expr, op: OpInfix, rhs: Expr) -> Expr {
+    let loc = match (lhs.loc(), rhs.loc()) {
+        (Some(start), Some(end)) => start.clone() + end.clone(),
+        (Some(loc), None) | (None, Some(loc)) => loc.clone(),
+        (None, None) => return Expr::Error,
+    };
+    Expr::Infix {
+        loc,
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    }
+}
+
+/// Whether `expr` reads placeholder `_`, functions and arguments of calls inside are not counted
+fn has_placeholder(expr: &Expr) -> bool {
+    match expr {
+        Expr::Id { name, .. } => name == "_",
+        Expr::Prefix { rhs: operand, .. }
+        | Expr::OpenRange { lhs: operand, .. }
+        | Expr::Parentheses {
+            content: operand, ..
+        }
+        | Expr::Call { lhs: operand, .. } => has_placeholder(operand),
+        Expr::Infix {
+            op: OpInfix::Member | OpInfix::SafeMember | OpInfix::DoubleColon,
+            lhs,
+            ..
+        } => has_placeholder(lhs),
+        Expr::Infix {
+            op: OpInfix::Assign,
+            ..
+        } => false,
+        Expr::Infix { lhs, rhs, .. } | Expr::Index { lhs, rhs, .. } => {
+            has_placeholder(lhs) || has_placeholder(rhs)
+        }
+        _ => false,
+    }
+}
+
+/// Turn an argument using placeholder `_` into a function of `_`, e.g. `f(_ > 3)` is
+/// `f(fn _ = _ > 3)`
+fn placeholder_fn(argument: Expr) -> Expr {
+    if !has_placeholder(&argument) {
+        return argument;
+    }
+    let loc = argument.get_loc();
+    Expr::Fn {
+        loc: loc.clone(),
+        parameters: vec![("_".into(), loc)],
+        body: Box::new(argument),
+    }
+}
+
+/// Describe an expression that can not be assigned to, `None` if it can
+fn invalid_target(expr: &Expr) -> Option<&'static str> {
+    match expr {
+        Expr::Id { .. } | Expr::Index { .. } | Expr::Error => None,
+        Expr::Infix {
+            op: OpInfix::Member,
+            rhs,
+            ..
+        } => match rhs.as_ref() {
+            Expr::Id { .. }
+            | Expr::Const {
+                value: Const::Int(_),
+                ..
+            } => None,
+            _ => Some("this member"),
+        },
+        Expr::Parentheses { content, .. } => invalid_target(content),
+        Expr::Const {
+            value: Const::List(_),
+            ..
+        } => Some("a list literal"),
+        Expr::Const {
+            value: Const::Table(_),
+            ..
+        } => Some("a table literal"),
+        Expr::Const { .. } => Some("a literal"),
+        Expr::Call { .. } => Some("a call result"),
+        Expr::Fn { .. } => Some("a function"),
+        Expr::Spread { .. } => Some("a spread"),
+        Expr::Block { .. } | Expr::If { .. } => Some("a block"),
+        Expr::Infix {
+            op: OpInfix::Assign,
+            ..
+        } => Some("an assignment"),
+        Expr::Infix {
+            op: OpInfix::SafeMember | OpInfix::SafeIndex,
+            ..
+        } => Some("a nil-safe access"),
+        Expr::Prefix { .. } | Expr::Infix { .. } | Expr::OpenRange { .. } => {
+            Some("the result of an operator")
+        }
+    }
 }
 
 impl<'a> Parser<'a> {
@@ -77,10 +429,56 @@ impl<'a> Parser<'a> {
             import_stack: Default::default(),
             relative_path: None,
             search_path,
+            packages: &[],
             fid: 0,
+            resolve_imports: true,
+            incremental: false,
+            keep_macros: false,
+            keyword_aliases: KeywordAliases::default(),
+            blocks: vec![],
+            stmt_errors: 0,
+            depth: 0,
+            too_deep: false,
         }
     }
 
+    /// Do not resolve or parse imported modules, `fid` of imports is set to 0
+    ///
+    /// This is used by tools working on a single file, e.g. the formatter.
+    pub fn skip_imports(mut self) -> Self {
+        self.resolve_imports = false;
+        self
+    }
+
+    /// Keep tokens and statement boundaries of parsed files so that they can be parsed
+    /// incrementally by `reparse` after being edited
+    pub fn incremental(mut self) -> Self {
+        self.incremental = true;
+        self
+    }
+
+    /// Keep calls of macros as they are written instead of expanding them
+    ///
+    /// This is used by tools working on source as written, e.g. the formatter.
+    pub fn keep_macros(mut self) -> Self {
+        self.keep_macros = true;
+        self
+    }
+
+    /// Import `name.module` from `module` in the directory of package `name`
+    ///
+    /// Packages are looked up after the directory of the importing file and before search paths.
+    pub fn packages(mut self, packages: &'a [(String, PathBuf)]) -> Self {
+        self.packages = packages;
+        self
+    }
+
+    /// Recognize `aliases` as keywords in every file parsed
+    pub fn keyword_aliases(mut self, aliases: KeywordAliases) -> Self {
+        self.keyword_aliases = aliases;
+        self
+    }
+
     /// Parse a file
     pub fn parse_file(&mut self, path: impl Into<OsString>, content: impl Into<String>) -> usize {
         let path = path.into();
@@ -105,23 +503,387 @@ impl<'a> Parser<'a> {
         fid
     }
 
+    /// Parse files, each as if it is passed to `parse_file`, and return their fids in order
+    ///
+    /// All files are added before any is parsed, thus fids follow the order of `paths` and a
+    /// file imported by another one is not added again. Files are lexed and parsed on a thread
+    /// pool with feature `parallel`, then imported modules are parsed file by file in order of
+    /// `paths`, so that the result does not depend on the feature. Diagnostics are in order of
+    /// `paths`, those of imports following those of the importing file. Nothing is parsed if
+    /// any file can not be read.
+    pub fn parse_many(&mut self, paths: &[PathBuf]) -> io::Result<Vec<usize>> {
+        let contents = paths
+            .iter()
+            .map(|path| self.file_manager.read_file(path))
+            .collect::<io::Result<Vec<_>>>()?;
+        let fids: Vec<_> = paths
+            .iter()
+            .zip(contents)
+            .map(|(path, content)| self.file_manager.add_file(path, content))
+            .collect();
+
+        let base = &*self.file_manager;
+        let (search_path, aliases, incremental, keep_macros) = (
+            self.search_path,
+            &self.keyword_aliases,
+            self.incremental,
+            self.keep_macros,
+        );
+        let parse = |(&fid, path): (&usize, &PathBuf)| {
+            let mut fork = base.fork();
+            let mut parser = Parser::new(&mut fork, search_path)
+                .skip_imports()
+                .keyword_aliases(aliases.clone());
+            parser.incremental = incremental;
+            parser.keep_macros = keep_macros;
+            parser.relative_path = path.parent().map(PathBuf::from);
+            parser.parse_fid(fid, None);
+            fork
+        };
+        #[cfg(feature = "parallel")]
+        let forks: Vec<_> = {
+            use rayon::prelude::*;
+            fids.par_iter().zip(paths).map(parse).collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let forks: Vec<_> = fids.iter().zip(paths).map(parse).collect();
+
+        for ((&fid, path), fork) in fids.iter().zip(paths).zip(forks) {
+            self.file_manager.join(fork);
+            if self.resolve_imports {
+                self.relative_path = path.parent().map(PathBuf::from);
+                self.link_imports(fid);
+            }
+        }
+        Ok(fids)
+    }
+
+    /// Parse modules imported by a file parsed with imports skipped
+    fn link_imports(&mut self, fid: usize) {
+        struct Linker<'p, 'a>(&'p mut Parser<'a>);
+
+        impl MutVisitor for Linker<'_, '_> {
+            fn visit_stmt(&mut self, stmt: &mut Stmt) {
+                if let Stmt::Import {
+                    loc, fid, module, ..
+                } = stmt
+                {
+                    self.0.stmt_errors = 0;
+                    match self.0.import_module(module, loc) {
+                        Some(module_fid) => *fid = module_fid,
+                        None => *stmt = Stmt::Error,
+                    }
+                    return;
+                }
+                walk_stmt_mut(self, stmt);
+            }
+        }
+
+        self.fid = fid;
+        self.import_stack.insert(fid, None);
+        let mut ast = self.file_manager.get_ast(fid).as_ref().clone();
+        let mut linker = Linker(self);
+        ast.iter_mut().for_each(|stmt| linker.visit_stmt(stmt));
+        self.import_stack.remove(&fid);
+        self.file_manager.set_ast(fid, ast);
+    }
+
     fn parse_fid(&mut self, fid: usize, loc: Option<Loc>) {
         self.fid = fid;
         self.import_stack.insert(fid, loc);
-        let token_stream = Lexer::lex(self.file_manager, fid);
-        let mut iter = token_stream.iter();
-        let mut stmts = vec![];
+        let token_stream = Lexer::lex_with_aliases(self.file_manager, fid, &self.keyword_aliases);
+        let (mut stmts, spans) = self.parse_stmts(token_stream.tokens(), 0, |_| false);
+        if !self.keep_macros {
+            self.expand_macros(&mut stmts);
+        }
+        self.import_stack.remove(&fid);
+        if self.incremental {
+            let cache = ParseCache {
+                tokens: token_stream,
+                stmts: spans,
+                relative_path: self.relative_path.clone(),
+            };
+            self.file_manager.set_parse_cache(fid, cache);
+        }
+        self.file_manager.set_ast(fid, stmts);
+    }
 
-        while iter.peek().is_some() {
-            let stmt = self.consume_stmt(&mut iter, None);
-            stmts.push(stmt);
+    /// Parse top level statements from `tokens[start..]` until the end or `stop` returns true
+    /// for the index of the token starting the next statement
+    fn parse_stmts(
+        &mut self,
+        tokens: &[(Token, Loc)],
+        start: usize,
+        mut stop: impl FnMut(usize) -> bool,
+    ) -> (Vec<Stmt>, Vec<StmtSpan>) {
+        let mut iter = TokenIterator::new(&tokens[start..]);
+        let (mut stmts, mut spans) = (vec![], vec![]);
+        // Imported files are parsed in the middle of a statement
+        let blocks = core::mem::take(&mut self.blocks);
+        let stmt_errors = core::mem::take(&mut self.stmt_errors);
+        let depth = core::mem::take(&mut self.depth);
+
+        loop {
+            let first = tokens.len() - iter.remaining();
+            if iter.peek().is_none() || stop(first) {
+                break;
+            }
+            let diagnostics = self.file_manager.diagnostic_count();
+            stmts.push(self.consume_stmt(&mut iter, None));
+            spans.push(StmtSpan {
+                tokens: first..tokens.len() - iter.remaining(),
+                clean: diagnostics == self.file_manager.diagnostic_count(),
+            });
         }
 
+        self.blocks = blocks;
+        self.stmt_errors = stmt_errors;
+        self.depth = depth;
+        self.too_deep = false;
+        (stmts, spans)
+    }
+
+    /// Apply `edit` to a file parsed before in incremental mode and parse it again
+    ///
+    /// Only the tokens around the edit are lexed again. Top level statements are parsed again
+    /// from the first one that may be affected by the edit until the parser meets the start of
+    /// an old statement after the edit. Statements with diagnostics are always parsed again so
+    /// that their diagnostics are reported. The whole file is parsed if it is not parsed in
+    /// incremental mode before, or if macros are expanded and the file may define any, since a
+    /// statement reused would keep its old expansion.
+    ///
+    /// # Panics
+    /// Panics if range of `edit` is out of bounds or not on char boundaries.
+    pub fn reparse(&mut self, fid: usize, edit: &TextEdit) -> Reparse {
+        let old_file = self.file_manager.get_shared_file(fid).clone();
+        let mut source = old_file.file.as_ref().clone();
+        source.replace_range(edit.range.clone(), &edit.text);
+        self.file_manager.replace_file(fid, source);
+        let keep_cache = |cache: &ParseCache| {
+            self.keep_macros
+                || !(defines_macro(cache.tokens.tokens()) || edit.text.contains("macro"))
+        };
+        let Some(cache) = self.file_manager.take_parse_cache(fid).filter(keep_cache) else {
+            let removed = self.file_manager.get_ast(fid).len();
+            let incremental = core::mem::replace(&mut self.incremental, true);
+            self.parse_fid(fid, None);
+            self.incremental = incremental;
+            return Reparse {
+                removed: 0..removed,
+                inserted: 0..self.file_manager.get_ast(fid).len(),
+            };
+        };
+        self.fid = fid;
+        self.relative_path = cache.relative_path.clone();
+        let delta = edit.text.len() as isize - edit.range.len() as isize;
+        let (tokens, old_range, new_range) = Lexer::relex(
+            self.file_manager,
+            fid,
+            &cache.tokens,
+            edit.range.clone(),
+            edit.text.len(),
+            &self.keyword_aliases,
+        );
+        let old_tokens = cache.tokens.tokens();
+        let token_delta = new_range.len() as isize - old_range.len() as isize;
+
+        // Bytes lexed again, in old file
+        let dirty_start = old_tokens
+            .get(old_range.start)
+            .map_or(edit.range.start, |(_, loc)| loc.start)
+            .min(edit.range.start);
+        let dirty_end = old_tokens
+            .get(old_range.end)
+            .map_or(old_file.file.len(), |(_, loc)| loc.start)
+            .max(edit.range.end);
+        // A statement depends on indentation of its first line and two tokens following it
+        let affected = |span: &StmtSpan| {
+            let start = old_tokens[span.tokens.start].1.start;
+            let start = old_file
+                .line_start(old_file.line(start))
+                .unwrap_or_default();
+            let end = old_tokens
+                .get(span.tokens.end + 1)
+                .map_or(usize::MAX, |(_, loc)| loc.end);
+            !span.clean || (start < dirty_end.max(dirty_start + 1) && end > dirty_start)
+        };
+        let stmt_start = |span: &StmtSpan| old_tokens[span.tokens.start].1.start;
+        // Tokens lexed again may start new statements before the first affected one
+        let first = cache
+            .stmts
+            .iter()
+            .position(|span| affected(span) || stmt_start(span) >= dirty_start)
+            .unwrap_or(cache.stmts.len());
+        let reusable = cache
+            .stmts
+            .iter()
+            .rposition(affected)
+            .map_or(first, |last| last + 1);
+
+        let old_ast = self.file_manager.get_ast(fid);
+        let mut stmts: Vec<_> = old_ast[..first].to_vec();
+        let mut spans: Vec<_> = cache.stmts[..first].to_vec();
+        let mut resync = cache.stmts.len();
+        let start = spans.last().map_or(0, |span| span.tokens.end);
+        self.import_stack.insert(fid, None);
+        let (parsed, parsed_spans) = self.parse_stmts(tokens.tokens(), start, |index| {
+            if index < new_range.end {
+                return false;
+            }
+            let old_index = (index as isize - token_delta) as usize;
+            match cache.stmts[reusable..].binary_search_by_key(&old_index, |span| span.tokens.start)
+            {
+                Ok(i) => {
+                    resync = reusable + i;
+                    true
+                }
+                Err(_) => false,
+            }
+        });
         self.import_stack.remove(&fid);
-        self.file_manager.set_ast(self.fid, stmts);
+        stmts.extend(parsed);
+        spans.extend(parsed_spans);
+        let inserted = first..stmts.len();
+        stmts.extend(old_ast[resync..].iter().cloned().map(|mut stmt| {
+            stmt.shift(delta);
+            stmt
+        }));
+        spans.extend(cache.stmts[resync..].iter().map(|span| StmtSpan {
+            tokens: (span.tokens.start as isize + token_delta) as usize
+                ..(span.tokens.end as isize + token_delta) as usize,
+            clean: true,
+        }));
+
+        self.file_manager.set_parse_cache(
+            fid,
+            ParseCache {
+                tokens,
+                stmts: spans,
+                relative_path: cache.relative_path,
+            },
+        );
+        self.file_manager.set_ast(fid, stmts);
+        Reparse {
+            removed: first..resync,
+            inserted,
+        }
+    }
+
+    /// Indentation of the line containing `offset`, and whether only spaces precede it
+    fn indent_at(&self, offset: usize) -> (usize, bool) {
+        let file = self.file_manager.get_shared_file(self.fid);
+        let line_start = file.line_start(file.line(offset)).unwrap_or_default();
+        let prefix = &file.file[line_start..offset];
+        let blank = prefix.trim_start().is_empty();
+        let indent = &prefix[..prefix.len() - prefix.trim_start().len()];
+        (display_width(indent), blank)
+    }
+
+    /// Whether the next token is the first one of its line
+    fn at_line_start(&self, iter: &TokenIterator) -> bool {
+        iter.peek().is_some() && self.indent_at(iter.next_loc().start).1
+    }
+
+    /// Skip tokens to where the next statement possibly starts
+    fn synchronize(&mut self, iter: &mut TokenIterator) {
+        while let Some(token) = iter.peek() {
+            if is_sync_token(token, false) || self.at_line_start(iter) {
+                break;
+            }
+            iter.next();
+        }
+    }
+
+    fn open_block(&mut self, keyword: Keyword, loc: Loc) {
+        let indent = self.indent_at(loc.start).0;
+        self.blocks.push(OpenBlock {
+            keyword,
+            loc,
+            indent,
+        });
+    }
+
+    fn close_block(&mut self) {
+        self.blocks.pop();
+    }
+
+    /// Check if the innermost block misses its `end`, report it if so
+    ///
+    /// An `end` on its own line is considered to close an outer block if it lines up with that
+    /// block but not with the innermost one. So does an `else` or `elsif` belonging to an outer
+    /// `if`. Blocks are also unclosed if the file ends. Return true if the innermost block
+    /// should be closed without consuming any token.
+    fn check_missing_end(&mut self, iter: &TokenIterator) -> bool {
+        let Some((block, outer)) = self.blocks.split_last() else {
+            return false;
+        };
+        let closing = match iter.peek() {
+            None => None,
+            Some(Token::Key(Keyword::End)) => {
+                let loc = iter.next_loc();
+                let (indent, line_start) = self.indent_at(loc.start);
+                if !line_start
+                    || indent >= block.indent
+                    || !outer.iter().any(|block| block.indent == indent)
+                {
+                    return false;
+                }
+                Some(loc)
+            }
+            Some(Token::Key(Keyword::Else | Keyword::Elsif)) => {
+                if matches!(block.keyword, Keyword::If)
+                    || !outer
+                        .iter()
+                        .any(|block| matches!(block.keyword, Keyword::If))
+                {
+                    return false;
+                }
+                Some(iter.next_loc())
+            }
+            Some(_) => return false,
+        };
+        let loc = block.loc.clone();
+        let keyword = block.keyword;
+        // Always report unclosed blocks, they are not cascaded errors
+        let stmt_errors = core::mem::take(&mut self.stmt_errors);
+        self.add_diagnostic(ErrorCode::MissingEnd(keyword, closing), loc);
+        self.stmt_errors = stmt_errors;
+        true
     }
 
     fn consume_stmt(&mut self, iter: &mut TokenIterator, not_take_on_error: Option<Token>) -> Stmt {
+        if self.nesting_too_deep(iter) {
+            return Stmt::Error;
+        }
+        let stmt_errors = core::mem::take(&mut self.stmt_errors);
+        self.depth += 1;
+        let stmt = self.consume_stmt_(iter, not_take_on_error);
+        self.depth -= 1;
+        if self.stmt_errors > 0 {
+            self.synchronize(iter);
+        }
+        self.stmt_errors = stmt_errors;
+        stmt
+    }
+
+    /// Report an error and skip the rest of file if nesting reaches `MAX_NESTING_DEPTH`
+    ///
+    /// Errors of all enclosing statements and expressions are cascaded, thus not reported.
+    fn nesting_too_deep(&mut self, iter: &mut TokenIterator) -> bool {
+        if self.depth < MAX_NESTING_DEPTH {
+            return false;
+        }
+        self.add_diagnostic(ErrorCode::NestingTooDeep, iter.next_loc());
+        self.too_deep = true;
+        while iter.next().is_some() {}
+        true
+    }
+
+    fn consume_stmt_(
+        &mut self,
+        iter: &mut TokenIterator,
+        not_take_on_error: Option<Token>,
+    ) -> Stmt {
         use Keyword::*;
         use Token::*;
         while let Some(Token::Op(Operator::SemiColon)) = iter.peek() {
@@ -155,9 +917,32 @@ impl<'a> Parser<'a> {
                     },
                 }
             }
+            Some(Key(Defer)) => {
+                iter.next();
+                let body = self.consume_expr(iter, 0, not_take_on_error);
+                let body = self.consume_assignment(body);
+                let end = iter.loc();
+                Stmt::Defer {
+                    loc: start + end,
+                    body,
+                }
+            }
             Some(Key(Def)) => self.consume_def(iter),
+            Some(Op(Operator::At)) => self.consume_decorated(iter),
+            // `enum`, `interface` and `macro` are only keywords before a name, thus still usable
+            // as variables
+            Some(Id(name)) if name == "enum" && matches!(iter.peek2().1, Some(Id(_))) => {
+                self.consume_enum(iter)
+            }
+            Some(Id(name)) if name == "interface" && matches!(iter.peek2().1, Some(Id(_))) => {
+                self.consume_interface(iter)
+            }
+            Some(Id(name)) if name == "macro" && matches!(iter.peek2().1, Some(Id(_))) => {
+                self.consume_macro(iter)
+            }
             Some(expr_start_pattern!()) => {
                 let expr = self.consume_expr(iter, 0, not_take_on_error);
+                let expr = self.consume_assignment(expr);
                 let end = iter.loc();
                 Stmt::Expr {
                     loc: start + end,
@@ -187,6 +972,7 @@ impl<'a> Parser<'a> {
 
     /// Consume an iterator to an expected operator or EOF
     /// Errors are written to `self.diagnoser`
+    /// Stop at the start of a new statement if the expected operator is missing
     /// Return true if eof met otherwise false
     #[must_use]
     fn consume_to_op(
@@ -197,32 +983,50 @@ impl<'a> Parser<'a> {
     ) -> bool {
         fn test_match(op_type: Discriminant<Operator>, iter: &TokenIterator) -> bool {
             if let Some(Token::Op(op)) = iter.peek() {
-                if op_type == std::mem::discriminant(op) {
+                if op_type == core::mem::discriminant(op) {
                     return true;
                 }
             }
             false
         }
-        let op_type = std::mem::discriminant(&expected);
+        let op_type = core::mem::discriminant(&expected);
 
         if test_match(op_type, iter) {
             iter.next();
             return false;
-        } else {
-            let t = iter.next().cloned();
-            let loc_now = iter.loc();
-            self.add_diagnostic(
-                ErrorCode::UnexpectedToken(t, Some(Token::Op(expected)), previous),
-                loc_now,
-            );
+        }
+        let expected = Some(Token::Op(expected));
+        match iter.peek() {
+            // Assume the expected token is missing if a new statement seems to start
+            Some(t) if is_sync_token(t, true) || self.at_line_start(iter) => {
+                let t = t.clone();
+                let loc = iter.next_loc();
+                self.add_diagnostic(ErrorCode::UnexpectedToken(Some(t), expected, previous), loc);
+                return false;
+            }
+            Some(_) => {
+                let t = iter.next().cloned();
+                let loc_now = iter.loc();
+                self.add_diagnostic(ErrorCode::UnexpectedToken(t, expected, previous), loc_now);
+            }
+            None => {
+                self.add_diagnostic(
+                    ErrorCode::UnexpectedToken(None, expected, previous),
+                    iter.loc(),
+                );
+                return true;
+            }
         }
         loop {
             if test_match(op_type, iter) {
                 iter.next();
                 return false;
             }
-            match iter.next() {
-                Some(_) => (),
+            match iter.peek() {
+                Some(t) if is_sync_token(t, true) || self.at_line_start(iter) => return false,
+                Some(_) => {
+                    iter.next();
+                }
                 None => {
                     self.add_diagnostic(ErrorCode::UnexpectedEof, iter.loc());
                     return true;
@@ -233,6 +1037,7 @@ impl<'a> Parser<'a> {
 
     /// Consume an iterator to an expected keyword or EOF
     /// Errors are written to `self.diagnoser`
+    /// Stop at the start of a new statement if the expected keyword is missing
     /// Return true if eof met otherwise false
     #[must_use]
     fn consume_to_key(
@@ -243,32 +1048,50 @@ impl<'a> Parser<'a> {
     ) -> bool {
         fn test_match(key_type: Discriminant<Keyword>, iter: &TokenIterator) -> bool {
             if let Some(Token::Key(k)) = iter.peek() {
-                if key_type == std::mem::discriminant(k) {
+                if key_type == core::mem::discriminant(k) {
                     return true;
                 }
             }
             false
         }
-        let key_type = std::mem::discriminant(&expected);
+        let key_type = core::mem::discriminant(&expected);
 
         if test_match(key_type, iter) {
             iter.next();
             return false;
-        } else {
-            let t = iter.next().cloned();
-            let loc_now = iter.loc();
-            self.add_diagnostic(
-                ErrorCode::UnexpectedToken(t, Some(Token::Key(expected)), previous),
-                loc_now,
-            );
+        }
+        let expected = Some(Token::Key(expected));
+        match iter.peek() {
+            // Assume the expected token is missing if a new statement seems to start
+            Some(t) if is_sync_token(t, true) || self.at_line_start(iter) => {
+                let t = t.clone();
+                let loc = iter.next_loc();
+                self.add_diagnostic(ErrorCode::UnexpectedToken(Some(t), expected, previous), loc);
+                return false;
+            }
+            Some(_) => {
+                let t = iter.next().cloned();
+                let loc_now = iter.loc();
+                self.add_diagnostic(ErrorCode::UnexpectedToken(t, expected, previous), loc_now);
+            }
+            None => {
+                self.add_diagnostic(
+                    ErrorCode::UnexpectedToken(None, expected, previous),
+                    iter.loc(),
+                );
+                return true;
+            }
         }
         loop {
             if test_match(key_type, iter) {
                 iter.next();
                 return false;
             }
-            match iter.next() {
-                Some(_) => (),
+            match iter.peek() {
+                Some(t) if is_sync_token(t, true) || self.at_line_start(iter) => return false,
+                Some(_) => {
+                    iter.next();
+                }
                 None => {
                     self.add_diagnostic(ErrorCode::UnexpectedEof, iter.loc());
                     return true;
@@ -283,6 +1106,7 @@ impl<'a> Parser<'a> {
         let start = iter.loc();
         // match `condition`
         let condition = self.consume_expr(iter, 0, Some(Key(Then)));
+        self.check_parentheses(&condition);
         // match `then`
         if !self.consume_to_key(iter, Then, Some((Key(If), start))) {
             Some(condition)
@@ -291,7 +1115,77 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Regroup a statement around `=` and check every assigned target
+    ///
+    /// As `=` binds tighter than `,`, parallel assignment `a, b = b, a` is parsed as
+    /// `a, (b = b), a`, which is regrouped to `(a, b) = (b, a)`. Chained assignment `a = b = 0`
+    /// stays nested to the right.
+    fn consume_assignment(&mut self, expr: Expr) -> Expr {
+        let mut items = vec![];
+        flatten_comma(expr, &mut items);
+        let Some(i) = items.iter().position(|item| {
+            matches!(
+                item,
+                Expr::Infix {
+                    op: OpInfix::Assign,
+                    ..
+                }
+            )
+        }) else {
+            return join_comma(items);
+        };
+        let rest = items.split_off(i + 1);
+        let Some(Expr::Infix { lhs, rhs, .. }) = items.pop() else {
+            unreachable!()
+        };
+        items.push(*lhs);
+        for target in items.iter() {
+            let target = match target {
+                Expr::Parentheses { content, .. } => content,
+                target => target,
+            };
+            let mut targets = vec![];
+            flatten_comma(target.clone(), &mut targets);
+            for target in targets.iter() {
+                if let Some(what) = invalid_target(target) {
+                    self.add_diagnostic(ErrorCode::InvalidAssignTarget(what), target.get_loc());
+                }
+            }
+        }
+        let target_count = items.len();
+        let lhs = join_comma(items);
+        let rhs = self.consume_assignment(join_comma([vec![*rhs], rest].concat()));
+        if let Expr::Infix {
+            op: OpInfix::Comma, ..
+        } = &rhs
+        {
+            let mut values = vec![];
+            flatten_comma(rhs.clone(), &mut values);
+            if target_count > 1 && values.len() != target_count {
+                self.add_diagnostic(
+                    ErrorCode::AssignCountMismatch(target_count, values.len()),
+                    rhs.get_loc(),
+                );
+            }
+        }
+        join(lhs, OpInfix::Assign, rhs)
+    }
+
+    /// Warn if a condition is wrapped in parentheses
+    fn check_parentheses(&mut self, condition: &Expr) {
+        if let Expr::Parentheses { loc, .. } = condition {
+            self.add_warning(WarningCode::UnnecessaryParentheses, loc.clone());
+        }
+    }
+
     fn consume_if(&mut self, iter: &mut TokenIterator) -> Expr {
+        self.open_block(Keyword::If, iter.next_loc());
+        let expr = self.consume_if_(iter);
+        self.close_block();
+        expr
+    }
+
+    fn consume_if_(&mut self, iter: &mut TokenIterator) -> Expr {
         use Keyword::*;
         use Token::*;
         iter.next();
@@ -307,6 +1201,17 @@ impl<'a> Parser<'a> {
         // match block
         let mut block: Vec<Stmt> = vec![];
         loop {
+            if self.check_missing_end(iter) {
+                if iter.peek().is_none() {
+                    return Expr::Error;
+                }
+                conditional.push((condition, block));
+                return Expr::If {
+                    loc: start + iter.loc(),
+                    conditional,
+                    default: None,
+                };
+            }
             match iter.peek() {
                 Some(Key(Elsif)) => {
                     iter.next();
@@ -326,6 +1231,16 @@ impl<'a> Parser<'a> {
                     block = vec![];
                     iter.next();
                     loop {
+                        if self.check_missing_end(iter) {
+                            if iter.peek().is_none() {
+                                return Expr::Error;
+                            }
+                            return Expr::If {
+                                loc: start + iter.loc(),
+                                conditional,
+                                default: Some(block),
+                            };
+                        }
                         match iter.peek() {
                             Some(Key(End)) => {
                                 iter.next();
@@ -379,7 +1294,7 @@ impl<'a> Parser<'a> {
         loop {
             match expr {
                 Expr::Id { name, .. } => {
-                    item.push(name);
+                    item.push(name.into());
                     item.reverse();
                     return Ok(item);
                 }
@@ -390,7 +1305,7 @@ impl<'a> Parser<'a> {
                     ..
                 } => {
                     if let Expr::Id { name, .. } = *rhs {
-                        item.push(name);
+                        item.push(name.into());
                         expr = *lhs;
                     } else {
                         return Err(());
@@ -406,10 +1321,17 @@ impl<'a> Parser<'a> {
         use Token::*;
         let start = iter.next_loc();
         let path = self.consume_expr(iter, 3, None);
-        let path = self.convert_expr_to_import(path)?;
+        // Errors in the expression itself are reported by `consume_expr`
+        let reported = matches!(path, Expr::Error);
+        let Ok(path) = self.convert_expr_to_import(path) else {
+            if !reported {
+                self.add_diagnostic(ErrorCode::InvalidImport, start + iter.loc());
+            }
+            return Err(());
+        };
         let mut alias = None;
         if let (Some(Key(As)), Some(Id(name))) = iter.peek2() {
-            alias = Some(name.clone());
+            alias = Some(name.to_string());
             iter.next();
             iter.next();
         }
@@ -426,6 +1348,10 @@ impl<'a> Parser<'a> {
             return try_get_mod(&PathBuf::new(), mod_path, self.file_manager);
         }
 
+        if let Some(f) = try_load_mod(mod_path, self.file_manager) {
+            return Some(f);
+        }
+
         if let Some(f) = self
             .relative_path
             .as_ref()
@@ -434,11 +1360,46 @@ impl<'a> Parser<'a> {
             return Some(f);
         }
 
+        if let Some(f) = self
+            .packages
+            .iter()
+            .filter(|(name, _)| *name == mod_path[0])
+            .find_map(|(_, path)| try_get_mod(path, &mod_path[1..], self.file_manager))
+        {
+            return Some(f);
+        }
+
         self.search_path
             .iter()
             .find_map(|path| try_get_mod(path, mod_path, self.file_manager))
     }
 
+    /// Find module `module` and parse it, return its fid
+    fn import_module(&mut self, module: &[String], loc: &Loc) -> Option<usize> {
+        let Some((fid, path)) = self.resolve_mod(module) else {
+            self.add_diagnostic(ErrorCode::ModuleNotFound, loc.clone());
+            return None;
+        };
+
+        if let Some(import_loc) = self.import_stack.get(&fid) {
+            self.add_diagnostic(ErrorCode::CircularImport(import_loc.clone()), loc.clone());
+            return None;
+        }
+
+        let prev_path = if let Some(path) = path.parent() {
+            core::mem::replace(&mut self.relative_path, Some(PathBuf::from(path)))
+        } else {
+            None
+        };
+
+        let fid_prev = self.fid;
+        self.parse_fid(fid, Some(loc.clone()));
+        self.fid = fid_prev;
+
+        self.relative_path = prev_path;
+        Some(fid)
+    }
+
     fn consume_import(&mut self, iter: &mut TokenIterator) -> Stmt {
         use Keyword::*;
         use Operator::*;
@@ -483,42 +1444,27 @@ impl<'a> Parser<'a> {
                 if let Ok(item) = self.consume_import_item(iter) {
                     if let Some(Key(From)) = iter.peek() {
                         import_items.push(item);
-                    } else {
-                        let end = iter.loc();
-
-                        let module = self.resolve_mod(&item.path);
-                        let (fid, path) = match module {
-                            Some(module) => module,
-                            None => {
-                                self.add_diagnostic(ErrorCode::ModuleNotFound, start + end);
-                                return Stmt::Error;
-                            }
-                        };
+                    } else {
+                        let end = iter.loc();
 
-                        if let Some(loc) = self.import_stack.get(&fid) {
-                            self.add_diagnostic(
-                                ErrorCode::CircularImport(loc.clone()),
-                                start + end,
-                            );
-                            return Stmt::Error;
+                        if !self.resolve_imports {
+                            return Stmt::Import {
+                                loc: start + end,
+                                fid: 0,
+                                module: item.path.clone(),
+                                items: vec![item],
+                                direct_import_mod: true,
+                            };
                         }
 
-                        let prev_path = if let Some(path) = path.parent() {
-                            std::mem::replace(&mut self.relative_path, Some(PathBuf::from(path)))
-                        } else {
-                            None
-                        };
-
                         let loc = start + end;
-                        let fid_prev = self.fid;
-                        self.parse_fid(fid, Some(loc.clone()));
-                        self.fid = fid_prev;
-
-                        self.relative_path = prev_path;
-
+                        let Some(fid) = self.import_module(&item.path, &loc) else {
+                            return Stmt::Error;
+                        };
                         return Stmt::Import {
                             loc,
                             fid,
+                            module: item.path.clone(),
                             items: vec![item],
                             direct_import_mod: true,
                         };
@@ -534,6 +1480,9 @@ impl<'a> Parser<'a> {
         };
 
         let from = self.consume_expr(iter, 0, None);
+        if let Expr::Error = from {
+            return Stmt::Error;
+        }
         let loc = from.get_loc();
         let from = self.convert_expr_to_import(from);
         let from = match from {
@@ -545,41 +1494,36 @@ impl<'a> Parser<'a> {
         };
         let import_loc = start + iter.loc();
 
-        let module = self.resolve_mod(&from);
-        let (fid, path) = match module {
-            Some(module) => module,
-            None => {
-                self.add_diagnostic(ErrorCode::ModuleNotFound, import_loc);
-                return Stmt::Error;
-            }
-        };
-
-        if let Some(loc) = self.import_stack.get(&fid) {
-            self.add_diagnostic(ErrorCode::CircularImport(loc.clone()), import_loc);
-            return Stmt::Error;
+        if !self.resolve_imports {
+            return Stmt::Import {
+                loc: import_loc,
+                fid: 0,
+                module: from,
+                items: import_items,
+                direct_import_mod: false,
+            };
         }
 
-        let prev_path = if let Some(path) = path.parent() {
-            std::mem::replace(&mut self.relative_path, Some(PathBuf::from(path)))
-        } else {
-            None
+        let Some(fid) = self.import_module(&from, &import_loc) else {
+            return Stmt::Error;
         };
-
-        let fid_prev = self.fid;
-        self.parse_fid(fid, Some(import_loc.clone()));
-        self.fid = fid_prev;
-
-        self.relative_path = prev_path;
-
         Stmt::Import {
             loc: import_loc,
             fid,
+            module: from,
             items: import_items,
             direct_import_mod: false,
         }
     }
 
     fn consume_for(&mut self, iter: &mut TokenIterator) -> Stmt {
+        self.open_block(Keyword::For, iter.next_loc());
+        let stmt = self.consume_for_(iter);
+        self.close_block();
+        stmt
+    }
+
+    fn consume_for_(&mut self, iter: &mut TokenIterator) -> Stmt {
         use Keyword::*;
         use Token::*;
         iter.next();
@@ -595,6 +1539,17 @@ impl<'a> Parser<'a> {
         };
         let mut body = vec![];
         loop {
+            if self.check_missing_end(iter) {
+                if iter.peek().is_none() {
+                    return Stmt::Error;
+                }
+                return Stmt::For {
+                    loc: start + iter.loc(),
+                    loop_variable: Box::new(vars),
+                    iterator: Box::new(iterator),
+                    body,
+                };
+            }
             match iter.peek() {
                 Some(Key(End)) => {
                     iter.next();
@@ -615,6 +1570,17 @@ impl<'a> Parser<'a> {
     }
 
     fn consume_loop(&mut self, iter: &mut TokenIterator) -> Stmt {
+        let keyword = match iter.peek() {
+            Some(Token::Key(keyword)) => *keyword,
+            _ => unreachable!(),
+        };
+        self.open_block(keyword, iter.next_loc());
+        let stmt = self.consume_loop_(iter);
+        self.close_block();
+        stmt
+    }
+
+    fn consume_loop_(&mut self, iter: &mut TokenIterator) -> Stmt {
         use Keyword::*;
         use Token::*;
         let key = iter.next().cloned();
@@ -626,6 +1592,7 @@ impl<'a> Parser<'a> {
                 if matches!(iter.peek(), None) {
                     return Stmt::Error;
                 };
+                self.check_parentheses(&stmt);
                 if self.consume_to_key(iter, Do, Some((Key(Until), start.clone()))) {
                     return Stmt::Error;
                 };
@@ -635,6 +1602,16 @@ impl<'a> Parser<'a> {
         };
         let mut body = vec![];
         loop {
+            if self.check_missing_end(iter) {
+                if iter.peek().is_none() {
+                    return Stmt::Error;
+                }
+                return Stmt::Loop {
+                    loc: start + iter.loc(),
+                    condition,
+                    body,
+                };
+            }
             match iter.peek() {
                 Some(Key(End)) => {
                     iter.next();
@@ -665,6 +1642,10 @@ impl<'a> Parser<'a> {
                 Some(Id(name)) => {
                     parameters.push((name.clone(), iter.next_loc()));
                     iter.next();
+                    // Parameters may be separated by commas, with a trailing one
+                    if let Some(Op(Comma)) = iter.peek() {
+                        iter.next();
+                    }
                 }
                 Some(Op(Assign)) => {
                     iter.next();
@@ -674,7 +1655,7 @@ impl<'a> Parser<'a> {
                     self.add_diagnostic(
                         ErrorCode::UnexpectedToken(
                             Some(token.clone()),
-                            Some(Id("<parameter>".to_string())),
+                            Some(Id("<parameter>".into())),
                             Some((Key(Fn), start.clone())),
                         ),
                         iter.loc(),
@@ -695,7 +1676,53 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parse `@decorator` lines followed by `def`
+    fn consume_decorated(&mut self, iter: &mut TokenIterator) -> Stmt {
+        use Keyword::*;
+        use Operator::*;
+        use Token::*;
+        let start = iter.next_loc();
+        let mut decorators = vec![];
+        while let Some(Op(At)) = iter.peek() {
+            iter.next();
+            decorators.push(self.consume_expr(iter, 23, None));
+        }
+        match iter.peek() {
+            Some(Key(Def)) => match self.consume_def(iter) {
+                Stmt::Def {
+                    loc,
+                    variable,
+                    parameters,
+                    body,
+                    ..
+                } => Stmt::Def {
+                    loc: start + loc,
+                    decorators,
+                    variable,
+                    parameters,
+                    body,
+                },
+                stmt => stmt,
+            },
+            token => {
+                let token = token.cloned();
+                self.add_diagnostic(
+                    ErrorCode::UnexpectedToken(token, Some(Key(Def)), Some((Op(At), start))),
+                    iter.next_loc(),
+                );
+                Stmt::Error
+            }
+        }
+    }
+
     fn consume_def(&mut self, iter: &mut TokenIterator) -> Stmt {
+        self.open_block(Keyword::Def, iter.next_loc());
+        let stmt = self.consume_def_(iter);
+        self.close_block();
+        stmt
+    }
+
+    fn consume_def_(&mut self, iter: &mut TokenIterator) -> Stmt {
         use Keyword::*;
         use Operator::*;
         use Token::*;
@@ -710,6 +1737,10 @@ impl<'a> Parser<'a> {
                     iter.next();
                     let loc = iter.loc();
                     parameters.push((name, loc));
+                    // Parameters may be separated by commas, with a trailing one
+                    if let Some(Op(Comma)) = iter.peek() {
+                        iter.next();
+                    }
                 }
                 Some(Op(Assign)) => {
                     iter.next();
@@ -730,11 +1761,24 @@ impl<'a> Parser<'a> {
         }
         let mut body = vec![];
         loop {
+            if self.check_missing_end(iter) {
+                if iter.peek().is_none() {
+                    return Stmt::Error;
+                }
+                return Stmt::Def {
+                    loc: start + iter.loc(),
+                    decorators: vec![],
+                    variable: Box::new(variable),
+                    parameters,
+                    body,
+                };
+            }
             match iter.peek() {
                 Some(Key(End)) => {
                     iter.next();
                     return Stmt::Def {
                         loc: start + iter.loc(),
+                        decorators: vec![],
                         variable: Box::new(variable),
                         parameters,
                         body,
@@ -749,11 +1793,242 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Consume `enum Name Variant(field, ...) ... end`, variants may be separated by commas
+    fn consume_enum(&mut self, iter: &mut TokenIterator) -> Stmt {
+        use Keyword::*;
+        use Operator::*;
+        use Token::*;
+        iter.next();
+        let start = iter.loc();
+        let Some(Id(name)) = iter.next() else {
+            unreachable!()
+        };
+        let name = name.clone();
+        let name_loc = iter.loc();
+        let mut variants: Vec<Variant> = vec![];
+        loop {
+            match iter.peek() {
+                Some(Key(End)) => {
+                    iter.next();
+                    return Stmt::Enum {
+                        loc: start + iter.loc(),
+                        name,
+                        name_loc,
+                        variants,
+                    };
+                }
+                Some(Id(variant)) => {
+                    let variant = variant.clone();
+                    iter.next();
+                    let loc = iter.loc();
+                    if let Some(prev) = variants.iter().find(|prev| prev.name == variant) {
+                        self.add_diagnostic(
+                            ErrorCode::DuplicateKey(prev.loc.clone(), variant.to_string()),
+                            loc.clone(),
+                        );
+                    }
+                    let fields = match iter.peek() {
+                        Some(Op(LPar)) => match self.consume_fields(iter) {
+                            Some(fields) => Some(fields),
+                            None => return Stmt::Error,
+                        },
+                        _ => None,
+                    };
+                    variants.push(Variant {
+                        loc,
+                        name: variant,
+                        fields,
+                    });
+                    if let Some(Op(Comma)) = iter.peek() {
+                        iter.next();
+                    }
+                }
+                Some(token) => {
+                    self.add_diagnostic(
+                        ErrorCode::UnexpectedToken(Some(token.clone()), None, None),
+                        iter.next_loc(),
+                    );
+                    iter.next();
+                }
+                None => {
+                    self.add_diagnostic(ErrorCode::UnexpectedEof, iter.loc());
+                    return Stmt::Error;
+                }
+            }
+        }
+    }
+
+    /// Consume `interface Name method ... end`, methods may be separated by commas
+    fn consume_interface(&mut self, iter: &mut TokenIterator) -> Stmt {
+        use Keyword::*;
+        use Operator::*;
+        use Token::*;
+        iter.next();
+        let start = iter.loc();
+        let Some(Id(name)) = iter.next() else {
+            unreachable!()
+        };
+        let name = name.clone();
+        let name_loc = iter.loc();
+        let mut methods: Vec<(Symbol, Loc)> = vec![];
+        loop {
+            match iter.peek() {
+                Some(Key(End)) => {
+                    iter.next();
+                    return Stmt::Interface {
+                        loc: start + iter.loc(),
+                        name,
+                        name_loc,
+                        methods,
+                    };
+                }
+                Some(Id(method)) => {
+                    let method = method.clone();
+                    iter.next();
+                    let loc = iter.loc();
+                    if let Some((_, prev)) = methods.iter().find(|(prev, _)| *prev == method) {
+                        self.add_diagnostic(
+                            ErrorCode::DuplicateKey(prev.clone(), method.to_string()),
+                            loc.clone(),
+                        );
+                    }
+                    methods.push((method, loc));
+                    if let Some(Op(Comma)) = iter.peek() {
+                        iter.next();
+                    }
+                }
+                Some(token) => {
+                    self.add_diagnostic(
+                        ErrorCode::UnexpectedToken(Some(token.clone()), None, None),
+                        iter.next_loc(),
+                    );
+                    iter.next();
+                }
+                None => {
+                    self.add_diagnostic(ErrorCode::UnexpectedEof, iter.loc());
+                    return Stmt::Error;
+                }
+            }
+        }
+    }
+
+    /// Consume `macro name(parameter, ...) ... end`
+    fn consume_macro(&mut self, iter: &mut TokenIterator) -> Stmt {
+        use Keyword::*;
+        use Operator::*;
+        use Token::*;
+        iter.next();
+        let start = iter.loc();
+        let Some(Id(name)) = iter.next() else {
+            unreachable!()
+        };
+        let name = name.clone();
+        let name_loc = iter.loc();
+        let parameters = match iter.peek() {
+            Some(Op(LPar)) => match self.consume_fields(iter) {
+                Some(parameters) => parameters,
+                None => return Stmt::Error,
+            },
+            token => {
+                let token = token.cloned();
+                self.add_diagnostic(
+                    ErrorCode::UnexpectedToken(token, Some(Op(LPar)), None),
+                    iter.next_loc(),
+                );
+                return Stmt::Error;
+            }
+        };
+        let mut body = vec![];
+        loop {
+            match iter.peek() {
+                Some(Key(End)) => {
+                    iter.next();
+                    return Stmt::Macro {
+                        loc: start + iter.loc(),
+                        name,
+                        name_loc,
+                        parameters,
+                        body,
+                    };
+                }
+                Some(_) => body.push(self.consume_stmt(iter, Some(Key(End)))),
+                None => {
+                    self.add_diagnostic(ErrorCode::UnexpectedEof, iter.loc());
+                    return Stmt::Error;
+                }
+            }
+        }
+    }
+
+    /// Consume `(field, ...)` of an enum variant or parameters of a macro
+    fn consume_fields(&mut self, iter: &mut TokenIterator) -> Option<Vec<(Symbol, Loc)>> {
+        use Operator::*;
+        use Token::*;
+        iter.next();
+        let start = iter.loc();
+        let mut fields: Vec<(Symbol, Loc)> = vec![];
+        loop {
+            match iter.next() {
+                Some(Op(RPar)) => return Some(fields),
+                Some(Id(field)) => {
+                    let field = field.clone();
+                    let loc = iter.loc();
+                    if let Some((_, prev)) = fields.iter().find(|(prev, _)| *prev == field) {
+                        self.add_diagnostic(
+                            ErrorCode::DuplicateKey(prev.clone(), field.to_string()),
+                            loc.clone(),
+                        );
+                    }
+                    fields.push((field, loc));
+                    match iter.peek() {
+                        Some(Op(Comma)) => {
+                            iter.next();
+                        }
+                        Some(Op(RPar)) => (),
+                        token => {
+                            let token = token.cloned();
+                            self.add_diagnostic(
+                                ErrorCode::UnexpectedToken(
+                                    token,
+                                    Some(Op(RPar)),
+                                    Some((Op(LPar), start.clone())),
+                                ),
+                                iter.next_loc(),
+                            );
+                            return None;
+                        }
+                    }
+                }
+                token => {
+                    let token = token.cloned();
+                    self.add_diagnostic(ErrorCode::UnexpectedToken(token, None, None), iter.loc());
+                    return None;
+                }
+            }
+        }
+    }
+
     fn consume_block(&mut self, iter: &mut TokenIterator) -> Expr {
+        self.open_block(Keyword::Begin, iter.next_loc());
+        let expr = self.consume_block_(iter);
+        self.close_block();
+        expr
+    }
+
+    fn consume_block_(&mut self, iter: &mut TokenIterator) -> Expr {
         iter.next();
         let start = iter.loc();
         let mut body: Vec<Stmt> = vec![];
         loop {
+            if self.check_missing_end(iter) {
+                if iter.peek().is_none() {
+                    return Expr::Error;
+                }
+                return Expr::Block {
+                    loc: start + iter.loc(),
+                    body,
+                };
+            }
             match iter.peek() {
                 Some(Token::Key(Keyword::End)) => {
                     iter.next();
@@ -790,89 +2065,54 @@ impl<'a> Parser<'a> {
                 value: Const::Table(vec![]),
             };
         }
-        let mut key_vals: Vec<(String, Expr, Loc)> = vec![];
-        let mut content = self.consume_expr(iter, 0, Some(Op(RBrc)));
+        let mut key_vals: Vec<(Option<String>, Expr, Loc)> = vec![];
+        let content = self.consume_expr(iter, 0, Some(Op(RBrc)));
         if self.consume_to_op(iter, RBrc, Some((Op(LBrc), start.clone()))) {
             return Expr::Error;
         };
 
-        loop {
-            match content {
+        let mut entries = vec![];
+        flatten_comma(content, &mut entries);
+        for entry in entries {
+            match entry {
                 Expr::Infix {
                     loc,
                     op: OpInfix::Assign,
                     lhs,
                     rhs,
                 } => {
-                    let lhs = *lhs;
-                    let (name, name_loc) = match lhs {
-                        Expr::Id { loc, name } => (name, loc),
-                        _ => {
+                    let (name, name_loc) = match *lhs {
+                        Expr::Id { loc, name } => (String::from(name), loc),
+                        lhs => {
                             self.add_diagnostic(ErrorCode::InvalidTableKey, lhs.get_loc());
                             return Expr::Error;
                         }
                     };
-                    if let Some((name, _, prev_loc)) =
-                        key_vals.iter().find(|(prev, _, _)| prev == &name)
+                    if let Some((_, _, prev_loc)) = key_vals
+                        .iter()
+                        .find(|(prev, _, _)| prev.as_ref() == Some(&name))
                     {
                         self.add_diagnostic(
-                            ErrorCode::DuplicateKey(prev_loc.clone(), name.clone()),
+                            ErrorCode::DuplicateKey(prev_loc.clone(), name),
                             name_loc,
                         );
                         return Expr::Error;
                     }
-                    key_vals.push((name, *rhs, loc));
-                    break;
+                    key_vals.push((Some(name), *rhs, loc));
                 }
-                Expr::Infix {
-                    loc,
-                    op: OpInfix::Comma,
-                    lhs,
-                    rhs,
-                } => {
-                    match *rhs {
-                        Expr::Infix {
-                            loc,
-                            op: OpInfix::Assign,
-                            lhs,
-                            rhs,
-                        } => {
-                            let lhs = *lhs;
-                            let (name, name_loc) = match lhs {
-                                Expr::Id { loc, name } => (name, loc),
-                                _ => {
-                                    self.add_diagnostic(ErrorCode::InvalidTableKey, lhs.get_loc());
-                                    return Expr::Error;
-                                }
-                            };
-                            if let Some((name, _, prev_loc)) =
-                                key_vals.iter().find(|(prev, _, _)| prev == &name)
-                            {
-                                self.add_diagnostic(
-                                    ErrorCode::DuplicateKey(prev_loc.clone(), name.clone()),
-                                    name_loc,
-                                );
-                                return Expr::Error;
-                            }
-                            key_vals.push((name, *rhs, loc));
-                        }
-                        _ => {
-                            self.add_diagnostic(ErrorCode::InvalidTableFormat, loc);
-                            return Expr::Error;
-                        }
-                    }
-                    content = *lhs;
+                Expr::Spread { ref loc, .. } => {
+                    let loc = loc.clone();
+                    key_vals.push((None, entry, loc));
                 }
-                Expr::Error => return content,
-                _ => {
-                    self.add_diagnostic(ErrorCode::InvalidTableFormat, content.get_loc());
+                Expr::Error => return Expr::Error,
+                entry => {
+                    self.add_diagnostic(ErrorCode::InvalidTableFormat, entry.get_loc());
                     return Expr::Error;
                 }
             }
         }
 
         let end = iter.loc();
-        key_vals.reverse();
         Expr::Const {
             loc: start + end,
             value: Const::Table(key_vals),
@@ -889,6 +2129,21 @@ impl<'a> Parser<'a> {
         iter: &mut TokenIterator,
         min_precedence: u16,
         not_take_on_error: Option<Token>,
+    ) -> Expr {
+        if self.nesting_too_deep(iter) {
+            return Expr::Error;
+        }
+        self.depth += 1;
+        let expr = self.consume_expr_(iter, min_precedence, not_take_on_error);
+        self.depth -= 1;
+        expr
+    }
+
+    fn consume_expr_(
+        &mut self,
+        iter: &mut TokenIterator,
+        min_precedence: u16,
+        not_take_on_error: Option<Token>,
     ) -> Expr {
         use Keyword::*;
         use Operator::*;
@@ -941,7 +2196,7 @@ impl<'a> Parser<'a> {
                 if matches!(iter.peek(), Some(Op(RPar))) {
                     iter.next();
                     Expr::Const {
-                        loc: start.clone(),
+                        loc: start.clone() + iter.loc(),
                         value: Const::Unit,
                     }
                 } else {
@@ -950,7 +2205,7 @@ impl<'a> Parser<'a> {
                         return Expr::Error;
                     };
                     Expr::Parentheses {
-                        loc: start.clone(),
+                        loc: start.clone() + iter.loc(),
                         content: Box::new(lhs),
                     }
                 }
@@ -1013,10 +2268,10 @@ impl<'a> Parser<'a> {
                 let should_not_consume = if let Some(t_avoid) = not_take_on_error {
                     match (token, t_avoid) {
                         (Op(op), Op(op_avoid)) => {
-                            std::mem::discriminant(op) == std::mem::discriminant(&op_avoid)
+                            core::mem::discriminant(op) == core::mem::discriminant(&op_avoid)
                         }
                         (Key(k), Key(k_avoid)) => {
-                            std::mem::discriminant(k) == std::mem::discriminant(&k_avoid)
+                            core::mem::discriminant(k) == core::mem::discriminant(&k_avoid)
                         }
                         _ => false, // Other check is useless
                     }
@@ -1051,11 +2306,38 @@ impl<'a> Parser<'a> {
                     };
                     continue;
                 }
+                (Some(Op(Spread)), _) if min_precedence <= precedence_spread() => {
+                    iter.next();
+                    lhs = Expr::Spread {
+                        loc: start.clone() + iter.loc(),
+                        value: Box::new(lhs),
+                    };
+                    continue;
+                }
                 // Allow [1,2,] format
                 (Some(Op(Comma)), Some(Op(RPar | RBrk | RBrc))) => {
                     iter.next();
                     break;
                 }
+                (Some(Op(SafeMember)), Some(Op(LBrk))) => {
+                    if precedence_postfix() <= min_precedence {
+                        return lhs;
+                    }
+                    iter.next();
+                    iter.next();
+                    let match_loc = iter.loc();
+                    let expr = self.consume_expr(iter, 0, Some(Op(RBrk)));
+                    if self.consume_to_op(iter, RBrk, Some((Op(LBrk), match_loc))) {
+                        return Expr::Error;
+                    };
+                    lhs = Expr::Infix {
+                        loc: start.clone() + iter.loc(),
+                        op: OpInfix::SafeIndex,
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(expr),
+                    };
+                    continue;
+                }
                 (Some(Op(op @ (LBrk | LPar))), _) => {
                     let op = match op {
                         LPar => OpPostfix::Call,
@@ -1103,7 +2385,7 @@ impl<'a> Parser<'a> {
                                 lhs = Expr::Call {
                                     loc: start.clone() + iter.loc(),
                                     lhs: Box::new(lhs),
-                                    parameters: exprs,
+                                    parameters: exprs.into_iter().map(placeholder_fn).collect(),
                                 };
                                 continue;
                             }
@@ -1128,10 +2410,14 @@ impl<'a> Parser<'a> {
                 }
                 (Some(op), _) => match op {
                     Op(Assign) => OpInfix::Assign,
+                    Op(Pipe) => OpInfix::Pipe,
                     Op(Range) => OpInfix::Range,
                     Op(Or) => OpInfix::Or,
                     Op(And) => OpInfix::And,
                     Op(Is) => OpInfix::Is,
+                    // `in` ends loop variables of `for`, which are parsed to stop at it
+                    Key(In) if !matches!(not_take_on_error, Some(Key(In))) => OpInfix::In,
+                    Op(Not) if matches!(iter.peek2(), (_, Some(Key(In)))) => OpInfix::NotIn,
                     Op(Eq) => OpInfix::Eq,
                     Op(Ne) => OpInfix::Ne,
                     Op(Le) => OpInfix::Le,
@@ -1147,6 +2433,8 @@ impl<'a> Parser<'a> {
                     Op(Exp) => OpInfix::Exp,
                     Op(Comma) => OpInfix::Comma,
                     Op(Member) => OpInfix::Member,
+                    Op(SafeMember) => OpInfix::SafeMember,
+                    Op(Coalesce) => OpInfix::Coalesce,
                     Op(DoubleColon) => OpInfix::DoubleColon,
                     Op(LArrow) => OpInfix::LArrow,
                     _ => return lhs,
@@ -1160,6 +2448,9 @@ impl<'a> Parser<'a> {
             }
 
             iter.next();
+            if op == OpInfix::NotIn {
+                iter.next();
+            }
 
             let rhs = self.consume_expr(iter, precedence.1, not_take_on_error.clone());
 
@@ -1175,9 +2466,15 @@ impl<'a> Parser<'a> {
     }
 
     pub fn add_diagnostic(&mut self, error: ErrorCode, loc: Loc) {
+        if self.too_deep || self.stmt_errors >= MAX_STMT_ERRORS {
+            return;
+        }
+        self.stmt_errors += 1;
         let eof = matches!(
             error,
-            ErrorCode::UnexpectedEof | ErrorCode::UnexpectedToken(None, _, _)
+            ErrorCode::UnexpectedEof
+                | ErrorCode::UnexpectedToken(None, _, _)
+                | ErrorCode::MissingEnd(_, None)
         );
         let diag = match error {
         ErrorCode::UnexpectedToken(met, expected, to_match) => {
@@ -1244,8 +2541,62 @@ impl<'a> Parser<'a> {
             .with_code("E1008")
             .with_message("Not allowed in import statement")
             .with_labels(vec![Label::primary(self.fid, loc)]),
+        ErrorCode::MissingEnd(keyword, closing) => {
+            let mut labels = vec![Label::primary(self.fid, loc).with_message(format!("This `{keyword}` is never closed"))];
+            if let Some(closing) = closing {
+                labels.push(Label::secondary(self.fid, closing).with_message("This belongs to an outer block"));
+            }
+            Diagnostic::error()
+                .with_code("E1009")
+                .with_message(format!("Missing `end` for `{keyword}`"))
+                .with_labels(labels)
+                .with_notes(vec!["Consider add an `end` at the end of the block".to_string()])
+        }
+        ErrorCode::NestingTooDeep => Diagnostic::error()
+            .with_code("E1010")
+            .with_message("Nesting is too deep")
+            .with_labels(vec![Label::primary(self.fid, loc)])
+            .with_notes(vec![format!(
+                "Statements and expressions can be nested at most {MAX_NESTING_DEPTH} levels"
+            )]),
+        ErrorCode::InvalidAssignTarget(what) => Diagnostic::error()
+            .with_code("E1011")
+            .with_message(format!("Can not assign to {what}"))
+            .with_labels(vec![Label::primary(self.fid, loc)])
+            .with_notes(vec![
+                "Only variables, indexes and members can be assigned to".to_string(),
+            ]),
+        ErrorCode::AssignCountMismatch(targets, values) => Diagnostic::error()
+            .with_code("E1012")
+            .with_message(format!("Assign {values} values to {targets} targets"))
+            .with_labels(vec![Label::primary(self.fid, loc)])
+            .with_notes(vec![format!("Expected {targets} values here")]),
+        ErrorCode::MacroArgumentCount(macro_loc, name, parameters, arguments) => Diagnostic::error()
+            .with_code("E1013")
+            .with_message(format!("Macro `{name}` takes {parameters} arguments but {arguments} are given"))
+            .with_labels(vec![Label::primary(self.fid, loc), Label::secondary(self.fid, macro_loc).with_message("Macro defined here")]),
+        ErrorCode::MacroExpansionTooDeep(name) => Diagnostic::error()
+            .with_code("E1014")
+            .with_message(format!("Expansion of macro `{name}` is too deep"))
+            .with_labels(vec![Label::primary(self.fid, loc)])
+            .with_notes(vec![format!(
+                "Macros can be expanded inside one another at most {MAX_EXPANSION_DEPTH} levels, a macro may not call itself"
+            )]),
     };
 
         self.file_manager.add_diagnostic(diag, eof);
     }
+    pub fn add_warning(&mut self, warning: WarningCode, loc: Loc) {
+        let warning = match warning {
+            WarningCode::UnnecessaryParentheses => Warning {
+                name: "unnecessary-parentheses",
+                diagnostic: Diagnostic::warning()
+                    .with_code("W1500")
+                    .with_message("Unnecessary parentheses around condition")
+                    .with_labels(vec![Label::primary(self.fid, loc)])
+                    .with_notes(vec!["Consider remove these parentheses".to_string()]),
+            },
+        };
+        self.file_manager.add_warning(warning);
+    }
 }
@@ -1,4 +1,5 @@
-use std::{fs, path::PathBuf};
+use crate::compat::path::PathBuf;
+use alloc::string::String;
 
 use crate::file_manager::FileManager;
 
@@ -20,11 +21,22 @@ fn try_read_path(path: PathBuf, file_manager: &mut FileManager) -> Option<(usize
     if let Some(fid) = file_manager.look_up_fid(&path) {
         return Some((fid, path));
     }
-    fs::read_to_string(&path)
+    file_manager
+        .read_file(&path)
         .map(|content| (file_manager.add_file(path.clone(), content), path))
         .ok()
 }
 
+/// Module from the loader supplied by host, parsed as file `import/path.dm`
+pub fn try_load_mod(import: &[String], file_manager: &mut FileManager) -> Option<(usize, PathBuf)> {
+    let (path, _) = join_search_path(PathBuf::new(), import);
+    if let Some(fid) = file_manager.look_up_fid(&path) {
+        return Some((fid, path));
+    }
+    let content = file_manager.load_module(&import.join("."))?;
+    Some((file_manager.add_file(path.clone(), content), path))
+}
+
 pub fn try_get_mod(
     search_path: &PathBuf,
     import: &[String],
@@ -1,4 +1,17 @@
 use super::*;
+use crate::file_manager::{ColorChoice, RenderOptions};
+
+/// Number of errors and rendered diagnostics of parsing `code`
+fn errors(code: &str) -> (usize, String) {
+    let mut file_manager = FileManager::new();
+    let paths = vec![];
+    let mut parser = Parser::new(&mut file_manager, &paths);
+    let _ = parser.parse_file("test", code);
+    (
+        file_manager.error_count(),
+        file_manager.render(&RenderOptions::default()),
+    )
+}
 
 fn test_str(code: &str, should_fail: bool) {
     let mut file_manager = FileManager::new();
@@ -6,7 +19,10 @@ fn test_str(code: &str, should_fail: bool) {
     let mut parser = Parser::new(&mut file_manager, &paths);
     let _ = parser.parse_file("test", code);
     if !should_fail && file_manager.error_count() > 0 {
-        print!("{}", file_manager.render(true));
+        print!(
+            "{}",
+            file_manager.render(&RenderOptions::default().color(ColorChoice::Always))
+        );
     }
     if should_fail {
         assert!(file_manager.error_count() > 0);
@@ -23,7 +39,10 @@ fn test_expr_postfix_ambiguous() {
     let mut parser = Parser::new(&mut file_manager, &paths);
     let fid = parser.parse_file("test", code);
     if file_manager.error_count() > 0 {
-        print!("{}", file_manager.render(true));
+        print!(
+            "{}",
+            file_manager.render(&RenderOptions::default().color(ColorChoice::Always))
+        );
     }
     assert_eq!(file_manager.error_count(), 0);
     assert_eq!(file_manager.get_ast(fid).len(), 3);
@@ -76,6 +95,54 @@ fn test_def() {
     test_str("def x::a a b c = a+b+1 fn x = x end", false);
 }
 
+#[test]
+fn test_enum() {
+    test_str("enum Shape Circle(r) Rect(w, h) Empty end", false);
+    test_str("enum Color\n    Red,\n    Green,\nend", false);
+    test_str("enum Unit V() end enum = 1 enum + 1 t.enum()", false);
+    test_str("enum Shape Circle(r) Circle end", true);
+    test_str("enum Shape Rect(w, w) end", true);
+    test_str("enum Shape Circle(1) end", true);
+    test_str("enum Shape Circle(r)", true);
+}
+
+#[test]
+fn test_interface() {
+    test_str("interface Show show, name end", false);
+    test_str("interface Empty end interface = 1 t.interface()", false);
+    test_str("interface Show show show end", true);
+    test_str("interface Show show(x) end", true);
+    test_str("interface Show show", true);
+}
+
+#[test]
+fn test_decorator() {
+    test_str("@d def f = 1 end", false);
+    test_str("@a @b(1) def T.m x = x end", false);
+    test_str("@a.b\n@c::d(1, 2)\ndef f x = x end", false);
+    test_str("@d x = 1", true);
+    test_str("@ def f = 1 end", true);
+}
+
+#[test]
+fn test_macro() {
+    test_str(
+        "macro times(n, body) for _ in 0..n do body end end times(2, x)",
+        false,
+    );
+    test_str("macro unit() end unit()", false);
+    test_str("macro = 1 macro(2)", false);
+    test_str("macro m(a, a) a end", true);
+    test_str("macro m a end", true);
+    test_str("macro m(a) a", true);
+    test_str("macro m(a) a end m(1, 2)", true);
+    test_str("macro m(x) m(x) end m(1)", true);
+    // Invalid statements of a body are reported where the macro is defined
+    let (count, text) = errors("macro s()end\nmacro swap(a,b)import a=b\nend\nswap(x,y)");
+    assert!(count > 0, "{text}");
+    assert!(text.contains("Not allowed in import statement"), "{text}");
+}
+
 #[test]
 fn test_fn() {
     test_str("fn = 1", false);
@@ -96,6 +163,8 @@ fn test_statement() {
     );
     test_str("if false then break continue else end", false);
     test_str("return", false);
+    test_str("def f = defer g() defer x = 1 end", false);
+    test_str("defer", true);
     test_str("def x = return 1 end", false);
     test_str("def x = return end ", false);
     test_str("fn x = x <= 1", false);
@@ -118,3 +187,288 @@ fn test_table() {
     test_str("{ a = 1, b= 3, c= 'abc'}", false);
     test_str("{loop = 1}", true);
 }
+
+#[test]
+fn test_recovery() {
+    // Missing `end` is reported at the block opener
+    let (count, text) = errors("def f x =\n    if x then\n        1\n\nend\ny = 2\n");
+    assert_eq!(count, 1, "{text}");
+    assert!(text.contains("Missing `end` for `if`"), "{text}");
+    assert!(text.contains("2 |     if x then"), "{text}");
+    let (count, text) = errors("if a then\n  for x in y do\n    1\nelse\n  2\nend\n");
+    assert_eq!(count, 1, "{text}");
+    assert!(text.contains("Missing `end` for `for`"), "{text}");
+    let (count, text) = errors("begin\n  x = 1\n");
+    assert_eq!(count, 1, "{text}");
+    assert!(text.contains("Missing `end` for `begin`"), "{text}");
+
+    // Errors do not cascade into following statements
+    let (count, text) = errors("x = (1 + 2\ny = 3\nz = )\n");
+    assert_eq!(count, 2, "{text}");
+    let (count, text) = errors("for x in [1, 2 do\n  print(x)\nend\nw = 1\n");
+    assert_eq!(count, 1, "{text}");
+    let (count, text) = errors("y = ] ] ]\nz = ) )\n");
+    assert_eq!(count, 2, "{text}");
+}
+
+#[test]
+fn test_fuzz_regressions() {
+    // Escape at end of file
+    for code in ["x = \"\\", "'\\"] {
+        let (_, text) = errors(code);
+        assert!(text.contains("String literal is not terminated"), "{text}");
+    }
+    // Invalid module of import
+    let (count, _) = errors("import x from )");
+    assert!(count > 0);
+    // Invalid import paths
+    for code in [
+        "import-0",
+        "import -x",
+        "import (1)",
+        "import {a, -1} from b",
+    ] {
+        let (count, text) = errors(code);
+        assert!(count > 0, "{text}");
+        assert!(text.contains("Not allowed in import statement"), "{text}");
+    }
+
+    // Deep nesting is reported once instead of overflowing stack
+    for open in [
+        "(",
+        "[",
+        "{",
+        "- ",
+        "fn = ",
+        "begin ",
+        "if x then ",
+        "loop ",
+    ] {
+        let (count, text) = errors(&format!("{}1\ny = 2", open.repeat(1000)));
+        assert_eq!(count, 1, "{text}");
+        assert!(text.contains("Nesting is too deep"), "{text}");
+    }
+    let code = format!("x = {}1{}", "(".repeat(100), ")".repeat(100));
+    assert_eq!(errors(&code).0, 0);
+}
+
+#[test]
+fn test_trailing_comma() {
+    for code in [
+        "[1, 2,]",
+        "[\n  1,\n  2,\n]",
+        "f(1, 2,)",
+        "f(\n  1,\n)",
+        "{a = 1, b = 2,}",
+        "def f a, b, = a + b end",
+        "def f a b, = a + b end",
+        "g = fn x, y, = x * y",
+        "map(fn x, = x, [1,])",
+    ] {
+        test_str(code, false);
+    }
+    for code in ["[,]", "f(,)", "[1,,]", "def f , a = a end", "fn a,, = a"] {
+        test_str(code, true);
+    }
+}
+
+#[test]
+fn test_precedence() {
+    /// Parenthesize every operation of the only expression in `code`
+    fn grouped(code: &str) -> String {
+        fn show(expr: &Expr) -> String {
+            match expr {
+                Expr::Prefix { op, rhs, .. } => {
+                    let op = if *op == OpPrefix::Neg { "-" } else { "not " };
+                    format!("({op}{})", show(rhs))
+                }
+                Expr::Infix { op, lhs, rhs, .. } => {
+                    format!("({} {op:?} {})", show(lhs), show(rhs))
+                }
+                Expr::Call { lhs, .. } => format!("{}()", show(lhs)),
+                Expr::Id { name, .. } => name.to_string(),
+                Expr::Const {
+                    value: Const::Int(i),
+                    ..
+                } => i.to_string(),
+                expr => panic!("Unexpected expression {expr:?}"),
+            }
+        }
+        let mut file_manager = FileManager::new();
+        let fid = Parser::new(&mut file_manager, &[]).parse_file("test", code);
+        assert_eq!(
+            file_manager.error_count(),
+            0,
+            "{}",
+            file_manager.render(&RenderOptions::default())
+        );
+        match file_manager.get_ast(fid).as_slice() {
+            [Stmt::Expr { expr, .. }] => show(expr),
+            stmts => panic!("Expected an expression, found {stmts:?}"),
+        }
+    }
+
+    assert_eq!(grouped("-2 ** 2"), "(-(2 Exp 2))");
+    assert_eq!(grouped("-2 * 3"), "((-2) Mul 3)");
+    assert_eq!(grouped("2 ** -1"), "(2 Exp (-1))");
+    assert_eq!(grouped("2 ** 3 ** 2"), "(2 Exp (3 Exp 2))");
+    assert_eq!(grouped("a ** -b ** c"), "(a Exp (-(b Exp c)))");
+    assert_eq!(grouped("not a ** b"), "(not (a Exp b))");
+    assert_eq!(grouped("not a == b"), "((not a) Eq b)");
+    assert_eq!(grouped("-f() ** 2"), "(-(f() Exp 2))");
+    assert_eq!(grouped("1 - 2 - 3"), "((1 Minus 2) Minus 3)");
+    assert_eq!(grouped("a ?? b ?? c"), "(a Coalesce (b Coalesce c))");
+    assert_eq!(grouped("a ?? 1 + 2 == b"), "((a Coalesce (1 Plus 2)) Eq b)");
+    assert_eq!(grouped("a?.b.c()"), "((a SafeMember b) Member c)()");
+    assert_eq!(grouped("-a?.[b]"), "(-(a SafeIndex b))");
+    assert_eq!(
+        grouped("a = b |> f |> g()"),
+        "(a Assign ((b Pipe f) Pipe g()))"
+    );
+    assert_eq!(grouped("1..2 |> f"), "((1 Range 2) Pipe f)");
+    assert_eq!(grouped("a + 1 in b and c"), "(((a Plus 1) In b) And c)");
+    assert_eq!(grouped("not a not in b"), "((not a) NotIn b)");
+
+    let table = precedence_table();
+    assert!(table.windows(2).all(|w| w[0].precedence <= w[1].precedence));
+    let find = |symbol, fixity| {
+        table
+            .iter()
+            .find(|op| op.symbol == symbol && op.fixity == fixity)
+            .unwrap()
+    };
+    let exp = find("**", Fixity::Infix);
+    assert_eq!(exp.associativity, Associativity::Right);
+    assert!(find("-", Fixity::Prefix).precedence < exp.precedence);
+    assert!(find("-", Fixity::Prefix).precedence > find("*", Fixity::Infix).precedence);
+    assert_eq!(find("=", Fixity::Infix).associativity, Associativity::Right);
+    assert_eq!(table.len(), 33);
+}
+
+#[test]
+fn test_assignment_target() {
+    for code in [
+        "a, b = b, a",
+        "a = b = 0",
+        "(a, b) = 1, 2",
+        "x.a, x[0], x.0 = 1, 2, 3",
+        "a = 1, 2",
+        "{a = 1, b = 2}",
+    ] {
+        test_str(code, false);
+    }
+    for (code, message) in [
+        ("1 = x", "Can not assign to a literal"),
+        ("f() = 3", "Can not assign to a call result"),
+        ("a, 'b' = 1, 2", "Can not assign to a literal"),
+        ("a = [] = 1", "Can not assign to a list literal"),
+        ("a + b = 1", "Can not assign to the result of an operator"),
+        ("a, b = 1, 2, 3", "Assign 3 values to 2 targets"),
+    ] {
+        let (count, output) = errors(code);
+        assert_eq!(count, 1, "{output}");
+        assert!(output.contains(message), "{output}");
+    }
+}
+
+#[test]
+fn test_spread() {
+    for code in [
+        "[xs...]",
+        "[1, xs..., ys..., 2,]",
+        "[a + b...]",
+        "{base..., a = 1}",
+        "{a = 1, base..., b = 2,}",
+    ] {
+        test_str(code, false);
+    }
+    for code in ["[...]", "{a... = 1}", "[1 xs...]"] {
+        test_str(code, true);
+    }
+}
+
+#[test]
+fn test_placeholder() {
+    let mut file_manager = FileManager::new();
+    let fid =
+        Parser::new(&mut file_manager, &[]).parse_file("test", "f(_ > 3, _, x, g(_.a) + _, _ = 1)");
+    assert_eq!(file_manager.error_count(), 0);
+    let ast = file_manager.get_ast(fid);
+    let [Stmt::Expr {
+        expr: Expr::Call { parameters, .. },
+        ..
+    }] = ast.as_slice()
+    else {
+        panic!("Expected a call, found {ast:?}");
+    };
+    let placeholders: Vec<_> = parameters.iter().map(Expr::is_placeholder_fn).collect();
+    assert_eq!(placeholders, [true, true, false, true, false]);
+    // `_` of `g(_.a)` belongs to `g`
+    let Expr::Fn { body, .. } = &parameters[3] else {
+        unreachable!()
+    };
+    let Expr::Infix { lhs, .. } = body.as_ref() else {
+        panic!("Expected an infix expression, found {body:?}");
+    };
+    let Expr::Call { parameters, .. } = lhs.as_ref() else {
+        panic!("Expected a call, found {lhs:?}");
+    };
+    assert!(parameters[0].is_placeholder_fn());
+    assert!(!Expr::Fn {
+        loc: parameters[0].get_loc(),
+        parameters: vec![(
+            "_".into(),
+            Loc {
+                start: 0,
+                end: 1,
+                fid
+            }
+        )],
+        body: Box::new(Expr::Error),
+    }
+    .is_placeholder_fn());
+}
+
+#[test]
+fn test_parse_many() {
+    let dir = std::env::temp_dir().join(format!("diatom_parse_many_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let files = [
+        ("a.dm", "import b\nx = b.y"),
+        ("b.dm", "y = 1"),
+        ("c.dm", "begin\n    import {y} from b\nend\nz = )"),
+        ("d.dm", "import missing"),
+    ];
+    for (name, content) in files {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+    let paths: Vec<_> = files.iter().map(|(name, _)| dir.join(name)).collect();
+
+    let mut file_manager = FileManager::new();
+    let fids = Parser::new(&mut file_manager, &[])
+        .parse_many(&paths)
+        .unwrap();
+    assert_eq!(fids, [0, 1, 2, 3]);
+    assert_eq!(file_manager.look_up_fid(&paths[1]), Some(1));
+    assert!(matches!(
+        file_manager.get_ast(0)[0],
+        Stmt::Import { fid: 1, .. }
+    ));
+    let Stmt::Expr {
+        expr: Expr::Block { body, .. },
+        ..
+    } = &file_manager.get_ast(2)[0]
+    else {
+        panic!("Expected a block");
+    };
+    assert!(matches!(body[0], Stmt::Import { fid: 1, .. }));
+    assert!(matches!(file_manager.get_ast(3)[0], Stmt::Error));
+    assert_eq!(file_manager.error_count(), 2);
+    let rendered = file_manager.render(&RenderOptions::default());
+    assert!(rendered.find("c.dm").unwrap() < rendered.find("d.dm").unwrap());
+
+    assert!(Parser::new(&mut FileManager::new(), &[])
+        .parse_many(&[dir.join("none.dm")])
+        .is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,403 @@
+//! Traversal of the syntax tree
+//!
+//! Override methods of `Visitor` or `MutVisitor` to handle nodes of interest, and call the
+//! matching `walk_*` function inside to keep visiting their children.
+use crate::file_manager::Loc;
+
+use super::ast::{Const, Expr, Stmt};
+
+/// Visit nodes of a syntax tree in source order
+pub trait Visitor {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    /// Called for the location of every node, parameter, import item and table entry
+    fn visit_loc(&mut self, _loc: &Loc) {}
+}
+
+/// Visit location and children of `stmt`
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::Expr { loc, expr } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(expr);
+        }
+        Stmt::Continue { loc } | Stmt::Break { loc } => visitor.visit_loc(loc),
+        Stmt::Return { loc, value } => {
+            visitor.visit_loc(loc);
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        Stmt::Defer { loc, body } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(body);
+        }
+        Stmt::Loop {
+            loc,
+            condition,
+            body,
+        } => {
+            visitor.visit_loc(loc);
+            if let Some(condition) = condition {
+                visitor.visit_expr(condition);
+            }
+            body.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Stmt::For {
+            loc,
+            loop_variable,
+            iterator,
+            body,
+        } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(loop_variable);
+            visitor.visit_expr(iterator);
+            body.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Stmt::Def {
+            loc,
+            decorators,
+            variable,
+            parameters,
+            body,
+        } => {
+            visitor.visit_loc(loc);
+            decorators
+                .iter()
+                .for_each(|decorator| visitor.visit_expr(decorator));
+            visitor.visit_expr(variable);
+            parameters
+                .iter()
+                .for_each(|(_, loc)| visitor.visit_loc(loc));
+            body.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Stmt::Interface {
+            loc,
+            name_loc,
+            methods,
+            ..
+        } => {
+            visitor.visit_loc(loc);
+            visitor.visit_loc(name_loc);
+            methods.iter().for_each(|(_, loc)| visitor.visit_loc(loc));
+        }
+        Stmt::Enum {
+            loc,
+            name_loc,
+            variants,
+            ..
+        } => {
+            visitor.visit_loc(loc);
+            visitor.visit_loc(name_loc);
+            variants.iter().for_each(|variant| {
+                visitor.visit_loc(&variant.loc);
+                variant
+                    .fields
+                    .iter()
+                    .flatten()
+                    .for_each(|(_, loc)| visitor.visit_loc(loc));
+            });
+        }
+        Stmt::Macro {
+            loc,
+            name_loc,
+            parameters,
+            body,
+            ..
+        } => {
+            visitor.visit_loc(loc);
+            visitor.visit_loc(name_loc);
+            parameters
+                .iter()
+                .for_each(|(_, loc)| visitor.visit_loc(loc));
+            body.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Stmt::Import { loc, items, .. } => {
+            visitor.visit_loc(loc);
+            items.iter().for_each(|item| visitor.visit_loc(&item.loc));
+        }
+        Stmt::Error => (),
+    }
+}
+
+/// Visit location and children of `expr`
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Block { loc, body } => {
+            visitor.visit_loc(loc);
+            body.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Expr::If {
+            loc,
+            conditional,
+            default,
+        } => {
+            visitor.visit_loc(loc);
+            for (condition, body) in conditional {
+                visitor.visit_expr(condition);
+                body.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+            }
+            default
+                .iter()
+                .flatten()
+                .for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Expr::Prefix { loc, rhs, .. } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(rhs);
+        }
+        Expr::Call {
+            loc,
+            lhs,
+            parameters,
+        } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(lhs);
+            parameters.iter().for_each(|expr| visitor.visit_expr(expr));
+        }
+        Expr::Index { loc, lhs, rhs } | Expr::Infix { loc, lhs, rhs, .. } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        Expr::OpenRange { loc, lhs } | Expr::Spread { loc, value: lhs } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(lhs);
+        }
+        Expr::Fn {
+            loc,
+            parameters,
+            body,
+        } => {
+            visitor.visit_loc(loc);
+            parameters
+                .iter()
+                .for_each(|(_, loc)| visitor.visit_loc(loc));
+            visitor.visit_expr(body);
+        }
+        Expr::Id { loc, .. } => visitor.visit_loc(loc),
+        Expr::Parentheses { loc, content } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(content);
+        }
+        Expr::Const { loc, value } => {
+            visitor.visit_loc(loc);
+            match value {
+                Const::List(items) => items.iter().for_each(|expr| visitor.visit_expr(expr)),
+                Const::Table(items) => items.iter().for_each(|(_, expr, loc)| {
+                    visitor.visit_loc(loc);
+                    visitor.visit_expr(expr);
+                }),
+                _ => (),
+            }
+        }
+        Expr::Error => (),
+    }
+}
+
+/// Visit nodes of a syntax tree in source order, allowing them to be modified
+pub trait MutVisitor {
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    /// Called for the location of every node, parameter, import item and table entry
+    fn visit_loc(&mut self, _loc: &mut Loc) {}
+}
+
+/// Visit location and children of `stmt`
+pub fn walk_stmt_mut<V: MutVisitor + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Expr { loc, expr } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(expr);
+        }
+        Stmt::Continue { loc } | Stmt::Break { loc } => visitor.visit_loc(loc),
+        Stmt::Return { loc, value } => {
+            visitor.visit_loc(loc);
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        Stmt::Defer { loc, body } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(body);
+        }
+        Stmt::Loop {
+            loc,
+            condition,
+            body,
+        } => {
+            visitor.visit_loc(loc);
+            if let Some(condition) = condition {
+                visitor.visit_expr(condition);
+            }
+            body.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Stmt::For {
+            loc,
+            loop_variable,
+            iterator,
+            body,
+        } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(loop_variable);
+            visitor.visit_expr(iterator);
+            body.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Stmt::Def {
+            loc,
+            decorators,
+            variable,
+            parameters,
+            body,
+        } => {
+            visitor.visit_loc(loc);
+            decorators
+                .iter_mut()
+                .for_each(|decorator| visitor.visit_expr(decorator));
+            visitor.visit_expr(variable);
+            parameters
+                .iter_mut()
+                .for_each(|(_, loc)| visitor.visit_loc(loc));
+            body.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Stmt::Interface {
+            loc,
+            name_loc,
+            methods,
+            ..
+        } => {
+            visitor.visit_loc(loc);
+            visitor.visit_loc(name_loc);
+            methods
+                .iter_mut()
+                .for_each(|(_, loc)| visitor.visit_loc(loc));
+        }
+        Stmt::Enum {
+            loc,
+            name_loc,
+            variants,
+            ..
+        } => {
+            visitor.visit_loc(loc);
+            visitor.visit_loc(name_loc);
+            variants.iter_mut().for_each(|variant| {
+                visitor.visit_loc(&mut variant.loc);
+                variant
+                    .fields
+                    .iter_mut()
+                    .flatten()
+                    .for_each(|(_, loc)| visitor.visit_loc(loc));
+            });
+        }
+        Stmt::Macro {
+            loc,
+            name_loc,
+            parameters,
+            body,
+            ..
+        } => {
+            visitor.visit_loc(loc);
+            visitor.visit_loc(name_loc);
+            parameters
+                .iter_mut()
+                .for_each(|(_, loc)| visitor.visit_loc(loc));
+            body.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Stmt::Import { loc, items, .. } => {
+            visitor.visit_loc(loc);
+            items
+                .iter_mut()
+                .for_each(|item| visitor.visit_loc(&mut item.loc));
+        }
+        Stmt::Error => (),
+    }
+}
+
+/// Visit location and children of `expr`
+pub fn walk_expr_mut<V: MutVisitor + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Block { loc, body } => {
+            visitor.visit_loc(loc);
+            body.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Expr::If {
+            loc,
+            conditional,
+            default,
+        } => {
+            visitor.visit_loc(loc);
+            for (condition, body) in conditional {
+                visitor.visit_expr(condition);
+                body.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt));
+            }
+            default
+                .iter_mut()
+                .flatten()
+                .for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+        Expr::Prefix { loc, rhs, .. } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(rhs);
+        }
+        Expr::Call {
+            loc,
+            lhs,
+            parameters,
+        } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(lhs);
+            parameters
+                .iter_mut()
+                .for_each(|expr| visitor.visit_expr(expr));
+        }
+        Expr::Index { loc, lhs, rhs } | Expr::Infix { loc, lhs, rhs, .. } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        Expr::OpenRange { loc, lhs } | Expr::Spread { loc, value: lhs } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(lhs);
+        }
+        Expr::Fn {
+            loc,
+            parameters,
+            body,
+        } => {
+            visitor.visit_loc(loc);
+            parameters
+                .iter_mut()
+                .for_each(|(_, loc)| visitor.visit_loc(loc));
+            visitor.visit_expr(body);
+        }
+        Expr::Id { loc, .. } => visitor.visit_loc(loc),
+        Expr::Parentheses { loc, content } => {
+            visitor.visit_loc(loc);
+            visitor.visit_expr(content);
+        }
+        Expr::Const { loc, value } => {
+            visitor.visit_loc(loc);
+            match value {
+                Const::List(items) => items.iter_mut().for_each(|expr| visitor.visit_expr(expr)),
+                Const::Table(items) => items.iter_mut().for_each(|(_, expr, loc)| {
+                    visitor.visit_loc(loc);
+                    visitor.visit_expr(expr);
+                }),
+                _ => (),
+            }
+        }
+        Expr::Error => (),
+    }
+}
@@ -0,0 +1,77 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+};
+use codespan_reporting::diagnostic::Label;
+
+use crate::file_manager::{Diagnostic, Loc};
+
+/// Error code for `Resolver`
+///
+/// Shares `E2000` to `E2999` with the code generator, since both check names.
+pub enum ErrorCode {
+    /// E2011 Name read before it is defined later in a visible scope
+    ///
+    /// Parameters:
+    /// - 1 Name
+    /// - 2 Where it is read
+    /// - 3 Where it is defined
+    UsedBeforeDefinition(String, Loc, Loc),
+    /// E2012 Function defined by `def` or `fn` called with a wrong number of arguments
+    ///
+    /// Parameters:
+    /// - 1 Name of function
+    /// - 2 Where it is called
+    /// - 3 Where it is defined
+    /// - 4 Number of parameters
+    /// - 5 Number of arguments
+    ArityMismatch(String, Loc, Loc, usize, usize),
+    /// E2013 Name assigned in a nested block before it is declared, only in strict mode
+    ///
+    /// Parameters:
+    /// - 1 Name
+    /// - 2 Where it is assigned
+    /// - 3 Similar name if there is one
+    AssignToUndeclared(String, Loc, Option<String>),
+}
+
+impl From<ErrorCode> for Diagnostic {
+    fn from(value: ErrorCode) -> Self {
+        match value {
+            ErrorCode::UsedBeforeDefinition(name, loc, definition) => Diagnostic::error()
+                .with_code("E2011")
+                .with_message(format!("Name `{name}` is used before it is defined"))
+                .with_labels(vec![
+                    Label::primary(loc.fid, loc),
+                    Label::secondary(definition.fid, definition)
+                        .with_message(format!("`{name}` is defined here")),
+                ])
+                .with_notes(vec![
+                    "A name is only visible after the assignment that defines it".to_string(),
+                ]),
+            ErrorCode::ArityMismatch(name, loc, definition, expected, got) => Diagnostic::error()
+                .with_code("E2012")
+                .with_message(format!(
+                    "Function `{name}` takes {expected} parameters but {got} is provided"
+                ))
+                .with_labels(vec![
+                    Label::primary(loc.fid, loc),
+                    Label::secondary(definition.fid, definition)
+                        .with_message(format!("`{name}` is defined here")),
+                ]),
+            ErrorCode::AssignToUndeclared(name, loc, similar) => {
+                let mut notes = vec![
+                    "In strict mode, only assignments at top level of a file or function declare names"
+                        .to_string(),
+                ];
+                notes.extend(similar.map(|name| format!("Did you mean `{name}`?")));
+                Diagnostic::error()
+                    .with_code("E2013")
+                    .with_message(format!("Assign to undeclared name `{name}`"))
+                    .with_labels(vec![Label::primary(loc.fid, loc)])
+                    .with_notes(notes)
+            }
+        }
+    }
+}
@@ -0,0 +1,550 @@
+//! Bind names to their definitions before code generation
+//!
+//! Scopes are the same as those of the code generator: a name is visible after the assignment
+//! that defines it, blocks of `if`, `loop`, `for` and `begin` end the names defined inside,
+//! and functions see names of enclosing functions defined before them.
+mod error;
+
+use crate::compat::AHashMap;
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    file_manager::Loc,
+    frontend::{closest_name, Symbol},
+};
+
+use super::parser::{
+    ast::{Expr, ImportItem, OpInfix, Stmt},
+    visit::{walk_expr, walk_stmt, Visitor},
+};
+
+pub use error::ErrorCode;
+
+/// Where the value of a name is stored when it is read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+    /// Variable of the function reading it
+    Local,
+    /// Variable of an enclosing function, captured by the closure
+    Upvalue,
+    /// Variable at top level of the file, or one given by the host or the prelude
+    Global,
+}
+
+/// A name read and what it is bound to
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "unstable-ast"), allow(dead_code))]
+pub struct NameUse {
+    pub name: String,
+    pub loc: Loc,
+    pub binding: Binding,
+    /// Where the name is defined, `None` if it is not defined in the source
+    pub definition: Option<Loc>,
+}
+
+struct Definition {
+    name: Symbol,
+    /// Depth of the function defining it, 0 is top level
+    function: usize,
+    /// Defined in the outermost block of the file
+    global: bool,
+    /// `None` if defined outside the source
+    loc: Option<Loc>,
+    /// Number of parameters if it is defined as a function
+    arity: Option<usize>,
+    assignments: usize,
+}
+
+#[derive(Default)]
+struct Scope {
+    names: AHashMap<Symbol, usize>,
+    /// First assignment of every name in the block, whether it is reached yet or not
+    defined: AHashMap<String, Loc>,
+}
+
+/// Resolve names of a file, see [`Resolver::resolve`]
+pub struct Resolver<'a> {
+    is_global: Box<dyn Fn(&str) -> bool + 'a>,
+    definitions: Vec<Definition>,
+    /// Scopes of each function being resolved, innermost last
+    functions: Vec<Vec<Scope>>,
+    uses: Vec<NameUse>,
+    /// Location, callee and number of arguments of calls of a name
+    calls: Vec<(Loc, usize, usize)>,
+    errors: Vec<ErrorCode>,
+    strict: bool,
+}
+
+impl<'a> Resolver<'a> {
+    /// `is_global` tells whether a name not defined in the source exists, e.g. names of the
+    /// prelude or of previous inputs of a REPL
+    pub fn new(is_global: impl Fn(&str) -> bool + 'a) -> Self {
+        Self {
+            is_global: Box::new(is_global),
+            definitions: vec![],
+            functions: vec![vec![]],
+            uses: vec![],
+            calls: vec![],
+            errors: vec![],
+            strict: false,
+        }
+    }
+
+    /// In strict mode, assigning a new name in a nested block is an error
+    ///
+    /// Names are only declared by assignments in the outermost block of the file or of a function.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Bind every name read in `ast`, in source order
+    ///
+    /// Names that are neither defined nor global are bound as global without a definition, the
+    /// code generator reports them. Errors are names read before their definition, calls
+    /// with a wrong number of arguments to a function assigned only once, and assignments to
+    /// undeclared names in strict mode.
+    pub fn resolve(mut self, ast: &[Stmt]) -> (Vec<NameUse>, Vec<ErrorCode>) {
+        self.walk_block(ast);
+        for (loc, id, got) in core::mem::take(&mut self.calls) {
+            let callee = &self.definitions[id];
+            if let (Some(definition), Some(expected), 1) =
+                (&callee.loc, callee.arity, callee.assignments)
+            {
+                if expected != got {
+                    self.errors.push(ErrorCode::ArityMismatch(
+                        callee.name.to_string(),
+                        loc,
+                        definition.clone(),
+                        expected,
+                        got,
+                    ));
+                }
+            }
+        }
+        (self.uses, self.errors)
+    }
+
+    fn scopes(&mut self) -> &mut Vec<Scope> {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<usize> {
+        let found = self
+            .functions
+            .iter()
+            .rev()
+            .flat_map(|scopes| scopes.iter().rev())
+            .find_map(|scope| scope.names.get(name).copied());
+        if found.is_some() || !(self.is_global)(name) {
+            return found;
+        }
+        let id = self.definitions.len();
+        let name = Symbol::from(name);
+        self.definitions.push(Definition {
+            name: name.clone(),
+            function: 0,
+            global: true,
+            loc: None,
+            arity: None,
+            assignments: 1,
+        });
+        self.functions[0][0].names.insert(name, id);
+        Some(id)
+    }
+
+    fn define(&mut self, name: impl Into<Symbol>, loc: &Loc, arity: Option<usize>) {
+        let id = self.definitions.len();
+        let name = name.into();
+        self.definitions.push(Definition {
+            name: name.clone(),
+            function: self.functions.len() - 1,
+            global: self.functions.len() == 1 && self.functions[0].len() == 1,
+            loc: Some(loc.clone()),
+            arity,
+            assignments: 1,
+        });
+        self.scopes().last_mut().unwrap().names.insert(name, id);
+    }
+
+    /// Assign to an existing name, or define a new one in the innermost block
+    fn assign(&mut self, name: impl AsRef<str> + Into<Symbol>, loc: &Loc, arity: Option<usize>) {
+        match self.lookup(name.as_ref()) {
+            Some(id) => self.definitions[id].assignments += 1,
+            None => self.define(name, loc, arity),
+        }
+    }
+
+    /// Assign by an assignment statement, which only declares names at top level in strict mode
+    fn assign_variable(&mut self, name: &Symbol, loc: &Loc, arity: Option<usize>) {
+        let top_level = match self.functions.len() {
+            1 => self.scopes().len() == 1,
+            // Parameters are in a scope around the body
+            _ => self.scopes().len() <= 2,
+        };
+        // Names generated by macros and the parser are never declared by user
+        let generated = name.contains('@') || name.starts_with('#');
+        if self.strict && !top_level && !generated && self.lookup(name).is_none() {
+            let similar = closest_name(
+                name,
+                self.functions
+                    .iter()
+                    .flatten()
+                    .flat_map(|scope| scope.names.keys().map(Symbol::as_str)),
+            )
+            .map(str::to_string);
+            self.errors.push(ErrorCode::AssignToUndeclared(
+                name.to_string(),
+                loc.clone(),
+                similar,
+            ));
+        }
+        // Still define it, so that reads later are not reported again
+        self.assign(name, loc, arity);
+    }
+
+    fn read(&mut self, name: &str, loc: &Loc) -> Option<usize> {
+        let Some(id) = self.lookup(name) else {
+            let definition = self
+                .functions
+                .iter()
+                .rev()
+                .flat_map(|scopes| scopes.iter().rev())
+                .find_map(|scope| {
+                    scope
+                        .defined
+                        .get(name)
+                        .filter(|definition| definition.start > loc.start)
+                });
+            match definition {
+                Some(definition) => self.errors.push(ErrorCode::UsedBeforeDefinition(
+                    name.to_string(),
+                    loc.clone(),
+                    definition.clone(),
+                )),
+                None => self.uses.push(NameUse {
+                    name: name.to_string(),
+                    loc: loc.clone(),
+                    binding: Binding::Global,
+                    definition: None,
+                }),
+            }
+            return None;
+        };
+        let definition = &self.definitions[id];
+        let binding = if definition.global {
+            Binding::Global
+        } else if definition.function == self.functions.len() - 1 {
+            Binding::Local
+        } else {
+            Binding::Upvalue
+        };
+        self.uses.push(NameUse {
+            name: name.to_string(),
+            loc: loc.clone(),
+            binding,
+            definition: definition.loc.clone(),
+        });
+        Some(id)
+    }
+
+    fn walk_block(&mut self, body: &[Stmt]) {
+        let mut scope = Scope::default();
+        body.iter()
+            .for_each(|stmt| definitions(stmt, &mut scope.defined));
+        self.scopes().push(scope);
+        body.iter().for_each(|stmt| self.visit_stmt(stmt));
+        self.scopes().pop();
+    }
+
+    fn walk_function(&mut self, parameters: &[(Symbol, Loc)], walk_body: impl FnOnce(&mut Self)) {
+        self.functions.push(vec![Scope::default()]);
+        parameters
+            .iter()
+            .for_each(|(name, loc)| self.define(name, loc, None));
+        walk_body(self);
+        self.functions.pop();
+    }
+
+    fn walk_assignment(&mut self, lhs: &Expr, rhs: &Expr) {
+        match (lhs, rhs) {
+            (Expr::Id { name, loc }, rhs) if !is_assignment(rhs) => {
+                // A new name is visible in its own value, so that functions can recurse
+                let arity = match rhs {
+                    Expr::Fn { parameters, .. } => Some(parameters.len()),
+                    _ => None,
+                };
+                self.assign_variable(name, loc, arity);
+                self.visit_expr(rhs);
+            }
+            _ => {
+                // Value is evaluated before any target is assigned
+                let mut targets = vec![lhs];
+                let mut value = rhs;
+                while let Expr::Infix {
+                    op: OpInfix::Assign,
+                    lhs,
+                    rhs,
+                    ..
+                } = value
+                {
+                    targets.push(lhs);
+                    value = rhs;
+                }
+                self.visit_expr(value);
+                targets
+                    .into_iter()
+                    .rev()
+                    .for_each(|target| self.assign_target(target, true));
+            }
+        }
+    }
+
+    /// Assign names of `target`, by an assignment statement if `statement`
+    fn assign_target(&mut self, target: &Expr, statement: bool) {
+        match target {
+            Expr::Id { name, loc } if statement => self.assign_variable(name, loc, None),
+            Expr::Id { name, loc } => self.assign(name, loc, None),
+            Expr::Infix {
+                op: OpInfix::Comma,
+                lhs,
+                rhs,
+                ..
+            } => {
+                self.assign_target(lhs, statement);
+                self.assign_target(rhs, statement);
+            }
+            Expr::Parentheses { content, .. } => self.assign_target(content, statement),
+            target => self.visit_expr(target),
+        }
+    }
+
+    /// Visit callee of a call at `loc` with `arguments` arguments
+    fn visit_callee(&mut self, callee: &Expr, loc: &Loc, arguments: usize) {
+        match callee {
+            Expr::Id {
+                name,
+                loc: name_loc,
+            } => {
+                if let Some(id) = self.read(name, name_loc) {
+                    self.calls.push((loc.clone(), id, arguments));
+                }
+            }
+            callee => self.visit_expr(callee),
+        }
+    }
+}
+
+fn is_assignment(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Infix {
+            op: OpInfix::Assign,
+            ..
+        }
+    )
+}
+
+/// Add names assigned by `stmt` itself to `defined`, names assigned in nested blocks are not
+pub(crate) fn definitions(stmt: &Stmt, defined: &mut AHashMap<String, Loc>) {
+    let mut add = |name: &str, loc: &Loc| {
+        if !defined.contains_key(name) {
+            defined.insert(name.to_string(), loc.clone());
+        }
+    };
+    match stmt {
+        Stmt::Expr { expr, .. } => {
+            let mut expr = expr;
+            while let Expr::Infix {
+                op: OpInfix::Assign,
+                lhs,
+                rhs,
+                ..
+            } = expr
+            {
+                targets(lhs, &mut add);
+                expr = rhs;
+            }
+        }
+        Stmt::Def { variable, .. } => targets(variable, &mut add),
+        Stmt::Enum { name, name_loc, .. } | Stmt::Interface { name, name_loc, .. } => {
+            add(name, name_loc)
+        }
+        Stmt::Import { items, .. } => items
+            .iter()
+            .for_each(|item| add(import_name(item), &item.loc)),
+        _ => (),
+    }
+}
+
+/// Call `f` with every name assigned by assignment target `lhs`
+fn targets(lhs: &Expr, f: &mut impl FnMut(&str, &Loc)) {
+    match lhs {
+        Expr::Id { name, loc } => f(name, loc),
+        Expr::Infix {
+            op: OpInfix::Comma,
+            lhs,
+            rhs,
+            ..
+        } => {
+            targets(lhs, f);
+            targets(rhs, f);
+        }
+        Expr::Parentheses { content, .. } => targets(content, f),
+        _ => (),
+    }
+}
+
+fn import_name(item: &ImportItem) -> &String {
+    item.alias
+        .as_ref()
+        .unwrap_or_else(|| item.path.last().unwrap())
+}
+
+impl Visitor for Resolver<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr {
+                expr:
+                    Expr::Infix {
+                        op: OpInfix::Assign,
+                        lhs,
+                        rhs,
+                        ..
+                    },
+                ..
+            } => self.walk_assignment(lhs, rhs),
+            // Deferred body is compiled as a closure
+            Stmt::Defer { body, .. } => {
+                self.walk_function(&[], |resolver| resolver.visit_expr(body))
+            }
+            Stmt::Loop {
+                condition, body, ..
+            } => {
+                if let Some(condition) = condition {
+                    self.visit_expr(condition);
+                }
+                self.walk_block(body);
+            }
+            Stmt::For {
+                loop_variable,
+                iterator,
+                body,
+                ..
+            } => {
+                self.visit_expr(iterator);
+                // Loop variable is assigned in a block around the body
+                self.scopes().push(Scope::default());
+                self.assign_target(loop_variable, false);
+                self.walk_block(body);
+                self.scopes().pop();
+            }
+            Stmt::Def {
+                decorators,
+                variable,
+                parameters,
+                body,
+                ..
+            } => {
+                match variable.as_ref() {
+                    Expr::Id { name, loc } => self.assign(name, loc, Some(parameters.len())),
+                    variable => self.visit_expr(variable),
+                }
+                self.walk_function(parameters, |resolver| resolver.walk_block(body));
+                decorators
+                    .iter()
+                    .for_each(|decorator| self.visit_expr(decorator));
+            }
+            Stmt::Enum { name, name_loc, .. } | Stmt::Interface { name, name_loc, .. } => {
+                self.assign(name, name_loc, None)
+            }
+            Stmt::Import { items, .. } => items
+                .iter()
+                .for_each(|item| self.assign(import_name(item), &item.loc, None)),
+            // Calls of a macro not expanded read it as a function
+            Stmt::Macro {
+                name,
+                name_loc,
+                parameters,
+                body,
+                ..
+            } => {
+                self.assign(name, name_loc, Some(parameters.len()));
+                self.walk_function(parameters, |resolver| resolver.walk_block(body));
+            }
+            stmt => walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Id { name, loc } => {
+                self.read(name, loc);
+            }
+            Expr::Call {
+                loc,
+                lhs,
+                parameters,
+            } => {
+                self.visit_callee(lhs, loc, parameters.len());
+                parameters.iter().for_each(|expr| self.visit_expr(expr));
+            }
+            // `x |> f(y)` is `f(x, y)`
+            Expr::Infix {
+                loc,
+                op: OpInfix::Pipe,
+                lhs,
+                rhs,
+            } => {
+                self.visit_expr(lhs);
+                match rhs.as_ref() {
+                    Expr::Call {
+                        lhs: callee,
+                        parameters,
+                        ..
+                    } => {
+                        self.visit_callee(callee, loc, parameters.len() + 1);
+                        parameters.iter().for_each(|expr| self.visit_expr(expr));
+                    }
+                    callee => self.visit_callee(callee, loc, 1),
+                }
+            }
+            // Assignment not used as a statement is an error of the code generator
+            Expr::Infix {
+                op: OpInfix::Assign,
+                lhs,
+                rhs,
+                ..
+            } => self.walk_assignment(lhs, rhs),
+            // Names after `.` and `::` are keys
+            Expr::Infix {
+                op: OpInfix::Member | OpInfix::SafeMember | OpInfix::DoubleColon,
+                lhs,
+                ..
+            } => self.visit_expr(lhs),
+            Expr::Block { body, .. } => self.walk_block(body),
+            Expr::If {
+                conditional,
+                default,
+                ..
+            } => {
+                for (condition, body) in conditional {
+                    self.visit_expr(condition);
+                    self.walk_block(body);
+                }
+                if let Some(default) = default {
+                    self.walk_block(default);
+                }
+            }
+            Expr::Fn {
+                parameters, body, ..
+            } => self.walk_function(parameters, |resolver| resolver.visit_expr(body)),
+            expr => walk_expr(self, expr),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use alloc::{
+    borrow::Borrow,
+    string::{String, ToString},
+    sync::Arc,
+};
+use core::{
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+use crate::compat::AHashSet;
+
+/// An interned identifier
+///
+/// Cloning a symbol only bumps a reference count, and symbols from the same `SymbolTable`
+/// compare by pointer before falling back to their content.
+#[derive(Clone, PartialOrd, Ord)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl Eq for Symbol {}
+
+/// Same as `str` so that a symbol can be looked up by `&str`
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        &*self.0 == other.as_str()
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A symbol not shared with any table, for names made up by the compiler
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Self(name.into())
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Self(name.into())
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Self(name.as_str().into())
+    }
+}
+
+impl From<&Symbol> for Symbol {
+    fn from(symbol: &Symbol) -> Self {
+        symbol.clone()
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> Self {
+        symbol.0.to_string()
+    }
+}
+
+impl From<&Symbol> for String {
+    fn from(symbol: &Symbol) -> Self {
+        symbol.0.to_string()
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&*self.0, f)
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&*self.0, f)
+    }
+}
+
+/// Identifiers seen by the lexer, shared by all files of a `FileManager`
+#[derive(Clone, Default)]
+pub struct SymbolTable {
+    symbols: AHashSet<Symbol>,
+}
+
+impl SymbolTable {
+    /// The symbol of `name`, created if it is seen for the first time
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        match self.symbols.get(name) {
+            Some(symbol) => symbol.clone(),
+            None => {
+                let symbol = Symbol::from(name);
+                self.symbols.insert(symbol.clone());
+                symbol
+            }
+        }
+    }
+
+    /// Add symbols of another table that are not in this one
+    pub fn join(&mut self, other: Self) {
+        self.symbols.extend(other.symbols)
+    }
+}
@@ -1,4 +1,5 @@
-use std::str::Chars;
+use alloc::{vec, vec::Vec};
+use core::str::Chars;
 
 use crate::file_manager::Loc;
 
@@ -11,10 +12,11 @@ pub struct FileIterator<'a> {
 }
 
 impl<'a> FileIterator<'a> {
-    pub fn new(file: &'a str, fid: usize) -> Self {
+    /// Start from byte offset `offset` of `file`
+    pub fn at(file: &'a str, fid: usize, offset: usize) -> Self {
         Self {
-            offset: 0,
-            iterator: file.chars(),
+            offset,
+            iterator: file[offset..].chars(),
             fid,
         }
     }
@@ -45,7 +47,7 @@ impl<'a> FileIterator<'a> {
     }
 
     /// Return &str since current location
-    pub fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &'a str {
         self.iterator.as_str()
     }
 }
@@ -62,7 +64,7 @@ impl<'a> Iterator for FileIterator<'a> {
 }
 
 pub struct TokenIterator<'a> {
-    iter: std::slice::Iter<'a, (Token, Loc)>,
+    iter: core::slice::Iter<'a, (Token, Loc)>,
     loc: Loc,
 }
 
@@ -104,6 +106,11 @@ impl<'a> TokenIterator<'a> {
         self.loc.clone()
     }
 
+    /// Number of tokens not consumed yet
+    pub fn remaining(&self) -> usize {
+        self.iter.len()
+    }
+
     pub fn next_loc(&self) -> Loc {
         let mut iter = self.iter.clone();
         match iter.next() {
@@ -123,3 +130,49 @@ impl<'a> Iterator for TokenIterator<'a> {
         })
     }
 }
+
+/// Candidate closest to `name` in spelling, if it is close enough to be a typo
+pub fn closest_name<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        // Generated symbols are never written by user
+        if candidate == name || candidate.starts_with('#') {
+            continue;
+        }
+        let distance = edit_distance(name, candidate);
+        let better = match best {
+            Some((d, c)) => (distance, candidate) < (d, c),
+            None => true,
+        };
+        if distance <= max_distance && better {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, name)| name)
+}
+
+/// Edit distance counted in characters, where swapping adjacent characters is one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j] is the distance between a[..i] and b[..j]
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    (0..=a.len()).for_each(|i| d[i][0] = i);
+    (0..=b.len()).for_each(|j| d[0][j] = j);
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
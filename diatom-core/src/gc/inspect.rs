@@ -0,0 +1,250 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use crate::compat::AHashMap;
+
+use super::{Gc, GcObject, Reg};
+use crate::IoWrite;
+
+/// Default depth limit used by REPL echo and `inspect`
+pub const DEFAULT_INSPECT_DEPTH: usize = 8;
+
+const INDENT: &str = "    ";
+
+/// Limits of how values are shown by `print` and `inspect`
+///
+/// Containers nested deeper than `max_depth` are shown as `[...]`, `{...}` or `(...)`, and items
+/// of a container after the first `max_items` are elided as `...`. Unlimited if `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrintOptions {
+    pub max_depth: Option<usize>,
+    pub max_items: Option<usize>,
+}
+
+impl PrintOptions {
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_items(mut self, max_items: Option<usize>) -> Self {
+        self.max_items = max_items;
+        self
+    }
+}
+
+/// Pretty printer for structured values
+///
+/// Nested containers are rendered with indentation. If a container is reached again while it is
+/// still being printed, it is labeled as `#n` and the back reference is shown as `<cycle #n>`.
+struct Inspector<'a, Buffer: IoWrite> {
+    gc: &'a Gc<Buffer>,
+    max_depth: usize,
+    buffer: String,
+    /// Containers being printed and their offset in buffer
+    path: Vec<(usize, usize)>,
+    /// Cycle number of each reference
+    cycles: AHashMap<usize, usize>,
+    /// Labels to be inserted at given offset
+    labels: BTreeMap<usize, usize>,
+}
+
+fn escape_str(s: &str, buffer: &mut String) {
+    buffer.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => buffer.push_str("\\\""),
+            '\\' => buffer.push_str("\\\\"),
+            '\n' => buffer.push_str("\\n"),
+            '\r' => buffer.push_str("\\r"),
+            '\t' => buffer.push_str("\\t"),
+            '\0' => buffer.push_str("\\0"),
+            // Control characters are all below U+00A0
+            c if c.is_control() => write!(buffer, "\\x{:02x}", c as u32).unwrap(),
+            c => buffer.push(c),
+        }
+    }
+    buffer.push('"');
+}
+
+impl<'a, Buffer: IoWrite> Inspector<'a, Buffer> {
+    /// Check if a value is a non-empty container
+    fn is_nested(&self, reg: &Reg) -> bool {
+        match reg {
+            Reg::Ref(rid) => match self.gc.get_obj(*rid) {
+                Some(GcObject::List(l)) | Some(GcObject::Tuple(l)) => !l.is_empty(),
+                Some(GcObject::Table(t)) => match self.gc.variant_fields(t) {
+                    Some((_, fields)) => !fields.is_empty(),
+                    None => !t.attributes.is_empty() || !t.indices.is_empty(),
+                },
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn newline(&mut self, depth: usize) {
+        self.buffer.push('\n');
+        (0..depth).for_each(|_| self.buffer.push_str(INDENT));
+    }
+
+    /// Print items of a container with given delimiters
+    fn inspect_items<'b>(
+        &mut self,
+        (open, close): (&str, &str),
+        items: impl Iterator<Item = (Option<String>, &'b Reg)> + Clone,
+        depth: usize,
+    ) {
+        let max_items = self.gc.print_options.max_items.unwrap_or(usize::MAX);
+        let multiline = items
+            .clone()
+            .take(max_items)
+            .any(|(_, reg)| self.is_nested(reg));
+        self.buffer.push_str(open);
+        for (i, (key, reg)) in items.enumerate() {
+            if i > 0 {
+                self.buffer.push(',');
+                if !multiline {
+                    self.buffer.push(' ');
+                }
+            }
+            if multiline {
+                self.newline(depth + 1);
+            }
+            if i == max_items {
+                self.buffer.push_str("...");
+                break;
+            }
+            if let Some(key) = key {
+                write!(self.buffer, "{key} = ").unwrap();
+            }
+            self.inspect_reg(reg, depth + 1);
+        }
+        if multiline {
+            self.newline(depth);
+        }
+        self.buffer.push_str(close);
+    }
+
+    fn inspect_reg(&mut self, reg: &Reg, depth: usize) {
+        let rid = match reg {
+            Reg::Str(sid) => {
+                escape_str(self.gc.get_str(*sid).unwrap(), &mut self.buffer);
+                return;
+            }
+            Reg::Ref(rid) => *rid,
+            _ => {
+                self.buffer.push_str(&self.gc.print(reg));
+                return;
+            }
+        };
+
+        if let Some((_, offset)) = self.path.iter().find(|(r, _)| *r == rid) {
+            let offset = *offset;
+            let n = self.cycles.len() + 1;
+            let n = *self.cycles.entry(rid).or_insert(n);
+            self.labels.insert(offset, n);
+            write!(self.buffer, "<cycle #{n}>").unwrap();
+            return;
+        }
+
+        let gc = self.gc;
+        let obj = match gc.get_obj(rid) {
+            Some(obj) => obj,
+            None => {
+                write!(self.buffer, "<Invalid Ref@{rid}>").unwrap();
+                return;
+            }
+        };
+
+        let variant = match obj {
+            GcObject::Table(t) => gc.variant_fields(t),
+            _ => None,
+        };
+        if let Some((name, fields)) = &variant {
+            if fields.is_empty() {
+                self.buffer.push_str(name);
+                return;
+            }
+        }
+        let open = variant.as_ref().map(|(name, _)| format!("{name}("));
+        let delimiters = match obj {
+            GcObject::List(_) => ("[", "]"),
+            GcObject::Table(_) => match &open {
+                Some(open) => (open.as_str(), ")"),
+                None => ("{", "}"),
+            },
+            GcObject::Tuple(_) => ("(", ")"),
+            GcObject::Closure { .. }
+            | GcObject::NativeFunction(_)
+            | GcObject::BoundMethod { .. }
+            | GcObject::UserData => {
+                self.buffer.push_str(&gc.print(reg));
+                return;
+            }
+        };
+
+        if depth >= self.max_depth && self.is_nested(reg) {
+            write!(self.buffer, "{}...{}", delimiters.0, delimiters.1).unwrap();
+            return;
+        }
+
+        self.path.push((rid, self.buffer.len()));
+        match obj {
+            GcObject::List(l) | GcObject::Tuple(l) => {
+                self.inspect_items(delimiters, l.iter().map(|reg| (None, reg)), depth)
+            }
+            GcObject::Table(t) => match variant {
+                Some((_, fields)) => self.inspect_items(delimiters, fields.into_iter(), depth),
+                None => self.inspect_items(
+                    delimiters,
+                    t.indices
+                        .iter()
+                        .map(|(key, reg)| (Some(format!("[{key}]")), reg))
+                        .chain(t.attributes.iter().map(|(key, reg)| {
+                            (
+                                Some(gc.key_pool.look_up_key(*key).unwrap().to_string()),
+                                reg,
+                            )
+                        })),
+                    depth,
+                ),
+            },
+            _ => unreachable!(),
+        }
+        self.path.pop();
+    }
+
+    fn finish(mut self) -> String {
+        // Insert from back so that offsets are not invalidated
+        for (offset, n) in self.labels.iter().rev() {
+            self.buffer.insert_str(*offset, &format!("#{n} "));
+        }
+        self.buffer
+    }
+}
+
+impl<Buffer: IoWrite> Gc<Buffer> {
+    /// Pretty print a value
+    ///
+    /// Strings are quoted and escaped. Containers nested deeper than `max_depth` are shown as
+    /// `[...]`, `{...}` or `(...)`. Limits of print options also apply.
+    pub fn inspect(&self, reg: &Reg, max_depth: usize) -> String {
+        let mut inspector = Inspector {
+            gc: self,
+            max_depth: max_depth.min(self.print_options.max_depth.unwrap_or(usize::MAX)),
+            buffer: String::new(),
+            path: vec![],
+            cycles: AHashMap::default(),
+            labels: BTreeMap::new(),
+        };
+        inspector.inspect_reg(reg, 0);
+        inspector.finish()
+    }
+}
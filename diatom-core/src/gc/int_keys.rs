@@ -0,0 +1,73 @@
+use crate::compat::AHashMap;
+use alloc::vec::Vec;
+
+use super::Reg;
+
+/// Integer keys of a table
+///
+/// Keys `0..n` are stored densely in `array` so that tables used as arrays are not hashed,
+/// other keys are in `hash`. When a key extends the array, following keys are moved from
+/// `hash` so the array grows back after being filled out of order.
+#[derive(Default, Clone)]
+pub struct IntKeys {
+    array: Vec<Reg>,
+    hash: AHashMap<i64, Reg>,
+}
+
+impl IntKeys {
+    pub fn get(&self, key: i64) -> Option<&Reg> {
+        match usize::try_from(key) {
+            Ok(i) if i < self.array.len() => Some(&self.array[i]),
+            _ => self.hash.get(&key),
+        }
+    }
+
+    pub fn insert(&mut self, key: i64, value: Reg) {
+        match usize::try_from(key) {
+            Ok(i) if i < self.array.len() => self.array[i] = value,
+            Ok(i) if i == self.array.len() => {
+                self.array.push(value);
+                self.migrate();
+            }
+            _ => {
+                self.hash.insert(key, value);
+            }
+        }
+    }
+
+    /// Move keys following the array from the hash part
+    fn migrate(&mut self) {
+        while let Some(value) = self.hash.remove(&(self.array.len() as i64)) {
+            self.array.push(value);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.array.len() + self.hash.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keys and values in order of keys
+    pub fn iter(&self) -> impl Iterator<Item = (i64, &Reg)> + Clone {
+        let mut hashed: Vec<_> = self.hash.iter().map(|(key, value)| (*key, value)).collect();
+        hashed.sort_unstable_by_key(|(key, _)| *key);
+        let (negative, positive): (Vec<_>, Vec<_>) =
+            hashed.into_iter().partition(|(key, _)| *key < 0);
+        negative
+            .into_iter()
+            .chain(
+                self.array
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| (i as i64, value)),
+            )
+            .chain(positive)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Reg> {
+        self.array.iter().chain(self.hash.values())
+    }
+}
@@ -1,10 +1,31 @@
+use alloc::string::{String, ToString};
+#[cfg(not(feature = "std"))]
+use bimap::BiBTreeMap as BiHashMap;
+#[cfg(feature = "std")]
 use bimap::BiHashMap;
 
-#[derive(Default)]
+/// Key of the table of property getters in a meta table
+pub const GETTERS_KEY: usize = 0;
+/// Key of the table of property setters in a meta table
+pub const SETTERS_KEY: usize = 1;
+/// Key of the table of class-level members in a class
+pub const STATIC_KEY: usize = 2;
+
+#[derive(Clone)]
 pub struct KeyPool {
     pool: BiHashMap<String, usize>,
 }
 
+impl Default for KeyPool {
+    fn default() -> Self {
+        let mut pool = BiHashMap::new();
+        pool.insert("__getters".to_string(), GETTERS_KEY);
+        pool.insert("__setters".to_string(), SETTERS_KEY);
+        pool.insert("__static".to_string(), STATIC_KEY);
+        Self { pool }
+    }
+}
+
 impl KeyPool {
     pub fn get_or_insert(&mut self, key: impl Into<String> + AsRef<str>) -> usize {
         self.pool
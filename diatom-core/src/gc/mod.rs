@@ -1,21 +1,44 @@
-use std::{
-    any::Any,
+use alloc::{
+    boxed::Box,
     collections::{BTreeMap, BTreeSet},
-    sync::Arc,
+    format,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
 };
+use core::any::{Any, TypeId};
 
-use crate::{ffi::ForeignFunction, vm::Ip, IoWrite};
+use crate::compat::{io, AHashMap, AHashSet};
 
+use crate::{
+    ffi::{EventHooks, ForeignFunction, FunctionInfo, ScriptError, SourceLocation, State},
+    file_manager::Loc,
+    host::{default_clock, default_input, Clock, Deadline},
+    interpreter::SandboxPolicy,
+    vm::{error::RuntimeWarning, Ip},
+    IoRead, IoWrite,
+};
+
+mod inspect;
+mod int_keys;
 mod key_pool;
 mod pool;
+pub use inspect::{PrintOptions, DEFAULT_INSPECT_DEPTH};
+pub use int_keys::IntKeys;
 use key_pool::KeyPool;
+pub use key_pool::{GETTERS_KEY, SETTERS_KEY, STATIC_KEY};
 use more_asserts::debug_assert_gt;
 use pool::Pool;
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Table {
     pub attributes: BTreeMap<usize, Reg>,
+    /// Values of integer keys, set and read by indexing
+    pub indices: IntKeys,
     pub meta_table: Option<usize>,
+    /// Fields of a frozen table can not be set by scripts
+    pub frozen: bool,
 }
 
 pub enum GcObject<Buffer: IoWrite> {
@@ -26,13 +49,76 @@ pub enum GcObject<Buffer: IoWrite> {
         /// local id, shared reg
         captured: Vec<(usize, usize)>,
     },
-    UserData(Box<dyn Any + Send>),
+    /// User data, kept out of the pool by `Gc`
+    UserData,
     NativeFunction(Arc<ForeignFunction<Buffer>>),
+    /// A callable read by `receiver.attr`, called with `receiver` as the first parameter
+    BoundMethod {
+        receiver: Reg,
+        method: usize,
+    },
     List(Vec<Reg>),
     Table(Table),
     Tuple(Vec<Reg>),
 }
 
+/// Shortest representation of a float that parses back to it
+///
+/// Same on all platforms and always written with a `.` or an exponent, e.g. `1.0` and `1e100`,
+/// so that it is never read as an integer. Non-finite floats are `inf`, `-inf` and `NaN`.
+pub fn format_float(f: f64) -> String {
+    float_str(f, &mut ryu::Buffer::new()).to_string()
+}
+
+impl<Buffer: IoWrite> GcObject<Buffer> {
+    /// Closures, external functions and bound methods
+    pub fn is_callable(&self) -> bool {
+        matches!(
+            self,
+            GcObject::Closure { .. } | GcObject::NativeFunction(_) | GcObject::BoundMethod { .. }
+        )
+    }
+}
+
+fn float_str(f: f64, buffer: &mut ryu::Buffer) -> &str {
+    if f.is_finite() {
+        buffer.format_finite(f)
+    } else if f.is_nan() {
+        "NaN"
+    } else if f > 0.0 {
+        "inf"
+    } else {
+        "-inf"
+    }
+}
+
+impl<Buffer: IoWrite> Clone for GcObject<Buffer> {
+    fn clone(&self) -> Self {
+        match self {
+            GcObject::Closure {
+                func_id,
+                parameters,
+                reg_size,
+                captured,
+            } => GcObject::Closure {
+                func_id: *func_id,
+                parameters: *parameters,
+                reg_size: *reg_size,
+                captured: captured.clone(),
+            },
+            GcObject::UserData => GcObject::UserData,
+            GcObject::NativeFunction(f) => GcObject::NativeFunction(f.clone()),
+            GcObject::BoundMethod { receiver, method } => GcObject::BoundMethod {
+                receiver: receiver.clone(),
+                method: *method,
+            },
+            GcObject::List(l) => GcObject::List(l.clone()),
+            GcObject::Table(t) => GcObject::Table(t.clone()),
+            GcObject::Tuple(t) => GcObject::Tuple(t.clone()),
+        }
+    }
+}
+
 impl<Buffer: IoWrite> Default for GcObject<Buffer> {
     fn default() -> Self {
         Self::Tuple(vec![])
@@ -51,31 +137,46 @@ pub enum Reg {
     Ref(usize),
 }
 
+#[derive(Clone)]
 enum StackReg {
     Reg(Reg),
     Shared(usize),
 }
 
+#[derive(Clone)]
 struct Frame {
     ptr: usize,
     return_addr: Ip,
     write_back: Option<usize>,
     rid: usize,
     reg_size: usize,
+    /// Closures registered by `defer`, run in reverse order when the frame exits
+    deferred: Vec<usize>,
+    /// Frame of a deferred closure, which is not called by any call instruction
+    is_deferred: bool,
+    /// Value to return once deferred closures have run
+    return_value: Option<Reg>,
+    /// Instance initialized by this frame, which runs `__init` of a table called as constructor
+    instance: Option<usize>,
 }
 
+#[derive(Clone)]
 struct CallStack {
     frames: Vec<Frame>,
     regs: Vec<StackReg>,
     fp: Frame,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 struct GrayPool {
     pinned_string: BTreeSet<usize>,
     pinned_obj: BTreeSet<usize>,
     escaped: BTreeSet<usize>,
     objects: BTreeSet<usize>,
+    /// Strings created by an external function that is still running
+    temp_string: Vec<usize>,
+    /// Objects created by an external function that is still running
+    temp_obj: Vec<usize>,
 }
 
 pub enum PrimitiveMeta {
@@ -83,13 +184,18 @@ pub enum PrimitiveMeta {
     Float,
     Str,
     List,
+    Option,
+    Result,
 }
 
+#[derive(Clone)]
 struct MetaMap {
     int_meta: usize,
     float_meta: usize,
     list_meta: usize,
     str_meta: usize,
+    option_meta: usize,
+    result_meta: usize,
 }
 
 impl MetaMap {
@@ -99,10 +205,42 @@ impl MetaMap {
             PrimitiveMeta::Float => self.float_meta,
             PrimitiveMeta::Str => self.str_meta,
             PrimitiveMeta::List => self.list_meta,
+            PrimitiveMeta::Option => self.option_meta,
+            PrimitiveMeta::Result => self.result_meta,
         }
     }
 }
 
+/// Copy user data into a fork of the heap
+pub type CloneUserData = fn(&(dyn Any + Send)) -> Box<dyn Any + Send>;
+
+/// Methods and getters of a rust type registered by host
+#[derive(Clone)]
+pub struct UserType {
+    /// Type name shown in error messages
+    pub name: &'static str,
+    /// Table of methods, pinned
+    pub methods: usize,
+    /// Table key to getter function, pinned
+    pub getters: AHashMap<usize, usize>,
+    /// Copy user data of this type into a fork of the heap
+    pub clone: Option<CloneUserData>,
+}
+
+/// Stands in for user data that can not be copied into a fork of the heap
+struct Detached;
+
+/// Attribute of an enum value holding its variant, e.g. `Shape::Circle`
+pub const VARIANT_KEY: &str = "__variant";
+
+/// Shown name of a variant and its fields, named unless they are positional
+type VariantFields<'a> = (&'a str, Vec<(Option<String>, &'a Reg)>);
+
+/// Strings and objects allocated before the first collection
+const MIN_THRESHOLD: usize = 100;
+/// Bytes of strings allocated before the first collection
+const MIN_STRING_THRESHOLD: usize = 1 << 16;
+
 /// Garbage Collector
 pub struct Gc<Buffer: IoWrite> {
     /// Object managed pool
@@ -111,6 +249,8 @@ pub struct Gc<Buffer: IoWrite> {
     escaped_pool: Pool<Reg>,
     /// Immutable string pool
     string_pool: Pool<String>,
+    /// Bytes of strings in the pool, including garbage not yet collected
+    string_bytes: usize,
     /// Function call stack
     call_stack: CallStack,
     /// Up value stack
@@ -123,8 +263,61 @@ pub struct Gc<Buffer: IoWrite> {
     key_pool: KeyPool,
     /// Meta table id for primitive type
     meta_map: MetaMap,
+    /// What scripts are allowed to do
+    policy: SandboxPolicy,
+    /// Clock supplied by host, used for timeouts and benchmarks
+    clock: Option<Arc<dyn Clock>>,
+    /// Live allocations exceed `max_allocations` of policy even after a collection
+    out_of_memory: bool,
+    /// Values of user data objects, by object id
+    user_data: AHashMap<usize, Box<dyn Any + Send>>,
+    /// Rust types that can be used as user data with methods
+    user_types: AHashMap<TypeId, UserType>,
+    /// Values held by host, released once all handles are dropped
+    host_roots: Vec<Weak<Reg>>,
+    /// Host states accessible by external functions, one per type
+    contexts: AHashMap<TypeId, Box<dyn Any + Send>>,
+    /// Input source of scripts
+    input: Box<dyn IoRead + Send>,
+    /// Error reported by the running external function
+    raised: Option<ScriptError>,
+    /// Deadline of the running execution, read by external functions
+    deadline: Option<Deadline>,
+    /// Whether the running external function stopped waiting as the deadline passed
+    interrupted: bool,
+    /// Objects frozen by `freeze`, with the call that froze them
+    frozen: AHashMap<usize, Option<Loc>>,
+    /// Location of the running call of an external function
+    call_site: Option<Loc>,
+    /// Frozen object the running external function tried to modify
+    modified_frozen: Option<usize>,
+    /// Warnings reported by external functions since the last execution
+    warnings: Vec<RuntimeWarning>,
+    /// Number of strings and objects that starts the next collection
     threshold: usize,
+    /// Bytes of strings that start the next collection, so that a few long strings are collected
+    /// as well
+    string_threshold: usize,
     paused: bool,
+    /// Collect before every allocation and verify the heap after each collection
+    stress: bool,
+    /// Strings and objects allocated by scripts
+    allocations: u64,
+    /// Garbage collections run
+    collections: u64,
+    /// Most strings and objects alive at once since `reset_peaks`
+    peak_strings: usize,
+    peak_objects: usize,
+    /// Limits of `print` and `inspect`
+    print_options: PrintOptions,
+    /// Name, parameters and location of functions defined in script, by function id
+    functions: AHashMap<usize, FunctionInfo>,
+    /// Observer of calls and writes of globals set by host
+    hooks: Option<Box<dyn EventHooks<Buffer>>>,
+    /// Names of global variables by register, watched while there are hooks
+    global_names: AHashMap<usize, String>,
+    /// Registers of global variables in strict mode not assigned yet
+    undefined: AHashSet<usize>,
 }
 
 static UNIT_REG: Reg = Reg::Unit;
@@ -138,6 +331,8 @@ impl<Buffer: IoWrite> Gc<Buffer> {
             float_meta: usize::MAX,
             list_meta: usize::MAX,
             str_meta: usize::MAX,
+            option_meta: usize::MAX,
+            result_meta: usize::MAX,
         };
         let mut gc = Self {
             call_stack: CallStack {
@@ -153,17 +348,48 @@ impl<Buffer: IoWrite> Gc<Buffer> {
                     },
                     write_back: None,
                     reg_size: 0,
+                    deferred: vec![],
+                    is_deferred: false,
+                    return_value: None,
+                    instance: None,
                 },
             },
             module_map: Default::default(),
             up_values: vec![BTreeSet::new()],
             string_pool: Default::default(),
+            string_bytes: 0,
             obj_pool,
             escaped_pool: Default::default(),
             key_pool,
             gray_pool: Default::default(),
-            threshold: 100,
+            policy: SandboxPolicy::default(),
+            clock: default_clock(),
+            out_of_memory: false,
+            user_data: AHashMap::default(),
+            user_types: AHashMap::default(),
+            host_roots: vec![],
+            contexts: AHashMap::default(),
+            input: default_input(),
+            raised: None,
+            deadline: None,
+            interrupted: false,
+            frozen: AHashMap::default(),
+            call_site: None,
+            modified_frozen: None,
+            warnings: vec![],
+            threshold: MIN_THRESHOLD,
+            string_threshold: MIN_STRING_THRESHOLD,
             paused: false,
+            stress: cfg!(any(test, feature = "gc-stress")),
+            allocations: 0,
+            collections: 0,
+            peak_strings: 0,
+            peak_objects: 0,
+            print_options: PrintOptions::default(),
+            functions: AHashMap::default(),
+            hooks: None,
+            global_names: AHashMap::default(),
+            undefined: AHashSet::default(),
             meta_map,
         };
         let meta_map = MetaMap {
@@ -171,11 +397,80 @@ impl<Buffer: IoWrite> Gc<Buffer> {
             float_meta: gc.alloc_obj_pinned(GcObject::Table(Default::default())),
             list_meta: gc.alloc_obj_pinned(GcObject::Table(Default::default())),
             str_meta: gc.alloc_obj_pinned(GcObject::Table(Default::default())),
+            option_meta: gc.alloc_obj_pinned(GcObject::Table(Default::default())),
+            result_meta: gc.alloc_obj_pinned(GcObject::Table(Default::default())),
         };
         gc.meta_map = meta_map;
         gc
     }
 
+    /// Copy the heap and all settings
+    ///
+    /// Pages of the heap are shared until either copy writes to them. User data is copied if its
+    /// type is registered as cloneable, otherwise it is replaced by a value without methods. Host
+    /// roots, host states, hooks and input source are not copied.
+    pub fn fork(&self) -> Self {
+        let user_data = self
+            .user_data
+            .iter()
+            .map(|(rid, data)| {
+                let clone = self
+                    .user_types
+                    .get(&(**data).type_id())
+                    .and_then(|t| t.clone);
+                let data = match clone {
+                    Some(clone) => clone(data.as_ref()),
+                    None => Box::new(Detached),
+                };
+                (*rid, data)
+            })
+            .collect();
+        let mut gray_pool = self.gray_pool.clone();
+        gray_pool.temp_string.clear();
+        gray_pool.temp_obj.clear();
+        Self {
+            obj_pool: self.obj_pool.clone(),
+            escaped_pool: self.escaped_pool.clone(),
+            string_pool: self.string_pool.clone(),
+            string_bytes: self.string_bytes,
+            call_stack: self.call_stack.clone(),
+            up_values: self.up_values.clone(),
+            module_map: self.module_map.clone(),
+            gray_pool,
+            key_pool: self.key_pool.clone(),
+            meta_map: self.meta_map.clone(),
+            policy: self.policy.clone(),
+            clock: self.clock.clone(),
+            out_of_memory: false,
+            user_data,
+            user_types: self.user_types.clone(),
+            host_roots: vec![],
+            contexts: AHashMap::default(),
+            input: default_input(),
+            raised: None,
+            deadline: None,
+            interrupted: false,
+            // Frozen objects are copied with the same ids, thus stay frozen in the copy
+            frozen: self.frozen.clone(),
+            call_site: None,
+            modified_frozen: None,
+            warnings: vec![],
+            threshold: self.threshold,
+            string_threshold: self.string_threshold,
+            paused: self.paused,
+            stress: self.stress,
+            allocations: 0,
+            collections: 0,
+            peak_strings: 0,
+            peak_objects: 0,
+            print_options: self.print_options,
+            functions: self.functions.clone(),
+            hooks: None,
+            global_names: AHashMap::default(),
+            undefined: self.undefined.clone(),
+        }
+    }
+
     pub fn new_module(&mut self, fid: usize) {
         if self.module_map.get(&fid).is_none() {
             self.module_map.insert(fid, None);
@@ -204,7 +499,11 @@ impl<Buffer: IoWrite> Gc<Buffer> {
 
     pub fn alloc_obj(&mut self, obj: GcObject<Buffer>) -> usize {
         self.try_collect();
-        self.obj_pool.alloc(obj)
+        self.check_memory();
+        self.allocations += 1;
+        let id = self.obj_pool.alloc(obj);
+        self.peak_objects = self.peak_objects.max(self.obj_pool.len());
+        id
     }
 
     pub fn alloc_obj_pinned(&mut self, obj: GcObject<Buffer>) -> usize {
@@ -215,15 +514,367 @@ impl<Buffer: IoWrite> Gc<Buffer> {
 
     pub fn alloc_str(&mut self, s: String) -> usize {
         self.try_collect();
-        self.string_pool.alloc(s)
+        self.check_memory();
+        self.allocations += 1;
+        self.string_bytes += s.len();
+        let id = self.string_pool.alloc(s);
+        self.peak_strings = self.peak_strings.max(self.string_pool.len());
+        id
     }
 
     pub fn alloc_str_pinned(&mut self, s: String) -> usize {
+        self.string_bytes += s.len();
         let id = self.string_pool.alloc(s);
         self.gray_pool.pinned_string.insert(id);
         id
     }
 
+    /// Keep a string alive until `clear_temporaries` is called
+    pub fn root_temporary_str(&mut self, id: usize) {
+        self.gray_pool.temp_string.push(id);
+    }
+
+    /// Keep an object alive until `clear_temporaries` is called
+    pub fn root_temporary_obj(&mut self, id: usize) {
+        self.gray_pool.temp_obj.push(id);
+    }
+
+    /// Release all temporary roots
+    pub fn clear_temporaries(&mut self) {
+        self.gray_pool.temp_string.clear();
+        self.gray_pool.temp_obj.clear();
+    }
+
+    /// Keep a value alive as long as the returned handle or any of its clones is held
+    pub fn root_host(&mut self, reg: Reg) -> Arc<Reg> {
+        let root = Arc::new(reg);
+        self.host_roots.push(Arc::downgrade(&root));
+        root
+    }
+
+    pub fn replace_input(&mut self, input: Box<dyn IoRead + Send>) -> Box<dyn IoRead + Send> {
+        core::mem::replace(&mut self.input, input)
+    }
+
+    /// Read a line without line ending, return None at the end of input
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    /// Set host state of type `T` and return the old one
+    pub fn set_context<T: Any + Send>(&mut self, context: T) -> Option<T> {
+        self.contexts
+            .insert(TypeId::of::<T>(), Box::new(context))
+            .map(|old| *old.downcast::<T>().unwrap())
+    }
+
+    pub fn get_context<T: Any + Send>(&self) -> Option<&T> {
+        self.contexts
+            .get(&TypeId::of::<T>())
+            .map(|context| context.downcast_ref::<T>().unwrap())
+    }
+
+    pub fn get_context_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.contexts
+            .get_mut(&TypeId::of::<T>())
+            .map(|context| context.downcast_mut::<T>().unwrap())
+    }
+
+    pub fn take_context<T: Any + Send>(&mut self) -> Option<T> {
+        self.contexts
+            .remove(&TypeId::of::<T>())
+            .map(|context| *context.downcast::<T>().unwrap())
+    }
+
+    pub fn grant_capability(&mut self, capability: impl Into<String>) {
+        self.policy.grant(capability);
+    }
+
+    pub fn revoke_capability(&mut self, capability: impl AsRef<str>) {
+        self.policy.revoke(capability);
+    }
+
+    pub fn has_capability(&self, capability: impl AsRef<str>) -> bool {
+        self.policy.allows(capability)
+    }
+
+    pub fn policy(&self) -> &SandboxPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: SandboxPolicy) -> SandboxPolicy {
+        core::mem::replace(&mut self.policy, policy)
+    }
+
+    pub fn clock(&self) -> Option<&Arc<dyn Clock>> {
+        self.clock.as_ref()
+    }
+
+    pub fn set_clock(&mut self, clock: Option<Arc<dyn Clock>>) -> Option<Arc<dyn Clock>> {
+        core::mem::replace(&mut self.clock, clock)
+    }
+
+    pub fn print_options(&self) -> PrintOptions {
+        self.print_options
+    }
+
+    pub fn set_print_options(&mut self, options: PrintOptions) -> PrintOptions {
+        core::mem::replace(&mut self.print_options, options)
+    }
+
+    /// Set metadata of function `func_id`, closures of which are shown as e.g. `f at main.dm:1:1`
+    pub fn set_function_info(&mut self, func_id: usize, info: FunctionInfo) {
+        self.functions.insert(func_id, info);
+    }
+
+    pub fn function_info(&self, func_id: usize) -> Option<&FunctionInfo> {
+        self.functions.get(&func_id)
+    }
+
+    pub fn set_raised(&mut self, error: ScriptError) {
+        self.raised = Some(error);
+    }
+
+    pub fn take_raised(&mut self) -> Option<ScriptError> {
+        self.raised.take()
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<Deadline>) {
+        self.deadline = deadline;
+    }
+
+    pub fn deadline(&self) -> Option<&Deadline> {
+        self.deadline.as_ref()
+    }
+
+    pub fn set_interrupted(&mut self) {
+        self.interrupted = true;
+    }
+
+    pub fn take_interrupted(&mut self) -> bool {
+        core::mem::take(&mut self.interrupted)
+    }
+
+    /// Return the call site replaced, which is restored once the call returns
+    pub fn set_call_site(&mut self, loc: Option<Loc>) -> Option<Loc> {
+        core::mem::replace(&mut self.call_site, loc)
+    }
+
+    pub fn call_site(&self) -> Option<&Loc> {
+        self.call_site.as_ref()
+    }
+
+    /// Report a warning at the running call
+    pub fn warn(&mut self, name: &'static str, message: String) {
+        self.warnings.push(RuntimeWarning {
+            name,
+            message,
+            loc: self.call_site.clone(),
+        });
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<RuntimeWarning> {
+        core::mem::take(&mut self.warnings)
+    }
+
+    pub fn set_modified_frozen(&mut self, ref_id: usize) {
+        self.modified_frozen = Some(ref_id);
+    }
+
+    pub fn take_modified_frozen(&mut self) -> Option<usize> {
+        self.modified_frozen.take()
+    }
+
+    /// Make `reg` and all collections reachable from it immutable, `loc` is where it is frozen
+    ///
+    /// Meta tables, functions and user data are not frozen. Objects already frozen are kept
+    /// as they are, so the site of the first freeze is reported.
+    pub fn freeze(&mut self, reg: &Reg, loc: Option<Loc>) {
+        let mut pending = match reg {
+            Reg::Ref(rid) => vec![*rid],
+            _ => return,
+        };
+        while let Some(rid) = pending.pop() {
+            if self.frozen.contains_key(&rid) {
+                continue;
+            }
+            let items: Vec<&Reg> = match self.obj_pool.get_mut(rid) {
+                Some(GcObject::Table(t)) => {
+                    t.frozen = true;
+                    t.attributes.values().chain(t.indices.values()).collect()
+                }
+                Some(GcObject::List(l) | GcObject::Tuple(l)) => l.iter().collect(),
+                _ => continue,
+            };
+            pending.extend(items.into_iter().filter_map(|reg| match reg {
+                Reg::Ref(rid) => Some(*rid),
+                _ => None,
+            }));
+            self.frozen.insert(rid, loc.clone());
+        }
+    }
+
+    /// Whether an object can not be modified by scripts
+    pub fn is_frozen(&self, ref_id: usize) -> bool {
+        match self.obj_pool.get(ref_id) {
+            Some(GcObject::Table(t)) => t.frozen,
+            _ => self.frozen.contains_key(&ref_id),
+        }
+    }
+
+    /// Where an object is frozen by `freeze`
+    pub fn frozen_at(&self, ref_id: usize) -> Option<&Loc> {
+        self.frozen.get(&ref_id).and_then(Option::as_ref)
+    }
+
+    pub fn set_hooks(
+        &mut self,
+        hooks: Option<Box<dyn EventHooks<Buffer>>>,
+    ) -> Option<Box<dyn EventHooks<Buffer>>> {
+        core::mem::replace(&mut self.hooks, hooks)
+    }
+
+    pub fn has_hooks(&self) -> bool {
+        self.hooks.is_some()
+    }
+
+    /// Set names of global variables by register, whose writes are passed to hooks
+    pub fn set_global_names(&mut self, names: AHashMap<usize, String>) {
+        self.global_names = names;
+    }
+
+    /// Mark global variable at register `id` of main function as not assigned yet
+    pub fn undefine(&mut self, id: usize) {
+        self.undefined.insert(id);
+    }
+
+    /// Mark global variable at register `id` of main function as assigned
+    pub fn define(&mut self, id: usize) {
+        self.undefined.remove(&id);
+    }
+
+    pub fn is_undefined(&self, id: usize) -> bool {
+        self.undefined.contains(&id)
+    }
+
+    /// Run `f` with hooks and a view of the heap, do nothing if there are no hooks
+    pub fn with_hooks(&mut self, f: impl FnOnce(&mut dyn EventHooks<Buffer>, &State<Buffer>)) {
+        if let Some(mut hooks) = self.hooks.take() {
+            f(hooks.as_mut(), &State { gc: self });
+            self.hooks = Some(hooks);
+        }
+    }
+
+    /// Name of the global variable written at `n` of the register stack, if it is one
+    ///
+    /// A global is written at its register by code at top level, or through its shared register
+    /// by functions that capture it.
+    fn written_global(&self, n: usize) -> Option<&str> {
+        let stack = &self.call_stack;
+        match &stack.regs[n] {
+            StackReg::Reg(_) if stack.frames.is_empty() => self.global_names.get(&n),
+            StackReg::Reg(_) => None,
+            StackReg::Shared(id) => self
+                .global_names
+                .iter()
+                .find(|(reg, _)| matches!(stack.regs[**reg], StackReg::Shared(shared) if shared == *id))
+                .map(|(_, name)| name),
+        }
+        .map(String::as_str)
+    }
+
+    /// Check and reset out of memory flag
+    pub fn take_out_of_memory(&mut self) -> bool {
+        core::mem::take(&mut self.out_of_memory)
+    }
+
+    fn total_allocated(&self) -> usize {
+        self.string_pool.len() + self.obj_pool.len() + self.escaped_pool.len()
+    }
+
+    /// Flag out of memory if live allocations exceed limit of policy
+    fn check_memory(&mut self) {
+        if let Some(max_allocations) = self.policy.max_allocations {
+            if !self.paused && self.total_allocated() > max_allocations {
+                self.collect();
+                self.out_of_memory |= self.total_allocated() > max_allocations;
+            }
+        }
+    }
+
+    /// Get methods and getters registered for a user data type
+    pub fn get_user_type(&self, type_id: TypeId) -> Option<&UserType> {
+        self.user_types.get(&type_id)
+    }
+
+    fn user_type_entry(&mut self, type_id: TypeId, name: &'static str) -> &mut UserType {
+        if !self.user_types.contains_key(&type_id) {
+            let methods = self.alloc_obj_pinned(GcObject::Table(Default::default()));
+            self.user_types.insert(
+                type_id,
+                UserType {
+                    name,
+                    methods,
+                    getters: AHashMap::default(),
+                    clone: None,
+                },
+            );
+        }
+        self.user_types.get_mut(&type_id).unwrap()
+    }
+
+    /// Register a method of a user data type
+    pub fn register_method(
+        &mut self,
+        type_id: TypeId,
+        type_name: &'static str,
+        name: &str,
+        f: Arc<ForeignFunction<Buffer>>,
+    ) {
+        let key = self.get_or_insert_table_key(name);
+        let f = self.alloc_obj_pinned(GcObject::NativeFunction(f));
+        let methods = self.user_type_entry(type_id, type_name).methods;
+        let old = match self.get_obj_mut(methods) {
+            Some(GcObject::Table(t)) => t.attributes.insert(key, Reg::Ref(f)),
+            _ => unreachable!(),
+        };
+        // Overridden method is no longer reachable
+        if let Some(Reg::Ref(old)) = old {
+            self.gray_pool.pinned_obj.remove(&old);
+        }
+    }
+
+    /// Register a getter of a user data type
+    ///
+    /// Getter is called with the user data as the only parameter when the attribute is read.
+    pub fn register_getter(
+        &mut self,
+        type_id: TypeId,
+        type_name: &'static str,
+        name: &str,
+        f: Arc<ForeignFunction<Buffer>>,
+    ) {
+        let key = self.get_or_insert_table_key(name);
+        let f = self.alloc_obj_pinned(GcObject::NativeFunction(f));
+        let old = self
+            .user_type_entry(type_id, type_name)
+            .getters
+            .insert(key, f);
+        if let Some(old) = old {
+            self.gray_pool.pinned_obj.remove(&old);
+        }
+    }
+
     pub fn get_obj(&self, id: usize) -> Option<&GcObject<Buffer>> {
         self.obj_pool.get(id)
     }
@@ -232,6 +883,31 @@ impl<Buffer: IoWrite> Gc<Buffer> {
         self.obj_pool.get_mut(id)
     }
 
+    pub fn alloc_user_data(&mut self, data: Box<dyn Any + Send>) -> usize {
+        let id = self.alloc_obj(GcObject::UserData);
+        self.user_data.insert(id, data);
+        id
+    }
+
+    /// Value of user data object `id`
+    pub fn get_user_data(&self, id: usize) -> Option<&Box<dyn Any + Send>> {
+        self.user_data.get(&id)
+    }
+
+    pub fn get_user_data_mut(&mut self, id: usize) -> Option<&mut Box<dyn Any + Send>> {
+        self.user_data.get_mut(&id)
+    }
+
+    /// Copy user data of a type when the heap is forked, instead of detaching it
+    pub fn register_clone(
+        &mut self,
+        type_id: TypeId,
+        type_name: &'static str,
+        clone: CloneUserData,
+    ) {
+        self.user_type_entry(type_id, type_name).clone = Some(clone);
+    }
+
     pub unsafe fn get_obj_unchecked(&self, id: usize) -> &GcObject<Buffer> {
         self.obj_pool.get_unchecked(id)
     }
@@ -303,11 +979,17 @@ impl<Buffer: IoWrite> Gc<Buffer> {
         let n = stack.fp.ptr + n;
         debug_assert_gt!(stack.regs.len(), n);
         debug_assert_gt!(stack.fp.ptr + stack.fp.reg_size, n);
+        let watched = self.hooks.is_some().then(|| reg.clone());
         let prev = unsafe { stack.regs.get_unchecked_mut(n) };
         match prev {
             StackReg::Reg(r) => *r = reg,
             StackReg::Shared(id) => *unsafe { self.escaped_pool.get_unchecked_mut(*id) } = reg,
         }
+        if let Some(value) = watched {
+            if let Some(name) = self.written_global(n).map(str::to_string) {
+                self.with_hooks(|hooks, state| hooks.on_global_write(state, &name, &value));
+            }
+        }
     }
 
     pub fn alloc_call_stack(
@@ -319,7 +1001,7 @@ impl<Buffer: IoWrite> Gc<Buffer> {
     ) {
         let stack = &mut self.call_stack;
         let ptr = stack.fp.ptr + start;
-        let fp_old = std::mem::replace(
+        let fp_old = core::mem::replace(
             &mut stack.fp,
             Frame {
                 ptr,
@@ -327,6 +1009,10 @@ impl<Buffer: IoWrite> Gc<Buffer> {
                 write_back,
                 rid,
                 reg_size: usize::MAX,
+                deferred: vec![],
+                is_deferred: false,
+                return_value: None,
+                instance: None,
             },
         );
         stack.frames.push(fp_old);
@@ -356,6 +1042,17 @@ impl<Buffer: IoWrite> Gc<Buffer> {
         }
     }
 
+    /// Call closure `rid` with its registers placed after all registers of the current frame
+    pub fn alloc_call_stack_after_frame(
+        &mut self,
+        return_addr: Ip,
+        write_back: Option<usize>,
+        rid: usize,
+    ) {
+        let start = self.call_stack.fp.reg_size;
+        self.alloc_call_stack(return_addr, write_back, start, rid);
+    }
+
     /// None if there is no more call stack
     pub fn pop_call_stack(&mut self) -> (Ip, Option<usize>) {
         let stack = &mut self.call_stack;
@@ -378,7 +1075,7 @@ impl<Buffer: IoWrite> Gc<Buffer> {
             ptr,
             rid,
             ..
-        } = std::mem::replace(&mut stack.fp, fp);
+        } = core::mem::replace(&mut stack.fp, fp);
 
         if let GcObject::Closure { captured, .. } = unsafe { self.obj_pool.get_unchecked(rid) } {
             captured.iter().for_each(|(shared_reg, _)| {
@@ -393,6 +1090,76 @@ impl<Buffer: IoWrite> Gc<Buffer> {
         (return_addr, write_back)
     }
 
+    /// Run closure `rid` when the current frame exits
+    pub fn defer(&mut self, rid: usize) {
+        self.call_stack.fp.deferred.push(rid)
+    }
+
+    /// Copy value of register `id` to a shared register, which does not see later writes to `id`
+    ///
+    /// No garbage is collected, the shared register must be captured by a closure reachable
+    /// from roots before anything else is allocated.
+    pub fn copy_reg(&mut self, id: usize) -> usize {
+        let reg = self.read_reg(id).clone();
+        self.escaped_pool.alloc(reg)
+    }
+
+    /// Whether the current frame has any deferred closure left
+    pub fn has_deferred(&self) -> bool {
+        !self.call_stack.fp.deferred.is_empty()
+    }
+
+    /// Keep the value returned by the current frame while its deferred closures run
+    pub fn set_return_value(&mut self, reg: Reg) {
+        self.call_stack.fp.return_value = Some(reg)
+    }
+
+    /// Return `rid` instead of the value of the current frame, which initializes it
+    pub fn set_instance(&mut self, rid: usize) {
+        self.call_stack.fp.instance = Some(rid)
+    }
+
+    /// Instance initialized by the current frame
+    pub fn take_instance(&mut self) -> Option<usize> {
+        self.call_stack.fp.instance.take()
+    }
+
+    pub fn take_return_value(&mut self) -> Option<Reg> {
+        self.call_stack.fp.return_value.take()
+    }
+
+    /// Call the last closure deferred by the current frame, which returns to `return_addr`
+    ///
+    /// Return where the closure starts, or `None` if nothing is deferred.
+    pub fn call_deferred(&mut self, return_addr: Ip) -> Option<Ip> {
+        let rid = self.call_stack.fp.deferred.pop()?;
+        let func_id = match unsafe { self.obj_pool.get_unchecked(rid) } {
+            GcObject::Closure { func_id, .. } => *func_id,
+            _ => unreachable!(),
+        };
+        self.alloc_call_stack_after_frame(return_addr, None, rid);
+        self.call_stack.fp.is_deferred = true;
+        Some(Ip { func_id, inst: 0 })
+    }
+
+    /// Whether there is no frame other than the main function
+    pub fn is_call_stack_empty(&self) -> bool {
+        self.call_stack.frames.is_empty()
+    }
+
+    /// Return address of all frames except those of deferred closures, from the innermost one
+    pub fn call_trace(&self) -> Vec<Ip> {
+        let stack = &self.call_stack;
+        if stack.frames.is_empty() {
+            return vec![];
+        }
+        core::iter::once(&stack.fp)
+            .chain(stack.frames[1..].iter().rev())
+            .filter(|frame| !frame.is_deferred)
+            .map(|frame| frame.return_addr)
+            .collect()
+    }
+
     pub fn get_meta(&self, key: PrimitiveMeta) -> usize {
         self.meta_map.get(key)
     }
@@ -409,88 +1176,204 @@ impl<Buffer: IoWrite> Gc<Buffer> {
     pub fn print(&self, reg: &Reg) -> String {
         let mut buffer = String::new();
         let mut visited = BTreeSet::new();
-        self.print_reg(reg, &mut visited, &mut buffer);
+        self.print_reg(reg, &mut visited, 0, &mut buffer);
         buffer
     }
 
-    fn print_reg(&self, reg: &Reg, visited: &mut BTreeSet<usize>, buffer: &mut String) {
-        use std::fmt::Write;
+    /// Tag of enum value `t` and the attribute of its enum holding the variant, see `lower_enum`
+    fn variant_tag(&self, t: &Table) -> Option<(&str, &Reg)> {
+        let tag = match t.attributes.get(&self.get_table_key(VARIANT_KEY)?)? {
+            Reg::Str(sid) => self.get_str(*sid)?,
+            _ => return None,
+        };
+        let name = tag.rsplit("::").next()?;
+        let variant = match self.get_obj(t.meta_table?)? {
+            GcObject::Table(meta) => meta.attributes.get(&self.get_table_key(name)?)?,
+            _ => return None,
+        };
+        Some((tag, variant))
+    }
+
+    /// Whether object `rid` is a value of variant `variant`, i.e. the shared value of a variant
+    /// without fields or the constructor of a variant with fields
+    pub fn is_variant(&self, rid: usize, variant: usize) -> bool {
+        match self.get_obj(rid) {
+            Some(GcObject::Table(t)) => self
+                .variant_tag(t)
+                .is_some_and(|(_, reg)| matches!(reg, Reg::Ref(r) if *r == variant)),
+            _ => false,
+        }
+    }
+
+    /// Name and fields of enum value `t` as shown by `print` and `inspect`
+    ///
+    /// Variants of `Option` and `Result` are shown without their enum and field names, e.g.
+    /// `Some(1)`, others in full, e.g. `Shape::Circle(r = 1)`. Fields are in declaration order.
+    pub(crate) fn variant_fields<'a>(&'a self, t: &'a Table) -> Option<VariantFields<'a>> {
+        let (tag, variant) = self.variant_tag(t)?;
+        let meta = t.meta_table?;
+        let is_prelude = meta == self.get_meta(PrimitiveMeta::Option)
+            || meta == self.get_meta(PrimitiveMeta::Result);
+        let name = if is_prelude {
+            tag.rsplit("::").next()?
+        } else {
+            tag
+        };
+        let parameters = match variant {
+            Reg::Ref(r) => match self.get_obj(*r) {
+                Some(GcObject::Closure { func_id, .. }) => self
+                    .functions
+                    .get(func_id)
+                    .map_or(&[][..], |info| &info.parameters[..]),
+                _ => &[],
+            },
+            _ => &[],
+        };
+        let fields = parameters
+            .iter()
+            .filter_map(|field| {
+                let value = t.attributes.get(&self.get_table_key(field)?)?;
+                Some(((!is_prelude).then(|| field.clone()), value))
+            })
+            .collect();
+        Some((name, fields))
+    }
+
+    /// Write `reg` as `print` does, primitives are written directly without `core::fmt`
+    pub fn print_to<W: io::Write + ?Sized>(&self, reg: &Reg, out: &mut W) -> io::Result<()> {
         match reg {
-            Reg::Unit => write!(buffer, "()"),
-            Reg::Bool(b) => write!(buffer, "{b}"),
-            Reg::Int(i) => write!(buffer, "{i}"),
-            Reg::Float(f) => write!(buffer, "{f}"),
-            Reg::Str(sid) => write!(buffer, "{}", self.get_str(*sid).unwrap()),
-            Reg::Ref(r) => {
-                if visited.get(r).is_some() {
-                    write!(buffer, "<Recursive ref@{}>", *r).unwrap();
-                    return;
-                }
-                let obj = self.get_obj(*r);
-                let obj = if let Some(obj) = obj {
-                    obj
-                } else {
-                    write!(buffer, "<Invalid Ref@{r}>").unwrap();
-                    return;
-                };
-                visited.insert(*r);
-                match obj {
-                    GcObject::Closure {
-                        func_id,
-                        parameters: _,
-                        reg_size: _,
-                        captured: _,
-                    } => {
-                        write!(buffer, "Closure[{func_id}]")
-                    }
-                    GcObject::NativeFunction(f) => {
-                        write!(buffer, "External function@{:p}", Arc::as_ptr(f))
-                    }
-                    GcObject::UserData(data) => {
-                        write!(buffer, "UserData@{:p}", &data)
-                    }
-                    GcObject::List(l) => {
-                        write!(buffer, "[").unwrap();
-                        for (i, value) in l.iter().enumerate() {
-                            if i == l.len() - 1 {
-                                self.print_reg(value, visited, buffer);
-                            } else {
-                                self.print_reg(value, visited, buffer);
-                                write!(buffer, ", ").unwrap();
-                            }
-                        }
-                        write!(buffer, "]")
+            Reg::Unit => out.write_all(b"()"),
+            Reg::Bool(true) => out.write_all(b"true"),
+            Reg::Bool(false) => out.write_all(b"false"),
+            Reg::Int(i) => out.write_all(itoa::Buffer::new().format(*i).as_bytes()),
+            Reg::Float(f) => out.write_all(float_str(*f, &mut ryu::Buffer::new()).as_bytes()),
+            Reg::Str(sid) => out.write_all(self.get_str(*sid).unwrap().as_bytes()),
+            Reg::Ref(_) => out.write_all(self.print(reg).as_bytes()),
+        }
+    }
+
+    /// `visited` are containers being printed, reaching one of them again is a cycle
+    fn print_reg(
+        &self,
+        reg: &Reg,
+        visited: &mut BTreeSet<usize>,
+        depth: usize,
+        buffer: &mut String,
+    ) {
+        use core::fmt::Write;
+        let r = match reg {
+            Reg::Unit => return buffer.push_str("()"),
+            Reg::Bool(b) => return buffer.push_str(if *b { "true" } else { "false" }),
+            Reg::Int(i) => return buffer.push_str(itoa::Buffer::new().format(*i)),
+            Reg::Float(f) => return buffer.push_str(float_str(*f, &mut ryu::Buffer::new())),
+            Reg::Str(sid) => return buffer.push_str(self.get_str(*sid).unwrap()),
+            Reg::Ref(r) => *r,
+        };
+        if visited.contains(&r) {
+            write!(buffer, "<Recursive ref@{r}>").unwrap();
+            return;
+        }
+        let Some(obj) = self.get_obj(r) else {
+            write!(buffer, "<Invalid Ref@{r}>").unwrap();
+            return;
+        };
+        match obj {
+            GcObject::Closure { func_id, .. } => {
+                let info = self.functions.get(func_id);
+                match info.and_then(|info| Some((&info.name, info.location.as_ref()?))) {
+                    Some((Some(name), SourceLocation { path, line, column })) => {
+                        write!(buffer, "<fn {name} at {path}:{line}:{column}>").unwrap()
                     }
-                    GcObject::Table(t) => {
-                        write!(buffer, "{{").unwrap();
-                        for (i, (key, value)) in t.attributes.iter().enumerate() {
-                            let key = self.key_pool.look_up_key(*key).unwrap();
-                            write!(buffer, "{key} = ").unwrap();
-                            if i == t.attributes.len() - 1 {
-                                self.print_reg(value, visited, buffer);
-                            } else {
-                                self.print_reg(value, visited, buffer);
-                                write!(buffer, ", ").unwrap();
-                            }
-                        }
-                        write!(buffer, "}}")
+                    Some((None, SourceLocation { path, line, column })) => {
+                        write!(buffer, "<fn at {path}:{line}:{column}>").unwrap()
                     }
-                    GcObject::Tuple(t) => {
-                        write!(buffer, "(").unwrap();
-                        for (i, value) in t.iter().enumerate() {
-                            if i == t.len() - 1 {
-                                self.print_reg(value, visited, buffer);
-                            } else {
-                                self.print_reg(value, visited, buffer);
-                                write!(buffer, ", ").unwrap();
-                            }
-                        }
-                        write!(buffer, ")")
+                    None => write!(buffer, "<fn #{func_id}>").unwrap(),
+                }
+            }
+            GcObject::NativeFunction(f) => {
+                write!(buffer, "External function@{:p}", Arc::as_ptr(f)).unwrap()
+            }
+            GcObject::UserData => {
+                write!(buffer, "UserData@{:p}", self.user_data[&r].as_ref()).unwrap()
+            }
+            GcObject::BoundMethod { method, .. } => {
+                let mut inner = String::new();
+                self.print_reg(&Reg::Ref(*method), visited, depth, &mut inner);
+                let inner = inner
+                    .strip_prefix('<')
+                    .and_then(|s| s.strip_suffix('>'))
+                    .unwrap_or(&inner);
+                write!(buffer, "<bound {inner}>").unwrap()
+            }
+            GcObject::List(l) => {
+                let items = l.iter().map(|value| (None, value));
+                self.print_items(r, ("[", "]"), items, visited, depth, buffer)
+            }
+            GcObject::Tuple(t) => {
+                let items = t.iter().map(|value| (None, value));
+                self.print_items(r, ("(", ")"), items, visited, depth, buffer)
+            }
+            GcObject::Table(t) => {
+                if let Some((name, fields)) = self.variant_fields(t) {
+                    if fields.is_empty() {
+                        buffer.push_str(name);
+                    } else {
+                        let open = format!("{name}(");
+                        let items = fields.into_iter();
+                        self.print_items(r, (&open, ")"), items, visited, depth, buffer)
                     }
+                    return;
+                }
+                let indices = t
+                    .indices
+                    .iter()
+                    .map(|(key, value)| (Some(format!("[{key}]")), value));
+                let attributes = t.attributes.iter().map(|(key, value)| {
+                    let key = self.key_pool.look_up_key(*key).unwrap().to_string();
+                    (Some(key), value)
+                });
+                let items = indices.chain(attributes);
+                self.print_items(r, ("{", "}"), items, visited, depth, buffer)
+            }
+        }
+    }
+
+    /// Print items of container `r`, keys are printed before values if any
+    fn print_items<'a>(
+        &self,
+        r: usize,
+        (open, close): (&str, &str),
+        items: impl Iterator<Item = (Option<String>, &'a Reg)>,
+        visited: &mut BTreeSet<usize>,
+        depth: usize,
+        buffer: &mut String,
+    ) {
+        buffer.push_str(open);
+        let max_depth = self.print_options.max_depth.unwrap_or(usize::MAX);
+        let max_items = self.print_options.max_items.unwrap_or(usize::MAX);
+        let mut items = items.peekable();
+        if depth >= max_depth && items.peek().is_some() {
+            buffer.push_str("...");
+        } else {
+            visited.insert(r);
+            for (i, (key, value)) in items.enumerate() {
+                if i > 0 {
+                    buffer.push_str(", ");
                 }
+                if i == max_items {
+                    buffer.push_str("...");
+                    break;
+                }
+                if let Some(key) = key {
+                    buffer.push_str(&key);
+                    buffer.push_str(" = ");
+                }
+                self.print_reg(value, visited, depth + 1, buffer);
             }
+            // Siblings sharing a container are not cycles
+            visited.remove(&r);
         }
-        .unwrap();
+        buffer.push_str(close);
     }
 
     pub fn set_main_reg_size(&mut self, n: usize) {
@@ -498,31 +1381,24 @@ impl<Buffer: IoWrite> Gc<Buffer> {
         self.call_stack.fp.reg_size = n;
     }
 
-    /// Collect garbage if threshold requirement is met
-    #[cfg(not(test))]
+    /// Collect garbage if threshold requirement is met, or always in stress mode
     fn try_collect(&mut self) {
         if self.paused {
             return;
         }
-        let total_allocated =
-            self.string_pool.len() + self.obj_pool.len() + self.escaped_pool.len();
-        if total_allocated > self.threshold {
-            self.collect()
-        }
-
-        let total_allocated =
-            self.string_pool.len() + self.obj_pool.len() + self.escaped_pool.len();
-        self.threshold = total_allocated * 2;
-    }
-
-    #[cfg(test)]
-    fn try_collect(&mut self) {
-        if self.paused {
+        if self.stress {
+            self.collect();
+            if let Err(err) = self.verify_heap() {
+                panic!("Heap is corrupted: {err}");
+            }
             return;
         }
-        let _ = self.threshold;
-        let _ = self.obj_pool.len();
-        self.collect()
+        if self.total_allocated() > self.threshold || self.string_bytes > self.string_threshold {
+            self.collect();
+            // Thresholds only grow with what survives, otherwise garbage is never collected
+            self.threshold = (self.total_allocated() * 2).max(MIN_THRESHOLD);
+            self.string_threshold = (self.string_bytes * 2).max(MIN_STRING_THRESHOLD);
+        }
     }
 
     fn clear_marks(&mut self) {
@@ -531,17 +1407,21 @@ impl<Buffer: IoWrite> Gc<Buffer> {
         self.string_pool.clear_marks();
     }
 
-    fn mark_roots(&mut self) {
-        self.clear_marks();
-
-        let stack = &mut self.call_stack;
-        let stack_limit = stack
+    /// Number of stack registers used by frames, the rest are garbage
+    fn stack_limit(&self) -> usize {
+        let stack = &self.call_stack;
+        stack
             .frames
             .iter()
             .map(|frame| frame.ptr + frame.reg_size)
-            .fold(stack.fp.ptr + stack.fp.reg_size, usize::max);
+            .fold(stack.fp.ptr + stack.fp.reg_size, usize::max)
+    }
+
+    fn mark_roots(&mut self) {
+        self.clear_marks();
 
-        stack.regs.truncate(stack_limit);
+        let stack_limit = self.stack_limit();
+        self.call_stack.regs.truncate(stack_limit);
 
         self.call_stack.regs.iter().for_each(|reg| {
             match reg {
@@ -560,6 +1440,30 @@ impl<Buffer: IoWrite> Gc<Buffer> {
             };
         });
 
+        // Deferred closures, pending or running, may not be referenced by any register, neither
+        // do `__init` and the instance it initializes
+        let frames = self.call_stack.frames.iter().chain([&self.call_stack.fp]);
+        frames
+            .flat_map(|frame| {
+                let running = (frame.is_deferred || frame.instance.is_some()).then_some(&frame.rid);
+                frame.deferred.iter().chain(running).chain(&frame.instance)
+            })
+            .for_each(|rid| {
+                self.gray_pool.objects.insert(*rid);
+            });
+        self.call_stack
+            .frames
+            .iter()
+            .chain([&self.call_stack.fp])
+            .filter_map(|frame| frame.return_value.as_ref())
+            .for_each(|reg| match reg {
+                Reg::Str(sid) => self.string_pool.mark(*sid),
+                Reg::Ref(rid) => {
+                    self.gray_pool.objects.insert(*rid);
+                }
+                _ => (),
+            });
+
         self.gray_pool
             .pinned_string
             .iter()
@@ -569,6 +1473,28 @@ impl<Buffer: IoWrite> Gc<Buffer> {
             self.gray_pool.objects.insert(*sid);
         });
 
+        self.gray_pool
+            .temp_string
+            .iter()
+            .for_each(|sid| self.string_pool.mark(*sid));
+
+        self.gray_pool.temp_obj.iter().for_each(|rid| {
+            self.gray_pool.objects.insert(*rid);
+        });
+
+        self.host_roots.retain(|root| root.strong_count() > 0);
+        self.host_roots.iter().for_each(|root| {
+            if let Some(root) = root.upgrade() {
+                match root.as_ref() {
+                    Reg::Str(sid) => self.string_pool.mark(*sid),
+                    Reg::Ref(rid) => {
+                        self.gray_pool.objects.insert(*rid);
+                    }
+                    _ => (),
+                }
+            }
+        });
+
         self.module_map
             .values()
             .filter_map(|x| *x)
@@ -581,11 +1507,155 @@ impl<Buffer: IoWrite> Gc<Buffer> {
         self.paused = true
     }
 
+    /// Collect garbage before every allocation and verify the heap after each collection
+    ///
+    /// Very slow, used to test the collector and external functions holding values. A corrupted
+    /// heap panics with the first dangling reference found. Stress mode is on by default in
+    /// tests of this crate and with feature `gc-stress`.
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    /// Check that roots and values not freed only refer to values not freed
+    ///
+    /// As values reachable from roots are never collected, a dangling reference means a
+    /// reachable value is freed. Return a description of the first one found.
+    pub fn verify_heap(&self) -> Result<(), String> {
+        let stack = &self.call_stack;
+        for (i, reg) in stack.regs.iter().take(self.stack_limit()).enumerate() {
+            match reg {
+                StackReg::Reg(reg) => self.verify_reg(reg, || format!("Register #{i}"))?,
+                StackReg::Shared(id) => self.verify_escaped(*id, || format!("Register #{i}"))?,
+            }
+        }
+        for frame in stack.frames.iter().chain([&stack.fp]) {
+            let running = (frame.is_deferred || frame.instance.is_some()).then_some(&frame.rid);
+            for rid in frame.deferred.iter().chain(running).chain(&frame.instance) {
+                self.verify_obj(*rid, || "Call frame".to_string())?;
+            }
+            if let Some(reg) = &frame.return_value {
+                self.verify_reg(reg, || "Return value".to_string())?;
+            }
+        }
+
+        let gray_pool = &self.gray_pool;
+        for sid in gray_pool.pinned_string.iter().chain(&gray_pool.temp_string) {
+            self.verify_str(*sid, || "Rooted string".to_string())?;
+        }
+        for rid in gray_pool.pinned_obj.iter().chain(&gray_pool.temp_obj) {
+            self.verify_obj(*rid, || "Rooted object".to_string())?;
+        }
+        for root in self.host_roots.iter().filter_map(Weak::upgrade) {
+            self.verify_reg(&root, || "Host root".to_string())?;
+        }
+        for rid in self.module_map.values().flatten() {
+            self.verify_obj(*rid, || "Module cache".to_string())?;
+        }
+        for rid in self.frozen.keys() {
+            self.verify_obj(*rid, || "Frozen objects".to_string())?;
+        }
+
+        for (id, reg) in self.escaped_pool.iter() {
+            self.verify_reg(reg, || format!("Captured variable #{id}"))?;
+        }
+        for (id, obj) in self.obj_pool.iter() {
+            let from = || format!("Object #{id}");
+            match obj {
+                GcObject::List(l) | GcObject::Tuple(l) => {
+                    l.iter().try_for_each(|reg| self.verify_reg(reg, from))?
+                }
+                GcObject::Table(t) => {
+                    t.attributes
+                        .values()
+                        .chain(t.indices.values())
+                        .try_for_each(|reg| self.verify_reg(reg, from))?;
+                    if let Some(meta) = t.meta_table {
+                        self.verify_obj(meta, from)?;
+                    }
+                }
+                GcObject::Closure { captured, .. } => captured
+                    .iter()
+                    .try_for_each(|(_, id)| self.verify_escaped(*id, from))?,
+                GcObject::BoundMethod { receiver, method } => {
+                    self.verify_reg(receiver, from)?;
+                    self.verify_obj(*method, from)?;
+                }
+                GcObject::NativeFunction(_) | GcObject::UserData => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_reg(&self, reg: &Reg, from: impl Fn() -> String) -> Result<(), String> {
+        match reg {
+            Reg::Str(sid) => self.verify_str(*sid, from),
+            Reg::Ref(rid) => self.verify_obj(*rid, from),
+            _ => Ok(()),
+        }
+    }
+
+    fn verify_str(&self, sid: usize, from: impl Fn() -> String) -> Result<(), String> {
+        match self.string_pool.get(sid) {
+            Some(_) => Ok(()),
+            None => Err(format!("{} refers to freed string #{sid}", from())),
+        }
+    }
+
+    fn verify_obj(&self, rid: usize, from: impl Fn() -> String) -> Result<(), String> {
+        match self.obj_pool.get(rid) {
+            Some(_) => Ok(()),
+            None => Err(format!("{} refers to freed object #{rid}", from())),
+        }
+    }
+
+    fn verify_escaped(&self, id: usize, from: impl Fn() -> String) -> Result<(), String> {
+        match self.escaped_pool.get(id) {
+            Some(_) => Ok(()),
+            None => Err(format!(
+                "{} refers to freed captured variable #{id}",
+                from()
+            )),
+        }
+    }
+
     pub fn resume(&mut self) {
         self.paused = false
     }
 
+    /// Number of allocations and collections since the last reset
+    pub fn counters(&self) -> (u64, u64) {
+        (self.allocations, self.collections)
+    }
+
+    pub fn reset_counters(&mut self) {
+        self.allocations = 0;
+        self.collections = 0;
+    }
+
+    /// Bytes of live strings, including garbage not yet collected
+    #[cfg(test)]
+    pub fn string_bytes(&self) -> usize {
+        self.string_bytes
+    }
+
+    /// Number of live strings and objects, including garbage not yet collected
+    pub fn live_count(&self) -> (usize, usize) {
+        (self.string_pool.len(), self.obj_pool.len())
+    }
+
+    /// Most strings and objects alive at once since `reset_peaks`, including garbage not yet
+    /// collected
+    pub fn peak_count(&self) -> (usize, usize) {
+        (self.peak_strings, self.peak_objects)
+    }
+
+    /// Start tracking peaks from the number of strings and objects alive now
+    pub fn reset_peaks(&mut self) {
+        (self.peak_strings, self.peak_objects) = self.live_count();
+    }
+
     pub fn collect(&mut self) {
+        self.collections += 1;
         self.mark_roots();
         let gray_pool = &mut self.gray_pool;
 
@@ -616,7 +1686,7 @@ impl<Buffer: IoWrite> Gc<Buffer> {
                 // Mark all objects
                 let obj_id = gray_pool.objects.pop_last().unwrap();
                 match unsafe { self.obj_pool.get_unchecked_raw(obj_id) } {
-                    (_, true) | (GcObject::NativeFunction { .. } | GcObject::UserData(_), _) => (),
+                    (_, true) | (GcObject::NativeFunction { .. } | GcObject::UserData, _) => (),
                     (GcObject::List(l) | GcObject::Tuple(l), false) => {
                         for item in l.iter() {
                             mark_reg(item, &mut gray_pool.objects, &mut self.string_pool);
@@ -625,10 +1695,12 @@ impl<Buffer: IoWrite> Gc<Buffer> {
                     (
                         GcObject::Table(Table {
                             attributes,
+                            indices,
                             meta_table,
+                            ..
                         }),
                         false,
-                    ) => attributes.values().for_each(|reg| {
+                    ) => attributes.values().chain(indices.values()).for_each(|reg| {
                         mark_reg(reg, &mut gray_pool.objects, &mut self.string_pool);
                         if let Some(reg_id) = meta_table {
                             gray_pool.objects.insert(*reg_id);
@@ -639,13 +1711,22 @@ impl<Buffer: IoWrite> Gc<Buffer> {
                             gray_pool.escaped.insert(*reg_id);
                         });
                     }
+                    (GcObject::BoundMethod { receiver, method }, false) => {
+                        mark_reg(receiver, &mut gray_pool.objects, &mut self.string_pool);
+                        gray_pool.objects.insert(*method);
+                    }
                 }
                 self.obj_pool.mark(obj_id);
             }
         }
         self.escaped_pool.collect();
         self.string_pool.collect();
+        self.string_bytes = self.string_pool.iter().map(|(_, s)| s.len()).sum();
         self.obj_pool.collect();
+        let obj_pool = &self.obj_pool;
+        self.frozen.retain(|rid, _| obj_pool.get(*rid).is_some());
+        self.user_data
+            .retain(|rid, _| matches!(obj_pool.get(*rid), Some(GcObject::UserData)));
     }
 }
 
@@ -1,89 +1,141 @@
-use std::collections::BTreeSet;
+use alloc::{collections::BTreeSet, sync::Arc, vec, vec::Vec};
 
 use more_asserts::debug_assert_gt;
 
-pub struct Pool<T: Default> {
-    pool: Vec<(T, bool)>,
+/// Number of elements in a page
+const PAGE_SIZE: usize = 256;
+
+/// Elements are stored in pages shared between copies of a pool, a page is copied when a copy
+/// first writes to it, thus copying a pool only copies the page table.
+pub struct Pool<T: Clone + Default> {
+    pages: Vec<Arc<Vec<T>>>,
+    len: usize,
+    /// Marks of the current collection, never shared between copies
+    marks: Vec<bool>,
     free: BTreeSet<usize>,
 }
 
-impl<T: Default> Pool<T> {
+impl<T: Clone + Default> Pool<T> {
     pub fn new() -> Self {
         Self {
-            pool: vec![],
+            pages: vec![],
+            len: 0,
+            marks: vec![],
             free: BTreeSet::new(),
         }
     }
 
     pub fn len(&self) -> usize {
-        self.pool.len() - self.free.len()
+        self.len - self.free.len()
     }
 
     pub fn alloc(&mut self, value: T) -> usize {
-        if self.free.is_empty() {
-            self.pool.push((value, false));
-            self.pool.len() - 1
-        } else {
-            let ref_id = self.free.pop_last().unwrap();
-            debug_assert_gt!(self.pool.len(), ref_id);
-            *unsafe { self.pool.get_unchecked_mut(ref_id) } = (value, false);
+        if let Some(ref_id) = self.free.pop_last() {
+            debug_assert_gt!(self.len, ref_id);
+            *self.slot_mut(ref_id) = value;
             ref_id
+        } else {
+            if self.len.is_multiple_of(PAGE_SIZE) {
+                self.pages.push(Arc::new(Vec::with_capacity(PAGE_SIZE)));
+            }
+            Arc::make_mut(self.pages.last_mut().unwrap()).push(value);
+            self.len += 1;
+            self.len - 1
         }
     }
 
+    fn is_live(&self, idx: usize) -> bool {
+        idx < self.len && !self.free.contains(&idx)
+    }
+
+    fn slot(&self, idx: usize) -> &T {
+        &self.pages[idx / PAGE_SIZE][idx % PAGE_SIZE]
+    }
+
+    /// Copy the page of `idx` if it is shared
+    fn slot_mut(&mut self, idx: usize) -> &mut T {
+        &mut Arc::make_mut(&mut self.pages[idx / PAGE_SIZE])[idx % PAGE_SIZE]
+    }
+
     pub fn get(&self, idx: usize) -> Option<&T> {
-        if idx < self.pool.len() && self.free.get(&idx).is_none() {
-            Some(&self.pool[idx].0)
-        } else {
-            None
-        }
+        self.is_live(idx).then(|| self.slot(idx))
     }
 
     pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
-        if idx < self.pool.len() && self.free.get(&idx).is_none() {
-            Some(&mut self.pool[idx].0)
+        if self.is_live(idx) {
+            Some(self.slot_mut(idx))
         } else {
             None
         }
     }
 
-    pub unsafe fn get_unchecked_raw(&self, idx: usize) -> &(T, bool) {
-        debug_assert!(self.pool.len() > idx && self.free.get(&idx).is_none());
-        self.pool.get_unchecked(idx)
+    /// Get an element and if it is marked
+    pub unsafe fn get_unchecked_raw(&self, idx: usize) -> (&T, bool) {
+        debug_assert!(self.is_live(idx));
+        (
+            self.get_unchecked(idx),
+            self.marks.get(idx).copied().unwrap_or(false),
+        )
     }
 
     pub unsafe fn get_unchecked(&self, idx: usize) -> &T {
-        debug_assert!(self.pool.len() > idx && self.free.get(&idx).is_none());
-        &self.pool.get_unchecked(idx).0
+        debug_assert!(self.is_live(idx));
+        self.pages
+            .get_unchecked(idx / PAGE_SIZE)
+            .get_unchecked(idx % PAGE_SIZE)
     }
 
     pub unsafe fn get_unchecked_mut(&mut self, idx: usize) -> &mut T {
-        debug_assert!(self.pool.len() > idx && self.free.get(&idx).is_none());
-        &mut self.pool.get_unchecked_mut(idx).0
+        debug_assert!(self.is_live(idx));
+        Arc::make_mut(self.pages.get_unchecked_mut(idx / PAGE_SIZE))
+            .get_unchecked_mut(idx % PAGE_SIZE)
+    }
+
+    /// Ids and values of elements not freed
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.pages
+            .iter()
+            .flat_map(|page| page.iter())
+            .enumerate()
+            .filter(|(id, _)| !self.free.contains(id))
     }
 
     pub fn mark(&mut self, idx: usize) {
-        debug_assert!(self.pool.len() > idx && self.free.get(&idx).is_none());
-        unsafe { self.pool.get_unchecked_mut(idx).1 = true }
+        debug_assert!(self.is_live(idx));
+        if self.marks.len() <= idx {
+            self.marks.resize(self.len, false);
+        }
+        self.marks[idx] = true;
     }
 
     pub fn clear_marks(&mut self) {
-        self.pool.iter_mut().for_each(|pair| pair.1 = false);
+        self.marks.clear();
+        self.marks.resize(self.len, false);
     }
 
+    /// Free unmarked elements, pages without garbage are left shared
     pub fn collect(&mut self) {
-        self.pool
-            .iter_mut()
-            .enumerate()
-            .for_each(|(id, (obj, mark))| {
-                if !*mark {
-                    self.free.insert(id).then(|| *obj = Default::default());
-                }
-            });
+        for id in 0..self.len {
+            if !self.marks.get(id).copied().unwrap_or(false) && self.free.insert(id) {
+                *self.slot_mut(id) = Default::default();
+            }
+        }
+    }
+}
+
+impl<T: Clone + Default> Clone for Pool<T> {
+    /// Share all pages with the copy
+    fn clone(&self) -> Self {
+        Self {
+            pages: self.pages.clone(),
+            len: self.len,
+            marks: vec![],
+            free: self.free.clone(),
+        }
     }
 }
 
-impl<T: Default> Default for Pool<T> {
+impl<T: Clone + Default> Default for Pool<T> {
     fn default() -> Self {
         Self::new()
     }
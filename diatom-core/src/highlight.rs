@@ -0,0 +1,57 @@
+//! Token classification for syntax highlighting
+use alloc::{string::ToString, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    file_manager::FileManager,
+    frontend::{Lexer, Token},
+};
+
+/// Class of a token, see `highlight`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenClass {
+    /// Keywords including `true`, `false` and word operators like `and`
+    Keyword,
+    Operator,
+    Str,
+    /// Integer or float
+    Number,
+    Identifier,
+    Comment,
+    /// Input that can not be lexed, e.g. an unterminated string
+    Invalid,
+}
+
+/// Classify tokens of `source` by byte ranges, sorted by start
+///
+/// Only the lexer is used so partially invalid input is still classified. Whitespace is not
+/// covered by any range.
+pub fn highlight(source: &str) -> Vec<(Range<usize>, TokenClass)> {
+    let mut file_manager = FileManager::new();
+    let fid = file_manager.add_file("", source.to_string());
+    let stream = Lexer::lex(&mut file_manager, fid);
+    let tokens = stream.tokens().iter().map(|(token, loc)| {
+        let class = match token {
+            Token::Str(_) => TokenClass::Str,
+            Token::Integer(_) | Token::Float(_) => TokenClass::Number,
+            Token::Id(_) => TokenClass::Identifier,
+            Token::Key(_) => TokenClass::Keyword,
+            Token::Op(_) if source[loc.start..loc.end].starts_with(char::is_alphabetic) => {
+                TokenClass::Keyword
+            }
+            Token::Op(_) => TokenClass::Operator,
+        };
+        (loc.start..loc.end, class)
+    });
+    let comments = stream
+        .comments()
+        .iter()
+        .map(|loc| (loc.start..loc.end, TokenClass::Comment));
+    let invalid = stream
+        .invalid()
+        .iter()
+        .map(|loc| (loc.start..loc.end, TokenClass::Invalid));
+    let mut classes: Vec<_> = tokens.chain(comments).chain(invalid).collect();
+    classes.sort_by_key(|(range, _)| range.start);
+    classes
+}
@@ -0,0 +1,139 @@
+//! Services of the platform used by the interpreter, which can be supplied by host
+//!
+//! The standard library of Rust provides them on most platforms. On platforms where it does
+//! not, such as `wasm32-unknown-unknown` or an embedded device, host can supply them through
+//! `Interpreter::set_clock` and `Interpreter::set_file_system`. Without feature `std`, there is
+//! no clock and no file until host supplies them.
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc};
+use core::{hash::BuildHasher, time::Duration};
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::{collections::HashMap, fs, sync::OnceLock, time::Instant};
+
+use crate::compat::{io, path::Path};
+use crate::IoRead;
+
+/// A monotonic clock
+pub trait Clock: Send + Sync {
+    /// Time elapsed since an arbitrary point, never decreases
+    fn now(&self) -> Duration;
+}
+
+/// Clock of the standard library
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdClock;
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now(&self) -> Duration {
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed()
+    }
+}
+
+/// Time after which execution is aborted, set by `timeout` of the sandbox policy
+///
+/// External functions that may block for long, e.g. waiting for a process, get it by
+/// `State::deadline` and stop waiting once it passes, returning the error made by
+/// `State::interrupt`.
+#[derive(Clone)]
+pub struct Deadline {
+    clock: Arc<dyn Clock>,
+    at: Duration,
+}
+
+impl Deadline {
+    /// Deadline at `at` of `clock`
+    pub fn new(clock: Arc<dyn Clock>, at: Duration) -> Self {
+        Self { clock, at }
+    }
+
+    /// Time left before the deadline, zero once it has passed
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_sub(self.clock.now())
+    }
+
+    pub fn passed(&self) -> bool {
+        self.clock.now() >= self.at
+    }
+}
+
+/// Files that modules are imported from
+pub trait FileSystem: Send + Sync {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+}
+
+/// File system of the standard library
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdFileSystem;
+
+#[cfg(feature = "std")]
+impl FileSystem for StdFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+/// File system without any file, used without feature `std` until host supplies one
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoFileSystem;
+
+impl FileSystem for NoFileSystem {
+    fn read_to_string(&self, _: &Path) -> io::Result<String> {
+        Err(io::ErrorKind::NotFound.into())
+    }
+}
+
+/// Modules served by host instead of files, e.g. from memory, an archive or a database
+///
+/// Modules of the loader are found before files and are always imported by their full names,
+/// even by another module of the loader. A module imported from a loader is reported as file
+/// `name/of/module.dm` in diagnostics.
+pub trait ModuleLoader: Send + Sync {
+    /// Source of module `name`, whose parts are joined by `.`, e.g. `util.strings`
+    fn load(&self, name: &str) -> Option<String>;
+}
+
+impl<S: BuildHasher + Send + Sync> ModuleLoader for HashMap<String, String, S> {
+    fn load(&self, name: &str) -> Option<String> {
+        self.get(name).cloned()
+    }
+}
+
+impl ModuleLoader for BTreeMap<String, String> {
+    fn load(&self, name: &str) -> Option<String> {
+        self.get(name).cloned()
+    }
+}
+
+/// Whether the standard library has a clock, reading it panics on `wasm32-unknown-unknown`
+pub(crate) const HAS_STD_CLOCK: bool =
+    cfg!(feature = "std") && !cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+
+/// Clock used until host supplies one
+pub(crate) fn default_clock() -> Option<Arc<dyn Clock>> {
+    #[cfg(feature = "std")]
+    if HAS_STD_CLOCK {
+        return Some(Arc::new(StdClock));
+    }
+    None
+}
+
+/// File system used until host supplies one
+pub(crate) fn default_file_system() -> Arc<dyn FileSystem> {
+    #[cfg(feature = "std")]
+    return Arc::new(StdFileSystem);
+    #[cfg(not(feature = "std"))]
+    Arc::new(NoFileSystem)
+}
+
+/// Input of `input()` used until host supplies one, empty without feature `std`
+pub(crate) fn default_input() -> Box<dyn IoRead + Send> {
+    #[cfg(feature = "std")]
+    return Box::new(io::BufReader::new(io::stdin()));
+    #[cfg(not(feature = "std"))]
+    Box::new(&[][..])
+}
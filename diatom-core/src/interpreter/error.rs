@@ -1,3 +1,9 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
 use codespan_reporting::diagnostic::Label;
 
 use crate::file_manager::{Diagnostic, Loc};
@@ -8,8 +14,8 @@ use crate::file_manager::{Diagnostic, Loc};
 pub enum ErrorCode {
     /// E2000 Can not assign to this expression
     CannotAssign(Loc),
-    /// E2001 Name not defined
-    NameNotDefined(Loc, String),
+    /// E2001 Name not defined, with a similar name if there is one
+    NameNotDefined(Loc, String, Option<String>),
     /// E2002 Assignment Not Allowed here
     InvalidAssignment(Loc),
     /// E2003 Break outside loop
@@ -22,6 +28,18 @@ pub enum ErrorCode {
     InvalidMember(Loc),
     /// E2007 Set Meta Not Allowed
     MetaNotAllowed(Loc),
+    /// E2008 Spread outside a list or table literal
+    SpreadNotAllowed(Loc),
+    /// E2009 Defer outside function
+    DeferOutsideFunction(Loc),
+    /// E2010 Variable read in the assignment that defines it, only in strict mode
+    UsedBeforeAssigned(Loc, String, Option<String>),
+}
+
+fn did_you_mean(similar: Option<String>) -> Vec<String> {
+    similar
+        .map(|name| vec![format!("Did you mean `{name}`?")])
+        .unwrap_or_default()
 }
 
 impl From<ErrorCode> for Diagnostic {
@@ -31,10 +49,11 @@ impl From<ErrorCode> for Diagnostic {
                 .with_code("E2000")
                 .with_message("Can not assign to this expression")
                 .with_labels(vec![Label::primary(loc.fid, loc)]),
-            ErrorCode::NameNotDefined(loc, name) => Diagnostic::error()
+            ErrorCode::NameNotDefined(loc, name, similar) => Diagnostic::error()
                 .with_code("E2001")
                 .with_message(format!("Name `{name}` is not defined in current scope"))
-                .with_labels(vec![Label::primary(loc.fid, loc)]),
+                .with_labels(vec![Label::primary(loc.fid, loc)])
+                .with_notes(did_you_mean(similar)),
             ErrorCode::InvalidAssignment(loc) => Diagnostic::error()
                 .with_code("E2002")
                 .with_message("Assignment can not be used as expression")
@@ -63,6 +82,30 @@ impl From<ErrorCode> for Diagnostic {
                     "Meta table can only be set on newly created table".to_string(),
                     "For example: `table = {...} <- Meta`".to_string(),
                 ]),
+            ErrorCode::SpreadNotAllowed(loc) => Diagnostic::error()
+                .with_code("E2008")
+                .with_message("Spread is not allowed here")
+                .with_labels(vec![Label::primary(loc.fid, loc)])
+                .with_notes(vec![
+                    "`...` can only be used on items of a list or table literal".to_string(),
+                    "For example: `[1, xs..., 2]` or `{base..., key = 1}`".to_string(),
+                ]),
+            ErrorCode::DeferOutsideFunction(loc) => Diagnostic::error()
+                .with_code("E2009")
+                .with_message("Can not defer outside a function")
+                .with_labels(vec![Label::primary(loc.fid, loc)]),
+            ErrorCode::UsedBeforeAssigned(loc, name, similar) => {
+                let mut notes = vec![
+                    "Strict mode does not allow reading a variable in the assignment that defines it"
+                        .to_string(),
+                ];
+                notes.extend(did_you_mean(similar));
+                Diagnostic::error()
+                    .with_code("E2010")
+                    .with_message(format!("Name `{name}` is used before it is assigned"))
+                    .with_labels(vec![Label::primary(loc.fid, loc)])
+                    .with_notes(notes)
+            }
         }
     }
 }
@@ -0,0 +1,195 @@
+use super::*;
+use alloc::{format, string::String};
+
+/// Primitive types that can be extracted from parameters without accessing the heap
+pub trait ArgType: Sized {
+    /// Name of this type shown in error messages
+    const NAME: &'static str;
+    fn from_value(value: &DiatomValue) -> Option<Self>;
+}
+
+impl ArgType for () {
+    const NAME: &'static str = "Unit";
+    fn from_value(value: &DiatomValue) -> Option<Self> {
+        match value {
+            DiatomValue::Unit => Some(()),
+            _ => None,
+        }
+    }
+}
+
+impl ArgType for bool {
+    const NAME: &'static str = "Bool";
+    fn from_value(value: &DiatomValue) -> Option<Self> {
+        match value {
+            DiatomValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl ArgType for i64 {
+    const NAME: &'static str = "Int";
+    fn from_value(value: &DiatomValue) -> Option<Self> {
+        match value {
+            DiatomValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+impl ArgType for f64 {
+    const NAME: &'static str = "Float";
+    fn from_value(value: &DiatomValue) -> Option<Self> {
+        match value {
+            DiatomValue::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
+impl ArgType for DiatomValue {
+    const NAME: &'static str = "Any";
+    fn from_value(value: &DiatomValue) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+fn type_name(value: &DiatomValue) -> &'static str {
+    match value {
+        DiatomValue::Unit => "Unit",
+        DiatomValue::Bool(_) => "Bool",
+        DiatomValue::Int(_) => "Int",
+        DiatomValue::Float(_) => "Float",
+        DiatomValue::Str(_) => "String",
+        DiatomValue::Ref(_) => "Reference",
+    }
+}
+
+/// Typed accessor of external function parameters
+///
+/// All errors contain the function name and are ready to be returned from an external function.
+///
+/// # Example
+/// ```
+/// use diatom_core::ffi::{Args, DiatomValue, State};
+/// # use diatom_core::IoWrite;
+///
+/// fn repeat<Buffer: IoWrite>(
+///     state: &mut State<Buffer>,
+///     parameters: &[DiatomValue],
+///     _: &mut Buffer,
+/// ) -> Result<DiatomValue, String> {
+///     let args = Args::new("repeat", parameters);
+///     args.expect_len(2)?;
+///     let n = args.get::<i64>(1)?;
+///     let s = args.get_str(state, 0)?.repeat(n.max(0) as usize);
+///     Ok(DiatomValue::Str(state.create_str(s)))
+/// }
+/// ```
+pub struct Args<'a> {
+    name: &'a str,
+    parameters: &'a [DiatomValue],
+}
+
+impl<'a> Args<'a> {
+    pub fn new(name: &'a str, parameters: &'a [DiatomValue]) -> Self {
+        Self { name, parameters }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parameters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parameters.is_empty()
+    }
+
+    /// Assure exactly `n` parameters are provided
+    pub fn expect_len(&self, n: usize) -> Result<(), String> {
+        if self.parameters.len() != n {
+            return Err(format!(
+                "`{}` expected {n} parameter while {} is provided",
+                self.name,
+                self.parameters.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Assure number of parameters is within `min..=max`
+    pub fn expect_len_between(&self, min: usize, max: usize) -> Result<(), String> {
+        let len = self.parameters.len();
+        if len < min || len > max {
+            return Err(format!(
+                "`{}` expected {min} to {max} parameter while {len} is provided",
+                self.name,
+            ));
+        }
+        Ok(())
+    }
+
+    fn get_value(&self, idx: usize) -> Result<&'a DiatomValue, String> {
+        self.parameters.get(idx).ok_or_else(|| {
+            format!(
+                "`{}` expected at least {} parameter while {} is provided",
+                self.name,
+                idx + 1,
+                self.parameters.len()
+            )
+        })
+    }
+
+    fn type_error(&self, idx: usize, expected: &str, got: &DiatomValue) -> String {
+        format!(
+            "`{}` expected type `{expected}` for parameter #{idx} while `{}` is provided",
+            self.name,
+            type_name(got)
+        )
+    }
+
+    /// Get parameter at `idx` as a primitive type
+    pub fn get<T: ArgType>(&self, idx: usize) -> Result<T, String> {
+        let value = self.get_value(idx)?;
+        T::from_value(value).ok_or_else(|| self.type_error(idx, T::NAME, value))
+    }
+
+    /// Same as `get` but return None if parameter at `idx` is not provided
+    pub fn get_opt<T: ArgType>(&self, idx: usize) -> Result<Option<T>, String> {
+        if idx >= self.parameters.len() {
+            return Ok(None);
+        }
+        self.get(idx).map(Some)
+    }
+
+    /// Get parameter at `idx` as a string
+    pub fn get_str<'s, Buffer: IoWrite>(
+        &self,
+        state: &'s State<Buffer>,
+        idx: usize,
+    ) -> Result<&'s str, String> {
+        match self.get_value(idx)? {
+            DiatomValue::Str(sid) => Ok(state.get_string_by_id(*sid).unwrap()),
+            value => Err(self.type_error(idx, "String", value)),
+        }
+    }
+
+    /// Get parameter at `idx` and convert it into a rust value
+    pub fn get_as<T: FromDiatom, Buffer: IoWrite>(
+        &self,
+        state: &State<Buffer>,
+        idx: usize,
+    ) -> Result<T, String> {
+        let value = self.get_value(idx)?;
+        T::from_diatom(state, value)
+            .map_err(|err| format!("`{}` got invalid parameter #{idx}: {err}", self.name))
+    }
+
+    /// Get parameter at `idx` as a reference id
+    pub fn get_ref(&self, idx: usize) -> Result<usize, String> {
+        match self.get_value(idx)? {
+            DiatomValue::Ref(rid) => Ok(*rid),
+            value => Err(self.type_error(idx, "Reference", value)),
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::hash::BuildHasher;
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use super::*;
+
+/// Convert a rust value into a diatom value
+///
+/// Containers are converted recursively: `Vec` into `List`, maps with string keys into `Table`
+/// and `Option` into `Option::Some`/`Option::None`.
+pub trait IntoDiatom {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue;
+}
+
+/// Convert a diatom value into a rust value
+///
+/// Return `Err` with a description of the mismatch if value can not be converted.
+pub trait FromDiatom: Sized {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String>;
+}
+
+/// Type name of a value shown in error messages
+fn type_name<Buffer: IoWrite>(state: &State<Buffer>, value: &DiatomValue) -> &'static str {
+    match value {
+        DiatomValue::Unit => "Unit",
+        DiatomValue::Bool(_) => "Bool",
+        DiatomValue::Int(_) => "Int",
+        DiatomValue::Float(_) => "Float",
+        DiatomValue::Str(_) => "String",
+        DiatomValue::Ref(rid) => match state.gc.get_obj(*rid) {
+            Some(GcObject::Closure { .. }) => "Closure",
+            Some(GcObject::NativeFunction(_)) => "Extern_Function",
+            Some(GcObject::BoundMethod { .. }) => "Bound_Method",
+            Some(GcObject::Table(_)) => "Table",
+            Some(GcObject::Tuple(_)) => "Tuple",
+            Some(GcObject::List(_)) => "List",
+            Some(GcObject::UserData) => "UserData",
+            None => "Invalid_Reference",
+        },
+    }
+}
+
+fn type_error<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    expected: &str,
+    value: &DiatomValue,
+) -> String {
+    format!(
+        "Expected type `{expected}` while `{}` is provided",
+        type_name(state, value)
+    )
+}
+
+impl IntoDiatom for DiatomValue {
+    fn into_diatom<Buffer: IoWrite>(self, _: &mut State<Buffer>) -> DiatomValue {
+        self
+    }
+}
+
+impl FromDiatom for DiatomValue {
+    fn from_diatom<Buffer: IoWrite>(
+        _: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        Ok(value.clone())
+    }
+}
+
+macro_rules! impl_primitive {
+    ($t: ty, $variant: ident, $name: literal) => {
+        impl IntoDiatom for $t {
+            fn into_diatom<Buffer: IoWrite>(self, _: &mut State<Buffer>) -> DiatomValue {
+                DiatomValue::$variant(self.into())
+            }
+        }
+
+        impl FromDiatom for $t {
+            fn from_diatom<Buffer: IoWrite>(
+                state: &State<Buffer>,
+                value: &DiatomValue,
+            ) -> Result<Self, String> {
+                match value {
+                    DiatomValue::$variant(v) => (*v).try_into().map_err(|_| {
+                        format!("Value `{v}` is out of the range of `{}`", stringify!($t))
+                    }),
+                    _ => Err(type_error(state, $name, value)),
+                }
+            }
+        }
+    };
+}
+
+impl_primitive!(bool, Bool, "Bool");
+impl_primitive!(i64, Int, "Int");
+impl_primitive!(i32, Int, "Int");
+impl_primitive!(u32, Int, "Int");
+impl_primitive!(f64, Float, "Float");
+
+impl IntoDiatom for f32 {
+    fn into_diatom<Buffer: IoWrite>(self, _: &mut State<Buffer>) -> DiatomValue {
+        DiatomValue::Float(self as f64)
+    }
+}
+
+impl FromDiatom for f32 {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        f64::from_diatom(state, value).map(|f| f as f32)
+    }
+}
+
+impl IntoDiatom for () {
+    fn into_diatom<Buffer: IoWrite>(self, _: &mut State<Buffer>) -> DiatomValue {
+        DiatomValue::Unit
+    }
+}
+
+impl FromDiatom for () {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        match value {
+            DiatomValue::Unit => Ok(()),
+            _ => Err(type_error(state, "Unit", value)),
+        }
+    }
+}
+
+impl IntoDiatom for String {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        DiatomValue::Str(state.create_str(self))
+    }
+}
+
+impl IntoDiatom for &str {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        DiatomValue::Str(state.create_str(self.to_string()))
+    }
+}
+
+impl FromDiatom for String {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        match value {
+            DiatomValue::Str(sid) => Ok(state.get_string_by_id(*sid).unwrap().to_string()),
+            _ => Err(type_error(state, "String", value)),
+        }
+    }
+}
+
+impl<T: IntoDiatom> IntoDiatom for Vec<T> {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        let list = self.into_iter().map(|v| v.into_diatom(state)).collect();
+        DiatomValue::Ref(state.create_list(list).unwrap())
+    }
+}
+
+/// Accept both `List` and `Tuple`
+impl<T: FromDiatom> FromDiatom for Vec<T> {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        let values = match value {
+            DiatomValue::Ref(rid) => match state.gc.get_obj(*rid) {
+                Some(GcObject::List(l)) | Some(GcObject::Tuple(l)) => l,
+                _ => return Err(type_error(state, "List", value)),
+            },
+            _ => return Err(type_error(state, "List", value)),
+        };
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| T::from_diatom(state, v).map_err(|err| format!("At index {i}: {err}")))
+            .collect()
+    }
+}
+
+fn into_table<Buffer: IoWrite, T: IntoDiatom>(
+    state: &mut State<Buffer>,
+    map: impl Iterator<Item = (String, T)>,
+) -> DiatomValue {
+    let fields: Vec<_> = map.map(|(k, v)| (k, v.into_diatom(state))).collect();
+    DiatomValue::Ref(state.create_table(fields).unwrap())
+}
+
+fn from_table<Buffer: IoWrite, T: FromDiatom, C: FromIterator<(String, T)>>(
+    state: &State<Buffer>,
+    value: &DiatomValue,
+) -> Result<C, String> {
+    let table = match value {
+        DiatomValue::Ref(rid) => match state.gc.get_obj(*rid) {
+            Some(GcObject::Table(t)) => t,
+            _ => return Err(type_error(state, "Table", value)),
+        },
+        _ => return Err(type_error(state, "Table", value)),
+    };
+    table
+        .attributes
+        .iter()
+        .map(|(key, v)| {
+            let key = state.gc.look_up_table_key(*key).unwrap();
+            T::from_diatom(state, v)
+                .map(|v| (key.to_string(), v))
+                .map_err(|err| format!("In field `{key}`: {err}"))
+        })
+        .collect()
+}
+
+impl<T: IntoDiatom, S> IntoDiatom for HashMap<String, T, S> {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        into_table(state, self.into_iter())
+    }
+}
+
+impl<T: FromDiatom, S: BuildHasher + Default> FromDiatom for HashMap<String, T, S> {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        from_table(state, value)
+    }
+}
+
+impl<T: IntoDiatom> IntoDiatom for BTreeMap<String, T> {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        into_table(state, self.into_iter())
+    }
+}
+
+impl<T: FromDiatom> FromDiatom for BTreeMap<String, T> {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        from_table(state, value)
+    }
+}
+
+impl<T: IntoDiatom> IntoDiatom for Option<T> {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        match self {
+            Some(v) => {
+                let v = v.into_diatom(state);
+                DiatomValue::Ref(state.create_some(v).unwrap())
+            }
+            None => DiatomValue::Ref(state.create_none()),
+        }
+    }
+}
+
+/// `Option::None` and `()` are converted into `None`, `Option::Some(x)` is unwrapped and any
+/// other value is treated as `Some`.
+impl<T: FromDiatom> FromDiatom for Option<T> {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        if matches!(value, DiatomValue::Unit) || state.is_option_none(value) {
+            return Ok(None);
+        }
+        if let Some(inner) = state.option_value(value) {
+            return T::from_diatom(state, &inner).map(Some);
+        }
+        T::from_diatom(state, value).map(Some)
+    }
+}
+
+/// Implement `IntoDiatom` and `FromDiatom` for a struct with named fields
+///
+/// The struct is converted from and into a table with the same field names. All fields must
+/// implement both traits.
+///
+/// # Example
+/// ```
+/// use diatom_core::impl_diatom_table;
+///
+/// struct Point {
+///     x: i64,
+///     y: i64,
+///     label: Option<String>,
+/// }
+///
+/// impl_diatom_table!(Point { x, y, label });
+/// ```
+#[macro_export]
+macro_rules! impl_diatom_table {
+    ($t: ident { $($field: ident),* $(,)? }) => {
+        impl $crate::ffi::IntoDiatom for $t {
+            fn into_diatom<Buffer: $crate::IoWrite>(
+                self,
+                state: &mut $crate::ffi::State<Buffer>,
+            ) -> $crate::ffi::DiatomValue {
+                let fields = vec![$((
+                    stringify!($field),
+                    $crate::ffi::IntoDiatom::into_diatom(self.$field, state),
+                )),*];
+                $crate::ffi::DiatomValue::Ref(state.create_table(fields).unwrap())
+            }
+        }
+
+        impl $crate::ffi::FromDiatom for $t {
+            fn from_diatom<Buffer: $crate::IoWrite>(
+                state: &$crate::ffi::State<Buffer>,
+                value: &$crate::ffi::DiatomValue,
+            ) -> Result<Self, String> {
+                let table = match value {
+                    $crate::ffi::DiatomValue::Ref(rid) => match state.get_obj(*rid) {
+                        Some($crate::ffi::DiatomObject::Table(table)) => table,
+                        _ => return Err(format!("Expected type `Table` for `{}`", stringify!($t))),
+                    },
+                    _ => return Err(format!("Expected type `Table` for `{}`", stringify!($t))),
+                };
+                Ok(Self {$(
+                    $field: {
+                        let value = table.get_field(stringify!($field)).ok_or_else(|| {
+                            format!("Missing field `{}` for `{}`", stringify!($field), stringify!($t))
+                        })?;
+                        $crate::ffi::FromDiatom::from_diatom(state, &value).map_err(|err| {
+                            format!("In field `{}`: {err}", stringify!($field))
+                        })?
+                    },
+                )*})
+            }
+        }
+    };
+}
@@ -0,0 +1,220 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use super::*;
+
+/// Plain data owned by host, independent of any interpreter
+///
+/// Converted from a diatom value by `Interpreter::from_value` or `FromDiatom` and back by
+/// `Interpreter::to_value` or `IntoDiatom`. Tuples are converted into lists, `Option::Some(x)`
+/// into `x` and `Option::None` into `Nil`. Functions, user data and values that contain
+/// themselves can not be converted.
+///
+/// With feature `serde` enabled, it implements `Serialize` and `Deserialize` thus can be used
+/// with any data format supported by serde.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum DiatomData {
+    #[default]
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    List(Vec<DiatomData>),
+    Table(BTreeMap<String, DiatomData>),
+}
+
+impl IntoDiatom for DiatomData {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        match self {
+            DiatomData::Nil => DiatomValue::Unit,
+            DiatomData::Bool(b) => DiatomValue::Bool(b),
+            DiatomData::Int(i) => DiatomValue::Int(i),
+            DiatomData::Float(f) => DiatomValue::Float(f),
+            DiatomData::Str(s) => s.into_diatom(state),
+            DiatomData::List(l) => l.into_diatom(state),
+            DiatomData::Table(t) => t.into_diatom(state),
+        }
+    }
+}
+
+impl FromDiatom for DiatomData {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        from_value(state, value, &mut BTreeSet::new())
+    }
+}
+
+/// `visiting` holds references on the path from root to detect cycles
+fn from_value<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    value: &DiatomValue,
+    visiting: &mut BTreeSet<usize>,
+) -> Result<DiatomData, String> {
+    let rid = match value {
+        DiatomValue::Unit => return Ok(DiatomData::Nil),
+        DiatomValue::Bool(b) => return Ok(DiatomData::Bool(*b)),
+        DiatomValue::Int(i) => return Ok(DiatomData::Int(*i)),
+        DiatomValue::Float(f) => return Ok(DiatomData::Float(*f)),
+        DiatomValue::Str(sid) => {
+            return state
+                .get_string_by_id(*sid)
+                .map(|s| DiatomData::Str(s.to_string()))
+                .ok_or_else(|| "Invalid string id".to_string())
+        }
+        DiatomValue::Ref(rid) => *rid,
+    };
+    if state.is_option_none(value) {
+        return Ok(DiatomData::Nil);
+    }
+    if let Some(inner) = state.option_value(value) {
+        return from_value(state, &inner, visiting);
+    }
+    if !visiting.insert(rid) {
+        return Err("Can not convert a value that contains itself".to_string());
+    }
+    let data = match state.gc.get_obj(rid) {
+        Some(GcObject::List(l)) | Some(GcObject::Tuple(l)) => l
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                from_value(state, v, visiting).map_err(|err| format!("At index {i}: {err}"))
+            })
+            .collect::<Result<_, _>>()
+            .map(DiatomData::List),
+        Some(GcObject::Table(t)) => t
+            .attributes
+            .iter()
+            .map(|(key, v)| {
+                let key = state.gc.look_up_table_key(*key).unwrap();
+                from_value(state, v, visiting)
+                    .map(|v| (key.to_string(), v))
+                    .map_err(|err| format!("In field `{key}`: {err}"))
+            })
+            .collect::<Result<_, _>>()
+            .map(DiatomData::Table),
+        Some(
+            GcObject::Closure { .. } | GcObject::NativeFunction(_) | GcObject::BoundMethod { .. },
+        ) => Err("Can not convert a function".to_string()),
+        Some(GcObject::UserData) => Err("Can not convert user data".to_string()),
+        None => Err("Invalid reference id".to_string()),
+    };
+    visiting.remove(&rid);
+    data
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use std::{collections::BTreeMap, fmt};
+
+    use serde::{
+        de::{self, MapAccess, SeqAccess, Visitor},
+        ser::{SerializeMap, SerializeSeq},
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    use super::DiatomData;
+
+    impl Serialize for DiatomData {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                DiatomData::Nil => serializer.serialize_unit(),
+                DiatomData::Bool(b) => serializer.serialize_bool(*b),
+                DiatomData::Int(i) => serializer.serialize_i64(*i),
+                DiatomData::Float(f) => serializer.serialize_f64(*f),
+                DiatomData::Str(s) => serializer.serialize_str(s),
+                DiatomData::List(l) => {
+                    let mut seq = serializer.serialize_seq(Some(l.len()))?;
+                    for v in l {
+                        seq.serialize_element(v)?;
+                    }
+                    seq.end()
+                }
+                DiatomData::Table(t) => {
+                    let mut map = serializer.serialize_map(Some(t.len()))?;
+                    for (k, v) in t {
+                        map.serialize_entry(k, v)?;
+                    }
+                    map.end()
+                }
+            }
+        }
+    }
+
+    struct DataVisitor;
+
+    impl<'de> Visitor<'de> for DataVisitor {
+        type Value = DiatomData;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a diatom value")
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<DiatomData, E> {
+            Ok(DiatomData::Nil)
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<DiatomData, E> {
+            Ok(DiatomData::Nil)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<DiatomData, D::Error> {
+            DiatomData::deserialize(deserializer)
+        }
+
+        fn visit_bool<E: de::Error>(self, v: bool) -> Result<DiatomData, E> {
+            Ok(DiatomData::Bool(v))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<DiatomData, E> {
+            Ok(DiatomData::Int(v))
+        }
+
+        /// Integers out of range of `Int` are converted into `Float`
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<DiatomData, E> {
+            Ok(i64::try_from(v)
+                .map(DiatomData::Int)
+                .unwrap_or(DiatomData::Float(v as f64)))
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<DiatomData, E> {
+            Ok(DiatomData::Float(v))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<DiatomData, E> {
+            Ok(DiatomData::Str(v.to_string()))
+        }
+
+        fn visit_string<E: de::Error>(self, v: String) -> Result<DiatomData, E> {
+            Ok(DiatomData::Str(v))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<DiatomData, A::Error> {
+            let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(v) = seq.next_element()? {
+                list.push(v);
+            }
+            Ok(DiatomData::List(list))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<DiatomData, A::Error> {
+            let mut table = BTreeMap::new();
+            while let Some((k, v)) = map.next_entry::<String, DiatomData>()? {
+                table.insert(k, v);
+            }
+            Ok(DiatomData::Table(table))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for DiatomData {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(DataVisitor)
+        }
+    }
+}
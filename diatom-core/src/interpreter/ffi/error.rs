@@ -0,0 +1,26 @@
+use super::*;
+use alloc::string::String;
+
+/// Structured form of an error returned by the interpreter
+///
+/// Rendered diagnostics are meant for humans, while this is meant for hosts that handle errors
+/// programmatically. Get it from `Interpreter::last_error` after an `Err` is returned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptError {
+    /// Code attached by `State::raise`, or diagnostic code of a built-in error, e.g. `E3010`
+    pub code: Option<String>,
+    /// Error message without source location
+    pub message: String,
+    /// Data attached by `State::raise`
+    pub payload: Option<DiatomData>,
+}
+
+impl ScriptError {
+    pub(crate) fn new(code: Option<String>, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            payload: None,
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use alloc::sync::Arc;
+
+use super::*;
+
+/// A value held by host
+///
+/// Raw `DiatomValue::Str` and `DiatomValue::Ref` are only valid until the garbage collector runs.
+/// A rooted value keeps the string or object alive as long as it or any of its clones is held,
+/// and releases it once all of them are dropped.
+#[derive(Clone)]
+pub struct RootedValue {
+    root: Arc<DiatomValue>,
+}
+
+impl RootedValue {
+    /// Root a value, return None if it contains an invalid id
+    pub(crate) fn new<Buffer: IoWrite>(gc: &mut Gc<Buffer>, value: DiatomValue) -> Option<Self> {
+        if !obj_mut::check_value(gc, &value) {
+            return None;
+        }
+        Some(Self {
+            root: gc.root_host(value),
+        })
+    }
+
+    /// Get the raw value
+    ///
+    /// The raw value is valid as long as this handle is held.
+    pub fn value(&self) -> DiatomValue {
+        self.root.as_ref().clone()
+    }
+}
+
+/// A script function held by host
+///
+/// The function is kept alive as long as the handle or any of its clones is held, thus it can be
+/// called repeatedly with `Interpreter::call_function`, e.g. as a callback in an event loop.
+#[derive(Clone)]
+pub struct FunctionHandle {
+    root: RootedValue,
+}
+
+impl FunctionHandle {
+    /// Root a value if it is a closure, an external function or a bound method
+    pub(crate) fn new<Buffer: IoWrite>(gc: &mut Gc<Buffer>, value: &DiatomValue) -> Option<Self> {
+        match value {
+            DiatomValue::Ref(rid) => match gc.get_obj(*rid) {
+                Some(obj) if obj.is_callable() => Some(Self {
+                    root: RootedValue::new(gc, value.clone())?,
+                }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the function as a value
+    pub fn value(&self) -> DiatomValue {
+        self.root.value()
+    }
+}
+
+impl From<FunctionHandle> for RootedValue {
+    fn from(f: FunctionHandle) -> Self {
+        f.root
+    }
+}
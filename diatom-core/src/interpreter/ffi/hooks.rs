@@ -0,0 +1,22 @@
+use super::*;
+
+/// Observer of what scripts do, set by `Interpreter::set_hooks`
+///
+/// Useful for auditing untrusted scripts or recording an execution to replay it. All methods do
+/// nothing by default, so that hosts only implement events they subscribe to. Values are read
+/// with `state`, references among them are only valid during the call.
+pub trait EventHooks<Buffer: IoWrite>: Send {
+    /// A function is called with `args`, which do not include receivers of a bound method
+    ///
+    /// `name` is None for anonymous functions, external functions and constructors.
+    fn on_call(&mut self, _state: &State<Buffer>, _name: Option<&str>, _args: &[DiatomValue]) {}
+
+    /// Global variable `name` is assigned `value`, by code at top level or by a function
+    ///
+    /// An assignment may write a global more than once, as parts of a value can be computed in
+    /// the register of the global before the value itself.
+    fn on_global_write(&mut self, _state: &State<Buffer>, _name: &str, _value: &DiatomValue) {}
+
+    /// Execution fails with `error`, which is also returned by `Interpreter::last_error`
+    fn on_error(&mut self, _error: &ScriptError) {}
+}
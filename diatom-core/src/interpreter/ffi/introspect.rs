@@ -0,0 +1,95 @@
+use super::*;
+use alloc::{string::String, vec::Vec};
+
+/// Kind of a diatom value, see `Interpreter::globals`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiatomValueKind {
+    Unit,
+    Bool,
+    Int,
+    Float,
+    Str,
+    Table,
+    List,
+    Tuple,
+    /// Function defined in script
+    Function,
+    /// Function implemented by host
+    ExternFunction,
+    /// Method read as an attribute and bound to its receiver
+    BoundMethod,
+    UserData,
+}
+
+impl DiatomValueKind {
+    pub(crate) fn of<Buffer: IoWrite>(gc: &Gc<Buffer>, value: &DiatomValue) -> Self {
+        match value {
+            DiatomValue::Unit => Self::Unit,
+            DiatomValue::Bool(_) => Self::Bool,
+            DiatomValue::Int(_) => Self::Int,
+            DiatomValue::Float(_) => Self::Float,
+            DiatomValue::Str(_) => Self::Str,
+            DiatomValue::Ref(rid) => match gc.get_obj(*rid) {
+                Some(GcObject::Table(_)) | None => Self::Table,
+                Some(GcObject::List(_)) => Self::List,
+                Some(GcObject::Tuple(_)) => Self::Tuple,
+                Some(GcObject::Closure { .. }) => Self::Function,
+                Some(GcObject::NativeFunction(_)) => Self::ExternFunction,
+                Some(GcObject::BoundMethod { .. }) => Self::BoundMethod,
+                Some(GcObject::UserData) => Self::UserData,
+            },
+        }
+    }
+}
+
+/// Position in a source file, line and column start from 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Metadata of a function defined in script, see `Interpreter::function_info`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionInfo {
+    /// Name the function is assigned to where it is defined, None for anonymous functions
+    pub name: Option<String>,
+    /// Number of parameters
+    pub arity: usize,
+    /// Names of parameters in order
+    pub parameters: Vec<String>,
+    /// Where the function is defined
+    pub location: Option<SourceLocation>,
+}
+
+/// Counters of work done by an interpreter, see `Interpreter::stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Instructions executed
+    pub instructions: u64,
+    /// Strings and objects allocated by scripts
+    pub allocations: u64,
+    /// Garbage collections run
+    pub collections: u64,
+    /// Strings on the heap, including garbage not yet collected
+    pub strings: usize,
+    /// Objects on the heap, including garbage not yet collected
+    pub objects: usize,
+}
+
+/// Resources used by one execution, see `Interpreter::last_report`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExecutionReport {
+    /// Instructions executed
+    pub instructions: u64,
+    /// Strings and objects allocated
+    pub allocations: u64,
+    /// Most objects on the heap at once, including those alive before the execution and garbage
+    /// not yet collected
+    pub peak_gc_objects: usize,
+    /// Most strings on the heap at once, counted as `peak_gc_objects`
+    pub peak_strings: usize,
+    /// Time spent by the virtual machine, None if there is no clock, see `Interpreter::set_clock`
+    pub wall_time: Option<Duration>,
+}
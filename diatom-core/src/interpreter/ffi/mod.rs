@@ -1,14 +1,43 @@
+mod args;
+mod convert;
+mod data;
+mod error;
+mod handle;
+mod hooks;
+mod introspect;
+mod module;
 mod obj;
 mod obj_mut;
+mod user_type;
+
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+pub use args::{ArgType, Args};
+pub use convert::{FromDiatom, IntoDiatom};
+pub use data::DiatomData;
+pub use error::ScriptError;
+pub use handle::{FunctionHandle, RootedValue};
+pub use hooks::EventHooks;
+pub use introspect::{DiatomValueKind, ExecutionReport, FunctionInfo, SourceLocation, Stats};
+pub use module::ModuleBuilder;
 
 pub use obj::{DiatomList, DiatomObject, DiatomTable, DiatomTuple};
 pub use obj_mut::{DiatomListMut, DiatomObjectMut, DiatomTableMut, DiatomTupleMut};
+pub use user_type::UserTypeBuilder;
 
-use std::any::Any;
+use crate::compat::io;
+use core::{any::Any, time::Duration};
 
 use crate::{
     ffi::DiatomValue,
-    gc::{Gc, GcObject},
+    gc::{Gc, GcObject, PrimitiveMeta, Table, VARIANT_KEY},
+    host::Deadline,
+    interpreter::SandboxPolicy,
+    vm::op::find_attr,
     IoWrite,
 };
 
@@ -32,12 +61,228 @@ impl<'a, Buffer: IoWrite> State<'a, Buffer> {
     ///
     /// Return reference id to the string which can be put into `DiatomValue::Str()`.
     pub fn create_str(&mut self, s: String) -> usize {
-        self.gc.alloc_str(s)
+        let id = self.gc.alloc_str(s);
+        self.gc.root_temporary_str(id);
+        id
     }
 
     pub fn create_user_data(&mut self, data: Box<dyn Any + Send>) -> usize {
-        let obj = GcObject::UserData(data);
-        self.gc.alloc_obj(obj)
+        let id = self.gc.alloc_user_data(data);
+        self.gc.root_temporary_obj(id);
+        id
+    }
+
+    /// Create an external function
+    ///
+    /// Return reference id to the function which can be put into `DiatomValue::Ref()`.
+    pub fn create_function<F>(&mut self, f: F) -> usize
+    where
+        F: Fn(&mut State<Buffer>, &[DiatomValue], &mut Buffer) -> Result<DiatomValue, String>
+            + 'static
+            + Send
+            + Sync,
+    {
+        let id = self.gc.alloc_obj(GcObject::NativeFunction(Arc::new(f)));
+        self.gc.root_temporary_obj(id);
+        id
+    }
+
+    /// Create a new tuple
+    ///
+    /// Return reference id to the tuple which can be put into `DiatomValue::Ref()`. Return None
+    /// if any of the values contains an invalid id.
+    pub fn create_tuple(&mut self, values: Vec<DiatomValue>) -> Option<usize> {
+        if !values.iter().all(|v| obj_mut::check_value(self.gc, v)) {
+            return None;
+        }
+        let id = self.gc.alloc_obj(GcObject::Tuple(values));
+        self.gc.root_temporary_obj(id);
+        Some(id)
+    }
+
+    /// Create a new list
+    ///
+    /// Return reference id to the list which can be put into `DiatomValue::Ref()`. Return None
+    /// if any of the values contains an invalid id.
+    pub fn create_list(&mut self, values: Vec<DiatomValue>) -> Option<usize> {
+        if !values.iter().all(|v| obj_mut::check_value(self.gc, v)) {
+            return None;
+        }
+        let id = self.gc.alloc_obj(GcObject::List(values));
+        self.gc.root_temporary_obj(id);
+        Some(id)
+    }
+
+    /// Create a new table with given fields
+    ///
+    /// Return reference id to the table which can be put into `DiatomValue::Ref()`. Return None
+    /// if any of the values contains an invalid id.
+    pub fn create_table<K: AsRef<str>>(
+        &mut self,
+        fields: impl IntoIterator<Item = (K, DiatomValue)>,
+    ) -> Option<usize> {
+        self.create_table_with_meta(fields, None)
+    }
+
+    /// Create a table with given fields and meta table
+    fn create_table_with_meta<K: AsRef<str>>(
+        &mut self,
+        fields: impl IntoIterator<Item = (K, DiatomValue)>,
+        meta: Option<PrimitiveMeta>,
+    ) -> Option<usize> {
+        let mut table = Table {
+            attributes: Default::default(),
+            indices: Default::default(),
+            meta_table: meta.map(|meta| self.gc.get_meta(meta)),
+            frozen: false,
+        };
+        for (key, value) in fields {
+            if !obj_mut::check_value(self.gc, &value) {
+                return None;
+            }
+            let key = self.gc.get_or_insert_table_key(key.as_ref());
+            table.attributes.insert(key, value);
+        }
+        let id = self.gc.alloc_obj(GcObject::Table(table));
+        self.gc.root_temporary_obj(id);
+        Some(id)
+    }
+
+    /// Check if a value is an instance of `Option` without field `value`
+    pub fn is_option_none(&self, value: &DiatomValue) -> bool {
+        let rid = match value {
+            DiatomValue::Ref(rid) => *rid,
+            _ => return false,
+        };
+        let value_key = self.gc.get_table_key("value");
+        match self.gc.get_obj(rid) {
+            Some(GcObject::Table(Table {
+                attributes,
+                meta_table: Some(meta),
+                ..
+            })) => {
+                *meta == self.gc.get_meta(PrimitiveMeta::Option)
+                    && !value_key.is_some_and(|key| attributes.contains_key(&key))
+            }
+            _ => false,
+        }
+    }
+
+    /// Get field `value` of an `Option::Some`
+    pub(crate) fn option_value(&self, value: &DiatomValue) -> Option<DiatomValue> {
+        let rid = match value {
+            DiatomValue::Ref(rid) => *rid,
+            _ => return None,
+        };
+        match self.gc.get_obj(rid) {
+            Some(GcObject::Table(Table {
+                attributes,
+                meta_table: Some(meta),
+                ..
+            })) if *meta == self.gc.get_meta(PrimitiveMeta::Option) => {
+                let key = self.gc.get_table_key("value")?;
+                attributes.get(&key).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// Tag of a variant of an enum, see `lower_enum`
+    fn variant_tag(&mut self, variant: &str) -> DiatomValue {
+        DiatomValue::Str(self.create_str(variant.to_string()))
+    }
+
+    /// Create `Option::Some(value)`
+    ///
+    /// Return reference id to the option. Return None if value contains an invalid id.
+    pub fn create_some(&mut self, value: DiatomValue) -> Option<usize> {
+        let variant = self.variant_tag("Option::Some");
+        self.create_table_with_meta(
+            [(VARIANT_KEY, variant), ("value", value)],
+            Some(PrimitiveMeta::Option),
+        )
+    }
+
+    /// Get `Option::None`
+    ///
+    /// Return reference id to `Option::None` defined by standard library. If it is not defined, a
+    /// new empty option is created.
+    pub fn create_none(&mut self) -> usize {
+        let option = self.gc.get_meta(PrimitiveMeta::Option);
+        if let Some(DiatomObject::Table(table)) = self.get_obj(option) {
+            if let Some(DiatomValue::Ref(rid)) = table.get_field("None") {
+                return rid;
+            }
+        }
+        let variant = self.variant_tag("Option::None");
+        self.create_table_with_meta([(VARIANT_KEY, variant)], Some(PrimitiveMeta::Option))
+            .unwrap()
+    }
+
+    /// Create `Result::Ok(value)`
+    ///
+    /// Return reference id to the result. Return None if value contains an invalid id.
+    pub fn create_ok(&mut self, value: DiatomValue) -> Option<usize> {
+        let variant = self.variant_tag("Result::Ok");
+        self.create_table_with_meta(
+            [(VARIANT_KEY, variant), ("value", value)],
+            Some(PrimitiveMeta::Result),
+        )
+    }
+
+    /// Create `Result::Err(error)`
+    ///
+    /// Return reference id to the result. Return None if error contains an invalid id.
+    pub fn create_err(&mut self, error: DiatomValue) -> Option<usize> {
+        let variant = self.variant_tag("Result::Err");
+        self.create_table_with_meta(
+            [(VARIANT_KEY, variant), ("error", error)],
+            Some(PrimitiveMeta::Result),
+        )
+    }
+
+    /// Check if a capability has been granted by host
+    ///
+    /// Functions with side effects on the host (e.g. spawning processes) should refuse to run
+    /// unless the corresponding capability is granted.
+    pub fn has_capability(&self, capability: impl AsRef<str>) -> bool {
+        self.gc.has_capability(capability)
+    }
+
+    /// Time of the clock supplied by host, None if there is no clock
+    ///
+    /// The clock is monotonic and starts at an arbitrary point, it is meant for measuring time.
+    pub fn now(&self) -> Option<Duration> {
+        self.gc.clock().map(|clock| clock.now())
+    }
+
+    /// Get host state of type `T` set by `Interpreter::set_context`
+    pub fn context<T: Any + Send>(&self) -> Option<&T> {
+        self.gc.get_context()
+    }
+
+    /// Get mutable host state of type `T` set by `Interpreter::set_context`
+    pub fn context_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.gc.get_context_mut()
+    }
+
+    /// Register methods and getters of a rust type used as user data
+    ///
+    /// Same as `Interpreter::register_type`, for extensions which can not reach the interpreter.
+    pub fn register_type<T: Any + Send>(&mut self) -> UserTypeBuilder<'_, T, Buffer> {
+        UserTypeBuilder::new(self.gc)
+    }
+
+    /// If any method or getter of rust type `T` is registered
+    pub fn is_type_registered<T: Any + Send>(&self) -> bool {
+        self.gc
+            .get_user_type(core::any::TypeId::of::<T>())
+            .is_some()
+    }
+
+    /// Get what scripts are allowed to do
+    pub fn policy(&self) -> &SandboxPolicy {
+        self.gc.policy()
     }
 
     /// Get a mutable reference by reference id
@@ -53,6 +298,10 @@ impl<'a, Buffer: IoWrite> State<'a, Buffer> {
         Some(match obj {
             GcObject::Closure { func_id, .. } => DiatomObjectMut::Closure(*func_id),
             GcObject::NativeFunction(_) => DiatomObjectMut::ForeignFunction,
+            GcObject::BoundMethod { receiver, method } => DiatomObjectMut::BoundMethod {
+                receiver: receiver.clone(),
+                method: *method,
+            },
             GcObject::List(_) => DiatomObjectMut::List(DiatomListMut {
                 gc: self.gc,
                 ref_id,
@@ -65,7 +314,7 @@ impl<'a, Buffer: IoWrite> State<'a, Buffer> {
                 gc: self.gc,
                 ref_id,
             }),
-            GcObject::UserData(_) => DiatomObjectMut::UserData(UserDataMut {
+            GcObject::UserData => DiatomObjectMut::UserData(UserDataMut {
                 gc: self.gc,
                 ref_id,
             }),
@@ -83,6 +332,10 @@ impl<'a, Buffer: IoWrite> State<'a, Buffer> {
         Some(match obj {
             GcObject::Closure { func_id, .. } => DiatomObject::Closure(*func_id),
             GcObject::NativeFunction(_) => DiatomObject::ForeignFunction,
+            GcObject::BoundMethod { receiver, method } => DiatomObject::BoundMethod {
+                receiver: receiver.clone(),
+                method: *method,
+            },
             GcObject::List(list) => DiatomObject::List(DiatomList { list, ref_id }),
             GcObject::Table(table) => DiatomObject::Table(DiatomTable {
                 gc: self.gc,
@@ -90,14 +343,212 @@ impl<'a, Buffer: IoWrite> State<'a, Buffer> {
                 ref_id,
             }),
             GcObject::Tuple(tuple) => DiatomObject::Tuple(DiatomTuple { tuple, ref_id }),
-            GcObject::UserData(data) => DiatomObject::UserData(data),
+            GcObject::UserData => DiatomObject::UserData(self.gc.get_user_data(ref_id).unwrap()),
         })
     }
 
+    /// Look up attribute `name` of a value along its meta tables, as `value::name` does
+    ///
+    /// Methods of user data are found as well, while getters of properties are not called.
+    /// Return None if the value has no such attribute.
+    pub fn get_attr(&self, value: &DiatomValue, name: &str) -> Option<DiatomValue> {
+        let key = self.gc.get_table_key(name)?;
+        let meta = match value {
+            DiatomValue::Int(_) => PrimitiveMeta::Int,
+            DiatomValue::Float(_) => PrimitiveMeta::Float,
+            DiatomValue::Str(_) => PrimitiveMeta::Str,
+            DiatomValue::Ref(rid) => match self.gc.get_obj(*rid)? {
+                GcObject::Table(table) => return find_attr(self.gc, table, key).cloned(),
+                GcObject::List(_) => PrimitiveMeta::List,
+                GcObject::UserData => {
+                    let data = self.gc.get_user_data(*rid)?;
+                    let user_type = self.gc.get_user_type((**data).type_id())?;
+                    return match self.gc.get_obj(user_type.methods)? {
+                        GcObject::Table(methods) => methods.attributes.get(&key).cloned(),
+                        _ => None,
+                    };
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        match self.gc.get_obj(self.gc.get_meta(meta))? {
+            GcObject::Table(table) => table.attributes.get(&key).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Read a line from input of the interpreter
+    ///
+    /// Line ending is removed. Return None at the end of input.
+    pub fn read_line(&mut self) -> crate::compat::io::Result<Option<String>> {
+        self.gc.read_line()
+    }
+
+    /// Report an error with a machine readable code and payload
+    ///
+    /// Return the message, which should be returned as `Err` by the external function. Once the
+    /// error aborts execution, host can get code and payload by `Interpreter::last_error`.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom_core::ffi::{DiatomData, DiatomValue, State};
+    /// # use diatom_core::IoWrite;
+    ///
+    /// fn find_user<Buffer: IoWrite>(
+    ///     state: &mut State<Buffer>,
+    ///     parameters: &[DiatomValue],
+    ///     _: &mut Buffer,
+    /// ) -> Result<DiatomValue, String> {
+    ///     match parameters.first() {
+    ///         Some(DiatomValue::Int(id)) if *id > 0 => Ok(DiatomValue::Int(*id)),
+    ///         Some(DiatomValue::Int(id)) => {
+    ///             Err(state.raise("not_found", "No such user", DiatomData::Int(*id)))
+    ///         }
+    ///         _ => Err(state.raise("bad_request", "Expected an user id", DiatomData::Nil)),
+    ///     }
+    /// }
+    /// ```
+    pub fn raise(
+        &mut self,
+        code: impl Into<String>,
+        message: impl Into<String>,
+        payload: DiatomData,
+    ) -> String {
+        let message = message.into();
+        self.gc.set_raised(ScriptError {
+            code: Some(code.into()),
+            message: message.clone(),
+            payload: Some(payload),
+        });
+        message
+    }
+
+    /// Report a warning at the running call, e.g. when a value is converted lossily
+    ///
+    /// The warning has code `W3000` and can be suppressed by `name` in kebab case. It is rendered
+    /// with warnings of compilation once execution ends, see `Interpreter::emit_diagnostics`.
+    pub fn warn(&mut self, name: &'static str, message: impl Into<String>) {
+        self.gc.warn(name, message.into());
+    }
+
+    /// Deadline of the running execution, None if the sandbox policy has no timeout
+    ///
+    /// External functions that may block for long should stop waiting once it passes and return
+    /// the error made by `interrupt`.
+    pub fn deadline(&self) -> Option<&Deadline> {
+        self.gc.deadline()
+    }
+
+    /// Report that the function stopped waiting as the deadline passed
+    ///
+    /// Return `message` to be returned as error, which is reported as an interruption (E3027)
+    /// that aborts execution like the time limit does, instead of a panic of the function.
+    pub fn interrupt(&mut self, message: impl Into<String>) -> String {
+        self.gc.set_interrupted();
+        message.into()
+    }
+
+    /// Make a list, tuple or table and all collections inside it immutable
+    ///
+    /// Scripts modifying them fail with an error pointing at the call of the running external
+    /// function, i.e. where `freeze` is called by script. Values which are not collections are
+    /// left as they are.
+    pub fn freeze(&mut self, value: &DiatomValue) {
+        let site = self.gc.call_site().cloned();
+        self.gc.freeze(value, site);
+    }
+
+    /// Whether `value` is a collection that can not be modified
+    pub fn is_frozen(&self, value: &DiatomValue) -> bool {
+        matches!(value, DiatomValue::Ref(rid) if self.gc.is_frozen(*rid))
+    }
+
+    /// Check that object `ref_id` is not frozen before an external function modifies it
+    ///
+    /// Return the error message to be returned if it is frozen, which is reported like
+    /// modifying it in script (E3028) with where it is frozen.
+    pub fn check_mutable(&mut self, ref_id: usize) -> Result<(), String> {
+        if self.gc.is_frozen(ref_id) {
+            self.gc.set_modified_frozen(ref_id);
+            Err("Can not modify a frozen value".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check if all ids in value are valid
+    pub(crate) fn check_value(&self, value: &DiatomValue) -> bool {
+        obj_mut::check_value(self.gc, value)
+    }
+
+    /// Keep a value alive while the returned handle is held
+    ///
+    /// Return None if value contains an invalid id.
+    pub fn root(&mut self, value: DiatomValue) -> Option<RootedValue> {
+        RootedValue::new(self.gc, value)
+    }
+
+    /// Keep a function alive so that it can be called by host later
+    ///
+    /// Return None if value is neither a closure nor an external function.
+    pub fn function_handle(&mut self, value: &DiatomValue) -> Option<FunctionHandle> {
+        FunctionHandle::new(self.gc, value)
+    }
+
+    /// Get number of parameters of a closure
+    ///
+    /// Return None if reference id is invalid or not a closure. Foreign functions do not have a
+    /// fixed arity and thus also return None. The receiver of a bound method is not counted.
+    pub fn get_arity(&self, ref_id: usize) -> Option<usize> {
+        match self.gc.get_obj(ref_id) {
+            Some(GcObject::Closure { parameters, .. }) => Some(*parameters),
+            Some(GcObject::BoundMethod { method, .. }) => self.get_arity(*method)?.checked_sub(1),
+            _ => None,
+        }
+    }
+
+    /// Get name, parameters and location of a function defined in script
+    ///
+    /// Return None if value is not a closure or a method bound to one. The receiver of a bound
+    /// method is not counted as a parameter.
+    pub fn function_info(&self, value: &DiatomValue) -> Option<FunctionInfo> {
+        let DiatomValue::Ref(rid) = value else {
+            return None;
+        };
+        match self.gc.get_obj(*rid)? {
+            GcObject::Closure { func_id, .. } => self.gc.function_info(*func_id).cloned(),
+            GcObject::BoundMethod { method, .. } => {
+                let mut info = self.function_info(&DiatomValue::Ref(*method))?;
+                info.arity = info.arity.checked_sub(1)?;
+                info.parameters.remove(0);
+                Some(info)
+            }
+            _ => None,
+        }
+    }
+
     pub fn print(&self, value: &DiatomValue) -> String {
         self.gc.print(value)
     }
 
+    /// Write a value as `print` does, numbers are formatted without `core::fmt`
+    pub fn print_to<W: IoWrite + ?Sized>(
+        &self,
+        value: &DiatomValue,
+        out: &mut W,
+    ) -> io::Result<()> {
+        self.gc.print_to(value, out)
+    }
+
+    /// Pretty print a value
+    ///
+    /// Nested containers are indented, strings are quoted and escaped, cycles are shown as
+    /// `<cycle #n>` and containers deeper than `max_depth` are elided.
+    pub fn inspect(&self, value: &DiatomValue, max_depth: usize) -> String {
+        self.gc.inspect(value, max_depth)
+    }
+
     /// Immediately collect garbage
     pub fn collect_garbage(&mut self) {
         self.gc.collect()
@@ -0,0 +1,69 @@
+use super::*;
+use alloc::{string::String, vec, vec::Vec};
+
+/// Builder of a namespaced table of external functions and values
+///
+/// Created by `Interpreter::add_module`. Each entry becomes a field of the module table.
+/// Entries with the same name override each other. Functions are called with `::`, e.g.
+/// `fs::read(path)`, since `.` would pass the module itself as the first parameter.
+pub struct ModuleBuilder<'a, Buffer: IoWrite> {
+    state: State<'a, Buffer>,
+    fields: Vec<(String, DiatomValue)>,
+}
+
+impl<'a, Buffer: IoWrite> ModuleBuilder<'a, Buffer> {
+    pub(crate) fn new(gc: &'a mut Gc<Buffer>) -> Self {
+        Self {
+            state: State { gc },
+            fields: vec![],
+        }
+    }
+
+    /// Add an external function
+    pub fn func<F>(&mut self, name: impl Into<String>, f: F) -> &mut Self
+    where
+        F: Fn(&mut State<Buffer>, &[DiatomValue], &mut Buffer) -> Result<DiatomValue, String>
+            + 'static
+            + Send
+            + Sync,
+    {
+        let id = self.state.create_function(f);
+        self.fields.push((name.into(), DiatomValue::Ref(id)));
+        self
+    }
+
+    /// Add a value converted from rust
+    ///
+    /// # Panics
+    /// Panic if value is a `DiatomValue` that contains an invalid id.
+    pub fn value(&mut self, name: impl Into<String>, value: impl IntoDiatom) -> &mut Self {
+        let value = value.into_diatom(&mut self.state);
+        assert!(
+            obj_mut::check_value(self.state.gc, &value),
+            "Invalid value for module field"
+        );
+        self.fields.push((name.into(), value));
+        self
+    }
+
+    /// Add a nested module, e.g. `fs.v2.read`
+    pub fn module(
+        &mut self,
+        name: impl Into<String>,
+        build: impl FnOnce(&mut ModuleBuilder<Buffer>),
+    ) -> &mut Self {
+        let mut builder = ModuleBuilder::new(self.state.gc);
+        build(&mut builder);
+        let id = builder.finish();
+        self.fields.push((name.into(), DiatomValue::Ref(id)));
+        self
+    }
+
+    /// Create module table
+    ///
+    /// All values are checked when added and thus are always valid.
+    pub(crate) fn finish(self) -> usize {
+        let Self { mut state, fields } = self;
+        state.create_table(fields).unwrap()
+    }
+}
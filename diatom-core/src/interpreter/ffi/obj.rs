@@ -1,4 +1,5 @@
-use std::{any::Any, collections::BTreeMap};
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+use core::any::Any;
 
 use super::*;
 
@@ -86,6 +87,11 @@ pub enum DiatomObject<'a, Buffer: IoWrite> {
     Closure(usize),
     /// Foreign rust closure
     ForeignFunction,
+    /// Method read as an attribute, e.g. `obj.method`, and bound to its receiver
+    BoundMethod {
+        receiver: DiatomValue,
+        method: usize,
+    },
     /// Table
     Table(DiatomTable<'a, Buffer>),
     /// Tuple
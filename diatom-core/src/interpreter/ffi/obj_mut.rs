@@ -1,8 +1,9 @@
-use std::any::Any;
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::any::Any;
 
 use super::*;
 
-fn check_value<Buffer: IoWrite>(gc: &Gc<Buffer>, value: &DiatomValue) -> bool {
+pub(super) fn check_value<Buffer: IoWrite>(gc: &Gc<Buffer>, value: &DiatomValue) -> bool {
     match value {
         DiatomValue::Ref(rid) => gc.get_obj(*rid).is_some(),
         DiatomValue::Str(sid) => gc.get_str(*sid).is_some(),
@@ -194,11 +195,7 @@ impl<'a, Buffer: IoWrite> UserDataMut<'a, Buffer> {
     }
 
     pub fn get(&mut self) -> &mut Box<dyn Any + Send> {
-        let table = self.gc.get_obj_mut(self.ref_id).unwrap();
-        match table {
-            GcObject::UserData(data) => data,
-            _ => unreachable!(),
-        }
+        self.gc.get_user_data_mut(self.ref_id).unwrap()
     }
 }
 
@@ -208,6 +205,11 @@ pub enum DiatomObjectMut<'a, Buffer: IoWrite> {
     Closure(usize),
     /// Foreign rust closure
     ForeignFunction,
+    /// Method read as an attribute, e.g. `obj.method`, and bound to its receiver
+    BoundMethod {
+        receiver: DiatomValue,
+        method: usize,
+    },
     /// Table
     Table(DiatomTableMut<'a, Buffer>),
     /// Tuple
@@ -0,0 +1,145 @@
+use alloc::{boxed::Box, format, string::String, sync::Arc};
+use core::{
+    any::{type_name, Any, TypeId},
+    marker::PhantomData,
+};
+
+use super::*;
+
+/// Short name of a rust type, e.g. `Counter` instead of `my_crate::Counter`
+fn short_type_name<T>() -> &'static str {
+    let name = type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Register methods and getters of a rust type used as user data
+///
+/// Created by `Interpreter::register_type`. Once registered, user data of this type created by
+/// `State::create_user_data` supports method call syntax, e.g. `handle.send(msg)`, and getters
+/// are evaluated when the attribute is read, e.g. `handle.name`.
+pub struct UserTypeBuilder<'a, T: Any + Send, Buffer: IoWrite> {
+    gc: &'a mut Gc<Buffer>,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: Any + Send, Buffer: IoWrite> UserTypeBuilder<'a, T, Buffer> {
+    pub(crate) fn new(gc: &'a mut Gc<Buffer>) -> Self {
+        Self {
+            gc,
+            marker: PhantomData,
+        }
+    }
+
+    /// Add a method
+    ///
+    /// The receiver is detached from the heap while the method is running, thus accessing it
+    /// through `State` will find a different user data.
+    pub fn method<F>(&mut self, name: &str, f: F) -> &mut Self
+    where
+        F: Fn(&mut T, &mut State<Buffer>, &[DiatomValue]) -> Result<DiatomValue, String>
+            + 'static
+            + Send
+            + Sync,
+    {
+        let method_name = format!("{}.{name}", short_type_name::<T>());
+        let f = move |state: &mut State<Buffer>, parameters: &[DiatomValue], _: &mut Buffer| {
+            call_with_receiver(&method_name, state, parameters, &f)
+        };
+        self.gc
+            .register_method(TypeId::of::<T>(), short_type_name::<T>(), name, Arc::new(f));
+        self
+    }
+
+    /// Add a method that returns its receiver, so that calls can be chained, e.g.
+    /// `builder.push(a).push(b)`
+    pub fn chained_method<F>(&mut self, name: &str, f: F) -> &mut Self
+    where
+        F: Fn(&mut T, &mut State<Buffer>, &[DiatomValue]) -> Result<(), String>
+            + 'static
+            + Send
+            + Sync,
+    {
+        let method_name = format!("{}.{name}", short_type_name::<T>());
+        let f = move |state: &mut State<Buffer>, parameters: &[DiatomValue], _: &mut Buffer| {
+            call_with_receiver(&method_name, state, parameters, &f)?;
+            Ok(parameters[0].clone())
+        };
+        self.gc
+            .register_method(TypeId::of::<T>(), short_type_name::<T>(), name, Arc::new(f));
+        self
+    }
+
+    /// Copy user data of this type when the heap is forked, e.g. by `Interpreter::fork_env`
+    ///
+    /// User data of types not registered as cloneable is replaced by a value without methods in
+    /// the fork.
+    pub fn cloneable(&mut self) -> &mut Self
+    where
+        T: Clone,
+    {
+        self.gc
+            .register_clone(TypeId::of::<T>(), short_type_name::<T>(), |data| {
+                Box::new(data.downcast_ref::<T>().unwrap().clone())
+            });
+        self
+    }
+
+    /// Add a getter that is evaluated when attribute `name` is read
+    pub fn getter<R, F>(&mut self, name: &str, f: F) -> &mut Self
+    where
+        R: IntoDiatom,
+        F: Fn(&T) -> R + 'static + Send + Sync,
+    {
+        let getter_name = format!("{}.{name}", short_type_name::<T>());
+        let f = move |state: &mut State<Buffer>, parameters: &[DiatomValue], _: &mut Buffer| {
+            let value = match parameters.first() {
+                Some(DiatomValue::Ref(rid)) => state
+                    .gc
+                    .get_user_data(*rid)
+                    .and_then(|data| data.downcast_ref::<T>())
+                    .map(&f),
+                _ => None,
+            };
+            match value {
+                Some(value) => Ok(value.into_diatom(state)),
+                None => Err(receiver_error::<T>(&getter_name)),
+            }
+        };
+        self.gc
+            .register_getter(TypeId::of::<T>(), short_type_name::<T>(), name, Arc::new(f));
+        self
+    }
+}
+
+/// Call `f` with the user data passed as the first parameter and the rest parameters
+fn call_with_receiver<T: Any + Send, Buffer: IoWrite, R>(
+    method_name: &str,
+    state: &mut State<Buffer>,
+    parameters: &[DiatomValue],
+    f: impl Fn(&mut T, &mut State<Buffer>, &[DiatomValue]) -> Result<R, String>,
+) -> Result<R, String> {
+    let (this, parameters) = parameters
+        .split_first()
+        .ok_or_else(|| format!("`{method_name}` expected a receiver"))?;
+    let rid = match this {
+        DiatomValue::Ref(rid) => *rid,
+        _ => return Err(receiver_error::<T>(method_name)),
+    };
+    let mut data = match state.gc.get_user_data_mut(rid) {
+        Some(data) if data.is::<T>() => core::mem::replace(data, Box::new(())),
+        _ => return Err(receiver_error::<T>(method_name)),
+    };
+    let ret = f(data.downcast_mut::<T>().unwrap(), state, parameters);
+    if let Some(slot) = state.gc.get_user_data_mut(rid) {
+        *slot = data;
+    }
+    ret
+}
+
+fn receiver_error<T>(name: &str) -> String {
+    format!(
+        "`{name}` expected a `{}` as receiver",
+        short_type_name::<T>()
+    )
+}
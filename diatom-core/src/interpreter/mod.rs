@@ -1,35 +1,70 @@
-use crate::frontend::parser::ast::ImportItem;
-use crate::gc::{Gc, GcObject, PrimitiveMeta, Reg, Table};
-use std::ffi::OsStr;
-use std::fmt::Write;
-use std::io;
-use std::marker::PhantomData;
-use std::path::PathBuf;
-use std::sync::Arc;
-
-use ahash::{AHashMap, AHashSet};
-use codespan_reporting::diagnostic::Label;
+use crate::frontend::parser::ast::{
+    is_super, lower_decorators, lower_enum, lower_interface, lower_super, ImportItem,
+};
+use crate::frontend::parser::visit::{walk_expr, walk_stmt, Visitor};
+use crate::frontend::Symbol;
+use crate::gc::{Gc, GcObject, PrimitiveMeta, PrintOptions, Reg, Table, DEFAULT_INSPECT_DEPTH};
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    fmt::{self, Write},
+    marker::PhantomData,
+};
+#[cfg(feature = "std")]
+use std::env;
+
+use crate::compat::{
+    self,
+    ffi::OsStr,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+    AHashMap, AHashSet,
+};
+use codespan_reporting::diagnostic::{Label, Severity};
+use unicode_ident::{is_xid_continue, is_xid_start};
 
 mod error;
+mod policy;
+mod program;
 mod register_table;
 mod scanner;
 pub mod std_core;
 
 pub mod ffi;
-use crate::file_manager::FileManager;
+use crate::bundle::{Bundle, BundleFiles};
+use crate::file_manager::{ColorChoice, FileManager, RenderOptions, SourceMap, WarningOptions};
+use crate::host::{Clock, Deadline, FileSystem, ModuleLoader};
+use crate::lint::{lint, LintConfig};
+use crate::manifest::Manifest;
+#[cfg(feature = "std")]
+use crate::manifest::SEARCH_PATH_ENV;
 use crate::vm::op::{
-    OpGe, OpGetTable, OpGetTuple, OpImport, OpIndex, OpIs, OpLe, OpLt, OpMakeList, OpMakeTable,
-    OpMakeTuple, OpNe, OpSaveModule, OpSetIndex, OpSetMeta, OpSetTable, OpSetTuple,
+    OpAssertBool, OpCheckDefined, OpDefer, OpDefine, OpExtendList, OpExtendTable, OpGe, OpGetSuper,
+    OpGetTable, OpGetTuple, OpImport, OpIn, OpIndex, OpIs, OpIsUnit, OpLe, OpLt, OpMakeList,
+    OpMakeTable, OpMakeTuple, OpNe, OpSaveModule, OpSetIndex, OpSetMeta, OpSetTable, OpSetTuple,
 };
 use crate::{
-    ffi::{DiatomValue, State},
+    ffi::{
+        Args, DiatomData, DiatomValue, DiatomValueKind, EventHooks, ExecutionReport, FromDiatom,
+        FunctionHandle, FunctionInfo, IntoDiatom, ModuleBuilder, RootedValue, ScriptError,
+        SourceLocation, State, Stats, UserTypeBuilder,
+    },
     file_manager::{Diagnostic, Loc},
     frontend::{
         parser::ast::{Const, Expr, OpInfix, OpPrefix, Stmt},
-        Parser,
+        resolver::{definitions, ErrorCode as ResolveError},
+        KeywordAliases, Parser, Resolver,
     },
     vm::{
-        error::VmError,
+        error::{panic_message, VmError},
         op::{
             OpAdd, OpAllocReg, OpBranchFalse, OpBranchTrue, OpCall, OpDiv, OpDummy, OpEq, OpGt,
             OpIDiv, OpJump, OpMakeClosure, OpMove, OpMul, OpNeg, OpNot, OpPow, OpRem, OpRet, OpSub,
@@ -37,16 +72,23 @@ use crate::{
         },
         Instruction, Vm, VmInst,
     },
-    IoWrite,
+    IoRead, IoWrite,
 };
 
 use error::ErrorCode;
+pub use policy::{FsPolicy, SandboxPolicy};
+pub use program::Program;
+use program::Snapshot;
 pub use register_table::Capture;
 use register_table::{ConstantValue, Loop, RegisterTable};
 
+pub(crate) use self::scanner::ConstFolder;
 use self::scanner::{CaptureScanner, ConstScanner};
 use self::std_core::{Extension, ExtensionKind, StdCore};
 
+/// Size of code read by `Interpreter::exec_reader` before it is executed
+const STREAM_CHUNK_SIZE: usize = 1 << 16;
+
 #[derive(Clone)]
 pub struct FutureJump {
     condition_reg: Option<(usize, bool)>,
@@ -108,30 +150,59 @@ impl FutureJump {
     }
 }
 
+#[derive(Clone)]
 pub struct Func {
     pub id: usize,
     pub parameters: usize,
     pub insts: Vec<VmInst>,
 }
 
+/// Files imported by visited statements, in order of imports
+struct ImportedFiles(Vec<usize>);
+
+impl Visitor for ImportedFiles {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if let Stmt::Import { fid, .. } = stmt {
+            if !self.0.contains(fid) {
+                self.0.push(*fid);
+            }
+        }
+        walk_stmt(self, stmt);
+    }
+}
+
 pub struct Interpreter<Buffer: IoWrite, LibCore: StdCore> {
     registers: RegisterTable,
-    scopes: Vec<AHashSet<String>>,
-    byte_code: Vec<Func>,
+    scopes: Vec<AHashSet<Symbol>>,
+    byte_code: Vec<Arc<Func>>,
     vm: Vm,
     gc: Gc<Buffer>,
     out: Buffer,
     file_manager: FileManager,
-    color: bool,
+    render_options: RenderOptions,
     repl: bool,
     search_path: Vec<PathBuf>,
+    /// Name and module directory of packages imported by name, from a manifest
+    packages: Vec<(String, PathBuf)>,
+    keyword_aliases: KeywordAliases,
+    strict: bool,
+    /// Rules checked on each file compiled, if any
+    lint_config: Option<LintConfig>,
+    /// Variables defined by assignments being compiled in strict mode, and their function
+    declaring: Vec<(Symbol, usize)>,
+    /// Name of the function about to be compiled, set when it is assigned to a variable
+    closure_name: Option<Symbol>,
+    /// Globals of main function initialized by constant expressions
+    const_globals: ConstFolder,
+    last_error: Option<ScriptError>,
+    last_report: Option<ExecutionReport>,
     marker: PhantomData<LibCore>,
 }
 
 impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
     /// Create a new interpreter instance
     pub fn new(buffer: Buffer) -> Self {
-        Self::init(buffer, false)
+        Self::init(buffer, RenderOptions::default())
     }
 
     /// Enable or disable REPL mode (print last value to output buffer)
@@ -142,12 +213,106 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
 
     /// Add module search path
     pub fn with_search_path(&mut self, path: PathBuf) -> Result<(), io::Error> {
-        let path = path.canonicalize()?;
+        let path = compat::canonicalize(&path)?;
         self.search_path.push(path);
         Ok(())
     }
 
-    fn init(buffer: Buffer, color: bool) -> Self {
+    /// Search paths added so far, in the order they are searched
+    pub fn search_paths(&self) -> &[PathBuf] {
+        &self.search_path
+    }
+
+    /// Add search paths in environment variable `DIATOM_PATH`
+    ///
+    /// Paths are separated as `PATH` of the platform and those not found are ignored. They are
+    /// not read unless this is called, so that an embedded interpreter does not depend on the
+    /// environment of the host. Only available with feature `std`.
+    #[cfg(feature = "std")]
+    pub fn with_env_search_path(&mut self) -> &mut Self {
+        if let Some(paths) = env::var_os(SEARCH_PATH_ENV) {
+            for path in env::split_paths(&paths) {
+                let _ = self.with_search_path(path);
+            }
+        }
+        self
+    }
+
+    /// Import modules of a project described by `manifest`
+    ///
+    /// Its module directory is added as a search path and each dependency is imported by its
+    /// name, e.g. `import util.strings` for dependency `util`.
+    pub fn with_manifest(&mut self, manifest: &Manifest) -> Result<(), io::Error> {
+        let packages = manifest
+            .dependencies
+            .iter()
+            .map(|(name, path)| Ok((name.clone(), compat::canonicalize(path)?)))
+            .collect::<Result<Vec<_>, io::Error>>()?;
+        self.with_search_path(manifest.src.clone())?;
+        self.packages.extend(packages);
+        Ok(())
+    }
+
+    /// Set what scripts are allowed to do and return the old policy
+    pub fn set_policy(&mut self, policy: SandboxPolicy) -> SandboxPolicy {
+        self.gc.set_policy(policy)
+    }
+
+    pub fn policy(&self) -> &SandboxPolicy {
+        self.gc.policy()
+    }
+
+    /// Set clock used for timeouts and `std.bench`, None for no clock, return the old one
+    ///
+    /// The clock of the standard library is used by default where it is available. Without a
+    /// clock, `SandboxPolicy::timeout` is ignored.
+    pub fn set_clock(&mut self, clock: Option<Arc<dyn Clock>>) -> Option<Arc<dyn Clock>> {
+        self.gc.set_clock(clock)
+    }
+
+    /// Set file system that modules are imported from and return the old one
+    ///
+    /// The file system of the standard library is used by default.
+    pub fn set_file_system(&mut self, file_system: Arc<dyn FileSystem>) -> Arc<dyn FileSystem> {
+        self.file_manager.set_file_system(file_system)
+    }
+
+    /// Set hooks observing calls, writes of globals and errors, None for no hooks, return the
+    /// old ones
+    ///
+    /// Hooks are not copied by `fork_env` or `Program::spawn`. Execution is slower with hooks.
+    pub fn set_hooks(
+        &mut self,
+        hooks: Option<Box<dyn EventHooks<Buffer>>>,
+    ) -> Option<Box<dyn EventHooks<Buffer>>> {
+        self.gc.set_hooks(hooks)
+    }
+
+    /// Set loader that modules are imported from before files, None for no loader, return the
+    /// old one
+    pub fn set_module_loader(
+        &mut self,
+        loader: Option<Arc<dyn ModuleLoader>>,
+    ) -> Option<Arc<dyn ModuleLoader>> {
+        self.file_manager.set_module_loader(loader)
+    }
+
+    /// Grant a capability to external functions
+    ///
+    /// Some standard library functions (e.g. `process`) refuse to run unless the host has
+    /// explicitly granted the corresponding capability. See `SandboxPolicy::grant`.
+    pub fn enable_capability(&mut self, capability: impl Into<String>) -> &mut Self {
+        self.gc.grant_capability(capability);
+        self
+    }
+
+    /// Revoke a previously granted capability
+    pub fn disable_capability(&mut self, capability: impl AsRef<str>) -> &mut Self {
+        self.gc.revoke_capability(capability);
+        self
+    }
+
+    fn init(buffer: Buffer, render_options: RenderOptions) -> Self {
         let main = Func {
             id: 0,
             parameters: 0,
@@ -156,23 +321,34 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
 
         let mut interpreter = Self {
             registers: RegisterTable::new(0),
-            scopes: vec![AHashSet::new()],
-            byte_code: vec![main],
+            scopes: vec![AHashSet::default()],
+            byte_code: vec![Arc::new(main)],
             vm: Vm::new(),
             gc: Gc::new(),
             out: buffer,
             file_manager: FileManager::new(),
-            color,
+            render_options,
             repl: false,
             search_path: vec![],
+            packages: vec![],
+            keyword_aliases: KeywordAliases::default(),
+            strict: false,
+            lint_config: None,
+            declaring: vec![],
+            closure_name: None,
+            const_globals: ConstFolder::default(),
+            last_error: None,
+            last_report: None,
             marker: PhantomData::default(),
         };
-        // Initialize int and float meta table
+        // Initialize meta tables of primitive types
         [
             ("Int", PrimitiveMeta::Int),
             ("Float", PrimitiveMeta::Float),
             ("List", PrimitiveMeta::List),
             ("String", PrimitiveMeta::Str),
+            ("Option", PrimitiveMeta::Option),
+            ("Result", PrimitiveMeta::Result),
         ]
         .into_iter()
         .for_each(|(name, t)| {
@@ -190,17 +366,120 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
         // Execute prelude files
         LibCore::prelude_files().iter().for_each(|(name, code)| {
             if let Err(err) = interpreter.exec(code, name, true) {
-                print!("{err}");
-                panic!("Standard library failed to load: `{name}`");
+                panic!("Standard library failed to load: `{name}`\n{err}");
             }
         });
+        interpreter.last_report = None;
 
         interpreter
     }
 
     /// Enable ansi colored error message
     pub fn with_color(buffer: Buffer) -> Self {
-        Self::init(buffer, true)
+        Self::init(buffer, RenderOptions::default().color(ColorChoice::Always))
+    }
+
+    /// Create an interpreter under `policy` instead of the default one
+    pub fn with_policy(buffer: Buffer, policy: SandboxPolicy) -> Self {
+        let mut interpreter = Self::new(buffer);
+        interpreter.set_policy(policy);
+        interpreter
+    }
+
+    /// Set how diagnostics are rendered and return the old options
+    pub fn set_render_options(&mut self, options: RenderOptions) -> RenderOptions {
+        core::mem::replace(&mut self.render_options, options)
+    }
+
+    pub fn render_options(&self) -> &RenderOptions {
+        &self.render_options
+    }
+
+    /// Set limits of how values are shown by `print` and `inspect`, return the old options
+    pub fn set_print_options(&mut self, options: PrintOptions) -> PrintOptions {
+        self.gc.set_print_options(options)
+    }
+
+    pub fn print_options(&self) -> PrintOptions {
+        self.gc.print_options()
+    }
+
+    /// Set which warnings are reported and how, return the old options
+    ///
+    /// Warnings found while compiling are kept with other diagnostics, see `emit_diagnostics`.
+    /// They fail compilation if the level is `WarningLevel::Deny`.
+    pub fn set_warning_options(&mut self, options: WarningOptions) -> WarningOptions {
+        self.file_manager.set_warning_options(options)
+    }
+
+    pub fn warning_options(&self) -> &WarningOptions {
+        self.file_manager.warning_options()
+    }
+
+    /// Set names recognized as keywords in code compiled later, return the old aliases
+    pub fn set_keyword_aliases(&mut self, aliases: KeywordAliases) -> KeywordAliases {
+        core::mem::replace(&mut self.keyword_aliases, aliases)
+    }
+
+    pub fn keyword_aliases(&self) -> &KeywordAliases {
+        &self.keyword_aliases
+    }
+
+    /// Enable or disable strict mode for code compiled later, return the old setting
+    ///
+    /// A single file can also opt in by a line `--! strict` before any code.
+    pub fn set_strict(&mut self, strict: bool) -> bool {
+        core::mem::replace(&mut self.strict, strict)
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Check lint rules on code compiled later, or stop checking them if `None`
+    ///
+    /// Violated rules are reported with other diagnostics of compilation, filtered by warning
+    /// options. Rules set to `LintLevel::Deny` fail compilation. Return the old config.
+    pub fn set_lint(&mut self, config: Option<LintConfig>) -> Option<LintConfig> {
+        core::mem::replace(&mut self.lint_config, config)
+    }
+
+    pub fn lint_config(&self) -> Option<&LintConfig> {
+        self.lint_config.as_ref()
+    }
+
+    /// Whether code in file `fid` is compiled in strict mode
+    fn is_strict_file(&self, fid: usize) -> bool {
+        self.strict || has_strict_pragma(&self.file_manager.get_file(fid))
+    }
+
+    /// Set source map of code executed as `source`, remove it if `None`, return the old one
+    ///
+    /// Diagnostics in mapped spans get a note pointing to the original file, and locations of
+    /// functions defined there are reported in the original file.
+    pub fn set_source_map(
+        &mut self,
+        source: impl AsRef<OsStr>,
+        map: Option<SourceMap>,
+    ) -> Option<SourceMap> {
+        self.file_manager.set_source_map(source.as_ref(), map)
+    }
+
+    /// Number of warnings reported by the last call
+    pub fn warning_count(&self) -> usize {
+        self.file_manager.warning_count()
+    }
+
+    /// Write diagnostics of the last call to `writer` instead of rendering them to a string
+    ///
+    /// Diagnostics are kept until the next call that compiles or executes code.
+    pub fn emit_diagnostics(&self, writer: impl IoWrite) -> io::Result<()> {
+        self.file_manager.emit(writer, &self.render_options)
+    }
+
+    /// Write diagnostics of the last call to a `fmt::Write`
+    pub fn write_diagnostics(&self, writer: &mut impl fmt::Write) -> fmt::Result {
+        writer.write_str(&self.file_manager.render(&self.render_options))
     }
 
     fn traverse_ext(
@@ -217,7 +496,9 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
             ExtensionKind::ForeignFunctions(functions) => {
                 let mut table = Table {
                     attributes: Default::default(),
+                    indices: Default::default(),
                     meta_table: None,
+                    frozen: false,
                 };
                 functions.into_iter().for_each(|(name, f)| {
                     let table_key = self.gc.get_or_insert_table_key(name);
@@ -271,23 +552,407 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
     {
         let f = GcObject::NativeFunction(Arc::new(f));
         let gc_id = self.gc.alloc_obj(f);
-        let reg = Reg::Ref(gc_id);
+        self.write_global(name, Reg::Ref(gc_id));
+    }
+
+    /// Directly declare external function that has access to host state of type `T`
+    ///
+    /// The state is set by `set_context`. Calling the function fails if the state is not set.
+    /// While the function is running, the state is detached from the interpreter.
+    pub fn impl_extern_function_with_state<T, F>(&mut self, name: impl Into<String>, f: F)
+    where
+        T: Any + Send,
+        F: Fn(
+                &mut T,
+                &mut State<Buffer>,
+                &[DiatomValue],
+                &mut Buffer,
+            ) -> Result<DiatomValue, String>
+            + 'static
+            + Send
+            + Sync,
+    {
+        let name = name.into();
+        let function_name = name.clone();
+        self.impl_extern_function(name, move |state, parameters, out| {
+            let mut context = state.gc.take_context::<T>().ok_or_else(|| {
+                format!(
+                    "`{function_name}` requires host state `{}` which is not set",
+                    core::any::type_name::<T>()
+                )
+            })?;
+            let ret = f(&mut context, state, parameters, out);
+            state.gc.set_context(context);
+            ret
+        });
+    }
+
+    /// Set host state of type `T` and return the old one
+    ///
+    /// There is at most one state for each type.
+    pub fn set_context<T: Any + Send>(&mut self, context: T) -> Option<T> {
+        self.gc.set_context(context)
+    }
+
+    /// Get host state of type `T`
+    pub fn context<T: Any + Send>(&self) -> Option<&T> {
+        self.gc.get_context()
+    }
+
+    /// Get host state of type `T` mutably
+    pub fn context_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.gc.get_context_mut()
+    }
+
+    /// Remove host state of type `T` from the interpreter
+    pub fn take_context<T: Any + Send>(&mut self) -> Option<T> {
+        self.gc.take_context()
+    }
+
+    /// Declare a global variable if not declared and write value to it
+    fn write_global(&mut self, name: impl Into<String>, reg: Reg) {
         let reg_id = self.registers.declare_variable(name.into(), None);
-        self.gc.alloc_reg_file(reg_id + 1);
-        self.gc.set_main_reg_size(reg_id + 1);
+        // Registers of all globals must be kept, not only those before this one
+        let reg_size = self.registers.assigned;
+        self.gc.alloc_reg_file(reg_size);
+        self.gc.set_main_reg_size(reg_size);
         self.gc.write_reg(reg_id, reg);
     }
 
+    /// Set global variable `name` to a value converted from rust
+    ///
+    /// The variable is declared if it does not exist.
+    ///
+    /// # Panics
+    /// Panic if value is a `DiatomValue` that contains an invalid id.
+    pub fn set_global(&mut self, name: impl Into<String>, value: impl IntoDiatom) {
+        let mut state = State { gc: &mut self.gc };
+        let value = value.into_diatom(&mut state);
+        assert!(
+            state.check_value(&value),
+            "Invalid value for global variable"
+        );
+        self.write_global(name, value);
+        self.gc.clear_temporaries();
+    }
+
+    /// Get value of global variable `name`
+    ///
+    /// Return None if `name` is not defined. If value is a reference, it is only guaranteed to be
+    /// valid until next call to the interpreter unless it is rooted by `root`.
+    pub fn get_global(&self, name: impl AsRef<str>) -> Option<DiatomValue> {
+        let (reg_id, _) = self.registers.variables.get(name.as_ref())?;
+        Some(self.gc.read_reg(*reg_id).clone())
+    }
+
+    /// Make the collection held by global variable `name` deeply immutable, as `freeze` does
+    ///
+    /// Useful for data handed to untrusted scripts, e.g. configurations shared by plugins.
+    /// Collections stay frozen in interpreters copied by `fork_env`. Return false if `name` is
+    /// not defined.
+    pub fn freeze_global(&mut self, name: impl AsRef<str>) -> bool {
+        match self.get_global(name) {
+            Some(value) => {
+                self.gc.freeze(&value, None);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Names and kinds of all global variables, sorted by name
+    ///
+    /// Names imported from prelude are included.
+    pub fn globals(&self) -> impl Iterator<Item = (&str, DiatomValueKind)> {
+        let mut globals: Vec<_> = self
+            .registers
+            .variables
+            .iter()
+            .map(|(name, (reg_id, _))| {
+                let kind = DiatomValueKind::of(&self.gc, self.gc.read_reg(*reg_id));
+                (name.as_str(), kind)
+            })
+            .collect();
+        globals.sort_unstable_by_key(|(name, _)| *name);
+        globals.into_iter()
+    }
+
+    /// Metadata of function stored in global variable `name`
+    ///
+    /// Return None if `name` is not defined or is not a function defined in script.
+    pub fn function_info(&self, name: impl AsRef<str>) -> Option<FunctionInfo> {
+        let func_id = match self.get_global(name)? {
+            Reg::Ref(rid) => match self.gc.get_obj(rid)? {
+                GcObject::Closure { func_id, .. } => *func_id,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        self.gc.function_info(func_id).cloned()
+    }
+
+    /// Counters of work done since the interpreter is created or `reset_stats` is called
+    ///
+    /// Counters accumulate over calls, so that the cost of a script can be measured by
+    /// resetting them before running it. Heap sizes are those of now.
+    pub fn stats(&self) -> Stats {
+        let (allocations, collections) = self.gc.counters();
+        let (strings, objects) = self.gc.live_count();
+        Stats {
+            instructions: self.vm.instructions(),
+            allocations,
+            collections,
+            strings,
+            objects,
+        }
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.vm.reset_instructions();
+        self.gc.reset_counters();
+    }
+
+    /// Collect garbage before every allocation and verify the heap after each collection
+    ///
+    /// Very slow, meant for testing external functions that hold values, which panic once a
+    /// value they use is collected instead of reading a reused slot.
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.gc.set_stress(stress);
+    }
+
+    /// Get value of global variable `name` and convert it into a rust value
+    ///
+    /// Return `Err` if `name` is not defined or value can not be converted.
+    pub fn get_global_as<T: FromDiatom>(&mut self, name: impl AsRef<str>) -> Result<T, String> {
+        let value = self
+            .get_global(name.as_ref())
+            .ok_or_else(|| format!("Name `{}` is not defined in global scope", name.as_ref()))?;
+        T::from_diatom(&State { gc: &mut self.gc }, &value)
+            .map_err(|err| format!("Global variable `{}`: {err}", name.as_ref()))
+    }
+
+    /// Convert plain data into a diatom value and keep it alive while the handle is held
+    pub fn to_value(&mut self, data: DiatomData) -> RootedValue {
+        let value = data.into_diatom(&mut State { gc: &mut self.gc });
+        let rooted = RootedValue::new(&mut self.gc, value).unwrap();
+        self.gc.clear_temporaries();
+        rooted
+    }
+
+    /// Convert a diatom value into plain data
+    ///
+    /// Return `Err` if value contains functions, user data or itself.
+    pub fn from_value(&mut self, value: &DiatomValue) -> Result<DiatomData, String> {
+        DiatomData::from_diatom(&State { gc: &mut self.gc }, value)
+    }
+
+    /// Declare a table of external functions and values as variable
+    ///
+    /// Unlike `impl_extern_function`, only the module name is declared so that host APIs do not
+    /// pollute the global scope. Nested modules can be used to version an API.
+    pub fn add_module(
+        &mut self,
+        name: impl Into<String>,
+        build: impl FnOnce(&mut ModuleBuilder<Buffer>),
+    ) {
+        let mut builder = ModuleBuilder::new(&mut self.gc);
+        build(&mut builder);
+        let table = builder.finish();
+        self.write_global(name, Reg::Ref(table));
+        self.gc.clear_temporaries();
+    }
+
+    /// Declare a frozen table of named integer constants, e.g. variants of a rust enum
+    ///
+    /// Each variant becomes a field of the table. Function `name_of` looks up the name of a
+    /// value and returns an `Option`, e.g. `Color::name_of(1)`. Setting fields of the table is a
+    /// runtime error and a misspelled variant is reported as a missing key.
+    ///
+    /// # Panics
+    /// Panic if a variant is named `name_of`.
+    pub fn add_enum(&mut self, name: impl Into<String>, variants: &[(&str, i64)]) {
+        assert!(
+            variants.iter().all(|(variant, _)| *variant != "name_of"),
+            "`name_of` is reserved for reverse lookup"
+        );
+        let names: BTreeMap<i64, String> = variants
+            .iter()
+            .rev()
+            .map(|(variant, value)| (*value, variant.to_string()))
+            .collect();
+        let mut builder = ModuleBuilder::new(&mut self.gc);
+        for (variant, value) in variants {
+            builder.value(*variant, *value);
+        }
+        builder.func("name_of", move |state, parameters, _| {
+            let args = Args::new("name_of", parameters);
+            args.expect_len(1)?;
+            let value = args.get::<i64>(0)?;
+            Ok(names.get(&value).cloned().into_diatom(state))
+        });
+        let table = builder.finish();
+        if let Some(GcObject::Table(t)) = self.gc.get_obj_mut(table) {
+            t.frozen = true;
+        }
+        self.write_global(name, Reg::Ref(table));
+        self.gc.clear_temporaries();
+    }
+
+    /// Register methods and getters of a rust type used as user data
+    ///
+    /// Registering the same type again adds to or overrides previous methods and getters.
+    pub fn register_type<T: Any + Send>(&mut self) -> UserTypeBuilder<'_, T, Buffer> {
+        UserTypeBuilder::new(&mut self.gc)
+    }
+
     /// Check if input is completeness
     ///
     /// Incomplete input usually contains unclosed parentheses, quotes or open expression.
     pub fn verify_input_completeness(&self, code: impl AsRef<str>) -> bool {
         let mut file_manager = FileManager::new();
-        let mut parser = Parser::new(&mut file_manager, &self.search_path);
+        file_manager.set_file_system(self.file_manager.file_system().clone());
+        file_manager.set_module_loader(self.file_manager.module_loader().cloned());
+        let mut parser = Parser::new(&mut file_manager, &self.search_path)
+            .packages(&self.packages)
+            .keyword_aliases(self.keyword_aliases.clone());
         let _ = parser.parse_file(OsStr::new(""), code.as_ref());
         !file_manager.input_can_continue()
     }
 
+    /// Compile code and report diagnostics without executing it
+    pub fn check(
+        &mut self,
+        code: impl AsRef<str>,
+        source: impl AsRef<OsStr>,
+        is_phony: bool,
+    ) -> Result<(), String> {
+        self.compile(code, source.as_ref(), is_phony)
+    }
+
+    /// Check code against lint rules without executing it
+    ///
+    /// Imported modules are neither resolved nor checked. Return rendered warnings, which is empty
+    /// if there is none. If code has syntax errors or a rule set to `LintLevel::Deny` is
+    /// violated, all diagnostics are returned as error. Warnings are filtered by warning options.
+    pub fn lint(
+        &self,
+        code: impl AsRef<str>,
+        source: impl AsRef<OsStr>,
+        config: &LintConfig,
+    ) -> Result<String, String> {
+        let mut file_manager = FileManager::new();
+        file_manager.set_warning_options(self.file_manager.warning_options().clone());
+        let mut parser = Parser::new(&mut file_manager, &[])
+            .skip_imports()
+            .keyword_aliases(self.keyword_aliases.clone());
+        let fid = parser.parse_file_phony(source.as_ref(), code.as_ref());
+        if file_manager.error_count() == 0 {
+            add_lint_warnings(&mut file_manager, fid, config);
+        }
+        if file_manager.error_count() > 0 {
+            Err(file_manager.render(&self.render_options))
+        } else {
+            Ok(file_manager.render(&self.render_options))
+        }
+    }
+
+    /// Parse files and modules they import without compiling them
+    ///
+    /// Files are parsed in parallel with feature `parallel`. Return rendered warnings, which is
+    /// empty if there is none. If any file can not be read or has a syntax error, the error is
+    /// returned instead.
+    pub fn parse_files(&self, paths: &[PathBuf]) -> Result<String, String> {
+        let mut file_manager = FileManager::new();
+        file_manager.set_warning_options(self.file_manager.warning_options().clone());
+        file_manager.set_file_system(self.file_manager.file_system().clone());
+        file_manager.set_module_loader(self.file_manager.module_loader().cloned());
+        let mut parser = Parser::new(&mut file_manager, &self.search_path)
+            .packages(&self.packages)
+            .keyword_aliases(self.keyword_aliases.clone());
+        parser.parse_many(paths).map_err(|err| err.to_string())?;
+        if file_manager.error_count() > 0 {
+            Err(file_manager.render(&self.render_options))
+        } else {
+            Ok(file_manager.render(&self.render_options))
+        }
+    }
+
+    /// Pack source of code and files it imports into a bundle run by `exec_bundle`
+    ///
+    /// The bundle is compiled by this interpreter as `check` does, reading files from the bundle,
+    /// thus compile errors such as unresolved names are reported when the bundle is made instead
+    /// of when it is run. Modules of extensions are not bundled.
+    pub fn bundle(
+        &mut self,
+        code: impl AsRef<str>,
+        source: impl AsRef<OsStr>,
+    ) -> Result<Bundle, String> {
+        let mut file_manager = self.file_manager.fork();
+        let mut parser = Parser::new(&mut file_manager, &self.search_path)
+            .packages(&self.packages)
+            .keyword_aliases(self.keyword_aliases.clone());
+        let main = parser.parse_file(source.as_ref(), code.as_ref());
+        if file_manager.error_count() > 0 {
+            return Err(file_manager.render(&self.render_options));
+        }
+
+        let mut imports = ImportedFiles(vec![main]);
+        let mut files = vec![];
+        let mut next = 0;
+        while let Some(&fid) = imports.0.get(next) {
+            next += 1;
+            let Some(path) = file_manager.file_path(fid) else {
+                continue;
+            };
+            let is_ext = path
+                .iter()
+                .next()
+                .is_some_and(|name| file_manager.is_ext_name(name.to_string_lossy()));
+            if is_ext && fid != main {
+                continue;
+            }
+            files.push((path, file_manager.get_file(fid).to_string()));
+            for stmt in file_manager.get_ast(fid).iter() {
+                imports.visit_stmt(stmt);
+            }
+        }
+        let bundle = Bundle {
+            main: PathBuf::from(source.as_ref()),
+            search_path: self.search_path.clone(),
+            packages: self.packages.clone(),
+            files,
+        };
+        self.compile_bundle(&bundle)?;
+        Ok(bundle)
+    }
+
+    /// Run a bundle made by `bundle`
+    ///
+    /// The bundle is compiled as a script named by its main path. Its imports are resolved with
+    /// search paths and packages of the bundle, reading files from the bundle instead of the file
+    /// system.
+    pub fn exec_bundle(&mut self, bundle: &Bundle) -> Result<(), String> {
+        self.compile_bundle(bundle)?;
+        self.execute().map(|_| ())
+    }
+
+    fn compile_bundle(&mut self, bundle: &Bundle) -> Result<(), String> {
+        let code = bundle
+            .main_script()
+            .ok_or_else(|| "Bundle does not contain its main script".to_string())?
+            .to_string();
+        let file_system = self
+            .file_manager
+            .set_file_system(Arc::new(BundleFiles::new(bundle)));
+        let search_path = core::mem::replace(&mut self.search_path, bundle.search_path.clone());
+        let packages = core::mem::replace(&mut self.packages, bundle.packages.clone());
+        let compiled = self.compile(code, bundle.main.as_os_str(), false);
+        self.file_manager.set_file_system(file_system);
+        self.search_path = search_path;
+        self.packages = packages;
+        compiled
+    }
+
     /// Show decompiled byte code for given source code.
     ///
     /// If compilation failed, `Err` will be returned.
@@ -303,7 +968,8 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
             id,
             parameters,
             insts,
-        } in self.byte_code.iter()
+            ..
+        } in self.byte_code.iter().map(AsRef::as_ref)
         {
             writeln!(decompiled, "Function: Func@{id}\nParameters: {parameters}").unwrap();
             writeln!(decompiled, "Body:").unwrap();
@@ -319,7 +985,14 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
 
     /// Replace output buffer and get the old one
     pub fn replace_buffer(&mut self, buffer: Buffer) -> Buffer {
-        std::mem::replace(&mut self.out, buffer)
+        core::mem::replace(&mut self.out, buffer)
+    }
+
+    /// Replace input source and get the old one
+    ///
+    /// Standard input is used by default.
+    pub fn replace_input(&mut self, input: Box<dyn IoRead + Send>) -> Box<dyn IoRead + Send> {
+        self.gc.replace_input(input)
     }
 
     fn compile(
@@ -329,35 +1002,83 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
         is_phony: bool,
     ) -> Result<(), String> {
         self.file_manager.clear_diagnoses();
-        let mut parser = Parser::new(&mut self.file_manager, &self.search_path);
+        let mut parser = Parser::new(&mut self.file_manager, &self.search_path)
+            .packages(&self.packages)
+            .keyword_aliases(self.keyword_aliases.clone());
         let fid = if is_phony {
             parser.parse_file_phony(source, code.as_ref())
         } else {
             parser.parse_file(source, code.as_ref())
         };
         if self.file_manager.error_count() > 0 {
-            return Err(self.file_manager.render(self.color));
+            return Err(self.render_diagnoses());
+        }
+        if let Some(config) = &self.lint_config {
+            add_lint_warnings(&mut self.file_manager, fid, config);
+            if self.file_manager.error_count() > 0 {
+                return Err(self.render_diagnoses());
+            }
+        }
+        let ast = self.file_manager.get_ast(fid);
+        self.compile_main(&ast)
+    }
+
+    /// Compile statements into main function
+    fn compile_main(&mut self, ast: &[Stmt]) -> Result<(), String> {
+        let strict = self.strict
+            || ast
+                .first()
+                .and_then(Stmt::loc)
+                .is_some_and(|loc| self.is_strict_file(loc.fid));
+        let registers = &self.registers;
+        let (_, errors) = Resolver::new(|name| registers.lookup_variable(name).is_some())
+            .strict(strict)
+            .resolve(ast);
+        if !errors.is_empty() {
+            errors.into_iter().for_each(|code| {
+                // Globals of previous inputs are only known by the register table
+                let code = match code {
+                    ResolveError::AssignToUndeclared(name, loc, None) => {
+                        let similar = self.registers.similar_name(&name).map(str::to_string);
+                        ResolveError::AssignToUndeclared(name, loc, similar)
+                    }
+                    code => code,
+                };
+                self.file_manager
+                    .add_diagnostic(Diagnostic::from(code), false)
+            });
+            return Err(self.render_diagnoses());
         }
 
         let registers_prev = self.registers.clone();
+        let assigned_prev = registers_prev.assigned;
         // clear all executed code
-        self.byte_code[0].insts.clear();
+        let main_prev = core::mem::replace(
+            &mut self.byte_code[0],
+            Arc::new(Func {
+                id: 0,
+                parameters: 0,
+                insts: vec![],
+            }),
+        );
         self.vm.reset_ip();
 
-        let ast = self.file_manager.get_ast(fid);
-        let return_value = self.compile_ast(&ast).map_err(|_| {
-            // restore variable table if compile failed
+        let return_value = self.compile_ast(ast).map_err(|_| {
+            // restore variable table and main function if compile failed
             self.registers = registers_prev;
-            self.file_manager.render(self.color)
+            self.byte_code[0] = main_prev;
+            self.render_diagnoses()
         })?;
 
         // return after main
-        self.byte_code[0].insts.push(VmInst::OpYield(OpYield {
-            show_id: return_value,
-        }));
+        Arc::make_mut(&mut self.byte_code[0])
+            .insts
+            .push(VmInst::OpYield(OpYield {
+                show_id: return_value,
+            }));
 
         // Alloc registers
-        self.byte_code[0].insts.insert(
+        Arc::make_mut(&mut self.byte_code[0]).insts.insert(
             0,
             VmInst::OpAllocReg(OpAllocReg {
                 n_reg: self.registers.assigned,
@@ -365,6 +1086,15 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
         );
 
         self.gc.set_main_reg_size(self.registers.assigned);
+        // Globals declared in strict mode are defined once their assignments complete
+        if strict {
+            self.registers
+                .variables
+                .iter()
+                .filter(|(name, (id, _))| *id >= assigned_prev && !name.starts_with('#'))
+                .map(|(_, id)| id)
+                .for_each(|(id, _)| self.gc.undefine(*id));
+        }
 
         Ok(())
     }
@@ -387,38 +1117,522 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
         is_phony: bool,
     ) -> Result<(), String> {
         self.compile(code, source.as_ref(), is_phony)?;
-        match self.vm.exec(&self.byte_code, &mut self.gc, &mut self.out) {
-            (VmError::Yield(Some(reg_id)), _) if self.repl => {
-                let reg = self.gc.read_reg(reg_id);
-                match reg {
-                    Reg::Unit => Ok(()),
-                    _ => {
-                        let content = self.gc.print(reg);
-                        writeln!(self.out, "{content}").map_err(|err| {
-                            let error_code = VmError::IoError {
-                                loc: None,
-                                error: err,
-                            };
-                            self.file_manager.add_diagnostic(error_code.into(), false);
-                            self.file_manager.render(self.color)
-                        })
-                    }
+        self.execute().map(|_| ())
+    }
+
+    /// Run diatom source code and return the value of its last expression
+    ///
+    /// Source is a place holder like `exec` with `is_phony`, thus only modules on search paths
+    /// can be imported. Unit is returned if code does not end with an expression. If the value is
+    /// a reference, it is only guaranteed to be valid until next call to the interpreter unless
+    /// it is rooted by `root`.
+    pub fn eval(
+        &mut self,
+        code: impl AsRef<str>,
+        source: impl AsRef<OsStr>,
+    ) -> Result<DiatomValue, String> {
+        self.compile(code, source.as_ref(), true)?;
+        self.execute()
+    }
+
+    /// Run diatom source code read from `reader` piece by piece
+    ///
+    /// Once `STREAM_CHUNK_SIZE` (64 KiB) bytes are read, code is cut before the next line that starts a
+    /// statement and the piece is executed before reading on, thus a large generated script is
+    /// never wholly in memory. Pieces that define no function are dropped after execution.
+    /// Diagnostics report lines of the whole source named `source`.
+    ///
+    /// Execution stops at the first error, code before it has been executed.
+    #[cfg(feature = "std")]
+    pub fn exec_reader(
+        &mut self,
+        reader: impl io::Read,
+        source: impl AsRef<OsStr>,
+    ) -> Result<(), String> {
+        self.exec_lines(io::BufReader::new(reader), source)
+    }
+
+    /// Run diatom source code read from `reader` piece by piece, see the version with `std`
+    #[cfg(not(feature = "std"))]
+    pub fn exec_reader(
+        &mut self,
+        reader: impl BufRead,
+        source: impl AsRef<OsStr>,
+    ) -> Result<(), String> {
+        self.exec_lines(reader, source)
+    }
+
+    fn exec_lines(
+        &mut self,
+        mut reader: impl BufRead,
+        source: impl AsRef<OsStr>,
+    ) -> Result<(), String> {
+        let source = source.as_ref().to_string_lossy().into_owned();
+        let mut chunk = String::new();
+        let mut line = String::new();
+        // Line of the start of the chunk and number of lines in it
+        let (mut start, mut lines) = (1, 0);
+        // Checking completeness parses the chunk, grow the limit when it fails so that a long
+        // statement is not parsed again for every line
+        let mut limit = STREAM_CHUNK_SIZE;
+        loop {
+            line.clear();
+            let eof = match reader.read_line(&mut line) {
+                Ok(n) => n == 0,
+                Err(err) => {
+                    self.file_manager.clear_diagnoses();
+                    return Err(self.render_host_error(format!("Can not read {source}: {err}")));
+                }
+            };
+            if eof {
+                return match chunk.is_empty() {
+                    true => Ok(()),
+                    false => self.exec_chunk(&chunk, &source, start),
+                };
+            }
+            if chunk.len() >= limit && self.starts_statement(&line) {
+                if self.verify_input_completeness(&chunk) {
+                    self.exec_chunk(&chunk, &source, start)?;
+                    chunk.clear();
+                    (start, lines) = (start + lines, 0);
+                    limit = STREAM_CHUNK_SIZE;
+                } else {
+                    limit = chunk.len() * 2;
                 }
             }
-            (VmError::Yield(_), _) => Ok(()),
+            chunk.push_str(&line);
+            lines += 1;
+        }
+    }
+
+    /// Execute a piece of `source` starting at `line`
+    fn exec_chunk(&mut self, code: &str, source: &str, line: usize) -> Result<(), String> {
+        let name = format!("{source}:{line}");
+        // Also map the end of code where unexpected EOF is reported
+        let map = SourceMap::new().map(0..code.len() + 1, source, line, 1);
+        self.file_manager.set_source_map(name.as_str(), Some(map));
+        self.exec(code, &name, true)?;
+        let fid = self.file_manager.look_up_fid(name.as_str()).unwrap();
+        // Functions keep locations in their file for diagnostics
+        if !defines_function(&self.file_manager.get_ast(fid)) {
+            self.file_manager.release_file(fid);
+        }
+        Ok(())
+    }
+
+    /// Whether `line` may start a statement, not continue the one before
+    ///
+    /// Only lines starting with an identifier or a keyword other than those in the middle of a
+    /// statement are accepted, indented lines are assumed to be inside a statement.
+    fn starts_statement(&self, line: &str) -> bool {
+        if !line.starts_with(|c: char| c == '_' || is_xid_start(c)) {
+            return false;
+        }
+        let word = line
+            .split(|c: char| !is_xid_continue(c))
+            .next()
+            .unwrap_or_default();
+        let word = self.keyword_aliases.get(word).unwrap_or(word);
+        !matches!(
+            word,
+            "and"
+                | "or"
+                | "is"
+                | "in"
+                | "as"
+                | "from"
+                | "then"
+                | "do"
+                | "until"
+                | "else"
+                | "elsif"
+                | "end"
+        )
+    }
+
+    /// Compile a piece of diatom source code into a program without running it
+    ///
+    /// The program can be run by this interpreter with `run` or by any interpreter spawned from
+    /// the returned program.
+    ///
+    /// # Return
+    /// * If compilation failed, an `Err(String)` that illustrates the error is returned.
+    pub fn compile_program(
+        &mut self,
+        code: impl AsRef<str>,
+        source: impl AsRef<OsStr>,
+        is_phony: bool,
+    ) -> Result<Program<Buffer, LibCore>, String> {
+        self.compile(code, source.as_ref(), is_phony)?;
+        Ok(Program::new(self))
+    }
+
+    /// Copy the interpreter with its globals, so that code run by the copy never changes them
+    ///
+    /// Byte code and unchanged parts of the heap are shared as by `Program::spawn`, and the same
+    /// state is left out of the copy. REPL mode and lint rules are kept.
+    pub fn fork_env(&self, buffer: Buffer) -> Self {
+        let mut interpreter = Snapshot::of(self).into_interpreter(buffer);
+        interpreter.repl = self.repl;
+        interpreter.lint_config = self.lint_config.clone();
+        interpreter
+    }
+
+    /// Reload function definitions from a changed file while keeping global state
+    ///
+    /// Only top level `def` statements are executed, thus globals assigned elsewhere keep their
+    /// current values. All definitions are compiled before any of them is replaced, so nothing
+    /// changes if the file fails to compile.
+    ///
+    /// `on_change` is called after reloading with names of functions whose definitions are new
+    /// or differ from the last time `path` was executed or reloaded.
+    pub fn reload(
+        &mut self,
+        path: impl AsRef<Path>,
+        on_change: impl FnOnce(&[String]),
+    ) -> Result<(), String> {
+        let path = path.as_ref();
+        self.file_manager.clear_diagnoses();
+        let code = self.file_manager.read_file(path).map_err(|err| {
+            self.render_host_error(format!("Can not read `{}`: {err}", path.display()))
+        })?;
+
+        // Source of each definition, keyed by function name
+        let definitions = |file_manager: &FileManager, fid: usize| {
+            let file = file_manager.get_file(fid);
+            file_manager
+                .get_ast(fid)
+                .iter()
+                .filter_map(|stmt| match stmt {
+                    Stmt::Def { loc, variable, .. } => {
+                        let name = variable.get_loc();
+                        Some((
+                            file[name.start..name.end].to_string(),
+                            file[loc.start..loc.end].to_string(),
+                        ))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let previous: AHashMap<_, _> = self
+            .file_manager
+            .look_up_fid(path)
+            .map(|fid| definitions(&self.file_manager, fid).into_iter().collect())
+            .unwrap_or_default();
+
+        let mut parser = Parser::new(&mut self.file_manager, &self.search_path)
+            .packages(&self.packages)
+            .keyword_aliases(self.keyword_aliases.clone());
+        let fid = parser.parse_file(path, code);
+        if self.file_manager.error_count() > 0 {
+            return Err(self.render_diagnoses());
+        }
+        let ast: Vec<_> = self
+            .file_manager
+            .get_ast(fid)
+            .iter()
+            .filter(|stmt| matches!(stmt, Stmt::Def { .. }))
+            .cloned()
+            .collect();
+        self.compile_main(&ast)?;
+        self.execute()?;
+
+        let changed: Vec<_> = definitions(&self.file_manager, fid)
+            .into_iter()
+            .filter(|(name, source)| previous.get(name) != Some(source))
+            .map(|(name, _)| name)
+            .collect();
+        on_change(&changed);
+        Ok(())
+    }
+
+    /// Run the last compiled code again
+    ///
+    /// Mostly used with interpreters spawned by `Program::spawn`. Globals keep the values left
+    /// by the previous run unless they are assigned again by the code.
+    pub fn run(&mut self) -> Result<(), String> {
+        self.file_manager.clear_diagnoses();
+        self.execute().map(|_| ())
+    }
+
+    /// Execute the last compiled code, warnings of compilation are kept
+    ///
+    /// Return value of the last expression, which is shown in repl mode.
+    fn execute(&mut self) -> Result<DiatomValue, String> {
+        self.vm.reset_ip();
+        self.prepare_vm();
+        let value = match self.exec_vm() {
+            (VmError::Yield(Some(reg_id)), _) => self.gc.read_reg(reg_id).clone(),
+            (VmError::Yield(None), _) => Reg::Unit,
             (error, trace) => {
-                trace.into_iter().rev().for_each(|loc| {
-                    self.file_manager.add_diagnostic(
-                        Diagnostic::error()
-                            .with_message("Trace back")
-                            .with_labels(vec![Label::primary(loc.fid, loc)]),
-                        false,
-                    )
-                });
-                self.file_manager.add_diagnostic(error.into(), false);
-                Err(self.file_manager.render(self.color))
+                // Execution error is more useful than a flush error
+                let _ = self.out.flush();
+                return Err(self.render_vm_error(error, trace));
             }
+        };
+        let result = if self.repl && !matches!(value, Reg::Unit) {
+            let content = self.gc.inspect(&value, DEFAULT_INSPECT_DEPTH);
+            writeln!(self.out, "{content}")
+        } else {
+            Ok(())
+        };
+        // End of execution is a flush point
+        result
+            .and_then(|_| self.out.flush())
+            .map(|_| value)
+            .map_err(|err| {
+                let error_code = VmError::IoError {
+                    loc: None,
+                    error: err,
+                };
+                self.file_manager.add_diagnostic(error_code.into(), false);
+                self.render_diagnoses()
+            })
+    }
+
+    /// Execute byte code from current ip
+    ///
+    /// A panic of the virtual machine is reported as an internal error instead of unwinding into
+    /// host. The call stack is cleaned so that the interpreter can still be used, but the heap
+    /// may be left in an inconsistent state.
+    fn exec_vm(&mut self) -> (VmError, Vec<Loc>) {
+        let instructions = self.vm.instructions();
+        let (allocations, _) = self.gc.counters();
+        let start = self.gc.clock().map(|clock| clock.now());
+        self.gc.reset_peaks();
+
+        let Self {
+            vm,
+            byte_code,
+            gc,
+            out,
+            ..
+        } = self;
+        let result =
+            compat::catch_unwind(|| vm.exec(byte_code, gc, out)).unwrap_or_else(|payload| {
+                self.gc.clean_call_stack();
+                (VmError::InternalPanic(panic_message(payload)), vec![])
+            });
+        for warning in self.gc.take_warnings() {
+            self.file_manager.add_warning(warning.into());
         }
+
+        let (peak_strings, peak_gc_objects) = self.gc.peak_count();
+        let wall_time = match (start, self.gc.clock()) {
+            (Some(start), Some(clock)) => Some(clock.now().saturating_sub(start)),
+            _ => None,
+        };
+        self.last_report = Some(ExecutionReport {
+            instructions: self.vm.instructions() - instructions,
+            allocations: self.gc.counters().0 - allocations,
+            peak_gc_objects,
+            peak_strings,
+            wall_time,
+        });
+        result
+    }
+
+    /// Apply resource limits of policy before execution
+    fn prepare_vm(&mut self) {
+        self.vm.set_fuel(self.gc.policy().max_fuel);
+        let deadline = match (self.gc.policy().timeout, self.gc.clock()) {
+            (Some(timeout), Some(clock)) => {
+                Some(Deadline::new(clock.clone(), clock.now() + timeout))
+            }
+            _ => None,
+        };
+        self.vm.set_deadline(deadline.clone());
+        self.gc.set_deadline(deadline);
+        self.gc.take_out_of_memory();
+        self.gc.take_raised();
+        self.gc.take_interrupted();
+        if self.gc.has_hooks() {
+            let names = self
+                .registers
+                .variables
+                .iter()
+                .map(|(name, (reg_id, _))| (*reg_id, name.to_string()))
+                .collect();
+            self.gc.set_global_names(names);
+        }
+    }
+
+    /// Render all diagnoses and record the first one as last error
+    fn render_diagnoses(&mut self) -> String {
+        self.last_error = self
+            .file_manager
+            .first_error()
+            .map(|diagnostic| ScriptError::new(diagnostic.code.clone(), &diagnostic.message));
+        self.file_manager.render(&self.render_options)
+    }
+
+    fn render_vm_error(&mut self, error: VmError, trace: Vec<Loc>) -> String {
+        // Frame of a call made by host has no source to show
+        trace
+            .into_iter()
+            .rev()
+            .filter(|loc| !loc.is_host())
+            .for_each(|loc| {
+                self.file_manager.add_diagnostic(
+                    Diagnostic::error()
+                        .with_message("Trace back")
+                        .with_labels(vec![Label::primary(loc.fid, loc)]),
+                    false,
+                )
+            });
+        // Error of external functions is more useful than the generic message
+        let raised = self.gc.take_raised();
+        let (raised, reason) = match &error {
+            VmError::Panic { reason, .. } => (raised, Some(reason.clone())),
+            VmError::ExternPanic { reason, .. } => (None, Some(reason.clone())),
+            _ => (None, None),
+        };
+        let diagnostic = Diagnostic::from(error);
+        let error = match (raised, reason) {
+            (Some(raised), _) => raised,
+            (_, Some(reason)) => ScriptError::new(diagnostic.code.clone(), reason),
+            _ => ScriptError::new(diagnostic.code.clone(), &diagnostic.message),
+        };
+        self.gc.with_hooks(|hooks, _| hooks.on_error(&error));
+        self.last_error = Some(error);
+        self.file_manager.add_diagnostic(diagnostic, false);
+        self.file_manager.render(&self.render_options)
+    }
+
+    /// Render an error that is not related to any source code
+    fn render_host_error(&mut self, message: impl Into<String>) -> String {
+        let message = message.into();
+        self.last_error = Some(ScriptError::new(None, &message));
+        self.file_manager
+            .add_diagnostic(Diagnostic::error().with_message(message), false);
+        self.file_manager.render(&self.render_options)
+    }
+
+    /// Resources used by the last execution of code, by `exec`, `run`, `call` or `call_function`
+    ///
+    /// Code read by `exec_reader` is executed piece by piece, the report is of the last piece.
+    /// Return None if no code has been executed.
+    pub fn last_report(&self) -> Option<&ExecutionReport> {
+        self.last_report.as_ref()
+    }
+
+    /// Structured form of the last `Err` returned by the interpreter
+    ///
+    /// Errors reported by `State::raise` keep their code and payload. Other errors carry the
+    /// diagnostic code if there is one, e.g. `E3010` for accessing a missing key.
+    pub fn last_error(&self) -> Option<&ScriptError> {
+        self.last_error.as_ref()
+    }
+
+    /// Call a function stored in global variable `name`
+    ///
+    /// # Return
+    /// * Return value of the function. If it is a reference, it is only guaranteed to be valid
+    ///   until next call to the interpreter unless it is rooted by `root`.
+    /// * If `name` is not defined, parameters contain an invalid id or error occurs during
+    ///   execution, an `Err(String)` that illustrates the error is returned.
+    pub fn call(
+        &mut self,
+        name: impl AsRef<str>,
+        parameters: &[DiatomValue],
+    ) -> Result<DiatomValue, String> {
+        self.file_manager.clear_diagnoses();
+        match self.registers.variables.get(name.as_ref()) {
+            Some((reg_id, _)) => {
+                let f = self.gc.read_reg(*reg_id).clone();
+                self.call_value(f, parameters)
+            }
+            None => Err(self.render_host_error(format!(
+                "Name `{}` is not defined in global scope",
+                name.as_ref()
+            ))),
+        }
+    }
+
+    /// Keep a value alive across calls to the interpreter while the returned handle is held
+    ///
+    /// Return None if value contains an invalid id.
+    pub fn root(&mut self, value: DiatomValue) -> Option<RootedValue> {
+        RootedValue::new(&mut self.gc, value)
+    }
+
+    /// Get a function stored in global variable `name` and keep it alive
+    ///
+    /// Return None if `name` is not defined or is neither a closure nor an external function.
+    pub fn get_function(&mut self, name: impl AsRef<str>) -> Option<FunctionHandle> {
+        let (reg_id, _) = self.registers.variables.get(name.as_ref())?;
+        let f = self.gc.read_reg(*reg_id).clone();
+        FunctionHandle::new(&mut self.gc, &f)
+    }
+
+    /// Call a function held by host
+    ///
+    /// Same as `call` except that function is given by a handle.
+    pub fn call_function(
+        &mut self,
+        f: &FunctionHandle,
+        parameters: &[DiatomValue],
+    ) -> Result<DiatomValue, String> {
+        self.file_manager.clear_diagnoses();
+        self.call_value(f.value(), parameters)
+    }
+
+    fn call_value(&mut self, f: Reg, parameters: &[DiatomValue]) -> Result<DiatomValue, String> {
+        let is_callable =
+            matches!(f, Reg::Ref(rid) if self.gc.get_obj(rid).is_some_and(GcObject::is_callable));
+        if !is_callable {
+            return Err(self.render_host_error("Value is not callable"));
+        }
+        let is_valid = parameters.iter().all(|para| match para {
+            Reg::Str(sid) => self.gc.get_str(*sid).is_some(),
+            Reg::Ref(rid) => self.gc.get_obj(*rid).is_some(),
+            _ => true,
+        });
+        if !is_valid {
+            return Err(self.render_host_error("Parameters contain an invalid id"));
+        }
+
+        // Layout after main registers: function, return value, Reg#0 of callee, parameters
+        let base = self.registers.assigned;
+        let start = base + 3;
+        let reg_size = start + parameters.len();
+        self.gc.alloc_reg_file(reg_size);
+        self.gc.set_main_reg_size(reg_size);
+        self.gc.write_reg(base, f);
+        parameters
+            .iter()
+            .enumerate()
+            .for_each(|(i, para)| self.gc.write_reg(start + i, para.clone()));
+
+        // Main function is restored afterwards so that `run` is not affected
+        let trampoline = Func {
+            id: 0,
+            parameters: 0,
+            insts: vec![
+                VmInst::OpCall(OpCall {
+                    reg_id: base,
+                    parameters: parameters.len(),
+                    start,
+                    write_back: Some(base + 1),
+                    loc: Loc::HOST,
+                }),
+                VmInst::OpYield(OpYield {
+                    show_id: Some(base + 1),
+                }),
+            ],
+        };
+        let main = core::mem::replace(&mut self.byte_code[0], Arc::new(trampoline));
+        self.vm.reset_ip();
+        self.prepare_vm();
+
+        let result = match self.exec_vm() {
+            (VmError::Yield(Some(reg_id)), _) => Ok(self.gc.read_reg(reg_id).clone()),
+            (error, trace) => Err(self.render_vm_error(error, trace)),
+        };
+        self.byte_code[0] = main;
+        self.gc.set_main_reg_size(self.registers.assigned);
+        result
     }
 
     /// if compile succeeded, return last expression's reg id
@@ -426,18 +1640,28 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
         let mut return_value = None;
         let mut has_error = false;
 
-        // scan all constant values
+        // scan all constant values, folded initializers are replaced by their value
+        self.const_globals = ConstFolder::new(ast);
         let func_id = self.registers.func_id;
         let mut const_scanner = ConstScanner {
             register_table: &mut self.registers,
             gc: &mut self.gc,
-            insts: &mut self.byte_code[func_id].insts,
+            insts: &mut Arc::make_mut(&mut self.byte_code[func_id]).insts,
         };
-        ast.iter().for_each(|stmt| const_scanner.scan_stmt(stmt));
+        self.const_globals
+            .values()
+            .for_each(|value| const_scanner.scan_const(value));
+        ast.iter()
+            .filter(|stmt| !self.const_globals.folds(stmt))
+            .for_each(|stmt| const_scanner.scan_stmt(stmt));
 
         for (i, stmt) in ast.iter().enumerate() {
+            let assigned = self.registers.assigned;
             match self.compile_stmt(stmt, i != ast.len() - 1, None) {
-                Ok(ret) => return_value = ret,
+                Ok(ret) => {
+                    return_value = ret;
+                    self.define_globals(stmt, assigned);
+                }
                 Err(code) => {
                     has_error = true;
                     self.file_manager
@@ -446,6 +1670,7 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
             }
         }
 
+        self.const_globals = ConstFolder::default();
         if has_error {
             Err(())
         } else {
@@ -453,6 +1678,70 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
         }
     }
 
+    /// Mark globals of strict mode assigned by top level statement `stmt` of main as defined
+    ///
+    /// Registers from `assigned` are declared by `stmt`. Others are only marked if a previous
+    /// assignment failed, see `OpCheckDefined`.
+    fn define_globals(&mut self, stmt: &Stmt, assigned: usize) {
+        let strict = stmt.loc().is_some_and(|loc| self.is_strict_file(loc.fid));
+        if self.registers.func_id != 0 || !strict {
+            return;
+        }
+        let mut names = AHashMap::default();
+        definitions(stmt, &mut names);
+        let mut ids: Vec<usize> = names
+            .keys()
+            .filter_map(|name| self.registers.lookup_variable(name))
+            .map(|(id, _, _)| id)
+            .filter(|id| *id >= assigned || self.gc.is_undefined(*id))
+            .collect();
+        ids.sort_unstable();
+        for rd in ids {
+            self.get_current_insts()
+                .push(VmInst::OpDefine(OpDefine { rd }));
+        }
+    }
+
+    /// Mark global at `id` as defined if it is assigned in a nested block of main after a
+    /// previous assignment failed, top level statements are handled by `define_globals`
+    fn define_nested_global(&mut self, id: usize) {
+        if self.registers.func_id == 0 && self.scopes.len() > 1 && self.gc.is_undefined(id) {
+            self.get_current_insts()
+                .push(VmInst::OpDefine(OpDefine { rd: id }));
+        }
+    }
+
+    /// Raise an error before closure of main captures a global whose assignment failed
+    ///
+    /// Register `skip` is the variable the closure is assigned to, which is defined after.
+    fn check_captured_globals(&mut self, loc: &Loc, capture: &[Capture], skip: Option<usize>) {
+        if self.registers.func_id != 0 {
+            return;
+        }
+        for Capture { rs, .. } in capture {
+            if Some(*rs) == skip || !self.gc.is_undefined(*rs) {
+                continue;
+            }
+            let Some(name) = self
+                .registers
+                .variables
+                .iter()
+                .find(|(_, (id, _))| id == rs)
+                .map(|(name, _)| name.clone())
+            else {
+                continue;
+            };
+            let similar = self.registers.similar_name(&name).map(str::to_string);
+            self.get_current_insts()
+                .push(VmInst::OpCheckDefined(OpCheckDefined {
+                    loc: loc.clone(),
+                    rs: *rs,
+                    name: name.to_string(),
+                    similar,
+                }));
+        }
+    }
+
     /// Compile a statement
     /// Return value is already properly freed
     fn compile_stmt(
@@ -575,6 +1864,33 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                 self.get_current_insts()
                     .push(VmInst::OpRet(OpRet { return_reg }))
             }
+            Stmt::Defer { loc, body } => {
+                if self.registers.prev.is_none() {
+                    return Err(ErrorCode::DeferOutsideFunction(loc.clone()));
+                }
+                // Body is compiled as a closure, which copies variables of this function when it
+                // is deferred since registers of nested blocks are reused before exit
+                let body = Expr::Block {
+                    loc: loc.clone(),
+                    body: vec![Stmt::Expr {
+                        loc: loc.clone(),
+                        expr: body.clone(),
+                    }],
+                };
+                let (func_id, _, capture, reg_size) = self.compile_closure(loc, &[], &body)?;
+                let (capture, copy) = capture.into_iter().partition(|Capture { rs, .. }| {
+                    self.registers
+                        .capture
+                        .iter()
+                        .any(|up_value| up_value.rd == *rs)
+                });
+                self.get_current_insts().push(VmInst::OpDefer(OpDefer {
+                    func_id,
+                    capture,
+                    copy,
+                    reg_size,
+                }));
+            }
             Stmt::For {
                 loc,
                 loop_variable,
@@ -598,7 +1914,7 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                             lhs: iterator.clone(),
                             rhs: Box::new(Expr::Id {
                                 loc: iterator.get_loc(),
-                                name: "__iter".to_string(),
+                                name: "__iter".into(),
                             }),
                         }),
                         parameters: vec![],
@@ -634,7 +1950,7 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                                 }),
                                 rhs: Box::new(Expr::Id {
                                     loc: loop_variable.get_loc(),
-                                    name: "__next".to_string(),
+                                    name: "__next".into(),
                                 }),
                             }),
                             parameters: vec![],
@@ -659,11 +1975,11 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                         op: OpInfix::DoubleColon,
                         lhs: Box::new(Expr::Id {
                             loc: loop_variable.get_loc(),
-                            name: "Option".to_string(),
+                            name: "Option".into(),
                         }),
                         rhs: Box::new(Expr::Id {
                             loc: loop_variable.get_loc(),
-                            name: "None".to_string(),
+                            name: "None".into(),
                         }),
                     }),
                 };
@@ -683,7 +1999,7 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                             }),
                             rhs: Box::new(Expr::Id {
                                 loc: loop_variable.get_loc(),
-                                name: "value".to_string(),
+                                name: "value".into(),
                             }),
                         }),
                     },
@@ -707,20 +2023,38 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
             }
             Stmt::Def {
                 loc,
+                decorators,
                 variable,
                 parameters,
                 body,
             } => {
+                let is_method = matches!(
+                    variable.as_ref(),
+                    Expr::Infix {
+                        op: OpInfix::Member,
+                        ..
+                    }
+                );
+                let lowered = is_method
+                    .then(|| Symbol::from(format!("#super{}", self.registers.gen_sym())))
+                    .and_then(|table| lower_super(loc, variable, parameters, body, &table));
+                let (lhs, body) = match lowered {
+                    Some((assignment, variable, body)) => {
+                        self.compile_stmt(&assignment, true, None)?;
+                        (Box::new(variable), body)
+                    }
+                    None => (variable.clone(), body.clone()),
+                };
                 let expr = Expr::Infix {
                     loc: loc.clone(),
                     op: OpInfix::Assign,
-                    lhs: variable.clone(),
+                    lhs,
                     rhs: Box::new(Expr::Fn {
                         loc: loc.clone(),
                         parameters: parameters.clone(),
                         body: Box::new(Expr::Block {
                             loc: loc.clone(),
-                            body: body.clone(),
+                            body,
                         }),
                     }),
                 };
@@ -732,12 +2066,35 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                     discard,
                     target,
                 )?;
+                if let Some(stmt) = lower_decorators(loc, variable, decorators) {
+                    self.compile_stmt(&stmt, discard, target)?;
+                }
             }
+            Stmt::Enum {
+                loc,
+                name,
+                variants,
+                ..
+            } => {
+                // Variants are added to the table of an existing variable of the same name
+                let create = self.registers.lookup_variable(name).is_none();
+                for stmt in lower_enum(loc, name, variants, create) {
+                    self.compile_stmt(&stmt, true, None)?;
+                }
+            }
+            Stmt::Interface {
+                loc, name, methods, ..
+            } => {
+                self.compile_stmt(&lower_interface(loc, name, methods), true, None)?;
+            }
+            // Calls of macros are expanded by the parser
+            Stmt::Macro { .. } => (),
             Stmt::Import {
                 loc,
                 fid,
                 items,
                 direct_import_mod,
+                ..
             } => {
                 self.gc.new_module(*fid);
                 let body = self.file_manager.get_ast(*fid);
@@ -747,11 +2104,11 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                 };
                 // Make a new closure
                 let func_id = self.byte_code.len();
-                self.byte_code.push(Func {
+                self.byte_code.push(Arc::new(Func {
                     id: func_id,
                     parameters: 0,
                     insts: vec![],
-                });
+                }));
                 self.registers.enter_function(func_id);
 
                 let func_id = self.registers.func_id;
@@ -759,7 +2116,7 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                 let mut const_scanner = ConstScanner {
                     register_table: &mut self.registers,
                     gc: &mut self.gc,
-                    insts: &mut self.byte_code[func_id].insts,
+                    insts: &mut Arc::make_mut(&mut self.byte_code[func_id]).insts,
                 };
                 const_scanner.scan_expr(&body);
 
@@ -767,12 +2124,14 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                 let mut capture_scanner = CaptureScanner {
                     register_table: &mut self.registers,
                     gc: &mut self.gc,
-                    insts: &mut self.byte_code[func_id].insts,
-                    overridden: AHashMap::new(),
+                    insts: &mut Arc::make_mut(&mut self.byte_code[func_id]).insts,
+                    overridden: AHashMap::default(),
                 };
                 LibCore::prelude_names()
                     .iter()
                     .for_each(|name| capture_scanner.scan_name(name));
+                // Names defined by the importer must not leak into the module
+                self.registers.isolated = true;
 
                 let result = match &body {
                     Expr::Infix {
@@ -834,7 +2193,7 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                             assert!(depth == 0);
                             id
                         } else {
-                            self.scopes.last_mut().unwrap().insert(name.clone());
+                            self.scopes.last_mut().unwrap().insert(name.into());
                             self.registers.declare_variable(name, Some(loc.clone()))
                         };
                     self.get_current_insts().push(VmInst::OpMove(OpMove {
@@ -850,6 +2209,8 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                                     rs: item_reg_id,
                                     rd: item_reg_id,
                                     attr: key_id,
+                                    bind: false,
+                                    member: false,
                                 }))
                         });
                     }
@@ -931,7 +2292,7 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                     loc: loc.clone(),
                     lhs: Box::new(Expr::Id {
                         loc: loc.clone(),
-                        name: "Range".to_string(),
+                        name: "Range".into(),
                     }),
                     parameters: vec![lhs.as_ref().clone(), rhs.as_ref().clone()],
                 };
@@ -947,29 +2308,97 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                     loc: loc.clone(),
                     lhs: Box::new(Expr::Id {
                         loc: loc.clone(),
-                        name: "Range".to_string(),
+                        name: "Range".into(),
                     }),
                     parameters: vec![lhs.as_ref().clone(), rhs],
                 };
                 self.compile_expr(&expr, false, target)
             }
+            // lhs.rhs unless lhs is ()
+            Expr::Infix {
+                loc,
+                op: OpInfix::SafeMember,
+                lhs,
+                rhs,
+            } => self.compile_nil_safe(
+                loc,
+                lhs,
+                |receiver| Expr::Infix {
+                    loc: loc.clone(),
+                    op: OpInfix::Member,
+                    lhs: receiver,
+                    rhs: rhs.clone(),
+                },
+                target,
+            ),
+            // lhs[rhs] unless lhs is ()
+            Expr::Infix {
+                loc,
+                op: OpInfix::SafeIndex,
+                lhs,
+                rhs,
+            } => self.compile_nil_safe(
+                loc,
+                lhs,
+                |receiver| Expr::Index {
+                    loc: loc.clone(),
+                    lhs: receiver,
+                    rhs: rhs.clone(),
+                },
+                target,
+            ),
+            // Attribute of the parent of the class defining a method, see `lower_super`
+            Expr::Infix {
+                loc,
+                op: OpInfix::Member,
+                lhs,
+                rhs,
+            } if matches!(lhs.as_ref(), Expr::Id { name, .. } if is_super(name)) => {
+                let Expr::Id { name, .. } = rhs.as_ref() else {
+                    unreachable!()
+                };
+                let (rs, tmp) = self.compile_expr(lhs, false, None)?;
+                if tmp {
+                    self.registers.free_intermediate(rs);
+                }
+                let rd = target.unwrap_or_else(|| self.registers.declare_intermediate());
+                let attr = self.gc.get_or_insert_table_key(name);
+                self.get_current_insts()
+                    .push(VmInst::OpGetSuper(OpGetSuper {
+                        loc: loc.clone(),
+                        rs,
+                        rd,
+                        attr,
+                    }));
+                Ok((rd, target.is_none()))
+            }
             Expr::Infix {
                 loc,
-                op: OpInfix::Member | OpInfix::DoubleColon,
+                op: op @ (OpInfix::Member | OpInfix::DoubleColon),
                 lhs,
                 rhs,
             } => {
+                // A callable read by `.` is bound to the receiver, while `::` reads it as it is
+                let bind = *op == OpInfix::Member && matches!(rhs.as_ref(), Expr::Id { .. });
                 let (lhs, tmp) = self.compile_expr(lhs, false, None)?;
-                if tmp {
+                if tmp && !bind {
                     self.registers.free_intermediate(lhs);
                 }
                 let rd = target.unwrap_or_else(|| self.registers.declare_intermediate());
+                // The receiver is read after the lookup, which thus must not overwrite it
+                let get_rd = if bind && rd == lhs {
+                    self.registers.declare_intermediate()
+                } else {
+                    rd
+                };
                 let op = match rhs.as_ref() {
                     Expr::Id { loc: _, name } => VmInst::OpGetTable(OpGetTable {
                         loc: loc.clone(),
                         rs: lhs,
-                        rd,
+                        rd: get_rd,
                         attr: self.gc.get_or_insert_table_key(name),
+                        bind,
+                        member: bind,
                     }),
                     Expr::Const {
                         loc: _,
@@ -984,8 +2413,15 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                         return Err(ErrorCode::InvalidMember(expr.get_loc()));
                     }
                 };
-
                 self.get_current_insts().push(op);
+                if get_rd != rd {
+                    self.get_current_insts()
+                        .push(VmInst::OpMove(OpMove { rs: get_rd, rd }));
+                    self.registers.free_intermediate(get_rd);
+                }
+                if tmp && bind {
+                    self.registers.free_intermediate(lhs);
+                }
                 Ok((rd, target.is_none()))
             }
             Expr::Infix {
@@ -1016,6 +2452,7 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                     Err(ErrorCode::MetaNotAllowed(loc.clone()))
                 }
             }
+            Expr::Spread { loc, .. } => Err(ErrorCode::SpreadNotAllowed(loc.clone())),
             // prevent use assignment as expression
             Expr::Infix {
                 loc,
@@ -1037,13 +2474,72 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                     inst_offset: self.get_current_insts().len(),
                     loc: loc.clone(),
                 };
+                self.get_current_insts().push(VmInst::OpDummy(OpDummy));
                 self.compile_expr(rhs, false, Some(rd))?;
+                self.get_current_insts()
+                    .push(VmInst::OpAssertBool(OpAssertBool {
+                        loc: rhs.get_loc(),
+                        rs: rd,
+                    }));
                 br_true_to_end.patch_forward(self.get_current_func());
                 if target.is_none() {
                     self.registers.free_intermediate(rd);
                 }
                 Ok((rd, target.is_none()))
             }
+            // f(lhs, ...) for lhs |> f(...), otherwise rhs(lhs)
+            Expr::Infix {
+                loc,
+                op: OpInfix::Pipe,
+                lhs,
+                rhs,
+            } => {
+                let expr = match rhs.as_ref() {
+                    Expr::Call {
+                        lhs: callee,
+                        parameters,
+                        ..
+                    } => Expr::Call {
+                        loc: loc.clone(),
+                        lhs: callee.clone(),
+                        parameters: [lhs.as_ref().clone()]
+                            .into_iter()
+                            .chain(parameters.iter().cloned())
+                            .collect(),
+                    },
+                    callee => Expr::Call {
+                        loc: loc.clone(),
+                        lhs: Box::new(callee.clone()),
+                        parameters: vec![lhs.as_ref().clone()],
+                    },
+                };
+                self.compile_expr(&expr, discard, target)
+            }
+            // lhs unless it is (), then rhs
+            Expr::Infix {
+                loc,
+                op: OpInfix::Coalesce,
+                lhs,
+                rhs,
+            } => {
+                let rd = self.registers.declare_intermediate();
+                self.compile_expr(lhs, false, Some(rd))?;
+                let is_unit = self.registers.declare_intermediate();
+                self.get_current_insts().push(VmInst::OpIsUnit(OpIsUnit {
+                    rs: rd,
+                    rd: is_unit,
+                }));
+                let br_false_to_end = FutureJump {
+                    condition_reg: Some((is_unit, true)),
+                    inst_offset: self.get_current_insts().len(),
+                    loc: loc.clone(),
+                };
+                self.get_current_insts().push(VmInst::OpDummy(OpDummy));
+                self.registers.free_intermediate(is_unit);
+                self.compile_expr(rhs, false, Some(rd))?;
+                br_false_to_end.patch_forward(self.get_current_func());
+                Ok(self.move_to_target(rd, target))
+            }
             // short circuit or
             Expr::Infix {
                 loc,
@@ -1058,7 +2554,13 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                     inst_offset: self.get_current_insts().len(),
                     loc: loc.clone(),
                 };
+                self.get_current_insts().push(VmInst::OpDummy(OpDummy));
                 self.compile_expr(rhs, false, Some(rd))?;
+                self.get_current_insts()
+                    .push(VmInst::OpAssertBool(OpAssertBool {
+                        loc: rhs.get_loc(),
+                        rs: rd,
+                    }));
                 br_true_to_end.patch_forward(self.get_current_func());
                 if target.is_none() {
                     self.registers.free_intermediate(rd);
@@ -1077,20 +2579,48 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                 };
                 Ok(ret)
             }
-            Expr::Id { loc, name } => match self.registers.lookup_variable(name) {
-                Some((id, depth, _)) => {
-                    assert!(depth == 0);
-                    Ok(if let Some(target) = target {
-                        self.get_current_func()
-                            .insts
-                            .push(VmInst::OpMove(OpMove { rs: id, rd: target }));
-                        (target, false)
-                    } else {
-                        (id, false)
-                    })
+            Expr::Id { loc, name } => {
+                let func_id = self.registers.func_id;
+                if self.declaring.contains(&(name.clone(), func_id)) {
+                    let similar = self.registers.similar_name(name).map(str::to_string);
+                    return Err(ErrorCode::UsedBeforeAssigned(
+                        loc.clone(),
+                        name.to_string(),
+                        similar,
+                    ));
                 }
-                None => Err(ErrorCode::NameNotDefined(loc.clone(), name.clone())),
-            },
+                match self.registers.lookup_variable(name) {
+                    Some((id, depth, _)) => {
+                        assert!(depth == 0);
+                        if func_id == 0 && self.gc.is_undefined(id) {
+                            let similar = self.registers.similar_name(name).map(str::to_string);
+                            self.get_current_insts()
+                                .push(VmInst::OpCheckDefined(OpCheckDefined {
+                                    loc: loc.clone(),
+                                    rs: id,
+                                    name: name.to_string(),
+                                    similar,
+                                }));
+                        }
+                        Ok(if let Some(target) = target {
+                            self.get_current_func()
+                                .insts
+                                .push(VmInst::OpMove(OpMove { rs: id, rd: target }));
+                            (target, false)
+                        } else {
+                            (id, false)
+                        })
+                    }
+                    None => {
+                        let similar = self.registers.similar_name(name).map(str::to_string);
+                        Err(ErrorCode::NameNotDefined(
+                            loc.clone(),
+                            name.to_string(),
+                            similar,
+                        ))
+                    }
+                }
+            }
             Expr::Parentheses { loc: _, content } => self.compile_expr(content, discard, target),
             Expr::Const { value, .. } => Ok(self.compile_constant(value, target))?,
             Expr::Error => unreachable!(),
@@ -1228,18 +2758,59 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                     .map(|reg| (reg, target.is_none()))
                     .unwrap_or((usize::MAX, false)))
             }
+            // lhs.f(...) unless lhs is ()
+            Expr::Call {
+                loc,
+                lhs: callee,
+                parameters,
+            } if matches!(
+                callee.as_ref(),
+                Expr::Infix {
+                    op: OpInfix::SafeMember,
+                    ..
+                }
+            ) =>
+            {
+                let Expr::Infix {
+                    loc: member_loc,
+                    lhs,
+                    rhs,
+                    ..
+                } = callee.as_ref()
+                else {
+                    unreachable!()
+                };
+                self.compile_nil_safe(
+                    loc,
+                    lhs,
+                    |receiver| Expr::Call {
+                        loc: loc.clone(),
+                        lhs: Box::new(Expr::Infix {
+                            loc: member_loc.clone(),
+                            op: OpInfix::Member,
+                            lhs: receiver,
+                            rhs: rhs.clone(),
+                        }),
+                        parameters: parameters.clone(),
+                    },
+                    target,
+                )
+            }
             Expr::Call {
                 loc,
                 lhs,
                 parameters,
             } => {
+                // Methods of `super` are called with the receiver passed explicitly
                 let is_member_call = if let Expr::Infix {
                     op: OpInfix::Member,
+                    lhs,
                     rhs,
                     ..
                 } = lhs.as_ref()
                 {
                     matches!(rhs.as_ref(), Expr::Id { .. })
+                        && !matches!(lhs.as_ref(), Expr::Id { name, .. } if is_super(name))
                 } else {
                     false
                 };
@@ -1261,6 +2832,8 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                                 rs: frame_start,
                                 rd: lhs_id,
                                 attr: self.gc.get_or_insert_table_key(name),
+                                bind: false,
+                                member: true,
                             }),
                             _ => unreachable!(),
                         };
@@ -1323,7 +2896,8 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                 body,
             } => {
                 let (func_id, parameters, capture, reg_size) =
-                    self.compile_closure(parameters, body)?;
+                    self.compile_closure(loc, parameters, body)?;
+                self.check_captured_globals(loc, &capture, target);
                 let rd = target.unwrap_or_else(|| self.registers.declare_intermediate());
                 self.get_current_func()
                     .insts
@@ -1341,20 +2915,78 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
     }
 
     fn compile_assignment(&mut self, lhs: &Expr, rhs: &Expr) -> Result<(), ErrorCode> {
+        let parallel = matches!(
+            lhs,
+            Expr::Infix {
+                op: OpInfix::Comma,
+                ..
+            } | Expr::Parentheses { .. }
+        );
+        let chained = matches!(
+            rhs,
+            Expr::Infix {
+                op: OpInfix::Assign,
+                ..
+            }
+        );
+        if parallel || chained {
+            // Value is evaluated before any target is assigned, `a, b = b, a` swaps
+            let mut targets = vec![lhs];
+            let mut value = rhs;
+            while let Expr::Infix {
+                op: OpInfix::Assign,
+                lhs,
+                rhs,
+                ..
+            } = value
+            {
+                targets.push(lhs);
+                value = rhs;
+            }
+            let (rs, tmp) = self.compile_expr(value, false, None)?;
+            for target in targets.into_iter().rev() {
+                self.assign_register(target, rs)?;
+            }
+            if tmp {
+                self.registers.free_intermediate(rs);
+            }
+            return Ok(());
+        }
         match lhs {
             Expr::Id { loc: id_loc, name } => {
                 // declare variable
-                let id = if let Some((id, depth, _)) = self.registers.lookup_variable(name) {
-                    assert!(depth == 0);
-                    id
-                } else {
-                    self.scopes.last_mut().unwrap().insert(name.clone());
-                    self.registers.declare_variable(name, Some(id_loc.clone()))
+                let (id, declared) =
+                    if let Some((id, depth, _)) = self.registers.lookup_variable(name) {
+                        assert!(depth == 0);
+                        (id, false)
+                    } else {
+                        self.scopes.last_mut().unwrap().insert(name.clone());
+                        let id = self.registers.declare_variable(name, Some(id_loc.clone()));
+                        (id, true)
+                    };
+                // In strict mode a new variable can not be read before it is assigned, except
+                // by functions defined in the assignment
+                let strict = declared && self.is_strict_file(id_loc.fid);
+                if strict {
+                    self.declaring.push((name.clone(), self.registers.func_id));
+                }
+                if matches!(rhs, Expr::Fn { .. }) {
+                    self.closure_name = Some(name.clone());
+                }
+                let result = match self.const_globals.get(name) {
+                    Some(value) if self.registers.func_id == 0 => {
+                        self.compile_constant(&value.clone(), Some(id))
+                    }
+                    _ => self.compile_expr(rhs, false, Some(id)),
                 };
-                let (rhs, tmp) = self.compile_expr(rhs, false, Some(id))?;
+                if strict {
+                    self.declaring.pop();
+                }
+                let (rhs, tmp) = result?;
                 if tmp {
                     self.registers.free_intermediate(rhs);
                 }
+                self.define_nested_global(id);
                 Ok(())
             }
             Expr::Index {
@@ -1390,6 +3022,9 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                 loc,
             } => {
                 let (rd_id, rd_tmp) = self.compile_expr(rd, false, None)?;
+                if let (Expr::Fn { .. }, Expr::Id { name, .. }) = (rhs, idx.as_ref()) {
+                    self.closure_name = Some(name.clone());
+                }
                 let (rs_id, rs_tmp) = self.compile_expr(rhs, false, None)?;
                 if rd_tmp {
                     self.registers.free_intermediate(rd_id);
@@ -1429,6 +3064,117 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
         }
     }
 
+    /// Assign value in register `rs` to `target`, a tuple is unpacked to comma separated targets
+    fn assign_register(&mut self, target: &Expr, rs: usize) -> Result<(), ErrorCode> {
+        match target {
+            Expr::Id { loc, name } => {
+                let rd = if let Some((id, depth, _)) = self.registers.lookup_variable(name) {
+                    assert!(depth == 0);
+                    id
+                } else {
+                    self.scopes.last_mut().unwrap().insert(name.clone());
+                    self.registers.declare_variable(name, Some(loc.clone()))
+                };
+                if rd != rs {
+                    self.get_current_insts()
+                        .push(VmInst::OpMove(OpMove { rs, rd }));
+                }
+                self.define_nested_global(rd);
+                Ok(())
+            }
+            Expr::Index { lhs, rhs: idx, loc } => {
+                let (rd, rd_tmp) = self.compile_expr(lhs, false, None)?;
+                let (idx, idx_tmp) = self.compile_expr(idx, false, None)?;
+                if rd_tmp {
+                    self.registers.free_intermediate(rd);
+                }
+                if idx_tmp {
+                    self.registers.free_intermediate(idx);
+                }
+                self.get_current_insts()
+                    .push(VmInst::OpSetIndex(OpSetIndex {
+                        loc: loc.clone(),
+                        rs,
+                        idx,
+                        rd,
+                    }));
+                Ok(())
+            }
+            Expr::Infix {
+                op: OpInfix::Member,
+                lhs,
+                rhs: attr,
+                loc,
+            } => {
+                let (rd, tmp) = self.compile_expr(lhs, false, None)?;
+                if tmp {
+                    self.registers.free_intermediate(rd);
+                }
+                let inst = match attr.as_ref() {
+                    Expr::Id { name, .. } => VmInst::OpSetTable(OpSetTable {
+                        loc: loc.clone(),
+                        rs,
+                        rd,
+                        attr: self.gc.get_or_insert_table_key(name),
+                    }),
+                    Expr::Const {
+                        value: Const::Int(i),
+                        ..
+                    } => VmInst::OpSetTuple(OpSetTuple {
+                        loc: loc.clone(),
+                        rs,
+                        rd,
+                        idx: *i as usize,
+                    }),
+                    expr => return Err(ErrorCode::CannotAssign(expr.get_loc())),
+                };
+                self.get_current_insts().push(inst);
+                Ok(())
+            }
+            Expr::Infix {
+                op: OpInfix::Comma,
+                loc,
+                ..
+            } => {
+                let mut items = vec![];
+                let mut left = target;
+                while let Expr::Infix {
+                    op: OpInfix::Comma,
+                    lhs,
+                    rhs,
+                    ..
+                } = left
+                {
+                    items.push(rhs.as_ref());
+                    left = lhs;
+                }
+                items.push(left);
+                items.reverse();
+                // Unpack every item first so that targets can not overwrite the tuple
+                let elements: Vec<_> = (0..items.len())
+                    .map(|idx| {
+                        let rd = self.registers.declare_intermediate();
+                        self.get_current_insts()
+                            .push(VmInst::OpGetTuple(OpGetTuple {
+                                loc: loc.clone(),
+                                rs,
+                                rd,
+                                idx,
+                            }));
+                        rd
+                    })
+                    .collect();
+                for (item, element) in items.into_iter().zip(elements) {
+                    self.assign_register(item, element)?;
+                    self.registers.free_intermediate(element);
+                }
+                Ok(())
+            }
+            Expr::Parentheses { content, .. } => self.assign_register(content, rs),
+            _ => Err(ErrorCode::CannotAssign(target.get_loc())),
+        }
+    }
+
     fn compile_constant(
         &mut self,
         constant: &Const,
@@ -1455,6 +3201,12 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                 .registers
                 .get_or_alloc_constant(ConstantValue::Bool(*b))
                 .unwrap(),
+            Const::List(list) if list.iter().any(|expr| matches!(expr, Expr::Spread { .. })) => {
+                return self.compile_list_spread(list, target)
+            }
+            Const::Table(pairs) if pairs.iter().any(|(key, _, _)| key.is_none()) => {
+                return self.compile_table_spread(pairs, target)
+            }
             Const::List(list) => {
                 let mut items = vec![];
                 let rd = target.unwrap_or_else(|| self.registers.declare_intermediate());
@@ -1480,11 +3232,14 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                     .insts
                     .push(VmInst::OpMakeTable(OpMakeTable { rd }));
                 for (attr, expr, loc) in pairs.iter() {
+                    if matches!(expr, Expr::Fn { .. }) {
+                        self.closure_name = attr.as_deref().map(Symbol::from);
+                    }
                     let (value, tmp) = self.compile_expr(expr, false, None)?;
                     if tmp {
                         self.registers.free_intermediate(value);
                     }
-                    let attr = self.gc.get_or_insert_table_key(attr);
+                    let attr = self.gc.get_or_insert_table_key(attr.as_ref().unwrap());
                     self.get_current_func()
                         .insts
                         .push(VmInst::OpSetTable(OpSetTable {
@@ -1508,6 +3263,166 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
         }
     }
 
+    /// Compile a list literal with spreads
+    ///
+    /// Items between spreads are made into a list and appended as a whole. The list is built in
+    /// a new register since target may be spread itself, as in `xs = [xs..., 1]`.
+    fn compile_list_spread(
+        &mut self,
+        list: &[Expr],
+        target: Option<usize>,
+    ) -> Result<(usize, bool), ErrorCode> {
+        let rd = self.registers.declare_intermediate();
+        self.get_current_insts()
+            .push(VmInst::OpMakeList(OpMakeList { rd, items: vec![] }));
+        let mut items = vec![];
+        for expr in list {
+            match expr {
+                Expr::Spread { loc, value } => {
+                    self.flush_list_items(rd, &mut items, loc);
+                    let (rs, tmp) = self.compile_expr(value, false, None)?;
+                    self.get_current_insts()
+                        .push(VmInst::OpExtendList(OpExtendList {
+                            loc: loc.clone(),
+                            rs,
+                            rd,
+                        }));
+                    if tmp {
+                        self.registers.free_intermediate(rs);
+                    }
+                }
+                expr => items.push(self.compile_expr(expr, false, None)?),
+            }
+        }
+        if let Some(Expr::Spread { loc, .. }) = list
+            .iter()
+            .rev()
+            .find(|expr| matches!(expr, Expr::Spread { .. }))
+        {
+            self.flush_list_items(rd, &mut items, loc);
+        }
+        Ok(self.move_to_target(rd, target))
+    }
+
+    /// Append items compiled so far to list `rd`
+    fn flush_list_items(&mut self, rd: usize, items: &mut Vec<(usize, bool)>, loc: &Loc) {
+        if items.is_empty() {
+            return;
+        }
+        let rs = self.registers.declare_intermediate();
+        self.get_current_insts()
+            .push(VmInst::OpMakeList(OpMakeList {
+                rd: rs,
+                items: items.iter().map(|(id, _)| *id).collect(),
+            }));
+        self.get_current_insts()
+            .push(VmInst::OpExtendList(OpExtendList {
+                loc: loc.clone(),
+                rs,
+                rd,
+            }));
+        self.registers.free_intermediate(rs);
+        items.drain(..).for_each(|(id, tmp)| {
+            if tmp {
+                self.registers.free_intermediate(id);
+            }
+        });
+    }
+
+    /// Compile a table literal with spreads, later entries override earlier ones
+    ///
+    /// The table is built in a new register since target may be spread itself.
+    fn compile_table_spread(
+        &mut self,
+        pairs: &[(Option<String>, Expr, Loc)],
+        target: Option<usize>,
+    ) -> Result<(usize, bool), ErrorCode> {
+        let rd = self.registers.declare_intermediate();
+        self.get_current_insts()
+            .push(VmInst::OpMakeTable(OpMakeTable { rd }));
+        for (attr, expr, loc) in pairs.iter() {
+            let (rs, tmp) = match (attr, expr) {
+                (None, Expr::Spread { value, .. }) => self.compile_expr(value, false, None)?,
+                _ => self.compile_expr(expr, false, None)?,
+            };
+            let inst = match attr {
+                Some(attr) => VmInst::OpSetTable(OpSetTable {
+                    loc: loc.clone(),
+                    rs,
+                    rd,
+                    attr: self.gc.get_or_insert_table_key(attr),
+                }),
+                None => VmInst::OpExtendTable(OpExtendTable {
+                    loc: loc.clone(),
+                    rs,
+                    rd,
+                }),
+            };
+            self.get_current_insts().push(inst);
+            if tmp {
+                self.registers.free_intermediate(rs);
+            }
+        }
+        Ok(self.move_to_target(rd, target))
+    }
+
+    /// Move intermediate `rs` to target if there is one
+    /// Compile `access` of the value of `receiver`, which is skipped if the value is `()`
+    ///
+    /// The value is kept in a hidden variable, `access` is given an expression reading it.
+    fn compile_nil_safe(
+        &mut self,
+        loc: &Loc,
+        receiver: &Expr,
+        access: impl FnOnce(Box<Expr>) -> Expr,
+        target: Option<usize>,
+    ) -> Result<(usize, bool), ErrorCode> {
+        let sym = Expr::Id {
+            loc: receiver.get_loc(),
+            name: self.registers.gen_sym(),
+        };
+        // sym = receiver
+        let init = Stmt::Expr {
+            loc: loc.clone(),
+            expr: Expr::Infix {
+                loc: loc.clone(),
+                op: OpInfix::Assign,
+                lhs: Box::new(sym.clone()),
+                rhs: Box::new(receiver.clone()),
+            },
+        };
+        self.compile_stmt(&init, true, None)?;
+        let (rs, _) = self.compile_expr(&sym, false, None)?;
+        let rd = self.registers.declare_intermediate();
+        self.get_current_insts()
+            .push(VmInst::OpMove(OpMove { rs, rd }));
+        let is_unit = self.registers.declare_intermediate();
+        self.get_current_insts()
+            .push(VmInst::OpIsUnit(OpIsUnit { rs, rd: is_unit }));
+        let br_true_to_end = FutureJump {
+            condition_reg: Some((is_unit, false)),
+            inst_offset: self.get_current_insts().len(),
+            loc: loc.clone(),
+        };
+        self.get_current_insts().push(VmInst::OpDummy(OpDummy));
+        self.registers.free_intermediate(is_unit);
+        self.compile_expr(&access(Box::new(sym)), false, Some(rd))?;
+        br_true_to_end.patch_forward(self.get_current_func());
+        Ok(self.move_to_target(rd, target))
+    }
+
+    fn move_to_target(&mut self, rs: usize, target: Option<usize>) -> (usize, bool) {
+        match target {
+            Some(rd) => {
+                self.get_current_insts()
+                    .push(VmInst::OpMove(OpMove { rs, rd }));
+                self.registers.free_intermediate(rs);
+                (rd, false)
+            }
+            None => (rs, true),
+        }
+    }
+
     fn compile_infix(
         &mut self,
         op: &OpInfix,
@@ -1521,10 +3436,21 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                 let rd = target.unwrap_or_else(|| self.registers.declare_intermediate());
                 self.get_current_func()
                     .insts
-                    .push(VmInst::OpIs(OpIs { loc, lhs, rhs, rd }));
+                    .push(VmInst::OpIs(OpIs { lhs, rhs, rd }));
+                (rd, target.is_none())
+            }
+            OpInfix::In | OpInfix::NotIn => {
+                let rd = target.unwrap_or_else(|| self.registers.declare_intermediate());
+                self.get_current_func().insts.push(VmInst::OpIn(OpIn {
+                    loc,
+                    lhs,
+                    rhs,
+                    rd,
+                    negate: *op == OpInfix::NotIn,
+                }));
                 (rd, target.is_none())
             }
-            OpInfix::Or | OpInfix::And => unreachable!(),
+            OpInfix::Or | OpInfix::And | OpInfix::Coalesce => unreachable!(),
             OpInfix::Eq => {
                 let rd = target.unwrap_or_else(|| self.registers.declare_intermediate());
                 self.get_current_func()
@@ -1617,9 +3543,12 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
                 (rd, target.is_none())
             }
             OpInfix::Assign
+            | OpInfix::Pipe
             | OpInfix::Range
             | OpInfix::Comma
             | OpInfix::Member
+            | OpInfix::SafeMember
+            | OpInfix::SafeIndex
             | OpInfix::DoubleColon
             | OpInfix::LArrow => {
                 unreachable!()
@@ -1655,15 +3584,30 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
     /// Return (func_id, parameters len, captured_regs, reg_size)
     fn compile_closure(
         &mut self,
-        parameters: &[(String, Loc)],
+        loc: &Loc,
+        parameters: &[(Symbol, Loc)],
         body: &Expr,
-    ) -> std::result::Result<(usize, usize, Vec<Capture>, usize), ErrorCode> {
+    ) -> core::result::Result<(usize, usize, Vec<Capture>, usize), ErrorCode> {
         let func_id = self.byte_code.len();
-        self.byte_code.push(Func {
+        self.byte_code.push(Arc::new(Func {
             id: func_id,
             parameters: parameters.len(),
             insts: vec![],
-        });
+        }));
+        let location = self
+            .file_manager
+            .locate(loc)
+            .map(|(path, line, column)| SourceLocation { path, line, column });
+        let info = FunctionInfo {
+            name: self.closure_name.take().map(|name| name.to_string()),
+            arity: parameters.len(),
+            parameters: parameters
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            location,
+        };
+        self.gc.set_function_info(func_id, info);
         self.registers.enter_function(func_id);
         for (para, loc) in parameters.iter() {
             self.registers.declare_variable(para, Some(loc.clone()));
@@ -1674,7 +3618,7 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
         let mut const_scanner = ConstScanner {
             register_table: &mut self.registers,
             gc: &mut self.gc,
-            insts: &mut self.byte_code[func_id].insts,
+            insts: &mut Arc::make_mut(&mut self.byte_code[func_id]).insts,
         };
         const_scanner.scan_expr(body);
 
@@ -1682,8 +3626,8 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
         let mut capture_scanner = CaptureScanner {
             register_table: &mut self.registers,
             gc: &mut self.gc,
-            insts: &mut self.byte_code[func_id].insts,
-            overridden: AHashMap::new(),
+            insts: &mut Arc::make_mut(&mut self.byte_code[func_id]).insts,
+            overridden: AHashMap::default(),
         };
         capture_scanner.scan_expr(body);
 
@@ -1716,16 +3660,16 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
 
     fn get_current_func(&mut self) -> &mut Func {
         let id = self.registers.func_id;
-        &mut self.byte_code[id]
+        Arc::make_mut(&mut self.byte_code[id])
     }
 
     fn get_current_insts(&mut self) -> &mut Vec<VmInst> {
         let id = self.registers.func_id;
-        &mut self.byte_code[id].insts
+        &mut Arc::make_mut(&mut self.byte_code[id]).insts
     }
 
     fn enter_block(&mut self) {
-        self.scopes.push(AHashSet::new());
+        self.scopes.push(AHashSet::default());
     }
 
     fn leave_block(&mut self) {
@@ -1736,5 +3680,46 @@ impl<Buffer: IoWrite, LibCore: StdCore> Interpreter<Buffer, LibCore> {
     }
 }
 
+/// Whether code defines functions, directly or in nested expressions
+fn defines_function(ast: &[Stmt]) -> bool {
+    #[derive(Default)]
+    struct Finder(bool);
+    impl Visitor for Finder {
+        fn visit_stmt(&mut self, stmt: &Stmt) {
+            self.0 |= matches!(stmt, Stmt::Def { .. });
+            walk_stmt(self, stmt);
+        }
+
+        fn visit_expr(&mut self, expr: &Expr) {
+            self.0 |= matches!(expr, Expr::Fn { .. });
+            walk_expr(self, expr);
+        }
+    }
+    let mut finder = Finder::default();
+    ast.iter().for_each(|stmt| finder.visit_stmt(stmt));
+    finder.0
+}
+
+/// Check lint rules on file `fid`, rules set to `LintLevel::Deny` are reported as errors
+fn add_lint_warnings(file_manager: &mut FileManager, fid: usize, config: &LintConfig) {
+    for warning in lint(&file_manager.get_ast(fid), config) {
+        if warning.diagnostic.severity >= Severity::Error {
+            file_manager.add_diagnostic(warning.diagnostic, false);
+        } else {
+            file_manager.add_warning(warning);
+        }
+    }
+}
+
+/// Whether a file opts into strict mode by a line `--! strict` before any code
+fn has_strict_pragma(source: &str) -> bool {
+    source
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.starts_with("#!"))
+        .take_while(|line| line.is_empty() || line.starts_with("--"))
+        .any(|line| line == "--! strict")
+}
+
 #[cfg(test)]
 mod tests;
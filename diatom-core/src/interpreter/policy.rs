@@ -0,0 +1,154 @@
+use alloc::string::String;
+use core::time::Duration;
+
+use crate::compat::AHashSet;
+
+use crate::host::HAS_STD_CLOCK;
+
+/// Access to the file system
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum FsPolicy {
+    /// No access at all
+    #[default]
+    Deny,
+    /// Read files and list directories only
+    ReadOnly,
+    /// Full access
+    ReadWrite,
+}
+
+impl FsPolicy {
+    pub fn can_read(&self) -> bool {
+        !matches!(self, FsPolicy::Deny)
+    }
+
+    pub fn can_write(&self) -> bool {
+        matches!(self, FsPolicy::ReadWrite)
+    }
+}
+
+/// What scripts are allowed to do
+///
+/// Privileged standard library modules consult the policy before touching the outside world,
+/// e.g. `std.os.process` refuses to run unless `process` is allowed. Capabilities other than the
+/// built-in ones can be granted by name for host defined modules.
+///
+/// The default policy only allows reading the clock and `eval`, which is the same as
+/// `SandboxPolicy::new()`. On platforms without a clock such as `wasm32-unknown-unknown`, `time` is never allowed.
+#[derive(Clone, Debug)]
+pub struct SandboxPolicy {
+    pub fs: FsPolicy,
+    pub net: bool,
+    pub process: bool,
+    /// Read arguments of scripts by `std.os.env`
+    pub env: bool,
+    pub time: bool,
+    /// Run code given as a string by `eval`, which is always run under the same policy
+    pub eval: bool,
+    /// Maximum number of live heap allocations (strings, objects and captured variables)
+    pub max_allocations: Option<usize>,
+    /// Maximum number of instructions executed by each call to `exec` or `call`
+    pub max_fuel: Option<usize>,
+    /// Maximum wall clock time of each call to `exec` or `call`
+    ///
+    /// Time spent in a blocking external function can not be interrupted. Ignored if there is no
+    /// clock, see `Interpreter::set_clock`.
+    pub timeout: Option<Duration>,
+    /// Capabilities defined by host
+    custom: AHashSet<String>,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SandboxPolicy {
+    /// Only allow reading the clock and `eval`
+    pub fn new() -> Self {
+        Self {
+            time: HAS_STD_CLOCK,
+            eval: true,
+            ..Self::deny_all()
+        }
+    }
+
+    /// Deny everything
+    pub fn deny_all() -> Self {
+        Self {
+            fs: FsPolicy::Deny,
+            net: false,
+            process: false,
+            env: false,
+            time: false,
+            eval: false,
+            max_allocations: None,
+            max_fuel: None,
+            timeout: None,
+            custom: AHashSet::default(),
+        }
+    }
+
+    /// Allow everything without resource limits
+    pub fn allow_all() -> Self {
+        Self {
+            fs: FsPolicy::ReadWrite,
+            net: true,
+            process: true,
+            env: true,
+            time: HAS_STD_CLOCK,
+            eval: true,
+            ..Self::deny_all()
+        }
+    }
+
+    /// Allow a capability by name
+    ///
+    /// Built-in names are `fs`, `net`, `process`, `env`, `time` and `eval`. Granting `fs` allows both
+    /// reading and writing.
+    pub fn grant(&mut self, capability: impl Into<String>) -> &mut Self {
+        let capability = capability.into();
+        match capability.as_str() {
+            "fs" => self.fs = FsPolicy::ReadWrite,
+            "net" => self.net = true,
+            "process" => self.process = true,
+            "env" => self.env = true,
+            "time" => self.time = HAS_STD_CLOCK,
+            "eval" => self.eval = true,
+            _ => {
+                self.custom.insert(capability);
+            }
+        }
+        self
+    }
+
+    /// Deny a capability by name
+    pub fn revoke(&mut self, capability: impl AsRef<str>) -> &mut Self {
+        match capability.as_ref() {
+            "fs" => self.fs = FsPolicy::Deny,
+            "net" => self.net = false,
+            "process" => self.process = false,
+            "env" => self.env = false,
+            "time" => self.time = false,
+            "eval" => self.eval = false,
+            capability => {
+                self.custom.remove(capability);
+            }
+        }
+        self
+    }
+
+    /// Check if a capability is allowed by name
+    pub fn allows(&self, capability: impl AsRef<str>) -> bool {
+        match capability.as_ref() {
+            "fs" => self.fs.can_read(),
+            "net" => self.net,
+            "process" => self.process,
+            "env" => self.env,
+            "time" => self.time,
+            "eval" => self.eval,
+            capability => self.custom.contains(capability),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use super::*;
+use alloc::{string::String, vec, vec::Vec};
+
+/// Globals, heap and byte code of an interpreter, without its output buffer
+///
+/// Copying a snapshot shares byte code and pages of the heap until either copy writes to them,
+/// thus it is much cheaper than loading the standard library and compiling code again.
+pub(super) struct Snapshot<Buffer: IoWrite> {
+    registers: RegisterTable,
+    scopes: Vec<AHashSet<Symbol>>,
+    byte_code: Vec<Arc<Func>>,
+    gc: Gc<Buffer>,
+    file_manager: FileManager,
+    render_options: RenderOptions,
+    search_path: Vec<PathBuf>,
+    packages: Vec<(String, PathBuf)>,
+    keyword_aliases: KeywordAliases,
+    strict: bool,
+}
+
+impl<Buffer: IoWrite> Snapshot<Buffer> {
+    pub(super) fn of<LibCore: StdCore>(interpreter: &Interpreter<Buffer, LibCore>) -> Self {
+        Self {
+            registers: interpreter.registers.clone(),
+            scopes: interpreter.scopes.clone(),
+            byte_code: interpreter.byte_code.clone(),
+            gc: interpreter.gc.fork(),
+            file_manager: interpreter.file_manager.clone(),
+            render_options: interpreter.render_options.clone(),
+            search_path: interpreter.search_path.clone(),
+            packages: interpreter.packages.clone(),
+            keyword_aliases: interpreter.keyword_aliases.clone(),
+            strict: interpreter.strict,
+        }
+    }
+
+    pub(super) fn into_interpreter<LibCore: StdCore>(
+        self,
+        buffer: Buffer,
+    ) -> Interpreter<Buffer, LibCore> {
+        Interpreter {
+            registers: self.registers,
+            scopes: self.scopes,
+            byte_code: self.byte_code,
+            vm: Vm::new(),
+            gc: self.gc,
+            out: buffer,
+            file_manager: self.file_manager,
+            render_options: self.render_options,
+            repl: false,
+            search_path: self.search_path,
+            packages: self.packages,
+            keyword_aliases: self.keyword_aliases,
+            strict: self.strict,
+            lint_config: None,
+            declaring: vec![],
+            closure_name: None,
+            const_globals: ConstFolder::default(),
+            last_error: None,
+            last_report: None,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<Buffer: IoWrite> Clone for Snapshot<Buffer> {
+    fn clone(&self) -> Self {
+        Self {
+            registers: self.registers.clone(),
+            scopes: self.scopes.clone(),
+            byte_code: self.byte_code.clone(),
+            gc: self.gc.fork(),
+            file_manager: self.file_manager.clone(),
+            render_options: self.render_options.clone(),
+            search_path: self.search_path.clone(),
+            packages: self.packages.clone(),
+            keyword_aliases: self.keyword_aliases.clone(),
+            strict: self.strict,
+        }
+    }
+}
+
+/// Compiled code that can be executed by many independent interpreters
+///
+/// Created by `Interpreter::compile_program`. A program is a snapshot of the interpreter right
+/// after compilation, including byte code, globals and the heap. Each interpreter spawned from
+/// it has its own globals and heap, which share byte code and unchanged parts of the heap with
+/// the program.
+///
+/// Host states set by `Interpreter::set_context` and input source are not part of a program,
+/// user data is copied as described by `UserTypeBuilder::cloneable`. Clone the program to use it
+/// in another thread.
+pub struct Program<Buffer: IoWrite, LibCore: StdCore> {
+    snapshot: Snapshot<Buffer>,
+    marker: PhantomData<LibCore>,
+}
+
+impl<Buffer: IoWrite, LibCore: StdCore> Program<Buffer, LibCore> {
+    pub(super) fn new(interpreter: &Interpreter<Buffer, LibCore>) -> Self {
+        Self {
+            snapshot: Snapshot::of(interpreter),
+            marker: PhantomData,
+        }
+    }
+
+    /// Create an interpreter ready to run this program with `Interpreter::run`
+    pub fn spawn(&self, buffer: Buffer) -> Interpreter<Buffer, LibCore> {
+        self.snapshot.clone().into_interpreter(buffer)
+    }
+}
+
+impl<Buffer: IoWrite, LibCore: StdCore> Clone for Program<Buffer, LibCore> {
+    fn clone(&self) -> Self {
+        Self {
+            snapshot: self.snapshot.clone(),
+            marker: PhantomData,
+        }
+    }
+}
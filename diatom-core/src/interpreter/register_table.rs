@@ -1,6 +1,10 @@
-use ahash::AHashMap;
+use crate::compat::AHashMap;
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
 
-use crate::file_manager::Loc;
+use crate::{
+    file_manager::Loc,
+    frontend::{closest_name, Symbol},
+};
 
 use super::FutureJump;
 
@@ -30,7 +34,7 @@ pub struct Loop {
 pub struct RegisterTable {
     pub prev: Option<Box<RegisterTable>>,
     /// name, location
-    pub variables: AHashMap<String, (usize, Option<Loc>)>,
+    pub variables: AHashMap<Symbol, (usize, Option<Loc>)>,
     free: Vec<usize>,
     pub assigned: usize,
     pub func_id: usize,
@@ -39,6 +43,8 @@ pub struct RegisterTable {
     pub capture: Vec<Capture>,
     pub loops: Vec<Loop>,
     pub symbols: usize,
+    /// Variables of enclosing functions are invisible, used by modules
+    pub isolated: bool,
 }
 
 impl RegisterTable {
@@ -49,18 +55,19 @@ impl RegisterTable {
             free: vec![],
             assigned: 1,
             func_id,
-            constant_table: AHashMap::from([(ConstantValue::Unit, 0)]),
+            constant_table: AHashMap::from_iter([(ConstantValue::Unit, 0)]),
             capture: vec![],
             loops: vec![],
             symbols: 0,
+            isolated: false,
         }
     }
 
     /// Generate an unique symbol
-    pub fn gen_sym(&mut self) -> String {
+    pub fn gen_sym(&mut self) -> Symbol {
         let sym = format!("#G{:0>3}", self.symbols);
         self.symbols += 1;
-        sym
+        sym.into()
     }
 
     /// prepare n free register for function call
@@ -70,24 +77,39 @@ impl RegisterTable {
         start
     }
 
-    pub fn declare_variable(&mut self, name: impl AsRef<str>, loc: Option<Loc>) -> usize {
+    pub fn declare_variable(
+        &mut self,
+        name: impl AsRef<str> + Into<Symbol>,
+        loc: Option<Loc>,
+    ) -> usize {
         if let Some(id) = self.variables.get_mut(name.as_ref()) {
             id.1 = loc;
             return id.0;
         }
-        let id = self.declare_intermediate();
-        self.variables.insert(name.as_ref().to_string(), (id, loc));
+        // Registers of globals are never those of intermediates, so that event hooks tell writes
+        // to globals from those to intermediates by register
+        let id = if self.prev.is_none() {
+            self.assigned += 1;
+            self.assigned - 1
+        } else {
+            self.declare_intermediate()
+        };
+        self.variables.insert(name.into(), (id, loc));
         id
     }
 
     /// Declare a captured variable
     ///
     /// This function alloc a new register instead of recycle old one
-    pub fn declare_captured_variable(&mut self, name: impl AsRef<str>, loc: Option<Loc>) -> usize {
+    pub fn declare_captured_variable(
+        &mut self,
+        name: impl AsRef<str> + Into<Symbol>,
+        loc: Option<Loc>,
+    ) -> usize {
         assert!(self.variables.get(name.as_ref()).is_none());
         let id = self.assigned;
         self.assigned += 1;
-        self.variables.insert(name.as_ref().to_string(), (id, loc));
+        self.variables.insert(name.into(), (id, loc));
         id
     }
     /// (reg_id, depth, loc)
@@ -96,8 +118,8 @@ impl RegisterTable {
         match var {
             Some((id, loc)) => Some((*id, depth, loc.clone())),
             None => match &self.prev {
-                Some(prev) => prev.lookup_variable_(name, depth + 1),
-                None => None,
+                Some(prev) if !self.isolated => prev.lookup_variable_(name, depth + 1),
+                _ => None,
             },
         }
     }
@@ -107,6 +129,25 @@ impl RegisterTable {
         self.lookup_variable_(name.as_ref(), 0)
     }
 
+    /// Visible name closest to `name` in spelling, if it is close enough to be a typo
+    pub fn similar_name(&self, name: &str) -> Option<&str> {
+        let mut tables = vec![];
+        let mut table = Some(self);
+        while let Some(current) = table {
+            tables.push(current);
+            table = match &current.prev {
+                Some(prev) if !current.isolated => Some(prev),
+                _ => None,
+            };
+        }
+        closest_name(
+            name,
+            tables
+                .into_iter()
+                .flat_map(|table| table.variables.keys().map(Symbol::as_str)),
+        )
+    }
+
     pub fn declare_intermediate(&mut self) -> usize {
         self.free.pop().unwrap_or_else(|| {
             self.assigned += 1;
@@ -132,13 +173,15 @@ impl RegisterTable {
     }
 
     pub fn enter_function(&mut self, func_id: usize) {
-        let old = std::mem::replace(self, RegisterTable::new(func_id));
+        let old = core::mem::replace(self, RegisterTable::new(func_id));
+        // Symbols of enclosing functions are visible, so continue their numbering
+        self.symbols = old.symbols;
         self.prev = Some(Box::new(old));
     }
 
     pub fn leave_function(&mut self) -> Vec<Capture> {
-        let prev = *std::mem::take(&mut self.prev).unwrap();
-        let pop = std::mem::replace(self, prev);
+        let prev = *core::mem::take(&mut self.prev).unwrap();
+        let pop = core::mem::replace(self, prev);
         pop.capture
     }
 }
@@ -1,6 +1,10 @@
-use ahash::AHashMap;
+use crate::compat::AHashMap;
+use alloc::vec::Vec;
 
-use crate::{frontend::parser::ast::ImportItem, interpreter::Capture};
+use crate::{
+    frontend::{parser::ast::ImportItem, Symbol},
+    interpreter::Capture,
+};
 
 use super::*;
 
@@ -9,7 +13,7 @@ pub struct CaptureScanner<'a, Buffer: IoWrite> {
     pub register_table: &'a mut RegisterTable,
     pub gc: &'a mut Gc<Buffer>,
     pub insts: &'a mut Vec<VmInst>,
-    pub overridden: AHashMap<String, usize>,
+    pub overridden: AHashMap<Symbol, usize>,
 }
 
 impl<'a, Buffer: IoWrite> CaptureScanner<'a, Buffer> {
@@ -46,7 +50,7 @@ impl<'a, Buffer: IoWrite> CaptureScanner<'a, Buffer> {
                     stmts.iter().for_each(|stmt| self.scan_stmt(stmt))
                 }
             }),
-            Expr::Prefix { rhs, .. } => self.scan_expr(rhs),
+            Expr::Prefix { rhs, .. } | Expr::Spread { value: rhs, .. } => self.scan_expr(rhs),
             Expr::Call {
                 lhs, parameters, ..
             } => {
@@ -64,14 +68,14 @@ impl<'a, Buffer: IoWrite> CaptureScanner<'a, Buffer> {
                         end: 0,
                         fid: usize::MAX,
                     },
-                    name: "Range".to_string(),
+                    name: "Range".into(),
                 });
                 self.scan_expr(lhs);
             }
             // Member rhs can not have legal constant values
             Expr::Infix {
                 lhs,
-                op: OpInfix::Member | OpInfix::DoubleColon,
+                op: OpInfix::Member | OpInfix::SafeMember | OpInfix::DoubleColon,
                 ..
             } => {
                 self.scan_expr(lhs);
@@ -85,7 +89,7 @@ impl<'a, Buffer: IoWrite> CaptureScanner<'a, Buffer> {
                             end: 0,
                             fid: usize::MAX,
                         },
-                        name: "Range".to_string(),
+                        name: "Range".into(),
                     });
                 }
                 self.scan_expr(lhs);
@@ -132,6 +136,7 @@ impl<'a, Buffer: IoWrite> CaptureScanner<'a, Buffer> {
                     self.scan_expr(expr)
                 }
             }
+            Stmt::Defer { body, .. } => self.scan_expr(body),
             Stmt::Loop {
                 condition, body, ..
             } => {
@@ -148,18 +153,22 @@ impl<'a, Buffer: IoWrite> CaptureScanner<'a, Buffer> {
                         end: 0,
                         fid: usize::MAX,
                     },
-                    name: "Option".to_string(),
+                    name: "Option".into(),
                 });
                 self.scan_expr(iterator);
                 body.iter().for_each(|stmt| self.scan_stmt(stmt));
             }
             Stmt::Def {
+                decorators,
                 variable,
                 parameters,
                 body,
                 ..
             } => {
                 self.scan_expr(variable);
+                decorators
+                    .iter()
+                    .for_each(|decorator| self.scan_expr(decorator));
 
                 // Parameters will override upper scope variables
                 parameters.iter().for_each(|(name, _)| {
@@ -180,6 +189,18 @@ impl<'a, Buffer: IoWrite> CaptureScanner<'a, Buffer> {
                     }
                 })
             }
+            Stmt::Enum {
+                loc,
+                name,
+                variants,
+                ..
+            } => lower_enum(loc, name, variants, false)
+                .iter()
+                .for_each(|stmt| self.scan_stmt(stmt)),
+            Stmt::Interface {
+                loc, name, methods, ..
+            } => self.scan_stmt(&lower_interface(loc, name, methods)),
+            Stmt::Macro { .. } => (),
             Stmt::Import { items, .. } => {
                 items.iter().for_each(|ImportItem { alias, path, .. }| {
                     let name = if let Some(alias) = alias {
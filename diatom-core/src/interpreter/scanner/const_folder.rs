@@ -0,0 +1,232 @@
+use crate::compat::{float::powf, AHashMap, AHashSet};
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+
+use crate::frontend::parser::{
+    ast::{ImportItem, OpPrefix},
+    visit::{walk_expr, walk_stmt, Visitor},
+};
+
+use super::*;
+
+/// Evaluate initializers of globals made of constants at compile time
+///
+/// A global is folded if it is assigned only once in the whole file, at top level, by an
+/// expression of literals, operators and other folded globals. Operations that could fail
+/// at runtime are left to the VM, so that their error is reported as usual.
+#[derive(Default)]
+pub struct ConstFolder {
+    values: AHashMap<String, Const>,
+}
+
+impl ConstFolder {
+    pub fn new(ast: &[Stmt]) -> Self {
+        let mut counter = AssignmentCounter::default();
+        ast.iter().for_each(|stmt| counter.visit_stmt(stmt));
+        let mut folder = Self::default();
+        for stmt in ast {
+            if let Stmt::Expr {
+                expr:
+                    Expr::Infix {
+                        op: OpInfix::Assign,
+                        lhs,
+                        rhs,
+                        ..
+                    },
+                ..
+            } = stmt
+            {
+                let Expr::Id { name, .. } = lhs.as_ref() else {
+                    continue;
+                };
+                if counter.once.contains(name.as_str()) && !counter.more.contains(name.as_str()) {
+                    if let Some(value) = folder.eval(rhs) {
+                        folder.values.insert(name.to_string(), value);
+                    }
+                }
+            }
+        }
+        folder
+    }
+
+    /// Folded value of global `name`
+    pub fn get(&self, name: &str) -> Option<&Const> {
+        self.values.get(name)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Const> {
+        self.values.values()
+    }
+
+    /// Whether `stmt` is the initializer of a folded global
+    pub fn folds(&self, stmt: &Stmt) -> bool {
+        match stmt {
+            Stmt::Expr {
+                expr:
+                    Expr::Infix {
+                        op: OpInfix::Assign,
+                        lhs,
+                        ..
+                    },
+                ..
+            } => {
+                matches!(lhs.as_ref(), Expr::Id { name, .. } if self.values.contains_key(name.as_str()))
+            }
+            _ => false,
+        }
+    }
+
+    /// Value of `expr` made of literals and operators only, e.g. `1 > 2`, used by lints
+    pub fn eval_literal(expr: &Expr) -> Option<Const> {
+        Self::default().eval(expr)
+    }
+
+    /// Value of `expr`, `None` if it is not constant or evaluating it may fail
+    fn eval(&self, expr: &Expr) -> Option<Const> {
+        match expr {
+            Expr::Const { value, .. } => match value {
+                Const::List(_) | Const::Table(_) => None,
+                value => Some(value.clone()),
+            },
+            Expr::Parentheses { content, .. } => self.eval(content),
+            Expr::Id { name, .. } => self.values.get(name.as_str()).cloned(),
+            Expr::Prefix { op, rhs, .. } => match (op, self.eval(rhs)?) {
+                (OpPrefix::Neg, Const::Int(i)) => i.checked_neg().map(Const::Int),
+                (OpPrefix::Neg, Const::Float(f)) => Some(Const::Float(-f)),
+                (OpPrefix::Not, Const::Bool(b)) => Some(Const::Bool(!b)),
+                _ => None,
+            },
+            Expr::Infix { op, lhs, rhs, .. } => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                eval_infix(*op, lhs, rhs)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Same as instructions of the VM, for operands they accept
+fn eval_infix(op: OpInfix, lhs: Const, rhs: Const) -> Option<Const> {
+    use Const::*;
+    let float = |lhs: &Const, rhs: &Const| match (lhs, rhs) {
+        (Int(i1), Int(i2)) => Some((*i1 as f64, *i2 as f64)),
+        (Int(i1), Float(f2)) => Some((*i1 as f64, *f2)),
+        (Float(f1), Int(i2)) => Some((*f1, *i2 as f64)),
+        (Float(f1), Float(f2)) => Some((*f1, *f2)),
+        _ => None,
+    };
+    let value = match (op, &lhs, &rhs) {
+        (OpInfix::Plus, Int(i1), Int(i2)) => Int(i1.wrapping_add(*i2)),
+        (OpInfix::Plus, Str(s1), Str(s2)) => Str(format!("{s1}{s2}")),
+        (OpInfix::Minus, Int(i1), Int(i2)) => Int(i1.wrapping_sub(*i2)),
+        (OpInfix::Mul, Int(i1), Int(i2)) => Int(i1.wrapping_mul(*i2)),
+        (OpInfix::Rem, Int(i1), Int(i2)) => Int(i1.checked_rem(*i2)?),
+        (OpInfix::Plus, ..) => float(&lhs, &rhs).map(|(f1, f2)| Float(f1 + f2))?,
+        (OpInfix::Minus, ..) => float(&lhs, &rhs).map(|(f1, f2)| Float(f1 - f2))?,
+        (OpInfix::Mul, ..) => float(&lhs, &rhs).map(|(f1, f2)| Float(f1 * f2))?,
+        (OpInfix::Div, ..) => float(&lhs, &rhs).map(|(f1, f2)| Float(f1 / f2))?,
+        (OpInfix::Exp, ..) => float(&lhs, &rhs).map(|(f1, f2)| Float(powf(f1, f2)))?,
+        (OpInfix::And, Bool(b1), Bool(b2)) => Bool(*b1 && *b2),
+        (OpInfix::Or, Bool(b1), Bool(b2)) => Bool(*b1 || *b2),
+        (OpInfix::Eq | OpInfix::Ne, ..) => {
+            let eq = match (&lhs, &rhs) {
+                (Unit, Unit) => true,
+                (Int(i1), Int(i2)) => i1 == i2,
+                (Bool(b1), Bool(b2)) => b1 == b2,
+                (Str(s1), Str(s2)) => s1 == s2,
+                _ => return None,
+            };
+            Bool(eq == (op == OpInfix::Eq))
+        }
+        (OpInfix::Lt | OpInfix::Gt | OpInfix::Le | OpInfix::Ge, ..) => {
+            let ordering = match (&lhs, &rhs) {
+                (Unit, Unit) => core::cmp::Ordering::Equal,
+                (Int(i1), Int(i2)) => i1.cmp(i2),
+                (Bool(b1), Bool(b2)) => b1.cmp(b2),
+                (Str(s1), Str(s2)) => s1.cmp(s2),
+                // Only `<` and `>` compare floats
+                _ if matches!(op, OpInfix::Lt | OpInfix::Gt) => {
+                    let (f1, f2) = float(&lhs, &rhs)?;
+                    f1.partial_cmp(&f2)?
+                }
+                _ => return None,
+            };
+            Bool(match op {
+                OpInfix::Lt => ordering.is_lt(),
+                OpInfix::Gt => ordering.is_gt(),
+                OpInfix::Le => ordering.is_le(),
+                _ => ordering.is_ge(),
+            })
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Names assigned in a file, anywhere including functions
+#[derive(Default)]
+struct AssignmentCounter {
+    once: AHashSet<String>,
+    more: AHashSet<String>,
+}
+
+impl AssignmentCounter {
+    fn assign(&mut self, name: &str) {
+        if !self.once.insert(name.to_string()) {
+            self.more.insert(name.to_string());
+        }
+    }
+
+    fn assign_target(&mut self, target: &Expr) {
+        match target {
+            Expr::Id { name, .. } => self.assign(name),
+            Expr::Infix {
+                op: OpInfix::Comma,
+                lhs,
+                rhs,
+                ..
+            } => {
+                self.assign_target(lhs);
+                self.assign_target(rhs);
+            }
+            Expr::Parentheses { content, .. } => self.assign_target(content),
+            _ => (),
+        }
+    }
+}
+
+impl Visitor for AssignmentCounter {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Def { variable, .. } => self.assign_target(variable),
+            Stmt::Enum { name, .. } | Stmt::Interface { name, .. } => self.assign(name),
+            // Loop variable is assigned for every item
+            Stmt::For { loop_variable, .. } => {
+                self.assign_target(loop_variable);
+                self.assign_target(loop_variable);
+            }
+            Stmt::Import { items, .. } => {
+                items.iter().for_each(|ImportItem { alias, path, .. }| {
+                    self.assign(alias.as_ref().unwrap_or_else(|| path.last().unwrap()))
+                })
+            }
+            _ => (),
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::Infix {
+            op: OpInfix::Assign,
+            lhs,
+            ..
+        } = expr
+        {
+            self.assign_target(lhs);
+        }
+        walk_expr(self, expr);
+    }
+}
@@ -1,4 +1,5 @@
 use super::*;
+use alloc::vec::Vec;
 
 /// Scan and declare all constant before compile closure
 pub struct ConstScanner<'a, Buffer: IoWrite> {
@@ -22,7 +23,7 @@ impl<'a, Buffer: IoWrite> ConstScanner<'a, Buffer> {
                     stmts.iter().for_each(|stmt| self.scan_stmt(stmt))
                 }
             }),
-            Expr::Prefix { rhs, .. } => self.scan_expr(rhs),
+            Expr::Prefix { rhs, .. } | Expr::Spread { value: rhs, .. } => self.scan_expr(rhs),
             Expr::Call {
                 lhs, parameters, ..
             } => {
@@ -40,7 +41,7 @@ impl<'a, Buffer: IoWrite> ConstScanner<'a, Buffer> {
             // Member rhs can not have legal constant values
             Expr::Infix {
                 lhs,
-                op: OpInfix::Member | OpInfix::DoubleColon,
+                op: OpInfix::Member | OpInfix::SafeMember | OpInfix::DoubleColon,
                 ..
             } => {
                 self.scan_expr(lhs);
@@ -67,6 +68,7 @@ impl<'a, Buffer: IoWrite> ConstScanner<'a, Buffer> {
                     self.scan_expr(expr)
                 }
             }
+            Stmt::Defer { body, .. } => self.scan_expr(body),
             Stmt::Loop {
                 condition, body, ..
             } => {
@@ -79,13 +81,33 @@ impl<'a, Buffer: IoWrite> ConstScanner<'a, Buffer> {
                 self.scan_expr(iterator);
                 body.iter().for_each(|stmt| self.scan_stmt(stmt));
             }
-            Stmt::Def { variable, .. } => self.scan_expr(variable),
-            Stmt::Import { .. } => (),
-            Stmt::Error => unreachable!(),
+            Stmt::Def {
+                decorators,
+                variable,
+                ..
+            } => {
+                self.scan_expr(variable);
+                decorators
+                    .iter()
+                    .for_each(|decorator| self.scan_expr(decorator));
+            }
+            Stmt::Enum {
+                loc,
+                name,
+                variants,
+                ..
+            } => lower_enum(loc, name, variants, false)
+                .iter()
+                .for_each(|stmt| self.scan_stmt(stmt)),
+            Stmt::Interface {
+                loc, name, methods, ..
+            } => self.scan_stmt(&lower_interface(loc, name, methods)),
+            Stmt::Import { .. } | Stmt::Macro { .. } => (),
+            Stmt::Error => (),
         }
     }
 
-    fn scan_const(&mut self, constant: &Const) {
+    pub fn scan_const(&mut self, constant: &Const) {
         let constant = match constant {
             Const::Unit => self
                 .register_table
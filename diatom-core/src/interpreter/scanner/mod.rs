@@ -1,6 +1,6 @@
 use crate::{
     file_manager::Loc,
-    frontend::parser::ast::{Const, Expr, OpInfix, Stmt},
+    frontend::parser::ast::{lower_enum, lower_interface, Const, Expr, OpInfix, Stmt},
     gc::Gc,
     vm::{op::OpLoadConstant, VmInst},
     IoWrite,
@@ -12,7 +12,9 @@ use super::{
 };
 
 mod capture_scanner;
+mod const_folder;
 mod const_scanner;
 
 pub use capture_scanner::CaptureScanner;
+pub use const_folder::ConstFolder;
 pub use const_scanner::ConstScanner;
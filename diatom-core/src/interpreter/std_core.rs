@@ -1,7 +1,12 @@
-use std::fmt::Debug;
-use std::sync::Arc;
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::fmt::Debug;
 
-use ahash::AHashMap;
+use crate::compat::AHashMap;
 
 use crate::ffi::ForeignFunction;
 use crate::IoWrite;
@@ -56,7 +61,7 @@ pub struct Extension<Buffer: IoWrite> {
 }
 
 impl<Buffer: IoWrite> Debug for Extension<Buffer> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "Extension<{}>", self.name)
     }
 }
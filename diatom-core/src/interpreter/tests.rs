@@ -1,4 +1,5 @@
 use super::std_core::LibDummy;
+use crate::gc::{GcObject, Reg};
 
 type Interpreter<Buffer> = super::Interpreter<Buffer, LibDummy>;
 
@@ -46,6 +47,8 @@ macro_rules! test_err {
 #[test]
 fn test_binary_op() {
     test_ok!("1+2-3*10/5 + (1 + 2.234**3)", "9.149348904");
+    test_ok!("-2 ** 2", "-4.0");
+    test_ok!("2 ** 3 ** 2", "512.0");
     test_ok!("8//5", "1");
     test_ok!("8%5", "3");
     test_ok!("true > false", "true");
@@ -57,8 +60,8 @@ fn test_binary_op() {
     test_ok!("1<1", "false");
     test_ok!("1>1", "false");
     test_ok!("'abc' > 'abcd'", "false");
-    test_ok!("'a'*3", "aaa");
-    test_ok!("'a' + 'b'", "ab");
+    test_ok!("'a'*3", r#""aaa""#);
+    test_ok!("'a' + 'b'", r#""ab""#);
 
     test_err!("1.5 >= 2.3");
     test_err!("false -1");
@@ -148,7 +151,7 @@ fn test_table() {
     test_ok!("{}", "{}");
     test_ok!("{a = 1}", "{a = 1}");
     test_ok!("x = {a = 1} x.a = 100 x.a", "100");
-    test_ok!("x = {a = {i = 1}} x.a.i = 'Hello' x.a.i", "Hello");
+    test_ok!("x = {a = {i = 1}} x.a.i = 'Hello' x.a.i", r#""Hello""#);
     test_err!("{a = 1, a = 1}");
     test_err!("a.b");
     test_err!("a.'hello'");
@@ -175,12 +178,262 @@ fn test_recursive() {
 #[test]
 fn test_compile_with_target() {
     test_ok!("def add a b = a + b end add(add(1,2), begin 3 end)", "6");
+    test_ok!("def add a, b, = a + b end add(1, 2,) + [3,][0]", "6");
 }
 
 #[test]
 fn test_tuple() {
     test_ok!("a = (1,2,3) a.2", "3");
-    test_ok!("a = (1,2, {}) a.2.idx='hello' b = a.2 b.idx", "hello");
+    test_ok!("a = (1,2, {}) a.2.idx='hello' b = a.2 b.idx", r#""hello""#);
+}
+
+#[test]
+fn test_spread() {
+    test_ok!("xs = [2, 3]; [1, xs..., 4]", "[1, 2, 3, 4]");
+    test_ok!("xs = [1] xs = [xs..., xs..., 2] xs", "[1, 1, 2]");
+    test_ok!("[(1, 2)..., [3]..., []...]", "[1, 2, 3]");
+    test_ok!("xs = [1] ys = [xs...] ys[0] = 2; xs", "[1]");
+    test_ok!(
+        "base = {a = 1, b = 2} t = {base..., b = 3}; (t.a, t.b, base.b)",
+        "(1, 3, 2)"
+    );
+    test_ok!("t = {b = 3, {a = 1, b = 2}...} t.b", "2");
+    test_ok!("t = {a = 1} t = {t..., c = 2} t.a + t.c", "3");
+    test_err!("[1...]");
+    test_err!("{[1]...}");
+    test_err!("b = [] {a = b...}");
+    test_err!("x = [1]... x");
+    test_err!("f = fn x = x f([1]...)");
+}
+
+#[test]
+fn test_parallel_assignment() {
+    test_ok!("a, b = 1, 2 a, b = b, a; (a, b)", "(2, 1)");
+    test_ok!("a = 1, 2 a", "(1, 2)");
+    test_ok!("t = (1, (2, 3)) x, (y, z) = t; (x, y, z)", "(1, 2, 3)");
+    test_ok!("t = (1, 2) t, x = t; (t, x)", "(1, 2)");
+    test_ok!("l = [0, 0] l[0], l[1] = 3, 4 l", "[3, 4]");
+    test_ok!("x = {} x.a, x.b = 1, 2 x.a + x.b", "3");
+    test_ok!("a = b = c = 1; (a, b, c)", "(1, 1, 1)");
+    test_ok!("a, b = c = 1, 2; (a, b, c.1)", "(1, 2, 2)");
+    test_err!("a, b, c = (1, 2)");
+    test_err!("a = 1 = 2");
+    test_err!("f = fn = 1 f() = 3");
+    test_err!("a, b = 1, 2, 3");
+}
+
+#[test]
+fn test_nil_safe() {
+    test_ok!("t = {a = {b = 1}} t?.a?.b", "1");
+    test_ok!("t = (); (t?.a?.b, t?.a)", "((), ())");
+    test_ok!("t = (); (t?.[0], [1, 2]?.[1])", "((), 2)");
+    test_ok!(
+        "t = {f = fn self x = x + 1} u = (); (t?.f(1), u?.f(1))",
+        "(2, ())"
+    );
+    test_ok!("t = {a = ()} t.a ?? 1", "1");
+    test_ok!("x = 2; x ?? 1", "2");
+    test_ok!("() ?? () ?? 3", "3");
+    test_ok!("() ?? 1 + 1", "2");
+    test_ok!("f = fn = begin x = () x?.y ?? 4 end f()", "4");
+    test_ok!("t = {f = fn self = false} true and t.f()", "false");
+    test_ok!("false or ()?.a ?? true", "true");
+    test_err!("t = {} t?.a");
+    test_err!("t = {} t?.a = 1");
+}
+
+#[test]
+fn test_defer() {
+    test_ok!(
+        "s = '' def f = defer s = s + 'a' defer s = s + 'b' s = s + 'c' 1 end (f(), s)",
+        r#"(1, "cba")"#
+    );
+    test_ok!(
+        "n = 0 def f x = defer n = n + 1 if x > 0 then return x end 0 end (f(2), f(-1), n)",
+        "(2, 0, 2)"
+    );
+    test_ok!(
+        "n = 0 f = fn = begin defer n = n * 2 defer begin n = n + 1 end n end; (f(), n)",
+        "(0, 2)"
+    );
+    test_err!("defer x = 1");
+    // Values of locals are captured when deferred, registers of the loop are reused at exit
+    test_ok!(
+        r#"
+        n = 0
+        def g =
+            i = 0
+            until i == 3 do
+                j = i
+                defer n = n * 10 + j
+                i = i + 1
+            end
+            x = 5
+            defer n = n * 10 + x
+            x = 9
+        end
+        g()
+        n
+    "#,
+        "5210"
+    );
+}
+
+#[test]
+fn test_strict() {
+    // Pragma only applies to its own file
+    test_err!("--! strict\nx = x");
+    test_err!("-- counter\n--! strict\ndef f = n = n + 1 end");
+    test_ok!("x = (); x = x; x", "");
+    test_ok!(
+        "--! strict\nf = fn n = if n > 0 then f(n - 1) + n else 0 end; f(3)",
+        "6"
+    );
+    test_ok!("--! strict\nn = 1; n = n + 1; n", "2");
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    assert!(!interpreter.set_strict(true));
+    interpreter.exec("total = 0", "test", true).unwrap();
+    let err = interpreter
+        .exec("def add x = totl = totl + x end", "test", true)
+        .unwrap_err();
+    assert!(err.contains("E2010"), "{err}");
+    assert!(err.contains("Did you mean `total`?"), "{err}");
+    let err = interpreter.exec("totl", "test", true).unwrap_err();
+    assert!(err.contains("Did you mean `total`?"), "{err}");
+
+    // Only assignments at top level declare names
+    test_err!("--! strict\nif true then y = 1 end");
+    test_err!("--! strict\ndef f = until true do y = 1 end end");
+    test_ok!("--! strict\ny = 0\nif true then y = 1 end\ny", "1");
+    test_ok!(
+        "--! strict\ndef f = y = 0; if true then y = 1 end; y end; f()",
+        "1"
+    );
+    let err = interpreter
+        .exec("if true then totl = 1 end", "test", true)
+        .unwrap_err();
+    assert!(err.contains("E2013"), "{err}");
+    assert!(err.contains("Did you mean `total`?"), "{err}");
+
+    // A global whose assignment failed is undefined until assigned
+    assert!(interpreter.exec("totals = [][0]", "test", true).is_err());
+    let err = interpreter.exec("totals", "test", true).unwrap_err();
+    assert!(err.contains("E3029"), "{err}");
+    assert!(err.contains("Did you mean `total`?"), "{err}");
+    let err = interpreter
+        .exec("f = fn = totals", "test", true)
+        .unwrap_err();
+    assert!(err.contains("E3029"), "{err}");
+    assert!(interpreter.exec("a = 1; b = [][0]", "test", true).is_err());
+    interpreter.exec("a", "test", true).unwrap();
+    assert!(interpreter.exec("b", "test", true).is_err());
+    interpreter
+        .exec("if true then totals = 1 end; b = totals", "test", true)
+        .unwrap();
+    interpreter.exec("b", "test", true).unwrap();
+    interpreter.set_strict(false);
+    interpreter
+        .exec("def add x = totl = totl end", "test", true)
+        .unwrap();
+}
+
+#[test]
+fn test_resolve() {
+    test_ok!(
+        "def f n = if n > 0 then f(n - 1) + n else 0 end end f(3)",
+        "6"
+    );
+    test_ok!("def f x = x end f = fn x y = x + y; f(1, 2)", "3");
+    test_ok!("t = {f = fn self x = x} def g x = x end; t.f(g(1))", "1");
+    test_ok!("def f a b = a - b end 10 |> f(3)", "7");
+    test_err!("def f = g() end def g = 1 end");
+    test_err!("if true then y = x end x = 1");
+    test_err!("def f x = x end; 1 |> f(2)");
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    let err = interpreter
+        .exec("def f = g() end\ndef g = 1 end", "test", true)
+        .unwrap_err();
+    assert!(err.contains("E2011"), "{err}");
+    assert!(err.contains("`g` is defined here"), "{err}");
+    let err = interpreter
+        .exec("def f x y = x end\nf(1)", "test", true)
+        .unwrap_err();
+    assert!(err.contains("E2012"), "{err}");
+    assert!(
+        err.contains("takes 2 parameters but 1 is provided"),
+        "{err}"
+    );
+    // Functions of previous inputs may be reassigned by the host
+    interpreter.exec("def h x = x end", "test", true).unwrap();
+    interpreter.exec("def h = 0 end h()", "test", true).unwrap();
+}
+
+#[test]
+fn test_const_fold() {
+    test_ok!("x = 1 + 2 * 3; y = -x // 2; y", "-4");
+    test_ok!("a = 2; b = a ** 10 / 4; b", "256.0");
+    test_ok!(
+        "s = \"ab\" + \"c\"; t = s == \"abc\" and not false; t",
+        "true"
+    );
+    test_ok!("x = 1 < 1.5; x", "true");
+    // Assigned again, so not folded
+    test_ok!("x = 1; y = x + 1; x = 5; y + x", "7");
+    test_ok!("n = 1; def inc = n = n + 1 end; inc(); m = n + 1; m", "3");
+    test_err!("x = 1 + \"a\"");
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    let decompiled = interpreter
+        .decompile("x = 1 + 2 * 3\ny = x * 2 - 1", "test", true)
+        .unwrap();
+    let main = decompiled.split("Function: Func@1").next().unwrap();
+    assert!(!main.contains("add") && !main.contains("mul"), "{main}");
+    assert!(main.contains("load    13 -> "), "{main}");
+}
+
+#[test]
+fn test_table_index() {
+    test_ok!("t = {} t[2] = 3 t[1] = 2 t[0] = 1; t[0] + t[1] + t[2]", "6");
+    test_ok!(
+        "t = {a = 1} t[-1] = 0 t[0] = 2 t[5] = 4; t",
+        "{[-1] = 0, [0] = 2, [5] = 4, a = 1}"
+    );
+    test_ok!("t = {} t[0] = 1 t[0] = 7; u = {t...}; u[0]", "7");
+    test_ok!(
+        "t = {} i = 0; until i >= 100 do t[99 - i] = i; i = i + 1 end t[0] + t[99]",
+        "99"
+    );
+    test_err!("t = {} t[0] = 1; t[1]");
+    test_err!("t = {} t[0]");
+}
+
+#[test]
+fn test_placeholder() {
+    test_ok!("twice = fn f x = f(f(x)); twice(_ * 3, 2)", "18");
+    test_ok!("g = fn x f = f(x); 3 |> g(_ + 1) |> g(_ * 2)", "8");
+    test_ok!("inc = fn x = x + 1; 1 |> inc |> inc", "3");
+    test_ok!("f = fn a b = a - b; 10 |> f(3)", "7");
+    test_ok!("h = fn f = f((1, 2)); h(_.1)", "2");
+    test_ok!("k = fn f = f(1); k(k(_ + 1) + _)", "3");
+    test_ok!(
+        "t = {v = 2, apply = fn self f = f(self.v)} t.apply(_ ** 3)",
+        "8.0"
+    );
+    test_ok!("xs = [1] g = fn x f = f(x); 0 |> g(xs[_])", "1");
+    test_err!("_ + 1");
+}
+
+#[test]
+fn test_inspect() {
+    test_ok!("[1, 'a\\n\\\"', (), {}]", r#"[1, "a\n\"", (), {}]"#);
+    test_ok!("['\\a\\x01']", r#"["\x07\x01"]"#);
+    test_ok!("[[1, 2], []]", "[\n    [1, 2],\n    []\n]");
+    test_ok!(
+        "x = {a = 1} x.self = x x",
+        "#1 {\n    a = 1,\n    self = <cycle #1>\n}"
+    );
 }
 
 #[test]
@@ -190,6 +443,29 @@ fn test_method() {
     test_ok!("a = (1, fn x = x) a.1(1)", "1");
 }
 
+#[test]
+fn test_bound_method() {
+    test_ok!(
+        "t = {v = 2, add = fn self x = self.v + x} f = t.add f(1)",
+        "3"
+    );
+    test_ok!("t = {v = 2, add = fn self x = self.v + x}; (t.add)(1)", "3");
+    test_ok!(
+        "t = {v = 2, add = fn self x = self.v + x} t::add(t, 1)",
+        "3"
+    );
+    test_ok!(
+        "t = {v = 2, add = fn self x = self.v + x} t = t.add t(1)",
+        "3"
+    );
+    test_ok!(
+        "m = {get = fn self = self.v} t = {v = 1} <- m f = t.get f()",
+        "1"
+    );
+    test_ok!("t = {v = 2} t.v", "2");
+    test_err!("t = {f = fn self = ()} f = t.f f(1)");
+}
+
 #[test]
 fn test_meta_table() {
     test_ok!(
@@ -200,6 +476,330 @@ fn test_meta_table() {
     "#,
         "true"
     );
+    test_ok!(
+        r#"
+        Animal = {name = 'animal', speak = fn self = self.name + ' speaks'}
+        Dog = {speak = fn self = Animal::speak(self) + ' and barks'} <- Animal
+        dog = {name = 'dog'} <- Dog
+        dog.speak(), Dog::name
+    "#,
+        r#"("dog speaks and barks", "animal")"#
+    );
+    test_ok!(
+        r#"
+        Walker = {walk = fn self = 'walk'}
+        Swimmer = {swim = fn self = 'swim', walk = fn self = 'paddle'}
+        Duck = {Walker..., Swimmer...}
+        duck = {} <- Duck
+        duck.walk(), duck.swim()
+    "#,
+        r#"("paddle", "swim")"#
+    );
+    test_err!("A = {} B = {} <- A b = {} <- B b.x");
+}
+
+#[test]
+fn test_super() {
+    // `super` is the parent of the class defining the method, not that of the receiver
+    test_ok!(
+        r#"
+        A = {name = 'a'}
+        def A.speak self suffix = self.name + ':A' + suffix end
+        B = {} <- A
+        def B.speak self suffix = super.speak('B' + suffix) end
+        C = {} <- B
+        def C.speak self suffix = super.speak('C' + suffix) end
+        c = {name = 'c'} <- C
+        c.speak('!'), B::speak(c, '?'), ({name = 'b'} <- B).speak('')
+    "#,
+        r#"("c:ABC!", "c:AB?", "b:AB")"#
+    );
+    // Skipped levels are looked up along the chain, `self` is captured by closures
+    test_ok!(
+        r#"
+        A = {}
+        def A.name self = 'a' end
+        B = {} <- A
+        C = {} <- B
+        def C.name self =
+            f = fn = super.name() + 'c'
+            f()
+        end
+        c = {} <- C
+        c.name()
+    "#,
+        r#""ac""#
+    );
+    test_ok!(
+        r#"
+        Animal = {}
+        def Animal.__init self name = self.name = name end
+        Dog = {} <- Animal
+        def Dog.__init self name =
+            super.__init(name)
+            self.sound = 'woof'
+        end
+        dog = Dog('rex')
+        dog.name, dog.sound
+    "#,
+        r#"("rex", "woof")"#
+    );
+    // A variable named `super` is not affected outside methods
+    test_ok!("super = {f = fn self = 1}; super.f()", "1");
+    test_err!("A = {} def A.f self = super.f() end ({} <- A).f()");
+    test_err!("def f self = super.f() end");
+}
+
+#[test]
+fn test_constructor() {
+    test_ok!(
+        r#"
+        Point = {x = 0, y = 0, __init = fn self x = begin self.x = x end}
+        p = Point(3)
+        p.x, p.y, Point.x
+    "#,
+        "(3, 0, 0)"
+    );
+    test_ok!(
+        r#"
+        Animal = {legs = 4, __init = fn self name = begin self.name = name end}
+        Bird = {legs = 2, fly = fn self = self.name + ' flies'} <- Animal
+        b = Bird('tweety')
+        b.fly(), b.legs
+    "#,
+        r#"("tweety flies", 2)"#
+    );
+    test_ok!(
+        r#"
+        Counter = {
+            items = [],
+            __init = fn self = begin self.items = [] end
+        }
+        a = Counter() b = Counter()
+        a.items = [1]
+        b.items
+    "#,
+        "[]"
+    );
+    test_err!("Point = {x = 0} Point()");
+    test_err!("Point = {__init = fn self x = ()} Point()");
+}
+
+#[test]
+fn test_property() {
+    test_ok!(
+        r#"
+        Square = {
+            __getters = {area = fn self = self.side * self.side},
+            __setters = {area = fn self v = begin self.side = v / self.side end}
+        }
+        s = {side = 3} <- Square
+        a = s.area
+        s.area = 6
+        a, s.side, s.area
+    "#,
+        "(9, 2.0, 4.0)"
+    );
+    test_ok!(
+        r#"
+        Shape = {__getters = {name = fn self = 'shape ' + self.id}}
+        Circle = {} <- Shape
+        c = {id = 'c', name = 'raw'} <- Circle
+        c.name
+    "#,
+        r#""shape c""#
+    );
+    test_ok!(
+        r#"
+        Logged = {__setters = {x = fn self v = begin self.log = v end}}
+        t = {x = 1} <- Logged
+        t.x = 2
+        t.x, t.log
+    "#,
+        "(1, 2)"
+    );
+}
+
+#[test]
+fn test_static_member() {
+    test_ok!(
+        r#"
+        Counter = {
+            __static = {
+                count = 0,
+                create = fn cls = begin cls.count = cls.count + 1; cls() end
+            },
+            __init = fn self = begin self.id = Counter.count end
+        }
+        a = Counter.create()
+        b = Counter.create()
+        a.id, b.id, Counter.count, a.count
+    "#,
+        "(1, 2, 2, 2)"
+    );
+    test_ok!(
+        r#"
+        Base = {__static = {total = 0}, __init = fn self = ()}
+        Derived = {__static = {}} <- Base
+        d = Derived()
+        d.total = 3
+        Base.total, Derived.total, d.total
+    "#,
+        "(3, 3, 3)"
+    );
+    test_ok!(
+        r#"
+        Point = {x = 0, __static = {}, norm = fn self = self.x}
+        Point::x, Point::norm({x = 2})
+    "#,
+        "(0, 2)"
+    );
+    test_err!("Point = {x = 0, __static = {}} Point.x");
+    test_err!("Point = {__static = {}, norm = fn self = self.x} Point.norm()");
+}
+
+#[test]
+fn test_interface() {
+    test_ok!(
+        r#"
+        interface Show
+            show,
+            name
+        end
+        Show.__methods
+    "#,
+        r#"["show", "name"]"#
+    );
+    test_ok!("interface Empty end Empty.__methods", "[]");
+}
+
+#[test]
+fn test_decorator() {
+    test_ok!(
+        r#"
+        def twice f = fn x = f(f(x)) end
+        def add n = fn f = fn x = f(x) + n end
+        @add(1)
+        @twice
+        def inc x = x + 1 end
+        inc(0)
+    "#,
+        "3"
+    );
+    test_ok!(
+        r#"
+        def twice f = fn x = f(f(x)) end
+        T = {}
+        @twice def T.dbl x = x * 2 end
+        def outer =
+            @twice def g x = x * 3 end
+            g(1)
+        end
+        T::dbl(1), outer()
+    "#,
+        "(4, 9)"
+    );
+}
+
+#[test]
+fn test_macro() {
+    test_ok!(
+        r#"
+        macro times(n, body)
+            i = 0
+            loop
+                if i == n then break end
+                body
+                i = i + 1
+            end
+        end
+        count, i = 0, 0
+        times(3, times(2, count = count + 1))
+        count, i
+    "#,
+        "(6, 0)"
+    );
+    // Names assigned by a macro do not clash with those of the caller
+    test_ok!(
+        r#"
+        macro swap(a, b)
+            t = a
+            a = b
+            b = t
+        end
+        x, y, t = 1, 2, 3
+        swap(x, y)
+        x, y, t
+    "#,
+        "(2, 1, 3)"
+    );
+    test_ok!(
+        r#"
+        macro square(e) v = e v * v end
+        def f =
+            macro twice(e) e + e end
+            square(twice(1 + 1))
+        end
+        f()
+    "#,
+        "16"
+    );
+    test_ok!("macro = 2 macro * macro", "4");
+}
+
+#[test]
+fn test_enum_declaration() {
+    test_ok!(
+        r#"
+        enum Shape
+            Circle(r)
+            Rect(w, h)
+            Empty
+        end
+        def Shape.area self =
+            if self is Shape::Circle then 3 * self.r * self.r
+            elsif self is Shape::Rect then self.w * self.h
+            else 0 end
+        end
+        Shape::Circle(2).area(), Shape::Rect(2, 3).area(), Shape.Empty.area()
+    "#,
+        "(12, 6, 0)"
+    );
+    test_ok!(
+        r#"
+        Color = {name = 'color'}
+        enum Color Red, Green end
+        Color.name, Color.Red is Color.Red, Color.Red is Color.Green
+    "#,
+        r#"("color", true, false)"#
+    );
+    test_ok!(
+        r#"
+        enum Shape Rect(w, h) end
+        Shape::Rect(1, 'a')
+    "#,
+        r#"Shape::Rect(w = 1, h = "a")"#
+    );
+    test_ok!(
+        r#"
+        enum Shape Circle(r), Rect(w, h) end
+        c = Shape::Circle(1)
+        c is Shape::Circle, c is Shape::Rect, Shape::Rect(1, 2) is Shape::Rect, {} is Shape::Circle
+    "#,
+        "(true, false, true, false)"
+    );
+    test_ok!(
+        r#"
+        def make =
+            enum Pair Of(a, b) end
+            Pair::Of(1, 2)
+        end
+        p = make()
+        p.a + p.b
+    "#,
+        "3"
+    );
+    test_err!("enum Shape Circle(r) end Shape::Circle()");
 }
 
 #[test]
@@ -218,3 +818,1572 @@ fn test_short_circuit() {
     test_ok!("false and true + false", "false");
     test_ok!("true or true + 1.1", "true");
 }
+
+#[test]
+fn test_extern_args() {
+    use crate::ffi::{Args, DiatomValue};
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.impl_extern_function("repeat", |state, parameters, _| {
+        let args = Args::new("repeat", parameters);
+        args.expect_len(2)?;
+        let n = args.get::<i64>(1)?;
+        let s = args.get_str(state, 0)?.repeat(n as usize);
+        Ok(DiatomValue::Str(state.create_str(s)))
+    });
+    interpreter.repl(true);
+    interpreter.exec("repeat('ab', 2)", "test", true).unwrap();
+    let output = interpreter.replace_buffer(vec![]);
+    assert_eq!(String::from_utf8(output).unwrap(), "\"abab\"\n");
+
+    let err = interpreter
+        .exec("repeat('ab', 2.0)", "test", true)
+        .unwrap_err();
+    assert!(err.contains("`repeat` expected type `Int` for parameter #1 while `Float` is provided"));
+    let err = interpreter.exec("repeat('ab')", "test", true).unwrap_err();
+    assert!(err.contains("`repeat` expected 2 parameter while 1 is provided"));
+}
+
+#[test]
+fn test_extern_conversion() {
+    use crate::ffi::{Args, IntoDiatom};
+
+    struct Point {
+        x: i64,
+        tags: Vec<String>,
+        label: Option<String>,
+    }
+    crate::impl_diatom_table!(Point { x, tags, label });
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.impl_extern_function("move_point", |state, parameters, _| {
+        let args = Args::new("move_point", parameters);
+        args.expect_len(1)?;
+        let mut point: Point = args.get_as(state, 0)?;
+        point.x += 1;
+        point.tags.push("moved".to_string());
+        point.label = None;
+        Ok(point.into_diatom(state))
+    });
+    interpreter.repl(true);
+    interpreter
+        .exec(
+            "p = move_point({x = 1, tags = ['a'], label = ()})",
+            "test",
+            true,
+        )
+        .unwrap();
+    interpreter.replace_buffer(vec![]);
+    interpreter.exec("p.x, p.tags[1]", "test", true).unwrap();
+    let output = interpreter.replace_buffer(vec![]);
+    assert_eq!(String::from_utf8(output).unwrap(), "(2, \"moved\")\n");
+
+    let err = interpreter
+        .exec("move_point({x = 1, tags = [1], label = ()})", "test", true)
+        .unwrap_err();
+    assert!(
+        err.contains("In field `tags`: At index 0: Expected type `String` while `Int` is provided")
+    );
+}
+
+#[test]
+fn test_add_module() {
+    use crate::ffi::{Args, DiatomValue};
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.add_module("fs", |m| {
+        m.value("sep", "/");
+        m.func("join", |state, parameters, _| {
+            let args = Args::new("join", parameters);
+            args.expect_len(2)?;
+            let s = format!("{}/{}", args.get_str(state, 0)?, args.get_str(state, 1)?);
+            Ok(DiatomValue::Str(state.create_str(s)))
+        });
+        m.module("v2", |m| {
+            m.value("tags", vec!["a", "b"]);
+        });
+    });
+    interpreter.repl(true);
+    interpreter
+        .exec("fs::join('a', 'b'), fs.sep, fs.v2.tags", "test", true)
+        .unwrap();
+    let output = interpreter.replace_buffer(vec![]);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "(\n    \"a/b\",\n    \"/\",\n    [\"a\", \"b\"]\n)\n"
+    );
+    assert!(interpreter.exec("join", "test", true).is_err());
+}
+
+#[test]
+fn test_user_type() {
+    use crate::ffi::{Args, DiatomValue};
+
+    struct Channel {
+        name: String,
+        sent: Vec<String>,
+    }
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter
+        .register_type::<Channel>()
+        .method("send", |channel, state, parameters| {
+            let args = Args::new("send", parameters);
+            args.expect_len(1)?;
+            channel.sent.push(args.get_str(state, 0)?.to_string());
+            Ok(DiatomValue::Int(channel.sent.len() as i64))
+        })
+        .getter("name", |channel| channel.name.clone())
+        .getter("sent", |channel| channel.sent.clone());
+    interpreter.impl_extern_function("open", |state, _, _| {
+        let channel = Channel {
+            name: "general".to_string(),
+            sent: vec![],
+        };
+        Ok(DiatomValue::Ref(state.create_user_data(Box::new(channel))))
+    });
+    interpreter.repl(true);
+    interpreter.exec("c = open()", "test", true).unwrap();
+    interpreter.exec("c.send('a')", "test", true).unwrap();
+    interpreter.exec("c.send('b')", "test", true).unwrap();
+    interpreter.replace_buffer(vec![]);
+    interpreter.exec("c.name, c.sent", "test", true).unwrap();
+    let output = interpreter.replace_buffer(vec![]);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "(\n    \"general\",\n    [\"a\", \"b\"]\n)\n"
+    );
+
+    let err = interpreter.exec("c.close()", "test", true).unwrap_err();
+    assert!(err.contains("close"));
+    let err = interpreter.exec("1 + c", "test", true).unwrap_err();
+    assert!(err.contains("`Channel`"));
+}
+
+#[test]
+fn test_call_function() {
+    use std::sync::{Arc, Mutex};
+
+    use crate::ffi::{DiatomValue, FunctionHandle};
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    let callback: Arc<Mutex<Option<FunctionHandle>>> = Arc::default();
+    let callback_capture = callback.clone();
+    interpreter.impl_extern_function("on_event", move |state, parameters, _| {
+        let f = state
+            .function_handle(&parameters[0])
+            .ok_or("Expected a function")?;
+        *callback_capture.lock().unwrap() = Some(f);
+        Ok(DiatomValue::Unit)
+    });
+    interpreter
+        .exec(
+            "def add x y = x + y end\ncount = 0\non_event(fn x = begin count = count + x\ncount end)",
+            "test",
+            true,
+        )
+        .unwrap();
+
+    let ret = interpreter
+        .call("add", &[DiatomValue::Int(1), DiatomValue::Int(2)])
+        .unwrap();
+    assert!(matches!(ret, DiatomValue::Int(3)));
+    let err = interpreter
+        .call("add", &[DiatomValue::Int(1)])
+        .err()
+        .unwrap();
+    assert!(err.contains("Function takes 2 parameters but 1 is provided"));
+    assert!(err.contains("Called by host"), "{err}");
+    let err = interpreter
+        .call("add", &[DiatomValue::Int(1), DiatomValue::Unit])
+        .err()
+        .unwrap();
+    assert!(err.contains("test:1:"), "{err}");
+    assert!(
+        !err.contains("<host>") && !err.contains("Trace back"),
+        "{err}"
+    );
+    let err = interpreter.call("undefined", &[]).err().unwrap();
+    assert!(err.contains("Name `undefined` is not defined"));
+    assert!(interpreter.call("count", &[]).is_err());
+
+    // Closure is kept alive by handle even if no variable refers to it
+    interpreter.exec("l = [1, 2, 3]", "test", true).unwrap();
+    let f = callback.lock().unwrap().take().unwrap();
+    for i in 1..=3 {
+        let ret = interpreter
+            .call_function(&f, &[DiatomValue::Int(i)])
+            .unwrap();
+        assert!(matches!(ret, DiatomValue::Int(n) if n == i * (i + 1) / 2));
+    }
+    let f = interpreter.get_function("add").unwrap();
+    let ret = interpreter
+        .call_function(&f, &[DiatomValue::Int(4), DiatomValue::Int(5)])
+        .unwrap();
+    assert!(matches!(ret, DiatomValue::Int(9)));
+    assert!(interpreter.get_function("count").is_none());
+
+    // Interpreter is still usable after calls
+    interpreter.repl(true);
+    interpreter.exec("count", "test", true).unwrap();
+    let output = interpreter.replace_buffer(vec![]);
+    assert_eq!(String::from_utf8(output).unwrap(), "6\n");
+}
+
+#[test]
+fn test_rooted_value() {
+    use crate::ffi::DiatomValue;
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter
+        .exec("def make n = [n, 'value'] end", "test", true)
+        .unwrap();
+    let list = interpreter.call("make", &[DiatomValue::Int(1)]).unwrap();
+    let rid = match list {
+        DiatomValue::Ref(rid) => rid,
+        _ => unreachable!(),
+    };
+    let rooted = interpreter.root(list).unwrap();
+    let cloned = rooted.clone();
+    drop(rooted);
+
+    // Garbage collector runs on every allocation in test
+    interpreter.call("make", &[DiatomValue::Int(2)]).unwrap();
+    assert!(matches!(interpreter.gc.get_obj(rid), Some(GcObject::List(l)) if l.len() == 2));
+    interpreter.gc.collect();
+    assert!(interpreter.gc.get_obj(rid).is_some());
+
+    assert!(matches!(cloned.value(), DiatomValue::Ref(id) if id == rid));
+    drop(cloned);
+    interpreter.gc.collect();
+    assert!(interpreter.gc.get_obj(rid).is_none());
+
+    assert!(interpreter.root(DiatomValue::Ref(usize::MAX)).is_none());
+}
+
+#[test]
+fn test_verify_heap() {
+    // Stress mode verifies the heap on every allocation in test
+    let mut interpreter = Interpreter::new(vec![]);
+    interpreter
+        .exec(
+            "def f x = fn = [x, {x = x}] end\ng = f(1)\nl = [g(), g(), (1, 'a')]",
+            "test",
+            true,
+        )
+        .unwrap();
+    assert!(interpreter.gc.verify_heap().is_ok());
+
+    let rid = interpreter
+        .gc
+        .alloc_obj_pinned(GcObject::List(vec![Reg::Ref(usize::MAX)]));
+    let err = interpreter.gc.verify_heap().unwrap_err();
+    assert!(err.contains(&format!("Object #{rid}")), "{err}");
+}
+
+#[test]
+fn test_global_variable() {
+    use crate::ffi::DiatomValue;
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.set_global("names", vec!["a", "b"]);
+    interpreter.set_global("limit", 2_i64);
+    interpreter
+        .exec("result = names[limit - 1] + '!'", "test", true)
+        .unwrap();
+    let result: String = interpreter.get_global_as("result").unwrap();
+    assert_eq!(result, "b!");
+
+    interpreter.set_global("limit", 1_i64);
+    interpreter
+        .exec("result = names[limit - 1]", "test", true)
+        .unwrap();
+    assert!(matches!(
+        interpreter.get_global("limit"),
+        Some(DiatomValue::Int(1))
+    ));
+    let result: String = interpreter.get_global_as("result").unwrap();
+    assert_eq!(result, "a");
+
+    assert!(interpreter.get_global("undefined").is_none());
+    let err = interpreter.get_global_as::<i64>("result").unwrap_err();
+    assert!(err.contains("Expected type `Int` while `String` is provided"));
+}
+
+#[test]
+fn test_extern_function_with_state() {
+    use crate::ffi::DiatomValue;
+
+    #[derive(Default)]
+    struct Score {
+        total: i64,
+        history: Vec<i64>,
+    }
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.impl_extern_function_with_state(
+        "add_score",
+        |score: &mut Score, _, parameters, _| match parameters {
+            [DiatomValue::Int(i)] => {
+                score.total += i;
+                score.history.push(*i);
+                Ok(DiatomValue::Int(score.total))
+            }
+            _ => Err("Expected an `Int`".to_string()),
+        },
+    );
+    let err = interpreter.exec("add_score(1)", "test", true).unwrap_err();
+    assert!(err.contains("which is not set"));
+
+    interpreter.set_context(Score::default());
+    interpreter
+        .exec("add_score(1)\nadd_score(2)", "test", true)
+        .unwrap();
+    assert_eq!(interpreter.context::<Score>().unwrap().total, 3);
+    interpreter.context_mut::<Score>().unwrap().total = 10;
+    interpreter.exec("add_score(5)", "test", true).unwrap();
+    let score = interpreter.take_context::<Score>().unwrap();
+    assert_eq!(score.total, 15);
+    assert_eq!(score.history, vec![1, 2, 5]);
+    assert!(interpreter.context::<Score>().is_none());
+}
+
+#[test]
+fn test_sandbox_policy() {
+    use super::SandboxPolicy;
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    assert!(interpreter.policy().time);
+    assert!(!interpreter.policy().process);
+    interpreter.enable_capability("process");
+    interpreter.enable_capability("custom");
+    assert!(interpreter.policy().process);
+    assert!(interpreter.policy().allows("custom"));
+    interpreter.disable_capability("custom");
+    assert!(!interpreter.policy().allows("custom"));
+    assert!(interpreter.policy().allows("eval"));
+    interpreter.disable_capability("eval");
+    assert!(!interpreter.policy().eval);
+    assert!(!SandboxPolicy::deny_all().allows("eval"));
+    let sandboxed = Interpreter::with_policy(Vec::<u8>::new(), SandboxPolicy::deny_all());
+    assert!(!sandboxed.policy().time);
+
+    let mut policy = SandboxPolicy::deny_all();
+    policy.max_fuel = Some(1000);
+    interpreter.set_policy(policy);
+    let err = interpreter.exec("loop end", "test", true).unwrap_err();
+    assert!(err.contains("E3019"));
+    // Fuel is refilled for each execution
+    interpreter.exec("x = 1", "test", true).unwrap();
+
+    let mut policy = SandboxPolicy::deny_all();
+    policy.max_allocations = Some(1000);
+    interpreter.set_policy(policy);
+    let err = interpreter
+        .exec("x = []\nloop x = [x] end", "test", true)
+        .unwrap_err();
+    assert!(err.contains("E3020"));
+    interpreter.exec("x = ()", "test", true).unwrap();
+    interpreter.exec("y = [1, 2, 3]", "test", true).unwrap();
+
+    let mut policy = SandboxPolicy::deny_all();
+    policy.timeout = Some(std::time::Duration::from_millis(10));
+    interpreter.set_policy(policy);
+    let err = interpreter.exec("loop end", "test", true).unwrap_err();
+    assert!(err.contains("E3021"));
+    interpreter.exec("x = 1", "test", true).unwrap();
+}
+
+#[test]
+fn test_program() {
+    use crate::ffi::DiatomValue;
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.set_global("n", 0_i64);
+    interpreter.set_global("offset", 0_i64);
+    interpreter
+        .exec("def double x = x * 2 end", "test", true)
+        .unwrap();
+    let program = interpreter
+        .compile_program(
+            "result = double(n) + offset\noffset = offset + 1",
+            "test",
+            true,
+        )
+        .unwrap();
+
+    let mut contexts: Vec<_> = (1..=3)
+        .map(|n| {
+            let mut context = program.spawn(Vec::<u8>::new());
+            context.set_global("n", n as i64);
+            context.set_global("offset", 0_i64);
+            context
+        })
+        .collect();
+    for (n, context) in contexts.iter_mut().enumerate() {
+        context.run().unwrap();
+        assert_eq!(
+            context.get_global_as::<i64>("result"),
+            Ok(2 * (n as i64 + 1))
+        );
+    }
+
+    // Globals are independent and kept across runs
+    let context = &mut contexts[0];
+    context.run().unwrap();
+    assert_eq!(context.get_global_as::<i64>("result"), Ok(3));
+    assert!(matches!(
+        context.call("double", &[DiatomValue::Int(4)]),
+        Ok(DiatomValue::Int(8))
+    ));
+    context.run().unwrap();
+    assert_eq!(context.get_global_as::<i64>("result"), Ok(4));
+    assert_eq!(contexts[1].get_global_as::<i64>("offset"), Ok(1));
+
+    // Spawning again starts from the state right after compilation
+    let mut context = program.clone().spawn(Vec::<u8>::new());
+    context.set_global("n", 5_i64);
+    context.set_global("offset", 1_i64);
+    context.run().unwrap();
+    assert_eq!(context.get_global_as::<i64>("result"), Ok(11));
+
+    // User data is detached unless its type is cloneable
+    interpreter.impl_extern_function("make", |state, _, _| {
+        Ok(DiatomValue::Ref(state.create_user_data(Box::new(1_i64))))
+    });
+    interpreter.exec("data = make()", "test", true).unwrap();
+    let program = interpreter.compile_program("data", "test", true).unwrap();
+    let mut context = program.spawn(Vec::<u8>::new());
+    context.run().unwrap();
+    assert!(context.exec("data.get()", "test", true).is_err());
+}
+
+#[test]
+fn test_fork_env() {
+    use crate::ffi::DiatomValue;
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter
+        .exec(
+            "count = 0\nsession = {user = 'guest'}\ndef visit name = count = count + 1; session.user = name; count end",
+            "test",
+            true,
+        )
+        .unwrap();
+
+    for name in ["alice", "bob"] {
+        let mut env = interpreter.fork_env(Vec::<u8>::new());
+        env.exec(format!("visit('{name}')\nfresh = 1"), "request", true)
+            .unwrap();
+        assert_eq!(env.get_global_as::<i64>("count"), Ok(1));
+        env.exec("user = session.user", "request", true).unwrap();
+        assert_eq!(env.get_global_as::<String>("user").unwrap(), name);
+    }
+    // Mutations of forks, including new globals, do not leak
+    assert_eq!(interpreter.get_global_as::<i64>("count"), Ok(0));
+    assert!(interpreter.get_global("fresh").is_none());
+    interpreter
+        .exec("user = session.user", "test", true)
+        .unwrap();
+    assert_eq!(
+        interpreter.get_global_as::<String>("user").unwrap(),
+        "guest"
+    );
+
+    // Cloneable user data is copied, other user data is detached in the fork
+    #[derive(Clone)]
+    struct Counter(i64);
+    interpreter
+        .register_type::<Counter>()
+        .cloneable()
+        .chained_method("incr", |counter, _, _| {
+            counter.0 += 1;
+            Ok(())
+        })
+        .getter("value", |counter| counter.0);
+    interpreter.impl_extern_function("make", |state, _, _| {
+        Ok(DiatomValue::Ref(
+            state.create_user_data(Box::new(Counter(0))),
+        ))
+    });
+    interpreter.impl_extern_function("opaque", |state, _, _| {
+        Ok(DiatomValue::Ref(state.create_user_data(Box::new(0_u8))))
+    });
+    interpreter
+        .exec("counter = make()\nhandle = opaque()", "test", true)
+        .unwrap();
+    let mut env = interpreter.fork_env(Vec::<u8>::new());
+    env.exec("n = counter.incr().incr().value", "request", true)
+        .unwrap();
+    assert_eq!(env.get_global_as::<i64>("n"), Ok(2));
+    interpreter.exec("n = counter.value", "test", true).unwrap();
+    assert_eq!(interpreter.get_global_as::<i64>("n"), Ok(0));
+    env.exec("handle", "request", true).unwrap();
+}
+
+#[test]
+fn test_invalid_import() {
+    for code in [
+        "import-0",
+        "import -x",
+        "import (1)",
+        "import {a, -1} from b",
+    ] {
+        let mut interpreter = Interpreter::new(Vec::<u8>::new());
+        let err = interpreter.check(code, "test", true).unwrap_err();
+        assert!(err.contains("E1008"), "{err}");
+        assert!(interpreter.exec(code, "test", true).is_err());
+    }
+}
+
+#[test]
+fn test_module_loader() {
+    use crate::host::ModuleLoader;
+    use std::{collections::BTreeMap, sync::Arc};
+
+    let modules = BTreeMap::from([
+        (
+            "app.main".to_string(),
+            "import {value} from app.helper\n{value = value}".to_string(),
+        ),
+        ("app.helper".to_string(), "{value = 1}".to_string()),
+        ("helper".to_string(), "{value = 2}".to_string()),
+        ("broken".to_string(), "1 +".to_string()),
+    ]);
+    assert_eq!(modules.load("helper").as_deref(), Some("{value = 2}"));
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    assert!(interpreter
+        .set_module_loader(Some(Arc::new(modules)))
+        .is_none());
+    interpreter
+        .exec(
+            "import {value} from app.main\nimport {value as other} from helper",
+            "test",
+            true,
+        )
+        .unwrap();
+    assert_eq!(interpreter.get_global_as::<i64>("value"), Ok(1));
+    assert_eq!(interpreter.get_global_as::<i64>("other"), Ok(2));
+    let err = interpreter.exec("import broken", "test", true).unwrap_err();
+    assert!(err.contains("broken.dm"), "{err}");
+    assert!(interpreter.exec("import missing", "test", true).is_err());
+    assert!(interpreter.set_module_loader(None).is_some());
+}
+
+#[test]
+fn test_bundle() {
+    use crate::bundle::Bundle;
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("diatom_bundle_{}", std::process::id()));
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(
+        dir.join("lib/mod.dm"),
+        "import {base} from lib.base\n{n = base + 1}",
+    )
+    .unwrap();
+    fs::write(dir.join("lib/base.dm"), "{base = 41}").unwrap();
+    let main = dir.join("main.dm");
+    let code = "import {n} from lib\ndef get = begin import {base} from lib.base base end end";
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.with_search_path(dir.clone()).unwrap();
+    let bundle = interpreter.bundle(code, &main).unwrap();
+    assert_eq!(bundle.main, main);
+    assert_eq!(bundle.files.len(), 3);
+    assert_eq!(bundle.main_script(), Some(code));
+    let bytes = bundle.to_bytes();
+    assert!(Bundle::is_bundle(&bytes));
+    assert_eq!(Bundle::from_bytes(&bytes).unwrap(), bundle);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.exec_bundle(&bundle).unwrap();
+    assert_eq!(interpreter.get_global_as::<i64>("n"), Ok(42));
+    interpreter.exec("x = get()", "test", true).unwrap();
+    assert_eq!(interpreter.get_global_as::<i64>("x"), Ok(41));
+    // Files of a bundle are only read while it is compiled
+    assert!(interpreter.exec("import lib", "test", true).is_err());
+
+    assert!(interpreter.bundle("import missing", &main).is_err());
+    let err = interpreter.bundle("print(missing)", &main).unwrap_err();
+    assert!(err.contains("missing"), "{err}");
+    assert_eq!(Bundle::from_bytes(b"x = 1").unwrap_err(), "Not a bundle");
+    assert!(Bundle::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(Bundle::from_bytes(b"diatom-bundle 1\n2\nmain 1\nm\n")
+        .unwrap_err()
+        .contains("version 2"));
+    let empty = Bundle::from_bytes(b"diatom-bundle 1\n1\nmain 1\nm\n").unwrap();
+    assert!(interpreter.exec_bundle(&empty).is_err());
+}
+
+#[cfg(feature = "manifest")]
+#[test]
+fn test_manifest() {
+    use crate::manifest::Manifest;
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("diatom_manifest_{}", std::process::id()));
+    let (app, util) = (dir.join("app"), dir.join("util"));
+    fs::create_dir_all(app.join("src/geo")).unwrap();
+    fs::create_dir_all(util.join("lib")).unwrap();
+    fs::write(
+        app.join("diatom.toml"),
+        "[package]\nname = 'app'\nsrc = 'src'\n[dependencies]\nutil = { path = '../util' }\nraw = '../util/lib'",
+    )
+    .unwrap();
+    fs::write(util.join("diatom.toml"), "[package]\nsrc = 'lib'").unwrap();
+    fs::write(app.join("src/geo/point.dm"), "{origin = 0}").unwrap();
+    fs::write(util.join("lib/mod.dm"), "{double = fn x = x * 2}").unwrap();
+    fs::write(util.join("lib/strings.dm"), "{shout = fn s = s + '!'}").unwrap();
+
+    let path = Manifest::find(&app.join("src/geo")).unwrap();
+    assert_eq!(path, app.join("diatom.toml"));
+    let manifest = Manifest::load(&path).unwrap();
+    assert_eq!(manifest.name.as_deref(), Some("app"));
+    assert_eq!(manifest.src, app.join("src"));
+    assert_eq!(
+        manifest.dependencies,
+        vec![
+            ("raw".to_string(), app.join("../util/lib")),
+            ("util".to_string(), app.join("../util/lib")),
+        ]
+    );
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.with_manifest(&manifest).unwrap();
+    assert_eq!(
+        interpreter.search_paths(),
+        [app.join("src").canonicalize().unwrap()]
+    );
+    interpreter
+        .exec(
+            "import {origin} from geo.point\nimport {double} from util\nimport {shout} from util.strings\ndouble(origin + 2), shout('a')",
+            "test",
+            true,
+        )
+        .unwrap();
+    interpreter
+        .exec("import raw.strings", "test", true)
+        .unwrap();
+    assert!(interpreter.exec("import util.lib", "test", true).is_err());
+
+    for (text, err) in [
+        ("[package]\nsrc = 1", "`package.src` should be a string"),
+        ("[package]\nversion = '1'", "Unknown key `package.version`"),
+        (
+            "[dependencies]\nutil = {}",
+            "Dependency `util` has no `path`",
+        ),
+        ("[bin]", "Unknown section `bin`"),
+    ] {
+        assert_eq!(Manifest::parse(text, &dir).unwrap_err(), err);
+    }
+    assert!(Manifest::parse("[package", &dir).is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_reload() {
+    let path = std::env::temp_dir().join(format!("diatom_reload_{}.dm", std::process::id()));
+    std::fs::write(
+        &path,
+        "score = 0\ndef add x = score = score + x end\ndef bonus = 1 end",
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter
+        .exec(std::fs::read_to_string(&path).unwrap(), &path, false)
+        .unwrap();
+    interpreter.exec("add(bonus())", "test", true).unwrap();
+
+    std::fs::write(
+        &path,
+        "score = 0\ndef add x = score = score + x * 10 end\ndef bonus = 1 end\ndef twice = 2 end",
+    )
+    .unwrap();
+    let mut changed = vec![];
+    interpreter
+        .reload(&path, |names| changed = names.to_vec())
+        .unwrap();
+    assert_eq!(changed, vec!["add".to_string(), "twice".to_string()]);
+    interpreter.exec("add(twice())", "test", true).unwrap();
+    assert_eq!(interpreter.get_global_as::<i64>("score"), Ok(21));
+
+    // Nothing is replaced if compilation failed
+    std::fs::write(&path, "def add x = undefined_name end\ndef bonus = ").unwrap();
+    assert!(interpreter.reload(&path, |_| panic!()).is_err());
+    interpreter.exec("add(bonus())", "test", true).unwrap();
+    assert_eq!(interpreter.get_global_as::<i64>("score"), Ok(31));
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(interpreter.reload(&path, |_| panic!()).is_err());
+}
+
+#[test]
+fn test_data_conversion() {
+    use crate::ffi::{DiatomData, DiatomValue};
+    use std::collections::BTreeMap;
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.impl_extern_function("host", |_, _, _| Ok(DiatomValue::Unit));
+    interpreter
+        .exec(
+            "data = {name = 'a', tags = ['x', (1, 2.5)], flag = true, nothing = ()}\nf = host",
+            "test",
+            true,
+        )
+        .unwrap();
+    let data = interpreter.get_global("data").unwrap();
+    let data = interpreter.from_value(&data).unwrap();
+    let expected = DiatomData::Table(BTreeMap::from([
+        ("name".to_string(), DiatomData::Str("a".to_string())),
+        (
+            "tags".to_string(),
+            DiatomData::List(vec![
+                DiatomData::Str("x".to_string()),
+                DiatomData::List(vec![DiatomData::Int(1), DiatomData::Float(2.5)]),
+            ]),
+        ),
+        ("flag".to_string(), DiatomData::Bool(true)),
+        ("nothing".to_string(), DiatomData::Nil),
+    ]));
+    assert_eq!(data, expected);
+
+    let value = interpreter.to_value(expected.clone());
+    interpreter.set_global("copy", value.value());
+    interpreter
+        .exec("copy.tags[1][0] = 2", "test", true)
+        .unwrap();
+    let copy = interpreter.get_global("copy").unwrap();
+    let copy = interpreter.from_value(&copy).unwrap();
+    assert_ne!(copy, expected);
+    assert_eq!(
+        interpreter.get_global_as::<DiatomData>("data"),
+        Ok(expected)
+    );
+
+    let f = interpreter.get_global("f").unwrap();
+    assert!(interpreter.from_value(&f).is_err());
+    interpreter
+        .exec("l = {a = 1}\nl.me = l", "test", true)
+        .unwrap();
+    let l = interpreter.get_global("l").unwrap();
+    let err = interpreter.from_value(&l).unwrap_err();
+    assert!(err.contains("contains itself"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_data_serde() {
+    use crate::ffi::DiatomData;
+    use serde::{de::value::MapDeserializer, Deserialize};
+
+    let map = MapDeserializer::<_, serde::de::value::Error>::new(
+        vec![("a", 1_u64), ("b", u64::MAX)].into_iter(),
+    );
+    let data = DiatomData::deserialize(map).unwrap();
+    assert_eq!(
+        data,
+        DiatomData::Table(
+            [
+                ("a".to_string(), DiatomData::Int(1)),
+                ("b".to_string(), DiatomData::Float(u64::MAX as f64)),
+            ]
+            .into_iter()
+            .collect()
+        )
+    );
+}
+
+#[test]
+fn test_script_error() {
+    use crate::ffi::{DiatomData, DiatomValue};
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.impl_extern_function("find", |state, parameters, _| match parameters {
+        [DiatomValue::Int(id)] if *id > 0 => Ok(DiatomValue::Int(*id)),
+        [DiatomValue::Int(id)] => {
+            Err(state.raise("not_found", "No such user", DiatomData::Int(*id)))
+        }
+        _ => Err("Expected an id".to_string()),
+    });
+    assert!(interpreter.last_error().is_none());
+
+    let err = interpreter
+        .exec("find(1)\nfind(-2)", "test", true)
+        .unwrap_err();
+    assert!(err.contains("No such user"));
+    let error = interpreter.last_error().unwrap();
+    assert_eq!(error.code.as_deref(), Some("not_found"));
+    assert_eq!(error.message, "No such user");
+    assert_eq!(error.payload, Some(DiatomData::Int(-2)));
+
+    // Plain errors of external functions
+    interpreter.exec("find('a')", "test", true).unwrap_err();
+    let error = interpreter.last_error().unwrap();
+    assert_eq!(error.code.as_deref(), Some("E3006"));
+    assert_eq!(error.message, "Expected an id");
+    assert!(error.payload.is_none());
+
+    // Built-in runtime and compile errors
+    interpreter
+        .exec("t = {}\nt.missing", "test", true)
+        .unwrap_err();
+    assert_eq!(
+        interpreter.last_error().unwrap().code.as_deref(),
+        Some("E3010")
+    );
+    interpreter
+        .exec("undefined_name", "test", true)
+        .unwrap_err();
+    assert!(interpreter.last_error().unwrap().code.is_some());
+    assert!(interpreter.call("undefined_name", &[]).is_err());
+    let error = interpreter.last_error().unwrap();
+    assert!(error.code.is_none());
+    assert!(error.message.contains("not defined"));
+}
+
+#[test]
+fn test_extern_panic() {
+    use crate::ffi::DiatomValue;
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.impl_extern_function("check", |_, parameters, _| match parameters {
+        [DiatomValue::Int(i)] if *i < 0 => panic!("negative value {i}"),
+        _ => Ok(DiatomValue::Unit),
+    });
+    interpreter.set_global("count", 0);
+    let err = interpreter
+        .exec(
+            "def f x = check(x) end
+f(1)
+f(-1)",
+            "test",
+            true,
+        )
+        .unwrap_err();
+    assert!(err.contains("External function panicked"));
+    assert!(err.contains("negative value -1"));
+    let error = interpreter.last_error().unwrap();
+    assert_eq!(error.code.as_deref(), Some("E3022"));
+    assert_eq!(error.message, "negative value -1");
+
+    // Interpreter is still usable after a panic
+    interpreter
+        .exec(
+            "f(2)
+count = 1",
+            "test",
+            true,
+        )
+        .unwrap();
+    assert!(matches!(
+        interpreter.get_global("count"),
+        Some(DiatomValue::Int(1))
+    ));
+    assert!(interpreter.call("f", &[DiatomValue::Int(-3)]).is_err());
+    assert_eq!(
+        interpreter.last_error().unwrap().code.as_deref(),
+        Some("E3022")
+    );
+}
+
+#[test]
+fn test_introspection() {
+    use crate::ffi::{DiatomValue, DiatomValueKind, SourceLocation};
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.impl_extern_function("host", |_, _, _| Ok(DiatomValue::Unit));
+    interpreter
+        .exec(
+            "count = 1\nname = 'a'\n\ndef add x y =\n  x + y\nend\nt = {f = fn = 1}",
+            "test",
+            true,
+        )
+        .unwrap();
+    // Skip built-in types
+    let globals: Vec<_> = interpreter
+        .globals()
+        .filter(|(name, _)| name.starts_with(char::is_lowercase))
+        .collect();
+    assert_eq!(
+        globals,
+        vec![
+            ("add", DiatomValueKind::Function),
+            ("count", DiatomValueKind::Int),
+            ("host", DiatomValueKind::ExternFunction),
+            ("name", DiatomValueKind::Str),
+            ("t", DiatomValueKind::Table),
+        ]
+    );
+
+    let info = interpreter.function_info("add").unwrap();
+    assert_eq!(info.name.as_deref(), Some("add"));
+    assert_eq!(info.arity, 2);
+    assert_eq!(info.parameters, vec!["x", "y"]);
+    assert_eq!(
+        info.location,
+        Some(SourceLocation {
+            path: "test".to_string(),
+            line: 4,
+            column: 1,
+        })
+    );
+    assert!(interpreter.function_info("host").is_none());
+    assert!(interpreter.function_info("count").is_none());
+    assert!(interpreter.function_info("missing").is_none());
+}
+
+#[test]
+fn test_enum() {
+    use crate::ffi::DiatomValue;
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.add_enum("Level", &[("Low", 0), ("High", 10)]);
+    interpreter.impl_extern_function("check", |_, parameters, _| match parameters {
+        [DiatomValue::Int(0 | 10)] => Ok(DiatomValue::Bool(true)),
+        _ => Err("Expected a level".to_string()),
+    });
+    interpreter.repl(true);
+    interpreter
+        .exec("check(Level.High), Level::name_of(0).value", "test", true)
+        .unwrap();
+    let output = interpreter.replace_buffer(vec![]);
+    assert_eq!(String::from_utf8(output).unwrap(), "(true, \"Low\")\n");
+
+    interpreter.exec("Level.Medium", "test", true).unwrap_err();
+    assert_eq!(
+        interpreter.last_error().unwrap().code.as_deref(),
+        Some("E3010")
+    );
+    interpreter.exec("Level.Low = 1", "test", true).unwrap_err();
+    assert_eq!(
+        interpreter.last_error().unwrap().code.as_deref(),
+        Some("E3024")
+    );
+    interpreter.exec("Level.Low", "test", true).unwrap();
+    let output = interpreter.replace_buffer(vec![]);
+    assert_eq!(String::from_utf8(output).unwrap(), "0\n");
+}
+
+#[test]
+fn test_lint() {
+    use crate::lint::{Lint, LintConfig, LintLevel};
+
+    let interpreter = Interpreter::new(Vec::<u8>::new());
+    let code = r#"
+n = 10
+def f x n =
+    unused = 1
+    _ignored = 2
+    captured = 3
+    g = fn = captured
+    if x = 1 then return g() end
+    return x
+    n
+end
+assert(n.float() == 1.0)
+"#;
+    let config = LintConfig::default();
+    let warnings = interpreter.lint(code, "test", &config).unwrap();
+    for expected in ["W0000", "`unused`", "W0001", "W0003", "W0004"] {
+        assert!(warnings.contains(expected), "{expected} in {warnings}");
+    }
+
+    let classes = r#"
+interface Show show, name end
+A = {__implements = [Show], show = fn self = 'a'}
+B = {__implements = [Show]}
+def B.show self = 'b' end
+B.name = fn self = 'B'
+C = {A..., __implements = [Show]}
+"#;
+    let warnings = interpreter.lint(classes, "test", &config).unwrap();
+    for expected in ["W0005", "`A` does not implement", "Missing `name`"] {
+        assert!(warnings.contains(expected), "{expected} in {warnings}");
+    }
+    for unexpected in ["W0002", "`_ignored`", "`captured`", "`g`"] {
+        assert!(!warnings.contains(unexpected), "{unexpected} in {warnings}");
+    }
+    for unexpected in ["`B`", "`C`"] {
+        assert!(!warnings.contains(unexpected), "{unexpected} in {warnings}");
+    }
+
+    let decorated = r#"
+def deprecated message = fn f = f end
+@deprecated('Use new')
+def old x = x end
+def new x = x end
+old(new(1))
+"#;
+    let warnings = interpreter.lint(decorated, "test", &config).unwrap();
+    for expected in ["W0006", "Function `old` is deprecated", "Use new"] {
+        assert!(warnings.contains(expected), "{expected} in {warnings}");
+    }
+    assert!(!warnings.contains("`new`"), "{warnings}");
+
+    let constant = r#"
+def f x p =
+    if 1 > 2 then return 0 elsif x == x then return 1 end
+    until true do x = x + 1 end
+    until not false do end
+    if p.y < p.y or x < p.y then return 2 end
+    x
+end
+"#;
+    let warnings = interpreter.lint(constant, "test", &config).unwrap();
+    for expected in [
+        "W0007",
+        "This branch is never taken",
+        "It is folded to `false`",
+        "W0008",
+        "`x == x` compares a value with itself",
+        "`p.y < p.y`",
+        "It is always `true`",
+        "W0009",
+        "The loop never runs",
+    ] {
+        assert!(warnings.contains(expected), "{expected} in {warnings}");
+    }
+    assert!(!warnings.contains("`x < p.y`"), "{warnings}");
+    // Same warnings are reported by compiling with lint rules
+    let mut compiler = Interpreter::new(Vec::<u8>::new());
+    compiler.set_lint(Some(config.clone()));
+    compiler.check(constant, "test", true).unwrap();
+    let mut rendered = vec![];
+    compiler.emit_diagnostics(&mut rendered).unwrap();
+    let rendered = String::from_utf8(rendered).unwrap();
+    assert_eq!(rendered, warnings);
+
+    let config = config.set(Lint::ShadowedName, LintLevel::Deny);
+    let errors = interpreter.lint(code, "test", &config).unwrap_err();
+    assert!(errors.contains("Parameter `n` shadows a variable"));
+
+    let config = LintConfig::default().set_all(LintLevel::Allow);
+    assert_eq!(interpreter.lint(code, "test", &config).unwrap(), "");
+    assert_eq!(Lint::from_name("float-equality"), Some(Lint::FloatEquality));
+    assert!(interpreter.lint("f(", "test", &config).is_err());
+}
+
+#[test]
+fn test_render_options() {
+    use crate::{lint::LintConfig, ColorChoice, RenderOptions};
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    let code = "assert(1.0 == 1.0)";
+    let config = LintConfig::default();
+    let plain = interpreter.lint(code, "test", &config).unwrap();
+    assert!(!plain.contains('\x1b'));
+    assert!(plain.contains("are not defined between floats"));
+
+    interpreter.set_render_options(
+        RenderOptions::default()
+            .color(ColorChoice::Always)
+            .width(Some(40)),
+    );
+    let wrapped = interpreter.lint(code, "test", &config).unwrap();
+    assert!(wrapped.contains('\x1b'));
+    assert!(!wrapped.contains("are not defined between floats"));
+    assert!(wrapped.contains("between floats and panic at"));
+
+    interpreter.set_render_options(RenderOptions::default());
+    let error = interpreter.exec("1 +", "test", true).unwrap_err();
+    let mut emitted = vec![];
+    interpreter.emit_diagnostics(&mut emitted).unwrap();
+    assert_eq!(String::from_utf8(emitted).unwrap(), error);
+    let mut written = String::new();
+    interpreter.write_diagnostics(&mut written).unwrap();
+    assert_eq!(written, error);
+}
+
+#[test]
+fn test_warnings() {
+    use crate::{lint::LintConfig, WarningLevel, WarningOptions};
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    let code = "x = 1e400\ny = 1e-400\nz = 0.0e-400\nuntil (true) do end";
+    interpreter.exec(code, "test", true).unwrap();
+    assert_eq!(interpreter.warning_count(), 3);
+    let mut warnings = String::new();
+    interpreter.write_diagnostics(&mut warnings).unwrap();
+    assert_eq!(warnings.matches("W1000").count(), 2, "{warnings}");
+    assert!(warnings.contains("W1500"), "{warnings}");
+
+    // Warnings of compilation are rendered with runtime errors
+    let error = interpreter.exec("if (false) then 1 end\n1 + ()", "test", true);
+    let error = error.unwrap_err();
+    assert!(error.contains("W1500") && error.contains("E3"), "{error}");
+    assert!(interpreter
+        .last_error()
+        .unwrap()
+        .code
+        .as_ref()
+        .unwrap()
+        .starts_with('E'));
+
+    let options = WarningOptions::default()
+        .with_flag("no-W1000")
+        .and_then(|options| options.with_flag("no-unnecessary"))
+        .unwrap();
+    interpreter.set_warning_options(options);
+    interpreter.exec(code, "test", true).unwrap();
+    assert_eq!(interpreter.warning_count(), 0);
+    assert!(WarningOptions::default().with_flag("no-").is_err());
+    assert!(WarningOptions::default().with_flag("error").is_err());
+
+    interpreter.set_warning_options(WarningOptions::default().level(WarningLevel::Allow));
+    interpreter.exec(code, "test", true).unwrap();
+    assert_eq!(interpreter.warning_count(), 0);
+
+    interpreter.set_warning_options(WarningOptions::default().level(WarningLevel::Deny));
+    let error = interpreter.exec(code, "test", true).unwrap_err();
+    assert!(error.contains("error[W1000]"), "{error}");
+
+    // Lints follow warning options too
+    let code = "def f =\n    a = 1\nend";
+    let config = LintConfig::default();
+    assert!(interpreter.lint(code, "test", &config).is_err());
+    interpreter.set_warning_options(WarningOptions::default().suppress("unused"));
+    assert_eq!(interpreter.lint(code, "test", &config).unwrap(), "");
+}
+
+#[test]
+fn test_source_map() {
+    use crate::{ffi::SourceLocation, SourceMap};
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    // Line 2 to 4 are copied from `a.tpl`, line 5 from `b.tpl`
+    let code = "x = 1\ndef f a =\n  a\nend\ny = z\n";
+    let map = SourceMap::new()
+        .map(6..24, "a.tpl", 3, 5)
+        .map(24..29, "b.tpl", 7, 1);
+    assert!(interpreter.set_source_map("gen", Some(map)).is_none());
+
+    let error = interpreter.exec(code, "gen", true).unwrap_err();
+    assert!(error.contains("Generated from `b.tpl:7:5`"), "{error}");
+    assert!(!error.contains("a.tpl"), "{error}");
+
+    interpreter.exec(&code[..24], "gen", true).unwrap();
+    assert_eq!(
+        interpreter.function_info("f").unwrap().location,
+        Some(SourceLocation {
+            path: "a.tpl".to_string(),
+            line: 3,
+            column: 5,
+        })
+    );
+
+    // Unmapped spans and other files are reported as is
+    let error = interpreter.exec("y = z", "other", true).unwrap_err();
+    assert!(!error.contains("Generated from"), "{error}");
+    assert!(interpreter.set_source_map("gen", None).is_some());
+    let error = interpreter.exec(code, "gen", true).unwrap_err();
+    assert!(!error.contains("Generated from"), "{error}");
+}
+
+#[test]
+fn test_keyword_aliases() {
+    use crate::{KeywordAliases, WarningOptions};
+
+    let code = "function f x = x * 2 end\ny = f(2)";
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    assert!(interpreter.exec(code, "test", true).is_err());
+
+    let aliases = KeywordAliases::default().alias("function", "def").unwrap();
+    interpreter.set_keyword_aliases(aliases);
+    interpreter.exec(code, "test", true).unwrap();
+    assert_eq!(interpreter.get_global_as::<i64>("y"), Ok(4));
+    assert_eq!(interpreter.warning_count(), 1);
+    let mut diagnostics = vec![];
+    interpreter.emit_diagnostics(&mut diagnostics).unwrap();
+    let diagnostics = String::from_utf8(diagnostics).unwrap();
+    assert!(diagnostics.contains("warning[W1002]"), "{diagnostics}");
+    assert!(
+        diagnostics.contains("`function` is an alias of keyword `def`"),
+        "{diagnostics}"
+    );
+
+    // An alias is a keyword everywhere, so it can not be a name any more
+    interpreter
+        .exec("t = {function = 1}", "test", true)
+        .unwrap_err();
+    interpreter.set_warning_options(WarningOptions::default().suppress("keyword-alias"));
+    interpreter.exec(code, "test", true).unwrap();
+    assert_eq!(interpreter.warning_count(), 0);
+
+    assert!(KeywordAliases::default().alias("function", "func").is_err());
+    assert!(KeywordAliases::default().alias("if", "def").is_err());
+    assert!(KeywordAliases::default().alias("1st", "def").is_err());
+    assert!(KeywordAliases::default().alias("and_also", "and").is_ok());
+}
+
+#[test]
+fn test_exec_reader() {
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    // Several chunks, with statements spanning lines at every possible cut
+    let mut code = "total = 0\nn = 0\ndef add x =\n  total = total + x\nend\n".to_string();
+    for i in 0..5000 {
+        code += &format!(
+            "add({i})\nt = {{\n  a = {i}\n}}\nif t.a > 0 then\n  n = t.a\nelse\n  n = 0\nend\n"
+        );
+    }
+    interpreter.exec_reader(code.as_bytes(), "data.dm").unwrap();
+    assert_eq!(interpreter.get_global_as::<i64>("total"), Ok(12497500));
+    assert_eq!(interpreter.get_global_as::<i64>("n"), Ok(4999));
+
+    // Lines are counted from the start of the whole source
+    let lines = code.lines().count();
+    code += "y = missing\n";
+    let error = interpreter
+        .exec_reader(code.as_bytes(), "data.dm")
+        .unwrap_err();
+    assert!(error.contains(&format!("data.dm:{}", lines + 1)), "{error}");
+    let error = interpreter
+        .exec_reader("x = (1 +\n".as_bytes(), "data.dm")
+        .unwrap_err();
+    assert!(error.contains("data.dm:1"), "{error}");
+}
+
+#[test]
+fn test_stats() {
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    assert_eq!(interpreter.stats().instructions, 0);
+    interpreter
+        .exec("i = 0\nuntil i >= 10 do i = i + 1 end", "test", true)
+        .unwrap();
+    let stats = interpreter.stats();
+    assert!(stats.instructions >= 30, "{stats:?}");
+
+    // Counters accumulate until they are reset
+    interpreter
+        .exec(
+            "i = 0\nuntil i >= 1000 do\n  l = [i, {a = i}]\n  i = i + 1\nend",
+            "test",
+            true,
+        )
+        .unwrap();
+    let more = interpreter.stats();
+    assert!(more.instructions > stats.instructions + 1000, "{more:?}");
+    assert!(more.allocations >= 2000, "{more:?}");
+    assert!(more.collections > 0, "{more:?}");
+    interpreter.reset_stats();
+    let stats = interpreter.stats();
+    assert_eq!((stats.instructions, stats.allocations), (0, 0));
+    assert_eq!(stats.objects, more.objects);
+}
+
+#[test]
+fn test_execution_report() {
+    use crate::ffi::DiatomValue;
+    use crate::host::HAS_STD_CLOCK;
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    assert!(interpreter.last_report().is_none());
+    interpreter
+        .exec(
+            "l = []\ni = 0\nuntil i >= 100 do\n  l = [l, {a = i}]\n  i = i + 1\nend",
+            "test",
+            true,
+        )
+        .unwrap();
+    let report = *interpreter.last_report().unwrap();
+    assert!(report.instructions >= 300, "{report:?}");
+    assert!(report.allocations >= 200, "{report:?}");
+    // All lists are reachable from `l` at the end
+    let (strings, objects) = interpreter.gc.live_count();
+    assert!(report.peak_gc_objects >= objects, "{report:?}");
+    assert!(report.peak_strings >= strings, "{report:?}");
+    assert_eq!(report.wall_time.is_some(), HAS_STD_CLOCK);
+
+    // Each execution is reported on its own
+    interpreter
+        .exec("def f x = x + 1 end", "test", true)
+        .unwrap();
+    let small = *interpreter.last_report().unwrap();
+    assert!(small.instructions < report.instructions, "{small:?}");
+    interpreter.call("f", &[DiatomValue::Int(1)]).unwrap();
+    let call = *interpreter.last_report().unwrap();
+    assert!(call.instructions > 0, "{call:?}");
+    assert_eq!(call.allocations, 0);
+    interpreter.set_clock(None);
+    interpreter.exec("x = 1", "test", true).unwrap();
+    assert!(interpreter.last_report().unwrap().wall_time.is_none());
+}
+
+#[test]
+fn test_collect_garbage() {
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    // Collect by thresholds as without tests
+    interpreter.set_gc_stress(false);
+    interpreter.set_global("piece", "x".repeat(200));
+    let bytes = interpreter.gc.string_bytes();
+    interpreter
+        .exec(
+            "s = ''\ni = 0\nuntil i >= 1000 do\n  s = s + piece\n  i = i + 1\nend",
+            "test",
+            true,
+        )
+        .unwrap();
+    // 100 MB of strings are allocated, less than twice the result is kept
+    assert!(
+        interpreter.gc.string_bytes() - bytes < 3 * 200_000,
+        "{}",
+        interpreter.gc.string_bytes()
+    );
+
+    let (_, objects) = interpreter.gc.live_count();
+    interpreter
+        .exec(
+            "i = 0\nuntil i >= 10000 do\n  l = [i]\n  i = i + 1\nend",
+            "test",
+            true,
+        )
+        .unwrap();
+    assert!(interpreter.gc.live_count().1 < objects * 2 + 1000);
+}
+
+#[test]
+fn test_host_services() {
+    use crate::host::{Clock, FileSystem};
+    use crate::SandboxPolicy;
+    use std::{
+        io,
+        path::Path,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    /// Advance a second every time it is read
+    #[derive(Default)]
+    struct FastClock(AtomicU64);
+    impl Clock for FastClock {
+        fn now(&self) -> Duration {
+            Duration::from_secs(self.0.fetch_add(1, Ordering::Relaxed))
+        }
+    }
+
+    /// A single module named `answer`
+    struct Files;
+    impl FileSystem for Files {
+        fn read_to_string(&self, path: &Path) -> io::Result<String> {
+            match path.file_name().and_then(|name| name.to_str()) {
+                Some("answer.dm") => Ok("{answer = 42}".to_string()),
+                _ => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    let mut policy = SandboxPolicy::deny_all();
+    policy.timeout = Some(Duration::from_secs(10));
+    interpreter.set_policy(policy);
+    interpreter.set_clock(Some(Arc::new(FastClock::default())));
+    let err = interpreter.exec("loop end", "test", true).unwrap_err();
+    assert!(err.contains("E3021"));
+    // Timeout is ignored without a clock
+    interpreter.set_clock(None);
+    interpreter
+        .exec("i = 0\nuntil i > 10000 do i = i + 1 end", "test", true)
+        .unwrap();
+
+    interpreter.with_search_path(std::env::temp_dir()).unwrap();
+    interpreter.set_file_system(Arc::new(Files));
+    interpreter
+        .exec("import {answer} from answer", "test", true)
+        .unwrap();
+    assert_eq!(interpreter.get_global_as::<i64>("answer"), Ok(42));
+    assert!(interpreter.exec("import other", "test", true).is_err());
+}
+
+#[test]
+fn test_deadline() {
+    use crate::extension::{Extension, ExtensionKind};
+    use crate::ffi::{DiatomValue, ForeignFunction};
+    use crate::host::Clock;
+    use crate::SandboxPolicy;
+    use ahash::AHashMap;
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    /// Advance a second every time it is read
+    #[derive(Default)]
+    struct FastClock(AtomicU64);
+    impl Clock for FastClock {
+        fn now(&self) -> Duration {
+            Duration::from_secs(self.0.fetch_add(1, Ordering::Relaxed))
+        }
+    }
+
+    // Block until the deadline if there is one
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Vec<u8>>>> = AHashMap::default();
+    funcs.insert(
+        "block".to_string(),
+        Arc::new(|state, _, _| {
+            let Some(deadline) = state.deadline().cloned() else {
+                return Ok(DiatomValue::Unit);
+            };
+            while !deadline.passed() {}
+            Err(state.interrupt("Blocking is interrupted"))
+        }),
+    );
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter
+        .load_ext(Extension {
+            name: "block".to_string(),
+            kind: ExtensionKind::ForeignFunctions(funcs),
+        })
+        .unwrap();
+    interpreter
+        .exec("import {block} from block\nblock()", "test", true)
+        .unwrap();
+
+    let mut policy = SandboxPolicy::allow_all();
+    policy.timeout = Some(Duration::from_secs(10));
+    interpreter.set_policy(policy);
+    interpreter.set_clock(Some(Arc::new(FastClock::default())));
+    let err = interpreter
+        .exec("import {block} from block\nblock()", "test", true)
+        .unwrap_err();
+    assert!(err.contains("E3027"));
+    assert_eq!(
+        interpreter.last_error().unwrap().code.as_deref(),
+        Some("E3027")
+    );
+}
+
+#[test]
+fn test_event_hooks() {
+    use crate::ffi::{DiatomValue, EventHooks, ScriptError, State};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+    impl EventHooks<Vec<u8>> for Recorder {
+        fn on_call(&mut self, state: &State<Vec<u8>>, name: Option<&str>, args: &[DiatomValue]) {
+            let args: Vec<_> = args.iter().map(|arg| state.print(arg)).collect();
+            let name = name.unwrap_or("<anonymous>");
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("call {name}({})", args.join(", ")));
+        }
+        fn on_global_write(&mut self, state: &State<Vec<u8>>, name: &str, value: &DiatomValue) {
+            let value = state.print(value);
+            self.0.lock().unwrap().push(format!("{name} = {value}"));
+        }
+        fn on_error(&mut self, error: &ScriptError) {
+            let code = error.code.clone().unwrap_or_default();
+            self.0.lock().unwrap().push(format!("error {code}"));
+        }
+    }
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.exec("count = 0", "test", true).unwrap();
+    assert!(interpreter
+        .set_hooks(Some(Box::new(Recorder(events.clone()))))
+        .is_none());
+    let code = r#"
+def add n =
+    y = n + 1
+    count = count + y
+end
+add(1)
+id = fn x = x
+id('s')
+"#;
+    interpreter.exec(code, "test", true).unwrap();
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            "add = <fn add at test:2:1>",
+            "call add(1)",
+            "count = 2",
+            "id = <fn id at test:7:6>",
+            "call id(s)",
+        ]
+    );
+
+    events.lock().unwrap().clear();
+    assert!(interpreter.exec("add(true)", "test", true).is_err());
+    assert_eq!(*events.lock().unwrap(), ["call add(true)", "error E3001"]);
+
+    interpreter.set_hooks(None);
+    interpreter.exec("add(2)", "test", true).unwrap();
+    assert_eq!(events.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn test_membership() {
+    test_ok!("2 in [1, 2, 3]", "true");
+    test_ok!("'b' in [1, 'b']", "true");
+    test_ok!("4 not in (1, 2, 3)", "true");
+    test_ok!("1 in [1.0]", "false");
+    test_ok!("'ell' in 'hello'", "true");
+    test_ok!("'x' not in 'hello'", "true");
+    test_ok!("'a' in {a = 1}", "true");
+    test_ok!("'b' in {a = 1}", "false");
+    test_ok!("t = {}\nt[3] = 1\n3 in t", "true");
+    test_ok!("xs = [1]\nxs in [xs]", "true");
+    test_ok!("xs = [1]\nys = [1]\nys in [xs]", "false");
+    test_ok!("if 1 not in [2, 3] then 'yes' else 'no' end", "\"yes\"");
+    test_ok!("1 + 1 in [2] and true", "true");
+    test_err!("1 in 2");
+    test_err!("1 not in 'abc'");
+}
+
+#[test]
+fn test_identity() {
+    test_ok!("xs = [1]\nys = xs\nxs is ys", "true");
+    test_ok!("[1] is [1]", "false");
+    test_ok!("s = 'a'\ns is s", "true");
+    test_ok!("1 is 1", "true");
+    test_ok!("1 is 1.0", "false");
+    test_ok!("() is ()", "true");
+    test_ok!("[] is 1", "false");
+}
+
+#[test]
+fn test_conditions() {
+    test_ok!("true and false", "false");
+    test_ok!("false or true", "true");
+    // Right hand side is not evaluated, thus not checked either
+    test_ok!("false and 1", "false");
+    test_ok!("true or 1", "true");
+    test_err!("true and 1");
+    test_err!("false or 'a'");
+    test_err!("not 0");
+    test_err!("if 0 then 1 end");
+    test_err!("until () do end");
+}
+
+#[test]
+fn test_mixed_comparison() {
+    test_ok!("1 == 'a'", "false");
+    test_ok!("1 <> 'a'", "true");
+    test_ok!("[] == 0", "false");
+    test_ok!("() <> false", "true");
+    test_ok!("(1 < 2.5, 2 > 1.5)", "(true, true)");
+    test_err!("1 == 1.0");
+    test_err!("1 <= 2.5");
+    test_err!("[] == []");
+
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    let _ = interpreter.exec("1 < 'a'", "test", true).unwrap_err();
+    let error = interpreter.last_error().unwrap();
+    assert_eq!(error.code.as_deref(), Some("E3001"));
+    assert!(
+        error.message.contains("`Int` and `String`"),
+        "{}",
+        error.message
+    );
+}
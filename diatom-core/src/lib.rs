@@ -1,22 +1,58 @@
 //! Diatom Interpreter Core
+//!
+//! Parser, compiler and virtual machine of diatom, without the standard library. Applications
+//! should depend on crate `diatom`, which re-exports the public parts of this crate.
+//!
+//! Without feature `std`, the crate only depends on `alloc`. Host then supplies the clock, files
+//! and input, and diagnostics are rendered without color.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "unstable-ast")]
+pub mod ast;
+pub mod bundle;
+pub mod compat;
+pub mod doc;
 mod file_manager;
+pub mod format;
 mod frontend;
 mod gc;
+pub mod highlight;
+pub mod host;
 mod interpreter;
+pub mod lint;
+pub mod manifest;
+mod pipeline;
+pub mod syntax;
+pub mod tokens;
 mod vm;
 
 #[cfg(test)]
 mod tests;
 
+pub use compat::io::BufRead as IoRead;
+pub use compat::io::Write as IoWrite;
+pub use file_manager::{
+    ColorChoice, RenderOptions, SharedFile, SourceMap, SourceMapping, WarningLevel, WarningOptions,
+};
+pub use frontend::KeywordAliases;
+pub use gc::PrintOptions;
 pub use interpreter::std_core::StdCore;
 pub use interpreter::Interpreter;
-pub use std::io::Write as IoWrite;
+pub use interpreter::{FsPolicy, Program, SandboxPolicy};
+pub use pipeline::{Compiler, ParsedSource, Parser, Vm};
 
 /// Diatom Foreign Function Interface
 pub mod ffi {
     /// Unboxed Primitive Types
     pub use super::gc::Reg as DiatomValue;
+    /// Default depth limit of `State::inspect`
+    pub use super::gc::DEFAULT_INSPECT_DEPTH;
     use super::interpreter::ffi;
+    pub use ffi::ArgType;
+    pub use ffi::Args;
+    pub use ffi::DiatomData;
     pub use ffi::DiatomList;
     pub use ffi::DiatomListMut;
     pub use ffi::DiatomObject;
@@ -25,12 +61,27 @@ pub mod ffi {
     pub use ffi::DiatomTableMut;
     pub use ffi::DiatomTuple;
     pub use ffi::DiatomTupleMut;
+    pub use ffi::DiatomValueKind;
+    pub use ffi::EventHooks;
+    pub use ffi::ExecutionReport;
+    pub use ffi::FromDiatom;
+    pub use ffi::FunctionHandle;
+    pub use ffi::FunctionInfo;
+    pub use ffi::IntoDiatom;
+    pub use ffi::ModuleBuilder;
+    pub use ffi::RootedValue;
+    pub use ffi::ScriptError;
+    pub use ffi::SourceLocation;
     pub use ffi::State;
+    pub use ffi::Stats;
+    pub use ffi::UserTypeBuilder;
     /// # Foreign Rust Function/Closure type
     ///
     /// This function does not accept due to potential recursive calls on a FnMut would violating
     /// borrow rules. You may want to use interior mutability if Fn is not flexible enough.
-    /// External function should **NEVER PANIC**, otherwise it will crush the virtual machine.
+    /// With feature `std`, a panic of an external function is caught and reported as a runtime
+    /// error. Without it, external function should **NEVER PANIC**, otherwise it will crash the
+    /// virtual machine.
     ///
     /// # External function parameters:
     /// * `State` - Access state and heap memory of the virtual machine.
@@ -45,7 +96,13 @@ pub mod ffi {
     /// This will cause virtual machine to enter **panic mode** and stop execution.
     /// * If return value is `DiatomValue::Str` or `DiatomValue::Ref`, the reference id is checked.
     /// An invalid id would cause virtual machine to enter **panic mode** and stop execution.
-    pub type ForeignFunction<Buffer> = dyn Fn(&mut State<Buffer>, &[DiatomValue], &mut Buffer) -> Result<DiatomValue, String>
+    /// * With feature `std`, a panic of the function is caught and reported as a runtime error,
+    ///   it does not unwind into host.
+    pub type ForeignFunction<Buffer> = dyn Fn(
+            &mut State<Buffer>,
+            &[DiatomValue],
+            &mut Buffer,
+        ) -> Result<DiatomValue, alloc::string::String>
         + Send
         + Sync;
 }
@@ -54,5 +111,5 @@ pub mod ffi {
 pub mod extension {
     pub use super::interpreter::std_core::Extension;
     pub use super::interpreter::std_core::ExtensionKind;
-    pub use ahash::AHashMap;
+    pub use crate::compat::AHashMap;
 }
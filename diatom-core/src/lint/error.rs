@@ -0,0 +1,170 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use codespan_reporting::diagnostic::{Label, Severity};
+
+use crate::file_manager::{Diagnostic, Loc};
+
+use super::Lint;
+
+/// Warning code for lints
+///
+/// W0000 - W0999
+pub enum WarningCode {
+    /// W0000 Variable is never used
+    UnusedVariable(Loc, String),
+    /// W0001 Unreachable code
+    ///
+    /// Parameters:
+    /// - 1 Unreachable statements
+    /// - 2 The `return`, `break` or `continue` before them
+    UnreachableCode(Loc, Loc),
+    /// W0002 Parameter shadows a variable
+    ///
+    /// Parameters:
+    /// - 1 Parameter
+    /// - 2 Name of parameter
+    /// - 3 Where the shadowed variable is defined
+    ShadowedName(Loc, String, Loc),
+    /// W0003 Assignment in condition
+    AssignInCondition(Loc),
+    /// W0004 Float compared with `==` or `<>`
+    FloatEquality(Loc),
+    /// W0005 Class misses methods of an interface it implements
+    ///
+    /// Parameters:
+    /// - 1 The interface in `__implements` of the class
+    /// - 2 Name of the class
+    /// - 3 Name of the interface
+    /// - 4 Names of missing methods
+    MissingMethod(Loc, String, String, Vec<String>),
+    /// W0006 Function defined with `@deprecated` is called
+    ///
+    /// Parameters:
+    /// - 1 The call
+    /// - 2 Name of the function
+    /// - 3 Message given to `@deprecated`
+    DeprecatedCall(Loc, String, Option<String>),
+    /// W0007 Condition of `if`, `elsif` or `until` is a constant
+    ///
+    /// Parameters:
+    /// - 1 The condition
+    /// - 2 Value it is folded to
+    /// - 3 What the value means to the branch or loop
+    ConstantCondition(Loc, bool, &'static str),
+    /// W0008 Value compared with itself
+    ///
+    /// Parameters:
+    /// - 1 The comparison
+    /// - 2 The comparison as written, e.g. `x == x`
+    /// - 3 Value of the comparison
+    TautologicalComparison(Loc, String, bool),
+    /// W0009 Body of `until` loop is unreachable as the condition is always true
+    ///
+    /// Parameters:
+    /// - 1 Body of the loop
+    /// - 2 The condition
+    UnreachableLoopBody(Loc, Loc),
+}
+
+impl WarningCode {
+    pub fn lint(&self) -> Lint {
+        match self {
+            WarningCode::UnusedVariable(..) => Lint::UnusedVariable,
+            WarningCode::UnreachableCode(..) => Lint::UnreachableCode,
+            WarningCode::ShadowedName(..) => Lint::ShadowedName,
+            WarningCode::AssignInCondition(..) => Lint::AssignInCondition,
+            WarningCode::FloatEquality(..) => Lint::FloatEquality,
+            WarningCode::MissingMethod(..) => Lint::MissingMethod,
+            WarningCode::DeprecatedCall(..) => Lint::DeprecatedCall,
+            WarningCode::ConstantCondition(..) => Lint::ConstantCondition,
+            WarningCode::TautologicalComparison(..) => Lint::TautologicalComparison,
+            WarningCode::UnreachableLoopBody(..) => Lint::UnreachableCode,
+        }
+    }
+
+    pub fn into_diagnostic(self, severity: Severity) -> Diagnostic {
+        let diagnostic = Diagnostic::new(severity);
+        match self {
+            WarningCode::UnusedVariable(loc, name) => diagnostic
+                .with_code("W0000")
+                .with_message(format!("Variable `{name}` is never used"))
+                .with_labels(vec![Label::primary(loc.fid, loc)])
+                .with_notes(vec![format!("Rename it to `_{name}` if this is intended")]),
+            WarningCode::UnreachableCode(loc, exit) => diagnostic
+                .with_code("W0001")
+                .with_message("Unreachable code")
+                .with_labels(vec![
+                    Label::primary(loc.fid, loc),
+                    Label::secondary(exit.fid, exit)
+                        .with_message("Any code following this is unreachable"),
+                ]),
+            WarningCode::ShadowedName(loc, name, previous) => diagnostic
+                .with_code("W0002")
+                .with_message(format!("Parameter `{name}` shadows a variable"))
+                .with_labels(vec![
+                    Label::primary(loc.fid, loc),
+                    Label::secondary(previous.fid, previous)
+                        .with_message(format!("`{name}` is defined here")),
+                ]),
+            WarningCode::AssignInCondition(loc) => diagnostic
+                .with_code("W0003")
+                .with_message("Assignment used as condition")
+                .with_labels(vec![Label::primary(loc.fid, loc)])
+                .with_notes(vec!["Use `==` to compare values".to_string()]),
+            WarningCode::FloatEquality(loc) => diagnostic
+                .with_code("W0004")
+                .with_message("Float compared for equality")
+                .with_labels(vec![Label::primary(loc.fid, loc)])
+                .with_notes(vec![
+                    "`==` and `<>` are not defined between floats and panic at runtime".to_string(),
+                    "Compare the difference with a tolerance instead, e.g. `(a - b).abs() < 1e-9`"
+                        .to_string(),
+                ]),
+            WarningCode::MissingMethod(loc, class, interface, missing) => {
+                let missing: Vec<_> = missing.iter().map(|method| format!("`{method}`")).collect();
+                diagnostic
+                    .with_code("W0005")
+                    .with_message(format!(
+                        "`{class}` does not implement all methods of `{interface}`"
+                    ))
+                    .with_labels(vec![Label::primary(loc.fid, loc)
+                        .with_message(format!("Missing {}", missing.join(", ")))])
+            }
+            WarningCode::DeprecatedCall(loc, name, message) => {
+                let diagnostic = diagnostic
+                    .with_code("W0006")
+                    .with_message(format!("Function `{name}` is deprecated"))
+                    .with_labels(vec![Label::primary(loc.fid, loc)]);
+                match message {
+                    Some(message) => diagnostic.with_notes(vec![message]),
+                    None => diagnostic,
+                }
+            }
+            WarningCode::ConstantCondition(loc, value, effect) => diagnostic
+                .with_code("W0007")
+                .with_message("Condition is constant")
+                .with_labels(vec![Label::primary(loc.fid, loc).with_message(effect)])
+                .with_notes(vec![format!("It is folded to `{value}` at compile time")]),
+            WarningCode::TautologicalComparison(loc, comparison, value) => diagnostic
+                .with_code("W0008")
+                .with_message(format!("`{comparison}` compares a value with itself"))
+                .with_labels(vec![Label::primary(loc.fid, loc)])
+                .with_notes(vec![format!("It is always `{value}`")]),
+            WarningCode::UnreachableLoopBody(loc, condition) => diagnostic
+                .with_code("W0009")
+                .with_message("Loop body is unreachable")
+                .with_labels(vec![
+                    Label::primary(loc.fid, loc),
+                    Label::secondary(condition.fid, condition)
+                        .with_message("The loop ends before the first iteration"),
+                ])
+                .with_notes(vec![
+                    "Condition of `until` is folded to `true` at compile time".to_string(),
+                ]),
+        }
+    }
+}
@@ -0,0 +1,742 @@
+//! Static checks on the syntax tree, see `Interpreter::lint`
+mod error;
+
+use crate::compat::{AHashMap, AHashSet};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use codespan_reporting::diagnostic::Severity;
+
+use crate::{
+    file_manager::{Loc, Warning},
+    frontend::{
+        parser::{
+            ast::{Const, Expr, OpInfix, OpPrefix, Stmt},
+            visit::{walk_expr, Visitor},
+        },
+        Symbol,
+    },
+    interpreter::ConstFolder,
+};
+
+use error::WarningCode;
+
+/// A rule checked by the linter
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A variable local to a function is assigned but never read
+    UnusedVariable,
+    /// Statements following `return`, `break` or `continue` in the same block
+    UnreachableCode,
+    /// A parameter has the same name as a variable of an enclosing scope
+    ShadowedName,
+    /// `=` used as condition of `if`, `elsif` or `until`
+    AssignInCondition,
+    /// `==` or `<>` with an operand that is obviously a float
+    FloatEquality,
+    /// A class misses methods of an interface listed in its `__implements`
+    MissingMethod,
+    /// A function defined with decorator `@deprecated` is called
+    DeprecatedCall,
+    /// Condition of `if`, `elsif` or `until` made of literals, e.g. `if 1 > 2 then`
+    ConstantCondition,
+    /// A variable or its attribute compared with itself, e.g. `x == x`
+    TautologicalComparison,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 9] = [
+        Lint::UnusedVariable,
+        Lint::UnreachableCode,
+        Lint::ShadowedName,
+        Lint::AssignInCondition,
+        Lint::FloatEquality,
+        Lint::MissingMethod,
+        Lint::DeprecatedCall,
+        Lint::ConstantCondition,
+        Lint::TautologicalComparison,
+    ];
+
+    /// Name of the rule in kebab case, e.g. `unused-variable`
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::ShadowedName => "shadowed-name",
+            Lint::AssignInCondition => "assign-in-condition",
+            Lint::FloatEquality => "float-equality",
+            Lint::MissingMethod => "missing-method",
+            Lint::DeprecatedCall => "deprecated-call",
+            Lint::ConstantCondition => "constant-condition",
+            Lint::TautologicalComparison => "tautological-comparison",
+        }
+    }
+
+    /// Look up a rule by `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+
+    fn default_level(self) -> LintLevel {
+        match self {
+            Lint::ShadowedName => LintLevel::Allow,
+            _ => LintLevel::Warn,
+        }
+    }
+}
+
+/// How a violated rule is reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LintLevel {
+    /// Not checked
+    Allow,
+    /// Reported as a warning
+    Warn,
+    /// Reported as an error
+    Deny,
+}
+
+/// Level of each rule
+///
+/// All rules are warnings by default except `Lint::ShadowedName`, which is allowed since
+/// parameters like `x` are commonly reused in nested closures.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LintConfig {
+    levels: [LintLevel; Lint::ALL.len()],
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            levels: Lint::ALL.map(Lint::default_level),
+        }
+    }
+}
+
+impl LintConfig {
+    /// Set level of `lint`
+    pub fn set(mut self, lint: Lint, level: LintLevel) -> Self {
+        self.levels[lint as usize] = level;
+        self
+    }
+
+    /// Set level of all rules
+    pub fn set_all(mut self, level: LintLevel) -> Self {
+        self.levels = [level; Lint::ALL.len()];
+        self
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        self.levels[lint as usize]
+    }
+}
+
+/// Check `ast` of a file and return diagnostics in order of appearance
+///
+/// Rules set to `LintLevel::Deny` are returned as warnings with severity of error.
+///
+/// Variables of the top level scope may be used by other modules or later inputs of REPL, thus
+/// only variables defined in functions are checked for being unused.
+pub(crate) fn lint(ast: &[Stmt], config: &LintConfig) -> Vec<Warning> {
+    let mut linter = Linter {
+        config,
+        warnings: vec![],
+    };
+    let mut scope = Scope::default();
+    linter.stmts(ast, &mut scope);
+    linter.interfaces(ast);
+    linter.deprecated_calls(ast);
+    let functions = core::mem::take(&mut scope.functions);
+    for function in functions {
+        linter.function(function, &[&scope]);
+    }
+    let mut warnings = linter.warnings;
+    warnings.sort_by_key(|(loc, _)| loc.start);
+    warnings.into_iter().map(|(_, warning)| warning).collect()
+}
+
+enum Body<'a> {
+    Stmts(&'a [Stmt]),
+    Expr(&'a Expr),
+}
+
+struct Function<'a> {
+    parameters: &'a [(Symbol, Loc)],
+    body: Body<'a>,
+}
+
+/// Names of a function or the top level, not including nested functions
+#[derive(Default)]
+struct Scope<'a> {
+    /// Names assigned and where they are assigned first
+    defined: Vec<(&'a str, &'a Loc)>,
+    read: AHashSet<&'a str>,
+    functions: Vec<Function<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    fn define(&mut self, name: &'a str, loc: &'a Loc) {
+        if !self.defined.iter().any(|(defined, _)| *defined == name) {
+            self.defined.push((name, loc));
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&'a Loc> {
+        self.defined
+            .iter()
+            .find(|(defined, _)| *defined == name)
+            .map(|(_, loc)| *loc)
+    }
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    /// Warnings with location to sort by
+    warnings: Vec<(Loc, Warning)>,
+}
+
+impl<'a> Linter<'a> {
+    fn warn(&mut self, loc: &Loc, warning: WarningCode) {
+        let severity = match self.config.level(warning.lint()) {
+            LintLevel::Allow => return,
+            LintLevel::Warn => Severity::Warning,
+            LintLevel::Deny => Severity::Error,
+        };
+        let warning = Warning {
+            name: warning.lint().name(),
+            diagnostic: warning.into_diagnostic(severity),
+        };
+        self.warnings.push((loc.clone(), warning));
+    }
+
+    /// Check classes at the top level against interfaces they list in `__implements`
+    ///
+    /// Only interfaces declared in the same file are known. Methods of a class are keys of its
+    /// table literal and attributes set by `def Class.method` or `Class.method = ...` at the top
+    /// level. Classes with a meta table or spread entries are not checked since their methods
+    /// are not known.
+    fn interfaces(&mut self, ast: &[Stmt]) {
+        let mut interfaces: AHashMap<&str, &[(Symbol, Loc)]> = AHashMap::default();
+        let mut classes = vec![];
+        let mut attributes: AHashMap<&str, AHashSet<&str>> = AHashMap::default();
+        let mut add_attribute = |target| {
+            if let Some((class, name)) = class_attribute(target) {
+                attributes.entry(class).or_default().insert(name);
+            }
+        };
+        for stmt in ast {
+            match stmt {
+                Stmt::Interface { name, methods, .. } => {
+                    interfaces.insert(name, methods);
+                }
+                Stmt::Def { variable, .. } => add_attribute(variable),
+                Stmt::Expr {
+                    expr:
+                        Expr::Infix {
+                            op: OpInfix::Assign,
+                            lhs,
+                            rhs,
+                            ..
+                        },
+                    ..
+                } => {
+                    add_attribute(lhs);
+                    if let (
+                        Expr::Id { name, .. },
+                        Expr::Const {
+                            value: Const::Table(entries),
+                            ..
+                        },
+                    ) = (lhs.as_ref(), rhs.as_ref())
+                    {
+                        classes.push((&**name, entries));
+                    }
+                }
+                _ => (),
+            }
+        }
+        for (class, entries) in classes {
+            if entries.iter().any(|(key, _, _)| key.is_none()) {
+                continue;
+            }
+            let implemented = entries.iter().find_map(|(key, value, _)| match value {
+                Expr::Const {
+                    value: Const::List(implemented),
+                    ..
+                } if key.as_deref() == Some("__implements") => Some(implemented),
+                _ => None,
+            });
+            for interface in implemented.into_iter().flatten() {
+                let Expr::Id { name, loc } = interface else {
+                    continue;
+                };
+                let Some(required) = interfaces.get(&**name) else {
+                    continue;
+                };
+                let missing: Vec<String> = required
+                    .iter()
+                    .filter(|(method, _)| {
+                        !entries
+                            .iter()
+                            .any(|(key, _, _)| key.as_deref() == Some(&**method))
+                            && !attributes
+                                .get(class)
+                                .is_some_and(|attributes| attributes.contains(&**method))
+                    })
+                    .map(|(method, _)| method.to_string())
+                    .collect();
+                if !missing.is_empty() {
+                    let warning = WarningCode::MissingMethod(
+                        loc.clone(),
+                        class.to_string(),
+                        name.to_string(),
+                        missing,
+                    );
+                    self.warn(loc, warning);
+                }
+            }
+        }
+    }
+
+    /// Check calls of functions defined at the top level with `@deprecated` or
+    /// `@deprecated(message)`
+    ///
+    /// Functions are known by name, a call of another variable of the same name also warns.
+    fn deprecated_calls(&mut self, ast: &[Stmt]) {
+        let mut deprecated: AHashMap<&str, Option<&str>> = AHashMap::default();
+        for stmt in ast {
+            let Stmt::Def {
+                decorators,
+                variable,
+                ..
+            } = stmt
+            else {
+                continue;
+            };
+            let Expr::Id { name, .. } = variable.as_ref() else {
+                continue;
+            };
+            for decorator in decorators {
+                match decorator {
+                    Expr::Id {
+                        name: decorator, ..
+                    } if decorator == "deprecated" => {
+                        deprecated.insert(name, None);
+                    }
+                    Expr::Call {
+                        lhs, parameters, ..
+                    } if matches!(lhs.as_ref(), Expr::Id { name, .. } if name == "deprecated") => {
+                        let message = match parameters.first() {
+                            Some(Expr::Const {
+                                value: Const::Str(message),
+                                ..
+                            }) => Some(message.as_str()),
+                            _ => None,
+                        };
+                        deprecated.insert(name, message);
+                    }
+                    _ => (),
+                }
+            }
+        }
+        if deprecated.is_empty() {
+            return;
+        }
+
+        struct Calls(Vec<(Symbol, Loc)>);
+        impl Visitor for Calls {
+            fn visit_expr(&mut self, expr: &Expr) {
+                if let Expr::Call { loc, lhs, .. } = expr {
+                    if let Expr::Id { name, .. } = lhs.as_ref() {
+                        self.0.push((name.clone(), loc.clone()));
+                    }
+                }
+                walk_expr(self, expr);
+            }
+        }
+        let mut calls = Calls(vec![]);
+        ast.iter().for_each(|stmt| calls.visit_stmt(stmt));
+        for (name, loc) in calls.0 {
+            if let Some(message) = deprecated.get(&*name) {
+                let warning = WarningCode::DeprecatedCall(
+                    loc.clone(),
+                    name.to_string(),
+                    message.map(str::to_string),
+                );
+                self.warn(&loc, warning);
+            }
+        }
+    }
+
+    /// Check a function and return names it reads, including reads of nested functions
+    fn function<'b>(
+        &mut self,
+        function: Function<'b>,
+        enclosing: &[&Scope<'b>],
+    ) -> AHashSet<&'b str> {
+        let mut scope = Scope::default();
+        for (name, loc) in function.parameters {
+            if let Some(previous) = enclosing.iter().rev().find_map(|s| s.lookup(name)) {
+                self.warn(
+                    loc,
+                    WarningCode::ShadowedName(loc.clone(), name.to_string(), previous.clone()),
+                );
+            }
+        }
+        match function.body {
+            Body::Stmts(stmts) => self.stmts(stmts, &mut scope),
+            Body::Expr(expr) => self.expr(expr, &mut scope),
+        }
+
+        // Parameters are visible to nested functions but are not checked for being unused
+        let mut visible = Scope::default();
+        function
+            .parameters
+            .iter()
+            .for_each(|(name, loc)| visible.define(name, loc));
+        scope
+            .defined
+            .iter()
+            .for_each(|(name, loc)| visible.define(name, loc));
+        let mut nested_enclosing = enclosing.to_vec();
+        nested_enclosing.push(&visible);
+        let mut read = core::mem::take(&mut scope.read);
+        for nested in core::mem::take(&mut scope.functions) {
+            read.extend(self.function(nested, &nested_enclosing));
+        }
+
+        for (name, loc) in &scope.defined {
+            let is_local = !function.parameters.iter().any(|(p, _)| p == name)
+                && !enclosing.iter().any(|s| s.lookup(name).is_some());
+            if is_local && !name.starts_with('_') && !read.contains(name) {
+                self.warn(
+                    loc,
+                    WarningCode::UnusedVariable((*loc).clone(), name.to_string()),
+                );
+            }
+        }
+        read
+    }
+
+    fn stmts<'b>(&mut self, stmts: &'b [Stmt], scope: &mut Scope<'b>) {
+        let exit = stmts.iter().position(|stmt| {
+            matches!(
+                stmt,
+                Stmt::Return { .. } | Stmt::Break { .. } | Stmt::Continue { .. }
+            )
+        });
+        if let Some(i) = exit {
+            if let (Some(first), Some(last)) = (stmts.get(i + 1), stmts.last()) {
+                if let (Some(first), Some(last), Some(exit)) =
+                    (first.loc(), last.loc(), stmts[i].loc())
+                {
+                    let loc = first.clone() + last.clone();
+                    self.warn(
+                        &loc,
+                        WarningCode::UnreachableCode(loc.clone(), exit.clone()),
+                    );
+                }
+            }
+        }
+        stmts.iter().for_each(|stmt| self.stmt(stmt, scope));
+    }
+
+    fn stmt<'b>(&mut self, stmt: &'b Stmt, scope: &mut Scope<'b>) {
+        match stmt {
+            Stmt::Expr { expr, .. } => self.expr(expr, scope),
+            // Bodies of macros are checked where they are expanded
+            Stmt::Continue { .. } | Stmt::Break { .. } | Stmt::Macro { .. } | Stmt::Error => (),
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value, scope)
+                }
+            }
+            Stmt::Loop {
+                condition, body, ..
+            } => {
+                if let Some(condition) = condition {
+                    self.condition(condition, scope);
+                    let loc = condition.get_loc();
+                    match (constant(condition), body.first(), body.last()) {
+                        (Some(true), Some(first), Some(last)) => {
+                            if let (Some(first), Some(last)) = (first.loc(), last.loc()) {
+                                let body = first.clone() + last.clone();
+                                self.warn(
+                                    &body,
+                                    WarningCode::UnreachableLoopBody(body.clone(), loc),
+                                );
+                            }
+                        }
+                        (Some(true), ..) => self.warn(
+                            &loc,
+                            WarningCode::ConstantCondition(
+                                loc.clone(),
+                                true,
+                                "The loop never runs",
+                            ),
+                        ),
+                        (Some(false), ..) => self.warn(
+                            &loc,
+                            WarningCode::ConstantCondition(
+                                loc.clone(),
+                                false,
+                                "The loop never ends, use `loop` instead",
+                            ),
+                        ),
+                        (None, ..) => (),
+                    }
+                }
+                self.stmts(body, scope);
+            }
+            Stmt::For {
+                loop_variable,
+                iterator,
+                body,
+                ..
+            } => {
+                self.assign(loop_variable, scope);
+                self.expr(iterator, scope);
+                self.stmts(body, scope);
+            }
+            Stmt::Defer { body, .. } => scope.functions.push(Function {
+                parameters: &[],
+                body: Body::Expr(body),
+            }),
+            Stmt::Def {
+                decorators,
+                variable,
+                parameters,
+                body,
+                ..
+            } => {
+                self.assign(variable, scope);
+                scope.functions.push(Function {
+                    parameters,
+                    body: Body::Stmts(body),
+                });
+                decorators
+                    .iter()
+                    .for_each(|decorator| self.expr(decorator, scope));
+            }
+            Stmt::Enum { name, name_loc, .. } | Stmt::Interface { name, name_loc, .. } => {
+                scope.define(name, name_loc)
+            }
+            Stmt::Import { items, .. } => {
+                for item in items {
+                    if let Some(name) = item.alias.as_ref().or(item.path.last()) {
+                        scope.define(name, &item.loc);
+                    }
+                }
+            }
+        }
+    }
+
+    fn condition<'b>(&mut self, condition: &'b Expr, scope: &mut Scope<'b>) {
+        if let Expr::Infix {
+            loc,
+            op: OpInfix::Assign,
+            ..
+        } = condition
+        {
+            self.warn(loc, WarningCode::AssignInCondition(loc.clone()));
+        }
+        self.expr(condition, scope);
+    }
+
+    /// Visit target of an assignment
+    fn assign<'b>(&mut self, target: &'b Expr, scope: &mut Scope<'b>) {
+        match target {
+            Expr::Id { loc, name } => scope.define(name, loc),
+            Expr::Infix {
+                op: OpInfix::Comma,
+                lhs,
+                rhs,
+                ..
+            } => {
+                self.assign(lhs, scope);
+                self.assign(rhs, scope);
+            }
+            Expr::Parentheses { content, .. } => self.assign(content, scope),
+            _ => self.expr(target, scope),
+        }
+    }
+
+    fn expr<'b>(&mut self, expr: &'b Expr, scope: &mut Scope<'b>) {
+        match expr {
+            Expr::Block { body, .. } => self.stmts(body, scope),
+            Expr::If {
+                conditional,
+                default,
+                ..
+            } => {
+                for (i, (condition, body)) in conditional.iter().enumerate() {
+                    self.condition(condition, scope);
+                    if let Some(value) = constant(condition) {
+                        let is_last = i + 1 == conditional.len() && default.is_none();
+                        let effect = match (value, is_last) {
+                            (false, _) => "This branch is never taken",
+                            (true, true) => "This branch is always taken",
+                            (true, false) => "Branches after this are never taken",
+                        };
+                        let loc = condition.get_loc();
+                        self.warn(
+                            &loc,
+                            WarningCode::ConstantCondition(loc.clone(), value, effect),
+                        );
+                    }
+                    self.stmts(body, scope);
+                }
+                if let Some(default) = default {
+                    self.stmts(default, scope);
+                }
+            }
+            Expr::Prefix { rhs, .. }
+            | Expr::OpenRange { lhs: rhs, .. }
+            | Expr::Spread { value: rhs, .. } => self.expr(rhs, scope),
+            Expr::Call {
+                lhs, parameters, ..
+            } => {
+                self.expr(lhs, scope);
+                parameters.iter().for_each(|p| self.expr(p, scope));
+            }
+            Expr::Index { lhs, rhs, .. } => {
+                self.expr(lhs, scope);
+                self.expr(rhs, scope);
+            }
+            Expr::Infix { op, lhs, rhs, loc } => match op {
+                OpInfix::Assign => {
+                    self.assign(lhs, scope);
+                    self.expr(rhs, scope);
+                }
+                // Right hand side is a name of attribute
+                OpInfix::Member | OpInfix::SafeMember | OpInfix::DoubleColon => {
+                    self.expr(lhs, scope)
+                }
+                _ => {
+                    if matches!(op, OpInfix::Eq | OpInfix::Ne) && (is_float(lhs) || is_float(rhs)) {
+                        self.warn(loc, WarningCode::FloatEquality(loc.clone()));
+                    }
+                    if let Some((comparison, value)) = tautology(*op, lhs, rhs) {
+                        let warning =
+                            WarningCode::TautologicalComparison(loc.clone(), comparison, value);
+                        self.warn(loc, warning);
+                    }
+                    self.expr(lhs, scope);
+                    self.expr(rhs, scope);
+                }
+            },
+            Expr::Fn {
+                parameters, body, ..
+            } => scope.functions.push(Function {
+                parameters,
+                body: Body::Expr(body),
+            }),
+            Expr::Id { name, .. } => {
+                scope.read.insert(name);
+            }
+            Expr::Parentheses { content, .. } => self.expr(content, scope),
+            Expr::Const { value, .. } => match value {
+                Const::List(items) => items.iter().for_each(|item| self.expr(item, scope)),
+                Const::Table(items) => items
+                    .iter()
+                    .for_each(|(_, value, _)| self.expr(value, scope)),
+                _ => (),
+            },
+            Expr::Error => (),
+        }
+    }
+}
+
+/// Whether `expr` is obviously a float, i.e. a float literal, a call to method `float` or
+/// arithmetic of them
+fn is_float(expr: &Expr) -> bool {
+    match expr {
+        Expr::Const {
+            value: Const::Float(_),
+            ..
+        } => true,
+        Expr::Prefix {
+            op: OpPrefix::Neg,
+            rhs,
+            ..
+        } => is_float(rhs),
+        Expr::Parentheses { content, .. } => is_float(content),
+        Expr::Call { lhs, .. } => matches!(
+            lhs.as_ref(),
+            Expr::Infix { op: OpInfix::Member, rhs, .. }
+                if matches!(rhs.as_ref(), Expr::Id { name, .. } if name == "float")
+        ),
+        Expr::Infix {
+            op: OpInfix::Plus | OpInfix::Minus | OpInfix::Mul | OpInfix::Div | OpInfix::Exp,
+            lhs,
+            rhs,
+            ..
+        } => is_float(lhs) || is_float(rhs),
+        _ => false,
+    }
+}
+
+/// Value of a condition made of literals and operators
+fn constant(condition: &Expr) -> Option<bool> {
+    match ConstFolder::eval_literal(condition) {
+        Some(Const::Bool(value)) => Some(value),
+        _ => None,
+    }
+}
+
+/// A variable or attribute of it as written, e.g. `point.x`
+fn operand_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Id { name, .. } => Some(name.to_string()),
+        Expr::Parentheses { content, .. } => operand_name(content),
+        Expr::Infix {
+            op: OpInfix::Member,
+            lhs,
+            rhs,
+            ..
+        } => match rhs.as_ref() {
+            Expr::Id { name, .. } => Some(format!("{}.{name}", operand_name(lhs)?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The comparison as written and its value if both operands are the same variable
+///
+/// Reading a variable has no side effect, thus both operands are the same value. Getters and
+/// meta tables could return different values for an attribute but they rarely do.
+fn tautology(op: OpInfix, lhs: &Expr, rhs: &Expr) -> Option<(String, bool)> {
+    let (op, value) = match op {
+        OpInfix::Eq => ("==", true),
+        OpInfix::Ne => ("<>", false),
+        OpInfix::Lt => ("<", false),
+        OpInfix::Gt => (">", false),
+        OpInfix::Le => ("<=", true),
+        OpInfix::Ge => (">=", true),
+        OpInfix::Is => ("is", true),
+        _ => return None,
+    };
+    let name = operand_name(lhs)?;
+    (operand_name(rhs)? == name).then(|| (format!("{name} {op} {name}"), value))
+}
+
+/// Class and attribute of `Class.attribute` or `Class::attribute`
+fn class_attribute(target: &Expr) -> Option<(&str, &str)> {
+    let Expr::Infix {
+        op: OpInfix::Member | OpInfix::DoubleColon,
+        lhs,
+        rhs,
+        ..
+    } = target
+    else {
+        return None;
+    };
+    match (lhs.as_ref(), rhs.as_ref()) {
+        (Expr::Id { name: class, .. }, Expr::Id { name, .. }) => Some((class, name)),
+        _ => None,
+    }
+}
@@ -0,0 +1,157 @@
+//! Project manifest `diatom.toml`, which tells where modules of a project are imported from
+//!
+//! ```toml
+//! [package]
+//! name = "app"
+//! # Directory of modules, relative to the manifest, the directory of the manifest by default
+//! src = "src"
+//!
+//! [dependencies]
+//! # `import util.strings` reads `strings.dm` from the module directory of `../util`
+//! util = { path = "../util" }
+//! ```
+//!
+//! A dependency is a directory. If it has a manifest of its own, its modules are in the `src`
+//! of that manifest, otherwise in the directory itself.
+//!
+//! Reading manifests needs feature `manifest`, without it hosts build a [`Manifest`] themselves.
+#[cfg(feature = "manifest")]
+use alloc::{format, string::ToString, vec};
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "manifest")]
+use std::{fs, io};
+
+#[cfg(feature = "std")]
+use crate::compat::path::Path;
+use crate::compat::path::PathBuf;
+
+/// File name of a manifest
+pub const MANIFEST_NAME: &str = "diatom.toml";
+
+/// Environment variable of extra search paths, separated as `PATH` of the platform
+pub const SEARCH_PATH_ENV: &str = "DIATOM_PATH";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    /// Name of the package, if given
+    pub name: Option<String>,
+    /// Directory of modules of the package
+    pub src: PathBuf,
+    /// Name and module directory of each dependency, imported as `import name.module`
+    pub dependencies: Vec<(String, PathBuf)>,
+}
+
+#[cfg(feature = "manifest")]
+fn invalid(path: &Path, message: impl core::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid manifest `{}`: {message}", path.display()),
+    )
+}
+
+#[cfg(feature = "std")]
+impl Manifest {
+    /// Find the manifest in `dir` or the closest of its ancestors
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|dir| dir.join(MANIFEST_NAME))
+            .find(|path| path.is_file())
+    }
+}
+
+#[cfg(feature = "manifest")]
+impl Manifest {
+    /// Read a manifest, paths in it are relative to the directory of the manifest
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let root = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&text, root).map_err(|err| invalid(path, err))
+    }
+
+    /// Parse a manifest whose paths are relative to `root`
+    ///
+    /// Module directories of dependencies are found by reading their manifests, if any.
+    /// Dependencies of a dependency are not read.
+    pub fn parse(text: &str, root: &Path) -> Result<Self, String> {
+        let table = parse_table(text)?;
+        let (name, src) = package(&table, root)?;
+
+        let mut dependencies = vec![];
+        if let Some(section) = section(&table, "dependencies")? {
+            for (key, value) in section {
+                let path = match value {
+                    toml::Value::String(path) => path.clone(),
+                    toml::Value::Table(dependency) => match dependency.get("path") {
+                        Some(path) => string(&format!("dependencies.{key}.path"), path)?,
+                        None => return Err(format!("Dependency `{key}` has no `path`")),
+                    },
+                    _ => return Err(format!("Dependency `{key}` should be a path or a table")),
+                };
+                let dir = root.join(path);
+                let manifest = dir.join(MANIFEST_NAME);
+                let src = if manifest.is_file() {
+                    let text = fs::read_to_string(&manifest).map_err(|err| err.to_string())?;
+                    package(&parse_table(&text)?, &dir)
+                        .map_err(|err| invalid(&manifest, err).to_string())?
+                        .1
+                } else {
+                    dir
+                };
+                dependencies.push((key.clone(), src));
+            }
+        }
+
+        for key in table.keys() {
+            if key != "package" && key != "dependencies" {
+                return Err(format!("Unknown section `{key}`"));
+            }
+        }
+
+        Ok(Self {
+            name,
+            src,
+            dependencies,
+        })
+    }
+}
+
+#[cfg(feature = "manifest")]
+fn parse_table(text: &str) -> Result<toml::Table, String> {
+    text.parse()
+        .map_err(|err: toml::de::Error| err.message().to_string())
+}
+
+#[cfg(feature = "manifest")]
+fn section<'a>(table: &'a toml::Table, name: &str) -> Result<Option<&'a toml::Table>, String> {
+    match table.get(name) {
+        None => Ok(None),
+        Some(toml::Value::Table(section)) => Ok(Some(section)),
+        Some(_) => Err(format!("`{name}` should be a table")),
+    }
+}
+
+#[cfg(feature = "manifest")]
+fn string(key: &str, value: &toml::Value) -> Result<String, String> {
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("`{key}` should be a string"))
+}
+
+#[cfg(feature = "manifest")]
+/// Name and module directory in section `package`
+fn package(table: &toml::Table, root: &Path) -> Result<(Option<String>, PathBuf), String> {
+    let mut name = None;
+    let mut src = root.to_path_buf();
+    if let Some(package) = section(table, "package")? {
+        for (key, value) in package {
+            let key_path = format!("package.{key}");
+            match key.as_str() {
+                "name" => name = Some(string(&key_path, value)?),
+                "src" => src = root.join(string(&key_path, value)?),
+                _ => return Err(format!("Unknown key `{key_path}`")),
+            }
+        }
+    }
+    Ok((name, src))
+}
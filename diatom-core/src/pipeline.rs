@@ -0,0 +1,172 @@
+//! Stages of running a script, for hosts that run them separately
+//!
+//! [`Parser`] checks syntax of source code, [`Compiler`] compiles checked source into a
+//! [`Program`] in the environment of an interpreter, and [`Vm`] executes a program with its own
+//! globals and heap. `Interpreter::exec` runs all of them at once. Syntax tree and byte code are
+//! not exposed, as they still change between releases.
+use crate::compat::ffi::{OsStr, OsString};
+use alloc::string::String;
+
+use crate::{
+    file_manager::FileManager, frontend, interpreter::std_core::StdCore, Interpreter, IoWrite,
+    KeywordAliases, Program, RenderOptions,
+};
+
+/// Check syntax of source code before it is compiled
+///
+/// Imports are not resolved, as modules are looked up by the compiler.
+#[derive(Clone, Debug, Default)]
+pub struct Parser {
+    keyword_aliases: KeywordAliases,
+    render_options: RenderOptions,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Recognize `aliases` as keywords, which are also used to compile the parsed source
+    pub fn keyword_aliases(mut self, aliases: KeywordAliases) -> Self {
+        self.keyword_aliases = aliases;
+        self
+    }
+
+    /// Options to render syntax errors
+    pub fn render_options(mut self, options: RenderOptions) -> Self {
+        self.render_options = options;
+        self
+    }
+
+    /// Check syntax of `code`, see `Interpreter::exec` for `source` and `is_phony`
+    ///
+    /// # Return
+    /// * Return an `Err(String)` that illustrates syntax errors if there is any.
+    pub fn parse(
+        &self,
+        code: impl Into<String>,
+        source: impl AsRef<OsStr>,
+        is_phony: bool,
+    ) -> Result<ParsedSource, String> {
+        let code = code.into();
+        let mut file_manager = FileManager::new();
+        frontend::Parser::new(&mut file_manager, &[])
+            .skip_imports()
+            .keyword_aliases(self.keyword_aliases.clone())
+            .parse_file_phony(source.as_ref(), code.as_str());
+        if file_manager.error_count() > 0 {
+            return Err(file_manager.render(&self.render_options));
+        }
+        Ok(ParsedSource {
+            code,
+            source: source.as_ref().to_os_string(),
+            is_phony,
+            keyword_aliases: self.keyword_aliases.clone(),
+        })
+    }
+}
+
+/// Source code without syntax errors, created by [`Parser::parse`]
+#[derive(Clone, Debug)]
+pub struct ParsedSource {
+    code: String,
+    source: OsString,
+    is_phony: bool,
+    keyword_aliases: KeywordAliases,
+}
+
+impl ParsedSource {
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Name of the source shown in error messages
+    pub fn source(&self) -> &OsStr {
+        &self.source
+    }
+
+    /// Whether source is a place holder rather than a real path
+    pub fn is_phony(&self) -> bool {
+        self.is_phony
+    }
+
+    /// Keyword aliases the source is parsed with
+    pub fn keyword_aliases(&self) -> &KeywordAliases {
+        &self.keyword_aliases
+    }
+}
+
+/// Compile parsed source into programs in the environment of an interpreter
+///
+/// Globals and extensions of the interpreter are visible to the compiled code, and globals
+/// assigned by one program are visible to programs compiled later.
+pub struct Compiler<Buffer: IoWrite, LibCore: StdCore> {
+    interpreter: Interpreter<Buffer, LibCore>,
+}
+
+impl<Buffer: IoWrite, LibCore: StdCore> Compiler<Buffer, LibCore> {
+    pub fn new(interpreter: Interpreter<Buffer, LibCore>) -> Self {
+        Self { interpreter }
+    }
+
+    /// Compile `parsed` into a program, see `Interpreter::compile_program`
+    ///
+    /// Source is parsed again by the compiler with imports resolved, thus errors of imported
+    /// modules are reported here.
+    pub fn compile(&mut self, parsed: &ParsedSource) -> Result<Program<Buffer, LibCore>, String> {
+        let aliases = self
+            .interpreter
+            .set_keyword_aliases(parsed.keyword_aliases.clone());
+        let program =
+            self.interpreter
+                .compile_program(&parsed.code, &parsed.source, parsed.is_phony);
+        self.interpreter.set_keyword_aliases(aliases);
+        program
+    }
+
+    pub fn interpreter(&self) -> &Interpreter<Buffer, LibCore> {
+        &self.interpreter
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter<Buffer, LibCore> {
+        &mut self.interpreter
+    }
+
+    pub fn into_interpreter(self) -> Interpreter<Buffer, LibCore> {
+        self.interpreter
+    }
+}
+
+/// Execute a compiled program with its own globals and heap
+///
+/// Byte code is shared with the program, thus creating a virtual machine is cheap.
+pub struct Vm<Buffer: IoWrite, LibCore: StdCore> {
+    interpreter: Interpreter<Buffer, LibCore>,
+}
+
+impl<Buffer: IoWrite, LibCore: StdCore> Vm<Buffer, LibCore> {
+    /// Create a virtual machine ready to run `program`, writing output to `buffer`
+    pub fn new(program: &Program<Buffer, LibCore>, buffer: Buffer) -> Self {
+        Self {
+            interpreter: program.spawn(buffer),
+        }
+    }
+
+    /// Run the program, globals keep the values left by the previous run
+    pub fn run(&mut self) -> Result<(), String> {
+        self.interpreter.run()
+    }
+
+    /// Interpreter of this virtual machine, e.g. to read and set globals
+    pub fn interpreter(&self) -> &Interpreter<Buffer, LibCore> {
+        &self.interpreter
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter<Buffer, LibCore> {
+        &mut self.interpreter
+    }
+
+    pub fn into_interpreter(self) -> Interpreter<Buffer, LibCore> {
+        self.interpreter
+    }
+}
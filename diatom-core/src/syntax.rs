@@ -0,0 +1,508 @@
+//! Lossless concrete syntax tree for external tools
+//!
+//! Every byte of the source belongs to exactly one token, including whitespace and comments, so
+//! concatenating text of all tokens in order gives the source back. Nodes are built from the
+//! parser and only expose their kind and byte range, thus tools do not depend on the internal
+//! syntax tree used by the compiler.
+//!
+//! # Example
+//! ```
+//! use diatom_core::syntax::{NodeKind, SyntaxTree, TokenKind};
+//!
+//! let source = "x = f(1) -- call";
+//! let tree = SyntaxTree::parse(source);
+//! assert!(!tree.has_errors());
+//! let text: String = tree.root().tokens().map(|token| &source[token.range.clone()]).collect();
+//! assert_eq!(text, source);
+//! assert!(tree.root().descendants().any(|node| node.kind == NodeKind::Call));
+//! assert!(tree.root().tokens().any(|token| token.kind == TokenKind::Comment));
+//! ```
+//!
+//! A [`Document`] keeps a source being edited and only parses statements affected by each edit.
+//! [`precedence_table`] lists how tightly each operator binds.
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    file_manager::{FileManager, Loc},
+    frontend::{
+        parser::{
+            ast::{Const, Expr, Stmt},
+            visit::{walk_expr, walk_stmt, Visitor},
+        },
+        Lexer, Parser, Token, TokenStream,
+    },
+};
+
+pub use crate::frontend::parser::{
+    precedence_table, Associativity, Fixity, OperatorPrecedence, Reparse, TextEdit,
+};
+
+/// Kind of a token
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// Keywords including `true` and `false`
+    Keyword,
+    /// Operators including word operators like `and` and punctuation like `(`
+    Operator,
+    Identifier,
+    Int,
+    Float,
+    Str,
+    Whitespace,
+    Comment,
+    /// Comment starting with `---`, which documents the item after it
+    DocComment,
+    /// `#!...` at the start of file
+    Shebang,
+    /// Input that can not be lexed, e.g. an unterminated string
+    Invalid,
+}
+
+/// Kind of a node
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    /// Root of a tree, covering the whole source
+    File,
+    /// `import ...`
+    Import,
+    /// A name imported, with an optional alias
+    ImportItem,
+    /// `def ... end`
+    Def,
+    /// `enum ... end`
+    Enum,
+    /// `interface ... end`
+    Interface,
+    /// `macro ...(...) ... end`
+    Macro,
+    /// `for ... in ... do ... end`
+    For,
+    /// `loop ... end` or `until ... do ... end`
+    Loop,
+    Return,
+    /// `defer ...`
+    Defer,
+    Break,
+    Continue,
+    /// Expression used as statement
+    ExprStmt,
+    /// `begin ... end`
+    Block,
+    /// `if ... end`
+    If,
+    /// `not x` or `-x`
+    Prefix,
+    /// Binary operation including assignment, `.`, `::` and tuple `,`
+    Infix,
+    Call,
+    Index,
+    /// `x..`
+    OpenRange,
+    /// `fn ... = ...`
+    Fn,
+    Name,
+    Parentheses,
+    /// Unit, boolean, number or string
+    Literal,
+    List,
+    Table,
+    /// `key = value` in a table
+    TableEntry,
+    /// `value...` in a list or table
+    Spread,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxToken {
+    pub kind: TokenKind,
+    /// Byte range in source
+    pub range: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    /// Byte range in source, from the first token to the last non-trivia token
+    pub range: Range<usize>,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    /// Tokens of this node and all descendants in source order
+    pub fn tokens(&self) -> Box<dyn Iterator<Item = &SyntaxToken> + '_> {
+        Box::new(self.children.iter().flat_map(|child| match child {
+            SyntaxElement::Node(node) => node.tokens(),
+            SyntaxElement::Token(token) => Box::new(core::iter::once(token)),
+        }))
+    }
+
+    /// Child nodes
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// All nodes under this node in pre-order, not including itself
+    pub fn descendants(&self) -> Box<dyn Iterator<Item = &SyntaxNode> + '_> {
+        Box::new(
+            self.nodes()
+                .flat_map(|node| core::iter::once(node).chain(node.descendants())),
+        )
+    }
+
+    /// Render the tree with one element per line, mostly for debugging
+    pub fn dump(&self, source: &str) -> String {
+        let mut out = String::new();
+        self.dump_to(source, 0, &mut out);
+        out
+    }
+
+    fn dump_to(&self, source: &str, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+        out.push_str(&format!("{indent}{:?}@{:?}\n", self.kind, self.range));
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.dump_to(source, depth + 1, out),
+                SyntaxElement::Token(token) => out.push_str(&format!(
+                    "{indent}  {:?}@{:?} {:?}\n",
+                    token.kind,
+                    token.range,
+                    &source[token.range.clone()]
+                )),
+            }
+        }
+    }
+}
+
+/// A parsed source file
+pub struct SyntaxTree {
+    root: SyntaxNode,
+    has_errors: bool,
+}
+
+impl SyntaxTree {
+    /// Parse `source`, imports are not resolved
+    ///
+    /// A tree is always built. Tokens that can not be parsed are kept as children of the
+    /// enclosing node.
+    pub fn parse(source: &str) -> Self {
+        let mut file_manager = FileManager::new();
+        let mut parser = Parser::new(&mut file_manager, &[])
+            .skip_imports()
+            .keep_macros();
+        let fid = parser.parse_file_phony("<source>", source);
+        let has_errors = file_manager.error_count() > 0;
+        let stream = Lexer::lex(&mut file_manager, fid);
+        Self::build(source, &stream, &file_manager.get_ast(fid), has_errors)
+    }
+
+    fn build(source: &str, stream: &TokenStream, ast: &[Stmt], has_errors: bool) -> Self {
+        let mut tokens: Vec<SyntaxToken> = stream
+            .tokens()
+            .iter()
+            .map(|(token, loc)| {
+                let kind = match token {
+                    Token::Str(_) => TokenKind::Str,
+                    Token::Integer(_) => TokenKind::Int,
+                    Token::Float(_) => TokenKind::Float,
+                    Token::Id(_) => TokenKind::Identifier,
+                    Token::Key(_) => TokenKind::Keyword,
+                    Token::Op(_) => TokenKind::Operator,
+                };
+                (kind, loc)
+            })
+            .chain(stream.comments().iter().map(|loc| {
+                if Lexer::is_doc_comment(&source[loc.start..loc.end]) {
+                    (TokenKind::DocComment, loc)
+                } else {
+                    (TokenKind::Comment, loc)
+                }
+            }))
+            .chain(stream.invalid().iter().map(|loc| (TokenKind::Invalid, loc)))
+            .map(|(kind, loc)| SyntaxToken {
+                kind,
+                range: loc.start..loc.end,
+            })
+            .collect();
+        tokens.sort_by_key(|token| token.range.start);
+        let tokens = fill_gaps(source, tokens);
+
+        let mut collector = NodeCollector::default();
+        ast.iter().for_each(|stmt| collector.visit_stmt(stmt));
+        let mut nodes = collector.0;
+        // Stable sort keeps parents before children of the same range
+        nodes.sort_by_key(|(_, range)| (range.start, core::cmp::Reverse(range.end)));
+
+        Self {
+            root: build(source, tokens, nodes),
+            has_errors,
+        }
+    }
+
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+
+    /// Whether source has any syntax error
+    pub fn has_errors(&self) -> bool {
+        self.has_errors
+    }
+}
+
+/// A source being edited, parsed incrementally
+///
+/// Only top level statements affected by an edit are parsed again, the others are reused.
+///
+/// # Example
+/// ```
+/// use diatom_core::syntax::{Document, SyntaxTree, TextEdit};
+///
+/// let mut document = Document::new("x = 1\ny = 2 + 3\nz = 4\n");
+/// let reparse = document.edit(TextEdit {
+///     range: 14..15,
+///     text: "[1, 2]".to_string(),
+/// });
+/// assert_eq!(document.source(), "x = 1\ny = 2 + [1, 2]\nz = 4\n");
+/// assert_eq!(reparse.inserted, 1..2);
+/// assert_eq!(document.statements(), vec![0..5, 6..20, 21..26]);
+/// let tree = SyntaxTree::parse(document.source());
+/// assert_eq!(document.syntax_tree().root(), tree.root());
+/// ```
+pub struct Document {
+    file_manager: FileManager,
+    fid: usize,
+}
+
+impl Document {
+    /// Parse `source`, imports are not resolved
+    pub fn new(source: impl Into<String>) -> Self {
+        let mut file_manager = FileManager::new();
+        let fid = Parser::new(&mut file_manager, &[])
+            .skip_imports()
+            .keep_macros()
+            .incremental()
+            .parse_file_phony("<source>", source);
+        Self { file_manager, fid }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.file_manager.get_shared_file(self.fid).file
+    }
+
+    /// Replace a byte range of source and parse statements affected
+    ///
+    /// # Panics
+    /// Panics if range of `edit` is out of bounds or not on char boundaries, same as
+    /// `String::replace_range`.
+    pub fn edit(&mut self, edit: TextEdit) -> Reparse {
+        self.file_manager.clear_diagnoses();
+        Parser::new(&mut self.file_manager, &[])
+            .skip_imports()
+            .keep_macros()
+            .incremental()
+            .reparse(self.fid, &edit)
+    }
+
+    /// Byte range of each top level statement
+    pub fn statements(&self) -> Vec<Range<usize>> {
+        self.file_manager
+            .get_parse_cache(self.fid)
+            .map(|cache| cache.statements().collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether source has any syntax error
+    pub fn has_errors(&self) -> bool {
+        self.file_manager.error_count() > 0
+    }
+
+    pub fn syntax_tree(&self) -> SyntaxTree {
+        let cache = self.file_manager.get_parse_cache(self.fid).unwrap();
+        SyntaxTree::build(
+            self.source(),
+            cache.tokens(),
+            &self.file_manager.get_ast(self.fid),
+            self.has_errors(),
+        )
+    }
+}
+
+/// Cover text between tokens with whitespace, shebang or invalid tokens
+fn fill_gaps(source: &str, tokens: Vec<SyntaxToken>) -> Vec<SyntaxToken> {
+    let mut filled = vec![];
+    let mut offset = 0;
+    let gap = |range: Range<usize>| {
+        let text = &source[range.clone()];
+        let kind = if range.start == 0 && text.starts_with("#!") {
+            TokenKind::Shebang
+        } else if text.chars().all(char::is_whitespace) {
+            TokenKind::Whitespace
+        } else {
+            TokenKind::Invalid
+        };
+        SyntaxToken { kind, range }
+    };
+    for token in tokens {
+        if token.range.start < offset {
+            continue;
+        }
+        if token.range.start > offset {
+            filled.push(gap(offset..token.range.start));
+        }
+        offset = token.range.end;
+        filled.push(token);
+    }
+    if offset < source.len() {
+        filled.push(gap(offset..source.len()));
+    }
+    // Split shebang from whitespace following it
+    if let Some(first) = filled.first_mut().filter(|t| t.kind == TokenKind::Shebang) {
+        let end = source.find('\n').unwrap_or(source.len());
+        if end < first.range.end {
+            let rest = end..first.range.end;
+            first.range.end = end;
+            filled.insert(1, gap(rest));
+        }
+    }
+    filled
+}
+
+/// Collect kind and range of nodes
+#[derive(Default)]
+struct NodeCollector(Vec<(NodeKind, Range<usize>)>);
+
+impl NodeCollector {
+    fn push(&mut self, kind: NodeKind, loc: &Loc) {
+        self.0.push((kind, loc.start..loc.end));
+    }
+}
+
+impl Visitor for NodeCollector {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        let kind = match stmt {
+            Stmt::Expr { .. } => NodeKind::ExprStmt,
+            Stmt::Continue { .. } => NodeKind::Continue,
+            Stmt::Break { .. } => NodeKind::Break,
+            Stmt::Return { .. } => NodeKind::Return,
+            Stmt::Defer { .. } => NodeKind::Defer,
+            Stmt::Loop { .. } => NodeKind::Loop,
+            Stmt::For { .. } => NodeKind::For,
+            Stmt::Def { .. } => NodeKind::Def,
+            Stmt::Enum { .. } => NodeKind::Enum,
+            Stmt::Interface { .. } => NodeKind::Interface,
+            Stmt::Macro { .. } => NodeKind::Macro,
+            Stmt::Import { loc, items, .. } => {
+                self.push(NodeKind::Import, loc);
+                items
+                    .iter()
+                    .for_each(|item| self.push(NodeKind::ImportItem, &item.loc));
+                return;
+            }
+            Stmt::Error => return,
+        };
+        self.push(kind, stmt.loc().unwrap());
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        let kind = match expr {
+            Expr::Block { .. } => NodeKind::Block,
+            Expr::If { .. } => NodeKind::If,
+            Expr::Prefix { .. } => NodeKind::Prefix,
+            Expr::Call { .. } => NodeKind::Call,
+            Expr::Index { .. } => NodeKind::Index,
+            Expr::Infix { .. } => NodeKind::Infix,
+            Expr::OpenRange { .. } => NodeKind::OpenRange,
+            Expr::Spread { .. } => NodeKind::Spread,
+            Expr::Fn { .. } => NodeKind::Fn,
+            Expr::Id { .. } => NodeKind::Name,
+            Expr::Parentheses { .. } => NodeKind::Parentheses,
+            Expr::Const {
+                loc,
+                value: Const::Table(items),
+            } => {
+                self.push(NodeKind::Table, loc);
+                for (key, value, loc) in items {
+                    // A spread is a node by itself
+                    if key.is_some() {
+                        self.push(NodeKind::TableEntry, loc);
+                    }
+                    self.visit_expr(value);
+                }
+                return;
+            }
+            Expr::Const {
+                value: Const::List(_),
+                ..
+            } => NodeKind::List,
+            Expr::Const { .. } => NodeKind::Literal,
+            Expr::Error => return,
+        };
+        self.push(kind, expr.loc().unwrap());
+        walk_expr(self, expr);
+    }
+}
+
+/// Nest tokens into nodes sorted by start, trivia around a node belongs to its parent
+fn build(
+    source: &str,
+    tokens: Vec<SyntaxToken>,
+    nodes: Vec<(NodeKind, Range<usize>)>,
+) -> SyntaxNode {
+    fn close(stack: &mut Vec<SyntaxNode>) {
+        let node = stack.pop().unwrap();
+        stack
+            .last_mut()
+            .unwrap()
+            .children
+            .push(SyntaxElement::Node(node));
+    }
+
+    let mut stack = vec![SyntaxNode {
+        kind: NodeKind::File,
+        range: 0..source.len(),
+        children: vec![],
+    }];
+    let mut nodes = nodes.into_iter().peekable();
+    for token in tokens {
+        while stack.len() > 1 && stack.last().unwrap().range.end <= token.range.start {
+            close(&mut stack);
+        }
+        // Nodes not covering any token, e.g. locations of invalid input
+        while nodes
+            .next_if(|(_, range)| range.end <= token.range.start)
+            .is_some()
+        {}
+        let is_trivia = matches!(
+            token.kind,
+            TokenKind::Whitespace | TokenKind::Comment | TokenKind::DocComment
+        );
+        while let Some((kind, range)) = nodes.next_if(|(_, range)| {
+            range.start <= token.range.start && !is_trivia && range.end > token.range.start
+        }) {
+            stack.push(SyntaxNode {
+                kind,
+                range,
+                children: vec![],
+            });
+        }
+        stack
+            .last_mut()
+            .unwrap()
+            .children
+            .push(SyntaxElement::Token(token));
+    }
+    while stack.len() > 1 {
+        close(&mut stack);
+    }
+    stack.pop().unwrap()
+}
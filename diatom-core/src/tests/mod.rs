@@ -45,3 +45,459 @@ fn test_import_non_table() {
         .exec(code, path, false)
         .expect_err("Example test not failing");
 }
+
+#[test]
+fn test_highlight() {
+    use crate::highlight::{highlight, TokenClass::*};
+
+    let source = "#!/bin/diatom\nif x and 1.5 then -- note\n  print('ok') end\ns = \"open";
+    let classes: Vec<_> = highlight(source)
+        .into_iter()
+        .map(|(range, class)| (&source[range], class))
+        .collect();
+    assert_eq!(
+        classes,
+        vec![
+            ("if", Keyword),
+            ("x", Identifier),
+            ("and", Keyword),
+            ("1.5", Number),
+            ("then", Keyword),
+            ("-- note", Comment),
+            ("print", Identifier),
+            ("(", Operator),
+            ("'ok'", Str),
+            (")", Operator),
+            ("end", Keyword),
+            ("s", Identifier),
+            ("=", Operator),
+            ("\"open", Invalid),
+        ]
+    );
+    assert!(highlight("#!").is_empty());
+}
+
+#[test]
+fn test_format() {
+    use crate::format::format;
+
+    let source = "#!/usr/bin/env diatom\n-- head\nimport {a as b,c} from m.n\n\n\n\
+        def f x y=x+y end -- add\nt={k=1,\n-- inside\nv=[1,2,]}\n\
+        if not t.k==1 then print( - -1) elsif x then\n\n  loop break end else until x do end end\n\
+        for k,v in t do print(k,v)\n-- tail\nend\n";
+    let expected = "#!/usr/bin/env diatom\n-- head\nimport {a as b, c} from m.n\n\n\
+        def f x y =\n    x + y\nend -- add\n-- inside\nt = {k = 1, v = [1, 2]}\n\
+        if not t.k == 1 then\n    print(- -1)\nelsif x then\n    loop\n        break\n    end\n\
+        else\n    until x do\n    end\nend\nfor k, v in t do\n    print(k, v)\n    -- tail\nend\n";
+    let formatted = format(source).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(format(&formatted).unwrap(), formatted);
+
+    let long = format!("f({})", ["argument"; 12].join(", "));
+    let wrapped = format(&long).unwrap();
+    assert!(wrapped.starts_with("f(\n    argument,\n"));
+    assert!(wrapped.ends_with("    argument,\n)\n"));
+    assert_eq!(format(&wrapped).unwrap(), wrapped);
+
+    assert!(format("f(").is_err());
+    assert_eq!(
+        format("enum Shape Circle(r) Rect(w,h), Empty end\n").unwrap(),
+        "enum Shape\n    Circle(r)\n    Rect(w, h)\n    Empty\nend\n"
+    );
+    assert_eq!(
+        format("interface Show show,name end\n").unwrap(),
+        "interface Show\n    show\n    name\nend\n"
+    );
+    assert_eq!(
+        format("macro sq(e) e*e end\nsq(1+2)\n").unwrap(),
+        "macro sq(e)\n    e * e\nend\nsq(1 + 2)\n"
+    );
+    assert_eq!(
+        format("@memoize @trace(1) def T.f x=x end\n").unwrap(),
+        "@memoize\n@trace(1)\ndef T.f x =\n    x\nend\n"
+    );
+    assert_eq!(
+        format("x=[1,xs...,2]\nt={base...,k=1}\n").unwrap(),
+        "x = [1, xs..., 2]\nt = {base..., k = 1}\n"
+    );
+    assert_eq!(
+        format("x=a?.b?.[0]??c?.f()\n").unwrap(),
+        "x = a?.b?.[0] ?? c?.f()\n"
+    );
+    assert_eq!(
+        format("y=xs|>f(_>3,fn _=_)|>g\n").unwrap(),
+        "y = xs |> f(_ > 3, fn _ = _) |> g\n"
+    );
+    assert_eq!(
+        format("def f=defer close(x)\nend\n").unwrap(),
+        "def f =\n    defer close(x)\nend\n"
+    );
+}
+
+#[test]
+fn test_syntax_tree() {
+    use crate::syntax::{NodeKind, SyntaxTree, TokenKind};
+
+    let source = "#!/bin/diatom\ndef f x = -- add one\n  x + 1\nend\n";
+    let tree = SyntaxTree::parse(source);
+    assert!(!tree.has_errors());
+    assert_eq!(
+        tree.root().dump(source),
+        r##"File@0..47
+  Shebang@0..13 "#!/bin/diatom"
+  Whitespace@13..14 "\n"
+  Def@14..46
+    Keyword@14..17 "def"
+    Whitespace@17..18 " "
+    Name@18..19
+      Identifier@18..19 "f"
+    Whitespace@19..20 " "
+    Identifier@20..21 "x"
+    Whitespace@21..22 " "
+    Operator@22..23 "="
+    Whitespace@23..24 " "
+    Comment@24..34 "-- add one"
+    Whitespace@34..37 "\n  "
+    ExprStmt@37..42
+      Infix@37..42
+        Name@37..38
+          Identifier@37..38 "x"
+        Whitespace@38..39 " "
+        Operator@39..40 "+"
+        Whitespace@40..41 " "
+        Literal@41..42
+          Int@41..42 "1"
+    Whitespace@42..43 "\n"
+    Keyword@43..46 "end"
+  Whitespace@46..47 "\n"
+"##
+    );
+    let kinds: Vec<_> = SyntaxTree::parse("t = {a = (f(1))}")
+        .root()
+        .descendants()
+        .map(|node| node.kind)
+        .collect();
+    use NodeKind::*;
+    assert_eq!(
+        kinds,
+        vec![
+            ExprStmt,
+            Infix,
+            Name,
+            Table,
+            TableEntry,
+            Parentheses,
+            Call,
+            Name,
+            Literal
+        ]
+    );
+
+    for path in ["../examples/sieve.dm", "../examples/meta_table.dm"] {
+        let source = std::fs::read_to_string(path).unwrap();
+        let tree = SyntaxTree::parse(&source);
+        let text: String = tree
+            .root()
+            .tokens()
+            .map(|token| &source[token.range.clone()])
+            .collect();
+        assert_eq!(text, source);
+    }
+
+    let source = "x = (1 + \"open";
+    let tree = SyntaxTree::parse(source);
+    assert!(tree.has_errors());
+    let last = tree.root().tokens().last().unwrap();
+    assert_eq!(
+        (last.kind, last.range.end),
+        (TokenKind::Invalid, source.len())
+    );
+    assert!(tree.root().descendants().all(|n| n.kind != NodeKind::Call));
+}
+
+#[test]
+fn test_shared_file() {
+    use crate::{RenderOptions, SharedFile};
+
+    let file = SharedFile::new("a = \"漢字\"\n\tb🐶 = e\u{301}\n");
+    let b = file.file.find('b').unwrap();
+    assert_eq!(file.line(0), 1);
+    assert_eq!(file.line(b), 2);
+    assert_eq!(file.line_start(2), Some(b - 1));
+    assert_eq!(file.line_start(4), None);
+    assert_eq!(file.line_col(b), (2, 2));
+    assert_eq!(file.line_col_to_byte(2, 2), Some(b));
+    assert_eq!(file.line_col_to_byte(2, 100), None);
+    assert_eq!(file.byte_to_char(b), 10);
+    assert_eq!(file.char_to_byte(10), Some(b));
+    // Inside `漢`
+    assert_eq!(file.byte_to_char(6), 5);
+    assert_eq!(file.line_col(6), (1, 6));
+    assert_eq!(file.display_column(file.file.find('"').unwrap() + 7), 10);
+    assert_eq!(file.display_column(b), 5);
+    assert_eq!(file.display_column(file.file.find('=').unwrap()), 3);
+    let eq = file.file.rfind('=').unwrap();
+    assert_eq!(file.display_column(eq), 9);
+    assert_eq!(file.display_column(file.file.len() - 1), 12);
+
+    // Carets are aligned with wide characters
+    let mut interpreter = Interpreter::new(Vec::<u8>::new());
+    interpreter.set_render_options(RenderOptions::default());
+    let error = interpreter
+        .exec("\ts = \"漢字\" + ()", "test", true)
+        .unwrap_err();
+    assert!(error.contains("\n  |         ^^^^^^^^^^^\n"), "{error}");
+}
+
+#[test]
+fn test_incremental() {
+    use crate::syntax::{Document, SyntaxTree, TextEdit};
+
+    fn edit(document: &mut Document, pattern: &str, text: &str) -> crate::syntax::Reparse {
+        let start = document.source().find(pattern).unwrap();
+        let reparse = document.edit(TextEdit {
+            range: start..start + pattern.len(),
+            text: text.to_string(),
+        });
+        // Same as parsing from scratch
+        let source = document.source().to_string();
+        let tree = SyntaxTree::parse(&source);
+        assert_eq!(document.has_errors(), tree.has_errors(), "{source}");
+        assert_eq!(
+            document.syntax_tree().root().dump(&source),
+            tree.root().dump(&source)
+        );
+        assert_eq!(document.statements(), Document::new(source).statements());
+        reparse
+    }
+
+    let mut document = Document::new("x = 1\ndef f a =\n  a + 1\nend\ny = f(x) -- call\n");
+    assert!(!document.has_errors());
+    let reparse = edit(&mut document, "1\n", "10\n");
+    assert_eq!(reparse.removed, 0..1);
+    assert_eq!(reparse.inserted, 0..1);
+
+    // Unclosed `if` takes following statements
+    edit(&mut document, "y =", "if x then\ny =");
+    assert!(document.has_errors());
+    assert_eq!(document.statements().len(), 3);
+    let reparse = edit(&mut document, "-- call\n", "-- call\nend\n");
+    assert!(!document.has_errors());
+    assert_eq!(reparse.inserted, 2..3);
+
+    // Strings and comments
+    edit(&mut document, "a + 1", "a + \"1");
+    assert!(document.has_errors());
+    edit(&mut document, "\"1", "\"1\"");
+    edit(&mut document, "def", "-- def");
+    edit(&mut document, "-- def", "def");
+    edit(&mut document, "-- call", "-- a\nz = 2");
+    assert!(!document.has_errors());
+    assert_eq!(document.statements().len(), 3);
+
+    // Newline inserted in the middle of an expression
+    edit(&mut document, "x = 10", "x = 10 +\n");
+    edit(&mut document, " +\n", "");
+    let reparse = edit(&mut document, "z = 2", "z = 3");
+    assert_eq!(reparse.removed, 2..3);
+    assert_eq!(reparse.inserted, 2..3);
+
+    // Numbers after `.` are members rather than floats
+    edit(&mut document, "x = 10", "x = t.0.1");
+    edit(&mut document, "t.", "");
+    edit(&mut document, "x = 0", "x = t.0");
+    edit(&mut document, "x = t.0.1", "x = 10");
+
+    // Edits across statements
+    edit(&mut document, "=\n  a", "=\n  0\nend\nq = a");
+    edit(&mut document, "x = 10\ndef", "def");
+    edit(&mut document, "", "w = ");
+    assert_eq!(document.source().lines().next(), Some("w = def f a ="));
+}
+
+#[test]
+fn test_tokenize() {
+    use crate::{
+        syntax::SyntaxTree,
+        tokens::{tokenize, Token},
+    };
+
+    // Same tokens as the syntax tree, which lexes the whole file at once
+    for source in [
+        "#!/bin/diatom\nx = t.0.1 + 1.5 -- sum\n",
+        "def f a = \"\\q\" ++ 'ok'\n-- end",
+        "  y = 1__0 @ z \"open",
+        "",
+    ] {
+        let streamed: Vec<_> = tokenize(source)
+            .with_trivia()
+            .map(|(token, span)| (token.kind(), span))
+            .collect();
+        let tree = SyntaxTree::parse(source);
+        let expected: Vec<_> = tree
+            .root()
+            .tokens()
+            .map(|token| (token.kind, token.range.clone()))
+            .collect();
+        assert_eq!(streamed, expected, "{source}");
+    }
+
+    let tokens: Vec<_> = tokenize("'a\\tb' 1.5 n\u{0303}").collect();
+    assert_eq!(
+        tokens,
+        [
+            (Token::Str("a\tb".to_string()), 0..6),
+            (Token::Float(1.5), 7..10),
+            (Token::Identifier("\u{f1}".to_string()), 11..14),
+        ]
+    );
+    let mut tokens = tokenize("1 -- one\n2").with_trivia();
+    assert_eq!(tokens.nth(2), Some((Token::Comment, 2..8)));
+    assert!(tokens.all(|(token, _)| token != Token::Shebang));
+}
+
+#[test]
+fn test_doc() {
+    use crate::doc::{extract, ItemKind};
+    use crate::tokens::{tokenize, Token};
+
+    let source = r#"#!/usr/bin/env diatom
+--- Shapes
+---
+--- Drawn on a <canvas>
+
+-- Not a doc comment
+--- Kinds of shapes
+enum Shape
+    Circle(r)
+end
+
+--- Scale `shape`
+--- by `k`
+@memoize
+def scale shape k = shape end
+
+def Point.new x y = {x = x, y = y} end
+
+---- Separator, not a doc comment
+undocumented = 1
+--- Unit circle
+unit = Shape::Circle(1)
+
+x = 1 --- Trailing, not a doc comment
+def f = () end
+"#;
+    let docs = extract(source);
+    assert_eq!(
+        docs.doc.as_deref(),
+        Some(
+            "Shapes
+
+Drawn on a <canvas>"
+        )
+    );
+    let items: Vec<_> = docs
+        .items
+        .iter()
+        .map(|item| (item.kind, item.signature.as_str(), item.doc.as_str()))
+        .collect();
+    assert_eq!(
+        items,
+        [
+            (ItemKind::Enum, "enum Shape", "Kinds of shapes"),
+            (
+                ItemKind::Function,
+                "def scale shape k",
+                "Scale `shape`\nby `k`"
+            ),
+            (ItemKind::Function, "def Point.new x y", ""),
+            (ItemKind::Variable, "unit", "Unit circle"),
+            (ItemKind::Function, "def f", ""),
+        ]
+    );
+    let scale = docs.get("scale").unwrap();
+    assert_eq!(scale.line, 14);
+    assert_eq!(docs.item_at(scale.range.start + 4), Some(scale));
+
+    let markdown = docs.to_markdown("shapes");
+    assert!(markdown.starts_with("# shapes\n\nShapes\n\nDrawn on a <canvas>\n"));
+    assert!(markdown.contains("\n## `def scale shape k`\n\nScale `shape`\nby `k`\n"));
+    let html = docs.to_html("shapes");
+    assert!(html.contains("<p>Drawn on a &lt;canvas&gt;</p>"));
+    assert!(html.contains("<h2 id=\"Point.new\"><code>def Point.new x y</code></h2>"));
+
+    // A doc comment right before an item is not the doc of the module
+    let docs = extract("--- Answer\nanswer = 42");
+    assert_eq!(docs.doc, None);
+    assert_eq!(docs.get("answer").unwrap().doc, "Answer");
+
+    let tokens: Vec<_> = tokenize("--- doc\n-- comment\n---- line")
+        .with_trivia()
+        .filter(|(token, _)| token.is_trivia() && *token != Token::Whitespace)
+        .map(|(token, _)| token)
+        .collect();
+    assert_eq!(tokens, [Token::DocComment, Token::Comment, Token::Comment]);
+}
+
+#[test]
+fn test_ast_visitor() {
+    use crate::{
+        file_manager::{FileManager, Loc},
+        frontend::{
+            parser::{
+                ast::Expr,
+                visit::{walk_expr, walk_expr_mut, MutVisitor, Visitor},
+            },
+            Parser,
+        },
+    };
+
+    struct Rename;
+    impl MutVisitor for Rename {
+        fn visit_expr(&mut self, expr: &mut Expr) {
+            if let Expr::Id { name, .. } = expr {
+                *name = name.to_ascii_uppercase().into();
+            }
+            walk_expr_mut(self, expr);
+        }
+    }
+
+    #[derive(Default)]
+    struct Spans(Vec<(usize, usize)>, Vec<String>);
+    impl Visitor for Spans {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let Expr::Id { name, .. } = expr {
+                self.1.push(name.to_string());
+            }
+            walk_expr(self, expr);
+        }
+        fn visit_loc(&mut self, loc: &Loc) {
+            self.0.push((loc.start, loc.end));
+        }
+    }
+
+    let mut file_manager = FileManager::new();
+    let fid = Parser::new(&mut file_manager, &[])
+        .parse_file_phony("<test>", "def f a =\n  {k = [a, b]}\nend\n");
+    let mut ast = file_manager.get_ast(fid).as_ref().clone();
+    ast.iter_mut().for_each(|stmt| Rename.visit_stmt(stmt));
+    ast[0].shift(1);
+    let mut spans = Spans::default();
+    ast.iter().for_each(|stmt| spans.visit_stmt(stmt));
+    assert_eq!(spans.1, ["F", "A", "B"]);
+    // Def, name, parameter, expression statement, table, entry, list and items
+    assert_eq!(
+        spans.0,
+        [
+            (1, 29),
+            (5, 6),
+            (7, 8),
+            (13, 25),
+            (13, 25),
+            (14, 24),
+            (18, 24),
+            (19, 20),
+            (22, 23)
+        ]
+    );
+}
@@ -0,0 +1,226 @@
+//! Streaming access to tokens, for tools that do not need a syntax tree
+//!
+//! Tokens are lexed lazily as the iterator advances. Whitespace, comments and the shebang
+//! are trivia, which are only returned if asked for by [`Tokens::with_trivia`]. With trivia,
+//! every byte of the source is covered by exactly one token.
+//!
+//! # Example
+//! ```
+//! use diatom_core::tokens::{tokenize, Token};
+//!
+//! let source = "x = 0x10 -- hex";
+//! let tokens: Vec<_> = tokenize(source).collect();
+//! assert_eq!(tokens[0], (Token::Identifier("x".to_string()), 0..1));
+//! assert_eq!(tokens[2], (Token::Int(16), 4..8));
+//! assert_eq!(tokens.len(), 3);
+//!
+//! let text: String = tokenize(source)
+//!     .with_trivia()
+//!     .map(|(_, span)| &source[span])
+//!     .collect();
+//! assert_eq!(text, source);
+//! ```
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::ops::Range;
+
+use crate::{
+    file_manager::{FileManager, Loc},
+    frontend::{self, Lexer},
+};
+
+pub use crate::syntax::TokenKind;
+
+/// Byte range of a token in source
+pub type Span = Range<usize>;
+
+/// A token and its value, text of a token is the source in its span
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// Keywords including `true` and `false`
+    Keyword,
+    /// Operators including word operators like `and` and punctuation like `(`
+    Operator,
+    /// Name in Unicode normalization form C
+    Identifier(String),
+    Int(i64),
+    Float(f64),
+    /// Content of string with escapes resolved
+    Str(String),
+    Whitespace,
+    Comment,
+    /// Comment starting with `---`, which documents the item after it
+    DocComment,
+    /// `#!...` at the start of file
+    Shebang,
+    /// Input that can not be lexed, e.g. an unterminated string
+    Invalid,
+}
+
+impl Token {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::Keyword => TokenKind::Keyword,
+            Token::Operator => TokenKind::Operator,
+            Token::Identifier(_) => TokenKind::Identifier,
+            Token::Int(_) => TokenKind::Int,
+            Token::Float(_) => TokenKind::Float,
+            Token::Str(_) => TokenKind::Str,
+            Token::Whitespace => TokenKind::Whitespace,
+            Token::Comment => TokenKind::Comment,
+            Token::DocComment => TokenKind::DocComment,
+            Token::Shebang => TokenKind::Shebang,
+            Token::Invalid => TokenKind::Invalid,
+        }
+    }
+
+    /// Whitespace, comment or shebang
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            Token::Whitespace | Token::Comment | Token::DocComment | Token::Shebang
+        )
+    }
+}
+
+/// Lex `source` lazily, see [`Tokens`]
+pub fn tokenize(source: &str) -> Tokens {
+    Tokens::new(source)
+}
+
+/// Iterator of tokens of a source and their spans, in order
+pub struct Tokens {
+    file_manager: FileManager,
+    fid: usize,
+    source_len: usize,
+    trivia: bool,
+    /// Where lexing continues
+    offset: usize,
+    /// End of the last token returned, including trivia not returned
+    covered: usize,
+    /// Lexed but not returned yet, in reverse order
+    pending: Vec<(Token, Span)>,
+    /// Token before `offset`, since numbers are lexed differently after `.`
+    previous: Option<(frontend::Token, Loc)>,
+}
+
+impl Tokens {
+    pub fn new(source: &str) -> Self {
+        let mut file_manager = FileManager::new();
+        let fid = file_manager.add_file("", source.to_string());
+        let mut pending = vec![];
+        let mut offset = 0;
+        if source.starts_with("#!") {
+            offset = source.find('\n').unwrap_or(source.len());
+            pending.push((Token::Shebang, 0..offset));
+        }
+        Self {
+            file_manager,
+            fid,
+            source_len: source.len(),
+            trivia: false,
+            offset,
+            covered: 0,
+            pending,
+            previous: None,
+        }
+    }
+
+    /// Also return whitespace, comments and the shebang
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = true;
+        self
+    }
+
+    /// Lex until the next token, return false at end of file
+    fn lex_more(&mut self) -> bool {
+        if self.offset >= self.source_len {
+            return false;
+        }
+        let (stream, stop) = Lexer::lex_next(
+            &mut self.file_manager,
+            self.fid,
+            self.offset,
+            self.previous.take(),
+        );
+        // Errors are shown as invalid tokens
+        self.file_manager.clear_diagnoses();
+        self.previous = stream.tokens().last().cloned();
+        let tokens = stream.tokens().iter().map(|(token, loc)| {
+            let token = match token {
+                frontend::Token::Str(s) => Token::Str(s.clone()),
+                frontend::Token::Integer(i) => Token::Int(*i),
+                frontend::Token::Float(f) => Token::Float(*f),
+                frontend::Token::Id(name) => Token::Identifier(name.to_string()),
+                frontend::Token::Key(_) => Token::Keyword,
+                frontend::Token::Op(_) => Token::Operator,
+            };
+            (token, loc.start..loc.end)
+        });
+        let source = self.file_manager.get_file(self.fid);
+        let comments = stream.comments().iter().map(|loc| {
+            let token = if Lexer::is_doc_comment(&source[loc.start..loc.end]) {
+                Token::DocComment
+            } else {
+                Token::Comment
+            };
+            (token, loc.start..loc.end)
+        });
+        let invalid = stream
+            .invalid()
+            .iter()
+            .map(|loc| (Token::Invalid, loc.start..loc.end));
+        let mut lexed: Vec<_> = tokens.chain(comments).chain(invalid).collect();
+        lexed.sort_by_key(|(_, span)| core::cmp::Reverse(span.start));
+        self.pending = lexed;
+        self.offset = stop;
+        true
+    }
+
+    /// Text between tokens, which is whitespace unless the lexer skipped invalid input
+    fn gap(&self, span: Span) -> (Token, Span) {
+        let source = self.file_manager.get_file(self.fid);
+        if source[span.clone()].chars().all(char::is_whitespace) {
+            (Token::Whitespace, span)
+        } else {
+            (Token::Invalid, span)
+        }
+    }
+}
+
+impl Iterator for Tokens {
+    type Item = (Token, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.pending.is_empty() && !self.lex_more() {
+                if self.trivia && self.covered < self.source_len {
+                    let gap = self.gap(self.covered..self.source_len);
+                    self.covered = self.source_len;
+                    return Some(gap);
+                }
+                return None;
+            }
+            let Some((token, span)) = self.pending.pop() else {
+                continue;
+            };
+            // Invalid input inside a token already returned
+            if span.start < self.covered {
+                continue;
+            }
+            if self.trivia && span.start > self.covered {
+                let gap = self.gap(self.covered..span.start);
+                self.pending.push((token, span));
+                self.covered = gap.1.end;
+                return Some(gap);
+            }
+            self.covered = span.end;
+            if self.trivia || !token.is_trivia() {
+                return Some((token, span));
+            }
+        }
+    }
+}
@@ -1,6 +1,15 @@
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::any::Any;
+
 use codespan_reporting::diagnostic::Label;
 
-use crate::file_manager::{Diagnostic, Loc};
+use crate::file_manager::{Diagnostic, Loc, Warning};
 
 pub enum VmError {
     /// Yield control back to host
@@ -34,7 +43,7 @@ pub enum VmError {
     /// E3008 Io Error
     IoError {
         loc: Option<Loc>,
-        error: std::io::Error,
+        error: crate::compat::io::Error,
     },
     /// E3009 Can not set attr
     CanNotSetAttr { loc: Loc, t: String },
@@ -60,6 +69,56 @@ pub enum VmError {
     MissingExtern { loc: Loc, name: String },
     /// E3018 Module not return table
     ModuleInvalidReturn { loc: Loc, t: String },
+    /// E3019 Instruction limit of sandbox policy reached
+    OutOfFuel,
+    /// E3020 Memory limit of sandbox policy reached
+    OutOfMemory,
+    /// E3021 Time limit of sandbox policy reached
+    Timeout,
+    /// E3022 External function panicked
+    ExternPanic { loc: Loc, reason: String },
+    /// E3023 Interpreter panicked due to a bug
+    InternalPanic(String),
+    /// E3024 Set field of a frozen table
+    FrozenTable {
+        loc: Loc,
+        attr: String,
+        /// Where the table is frozen by `freeze`
+        frozen_at: Option<Loc>,
+    },
+    /// E3025 Spread a value which is not a collection
+    CanNotSpread {
+        loc: Loc,
+        t: String,
+        into: &'static str,
+    },
+    /// E3026 Read an instance member from a class
+    InstanceMemberAccess { loc: Loc, attr: String },
+    /// E3027 External function stopped waiting as time limit of sandbox policy is reached
+    Interrupted { loc: Loc, reason: String },
+    /// E3028 Modify a list or tuple frozen by `freeze`
+    FrozenObject {
+        loc: Loc,
+        t: String,
+        frozen_at: Option<Loc>,
+    },
+    /// E3029 Read a global variable of strict mode whose assignment has never completed
+    UndefinedGlobal {
+        loc: Loc,
+        name: String,
+        similar: Option<String>,
+    },
+}
+
+/// Extract message from payload of a caught panic
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "Unknown panic".to_string(),
+        },
+    }
 }
 
 impl From<VmError> for Diagnostic {
@@ -79,7 +138,11 @@ impl From<VmError> for Diagnostic {
             VmError::InvalidCondition(loc, t) => Diagnostic::error()
                 .with_code("E3003")
                 .with_message(format!("Expect a bool value as condition, got a `{t}`"))
-                .with_labels(vec![Label::primary(loc.fid, loc)]),
+                .with_labels(vec![Label::primary(loc.fid, loc)])
+                .with_notes(vec![
+                    "Values are not converted implicitly, consider `bool(x)` or a comparison"
+                        .to_string(),
+                ]),
             VmError::NotCallable(loc, t) => Diagnostic::error()
                 .with_code("E3004")
                 .with_message(format!("Type `{t}` is not callable"))
@@ -158,6 +221,108 @@ impl From<VmError> for Diagnostic {
                 .with_code("E3018")
                 .with_message(format!("Module returns type `{t}` which is not a table"))
                 .with_labels(vec![Label::primary(loc.fid, loc)]),
+            VmError::OutOfFuel => Diagnostic::error()
+                .with_code("E3019")
+                .with_message("Execution is aborted as instruction limit is reached"),
+            VmError::OutOfMemory => Diagnostic::error()
+                .with_code("E3020")
+                .with_message("Execution is aborted as memory limit is reached"),
+            VmError::Timeout => Diagnostic::error()
+                .with_code("E3021")
+                .with_message("Execution is aborted as time limit is reached"),
+            VmError::ExternPanic { loc, reason } => Diagnostic::error()
+                .with_code("E3022")
+                .with_message("External function panicked")
+                .with_labels(vec![Label::primary(loc.fid, loc).with_message(reason)]),
+            VmError::InternalPanic(reason) => Diagnostic::error()
+                .with_code("E3023")
+                .with_message("Interpreter panicked due to an internal error")
+                .with_notes(vec![
+                    reason,
+                    "This is a bug of diatom, please report it".to_string(),
+                ]),
+            VmError::FrozenTable {
+                loc,
+                attr,
+                frozen_at,
+            } => Diagnostic::error()
+                .with_code("E3024")
+                .with_message(format!("Can not set field `{attr}` of a frozen table"))
+                .with_labels(frozen_labels(loc, frozen_at)),
+            VmError::CanNotSpread { loc, t, into } => Diagnostic::error()
+                .with_code("E3025")
+                .with_message(format!("Type `{t}` can not be spread into a `{into}`"))
+                .with_labels(vec![Label::primary(loc.fid, loc)])
+                .with_notes(vec![
+                    "Only a `List` or `Tuple` can be spread into a `List`".to_string(),
+                    "Only a `Table` can be spread into a `Table`".to_string(),
+                ]),
+            VmError::InstanceMemberAccess { loc, attr } => Diagnostic::error()
+                .with_code("E3026")
+                .with_message(format!(
+                    "`{attr}` is an instance member, which can not be read from the class"
+                ))
+                .with_labels(vec![Label::primary(loc.fid, loc)])
+                .with_notes(vec![
+                    "Read it from an instance created by calling the class".to_string(),
+                    "Or move it into `__static` of the class to share it by all instances"
+                        .to_string(),
+                ]),
+            VmError::Interrupted { loc, reason } => Diagnostic::error()
+                .with_code("E3027")
+                .with_message("Execution is interrupted as time limit is reached")
+                .with_labels(vec![Label::primary(loc.fid, loc).with_message(reason)]),
+            VmError::FrozenObject { loc, t, frozen_at } => Diagnostic::error()
+                .with_code("E3028")
+                .with_message(format!("Can not modify a frozen `{t}`"))
+                .with_labels(frozen_labels(loc, frozen_at)),
+            VmError::UndefinedGlobal { loc, name, similar } => {
+                let mut notes = vec![format!(
+                    "The assignment that declares `{name}` failed before `{name}` is assigned"
+                )];
+                notes.extend(similar.map(|name| format!("Did you mean `{name}`?")));
+                Diagnostic::error()
+                    .with_code("E3029")
+                    .with_message(format!("Variable `{name}` is not defined"))
+                    .with_labels(vec![Label::primary(loc.fid, loc)])
+                    .with_notes(notes)
+            }
+        }
+    }
+}
+
+/// W3000 Warning reported by an external function, e.g. a lossy conversion
+///
+/// Warning code `W3000` to `W3499` is reserved.
+pub struct RuntimeWarning {
+    /// Name in kebab case, e.g. `float-overflow`
+    pub name: &'static str,
+    pub message: String,
+    /// Call of the external function, if any
+    pub loc: Option<Loc>,
+}
+
+impl From<RuntimeWarning> for Warning {
+    fn from(value: RuntimeWarning) -> Self {
+        let labels = value
+            .loc
+            .map(|loc| vec![Label::primary(loc.fid, loc)])
+            .unwrap_or_default();
+        Warning {
+            name: value.name,
+            diagnostic: Diagnostic::warning()
+                .with_code("W3000")
+                .with_message(value.message)
+                .with_labels(labels),
         }
     }
 }
+
+/// Labels of modifying a frozen collection, with where it is frozen if known
+fn frozen_labels(loc: Loc, frozen_at: Option<Loc>) -> Vec<Label<usize>> {
+    let mut labels = vec![Label::primary(loc.fid, loc)];
+    if let Some(frozen_at) = frozen_at {
+        labels.push(Label::secondary(frozen_at.fid, frozen_at).with_message("Frozen here"));
+    }
+    labels
+}
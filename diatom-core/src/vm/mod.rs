@@ -1,4 +1,6 @@
-use crate::{file_manager::Loc, gc::Gc, interpreter::Func, IoWrite};
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::{file_manager::Loc, gc::Gc, host::Deadline, interpreter::Func, IoWrite};
 
 use self::{error::VmError, op::*};
 
@@ -25,6 +27,7 @@ pub trait Instruction {
 
 #[allow(clippy::enum_variant_names)]
 #[enum_dispatch]
+#[derive(Clone)]
 pub enum VmInst {
     OpAdd,
     OpSub,
@@ -37,7 +40,12 @@ pub enum VmInst {
     OpJump,
     OpBranchTrue,
     OpBranchFalse,
+    OpAssertBool,
+    OpDefine,
+    OpCheckDefined,
     OpIs,
+    OpIn,
+    OpIsUnit,
     OpEq,
     OpNe,
     OpLt,
@@ -49,7 +57,9 @@ pub enum VmInst {
     OpRem,
     OpCall,
     OpRet,
+    OpDefer,
     OpGetTable,
+    OpGetSuper,
     OpSetTable,
     OpGetTuple,
     OpSetTuple,
@@ -58,6 +68,8 @@ pub enum VmInst {
     OpSetMeta,
     OpMakeTuple,
     OpMakeList,
+    OpExtendList,
+    OpExtendTable,
     OpAllocReg,
     OpMakeClosure,
     OpLoadConstant,
@@ -69,8 +81,28 @@ pub enum VmInst {
 
 pub struct Vm {
     ip: Ip,
+    /// Number of instructions that can still be executed
+    fuel: Option<usize>,
+    /// Execution is aborted once the deadline passes
+    deadline: Option<Deadline>,
+    /// Instructions executed since the deadline was last checked
+    ticks: usize,
+    /// Instructions executed since the last reset
+    instructions: u64,
+    /// Error and its trace, kept while deferred closures run before it is returned
+    unwinding: Option<(VmError, Vec<Loc>)>,
 }
 
+/// Deferred closures run during unwinding return here
+const UNWIND: Ip = Ip {
+    func_id: usize::MAX,
+    inst: 0,
+};
+
+/// Reading the clock is expensive, so the deadline is only checked once every this many
+/// instructions
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
+
 impl Vm {
     pub fn new() -> Self {
         Self {
@@ -78,12 +110,50 @@ impl Vm {
                 func_id: 0,
                 inst: 0,
             },
+            fuel: None,
+            deadline: None,
+            ticks: 0,
+            instructions: 0,
+            unwinding: None,
+        }
+    }
+
+    /// Limit number of instructions executed, None for unlimited
+    pub fn set_fuel(&mut self, fuel: Option<usize>) {
+        self.fuel = fuel;
+    }
+
+    /// Abort execution after the deadline, None for no deadline
+    pub fn set_deadline(&mut self, deadline: Option<Deadline>) {
+        self.deadline = deadline;
+        self.ticks = 0;
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn reset_instructions(&mut self) {
+        self.instructions = 0;
+    }
+
+    fn deadline_passed(&mut self) -> bool {
+        match &self.deadline {
+            Some(deadline) => {
+                self.ticks += 1;
+                if self.ticks < DEADLINE_CHECK_INTERVAL {
+                    return false;
+                }
+                self.ticks = 0;
+                deadline.passed()
+            }
+            None => false,
         }
     }
 
     pub fn exec<Buffer: IoWrite>(
         &mut self,
-        byte_code: &[Func],
+        byte_code: &[Arc<Func>],
         gc: &mut Gc<Buffer>,
         out: &mut Buffer,
     ) -> (VmError, Vec<Loc>) {
@@ -92,29 +162,89 @@ impl Vm {
             debug_assert!(byte_code.len() > func_id);
             let func = unsafe { byte_code.get_unchecked(func_id) };
             debug_assert!(func.insts.len() > inst);
-            self.ip = match unsafe { func.insts.get_unchecked(inst) }
-                .exec(self.ip, gc, out)
-                .map_err(|err| {
-                    let trace = gc.clean_call_stack();
-                    let trace = trace
-                        .into_iter()
-                        .map(|Ip { func_id, inst }| {
-                            let op = &byte_code[func_id].insts[inst - 1];
-                            if let VmInst::OpCall(OpCall { loc, .. }) = op {
-                                loc.clone()
-                            } else {
-                                unreachable!()
-                            }
-                        })
-                        .collect();
-                    (err, trace)
-                }) {
-                Ok(ip) => ip,
-                Err(err) => return err,
+            let timeout = self.deadline_passed();
+            let result = match &mut self.fuel {
+                Some(0) => Err(VmError::OutOfFuel),
+                _ if timeout => Err(VmError::Timeout),
+                fuel => {
+                    if let Some(fuel) = fuel {
+                        *fuel -= 1;
+                    }
+                    self.instructions += 1;
+                    unsafe { func.insts.get_unchecked(inst) }.exec(self.ip, gc, out)
+                }
             };
+            let result = result.and_then(|ip| {
+                if gc.take_out_of_memory() {
+                    Err(VmError::OutOfMemory)
+                } else {
+                    Ok(ip)
+                }
+            });
+            self.ip = match result {
+                Ok(ip) if ip.func_id != UNWIND.func_id => ip,
+                Ok(_) => match Self::unwind(gc) {
+                    Some(ip) => ip,
+                    None => return self.unwinding.take().unwrap(),
+                },
+                // Running out of resources or yielding aborts without running deferred closures
+                Err(
+                    err @ (VmError::Yield(_)
+                    | VmError::OutOfFuel
+                    | VmError::Timeout
+                    | VmError::Interrupted { .. }
+                    | VmError::OutOfMemory),
+                ) => {
+                    let trace = Self::trace(byte_code, gc.call_trace());
+                    gc.clean_call_stack();
+                    return self.unwinding.take().unwrap_or((err, trace));
+                }
+                Err(err) => {
+                    // An error in a deferred closure during unwinding is dropped, so that the
+                    // first error is reported
+                    if self.unwinding.is_none() {
+                        let trace = Self::trace(byte_code, gc.call_trace());
+                        self.unwinding = Some((err, trace));
+                    }
+                    match Self::unwind(gc) {
+                        Some(ip) => ip,
+                        None => return self.unwinding.take().unwrap(),
+                    }
+                }
+            };
+        }
+    }
+
+    /// Pop frames until one has a deferred closure, and call that closure
+    ///
+    /// Return `None` once all frames are popped.
+    fn unwind<Buffer: IoWrite>(gc: &mut Gc<Buffer>) -> Option<Ip> {
+        loop {
+            if let Some(ip) = gc.call_deferred(UNWIND) {
+                return Some(ip);
+            }
+            if gc.is_call_stack_empty() {
+                return None;
+            }
+            gc.pop_call_stack();
         }
     }
 
+    /// Locations of calls from return addresses
+    fn trace(byte_code: &[Arc<Func>], trace: Vec<Ip>) -> Vec<Loc> {
+        trace
+            .into_iter()
+            .map(|Ip { func_id, inst }| {
+                let op = &byte_code[func_id].insts[inst - 1];
+                if let VmInst::OpCall(OpCall { loc, .. }) = op {
+                    loc.clone()
+                } else {
+                    unreachable!()
+                }
+            })
+            .collect()
+    }
+
     pub fn reset_ip(&mut self) {
         self.ip = Ip {
             func_id: 0,
@@ -1,13 +1,27 @@
 use crate::{
-    ffi::State,
+    compat::{
+        catch_unwind,
+        float::{floor, powf},
+    },
+    ffi::{ForeignFunction, State},
     file_manager::Loc,
-    gc::{Gc, GcObject, PrimitiveMeta, Reg, Table},
+    gc::{
+        format_float, Gc, GcObject, PrimitiveMeta, Reg, Table, GETTERS_KEY, SETTERS_KEY,
+        STATIC_KEY, VARIANT_KEY,
+    },
     interpreter::Capture,
     IoWrite,
 };
-use std::{collections::BTreeMap, fmt::Write};
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{any::TypeId, cmp::Ordering, fmt::Write};
 
-use super::{Instruction, Ip, VmError};
+use super::{error::panic_message, Instruction, Ip, VmError};
 
 fn get_type<Buffer: IoWrite>(reg: &Reg, gc: &Gc<Buffer>) -> String {
     match reg {
@@ -26,10 +40,14 @@ fn get_type<Buffer: IoWrite>(reg: &Reg, gc: &Gc<Buffer>) -> String {
                     reg_size: _,
                 } => "Closure".to_string(),
                 GcObject::NativeFunction(_) => "Extern_Function".to_string(),
+                GcObject::BoundMethod { .. } => "Bound_Method".to_string(),
                 GcObject::Table(_) => "Table".to_string(),
                 GcObject::Tuple(_) => "Tuple".to_string(),
                 GcObject::List(_) => "List".to_string(),
-                GcObject::UserData(_) => "UserData".to_string(),
+                GcObject::UserData => gc
+                    .get_user_type((**gc.get_user_data(*r).unwrap()).type_id())
+                    .map_or("UserData", |t| t.name)
+                    .to_string(),
             }
         }
     }
@@ -37,10 +55,120 @@ fn get_type<Buffer: IoWrite>(reg: &Reg, gc: &Gc<Buffer>) -> String {
 
 const FORMAT_PAD: usize = 10;
 
+#[derive(Clone)]
 pub struct OpAllocReg {
     pub n_reg: usize,
 }
 
+/// Test if two values are equal for `==` and `<>`
+///
+/// Values of different types are never equal, except that comparing a float is an error since
+/// floats are inexact. Objects of the same type can not be compared, `is` tests identity.
+fn values_equal<Buffer: IoWrite>(
+    lhs: &Reg,
+    rhs: &Reg,
+    gc: &Gc<Buffer>,
+    loc: &Loc,
+    op: &'static str,
+) -> Result<bool, VmError> {
+    match (lhs, rhs) {
+        (Reg::Unit, Reg::Unit) => Ok(true),
+        (Reg::Int(i1), Reg::Int(i2)) => Ok(i1 == i2),
+        (Reg::Bool(b1), Reg::Bool(b2)) => Ok(b1 == b2),
+        (Reg::Str(s1), Reg::Str(s2)) => {
+            let s1 = unsafe { gc.get_str_unchecked(*s1) };
+            let s2 = unsafe { gc.get_str_unchecked(*s2) };
+            Ok(s1 == s2)
+        }
+        _ => {
+            let t1 = get_type(lhs, gc);
+            let t2 = get_type(rhs, gc);
+            let has_float = matches!(lhs, Reg::Float(_)) || matches!(rhs, Reg::Float(_));
+            if t1 == t2 || has_float {
+                Err(VmError::OpBinNotApplicable(loc.clone(), op, t1, t2))
+            } else {
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// Order two values for `<`, `<=`, `>` and `>=`
+///
+/// Numbers are ordered with each other, strings, bools and `()` with their own type. Other
+/// values, and values of different types, can not be ordered. None if either is NaN.
+///
+/// `<=` and `>=` test equality as well, thus reject floats as `==` does.
+fn order_values<Buffer: IoWrite>(
+    lhs: &Reg,
+    rhs: &Reg,
+    gc: &Gc<Buffer>,
+    loc: &Loc,
+    op: &'static str,
+) -> Result<Option<Ordering>, VmError> {
+    let strict = matches!(op, "<" | ">");
+    match (lhs, rhs) {
+        (Reg::Int(i1), Reg::Int(i2)) => Ok(Some(i1.cmp(i2))),
+        (Reg::Int(i1), Reg::Float(f2)) if strict => Ok((*i1 as f64).partial_cmp(f2)),
+        (Reg::Float(f1), Reg::Int(i2)) if strict => Ok(f1.partial_cmp(&(*i2 as f64))),
+        (Reg::Float(f1), Reg::Float(f2)) if strict => Ok(f1.partial_cmp(f2)),
+        (Reg::Str(s1), Reg::Str(s2)) => {
+            let s1 = unsafe { gc.get_str_unchecked(*s1) };
+            let s2 = unsafe { gc.get_str_unchecked(*s2) };
+            Ok(Some(s1.cmp(s2)))
+        }
+        (Reg::Bool(b1), Reg::Bool(b2)) => Ok(Some(b1.cmp(b2))),
+        (Reg::Unit, Reg::Unit) => Ok(Some(Ordering::Equal)),
+        _ => {
+            let t1 = get_type(lhs, gc);
+            let t2 = get_type(rhs, gc);
+            Err(VmError::OpBinNotApplicable(loc.clone(), op, t1, t2))
+        }
+    }
+}
+
+/// Call an external function, a panic of it is caught and reported as a runtime error
+fn call_extern<Buffer: IoWrite>(
+    f: &ForeignFunction<Buffer>,
+    parameters: &[Reg],
+    gc: &mut Gc<Buffer>,
+    out: &mut Buffer,
+    loc: &Loc,
+) -> Result<Reg, VmError> {
+    let caller_site = gc.set_call_site(Some(loc.clone()));
+    let mut state = State { gc };
+    let ret = catch_unwind(|| f(&mut state, parameters, out));
+    gc.set_call_site(caller_site);
+    // Values created by the call are reachable from `ret` now
+    gc.clear_temporaries();
+    match ret {
+        Ok(ret) => ret.map_err(|s| {
+            if let Some(rid) = gc.take_modified_frozen() {
+                VmError::FrozenObject {
+                    loc: loc.clone(),
+                    t: get_type(&Reg::Ref(rid), gc),
+                    frozen_at: gc.frozen_at(rid).cloned(),
+                }
+            } else if gc.take_interrupted() {
+                VmError::Interrupted {
+                    loc: loc.clone(),
+                    reason: s,
+                }
+            } else {
+                VmError::Panic {
+                    loc: loc.clone(),
+                    reason: s,
+                    notes: vec![],
+                }
+            }
+        }),
+        Err(payload) => Err(VmError::ExternPanic {
+            loc: loc.clone(),
+            reason: panic_message(payload),
+        }),
+    }
+}
+
 impl Instruction for OpAllocReg {
     #[cfg_attr(feature = "profile", inline(never))]
     fn exec<Buffer: IoWrite>(
@@ -66,6 +194,7 @@ impl Instruction for OpAllocReg {
     }
 }
 
+#[derive(Clone)]
 pub struct OpCall {
     pub reg_id: usize,
     pub parameters: usize,
@@ -74,6 +203,308 @@ pub struct OpCall {
     pub loc: Loc,
 }
 
+impl OpCall {
+    /// Call a table as constructor
+    ///
+    /// A new instance of the table gets a copy of all its attributes, and those of its meta tables,
+    /// which are neither callable nor named with a leading `__`. Then `__init` is called with the
+    /// instance as the first parameter.
+    fn construct<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        class: usize,
+        gc: &mut Gc<Buffer>,
+        out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        let not_callable = |gc: &Gc<Buffer>| {
+            VmError::NotCallable(self.loc.clone(), get_type(&Reg::Ref(class), gc))
+        };
+        let GcObject::Table(t) = (unsafe { gc.get_obj_unchecked(class) }) else {
+            unreachable!()
+        };
+        let init = match gc
+            .get_table_key("__init")
+            .and_then(|key| find_attr(gc, t, key))
+        {
+            Some(Reg::Ref(init)) if unsafe { gc.get_obj_unchecked(*init) }.is_callable() => *init,
+            _ => return Err(not_callable(gc)),
+        };
+
+        // Attributes of meta tables are overridden by those closer to the instance
+        let mut chain = vec![t];
+        while let Some(meta) = chain.last().unwrap().meta_table {
+            match unsafe { gc.get_obj_unchecked(meta) } {
+                GcObject::Table(meta) => chain.push(meta),
+                _ => unreachable!(),
+            }
+        }
+        let mut attributes = BTreeMap::new();
+        for table in chain.into_iter().rev() {
+            for (key, value) in table.attributes.iter() {
+                let is_callable = match value {
+                    Reg::Ref(rid) => unsafe { gc.get_obj_unchecked(*rid) }.is_callable(),
+                    _ => false,
+                };
+                if !is_callable && !gc.look_up_table_key(*key).unwrap().starts_with("__") {
+                    attributes.insert(*key, value.clone());
+                }
+            }
+        }
+        let instance = gc.alloc_obj(GcObject::Table(Table {
+            attributes,
+            meta_table: Some(class),
+            ..Default::default()
+        }));
+
+        let parameters: Vec<Reg> = [Reg::Ref(instance)]
+            .into_iter()
+            .chain((self.start..self.start + self.parameters).map(|i| gc.read_reg(i).clone()))
+            .collect();
+        match unsafe { gc.get_obj_unchecked(init) } {
+            GcObject::Closure {
+                func_id,
+                parameters: expected,
+                ..
+            } => {
+                let func_id = *func_id;
+                if *expected != parameters.len() {
+                    return Err(VmError::ParameterLengthNotMatch {
+                        loc: self.loc.clone(),
+                        expected: *expected,
+                        got: parameters.len(),
+                    });
+                }
+                gc.alloc_call_stack(
+                    Ip {
+                        func_id: ip.func_id,
+                        inst: ip.inst + 1,
+                    },
+                    self.write_back,
+                    self.start - 1,
+                    init,
+                );
+                gc.set_instance(instance);
+                for (i, parameter) in parameters.into_iter().enumerate() {
+                    gc.write_reg(i + 1, parameter);
+                }
+                Ok(Ip { func_id, inst: 0 })
+            }
+            GcObject::NativeFunction(f) => {
+                let f = f.clone();
+                gc.root_temporary_obj(instance);
+                let ret = call_extern(&*f, &parameters, gc, out, &self.loc)?;
+                if let Some(write_back) = self.write_back {
+                    write_instance(gc, instance, ret, write_back);
+                }
+                Ok(Ip {
+                    func_id: ip.func_id,
+                    inst: ip.inst + 1,
+                })
+            }
+            _ => Err(not_callable(gc)),
+        }
+    }
+
+    /// Call a bound method, receivers are passed before the arguments
+    fn call_bound<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        rid: usize,
+        gc: &mut Gc<Buffer>,
+        out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        let args = (self.start..self.start + self.parameters).map(|i| gc.read_reg(i).clone());
+        let (method, parameters) = unbind(gc, rid, args);
+        match unsafe { gc.get_obj_unchecked(method) } {
+            GcObject::Closure {
+                func_id,
+                parameters: expected,
+                ..
+            } => {
+                let func_id = *func_id;
+                if *expected != parameters.len() {
+                    return Err(VmError::ParameterLengthNotMatch {
+                        loc: self.loc.clone(),
+                        expected: *expected,
+                        got: parameters.len(),
+                    });
+                }
+                gc.alloc_call_stack(
+                    Ip {
+                        func_id: ip.func_id,
+                        inst: ip.inst + 1,
+                    },
+                    self.write_back,
+                    self.start - 1,
+                    method,
+                );
+                // Arguments are shifted by the receivers, thus written in the frame of callee
+                for (i, parameter) in parameters.into_iter().enumerate() {
+                    gc.write_reg(i + 1, parameter);
+                }
+                Ok(Ip { func_id, inst: 0 })
+            }
+            GcObject::NativeFunction(f) => {
+                let f = f.clone();
+                call_native(ip, &*f, &parameters, self.write_back, &self.loc, gc, out)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Call an external function and write its return value to `write_back`
+fn call_native<Buffer: IoWrite>(
+    ip: Ip,
+    f: &ForeignFunction<Buffer>,
+    parameters: &[Reg],
+    write_back: Option<usize>,
+    loc: &Loc,
+    gc: &mut Gc<Buffer>,
+    out: &mut Buffer,
+) -> Result<Ip, VmError> {
+    let ret = call_extern(f, parameters, gc, out, loc)?;
+    match ret {
+        Reg::Str(id) => {
+            if gc.get_str(id).is_none() {
+                return Err(VmError::InvalidRef {
+                    loc: loc.clone(),
+                    t: "Str",
+                    id,
+                });
+            }
+        }
+        Reg::Ref(rid) => {
+            if gc.get_obj(rid).is_none() {
+                return Err(VmError::InvalidRef {
+                    loc: loc.clone(),
+                    t: "Reference",
+                    id: rid,
+                });
+            }
+        }
+        _ => (),
+    }
+    if let Some(write_back) = write_back {
+        gc.write_reg(write_back, ret)
+    }
+    Ok(Ip {
+        func_id: ip.func_id,
+        inst: ip.inst + 1,
+    })
+}
+
+/// Resolve a bound method into the function and its parameters, receivers are placed before
+/// `args`
+fn unbind<Buffer: IoWrite>(
+    gc: &Gc<Buffer>,
+    rid: usize,
+    args: impl Iterator<Item = Reg>,
+) -> (usize, Vec<Reg>) {
+    let mut receivers = vec![];
+    let mut method = rid;
+    while let GcObject::BoundMethod {
+        receiver,
+        method: inner,
+    } = unsafe { gc.get_obj_unchecked(method) }
+    {
+        receivers.push(receiver.clone());
+        method = *inner;
+    }
+    (method, receivers.into_iter().rev().chain(args).collect())
+}
+
+/// Call a function from an instruction other than `OpCall`, e.g. a getter of a property
+///
+/// Registers of a closure are placed after all registers of the current frame, as for deferred
+/// closures, since no register is reserved for the call.
+fn call_from_inst<Buffer: IoWrite>(
+    ip: Ip,
+    f: usize,
+    args: Vec<Reg>,
+    write_back: Option<usize>,
+    loc: &Loc,
+    gc: &mut Gc<Buffer>,
+    out: &mut Buffer,
+) -> Result<Ip, VmError> {
+    let (f, parameters) = unbind(gc, f, args.into_iter());
+    match unsafe { gc.get_obj_unchecked(f) } {
+        GcObject::Closure {
+            func_id,
+            parameters: expected,
+            ..
+        } => {
+            let func_id = *func_id;
+            if *expected != parameters.len() {
+                return Err(VmError::ParameterLengthNotMatch {
+                    loc: loc.clone(),
+                    expected: *expected,
+                    got: parameters.len(),
+                });
+            }
+            let return_addr = Ip {
+                func_id: ip.func_id,
+                inst: ip.inst + 1,
+            };
+            gc.alloc_call_stack_after_frame(return_addr, write_back, f);
+            for (i, parameter) in parameters.into_iter().enumerate() {
+                gc.write_reg(i + 1, parameter);
+            }
+            Ok(Ip { func_id, inst: 0 })
+        }
+        GcObject::NativeFunction(native) => {
+            let native = native.clone();
+            call_native(ip, &*native, &parameters, write_back, loc, gc, out)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Write the value of a constructor call: an `Err` returned by `__init` as it is, `Ok(instance)`
+/// for an `Ok`, otherwise the instance
+fn write_instance<Buffer: IoWrite>(
+    gc: &mut Gc<Buffer>,
+    instance: usize,
+    ret: Reg,
+    write_back: usize,
+) {
+    let is_ok = match &ret {
+        Reg::Ref(rid) => match unsafe { gc.get_obj_unchecked(*rid) } {
+            GcObject::Table(t) if t.meta_table == Some(gc.get_meta(PrimitiveMeta::Result)) => gc
+                .get_table_key(VARIANT_KEY)
+                .and_then(|key| t.attributes.get(&key))
+                .and_then(|variant| match variant {
+                    Reg::Str(sid) => gc.get_str(*sid).map(|variant| variant == "Result::Ok"),
+                    _ => None,
+                }),
+            _ => None,
+        },
+        _ => None,
+    };
+    match is_ok {
+        Some(false) => gc.write_reg(write_back, ret),
+        Some(true) => {
+            // Instance is kept alive by the register while the result is allocated
+            gc.write_reg(write_back, Reg::Ref(instance));
+            let attributes =
+                BTreeMap::from([(gc.get_or_insert_table_key("value"), Reg::Ref(instance))]);
+            let ok = gc.alloc_obj(GcObject::Table(Table {
+                attributes,
+                meta_table: Some(gc.get_meta(PrimitiveMeta::Result)),
+                ..Default::default()
+            }));
+            gc.write_reg(write_back, Reg::Ref(ok));
+            // So is the result while its tag is allocated
+            let variant = Reg::Str(gc.alloc_str("Result::Ok".to_string()));
+            let key = gc.get_or_insert_table_key(VARIANT_KEY);
+            if let GcObject::Table(t) = unsafe { gc.get_obj_unchecked_mut(ok) } {
+                t.attributes.insert(key, variant);
+            }
+        }
+        None => gc.write_reg(write_back, Reg::Ref(instance)),
+    }
+}
+
 impl Instruction for OpCall {
     #[inline(never)]
     fn exec<Buffer: IoWrite>(
@@ -84,6 +515,15 @@ impl Instruction for OpCall {
     ) -> Result<Ip, VmError> {
         // get closure
         let obj = gc.read_reg(self.reg_id).clone();
+        if gc.has_hooks() {
+            let args: Vec<Reg> = (self.start..self.start + self.parameters)
+                .map(|i| gc.read_reg(i).clone())
+                .collect();
+            gc.with_hooks(|hooks, state| {
+                let name = state.function_info(&obj).and_then(|info| info.name);
+                hooks.on_call(state, name.as_deref(), &args)
+            });
+        }
         match obj {
             Reg::Ref(r) => {
                 let obj = unsafe { gc.get_obj_unchecked(r) };
@@ -119,48 +559,21 @@ impl Instruction for OpCall {
                     }
                     GcObject::NativeFunction(f) => {
                         let f = f.clone();
-                        let mut parameters = vec![];
-                        (self.start..self.start + self.parameters)
-                            .into_iter()
-                            .for_each(|i| {
-                                let reg = gc.read_reg(i).clone();
-                                parameters.push(reg);
-                            });
-                        let mut state = State { gc };
-                        let ret = f(&mut state, &parameters, out).map_err(|s| VmError::Panic {
-                            loc: self.loc.clone(),
-                            reason: s,
-                            notes: vec![],
-                        })?;
-                        match ret {
-                            Reg::Str(id) => {
-                                if gc.get_str(id).is_none() {
-                                    return Err(VmError::InvalidRef {
-                                        loc: self.loc.clone(),
-                                        t: "Str",
-                                        id,
-                                    });
-                                }
-                            }
-                            Reg::Ref(rid) => {
-                                if gc.get_obj(rid).is_none() {
-                                    return Err(VmError::InvalidRef {
-                                        loc: self.loc.clone(),
-                                        t: "Reference",
-                                        id: rid,
-                                    });
-                                }
-                            }
-                            _ => (),
-                        }
-                        if let Some(write_back) = self.write_back {
-                            gc.write_reg(write_back, ret)
-                        }
-                        Ok(Ip {
-                            func_id: ip.func_id,
-                            inst: ip.inst + 1,
-                        })
+                        let parameters: Vec<Reg> = (self.start..self.start + self.parameters)
+                            .map(|i| gc.read_reg(i).clone())
+                            .collect();
+                        return call_native(
+                            ip,
+                            &*f,
+                            &parameters,
+                            self.write_back,
+                            &self.loc,
+                            gc,
+                            out,
+                        );
                     }
+                    GcObject::BoundMethod { .. } => return self.call_bound(ip, r, gc, out),
+                    GcObject::Table(_) => return self.construct(ip, r, gc, out),
                     _ => Err(()),
                 }
             }
@@ -185,6 +598,7 @@ impl Instruction for OpCall {
     }
 }
 
+#[derive(Clone)]
 pub struct OpImport {
     pub start: usize,
     pub fid: usize,
@@ -249,6 +663,7 @@ impl Instruction for OpImport {
     }
 }
 
+#[derive(Clone)]
 pub struct OpSaveModule {
     pub fid: usize,
     pub module_reg: usize,
@@ -300,6 +715,7 @@ impl Instruction for OpSaveModule {
     }
 }
 
+#[derive(Clone)]
 pub struct OpRet {
     pub return_reg: usize,
 }
@@ -308,18 +724,30 @@ impl Instruction for OpRet {
     #[cfg_attr(feature = "profile", inline(never))]
     fn exec<Buffer: IoWrite>(
         &self,
-        _: Ip,
+        ip: Ip,
         gc: &mut Gc<Buffer>,
         _out: &mut Buffer,
     ) -> Result<Ip, VmError> {
-        let reg = gc.read_reg(self.return_reg).clone();
+        let reg = match gc.take_return_value() {
+            Some(reg) => reg,
+            None => gc.read_reg(self.return_reg).clone(),
+        };
+
+        // Run deferred closures first, each of them returns to this instruction again
+        if gc.has_deferred() {
+            gc.set_return_value(reg);
+            return Ok(gc.call_deferred(ip).unwrap());
+        }
 
         // clean call stack
+        let instance = gc.take_instance();
         let (ip, write_back) = gc.pop_call_stack();
 
         // write return value back
-        if let Some(write_back) = write_back {
-            gc.write_reg(write_back, reg)
+        match (instance, write_back) {
+            (Some(instance), Some(write_back)) => write_instance(gc, instance, reg, write_back),
+            (None, Some(write_back)) => gc.write_reg(write_back, reg),
+            _ => (),
         }
 
         Ok(ip)
@@ -335,6 +763,81 @@ impl Instruction for OpRet {
     }
 }
 
+/// Make a closure and run it when current function exits
+///
+/// Variables of enclosing functions are shared as by `OpMakeClosure`, while those of the current
+/// function are copied, so that the closure sees their values when it is deferred rather than
+/// whatever their registers hold at exit.
+#[derive(Clone)]
+pub struct OpDefer {
+    /// closure function id
+    pub func_id: usize,
+    pub capture: Vec<Capture>,
+    /// captured variables of the current function
+    pub copy: Vec<Capture>,
+    pub reg_size: usize,
+}
+
+impl Instruction for OpDefer {
+    #[cfg_attr(feature = "profile", inline(never))]
+    fn exec<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        _out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        let mut captured_regs: Vec<_> = self
+            .capture
+            .iter()
+            .map(|Capture { rd, rs }| (*rd, gc.share_reg(*rs)))
+            .collect();
+        let rid = gc.alloc_obj(GcObject::Closure {
+            func_id: self.func_id,
+            parameters: 0,
+            captured: vec![],
+            reg_size: self.reg_size,
+        });
+        gc.defer(rid);
+        // Copies are not collected once the closure is deferred
+        for Capture { rd, rs } in self.copy.iter() {
+            captured_regs.push((*rd, gc.copy_reg(*rs)));
+        }
+        if let Some(GcObject::Closure { captured, .. }) = gc.get_obj_mut(rid) {
+            *captured = captured_regs;
+        }
+        Ok(Ip {
+            func_id: ip.func_id,
+            inst: ip.inst + 1,
+        })
+    }
+
+    fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, _gc: &Gc<Buffer>) {
+        writeln!(
+            decompiled,
+            "{: >FORMAT_PAD$}    Func@{}",
+            "defer", self.func_id
+        )
+        .unwrap();
+        for Capture { rd, rs } in self.capture.iter() {
+            writeln!(
+                decompiled,
+                "    {: >FORMAT_PAD$}    Reg#{rd} <- Reg@#{rs}",
+                ""
+            )
+            .unwrap()
+        }
+        for Capture { rd, rs } in self.copy.iter() {
+            writeln!(
+                decompiled,
+                "    {: >FORMAT_PAD$}    Reg#{rd} <- Reg#{rs}",
+                ""
+            )
+            .unwrap()
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct OpMakeClosure {
     pub loc: Loc,
     /// closure function id
@@ -394,6 +897,7 @@ impl Instruction for OpMakeClosure {
     }
 }
 
+#[derive(Clone)]
 pub struct OpLoadConstant {
     pub constant: Reg,
     pub rd: usize,
@@ -420,7 +924,7 @@ impl Instruction for OpLoadConstant {
             Reg::Unit => "()".to_string(),
             Reg::Bool(b) => b.to_string(),
             Reg::Int(i) => i.to_string(),
-            Reg::Float(f) => f.to_string(),
+            Reg::Float(f) => format_float(*f),
             Reg::Str(sid) => format!("'{}'", gc.get_str(*sid).unwrap()),
             Reg::Ref(_) => unreachable!(),
         };
@@ -433,6 +937,7 @@ impl Instruction for OpLoadConstant {
     }
 }
 
+#[derive(Clone)]
 pub struct OpNot {
     pub loc: Loc,
     pub lhs: usize,
@@ -472,6 +977,7 @@ impl Instruction for OpNot {
     }
 }
 
+#[derive(Clone)]
 pub struct OpNeg {
     pub loc: Loc,
     pub lhs: usize,
@@ -512,6 +1018,7 @@ impl Instruction for OpNeg {
     }
 }
 
+#[derive(Clone)]
 pub struct OpAdd {
     pub loc: Loc,
     pub lhs: usize,
@@ -565,6 +1072,7 @@ impl Instruction for OpAdd {
     }
 }
 
+#[derive(Clone)]
 pub struct OpSub {
     pub loc: Loc,
     pub lhs: usize,
@@ -610,6 +1118,7 @@ impl Instruction for OpSub {
     }
 }
 
+#[derive(Clone)]
 pub struct OpMul {
     pub loc: Loc,
     pub lhs: usize,
@@ -665,6 +1174,7 @@ impl Instruction for OpMul {
     }
 }
 
+#[derive(Clone)]
 pub struct OpDiv {
     pub loc: Loc,
     pub lhs: usize,
@@ -710,6 +1220,7 @@ impl Instruction for OpDiv {
     }
 }
 
+#[derive(Clone)]
 pub struct OpIDiv {
     pub loc: Loc,
     pub lhs: usize,
@@ -738,7 +1249,7 @@ impl Instruction for OpIDiv {
                 return Err(VmError::OpBinNotApplicable(self.loc.clone(), "//", t1, t2));
             }
         };
-        let reg = Reg::Int(result.floor() as i64);
+        let reg = Reg::Int(floor(result) as i64);
         gc.write_reg(self.rd, reg);
         Ok(Ip {
             func_id: ip.func_id,
@@ -756,6 +1267,7 @@ impl Instruction for OpIDiv {
     }
 }
 
+#[derive(Clone)]
 pub struct OpRem {
     pub loc: Loc,
     pub lhs: usize,
@@ -798,6 +1310,7 @@ impl Instruction for OpRem {
     }
 }
 
+#[derive(Clone)]
 pub struct OpPow {
     pub loc: Loc,
     pub lhs: usize,
@@ -816,10 +1329,10 @@ impl Instruction for OpPow {
         let lhs = gc.read_reg(self.lhs);
         let rhs = gc.read_reg(self.rhs);
         let reg = match (lhs, rhs) {
-            (Reg::Int(i1), Reg::Int(i2)) => Reg::Float(f64::powf(*i1 as f64, *i2 as f64)),
-            (Reg::Int(i1), Reg::Float(f2)) => Reg::Float(f64::powf(*i1 as f64, *f2)),
-            (Reg::Float(f1), Reg::Int(i2)) => Reg::Float(f64::powf(*f1, *i2 as f64)),
-            (Reg::Float(f1), Reg::Float(f2)) => Reg::Float(f64::powf(*f1, *f2)),
+            (Reg::Int(i1), Reg::Int(i2)) => Reg::Float(powf(*i1 as f64, *i2 as f64)),
+            (Reg::Int(i1), Reg::Float(f2)) => Reg::Float(powf(*i1 as f64, *f2)),
+            (Reg::Float(f1), Reg::Int(i2)) => Reg::Float(powf(*f1, *i2 as f64)),
+            (Reg::Float(f1), Reg::Float(f2)) => Reg::Float(powf(*f1, *f2)),
             _ => {
                 let t1 = get_type(lhs, gc);
                 let t2 = get_type(rhs, gc);
@@ -843,6 +1356,7 @@ impl Instruction for OpPow {
     }
 }
 
+#[derive(Clone)]
 pub struct OpIndex {
     pub loc: Loc,
     pub lhs: usize,
@@ -879,6 +1393,12 @@ impl Instruction for OpIndex {
                             l[l.len() - (idx.unsigned_abs() as usize)].clone()
                         })
                     }
+                } else if let GcObject::Table(t) = unsafe { gc.get_obj_unchecked(*rid) } {
+                    let value = t.indices.get(idx).ok_or_else(|| VmError::NoSuchKey {
+                        loc: self.loc.clone(),
+                        attr: idx.to_string(),
+                    })?;
+                    Ok(value.clone())
                 } else {
                     Err(())
                 }
@@ -907,6 +1427,7 @@ impl Instruction for OpIndex {
     }
 }
 
+#[derive(Clone)]
 pub struct OpSetIndex {
     pub loc: Loc,
     pub rs: usize,
@@ -928,7 +1449,15 @@ impl Instruction for OpSetIndex {
         match (&rd, &idx) {
             (Reg::Ref(rid), Reg::Int(idx)) => {
                 let idx = *idx;
+                let frozen = gc.is_frozen(*rid).then(|| gc.frozen_at(*rid).cloned());
                 if let GcObject::List(l) = unsafe { gc.get_obj_unchecked_mut(*rid) } {
+                    if let Some(frozen_at) = frozen {
+                        return Err(VmError::FrozenObject {
+                            loc: self.loc.clone(),
+                            t: "List".to_string(),
+                            frozen_at,
+                        });
+                    }
                     if (idx >= 0 && idx as usize >= l.len())
                         || (idx < 0 && idx.unsigned_abs() as usize > l.len())
                     {
@@ -946,6 +1475,16 @@ impl Instruction for OpSetIndex {
                         };
                         Ok(())
                     }
+                } else if let GcObject::Table(t) = unsafe { gc.get_obj_unchecked_mut(*rid) } {
+                    if let Some(frozen_at) = frozen {
+                        return Err(VmError::FrozenTable {
+                            loc: self.loc.clone(),
+                            attr: idx.to_string(),
+                            frozen_at,
+                        });
+                    }
+                    t.indices.insert(idx, rs);
+                    Ok(())
                 } else {
                     Err(())
                 }
@@ -973,8 +1512,11 @@ impl Instruction for OpSetIndex {
     }
 }
 
+/// Test if two values are the same object, `==` compares contents instead
+///
+/// An enum value is also a variant given by its constructor, e.g. `v is Shape::Circle`.
+#[derive(Clone)]
 pub struct OpIs {
-    pub loc: Loc,
     pub lhs: usize,
     pub rhs: usize,
     pub rd: usize,
@@ -991,12 +1533,14 @@ impl Instruction for OpIs {
         let lhs = gc.read_reg(self.lhs);
         let rhs = gc.read_reg(self.rhs);
         let reg = match (lhs, rhs) {
-            (Reg::Ref(r1), Reg::Ref(r2)) => Reg::Bool(r1 == r2),
-            _ => {
-                let t1 = get_type(lhs, gc);
-                let t2 = get_type(rhs, gc);
-                return Err(VmError::OpBinNotApplicable(self.loc.clone(), "is", t1, t2));
-            }
+            (Reg::Ref(r1), Reg::Ref(r2)) => Reg::Bool(r1 == r2 || gc.is_variant(*r1, *r2)),
+            (Reg::Str(s1), Reg::Str(s2)) => Reg::Bool(s1 == s2),
+            // Values which are not objects are identical if they are equal
+            (Reg::Unit, Reg::Unit) => Reg::Bool(true),
+            (Reg::Bool(b1), Reg::Bool(b2)) => Reg::Bool(b1 == b2),
+            (Reg::Int(i1), Reg::Int(i2)) => Reg::Bool(i1 == i2),
+            (Reg::Float(f1), Reg::Float(f2)) => Reg::Bool(f1.to_bits() == f2.to_bits()),
+            _ => Reg::Bool(false),
         };
         gc.write_reg(self.rd, reg);
         Ok(Ip {
@@ -1009,20 +1553,41 @@ impl Instruction for OpIs {
         writeln!(
             decompiled,
             "{: >FORMAT_PAD$}    Reg#{} Reg#{} -> Reg#{}",
-            "eq", self.lhs, self.rhs, self.rd
+            "is", self.lhs, self.rhs, self.rd
         )
         .unwrap()
     }
 }
 
-pub struct OpEq {
+/// Test if collection `rhs` contains `lhs`, or does not if `negate` is set
+///
+/// Lists and tuples contain their elements, tables their own keys and strings their substrings.
+/// Objects built on tables, e.g. ranges, thus test their keys rather than what they represent.
+#[derive(Clone)]
+pub struct OpIn {
     pub loc: Loc,
     pub lhs: usize,
     pub rhs: usize,
     pub rd: usize,
+    pub negate: bool,
+}
+
+impl OpIn {
+    /// Elements match if they are equal values or the same object
+    fn matches<Buffer: IoWrite>(x: &Reg, element: &Reg, gc: &Gc<Buffer>) -> bool {
+        match (x, element) {
+            (Reg::Unit, Reg::Unit) => true,
+            (Reg::Bool(b1), Reg::Bool(b2)) => b1 == b2,
+            (Reg::Int(i1), Reg::Int(i2)) => i1 == i2,
+            (Reg::Float(f1), Reg::Float(f2)) => f1 == f2,
+            (Reg::Str(s1), Reg::Str(s2)) => gc.get_str(*s1) == gc.get_str(*s2),
+            (Reg::Ref(r1), Reg::Ref(r2)) => r1 == r2,
+            _ => false,
+        }
+    }
 }
 
-impl Instruction for OpEq {
+impl Instruction for OpIn {
     #[cfg_attr(feature = "profile", inline(never))]
     fn exec<Buffer: IoWrite>(
         &self,
@@ -1032,22 +1597,36 @@ impl Instruction for OpEq {
     ) -> Result<Ip, VmError> {
         let lhs = gc.read_reg(self.lhs);
         let rhs = gc.read_reg(self.rhs);
-        let reg = match (lhs, rhs) {
-            (Reg::Unit, Reg::Unit) => Reg::Bool(true),
-            (Reg::Int(i1), Reg::Int(i2)) => Reg::Bool(*i1 == *i2),
-            (Reg::Bool(b1), Reg::Bool(b2)) => Reg::Bool(*b1 == *b2),
+        let not_applicable = |gc: &Gc<Buffer>| {
+            let op = if self.negate { "not in" } else { "in" };
+            let t1 = get_type(lhs, gc);
+            let t2 = get_type(rhs, gc);
+            VmError::OpBinNotApplicable(self.loc.clone(), op, t1, t2)
+        };
+        let contains = match (lhs, rhs) {
             (Reg::Str(s1), Reg::Str(s2)) => {
-                let s1 = gc.get_str(*s1);
-                let s2 = gc.get_str(*s2);
-                Reg::Bool(s1 == s2)
-            }
-            _ => {
-                let t1 = get_type(lhs, gc);
-                let t2 = get_type(rhs, gc);
-                return Err(VmError::OpBinNotApplicable(self.loc.clone(), "==", t1, t2));
+                let s1 = unsafe { gc.get_str_unchecked(*s1) };
+                let s2 = unsafe { gc.get_str_unchecked(*s2) };
+                s2.contains(s1)
             }
+            (x, Reg::Ref(rid)) => match unsafe { gc.get_obj_unchecked(*rid) } {
+                GcObject::List(elements) | GcObject::Tuple(elements) => {
+                    elements.iter().any(|element| Self::matches(x, element, gc))
+                }
+                GcObject::Table(table) => match x {
+                    Reg::Str(sid) => {
+                        let key = unsafe { gc.get_str_unchecked(*sid) };
+                        gc.get_table_key(key)
+                            .is_some_and(|key| table.attributes.contains_key(&key))
+                    }
+                    Reg::Int(i) => table.indices.get(*i).is_some(),
+                    _ => false,
+                },
+                _ => return Err(not_applicable(gc)),
+            },
+            _ => return Err(not_applicable(gc)),
         };
-        gc.write_reg(self.rd, reg);
+        gc.write_reg(self.rd, Reg::Bool(contains != self.negate));
         Ok(Ip {
             func_id: ip.func_id,
             inst: ip.inst + 1,
@@ -1058,20 +1637,23 @@ impl Instruction for OpEq {
         writeln!(
             decompiled,
             "{: >FORMAT_PAD$}    Reg#{} Reg#{} -> Reg#{}",
-            "eq", self.lhs, self.rhs, self.rd
+            if self.negate { "not_in" } else { "in" },
+            self.lhs,
+            self.rhs,
+            self.rd
         )
         .unwrap()
     }
 }
 
-pub struct OpNe {
-    pub loc: Loc,
-    pub lhs: usize,
-    pub rhs: usize,
+/// Test if a value is `()`, which `?.` and `??` treat as nil
+#[derive(Clone)]
+pub struct OpIsUnit {
+    pub rs: usize,
     pub rd: usize,
 }
 
-impl Instruction for OpNe {
+impl Instruction for OpIsUnit {
     #[cfg_attr(feature = "profile", inline(never))]
     fn exec<Buffer: IoWrite>(
         &self,
@@ -1079,24 +1661,8 @@ impl Instruction for OpNe {
         gc: &mut Gc<Buffer>,
         _out: &mut Buffer,
     ) -> Result<Ip, VmError> {
-        let lhs = gc.read_reg(self.lhs);
-        let rhs = gc.read_reg(self.rhs);
-        let reg = match (lhs, rhs) {
-            (Reg::Int(i1), Reg::Int(i2)) => Reg::Bool(*i1 != *i2),
-            (Reg::Str(s1), Reg::Str(s2)) => {
-                let s1 = unsafe { gc.get_str_unchecked(*s1) };
-                let s2 = unsafe { gc.get_str_unchecked(*s2) };
-                Reg::Bool(s1 != s2)
-            }
-            (Reg::Bool(b1), Reg::Bool(b2)) => Reg::Bool(*b1 != *b2),
-            (Reg::Unit, Reg::Unit) => Reg::Bool(false),
-            _ => {
-                let t1 = get_type(lhs, gc);
-                let t2 = get_type(rhs, gc);
-                return Err(VmError::OpBinNotApplicable(self.loc.clone(), "<>", t1, t2));
-            }
-        };
-        gc.write_reg(self.rd, reg);
+        let is_unit = matches!(gc.read_reg(self.rs), Reg::Unit);
+        gc.write_reg(self.rd, Reg::Bool(is_unit));
         Ok(Ip {
             func_id: ip.func_id,
             inst: ip.inst + 1,
@@ -1106,21 +1672,22 @@ impl Instruction for OpNe {
     fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, _gc: &Gc<Buffer>) {
         writeln!(
             decompiled,
-            "{: >FORMAT_PAD$}    Reg#{} Reg#{} -> Reg#{}",
-            "ne", self.lhs, self.rhs, self.rd
+            "{: >FORMAT_PAD$}    Reg#{} -> Reg#{}",
+            "is_unit", self.rs, self.rd
         )
         .unwrap()
     }
 }
 
-pub struct OpLt {
+#[derive(Clone)]
+pub struct OpEq {
     pub loc: Loc,
     pub lhs: usize,
     pub rhs: usize,
     pub rd: usize,
 }
 
-impl Instruction for OpLt {
+impl Instruction for OpEq {
     #[cfg_attr(feature = "profile", inline(never))]
     fn exec<Buffer: IoWrite>(
         &self,
@@ -1130,24 +1697,7 @@ impl Instruction for OpLt {
     ) -> Result<Ip, VmError> {
         let lhs = gc.read_reg(self.lhs);
         let rhs = gc.read_reg(self.rhs);
-        let reg = match (lhs, rhs) {
-            (Reg::Int(i1), Reg::Int(i2)) => Reg::Bool(*i1 < *i2),
-            (Reg::Int(i1), Reg::Float(f2)) => Reg::Bool((*i1 as f64) < *f2),
-            (Reg::Float(f1), Reg::Int(i2)) => Reg::Bool(*f1 < *i2 as f64),
-            (Reg::Float(f1), Reg::Float(f2)) => Reg::Bool(*f1 < *f2),
-            (Reg::Str(s1), Reg::Str(s2)) => {
-                let s1 = unsafe { gc.get_str_unchecked(*s1) };
-                let s2 = unsafe { gc.get_str_unchecked(*s2) };
-                Reg::Bool(s1 < s2)
-            }
-            (Reg::Bool(b1), Reg::Bool(b2)) => Reg::Bool(bool::lt(b1, b2)),
-            (Reg::Unit, Reg::Unit) => Reg::Bool(false),
-            _ => {
-                let t1 = get_type(lhs, gc);
-                let t2 = get_type(rhs, gc);
-                return Err(VmError::OpBinNotApplicable(self.loc.clone(), "<", t1, t2));
-            }
-        };
+        let reg = Reg::Bool(values_equal(lhs, rhs, gc, &self.loc, "==")?);
         gc.write_reg(self.rd, reg);
         Ok(Ip {
             func_id: ip.func_id,
@@ -1159,20 +1709,21 @@ impl Instruction for OpLt {
         writeln!(
             decompiled,
             "{: >FORMAT_PAD$}    Reg#{} Reg#{} -> Reg#{}",
-            "lt", self.lhs, self.rhs, self.rd
+            "eq", self.lhs, self.rhs, self.rd
         )
         .unwrap()
     }
 }
 
-pub struct OpLe {
+#[derive(Clone)]
+pub struct OpNe {
     pub loc: Loc,
     pub lhs: usize,
     pub rhs: usize,
     pub rd: usize,
 }
 
-impl Instruction for OpLe {
+impl Instruction for OpNe {
     #[cfg_attr(feature = "profile", inline(never))]
     fn exec<Buffer: IoWrite>(
         &self,
@@ -1182,21 +1733,7 @@ impl Instruction for OpLe {
     ) -> Result<Ip, VmError> {
         let lhs = gc.read_reg(self.lhs);
         let rhs = gc.read_reg(self.rhs);
-        let reg = match (lhs, rhs) {
-            (Reg::Int(i1), Reg::Int(i2)) => Reg::Bool(*i1 <= *i2),
-            (Reg::Str(s1), Reg::Str(s2)) => {
-                let s1 = unsafe { gc.get_str_unchecked(*s1) };
-                let s2 = unsafe { gc.get_str_unchecked(*s2) };
-                Reg::Bool(s1 <= s2)
-            }
-            (Reg::Bool(b1), Reg::Bool(b2)) => Reg::Bool(bool::le(b1, b2)),
-            (Reg::Unit, Reg::Unit) => Reg::Bool(true),
-            _ => {
-                let t1 = get_type(lhs, gc);
-                let t2 = get_type(rhs, gc);
-                return Err(VmError::OpBinNotApplicable(self.loc.clone(), "<=", t1, t2));
-            }
-        };
+        let reg = Reg::Bool(!values_equal(lhs, rhs, gc, &self.loc, "<>")?);
         gc.write_reg(self.rd, reg);
         Ok(Ip {
             func_id: ip.func_id,
@@ -1208,20 +1745,21 @@ impl Instruction for OpLe {
         writeln!(
             decompiled,
             "{: >FORMAT_PAD$}    Reg#{} Reg#{} -> Reg#{}",
-            "le", self.lhs, self.rhs, self.rd
+            "ne", self.lhs, self.rhs, self.rd
         )
         .unwrap()
     }
 }
 
-pub struct OpGt {
+#[derive(Clone)]
+pub struct OpLt {
     pub loc: Loc,
     pub lhs: usize,
     pub rhs: usize,
     pub rd: usize,
 }
 
-impl Instruction for OpGt {
+impl Instruction for OpLt {
     #[cfg_attr(feature = "profile", inline(never))]
     fn exec<Buffer: IoWrite>(
         &self,
@@ -1231,24 +1769,82 @@ impl Instruction for OpGt {
     ) -> Result<Ip, VmError> {
         let lhs = gc.read_reg(self.lhs);
         let rhs = gc.read_reg(self.rhs);
-        let reg = match (lhs, rhs) {
-            (Reg::Int(i1), Reg::Int(i2)) => Reg::Bool(*i1 > *i2),
-            (Reg::Int(i1), Reg::Float(f2)) => Reg::Bool((*i1 as f64) > *f2),
-            (Reg::Float(f1), Reg::Int(i2)) => Reg::Bool(*f1 > *i2 as f64),
-            (Reg::Float(f1), Reg::Float(f2)) => Reg::Bool(*f1 > *f2),
-            (Reg::Str(s1), Reg::Str(s2)) => {
-                let s1 = unsafe { gc.get_str_unchecked(*s1) };
-                let s2 = unsafe { gc.get_str_unchecked(*s2) };
-                Reg::Bool(s1 > s2)
-            }
-            (Reg::Bool(b1), Reg::Bool(b2)) => Reg::Bool(bool::gt(b1, b2)),
-            (Reg::Unit, Reg::Unit) => Reg::Bool(false),
-            _ => {
-                let t1 = get_type(lhs, gc);
-                let t2 = get_type(rhs, gc);
-                return Err(VmError::OpBinNotApplicable(self.loc.clone(), ">", t1, t2));
-            }
-        };
+        let ordering = order_values(lhs, rhs, gc, &self.loc, "<")?;
+        let reg = Reg::Bool(matches!(ordering, Some(Ordering::Less)));
+        gc.write_reg(self.rd, reg);
+        Ok(Ip {
+            func_id: ip.func_id,
+            inst: ip.inst + 1,
+        })
+    }
+
+    fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, _gc: &Gc<Buffer>) {
+        writeln!(
+            decompiled,
+            "{: >FORMAT_PAD$}    Reg#{} Reg#{} -> Reg#{}",
+            "lt", self.lhs, self.rhs, self.rd
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Clone)]
+pub struct OpLe {
+    pub loc: Loc,
+    pub lhs: usize,
+    pub rhs: usize,
+    pub rd: usize,
+}
+
+impl Instruction for OpLe {
+    #[cfg_attr(feature = "profile", inline(never))]
+    fn exec<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        _out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        let lhs = gc.read_reg(self.lhs);
+        let rhs = gc.read_reg(self.rhs);
+        let ordering = order_values(lhs, rhs, gc, &self.loc, "<=")?;
+        let reg = Reg::Bool(matches!(ordering, Some(Ordering::Less | Ordering::Equal)));
+        gc.write_reg(self.rd, reg);
+        Ok(Ip {
+            func_id: ip.func_id,
+            inst: ip.inst + 1,
+        })
+    }
+
+    fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, _gc: &Gc<Buffer>) {
+        writeln!(
+            decompiled,
+            "{: >FORMAT_PAD$}    Reg#{} Reg#{} -> Reg#{}",
+            "le", self.lhs, self.rhs, self.rd
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Clone)]
+pub struct OpGt {
+    pub loc: Loc,
+    pub lhs: usize,
+    pub rhs: usize,
+    pub rd: usize,
+}
+
+impl Instruction for OpGt {
+    #[cfg_attr(feature = "profile", inline(never))]
+    fn exec<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        _out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        let lhs = gc.read_reg(self.lhs);
+        let rhs = gc.read_reg(self.rhs);
+        let ordering = order_values(lhs, rhs, gc, &self.loc, ">")?;
+        let reg = Reg::Bool(matches!(ordering, Some(Ordering::Greater)));
         gc.write_reg(self.rd, reg);
         Ok(Ip {
             func_id: ip.func_id,
@@ -1266,6 +1862,7 @@ impl Instruction for OpGt {
     }
 }
 
+#[derive(Clone)]
 pub struct OpGe {
     pub loc: Loc,
     pub lhs: usize,
@@ -1283,21 +1880,11 @@ impl Instruction for OpGe {
     ) -> Result<Ip, VmError> {
         let lhs = gc.read_reg(self.lhs);
         let rhs = gc.read_reg(self.rhs);
-        let reg = match (lhs, rhs) {
-            (Reg::Int(i1), Reg::Int(i2)) => Reg::Bool(*i1 >= *i2),
-            (Reg::Str(s1), Reg::Str(s2)) => {
-                let s1 = unsafe { gc.get_str_unchecked(*s1) };
-                let s2 = unsafe { gc.get_str_unchecked(*s2) };
-                Reg::Bool(s1 >= s2)
-            }
-            (Reg::Bool(b1), Reg::Bool(b2)) => Reg::Bool(bool::ge(b1, b2)),
-            (Reg::Unit, Reg::Unit) => Reg::Bool(true),
-            _ => {
-                let t1 = get_type(lhs, gc);
-                let t2 = get_type(rhs, gc);
-                return Err(VmError::OpBinNotApplicable(self.loc.clone(), ">=", t1, t2));
-            }
-        };
+        let ordering = order_values(lhs, rhs, gc, &self.loc, ">=")?;
+        let reg = Reg::Bool(matches!(
+            ordering,
+            Some(Ordering::Greater | Ordering::Equal)
+        ));
         gc.write_reg(self.rd, reg);
         Ok(Ip {
             func_id: ip.func_id,
@@ -1315,6 +1902,7 @@ impl Instruction for OpGe {
     }
 }
 
+#[derive(Clone)]
 pub struct OpMove {
     pub rs: usize,
     pub rd: usize,
@@ -1346,6 +1934,114 @@ impl Instruction for OpMove {
     }
 }
 
+/// Fail unless `rs` is a `Bool`, which the right hand side of `and` and `or` must be
+///
+/// Only `true` and `false` are conditions, so that `a and b` is a `Bool` as `not a` is.
+#[derive(Clone)]
+pub struct OpAssertBool {
+    pub loc: Loc,
+    pub rs: usize,
+}
+
+impl Instruction for OpAssertBool {
+    #[cfg_attr(feature = "profile", inline(never))]
+    fn exec<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        _out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        let condition = gc.read_reg(self.rs);
+        if !matches!(condition, Reg::Bool(_)) {
+            let t = get_type(condition, gc);
+            return Err(VmError::InvalidCondition(self.loc.clone(), t));
+        }
+        Ok(Ip {
+            func_id: ip.func_id,
+            inst: ip.inst + 1,
+        })
+    }
+
+    fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, _gc: &Gc<Buffer>) {
+        writeln!(
+            decompiled,
+            "{: >FORMAT_PAD$}    Reg#{}",
+            "assert_bool", self.rs
+        )
+        .unwrap()
+    }
+}
+
+/// Mark a global variable of strict mode as assigned
+#[derive(Clone)]
+pub struct OpDefine {
+    pub rd: usize,
+}
+
+impl Instruction for OpDefine {
+    #[cfg_attr(feature = "profile", inline(never))]
+    fn exec<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        _out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        gc.define(self.rd);
+        Ok(Ip {
+            func_id: ip.func_id,
+            inst: ip.inst + 1,
+        })
+    }
+
+    fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, _gc: &Gc<Buffer>) {
+        writeln!(decompiled, "{: >FORMAT_PAD$}    Reg#{}", "define", self.rd).unwrap()
+    }
+}
+
+/// Raise an error if a global variable of strict mode is not assigned yet
+///
+/// Only emitted for globals whose assignment failed in a previous input of a REPL.
+#[derive(Clone)]
+pub struct OpCheckDefined {
+    pub loc: Loc,
+    pub rs: usize,
+    pub name: String,
+    /// Similar name to suggest
+    pub similar: Option<String>,
+}
+
+impl Instruction for OpCheckDefined {
+    #[cfg_attr(feature = "profile", inline(never))]
+    fn exec<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        _out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        if gc.is_undefined(self.rs) {
+            return Err(VmError::UndefinedGlobal {
+                loc: self.loc.clone(),
+                name: self.name.clone(),
+                similar: self.similar.clone(),
+            });
+        }
+        Ok(Ip {
+            func_id: ip.func_id,
+            inst: ip.inst + 1,
+        })
+    }
+
+    fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, _gc: &Gc<Buffer>) {
+        writeln!(
+            decompiled,
+            "{: >FORMAT_PAD$}    Reg#{}",
+            "check_defined", self.rs
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Clone)]
 pub struct OpBranchTrue {
     pub loc: Loc,
     pub condition: usize,
@@ -1389,6 +2085,7 @@ impl Instruction for OpBranchTrue {
     }
 }
 
+#[derive(Clone)]
 pub struct OpBranchFalse {
     pub loc: Loc,
     pub condition: usize,
@@ -1432,6 +2129,7 @@ impl Instruction for OpBranchFalse {
     }
 }
 
+#[derive(Clone)]
 pub struct OpJump {
     pub loc: Loc,
     pub offset: i64,
@@ -1456,6 +2154,7 @@ impl Instruction for OpJump {
     }
 }
 
+#[derive(Clone)]
 pub struct OpDummy;
 
 impl Instruction for OpDummy {
@@ -1476,6 +2175,7 @@ impl Instruction for OpDummy {
 }
 
 /// Pause execution and yield control
+#[derive(Clone)]
 pub struct OpYield {
     pub show_id: Option<usize>,
 }
@@ -1506,6 +2206,7 @@ impl Instruction for OpYield {
     }
 }
 
+#[derive(Clone)]
 pub struct OpSetTuple {
     pub loc: Loc,
     pub rs: usize,
@@ -1523,8 +2224,19 @@ impl Instruction for OpSetTuple {
     ) -> Result<Ip, VmError> {
         let target = gc.read_reg(self.rs).clone();
         let table = gc.read_reg(self.rd).clone();
+        let frozen = match table {
+            Reg::Ref(r) => gc.is_frozen(r).then(|| gc.frozen_at(r).cloned()),
+            _ => None,
+        };
         match table {
             Reg::Ref(r) => match unsafe { gc.get_obj_unchecked_mut(r) } {
+                GcObject::Tuple(_) if frozen.is_some() => {
+                    return Err(VmError::FrozenObject {
+                        loc: self.loc.clone(),
+                        t: "Tuple".to_string(),
+                        frozen_at: frozen.unwrap(),
+                    })
+                }
                 GcObject::Tuple(t) => {
                     let bound = t.len();
                     let item = t
@@ -1565,6 +2277,7 @@ impl Instruction for OpSetTuple {
     }
 }
 
+#[derive(Clone)]
 pub struct OpSetTable {
     pub loc: Loc,
     pub rs: usize,
@@ -1578,18 +2291,35 @@ impl Instruction for OpSetTable {
         &self,
         ip: Ip,
         gc: &mut Gc<Buffer>,
-        _out: &mut Buffer,
+        out: &mut Buffer,
     ) -> Result<Ip, VmError> {
         let target = gc.read_reg(self.rs).clone();
         let table = gc.read_reg(self.rd).clone();
+        if let Reg::Ref(rid) = table {
+            if let Some(setter) = find_property(gc, rid, SETTERS_KEY, self.attr) {
+                let args = vec![table, target];
+                return call_from_inst(ip, setter, args, None, &self.loc, gc, out);
+            }
+        }
         match table {
-            Reg::Ref(r) => match unsafe { gc.get_obj_unchecked_mut(r) } {
-                GcObject::Table(t) => {
-                    t.attributes.insert(self.attr, target);
-                    Ok(())
+            Reg::Ref(r) => {
+                // Class-level members are shared, thus set where they are stored
+                let r = find_static_owner(gc, r, self.attr).unwrap_or(r);
+                match unsafe { gc.get_obj_unchecked_mut(r) } {
+                    GcObject::Table(t) if t.frozen => {
+                        return Err(VmError::FrozenTable {
+                            loc: self.loc.clone(),
+                            attr: gc.look_up_table_key(self.attr).unwrap().to_string(),
+                            frozen_at: gc.frozen_at(r).cloned(),
+                        })
+                    }
+                    GcObject::Table(t) => {
+                        t.attributes.insert(self.attr, target);
+                        Ok(())
+                    }
+                    _ => Err(()),
                 }
-                _ => Err(()),
-            },
+            }
             _ => Err(()),
         }
         .map_err(|_| {
@@ -1619,45 +2349,181 @@ impl Instruction for OpSetTable {
     }
 }
 
+/// Find the getter or setter of property `attr` in meta tables of table `rid`
+///
+/// `accessors` is the key of the table of getters or setters in a meta table.
+fn find_property<Buffer: IoWrite>(
+    gc: &Gc<Buffer>,
+    rid: usize,
+    accessors: usize,
+    attr: usize,
+) -> Option<usize> {
+    let GcObject::Table(table) = (unsafe { gc.get_obj_unchecked(rid) }) else {
+        return None;
+    };
+    let mut meta = table.meta_table;
+    while let Some(meta_id) = meta {
+        let GcObject::Table(meta_table) = (unsafe { gc.get_obj_unchecked(meta_id) }) else {
+            unreachable!()
+        };
+        if let Some(Reg::Ref(table)) = meta_table.attributes.get(&accessors) {
+            if let GcObject::Table(table) = unsafe { gc.get_obj_unchecked(*table) } {
+                if let Some(Reg::Ref(f)) = table.attributes.get(&attr) {
+                    if unsafe { gc.get_obj_unchecked(*f) }.is_callable() {
+                        return Some(*f);
+                    }
+                }
+            }
+        }
+        meta = meta_table.meta_table;
+    }
+    None
+}
+
+/// Look up an attribute in a table, then in its meta table, the meta table of its meta table
+/// and so on
+///
+/// Class-level members in `__static` of a table are looked up right after its own attributes.
+pub(crate) fn find_attr<'a, Buffer: IoWrite>(
+    gc: &'a Gc<Buffer>,
+    table: &'a Table,
+    attr: usize,
+) -> Option<&'a Reg> {
+    let mut table = table;
+    loop {
+        if let Some(value) = table.attributes.get(&attr) {
+            return Some(value);
+        }
+        if let Some(value) = static_members(gc, table).and_then(|t| t.attributes.get(&attr)) {
+            return Some(value);
+        }
+        match unsafe { gc.get_obj_unchecked(table.meta_table?) } {
+            GcObject::Table(meta) => table = meta,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Table of class-level members of a class
+fn static_members<'a, Buffer: IoWrite>(gc: &'a Gc<Buffer>, table: &Table) -> Option<&'a Table> {
+    match table.attributes.get(&STATIC_KEY) {
+        Some(Reg::Ref(rid)) => match unsafe { gc.get_obj_unchecked(*rid) } {
+            GcObject::Table(t) => Some(t),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Find the `__static` table along the meta chain of table `rid` which has attribute `attr`,
+/// unless the table itself has it
+fn find_static_owner<Buffer: IoWrite>(gc: &Gc<Buffer>, rid: usize, attr: usize) -> Option<usize> {
+    match unsafe { gc.get_obj_unchecked(rid) } {
+        GcObject::Table(t) if !t.attributes.contains_key(&attr) => find_static(gc, t, attr),
+        _ => None,
+    }
+}
+
+/// Find the `__static` table which has attribute `attr` along the meta chain of a table
+fn find_static<Buffer: IoWrite>(gc: &Gc<Buffer>, table: &Table, attr: usize) -> Option<usize> {
+    let mut table = table;
+    loop {
+        if let Some(Reg::Ref(members)) = table.attributes.get(&STATIC_KEY) {
+            if let GcObject::Table(t) = unsafe { gc.get_obj_unchecked(*members) } {
+                if t.attributes.contains_key(&attr) {
+                    return Some(*members);
+                }
+            }
+        }
+        match unsafe { gc.get_obj_unchecked(table.meta_table?) } {
+            GcObject::Table(meta) => table = meta,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct OpGetTable {
     pub loc: Loc,
     pub rs: usize,
     pub rd: usize,
     pub attr: usize,
+    /// Bind a callable to `rs`, which must be a different register than `rd`
+    pub bind: bool,
+    /// Read by `.` rather than `::`, instance members of a class can not be read this way
+    pub member: bool,
 }
 
-impl Instruction for OpGetTable {
-    #[inline(never)]
-    fn exec<Buffer: IoWrite>(
+impl OpGetTable {
+    /// Look up getters and then methods registered for the type of user data
+    fn get_user_data_attr<Buffer: IoWrite>(
         &self,
         ip: Ip,
+        rid: usize,
+        type_id: TypeId,
         gc: &mut Gc<Buffer>,
-        _out: &mut Buffer,
+        out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        let no_such_key = |gc: &Gc<Buffer>| VmError::NoSuchKey {
+            loc: self.loc.clone(),
+            attr: gc.look_up_table_key(self.attr).unwrap().to_string(),
+        };
+        let user_type = match gc.get_user_type(type_id) {
+            Some(user_type) => user_type,
+            None => return Err(no_such_key(gc)),
+        };
+        let value = if let Some(getter) = user_type.getters.get(&self.attr) {
+            let f = match unsafe { gc.get_obj_unchecked(*getter) } {
+                GcObject::NativeFunction(f) => f.clone(),
+                _ => unreachable!(),
+            };
+            call_extern(&*f, &[Reg::Ref(rid)], gc, out, &self.loc)?
+        } else {
+            match unsafe { gc.get_obj_unchecked(user_type.methods) } {
+                GcObject::Table(t) => t
+                    .attributes
+                    .get(&self.attr)
+                    .cloned()
+                    .ok_or_else(|| no_such_key(gc))?,
+                _ => unreachable!(),
+            }
+        };
+        gc.write_reg(self.rd, value);
+        Ok(Ip {
+            func_id: ip.func_id,
+            inst: ip.inst + 1,
+        })
+    }
+
+    /// Look up an attribute and write it to `rd`
+    fn get<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        out: &mut Buffer,
     ) -> Result<Ip, VmError> {
         let table = gc.read_reg(self.rs);
         if let Reg::Ref(rid) = table {
             let rid = *rid;
             match unsafe { gc.get_obj_unchecked(rid) } {
                 GcObject::Table(t) => {
-                    let value = t
-                        .attributes
-                        .get(&self.attr)
-                        .or_else(|| {
-                            t.meta_table.and_then(|meta_table| {
-                                let meta_table = unsafe { gc.get_obj_unchecked(meta_table) };
-                                let meta_table = if let GcObject::Table(t) = meta_table {
-                                    t.attributes.get(&self.attr)
-                                } else {
-                                    unreachable!()
-                                };
-                                meta_table
-                            })
-                        })
+                    let value = find_attr(gc, t, self.attr)
                         .ok_or(VmError::NoSuchKey {
                             loc: self.loc.clone(),
                             attr: gc.look_up_table_key(self.attr).unwrap().to_string(),
                         })?
                         .clone();
+                    if self.member && t.attributes.contains_key(&STATIC_KEY) {
+                        let attr = gc.look_up_table_key(self.attr).unwrap();
+                        let is_static = !t.attributes.contains_key(&self.attr)
+                            && find_static(gc, t, self.attr).is_some();
+                        if !is_static && !attr.starts_with("__") {
+                            return Err(VmError::InstanceMemberAccess {
+                                loc: self.loc.clone(),
+                                attr: attr.to_string(),
+                            });
+                        }
+                    }
                     gc.write_reg(self.rd, value);
                     return Ok(Ip {
                         func_id: ip.func_id,
@@ -1684,6 +2550,10 @@ impl Instruction for OpGetTable {
                         unreachable!()
                     }
                 }
+                GcObject::UserData => {
+                    let type_id = (**gc.get_user_data(rid).unwrap()).type_id();
+                    return self.get_user_data_attr(ip, rid, type_id, gc, out);
+                }
                 _ => (),
             }
         }
@@ -1734,6 +2604,41 @@ impl Instruction for OpGetTable {
         }
     }
 
+    /// Replace a callable in `rd` by a method bound to the receiver in `rs`
+    fn bind_method<Buffer: IoWrite>(&self, gc: &mut Gc<Buffer>) {
+        debug_assert_ne!(self.rs, self.rd);
+        if let Reg::Ref(method) = *gc.read_reg(self.rd) {
+            if unsafe { gc.get_obj_unchecked(method) }.is_callable() {
+                let receiver = gc.read_reg(self.rs).clone();
+                let rid = gc.alloc_obj(GcObject::BoundMethod { receiver, method });
+                gc.write_reg(self.rd, Reg::Ref(rid));
+            }
+        }
+    }
+}
+
+impl Instruction for OpGetTable {
+    #[inline(never)]
+    fn exec<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        // Properties take precedence over attributes, their values are never bound
+        if let Reg::Ref(rid) = *gc.read_reg(self.rs) {
+            if let Some(getter) = find_property(gc, rid, GETTERS_KEY, self.attr) {
+                let args = vec![Reg::Ref(rid)];
+                return call_from_inst(ip, getter, args, Some(self.rd), &self.loc, gc, out);
+            }
+        }
+        let ip = self.get(ip, gc, out)?;
+        if self.bind {
+            self.bind_method(gc);
+        }
+        Ok(ip)
+    }
+
     fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, gc: &Gc<Buffer>) {
         writeln!(
             decompiled,
@@ -1747,6 +2652,64 @@ impl Instruction for OpGetTable {
     }
 }
 
+/// Look up an attribute from the meta table of the table at `rs`, which defines the running
+/// method, see `lower_super`
+///
+/// The attribute is never bound, as the receiver of the method is passed explicitly.
+#[derive(Clone)]
+pub struct OpGetSuper {
+    pub loc: Loc,
+    pub rs: usize,
+    pub rd: usize,
+    pub attr: usize,
+}
+
+impl Instruction for OpGetSuper {
+    #[cfg_attr(feature = "profile", inline(never))]
+    fn exec<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        _out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        let meta = match gc.read_reg(self.rs) {
+            Reg::Ref(rid) => match unsafe { gc.get_obj_unchecked(*rid) } {
+                GcObject::Table(t) => t.meta_table,
+                _ => None,
+            },
+            _ => None,
+        };
+        let value = meta
+            .and_then(|meta| match unsafe { gc.get_obj_unchecked(meta) } {
+                GcObject::Table(meta) => find_attr(gc, meta, self.attr),
+                _ => unreachable!(),
+            })
+            .ok_or_else(|| VmError::NoSuchKey {
+                loc: self.loc.clone(),
+                attr: gc.look_up_table_key(self.attr).unwrap().to_string(),
+            })?
+            .clone();
+        gc.write_reg(self.rd, value);
+        Ok(Ip {
+            func_id: ip.func_id,
+            inst: ip.inst + 1,
+        })
+    }
+
+    fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, gc: &Gc<Buffer>) {
+        writeln!(
+            decompiled,
+            "{: >FORMAT_PAD$}    Reg#{}.{} -> Reg#{}",
+            "get_super",
+            self.rs,
+            gc.look_up_table_key(self.attr).unwrap(),
+            self.rd
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Clone)]
 pub struct OpGetTuple {
     pub loc: Loc,
     pub rs: usize,
@@ -1799,6 +2762,7 @@ impl Instruction for OpGetTuple {
     }
 }
 
+#[derive(Clone)]
 pub struct OpMakeTable {
     pub rd: usize,
 }
@@ -1813,7 +2777,9 @@ impl Instruction for OpMakeTable {
     ) -> Result<Ip, VmError> {
         let table = gc.alloc_obj(GcObject::Table(Table {
             attributes: BTreeMap::new(),
+            indices: Default::default(),
             meta_table: None,
+            frozen: false,
         }));
         let table = Reg::Ref(table);
         gc.write_reg(self.rd, table);
@@ -1833,6 +2799,7 @@ impl Instruction for OpMakeTable {
     }
 }
 
+#[derive(Clone)]
 pub struct OpMakeTuple {
     pub rd: usize,
     pub size: usize,
@@ -1865,6 +2832,7 @@ impl Instruction for OpMakeTuple {
     }
 }
 
+#[derive(Clone)]
 pub struct OpMakeList {
     pub rd: usize,
     pub items: Vec<usize>,
@@ -1902,6 +2870,116 @@ impl Instruction for OpMakeList {
     }
 }
 
+/// Append items of a list or tuple to a list
+#[derive(Clone)]
+pub struct OpExtendList {
+    pub loc: Loc,
+    pub rs: usize,
+    pub rd: usize,
+}
+
+impl Instruction for OpExtendList {
+    #[inline(never)]
+    fn exec<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        _out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        let rs = gc.read_reg(self.rs);
+        let items = match rs {
+            Reg::Ref(rid) => match unsafe { gc.get_obj_unchecked(*rid) } {
+                GcObject::List(items) | GcObject::Tuple(items) => Some(items.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| VmError::CanNotSpread {
+            loc: self.loc.clone(),
+            t: get_type(rs, gc),
+            into: "List",
+        })?;
+        match gc.read_reg(self.rd) {
+            Reg::Ref(rid) => match unsafe { gc.get_obj_unchecked_mut(*rid) } {
+                GcObject::List(list) => list.extend(items),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+        Ok(Ip {
+            func_id: ip.func_id,
+            inst: ip.inst + 1,
+        })
+    }
+
+    fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, _gc: &Gc<Buffer>) {
+        writeln!(
+            decompiled,
+            "{: >FORMAT_PAD$}    Reg#{}... -> Reg#{}",
+            "extend_list", self.rs, self.rd
+        )
+        .unwrap()
+    }
+}
+
+/// Copy attributes and integer keys of a table to another, meta table is not copied
+#[derive(Clone)]
+pub struct OpExtendTable {
+    pub loc: Loc,
+    pub rs: usize,
+    pub rd: usize,
+}
+
+impl Instruction for OpExtendTable {
+    #[inline(never)]
+    fn exec<Buffer: IoWrite>(
+        &self,
+        ip: Ip,
+        gc: &mut Gc<Buffer>,
+        _out: &mut Buffer,
+    ) -> Result<Ip, VmError> {
+        let rs = gc.read_reg(self.rs);
+        let (attributes, indices) = match rs {
+            Reg::Ref(rid) => match unsafe { gc.get_obj_unchecked(*rid) } {
+                GcObject::Table(table) => Some((table.attributes.clone(), table.indices.clone())),
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| VmError::CanNotSpread {
+            loc: self.loc.clone(),
+            t: get_type(rs, gc),
+            into: "Table",
+        })?;
+        match gc.read_reg(self.rd) {
+            Reg::Ref(rid) => match unsafe { gc.get_obj_unchecked_mut(*rid) } {
+                GcObject::Table(table) => {
+                    table.attributes.extend(attributes);
+                    indices
+                        .iter()
+                        .for_each(|(key, value)| table.indices.insert(key, value.clone()));
+                }
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+        Ok(Ip {
+            func_id: ip.func_id,
+            inst: ip.inst + 1,
+        })
+    }
+
+    fn decompile<Buffer: IoWrite>(&self, decompiled: &mut String, _gc: &Gc<Buffer>) {
+        writeln!(
+            decompiled,
+            "{: >FORMAT_PAD$}    Reg#{}... -> Reg#{}",
+            "extend_table", self.rs, self.rd
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Clone)]
 pub struct OpSetMeta {
     pub rs: usize,
     pub rd: usize,
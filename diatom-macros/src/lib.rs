@@ -0,0 +1,59 @@
+//! Procedural macros of diatom, used as `diatom::script!` and `diatom::include_script!`
+//!
+//! Syntax of embedded scripts is checked with the parser of `diatom-core` while the host crate
+//! is compiled, thus a syntax error of a script is a compile error of the host.
+use std::{env, fs, path::PathBuf};
+
+use diatom_core::{
+    lint::{LintConfig, LintLevel},
+    ColorChoice, Interpreter, RenderOptions,
+};
+use diatom_std_core::StdLibCore;
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, LitStr};
+
+/// Parse `code` without compiling it, return rendered errors if there is any
+fn check(code: &str, source: &str) -> Result<(), String> {
+    let mut interpreter = Interpreter::<Vec<u8>, StdLibCore>::new(vec![]);
+    interpreter.set_render_options(RenderOptions::default().color(ColorChoice::Never));
+    let config = LintConfig::default().set_all(LintLevel::Allow);
+    interpreter.lint(code, source, &config).map(|_| ())
+}
+
+/// Embed a script given as a string literal
+#[proc_macro]
+pub fn script(input: TokenStream) -> TokenStream {
+    let code = parse_macro_input!(input as LitStr);
+    if let Err(err) = check(&code.value(), "<script>") {
+        return syn::Error::new(code.span(), err).to_compile_error().into();
+    }
+    quote! {
+        ::diatom::EmbeddedScript::__new(#code, concat!(file!(), ":", line!()))
+    }
+    .into()
+}
+
+/// Embed a script read from a file, whose path is relative to the root of the host crate
+#[proc_macro]
+pub fn include_script(input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(input as LitStr);
+    let mut full_path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap_or_default());
+    full_path.push(path.value());
+    let code = match fs::read_to_string(&full_path) {
+        Ok(code) => code,
+        Err(err) => {
+            let err = format!("Can not read `{}`: {err}", full_path.display());
+            return syn::Error::new(path.span(), err).to_compile_error().into();
+        }
+    };
+    if let Err(err) = check(&code, &path.value()) {
+        return syn::Error::new(path.span(), err).to_compile_error().into();
+    }
+    // Read by `include_str!` so that the host is rebuilt when the file changes
+    let full_path = full_path.to_string_lossy();
+    quote! {
+        ::diatom::EmbeddedScript::__new(include_str!(#full_path), #path)
+    }
+    .into()
+}
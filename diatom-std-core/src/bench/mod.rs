@@ -0,0 +1,151 @@
+use std::fmt;
+
+use diatom_core::ffi::{DiatomObject, State};
+
+use super::*;
+
+/// Timing of a benchmark, durations are in nanoseconds
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchStats {
+    pub name: String,
+    pub iterations: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+}
+
+impl BenchStats {
+    /// Compute statistics of samples, return `None` if there is no sample
+    pub fn from_samples(name: impl Into<String>, samples: &[i64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let n = sorted.len();
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] + sorted[n / 2]) as f64 / 2.0
+        } else {
+            sorted[n / 2] as f64
+        };
+        // Nearest rank
+        let p95 = sorted[(n * 95).div_ceil(100) - 1] as f64;
+        Some(Self {
+            name: name.into(),
+            iterations: n,
+            mean: sorted.iter().sum::<i64>() as f64 / n as f64,
+            median,
+            p95,
+        })
+    }
+}
+
+/// Format a duration in nanoseconds with a readable unit
+pub fn format_duration(ns: f64) -> String {
+    if ns < 1e3 {
+        format!("{ns:.0} ns")
+    } else if ns < 1e6 {
+        format!("{:.2} µs", ns / 1e3)
+    } else if ns < 1e9 {
+        format!("{:.2} ms", ns / 1e6)
+    } else {
+        format!("{:.2} s", ns / 1e9)
+    }
+}
+
+impl fmt::Display for BenchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: mean {}, median {}, p95 {} ({} iterations)",
+            self.name,
+            format_duration(self.mean),
+            format_duration(self.median),
+            format_duration(self.p95),
+            self.iterations
+        )
+    }
+}
+
+/// Benchmarks recorded by `run` of `std.bench`
+///
+/// Set it as context of an interpreter to collect results instead of printing them.
+#[derive(Default)]
+pub struct BenchResults(pub Vec<BenchStats>);
+
+fn to_samples<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    value: &DiatomValue,
+) -> Result<Vec<i64>, String> {
+    let list = match value {
+        DiatomValue::Ref(rid) => match state.get_obj(*rid) {
+            Some(DiatomObject::List(list)) => list,
+            _ => return Err("Expected type `List` as samples".to_string()),
+        },
+        _ => return Err("Expected type `List` as samples".to_string()),
+    };
+    (0..list.len())
+        .map(|i| match list.get(i).unwrap() {
+            DiatomValue::Int(i) => Ok(i),
+            _ => Err("Expected samples of type `Int`".to_string()),
+        })
+        .collect()
+}
+
+fn bench_util_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "now".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 0);
+            match state.now() {
+                Some(now) => Ok(DiatomValue::Int(now.as_nanos() as i64)),
+                None => Err("No clock is supplied by host".to_string()),
+            }
+        }),
+    );
+
+    funcs.insert(
+        "record".to_string(),
+        Arc::new(|state, parameters, out| {
+            assure_para_len!(parameters, 2);
+            let name = match parameters[0] {
+                DiatomValue::Str(sid) => state.get_string_by_id(sid).unwrap().to_string(),
+                _ => return Err("Expected type `String` as name of benchmark".to_string()),
+            };
+            let samples = to_samples(state, &parameters[1])?;
+            let stats = BenchStats::from_samples(name, &samples)
+                .ok_or_else(|| "Benchmark must run at least 1 iteration".to_string())?;
+            let table = state
+                .create_table([
+                    ("mean", DiatomValue::Float(stats.mean)),
+                    ("median", DiatomValue::Float(stats.median)),
+                    ("p95", DiatomValue::Float(stats.p95)),
+                ])
+                .unwrap();
+            match state.context_mut::<BenchResults>() {
+                Some(results) => results.0.push(stats),
+                None => writeln!(out, "{stats}").map_err(|err| format!("IoError: {err}"))?,
+            }
+            Ok(DiatomValue::Ref(table))
+        }),
+    );
+
+    Extension {
+        name: "util".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
+
+pub fn bench_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    Extension {
+        name: "bench".to_string(),
+        kind: ExtensionKind::SubExtensions(vec![
+            bench_util_extension(),
+            Extension {
+                name: "mod".to_string(),
+                kind: ExtensionKind::File(include_str!("bench.dm").to_string()),
+            },
+        ]),
+    }
+}
@@ -1,40 +1,158 @@
+use std::io;
+
+use diatom_core::ffi::{DiatomObject, State};
+
 use super::*;
 
+/// Write values separated by spaces
+fn print_values<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    parameters: &[DiatomValue],
+    out: &mut Buffer,
+) -> io::Result<()> {
+    for (i, parameter) in parameters.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b" ")?;
+        }
+        state.print_to(parameter, out)?;
+    }
+    Ok(())
+}
+
+/// Get depth limit from parameters of `inspect(value, depth)`
+fn inspect_depth(parameters: &[DiatomValue]) -> Result<usize, String> {
+    match parameters {
+        [_] => Ok(DEFAULT_INSPECT_DEPTH),
+        [_, DiatomValue::Int(i)] if *i >= 0 => Ok(*i as usize),
+        [_, DiatomValue::Int(i)] => Err(format!("Depth limit must not be negative, got {i}")),
+        [_, _] => Err("Expected type `Int` for depth limit".to_string()),
+        _ => Err(format!(
+            "Expected 1 or 2 parameter while {} is provided",
+            parameters.len()
+        )),
+    }
+}
+
+/// Names of methods required by an interface
+fn required_methods<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    interface: &DiatomValue,
+) -> Result<Vec<String>, String> {
+    let not_interface = || "Expected an interface declared by `interface`".to_string();
+    let methods = match interface {
+        DiatomValue::Ref(rid) => match state.get_obj(*rid) {
+            Some(DiatomObject::Table(table)) => table.get_field("__methods"),
+            _ => None,
+        },
+        _ => None,
+    };
+    let methods = match methods {
+        Some(DiatomValue::Ref(rid)) => match state.get_obj(rid) {
+            Some(DiatomObject::List(methods)) => methods,
+            _ => return Err(not_interface()),
+        },
+        _ => return Err(not_interface()),
+    };
+    (0..methods.len())
+        .map(|i| match methods.get(i) {
+            Some(DiatomValue::Str(sid)) => Ok(state.get_string_by_id(sid).unwrap().to_string()),
+            _ => Err(not_interface()),
+        })
+        .collect()
+}
+
 pub fn built_in_extension<Buffer: IoWrite>() -> Extension<Buffer> {
     let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
     funcs.insert(
         "print".to_string(),
         Arc::new(|state, parameters, out| {
-            let mut flag = true;
-            for parameter in parameters {
-                if flag {
-                    flag = false
-                } else {
-                    write!(out, " ").map_err(|err| format!("IoError: {err}"))?;
-                }
-
-                let text = state.print(parameter);
-                write!(out, "{text}").map_err(|err| format!("IoError: {err}"))?;
-            }
+            print_values(state, parameters, out).map_err(|err| format!("IoError: {err}"))?;
             Ok(DiatomValue::Unit)
         }),
     );
     funcs.insert(
         "println".to_string(),
         Arc::new(|state, parameters, out| {
-            let mut flag = true;
-            for parameter in parameters {
-                if flag {
-                    flag = false
-                } else {
-                    write!(out, " ").map_err(|err| format!("IoError: {err}"))?;
+            print_values(state, parameters, out)
+                .and_then(|_| out.write_all(b"\n"))
+                .and_then(|_| out.flush())
+                .map_err(|err| format!("IoError: {err}"))?;
+            Ok(DiatomValue::Unit)
+        }),
+    );
+    funcs.insert(
+        "write".to_string(),
+        Arc::new(|state, parameters, out| {
+            assure_para_len!(parameters, 1);
+            state
+                .print_to(&parameters[0], out)
+                .map_err(|err| format!("IoError: {err}"))?;
+            Ok(DiatomValue::Unit)
+        }),
+    );
+    funcs.insert(
+        "write_line".to_string(),
+        Arc::new(|state, parameters, out| {
+            assure_para_len!(parameters, 1);
+            state
+                .print_to(&parameters[0], out)
+                .and_then(|_| out.write_all(b"\n"))
+                .and_then(|_| out.flush())
+                .map_err(|err| format!("IoError: {err}"))?;
+            Ok(DiatomValue::Unit)
+        }),
+    );
+    funcs.insert(
+        "flush".to_string(),
+        Arc::new(|_, parameters, out| {
+            assure_para_len!(parameters, 0);
+            out.flush().map_err(|err| format!("IoError: {err}"))?;
+            Ok(DiatomValue::Unit)
+        }),
+    );
+    funcs.insert(
+        "inspect".to_string(),
+        Arc::new(|state, parameters, _| {
+            let depth = inspect_depth(parameters)?;
+            let text = state.inspect(&parameters[0], depth);
+            Ok(DiatomValue::Str(state.create_str(text)))
+        }),
+    );
+    funcs.insert(
+        "pp".to_string(),
+        Arc::new(|state, parameters, out| {
+            let depth = inspect_depth(parameters)?;
+            let text = state.inspect(&parameters[0], depth);
+            writeln!(out, "{text}").map_err(|err| format!("IoError: {err}"))?;
+            Ok(DiatomValue::Unit)
+        }),
+    );
+    funcs.insert(
+        "input".to_string(),
+        Arc::new(|state, parameters, out| {
+            match parameters {
+                [] => (),
+                [DiatomValue::Str(sid)] => {
+                    let prompt = state.get_string_by_id(*sid).unwrap();
+                    write!(out, "{prompt}").map_err(|err| format!("IoError: {err}"))?;
+                    out.flush().map_err(|err| format!("IoError: {err}"))?;
+                }
+                [_] => return Err("Expected type `String` for prompt".to_string()),
+                _ => {
+                    return Err(format!(
+                        "Expected 0 or 1 parameter while {} is provided",
+                        parameters.len()
+                    ))
                 }
-
-                let text = state.print(parameter);
-                write!(out, "{text}").map_err(|err| format!("IoError: {err}"))?;
             }
-            writeln!(out).map_err(|err| format!("IoError: {err}"))?;
-            Ok(DiatomValue::Unit)
+            let rid = match state.read_line().map_err(|err| format!("IoError: {err}"))? {
+                Some(line) => {
+                    let sid = state.create_str(line);
+                    state.create_some(DiatomValue::Str(sid)).unwrap()
+                }
+                None => state.create_none(),
+            };
+            Ok(DiatomValue::Ref(rid))
         }),
     );
     funcs.insert(
@@ -77,6 +195,92 @@ pub fn built_in_extension<Buffer: IoWrite>() -> Extension<Buffer> {
             }
         }),
     );
+    funcs.insert(
+        "implements".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 2);
+            let methods = required_methods(state, &parameters[1])?;
+            let implements =
+                methods
+                    .iter()
+                    .all(|method| match state.get_attr(&parameters[0], method) {
+                        Some(DiatomValue::Ref(rid)) => matches!(
+                            state.get_obj(rid),
+                            Some(
+                                DiatomObject::Closure(_)
+                                    | DiatomObject::ForeignFunction
+                                    | DiatomObject::BoundMethod { .. }
+                            )
+                        ),
+                        _ => false,
+                    });
+            Ok(DiatomValue::Bool(implements))
+        }),
+    );
+    funcs.insert(
+        "fn_info".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 1);
+            let Some(info) = state.function_info(&parameters[0]) else {
+                return Ok(DiatomValue::Ref(state.create_none()));
+            };
+            let name = match info.name {
+                Some(name) => {
+                    let sid = state.create_str(name);
+                    state.create_some(DiatomValue::Str(sid)).unwrap()
+                }
+                None => state.create_none(),
+            };
+            let names = info
+                .parameters
+                .into_iter()
+                .map(|name| DiatomValue::Str(state.create_str(name)))
+                .collect();
+            let names = state.create_list(names).unwrap();
+            let mut fields = vec![
+                ("name", DiatomValue::Ref(name)),
+                ("parameters", DiatomValue::Ref(names)),
+                ("arity", DiatomValue::Int(info.arity as i64)),
+            ];
+            if let Some(location) = info.location {
+                let path = state.create_str(location.path);
+                fields.push(("file", DiatomValue::Str(path)));
+                fields.push(("line", DiatomValue::Int(location.line as i64)));
+            }
+            let table = state.create_table(fields).unwrap();
+            Ok(DiatomValue::Ref(
+                state.create_some(DiatomValue::Ref(table)).unwrap(),
+            ))
+        }),
+    );
+    // Returns the collection itself, e.g. `config = freeze({debug = false})`
+    funcs.insert(
+        "freeze".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 1);
+            match parameters[0] {
+                DiatomValue::Ref(rid)
+                    if matches!(
+                        state.get_obj(rid),
+                        Some(
+                            DiatomObject::List(_) | DiatomObject::Tuple(_) | DiatomObject::Table(_)
+                        )
+                    ) =>
+                {
+                    state.freeze(&parameters[0]);
+                    Ok(parameters[0].clone())
+                }
+                _ => Err("Expected type `List`, `Tuple` or `Table` to freeze".to_string()),
+            }
+        }),
+    );
+    funcs.insert(
+        "is_frozen".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 1);
+            Ok(DiatomValue::Bool(state.is_frozen(&parameters[0])))
+        }),
+    );
     funcs.insert(
         "collect".to_string(),
         Arc::new(|state, parameters, _| {
@@ -0,0 +1,239 @@
+use std::{
+    collections::VecDeque,
+    sync::{Condvar, Mutex},
+};
+
+use diatom_core::{
+    ffi::{Args, DiatomData, DiatomObject, FromDiatom, IntoDiatom, State},
+    host::Deadline,
+};
+
+use super::*;
+use crate::shared::SharedValue;
+
+/// A value in a channel
+enum Message {
+    /// Deep copy of the value sent
+    Copied(DiatomData),
+    /// Handle of shared data, sent without copying
+    Shared(SharedValue),
+}
+
+impl Message {
+    fn into_data(self) -> DiatomData {
+        match self {
+            Message::Copied(data) => data,
+            Message::Shared(shared) => shared.to_data(),
+        }
+    }
+}
+
+impl IntoDiatom for Message {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        match self {
+            Message::Copied(data) => data.into_diatom(state),
+            Message::Shared(shared) => shared.into_diatom(state),
+        }
+    }
+}
+
+struct Queue {
+    values: VecDeque<Message>,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+/// Message channel that can be shared by interpreters on different threads
+///
+/// Values are deep copied into `DiatomData` when sent, thus functions, user data and values
+/// containing themselves can not be sent. Data shared by `share` is sent by reference instead,
+/// and receivers in scripts get the same `SharedValue`. All endpoints are equivalent: any clone
+/// can send and receive. Once closed, sending fails and receiving returns `None` after all values
+/// are taken.
+///
+/// In scripts, a channel is created by `channel()` and has methods `send(value)`, `recv()`,
+/// `try_recv()` and `close()`, where receiving methods return an `Option`. Hosts hand an endpoint to an interpreter by converting it into a
+/// diatom value, e.g. `interpreter.set_global("ch", channel.clone())`.
+#[derive(Clone)]
+pub struct Channel {
+    shared: Arc<Shared>,
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Channel {
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                queue: Mutex::new(Queue {
+                    values: VecDeque::new(),
+                    closed: false,
+                }),
+                ready: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Return `Err` if channel is closed
+    pub fn send(&self, value: DiatomData) -> Result<(), String> {
+        self.push(Message::Copied(value))
+    }
+
+    /// Send shared data without copying it, return `Err` if channel is closed
+    pub fn send_shared(&self, value: SharedValue) -> Result<(), String> {
+        self.push(Message::Shared(value))
+    }
+
+    fn push(&self, message: Message) -> Result<(), String> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.closed {
+            return Err("Can not send to a closed channel".to_string());
+        }
+        queue.values.push_back(message);
+        self.shared.ready.notify_one();
+        Ok(())
+    }
+
+    /// Block until a value is available, return None if channel is closed and empty
+    ///
+    /// Shared data is copied, as `DiatomData` owns all its values.
+    pub fn recv(&self) -> Option<DiatomData> {
+        self.recv_message().map(Message::into_data)
+    }
+
+    fn recv_message(&self) -> Option<Message> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        loop {
+            if let Some(value) = queue.values.pop_front() {
+                return Some(value);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = self.shared.ready.wait(queue).unwrap();
+        }
+    }
+
+    /// Same as `recv` but give up once the deadline passes
+    fn recv_before(&self, deadline: &Deadline) -> Result<Option<Message>, String> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        loop {
+            if let Some(value) = queue.values.pop_front() {
+                return Ok(Some(value));
+            }
+            if queue.closed {
+                return Ok(None);
+            }
+            let remaining = deadline.remaining();
+            if remaining.is_zero() {
+                return Err("No value is received from the channel before the deadline".to_string());
+            }
+            queue = self.shared.ready.wait_timeout(queue, remaining).unwrap().0;
+        }
+    }
+
+    /// Return None immediately if no value is available
+    pub fn try_recv(&self) -> Option<DiatomData> {
+        self.try_recv_message().map(Message::into_data)
+    }
+
+    fn try_recv_message(&self) -> Option<Message> {
+        self.shared.queue.lock().unwrap().values.pop_front()
+    }
+
+    /// Close the channel for all endpoints and wake up all receivers
+    pub fn close(&self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.ready.notify_all();
+    }
+}
+
+fn option<Buffer: IoWrite>(state: &mut State<Buffer>, value: Option<Message>) -> DiatomValue {
+    value.into_diatom(state)
+}
+
+/// Register methods of `Channel` unless they are registered
+fn register<Buffer: IoWrite>(state: &mut State<Buffer>) {
+    if state.is_type_registered::<Channel>() {
+        return;
+    }
+    state
+        .register_type::<Channel>()
+        .cloneable()
+        .method("send", |channel, state, parameters| {
+            let args = Args::new("Channel.send", parameters);
+            args.expect_len(1)?;
+            match args.get_as::<SharedValue, _>(state, 0) {
+                Ok(shared) => channel.send_shared(shared)?,
+                Err(_) => channel.send(args.get_as::<DiatomData, _>(state, 0)?)?,
+            }
+            Ok(DiatomValue::Unit)
+        })
+        .method("recv", |channel, state, parameters| {
+            Args::new("Channel.recv", parameters).expect_len(0)?;
+            // Waiting ends with the execution once time limit of the sandbox is reached
+            let value = match state.deadline().cloned() {
+                Some(deadline) => channel
+                    .recv_before(&deadline)
+                    .map_err(|err| state.interrupt(err))?,
+                None => channel.recv_message(),
+            };
+            Ok(option(state, value))
+        })
+        .method("try_recv", |channel, state, parameters| {
+            Args::new("Channel.try_recv", parameters).expect_len(0)?;
+            Ok(option(state, channel.try_recv_message()))
+        })
+        .method("close", |channel, _, parameters| {
+            Args::new("Channel.close", parameters).expect_len(0)?;
+            channel.close();
+            Ok(DiatomValue::Unit)
+        });
+}
+
+/// User data whose methods receive the channel as the first parameter
+impl IntoDiatom for Channel {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        register(state);
+        DiatomValue::Ref(state.create_user_data(Box::new(self)))
+    }
+}
+
+impl FromDiatom for Channel {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        match value {
+            DiatomValue::Ref(rid) => match state.get_obj(*rid) {
+                Some(DiatomObject::UserData(data)) => data.downcast_ref::<Channel>().cloned(),
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| "Expected a `Channel`".to_string())
+    }
+}
+
+pub fn channel_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "channel".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 0);
+            Ok(Channel::new().into_diatom(state))
+        }),
+    );
+    Extension {
+        name: "channel".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
@@ -0,0 +1,177 @@
+use diatom_core::ffi::{Args, DiatomObject, State};
+
+use super::*;
+
+/// Wrap a conversion result into diatom `Result`
+fn to_result<Buffer: IoWrite>(
+    state: &mut State<Buffer>,
+    result: Result<DiatomValue, String>,
+) -> DiatomValue {
+    let rid = match result {
+        Ok(value) => state.create_ok(value),
+        Err(err) => {
+            let sid = state.create_str(err);
+            state.create_err(DiatomValue::Str(sid))
+        }
+    };
+    DiatomValue::Ref(rid.unwrap())
+}
+
+/// Parse an integer with given base
+///
+/// Unlike `i64::from_str_radix`, error message tells exactly what goes wrong.
+fn parse_int(s: &str, base: u32) -> Result<i64, String> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if s.is_empty() {
+        return Err("Can not parse an empty string as `Int`".to_string());
+    }
+    if digits.is_empty() {
+        return Err(format!("No digits found in `{s}`"));
+    }
+    let offset = s.len() - digits.len();
+    let mut value: i64 = 0;
+    for (i, c) in digits.chars().enumerate() {
+        let digit = c.to_digit(base).ok_or_else(|| {
+            format!(
+                "Invalid digit `{c}` at position {} for base {base}",
+                i + offset
+            )
+        })? as i64;
+        // Accumulate negative numbers directly so that `Int::MIN` can be parsed
+        value = value
+            .checked_mul(base as i64)
+            .and_then(|v| {
+                if negative {
+                    v.checked_sub(digit)
+                } else {
+                    v.checked_add(digit)
+                }
+            })
+            .ok_or_else(|| format!("`{s}` is out of the range of `Int`"))?;
+    }
+    Ok(value)
+}
+
+/// Truth value of a value converted by `bool`
+///
+/// `false`, `()`, zero, NaN, empty strings, lists and tuples and `Option::None` are false, all
+/// other values are true. Conditions never convert values this way, they must be `Bool`.
+fn truthy<Buffer: IoWrite>(state: &State<Buffer>, value: &DiatomValue) -> bool {
+    match value {
+        DiatomValue::Unit => false,
+        DiatomValue::Bool(b) => *b,
+        DiatomValue::Int(i) => *i != 0,
+        DiatomValue::Float(f) => *f != 0.0 && !f.is_nan(),
+        DiatomValue::Str(sid) => !state.get_string_by_id(*sid).unwrap().is_empty(),
+        DiatomValue::Ref(rid) => match state.get_obj(*rid) {
+            Some(DiatomObject::List(list)) => !list.is_empty(),
+            Some(DiatomObject::Tuple(tuple)) => !tuple.is_empty(),
+            _ => !state.is_option_none(value),
+        },
+    }
+}
+
+pub fn convert_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "int".to_string(),
+        Arc::new(|state, parameters, _| {
+            let args = Args::new("int", parameters);
+            args.expect_len_between(1, 2)?;
+            let base = args.get_opt::<i64>(1)?.unwrap_or(10);
+            if !(2..=36).contains(&base) {
+                return Err(format!("Base must be within 2 to 36, got {base}"));
+            }
+            let result = parse_int(args.get_str(state, 0)?, base as u32).map(DiatomValue::Int);
+            Ok(to_result(state, result))
+        }),
+    );
+
+    funcs.insert(
+        "float".to_string(),
+        Arc::new(|state, parameters, _| {
+            let args = Args::new("float", parameters);
+            args.expect_len(1)?;
+            let s = args.get_str(state, 0)?;
+            let result = if s.is_empty() {
+                Err("Can not parse an empty string as `Float`".to_string())
+            } else {
+                s.parse::<f64>()
+                    .map_err(|_| format!("Can not parse `{s}` as `Float`"))
+            };
+            if let Ok(f) = result {
+                let spelled = s.trim_start_matches(['+', '-']);
+                if f.is_infinite()
+                    && !spelled.eq_ignore_ascii_case("inf")
+                    && !spelled.eq_ignore_ascii_case("infinity")
+                {
+                    let message = format!("`{s}` is too large for `Float` and becomes `{f}`");
+                    state.warn("float-overflow", message);
+                }
+            }
+            Ok(to_result(state, result.map(DiatomValue::Float)))
+        }),
+    );
+
+    funcs.insert(
+        "str".to_string(),
+        Arc::new(|state, parameters, _| {
+            Args::new("str", parameters).expect_len(1)?;
+            match parameters[0] {
+                DiatomValue::Str(sid) => Ok(DiatomValue::Str(sid)),
+                ref value => {
+                    let s = state.print(value);
+                    Ok(DiatomValue::Str(state.create_str(s)))
+                }
+            }
+        }),
+    );
+
+    funcs.insert(
+        "bool".to_string(),
+        Arc::new(|state, parameters, _| {
+            Args::new("bool", parameters).expect_len(1)?;
+            Ok(DiatomValue::Bool(truthy(state, &parameters[0])))
+        }),
+    );
+
+    funcs.insert(
+        "chr".to_string(),
+        Arc::new(|state, parameters, _| {
+            let args = Args::new("chr", parameters);
+            args.expect_len(1)?;
+            let i = args.get::<i64>(0)?;
+            let result = match u32::try_from(i).ok().and_then(char::from_u32) {
+                Some(c) => Ok(DiatomValue::Str(state.create_str(c.to_string()))),
+                None => Err(format!("`{i}` is not a valid unicode code point")),
+            };
+            Ok(to_result(state, result))
+        }),
+    );
+
+    funcs.insert(
+        "ord".to_string(),
+        Arc::new(|state, parameters, _| {
+            let args = Args::new("ord", parameters);
+            args.expect_len(1)?;
+            let s = args.get_str(state, 0)?;
+            let mut chars = s.chars();
+            let result = match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(DiatomValue::Int(c as i64)),
+                _ => Err(format!(
+                    "Expected a string of exactly one character, got {} characters",
+                    s.chars().count()
+                )),
+            };
+            Ok(to_result(state, result))
+        }),
+    );
+
+    Extension {
+        name: "convert".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
@@ -0,0 +1,118 @@
+//! `eval(code)` and `eval(code, env)`, which run code given as a string
+//!
+//! Code is run by a fresh interpreter with the standard library except `std.os`, under the same
+//! sandbox policy as the caller, and fuel and time limits of the policy apply to it separately.
+//! It does not see globals of the caller: names it needs are passed by `env`, a table whose
+//! fields are defined as globals before the code is run. Only plain data, i.e. what `DiatomData`
+//! holds, can be passed in and out, thus functions can not.
+//!
+//! Embedders disable `eval` by revoking capability `eval` of the policy.
+use std::cell::Cell;
+
+use diatom_core::{
+    ffi::{DiatomData, FromDiatom, IntoDiatom},
+    ColorChoice, Interpreter, RenderOptions, SandboxPolicy,
+};
+
+use super::*;
+
+/// Capability required by `eval`
+pub const EVAL_CAPABILITY: &str = "eval";
+
+/// Maximum number of `eval` running one inside another
+pub const MAX_EVAL_DEPTH: usize = 16;
+
+thread_local! {
+    static EVAL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Run `code` with globals of `env`, return value of its last expression and output
+fn eval(
+    code: &str,
+    env: DiatomData,
+    policy: SandboxPolicy,
+) -> (Result<DiatomData, String>, Vec<u8>) {
+    let mut interpreter = Interpreter::<Vec<u8>, StdLibCore>::new(vec![]);
+    interpreter.set_render_options(RenderOptions::default().color(ColorChoice::Never));
+    interpreter.set_policy(policy);
+    let std = Extension {
+        name: "std".to_string(),
+        kind: ExtensionKind::SubExtensions(std_lib()),
+    };
+    let _ = interpreter.load_ext(std);
+    if let DiatomData::Table(env) = env {
+        env.into_iter()
+            .for_each(|(name, value)| interpreter.set_global(name, value));
+    }
+
+    let result = match interpreter.eval(code, "<eval>") {
+        Ok(value) => interpreter.from_value(&value),
+        // Location of the error is in code of the caller, which is of little help
+        Err(err) => Err(interpreter
+            .last_error()
+            .map(|error| error.message.clone())
+            .unwrap_or(err)),
+    };
+    (result, interpreter.replace_buffer(vec![]))
+}
+
+pub fn eval_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "eval".to_string(),
+        Arc::new(|state, parameters, out| {
+            if !state.has_capability(EVAL_CAPABILITY) {
+                return Err("`eval` is not allowed by sandbox policy".to_string());
+            }
+            let code = match parameters {
+                [DiatomValue::Str(sid)] | [DiatomValue::Str(sid), _] => {
+                    state.get_string_by_id(*sid).unwrap().to_string()
+                }
+                [_] | [_, _] => return Err("Expected type `String` for code".to_string()),
+                _ => {
+                    return Err(format!(
+                        "Expected 1 or 2 parameter while {} is provided",
+                        parameters.len()
+                    ))
+                }
+            };
+            let env = match parameters.get(1) {
+                Some(env) => match DiatomData::from_diatom(state, env) {
+                    Ok(env @ DiatomData::Table(_)) => env,
+                    Ok(_) => return Err("Expected type `Table` for env".to_string()),
+                    Err(err) => return Err(format!("Env can not be passed to `eval`: {err}")),
+                },
+                None => DiatomData::Nil,
+            };
+
+            let depth = EVAL_DEPTH.with(Cell::get);
+            let (result, output) = if depth >= MAX_EVAL_DEPTH {
+                let err = format!("`eval` is nested more than {MAX_EVAL_DEPTH} times");
+                (Err(err), vec![])
+            } else {
+                EVAL_DEPTH.with(|d| d.set(depth + 1));
+                let evaluated = eval(&code, env, state.policy().clone());
+                EVAL_DEPTH.with(|d| d.set(depth));
+                evaluated
+            };
+            out.write_all(&output)
+                .map_err(|err| format!("IoError: {err}"))?;
+
+            let rid = match result {
+                Ok(value) => {
+                    let value = value.into_diatom(state);
+                    state.create_ok(value)
+                }
+                Err(err) => {
+                    let sid = state.create_str(err);
+                    state.create_err(DiatomValue::Str(sid))
+                }
+            };
+            Ok(DiatomValue::Ref(rid.unwrap()))
+        }),
+    );
+    Extension {
+        name: "eval".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
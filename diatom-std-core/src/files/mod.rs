@@ -4,9 +4,10 @@ macro_rules! prelude {
     };
 }
 
-pub static PRELUDE_FILES: [(&str, &str); 4] = [
+pub static PRELUDE_FILES: [(&str, &str); 5] = [
     prelude!("prelude.dm"),
     prelude!("option.dm"),
+    prelude!("result.dm"),
     prelude!("iter.dm"),
     prelude!("range.dm"),
 ];
@@ -21,6 +21,21 @@ macro_rules! load_func_1 {
     };
 }
 
+macro_rules! load_func_2 {
+    ($funcs: ident, $name: ident, $ret: expr) => {
+        $funcs.insert(
+            stringify!($name).to_string(),
+            Arc::new(|_, parameters, _| {
+                assure_para_len!(parameters, 2);
+                match (&parameters[0], &parameters[1]) {
+                    (DiatomValue::Float(f1), DiatomValue::Float(f2)) => Ok($ret(*f1, *f2)),
+                    _ => Err("Expected type `Float`".to_string()),
+                }
+            }),
+        );
+    };
+}
+
 pub fn float_extension<Buffer: IoWrite>() -> Extension<Buffer> {
     let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
     load_func_static!(funcs, MAX, DiatomValue::Float(f64::MAX));
@@ -36,6 +51,33 @@ pub fn float_extension<Buffer: IoWrite>() -> Extension<Buffer> {
     load_func_1!(funcs, round, |f: f64| DiatomValue::Float(f.round()));
     load_func_1!(funcs, is_nan, |f: f64| DiatomValue::Bool(f.is_nan()));
     load_func_1!(funcs, is_inf, |f: f64| DiatomValue::Bool(f.is_infinite()));
+    load_func_1!(funcs, is_finite, |f: f64| DiatomValue::Bool(f.is_finite()));
+    load_func_1!(funcs, trunc, |f: f64| DiatomValue::Float(f.trunc()));
+    load_func_1!(funcs, fract, |f: f64| DiatomValue::Float(f.fract()));
+    load_func_1!(funcs, sqrt, |f: f64| DiatomValue::Float(f.sqrt()));
+    load_func_1!(funcs, sign, |f: f64| DiatomValue::Float(f.signum()));
+
+    load_func_2!(funcs, min, |f1: f64, f2| DiatomValue::Float(f1.min(f2)));
+    load_func_2!(funcs, max, |f1: f64, f2| DiatomValue::Float(f1.max(f2)));
+
+    funcs.insert(
+        "clamp".to_string(),
+        Arc::new(|_, parameters, _| {
+            assure_para_len!(parameters, 3);
+            match parameters {
+                [DiatomValue::Float(f), DiatomValue::Float(min), DiatomValue::Float(max)] => {
+                    if min.is_nan() || max.is_nan() {
+                        return Err("Bounds can not be NaN".to_string());
+                    }
+                    if min > max {
+                        return Err(format!("Minimum {min} is greater than maximum {max}"));
+                    }
+                    Ok(DiatomValue::Float(f.clamp(*min, *max)))
+                }
+                _ => Err("Expected type `Float`".to_string()),
+            }
+        }),
+    );
 
     Extension {
         name: "float".to_string(),
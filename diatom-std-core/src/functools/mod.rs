@@ -0,0 +1,133 @@
+use diatom_core::ffi::{DiatomObject, DiatomObjectMut, State};
+
+use super::*;
+
+/// Hashable form of a diatom value
+///
+/// Strings are compared by content while references are compared by identity.
+#[derive(Clone, Hash, PartialEq, Eq)]
+enum Key {
+    Unit,
+    Bool(bool),
+    Int(i64),
+    Float(u64),
+    Str(String),
+    Ref(usize),
+}
+
+/// Map from a list of values to an index
+#[derive(Clone, Default)]
+struct KeyTable(AHashMap<Vec<Key>, i64>);
+
+fn to_keys<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    value: &DiatomValue,
+) -> Result<Vec<Key>, String> {
+    let list = match value {
+        DiatomValue::Ref(rid) => match state.get_obj(*rid) {
+            Some(DiatomObject::List(list)) => list,
+            _ => return Err("Expected type `List` as key".to_string()),
+        },
+        _ => return Err("Expected type `List` as key".to_string()),
+    };
+    Ok((0..list.len())
+        .map(|i| match list.get(i).unwrap() {
+            DiatomValue::Unit => Key::Unit,
+            DiatomValue::Bool(b) => Key::Bool(b),
+            DiatomValue::Int(i) => Key::Int(i),
+            DiatomValue::Float(f) => Key::Float(f.to_bits()),
+            DiatomValue::Str(sid) => Key::Str(state.get_string_by_id(sid).unwrap().to_string()),
+            DiatomValue::Ref(rid) => Key::Ref(rid),
+        })
+        .collect())
+}
+
+fn with_key_table<Buffer: IoWrite, T>(
+    state: &mut State<Buffer>,
+    value: &DiatomValue,
+    f: impl FnOnce(&mut AHashMap<Vec<Key>, i64>) -> T,
+) -> Result<T, String> {
+    if let DiatomValue::Ref(rid) = value {
+        if let Some(DiatomObjectMut::UserData(mut data)) = state.get_obj_mut(*rid) {
+            if let Some(table) = data.get().downcast_mut::<KeyTable>() {
+                return Ok(f(&mut table.0));
+            }
+        }
+    }
+    Err("Expected a `KeyTable` to operate".to_string())
+}
+
+fn functools_util_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "arity".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 1);
+            let arity = match parameters[0] {
+                DiatomValue::Ref(rid) => state.get_arity(rid),
+                _ => None,
+            };
+            let rid = match arity {
+                Some(n) => state.create_some(DiatomValue::Int(n as i64)).unwrap(),
+                None => state.create_none(),
+            };
+            Ok(DiatomValue::Ref(rid))
+        }),
+    );
+
+    funcs.insert(
+        "key_table".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 0);
+            if !state.is_type_registered::<KeyTable>() {
+                state.register_type::<KeyTable>().cloneable();
+            }
+            let table = KeyTable::default();
+            Ok(DiatomValue::Ref(state.create_user_data(Box::new(table))))
+        }),
+    );
+
+    funcs.insert(
+        "key_get".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 2);
+            let key = to_keys(state, &parameters[1])?;
+            let idx = with_key_table(state, &parameters[0], |t| {
+                t.get(&key).cloned().unwrap_or(-1)
+            })?;
+            Ok(DiatomValue::Int(idx))
+        }),
+    );
+
+    funcs.insert(
+        "key_set".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 3);
+            let key = to_keys(state, &parameters[1])?;
+            let idx = match parameters[2] {
+                DiatomValue::Int(i) => i,
+                _ => return Err("Expected type `Int` as index".to_string()),
+            };
+            with_key_table(state, &parameters[0], |t| t.insert(key, idx))?;
+            Ok(DiatomValue::Unit)
+        }),
+    );
+
+    Extension {
+        name: "util".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
+
+pub fn functools_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    Extension {
+        name: "functools".to_string(),
+        kind: ExtensionKind::SubExtensions(vec![
+            functools_util_extension(),
+            Extension {
+                name: "mod".to_string(),
+                kind: ExtensionKind::File(include_str!("functools.dm").to_string()),
+            },
+        ]),
+    }
+}
@@ -1,5 +1,37 @@
+use diatom_core::ffi::State;
+
 use super::*;
 
+macro_rules! load_func_1 {
+    ($funcs: ident, $name: ident, $ret: expr) => {
+        $funcs.insert(
+            stringify!($name).to_string(),
+            Arc::new(|state, parameters, _| {
+                assure_para_len!(parameters, 1);
+                match parameters[0] {
+                    DiatomValue::Int(i) => Ok($ret(state, i)),
+                    _ => Err("Expected type `Int`".to_string()),
+                }
+            }),
+        );
+    };
+}
+
+macro_rules! load_func_2 {
+    ($funcs: ident, $name: ident, $ret: expr) => {
+        $funcs.insert(
+            stringify!($name).to_string(),
+            Arc::new(|_, parameters, _| {
+                assure_para_len!(parameters, 2);
+                match (&parameters[0], &parameters[1]) {
+                    (DiatomValue::Int(i1), DiatomValue::Int(i2)) => $ret(*i1, *i2),
+                    _ => Err("Expected type `Int`".to_string()),
+                }
+            }),
+        );
+    };
+}
+
 pub fn int_extension<Buffer: IoWrite>() -> Extension<Buffer> {
     let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
     funcs.insert(
@@ -12,33 +44,44 @@ pub fn int_extension<Buffer: IoWrite>() -> Extension<Buffer> {
         Arc::new(|_, _, _| Ok(DiatomValue::Int(i64::MIN))),
     );
 
-    funcs.insert(
-        "abs".to_string(),
-        Arc::new(|_, parameters, _| {
-            assure_para_len!(parameters, 1);
-            match parameters[0] {
-                DiatomValue::Int(i) => Ok(DiatomValue::Int(i64::wrapping_abs(i))),
-                _ => Err("Expected type `Int`".to_string()),
-            }
-        }),
-    );
-    funcs.insert(
-        "abs".to_string(),
-        Arc::new(|_, parameters, _| {
-            assure_para_len!(parameters, 1);
-            match parameters[0] {
-                DiatomValue::Int(i) => Ok(DiatomValue::Int(i64::wrapping_abs(i))),
-                _ => Err("Expected type `Int`".to_string()),
-            }
-        }),
-    );
+    load_func_1!(funcs, abs, |_, i: i64| DiatomValue::Int(i.wrapping_abs()));
+    load_func_1!(funcs, float, |_, i: i64| DiatomValue::Float(i as f64));
+    load_func_1!(funcs, sign, |_, i: i64| DiatomValue::Int(i.signum()));
+    load_func_1!(funcs, is_even, |_, i: i64| DiatomValue::Bool(i % 2 == 0));
+    load_func_1!(funcs, is_odd, |_, i: i64| DiatomValue::Bool(i % 2 != 0));
+    // Negative numbers are written with a sign, e.g. `(-255).to_hex()` is `-ff`
+    load_func_1!(funcs, to_hex, |state: &mut State<Buffer>, i: i64| {
+        let sign = if i < 0 { "-" } else { "" };
+        DiatomValue::Str(state.create_str(format!("{sign}{:x}", i.unsigned_abs())))
+    });
+    load_func_1!(funcs, to_oct, |state: &mut State<Buffer>, i: i64| {
+        let sign = if i < 0 { "-" } else { "" };
+        DiatomValue::Str(state.create_str(format!("{sign}{:o}", i.unsigned_abs())))
+    });
+    load_func_1!(funcs, to_bin, |state: &mut State<Buffer>, i: i64| {
+        let sign = if i < 0 { "-" } else { "" };
+        DiatomValue::Str(state.create_str(format!("{sign}{:b}", i.unsigned_abs())))
+    });
+
+    load_func_2!(funcs, min, |i1: i64, i2| Ok(DiatomValue::Int(i1.min(i2))));
+    load_func_2!(funcs, max, |i1: i64, i2| Ok(DiatomValue::Int(i1.max(i2))));
+    // Wraps on overflow like other arithmetic of integers, unlike `**` which gives a float
+    load_func_2!(funcs, pow, |i: i64, exp: i64| match u32::try_from(exp) {
+        Ok(exp) => Ok(DiatomValue::Int(i.wrapping_pow(exp))),
+        Err(_) => Err(format!("Exponent {exp} is negative or too large")),
+    });
 
     funcs.insert(
-        "float".to_string(),
+        "clamp".to_string(),
         Arc::new(|_, parameters, _| {
-            assure_para_len!(parameters, 1);
-            match parameters[0] {
-                DiatomValue::Int(i) => Ok(DiatomValue::Float(i as f64)),
+            assure_para_len!(parameters, 3);
+            match parameters {
+                [DiatomValue::Int(i), DiatomValue::Int(min), DiatomValue::Int(max)] => {
+                    if min > max {
+                        return Err(format!("Minimum {min} is greater than maximum {max}"));
+                    }
+                    Ok(DiatomValue::Int(*i.clamp(min, max)))
+                }
                 _ => Err("Expected type `Int`".to_string()),
             }
         }),
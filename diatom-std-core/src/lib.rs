@@ -1,25 +1,47 @@
+mod bench;
 mod built_in;
+mod channel;
+mod convert;
+mod eval;
 mod files;
 mod float;
+mod functools;
 mod int;
 mod list;
+mod log;
 mod math;
+mod range;
+mod shared;
 mod string;
+mod test;
 
 use std::sync::Arc;
 
 use ahash::AHashMap;
 use diatom_core::{
     extension::{Extension, ExtensionKind},
-    ffi::{DiatomValue, ForeignFunction},
+    ffi::{DiatomValue, ForeignFunction, DEFAULT_INSPECT_DEPTH},
     IoWrite, StdCore,
 };
 
-static PRELUDE_NAMES: [&str; 17] = [
+static PRELUDE_NAMES: [&str; 41] = [
     "print",
     "println",
+    "write",
+    "write_line",
+    "flush",
+    "inspect",
+    "pp",
+    "input",
+    "channel",
+    "share",
+    "string_builder",
     "todo",
     "assert",
+    "implements",
+    "fn_info",
+    "freeze",
+    "is_frozen",
     "unreachable",
     "panic",
     "List",
@@ -29,12 +51,30 @@ static PRELUDE_NAMES: [&str; 17] = [
     "Table",
     "Iter",
     "Range",
+    "len",
     "Option",
     "Some",
     "None",
+    "Result",
+    "Ok",
+    "Err",
+    "int",
+    "float",
+    "bool",
+    "str",
+    "chr",
+    "ord",
     "Gc",
+    "eval",
 ];
 
+pub use bench::{format_duration, BenchResults, BenchStats};
+pub use channel::Channel;
+pub use eval::{EVAL_CAPABILITY, MAX_EVAL_DEPTH};
+pub use log::{LogLevel, LogRecord, LogSink, LogSinkContext};
+pub use shared::SharedValue;
+pub use test::TestCases;
+
 pub struct StdLibCore;
 
 impl StdCore for StdLibCore {
@@ -51,16 +91,28 @@ impl StdCore for StdLibCore {
             name: "prelude".to_string(),
             kind: ExtensionKind::SubExtensions(vec![
                 built_in::built_in_extension(),
+                channel::channel_extension(),
+                shared::shared_extension(),
+                string::string_extension(),
+                convert::convert_extension(),
+                eval::eval_extension(),
                 int::int_extension(),
                 float::float_extension(),
                 list::list_extension(),
+                range::range_extension(),
             ]),
         }
     }
 }
 
 pub fn std_lib<Buffer: IoWrite>() -> Vec<Extension<Buffer>> {
-    vec![math::math_extension()]
+    vec![
+        math::math_extension(),
+        functools::functools_extension(),
+        bench::bench_extension(),
+        test::test_extension(),
+        log::log_extension(),
+    ]
 }
 
 macro_rules! assure_para_len {
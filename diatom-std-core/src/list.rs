@@ -27,6 +27,9 @@ macro_rules! load_func_mut {
             stringify!($name).to_string(),
             Arc::new(|state, parameters, _| {
                 assure_para_len!(parameters, 1);
+                if let DiatomValue::Ref(id) = parameters[0] {
+                    state.check_mutable(id)?;
+                }
                 match parameters[0] {
                     DiatomValue::Ref(id) => match state.get_obj_mut(id) {
                         Some(DiatomObjectMut::List(l)) => Ok($ret(l, id)),
@@ -59,6 +62,9 @@ pub fn list_extension<Buffer: IoWrite>() -> Extension<Buffer> {
         "append".to_string(),
         Arc::new(|state, parameters, _| {
             assure_para_len!(parameters, 2);
+            if let DiatomValue::Ref(id) = parameters[0] {
+                state.check_mutable(id)?;
+            }
             match parameters[0] {
                 DiatomValue::Ref(id) => match state.get_obj_mut(id) {
                     Some(DiatomObjectMut::List(mut l)) => {
@@ -77,6 +83,9 @@ pub fn list_extension<Buffer: IoWrite>() -> Extension<Buffer> {
         "insert".to_string(),
         Arc::new(|state, parameters, _| {
             assure_para_len!(parameters, 3);
+            if let DiatomValue::Ref(id) = parameters[0] {
+                state.check_mutable(id)?;
+            }
             match (&parameters[0], &parameters[1]) {
                 (DiatomValue::Ref(id), DiatomValue::Int(idx)) => match state.get_obj_mut(*id) {
                     Some(DiatomObjectMut::List(mut l)) => {
@@ -108,6 +117,9 @@ pub fn list_extension<Buffer: IoWrite>() -> Extension<Buffer> {
         "remove".to_string(),
         Arc::new(|state, parameters, _| {
             assure_para_len!(parameters, 2);
+            if let DiatomValue::Ref(id) = parameters[0] {
+                state.check_mutable(id)?;
+            }
             match (&parameters[0], &parameters[1]) {
                 (DiatomValue::Ref(id), DiatomValue::Int(idx)) => match state.get_obj_mut(*id) {
                     Some(DiatomObjectMut::List(mut l)) => {
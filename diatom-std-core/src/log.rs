@@ -0,0 +1,125 @@
+//! `std.log`, which logs records of a level, a message and fields
+//!
+//! ```text
+//! import std.log
+//! log::info('user logged in', {id = 42})
+//! ```
+//!
+//! Records are passed to the sink set by host, so that scripts are logged by the pipeline of the
+//! application. Without a sink they are printed to output as `[info] message {fields}`.
+use std::{collections::BTreeMap, fmt};
+
+use diatom_core::ffi::{DiatomData, FromDiatom, State};
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Name of the function that logs records of this level
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    pub message: String,
+    /// Fields of the table passed with the message, empty if there is none
+    pub fields: BTreeMap<String, DiatomData>,
+}
+
+/// Receiver of records logged by `std.log`
+pub trait LogSink: Send {
+    fn log(&mut self, record: LogRecord);
+}
+
+impl<F: FnMut(LogRecord) + Send> LogSink for F {
+    fn log(&mut self, record: LogRecord) {
+        self(record)
+    }
+}
+
+/// Sink of `std.log`, set as host state of an interpreter
+pub struct LogSinkContext(pub Box<dyn LogSink>);
+
+fn log<Buffer: IoWrite>(
+    level: LogLevel,
+    state: &mut State<Buffer>,
+    parameters: &[DiatomValue],
+    out: &mut Buffer,
+) -> Result<DiatomValue, String> {
+    let message = match parameters {
+        [DiatomValue::Str(sid)] | [DiatomValue::Str(sid), _] => {
+            state.get_string_by_id(*sid).unwrap().to_string()
+        }
+        [_] | [_, _] => return Err("Expected type `String` for message".to_string()),
+        _ => {
+            return Err(format!(
+                "Expected 1 or 2 parameter while {} is provided",
+                parameters.len()
+            ))
+        }
+    };
+    let fields = match parameters.get(1) {
+        Some(fields) => match DiatomData::from_diatom(state, fields) {
+            Ok(DiatomData::Table(fields)) => fields,
+            Ok(_) => return Err("Expected type `Table` for fields".to_string()),
+            Err(err) => return Err(format!("Fields can not be logged: {err}")),
+        },
+        None => BTreeMap::new(),
+    };
+
+    match state.context_mut::<LogSinkContext>() {
+        Some(sink) => sink.0.log(LogRecord {
+            level,
+            message,
+            fields,
+        }),
+        None => {
+            let line = match parameters.get(1) {
+                Some(fields) => format!("[{level}] {message} {}", state.print(fields)),
+                None => format!("[{level}] {message}"),
+            };
+            writeln!(out, "{line}").map_err(|err| format!("IoError: {err}"))?;
+        }
+    }
+    Ok(DiatomValue::Unit)
+}
+
+pub fn log_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    for level in [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+    ] {
+        funcs.insert(
+            level.as_str().to_string(),
+            Arc::new(move |state, parameters, out| log(level, state, parameters, out)),
+        );
+    }
+    Extension {
+        name: "log".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
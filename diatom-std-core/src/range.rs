@@ -0,0 +1,108 @@
+use diatom_core::ffi::Args;
+
+use super::*;
+
+/// Integers from `start` towards `limit` (exclusive), `stride` apart
+struct Range {
+    start: i64,
+    limit: i64,
+    stride: i64,
+}
+
+impl Range {
+    fn from_args(args: &Args) -> Result<Self, String> {
+        let stride = args.get::<i64>(2)?;
+        if stride == 0 {
+            return Err("Step of a range must not be 0".to_string());
+        }
+        Ok(Self {
+            start: args.get(0)?,
+            limit: args.get(1)?,
+            stride,
+        })
+    }
+
+    /// Computed in i128 so that ranges spanning the whole `Int` do not overflow
+    fn len(&self) -> i64 {
+        let span = (self.limit as i128 - self.start as i128) * self.stride.signum() as i128;
+        let stride = (self.stride as i128).abs();
+        if span <= 0 {
+            0
+        } else {
+            ((span + stride - 1) / stride).min(i64::MAX as i128) as i64
+        }
+    }
+
+    fn contains(&self, x: i64) -> bool {
+        let in_bounds = if self.stride > 0 {
+            self.start <= x && x < self.limit
+        } else {
+            self.limit < x && x <= self.start
+        };
+        in_bounds && (x as i128 - self.start as i128) % self.stride as i128 == 0
+    }
+
+    /// Same elements in reverse order, as `(start, limit, stride)`
+    fn rev(&self) -> (i64, i64, i64) {
+        let stride = self.stride.saturating_neg();
+        let len = self.len();
+        if len == 0 {
+            return (self.start, self.start, stride);
+        }
+        let last = self.start as i128 + (len as i128 - 1) * self.stride as i128;
+        let limit =
+            (self.start as i128 - self.stride as i128).clamp(i64::MIN as i128, i64::MAX as i128);
+        (last as i64, limit as i64, stride)
+    }
+}
+
+pub fn range_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "len".to_string(),
+        Arc::new(|_, parameters, _| {
+            let args = Args::new("Range.len", parameters);
+            args.expect_len(3)?;
+            Ok(DiatomValue::Int(Range::from_args(&args)?.len()))
+        }),
+    );
+
+    funcs.insert(
+        "contains".to_string(),
+        Arc::new(|_, parameters, _| {
+            let args = Args::new("Range.contains", parameters);
+            args.expect_len(4)?;
+            let range = Range::from_args(&args)?;
+            let contains = match parameters[3] {
+                DiatomValue::Int(x) => range.contains(x),
+                DiatomValue::Float(x) if x.fract() == 0.0 && x.abs() < i64::MAX as f64 => {
+                    range.contains(x as i64)
+                }
+                _ => false,
+            };
+            Ok(DiatomValue::Bool(contains))
+        }),
+    );
+
+    funcs.insert(
+        "rev".to_string(),
+        Arc::new(|state, parameters, _| {
+            let args = Args::new("Range.rev", parameters);
+            args.expect_len(3)?;
+            let (start, limit, stride) = Range::from_args(&args)?.rev();
+            let tuple = state
+                .create_tuple(vec![
+                    DiatomValue::Int(start),
+                    DiatomValue::Int(limit),
+                    DiatomValue::Int(stride),
+                ])
+                .unwrap();
+            Ok(DiatomValue::Ref(tuple))
+        }),
+    );
+
+    Extension {
+        name: "range_util".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
@@ -0,0 +1,170 @@
+//! `share(value)`, which passes frozen data to other interpreters without copying
+//!
+//! ```text
+//! config = freeze({name = 'app', ports = [80, 443]})
+//! ch.send(share(config))
+//! ```
+//!
+//! Values sent by a channel are deep copied by default. Frozen data is copied once by `share`
+//! into a segment of immutable values counted by `Arc`, so that handles of it are sent and
+//! cloned by reference. Receivers read it by `get`, which copies nothing but the value read, and
+//! make a local mutable copy by `copy` only when they need to modify it.
+use std::collections::BTreeMap;
+
+use diatom_core::ffi::{Args, DiatomData, DiatomObject, FromDiatom, IntoDiatom, State};
+
+use super::*;
+
+/// Immutable data that can be shared by interpreters on different threads
+///
+/// Lists and tables are handles of reference counted values, thus cloning and sending them is
+/// cheap. In scripts, a list or table of it is user data with methods `get(key_or_index)`,
+/// `len()` and `copy()`, while other values are converted into plain values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum SharedValue {
+    #[default]
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(Arc<str>),
+    List(Arc<[SharedValue]>),
+    Table(Arc<BTreeMap<String, SharedValue>>),
+}
+
+impl From<DiatomData> for SharedValue {
+    fn from(data: DiatomData) -> Self {
+        match data {
+            DiatomData::Nil => SharedValue::Nil,
+            DiatomData::Bool(b) => SharedValue::Bool(b),
+            DiatomData::Int(i) => SharedValue::Int(i),
+            DiatomData::Float(f) => SharedValue::Float(f),
+            DiatomData::Str(s) => SharedValue::Str(s.into()),
+            DiatomData::List(l) => SharedValue::List(l.into_iter().map(Self::from).collect()),
+            DiatomData::Table(t) => SharedValue::Table(Arc::new(
+                t.into_iter().map(|(k, v)| (k, v.into())).collect(),
+            )),
+        }
+    }
+}
+
+impl SharedValue {
+    /// Deep copy as plain data
+    pub fn to_data(&self) -> DiatomData {
+        match self {
+            SharedValue::Nil => DiatomData::Nil,
+            SharedValue::Bool(b) => DiatomData::Bool(*b),
+            SharedValue::Int(i) => DiatomData::Int(*i),
+            SharedValue::Float(f) => DiatomData::Float(*f),
+            SharedValue::Str(s) => DiatomData::Str(s.to_string()),
+            SharedValue::List(l) => DiatomData::List(l.iter().map(Self::to_data).collect()),
+            SharedValue::Table(t) => {
+                DiatomData::Table(t.iter().map(|(k, v)| (k.clone(), v.to_data())).collect())
+            }
+        }
+    }
+
+    /// Item at `index` of a list, counted from the end if negative, or field `key` of a table
+    fn get(&self, key: &DiatomData) -> Result<&SharedValue, String> {
+        match (self, key) {
+            (SharedValue::List(l), DiatomData::Int(idx)) => {
+                let index = if *idx >= 0 {
+                    usize::try_from(*idx).ok()
+                } else {
+                    l.len().checked_sub(idx.unsigned_abs() as usize)
+                };
+                index
+                    .and_then(|index| l.get(index))
+                    .ok_or_else(|| format!("Index shared$[{idx}] while having {} items", l.len()))
+            }
+            (SharedValue::Table(t), DiatomData::Str(key)) => t
+                .get(key)
+                .ok_or_else(|| format!("Key `{key}` is not found in shared table")),
+            (SharedValue::List(_), _) => Err("Expected type `Int` as index".to_string()),
+            _ => Err("Expected type `String` as key".to_string()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            SharedValue::List(l) => l.len(),
+            SharedValue::Table(t) => t.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// Register methods of `SharedValue` unless they are registered
+fn register<Buffer: IoWrite>(state: &mut State<Buffer>) {
+    if state.is_type_registered::<SharedValue>() {
+        return;
+    }
+    state
+        .register_type::<SharedValue>()
+        .cloneable()
+        .method("get", |shared, state, parameters| {
+            let args = Args::new("SharedValue.get", parameters);
+            args.expect_len(1)?;
+            let key = args.get_as::<DiatomData, _>(state, 0)?;
+            Ok(shared.get(&key)?.clone().into_diatom(state))
+        })
+        .method("len", |shared, _, parameters| {
+            Args::new("SharedValue.len", parameters).expect_len(0)?;
+            Ok(DiatomValue::Int(shared.len() as i64))
+        })
+        .method("copy", |shared, state, parameters| {
+            Args::new("SharedValue.copy", parameters).expect_len(0)?;
+            Ok(shared.to_data().into_diatom(state))
+        });
+}
+
+/// Lists and tables become user data, other values become plain values
+impl IntoDiatom for SharedValue {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        match self {
+            SharedValue::List(_) | SharedValue::Table(_) => {
+                register(state);
+                DiatomValue::Ref(state.create_user_data(Box::new(self)))
+            }
+            _ => self.to_data().into_diatom(state),
+        }
+    }
+}
+
+impl FromDiatom for SharedValue {
+    fn from_diatom<Buffer: IoWrite>(
+        state: &State<Buffer>,
+        value: &DiatomValue,
+    ) -> Result<Self, String> {
+        match value {
+            DiatomValue::Ref(rid) => match state.get_obj(*rid) {
+                Some(DiatomObject::UserData(data)) => data.downcast_ref::<SharedValue>().cloned(),
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| "Expected a `SharedValue`".to_string())
+    }
+}
+
+pub fn shared_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "share".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 1);
+            if !state.is_frozen(&parameters[0]) {
+                return Err(
+                    "Only frozen values can be shared, consider `share(freeze(value))`".to_string(),
+                );
+            }
+            let data = DiatomData::from_diatom(state, &parameters[0])
+                .map_err(|err| format!("Value can not be shared: {err}"))?;
+            Ok(SharedValue::from(data).into_diatom(state))
+        }),
+    );
+    Extension {
+        name: "shared".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
@@ -1 +1,68 @@
+use diatom_core::ffi::{Args, IntoDiatom, State};
 
+use super::*;
+
+/// Mutable buffer for building a string piece by piece
+///
+/// `s = s + piece` copies `s` for every piece, thus building a long string in a loop is
+/// quadratic and leaves every intermediate string to the garbage collector. A builder appends to
+/// the same buffer and only creates a string when asked for it.
+///
+/// In scripts, a builder is created by `string_builder()` and has methods `push(value)`,
+/// `len()`, `clear()` and `to_string()`. `push` and `clear` return the builder so that calls can
+/// be chained, values other than strings are pushed as they are printed.
+#[derive(Clone, Default)]
+struct StringBuilder {
+    buffer: String,
+}
+
+/// User data whose methods receive the builder as the first parameter
+impl IntoDiatom for StringBuilder {
+    fn into_diatom<Buffer: IoWrite>(self, state: &mut State<Buffer>) -> DiatomValue {
+        if !state.is_type_registered::<StringBuilder>() {
+            state
+                .register_type::<StringBuilder>()
+                .cloneable()
+                .chained_method("push", |builder, state, parameters| {
+                    let args = Args::new("StringBuilder.push", parameters);
+                    args.expect_len(1)?;
+                    match &parameters[0] {
+                        DiatomValue::Str(_) => builder.buffer.push_str(args.get_str(state, 0)?),
+                        value => builder.buffer.push_str(&state.print(value)),
+                    }
+                    Ok(())
+                })
+                .method("len", |builder, _, parameters| {
+                    Args::new("StringBuilder.len", parameters).expect_len(0)?;
+                    let len = builder.buffer.chars().count();
+                    Ok(DiatomValue::Int(len as i64))
+                })
+                .chained_method("clear", |builder, _, parameters| {
+                    Args::new("StringBuilder.clear", parameters).expect_len(0)?;
+                    builder.buffer.clear();
+                    Ok(())
+                })
+                .method("to_string", |builder, state, parameters| {
+                    Args::new("StringBuilder.to_string", parameters).expect_len(0)?;
+                    let s = builder.buffer.clone();
+                    Ok(DiatomValue::Str(state.create_str(s)))
+                });
+        }
+        DiatomValue::Ref(state.create_user_data(Box::new(self)))
+    }
+}
+
+pub fn string_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "string_builder".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 0);
+            Ok(StringBuilder::default().into_diatom(state))
+        }),
+    );
+    Extension {
+        name: "string".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
@@ -0,0 +1,160 @@
+use ahash::AHashSet;
+use diatom_core::ffi::{DiatomObject, FunctionHandle, State};
+
+use super::*;
+
+/// Test cases registered by `case` of `std.test`
+///
+/// Set it as context of an interpreter before executing a test file, then take it back and call
+/// each case with `Interpreter::call_function`. Registering a case fails if it is not set.
+#[derive(Default)]
+pub struct TestCases(pub Vec<(String, FunctionHandle)>);
+
+/// Structural equality, references already being compared are assumed to be equal
+fn equal<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    a: &DiatomValue,
+    b: &DiatomValue,
+    comparing: &mut AHashSet<(usize, usize)>,
+) -> bool {
+    let (ra, rb) = match (a, b) {
+        (DiatomValue::Unit, DiatomValue::Unit) => return true,
+        (DiatomValue::Bool(a), DiatomValue::Bool(b)) => return a == b,
+        (DiatomValue::Int(a), DiatomValue::Int(b)) => return a == b,
+        (DiatomValue::Float(a), DiatomValue::Float(b)) => return a == b,
+        (DiatomValue::Str(a), DiatomValue::Str(b)) => {
+            return state.get_string_by_id(*a) == state.get_string_by_id(*b)
+        }
+        (DiatomValue::Ref(a), DiatomValue::Ref(b)) => (*a, *b),
+        _ => return false,
+    };
+    if ra == rb || !comparing.insert((ra, rb)) {
+        return true;
+    }
+    let (mut xs, mut ys) = (vec![], vec![]);
+    match (state.get_obj(ra), state.get_obj(rb)) {
+        (Some(DiatomObject::List(a)), Some(DiatomObject::List(b))) => {
+            xs.extend((0..a.len()).filter_map(|i| a.get(i)));
+            ys.extend((0..b.len()).filter_map(|i| b.get(i)));
+        }
+        (Some(DiatomObject::Tuple(a)), Some(DiatomObject::Tuple(b))) => {
+            xs.extend((0..a.len()).filter_map(|i| a.get(i)));
+            ys.extend((0..b.len()).filter_map(|i| b.get(i)));
+        }
+        (Some(DiatomObject::Table(a)), Some(DiatomObject::Table(b))) => {
+            if a.fields() != b.fields() {
+                return false;
+            }
+            for field in a.fields() {
+                xs.extend(a.get_field(field));
+                ys.extend(b.get_field(field));
+            }
+        }
+        _ => return false,
+    }
+    xs.len() == ys.len()
+        && xs
+            .iter()
+            .zip(&ys)
+            .all(|(x, y)| equal(state, x, y, comparing))
+}
+
+/// Line diff from `expected` to `actual`, lines are prefixed by `-`, `+` or a space
+fn diff(expected: &str, actual: &str) -> String {
+    let xs: Vec<_> = expected.lines().collect();
+    let ys: Vec<_> = actual.lines().collect();
+    // Length of longest common subsequence of xs[i..] and ys[j..]
+    let mut lcs = vec![vec![0usize; ys.len() + 1]; xs.len() + 1];
+    for i in (0..xs.len()).rev() {
+        for j in (0..ys.len()).rev() {
+            lcs[i][j] = if xs[i] == ys[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < xs.len() || j < ys.len() {
+        if i < xs.len() && j < ys.len() && xs[i] == ys[j] {
+            out += &format!(" {}\n", xs[i]);
+            i += 1;
+            j += 1;
+        } else if j == ys.len() || (i < xs.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out += &format!("-{}\n", xs[i]);
+            i += 1;
+        } else {
+            out += &format!("+{}\n", ys[j]);
+            j += 1;
+        }
+    }
+    out
+}
+
+fn test_util_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "register".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 2);
+            let name = match parameters[0] {
+                DiatomValue::Str(sid) => state.get_string_by_id(sid).unwrap().to_string(),
+                _ => return Err("Expected type `String` as name of test case".to_string()),
+            };
+            let f = state
+                .function_handle(&parameters[1])
+                .ok_or_else(|| "Expected a function as test case".to_string())?;
+            let cases = state.context_mut::<TestCases>().ok_or_else(|| {
+                "Test cases must be run by a test runner, e.g. `diatom test`".to_string()
+            })?;
+            cases.0.push((name, f));
+            Ok(DiatomValue::Unit)
+        }),
+    );
+
+    funcs.insert(
+        "equal".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 2);
+            let equal = equal(state, &parameters[0], &parameters[1], &mut AHashSet::new());
+            Ok(DiatomValue::Bool(equal))
+        }),
+    );
+
+    funcs.insert(
+        "diff".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 2);
+            let actual = state.inspect(&parameters[0], DEFAULT_INSPECT_DEPTH);
+            let expected = state.inspect(&parameters[1], DEFAULT_INSPECT_DEPTH);
+            let text = if actual.contains('\n') || expected.contains('\n') {
+                format!(
+                    "Values are not equal\n--- expected\n+++ actual\n{}",
+                    diff(&expected, &actual)
+                )
+            } else {
+                format!("Values are not equal\n  expected: {expected}\n    actual: {actual}")
+            };
+            Ok(DiatomValue::Str(state.create_str(text)))
+        }),
+    );
+
+    Extension {
+        name: "util".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
+
+pub fn test_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    Extension {
+        name: "test".to_string(),
+        kind: ExtensionKind::SubExtensions(vec![
+            test_util_extension(),
+            Extension {
+                name: "mod".to_string(),
+                kind: ExtensionKind::File(include_str!("test.dm").to_string()),
+            },
+        ]),
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use diatom_core::{
+    extension::{Extension, ExtensionKind},
+    ffi::{ForeignFunction, IntoDiatom},
+    IoWrite,
+};
+
+/// Capability that must be granted by host before scripts can read their arguments
+pub const ENV_CAPABILITY: &str = "env";
+
+/// Arguments passed to a script, set by host with `Interpreter::set_context`
+///
+/// Scripts read them with `args()` from `std.os.env`, aliased as `os.args()` after `import std.os`,
+/// which returns an empty list if they are not set. Host must grant `ENV_CAPABILITY` first.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptArgs(pub Vec<String>);
+
+pub fn env_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "args".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_para_len!(parameters, 0);
+            assure_capability!(state, ENV_CAPABILITY);
+            let args = state
+                .context::<ScriptArgs>()
+                .map(|args| args.0.clone())
+                .unwrap_or_default();
+            Ok(args.into_diatom(state))
+        }),
+    );
+    Extension {
+        name: "env".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
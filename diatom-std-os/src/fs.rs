@@ -0,0 +1,114 @@
+use std::{fs, io::Write, path::Path, sync::Arc};
+
+use ahash::AHashMap;
+use diatom_core::{
+    extension::{Extension, ExtensionKind},
+    ffi::{DiatomValue, ForeignFunction, IntoDiatom, State},
+    IoWrite,
+};
+
+/// Capability that must be granted by host before any file is read, see `FsPolicy`
+pub const FS_CAPABILITY: &str = "fs";
+
+fn get_str<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    value: &DiatomValue,
+    name: &str,
+) -> Result<String, String> {
+    match value {
+        DiatomValue::Str(sid) => Ok(state.get_string_by_id(*sid).unwrap().to_string()),
+        _ => Err(format!("Expected type `String` for {name}")),
+    }
+}
+
+/// Files can be written only if capability `fs` is granted with `FsPolicy::ReadWrite`
+fn assure_write<Buffer: IoWrite>(state: &State<Buffer>) -> Result<(), String> {
+    assure_capability!(state, FS_CAPABILITY);
+    if !state.policy().fs.can_write() {
+        return Err("Writing files is not allowed by host".to_string());
+    }
+    Ok(())
+}
+
+/// Write `text` to file `path`, appending to it instead of truncating if `append` is set
+fn write_file<Buffer: IoWrite>(
+    state: &mut State<Buffer>,
+    parameters: &[DiatomValue],
+    append: bool,
+) -> Result<DiatomValue, String> {
+    assure_write(state)?;
+    assure_para_len!(parameters, 2);
+    let path = get_str(state, &parameters[0], "path")?;
+    let text = get_str(state, &parameters[1], "text")?;
+    fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(&path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|err| format!("IoError: {err}"))?;
+    Ok(DiatomValue::Unit)
+}
+
+pub fn fs_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "read".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, FS_CAPABILITY);
+            assure_para_len!(parameters, 1);
+            let path = get_str(state, &parameters[0], "path")?;
+            let text = fs::read_to_string(path).map_err(|err| format!("IoError: {err}"))?;
+            Ok(text.into_diatom(state))
+        }),
+    );
+    funcs.insert(
+        "write".to_string(),
+        Arc::new(|state, parameters, _| write_file(state, parameters, false)),
+    );
+    funcs.insert(
+        "append".to_string(),
+        Arc::new(|state, parameters, _| write_file(state, parameters, true)),
+    );
+    funcs.insert(
+        "exists".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, FS_CAPABILITY);
+            assure_para_len!(parameters, 1);
+            let path = get_str(state, &parameters[0], "path")?;
+            Ok(DiatomValue::Bool(Path::new(&path).exists()))
+        }),
+    );
+    funcs.insert(
+        "list_dir".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, FS_CAPABILITY);
+            assure_para_len!(parameters, 1);
+            let path = get_str(state, &parameters[0], "path")?;
+            let mut names = fs::read_dir(path)
+                .and_then(|entries| {
+                    entries
+                        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .map_err(|err| format!("IoError: {err}"))?;
+            names.sort();
+            Ok(names.into_diatom(state))
+        }),
+    );
+    funcs.insert(
+        "remove".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_write(state)?;
+            assure_para_len!(parameters, 1);
+            let path = get_str(state, &parameters[0], "path")?;
+            fs::remove_file(path).map_err(|err| format!("IoError: {err}"))?;
+            Ok(DiatomValue::Unit)
+        }),
+    );
+    Extension {
+        name: "fs".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
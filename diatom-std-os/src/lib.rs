@@ -1,14 +1,13 @@
-use std::sync::Arc;
-
-use ahash::AHashMap;
+//! Standard library modules that access the operating system, loaded as `std.os`
+//!
+//! Each module is behind a feature of the same name, all enabled by default.
 use diatom_core::{
     extension::{Extension, ExtensionKind},
-    ffi::{DiatomObject, DiatomValue, ForeignFunction, State},
     IoWrite,
 };
-use humantime::format_duration;
-use time::{Duration, OffsetDateTime};
 
+// Macros are not used by some combinations of features
+#[allow(unused_macros)]
 macro_rules! assure_para_len {
     ($parameters: ident, $len: literal) => {
         if $parameters.len() != $len {
@@ -21,109 +20,65 @@ macro_rules! assure_para_len {
     };
 }
 
-fn cast_to_offset_date<'a, Buffer: IoWrite>(
-    state: &'a State<Buffer>,
-    value: &DiatomValue,
-) -> Option<&'a OffsetDateTime> {
-    if let DiatomValue::Ref(rid) = value {
-        if let Some(DiatomObject::UserData(b)) = state.get_obj(*rid) {
-            let date_time: Option<&OffsetDateTime> = b.downcast_ref();
-            return date_time;
-        }
-    }
-    None
-}
-
-fn cast_to_duration<'a, Buffer: IoWrite>(
-    state: &'a State<Buffer>,
-    value: &DiatomValue,
-) -> Option<&'a Duration> {
-    if let DiatomValue::Ref(rid) = value {
-        if let Some(DiatomObject::UserData(b)) = state.get_obj(*rid) {
-            let duration: Option<&Duration> = b.downcast_ref();
-            return duration;
+#[allow(unused_macros)]
+macro_rules! assure_capability {
+    ($state: ident, $capability: ident) => {
+        if !$state.has_capability($capability) {
+            return Err(format!(
+                "Capability `{}` is not granted by host",
+                $capability
+            ));
         }
-    }
-    None
+    };
 }
 
-fn time_util_extension<Buffer: IoWrite>() -> Extension<Buffer> {
-    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
-    funcs.insert(
-        "now".to_string(),
-        Arc::new(|state, parameters, _| {
-            assure_para_len!(parameters, 0);
-            let date_time = OffsetDateTime::now_utc();
-            let rid = state.create_user_data(Box::new(date_time));
-            Ok(DiatomValue::Ref(rid))
-        }),
-    );
-
-    funcs.insert(
-        "show_date_time".to_string(),
-        Arc::new(|state, parameters, _| {
-            assure_para_len!(parameters, 1);
-            if let Some(date_time) = cast_to_offset_date(state, &parameters[0]) {
-                let s = format!("{date_time}");
-                let sid = state.create_str(s);
-                return Ok(DiatomValue::Str(sid));
-            }
-            Err("Expected a `DateTime` to operate".to_string())
-        }),
-    );
+#[cfg(feature = "env")]
+mod env;
+#[cfg(feature = "fs")]
+mod fs;
+#[cfg(feature = "net")]
+mod net;
+#[cfg(feature = "process")]
+mod process;
+#[cfg(feature = "time")]
+mod time;
+#[cfg(feature = "env")]
+pub use env::{ScriptArgs, ENV_CAPABILITY};
+#[cfg(feature = "fs")]
+pub use fs::FS_CAPABILITY;
+#[cfg(feature = "net")]
+pub use net::NET_CAPABILITY;
+#[cfg(feature = "process")]
+pub use process::PROCESS_CAPABILITY;
+#[cfg(feature = "time")]
+pub use time::TIME_CAPABILITY;
 
-    funcs.insert(
-        "duration".to_string(),
-        Arc::new(|state, parameter, _| {
-            assure_para_len!(parameter, 2);
-            if let Some(d1) = cast_to_offset_date(state, &parameter[0]) {
-                if let Some(d2) = cast_to_offset_date(state, &parameter[1]) {
-                    let duration = *d2 - *d1;
-                    let duration = state.create_user_data(Box::new(duration));
-                    return Ok(DiatomValue::Ref(duration));
-                }
-            };
-            Err("Expected two DateTime objects to operate".to_string())
-        }),
-    );
-
-    funcs.insert(
-        "show_duration".to_string(),
-        Arc::new(|state, parameters, _| {
-            assure_para_len!(parameters, 1);
-            if let Some(duration) = cast_to_duration(state, &parameters[0]) {
-                let duration = std::time::Duration::try_from(*duration)
-                    .map_or_else(|err| format!("{err}"), |d| format_duration(d).to_string());
-                let s = duration;
-                let sid = state.create_str(s);
-                return Ok(DiatomValue::Str(sid));
-            }
-            Err("Expected a `Duration` to operate".to_string())
-        }),
-    );
-
-    Extension {
-        name: "util".to_string(),
-        kind: ExtensionKind::ForeignFunctions(funcs),
-    }
-}
-
-fn time_extension<Buffer: IoWrite>() -> Extension<Buffer> {
-    Extension {
-        name: "time".to_string(),
-        kind: ExtensionKind::SubExtensions(vec![
-            time_util_extension(),
-            Extension {
-                name: "mod".to_string(),
-                kind: ExtensionKind::File(include_str!("time.dm").to_string()),
-            },
-        ]),
-    }
-}
+/// Module `std.os` itself, which aliases functions used by most scripts as methods, e.g.
+/// `os.args()` after `import std.os`
+#[cfg(feature = "env")]
+const OS_MOD: &str = "import args from std.os.env\n{ args = fn _ = args() }";
+#[cfg(not(feature = "env"))]
+const OS_MOD: &str = "{}";
 
 pub fn os_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let exts: Vec<Extension<Buffer>> = vec![
+        #[cfg(feature = "time")]
+        time::time_extension(),
+        #[cfg(feature = "process")]
+        process::process_extension(),
+        #[cfg(feature = "env")]
+        env::env_extension(),
+        #[cfg(feature = "fs")]
+        fs::fs_extension(),
+        #[cfg(feature = "net")]
+        net::net_extension(),
+        Extension {
+            name: "mod".to_string(),
+            kind: ExtensionKind::File(OS_MOD.to_string()),
+        },
+    ];
     Extension {
         name: "os".to_string(),
-        kind: ExtensionKind::SubExtensions(vec![time_extension()]),
+        kind: ExtensionKind::SubExtensions(exts),
     }
 }
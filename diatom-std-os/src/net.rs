@@ -0,0 +1,110 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
+
+use ahash::AHashMap;
+use diatom_core::{
+    extension::{Extension, ExtensionKind},
+    ffi::{DiatomValue, ForeignFunction, IntoDiatom, State},
+    IoWrite,
+};
+
+/// Capability that must be granted by host before any connection is made
+pub const NET_CAPABILITY: &str = "net";
+
+fn get_str<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    value: &DiatomValue,
+    name: &str,
+) -> Result<String, String> {
+    match value {
+        DiatomValue::Str(sid) => Ok(state.get_string_by_id(*sid).unwrap().to_string()),
+        _ => Err(format!("Expected type `String` for {name}")),
+    }
+}
+
+/// Connect to `addr`, send `data`, close the sending half and read until the peer closes
+///
+/// `timeout` applies to connecting and each read or write, it is `None` if not limited.
+fn exchange(addr: &str, data: &str, timeout: Option<Duration>) -> io::Result<String> {
+    let mut stream = match timeout {
+        Some(timeout) => {
+            let mut last_err = None;
+            let mut stream = None;
+            for addr in addr.to_socket_addrs()? {
+                match TcpStream::connect_timeout(&addr, timeout) {
+                    Ok(s) => {
+                        stream = Some(s);
+                        break;
+                    }
+                    Err(err) => last_err = Some(err),
+                }
+            }
+            stream.ok_or_else(|| {
+                last_err.unwrap_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "Address is not resolved")
+                })
+            })?
+        }
+        None => TcpStream::connect(addr)?,
+    };
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+    stream.write_all(data.as_bytes())?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut response = vec![];
+    stream.read_to_end(&mut response)?;
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+pub fn net_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "resolve".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, NET_CAPABILITY);
+            assure_para_len!(parameters, 1);
+            let host = get_str(state, &parameters[0], "host")?;
+            let addrs: Vec<String> = (host.as_str(), 0)
+                .to_socket_addrs()
+                .map_err(|err| format!("IoError: {err}"))?
+                .map(|addr| addr.ip().to_string())
+                .collect();
+            Ok(addrs.into_diatom(state))
+        }),
+    );
+    funcs.insert(
+        "request".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, NET_CAPABILITY);
+            assure_para_len!(parameters, 2);
+            let addr = get_str(state, &parameters[0], "address")?;
+            let data = get_str(state, &parameters[1], "data")?;
+            let deadline = state.deadline().cloned();
+            // A zero timeout is rejected by sockets, thus the passed deadline is checked first
+            if deadline.as_ref().is_some_and(|deadline| deadline.passed()) {
+                return Err(state.interrupt("Request is aborted as the deadline passed"));
+            }
+            let timeout = deadline.as_ref().map(|deadline| deadline.remaining());
+            match exchange(&addr, &data, timeout) {
+                Ok(response) => Ok(response.into_diatom(state)),
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) && deadline.is_some_and(|deadline| deadline.passed()) =>
+                {
+                    Err(state.interrupt("Request is aborted as the deadline passed"))
+                }
+                Err(err) => Err(format!("IoError: {err}")),
+            }
+        }),
+    );
+    Extension {
+        name: "net".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
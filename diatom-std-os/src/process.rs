@@ -0,0 +1,360 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, ExitStatus, Stdio},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use ahash::AHashMap;
+use diatom_core::{
+    extension::{Extension, ExtensionKind},
+    ffi::{DiatomObject, DiatomObjectMut, DiatomValue, ForeignFunction, State},
+    host::Deadline,
+    IoWrite,
+};
+
+/// Capability that must be granted by host before any process can be spawned
+pub const PROCESS_CAPABILITY: &str = "process";
+
+/// A spawned child process with piped standard streams
+struct ProcessHandle {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: Option<BufReader<ChildStdout>>,
+    stderr: Option<BufReader<ChildStderr>>,
+}
+
+fn get_str<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    value: &DiatomValue,
+    name: &str,
+) -> Result<String, String> {
+    match value {
+        DiatomValue::Str(sid) => Ok(state.get_string_by_id(*sid).unwrap().to_string()),
+        _ => Err(format!("Expected type `String` for {name}")),
+    }
+}
+
+/// Build a command from `cmd`, `args` and `opts`
+///
+/// Return the command and the string that should be written to stdin (if any).
+fn build_command<Buffer: IoWrite>(
+    state: &State<Buffer>,
+    parameters: &[DiatomValue],
+) -> Result<(Command, Option<String>), String> {
+    let cmd = get_str(state, &parameters[0], "command")?;
+    let mut command = Command::new(cmd);
+
+    match &parameters[1] {
+        DiatomValue::Ref(rid) => match state.get_obj(*rid) {
+            Some(DiatomObject::List(list)) => {
+                for i in 0..list.len() {
+                    command.arg(get_str(state, &list.get(i).unwrap(), "argument")?);
+                }
+            }
+            _ => return Err("Expected type `List` for arguments".to_string()),
+        },
+        _ => return Err("Expected type `List` for arguments".to_string()),
+    }
+
+    let mut stdin = None;
+    match &parameters[2] {
+        DiatomValue::Unit => (),
+        DiatomValue::Ref(rid) => match state.get_obj(*rid) {
+            Some(DiatomObject::Table(opts)) => {
+                if let Some(cwd) = opts.get_field("cwd") {
+                    command.current_dir(get_str(state, &cwd, "`cwd`")?);
+                }
+                if let Some(input) = opts.get_field("stdin") {
+                    stdin = Some(get_str(state, &input, "`stdin`")?);
+                }
+                match opts.get_field("env") {
+                    None => (),
+                    Some(DiatomValue::Ref(rid)) => match state.get_obj(rid) {
+                        Some(DiatomObject::Table(env)) => {
+                            for key in env.fields() {
+                                let value = env.get_field(key).unwrap();
+                                command.env(key, get_str(state, &value, "environment variable")?);
+                            }
+                        }
+                        _ => return Err("Expected type `Table` for `env`".to_string()),
+                    },
+                    Some(_) => return Err("Expected type `Table` for `env`".to_string()),
+                }
+            }
+            _ => return Err("Expected type `Table` for options".to_string()),
+        },
+        _ => return Err("Expected type `Table` for options".to_string()),
+    }
+
+    Ok((command, stdin))
+}
+
+/// Map an exit status to an exit code
+///
+/// Process terminated by a signal has an exit code of `-1`.
+fn exit_code(status: std::process::ExitStatus) -> i64 {
+    status.code().map_or(-1, |code| code as i64)
+}
+
+/// How often a child is checked while waiting for it before a deadline
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Wait for a child to exit, and kill it once the deadline passes
+///
+/// Return None if the child is killed.
+fn wait_before(child: &mut Child, deadline: Option<&Deadline>) -> io::Result<Option<ExitStatus>> {
+    let Some(deadline) = deadline else {
+        return child.wait().map(Some);
+    };
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let remaining = deadline.remaining();
+        if remaining.is_zero() {
+            // The child may exit before it is killed
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(remaining.min(POLL_INTERVAL));
+    }
+}
+
+/// Read a pipe to the end on another thread
+fn read_to_end(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+fn with_handle<Buffer: IoWrite, T>(
+    state: &mut State<Buffer>,
+    value: &DiatomValue,
+    f: impl FnOnce(&mut ProcessHandle) -> Result<T, String>,
+) -> Result<T, String> {
+    if let DiatomValue::Ref(rid) = value {
+        if let Some(DiatomObjectMut::UserData(mut data)) = state.get_obj_mut(*rid) {
+            if let Some(handle) = data.get().downcast_mut::<ProcessHandle>() {
+                return f(handle);
+            }
+        }
+    }
+    Err("Expected a `Process` to operate".to_string())
+}
+
+/// Convert a line into a tuple of `(is_ok, line)`
+fn line_to_tuple<Buffer: IoWrite>(state: &mut State<Buffer>, line: Option<String>) -> DiatomValue {
+    let (ok, line) = match line {
+        Some(line) => (true, line),
+        None => (false, String::new()),
+    };
+    let sid = state.create_str(line);
+    let tuple = state
+        .create_tuple(vec![DiatomValue::Bool(ok), DiatomValue::Str(sid)])
+        .unwrap();
+    DiatomValue::Ref(tuple)
+}
+
+fn next_line<R: BufRead>(reader: &mut Option<R>) -> Result<Option<String>, String> {
+    let reader = match reader {
+        Some(reader) => reader,
+        None => return Ok(None),
+    };
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => Ok(None),
+        Ok(_) => {
+            if line.ends_with('\n') {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+            Ok(Some(line))
+        }
+        Err(err) => Err(format!("Can not read from process: {err}")),
+    }
+}
+
+fn process_util_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    let mut funcs: AHashMap<String, Arc<ForeignFunction<Buffer>>> = AHashMap::default();
+    funcs.insert(
+        "run".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, PROCESS_CAPABILITY);
+            assure_para_len!(parameters, 3);
+            let (mut command, input) = build_command(state, parameters)?;
+            command
+                .stdin(if input.is_some() {
+                    Stdio::piped()
+                } else {
+                    Stdio::null()
+                })
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            let mut child = command
+                .spawn()
+                .map_err(|err| format!("Can not spawn process: {err}"))?;
+            if let Some(input) = input {
+                // Write from another thread so that a full stdout pipe can not dead lock us.
+                // Dropping stdin closes the pipe.
+                let mut stdin = child.stdin.take().unwrap();
+                thread::spawn(move || stdin.write_all(input.as_bytes()));
+            }
+            let stdout = read_to_end(child.stdout.take());
+            let stderr = read_to_end(child.stderr.take());
+            let status = wait_before(&mut child, state.deadline())
+                .map_err(|err| format!("Can not wait for process: {err}"))?
+                .ok_or_else(|| state.interrupt("Process is killed as the deadline passed"))?;
+            let code = exit_code(status);
+            let stdout = stdout.join().unwrap_or_default();
+            let stderr = stderr.join().unwrap_or_default();
+            let stdout = state.create_str(String::from_utf8_lossy(&stdout).into_owned());
+            let stderr = state.create_str(String::from_utf8_lossy(&stderr).into_owned());
+            let tuple = state
+                .create_tuple(vec![
+                    DiatomValue::Int(code),
+                    DiatomValue::Str(stdout),
+                    DiatomValue::Str(stderr),
+                ])
+                .unwrap();
+            Ok(DiatomValue::Ref(tuple))
+        }),
+    );
+
+    funcs.insert(
+        "spawn".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, PROCESS_CAPABILITY);
+            assure_para_len!(parameters, 3);
+            let (mut command, input) = build_command(state, parameters)?;
+            command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            let mut child = command
+                .spawn()
+                .map_err(|err| format!("Can not spawn process: {err}"))?;
+            let mut stdin = child.stdin.take();
+            if let (Some(input), Some(pipe)) = (input, stdin.as_mut()) {
+                pipe.write_all(input.as_bytes())
+                    .map_err(|err| format!("Can not write to process: {err}"))?;
+            }
+            let handle = ProcessHandle {
+                stdin,
+                stdout: child.stdout.take().map(BufReader::new),
+                stderr: child.stderr.take().map(BufReader::new),
+                child,
+            };
+            let rid = state.create_user_data(Box::new(handle));
+            Ok(DiatomValue::Ref(rid))
+        }),
+    );
+
+    funcs.insert(
+        "read_line".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, PROCESS_CAPABILITY);
+            assure_para_len!(parameters, 1);
+            let line = with_handle(state, &parameters[0], |h| next_line(&mut h.stdout))?;
+            Ok(line_to_tuple(state, line))
+        }),
+    );
+
+    funcs.insert(
+        "read_err_line".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, PROCESS_CAPABILITY);
+            assure_para_len!(parameters, 1);
+            let line = with_handle(state, &parameters[0], |h| next_line(&mut h.stderr))?;
+            Ok(line_to_tuple(state, line))
+        }),
+    );
+
+    funcs.insert(
+        "write".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, PROCESS_CAPABILITY);
+            assure_para_len!(parameters, 2);
+            let s = get_str(state, &parameters[1], "input")?;
+            with_handle(state, &parameters[0], |h| match h.stdin.as_mut() {
+                Some(stdin) => stdin
+                    .write_all(s.as_bytes())
+                    .and_then(|_| stdin.flush())
+                    .map_err(|err| format!("Can not write to process: {err}")),
+                None => Err("Standard input of process is already closed".to_string()),
+            })?;
+            Ok(DiatomValue::Unit)
+        }),
+    );
+
+    funcs.insert(
+        "close_stdin".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, PROCESS_CAPABILITY);
+            assure_para_len!(parameters, 1);
+            with_handle(state, &parameters[0], |h| {
+                h.stdin = None;
+                Ok(())
+            })?;
+            Ok(DiatomValue::Unit)
+        }),
+    );
+
+    funcs.insert(
+        "wait".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, PROCESS_CAPABILITY);
+            assure_para_len!(parameters, 1);
+            let deadline = state.deadline().cloned();
+            let status = with_handle(state, &parameters[0], |h| {
+                // Close stdin so that child does not wait for input forever
+                h.stdin = None;
+                wait_before(&mut h.child, deadline.as_ref())
+                    .map_err(|err| format!("Can not wait for process: {err}"))
+            })?;
+            let status = status
+                .ok_or_else(|| state.interrupt("Process is killed as the deadline passed"))?;
+            Ok(DiatomValue::Int(exit_code(status)))
+        }),
+    );
+
+    funcs.insert(
+        "kill".to_string(),
+        Arc::new(|state, parameters, _| {
+            assure_capability!(state, PROCESS_CAPABILITY);
+            assure_para_len!(parameters, 1);
+            with_handle(state, &parameters[0], |h| {
+                h.child
+                    .kill()
+                    .map_err(|err| format!("Can not kill process: {err}"))
+            })?;
+            Ok(DiatomValue::Unit)
+        }),
+    );
+
+    Extension {
+        name: "util".to_string(),
+        kind: ExtensionKind::ForeignFunctions(funcs),
+    }
+}
+
+pub fn process_extension<Buffer: IoWrite>() -> Extension<Buffer> {
+    Extension {
+        name: "process".to_string(),
+        kind: ExtensionKind::SubExtensions(vec![
+            process_util_extension(),
+            Extension {
+                name: "mod".to_string(),
+                kind: ExtensionKind::File(include_str!("process.dm").to_string()),
+            },
+        ]),
+    }
+}
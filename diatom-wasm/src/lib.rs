@@ -0,0 +1,121 @@
+//! # Diatom for WebAssembly
+//!
+//! Bindings generated by `wasm-bindgen` to run diatom in a browser, e.g. for an online
+//! playground. Build with `wasm-pack build diatom-wasm --target web`, then in JavaScript:
+//!
+//! ```js
+//! import init, { eval_source, Playground } from "./pkg/diatom_wasm.js";
+//!
+//! await init();
+//! const result = eval_source("print('Hello, world!')");
+//! console.log(result.output, result.diagnostics);
+//!
+//! // Globals are kept between calls of the same playground
+//! const playground = new Playground();
+//! playground.eval("x = 1");
+//! playground.eval("print(x + 1)");
+//! ```
+//!
+//! Scripts can not read files or the clock, and `input()` always reaches end of input.
+use std::io;
+
+use diatom::{ColorChoice, Interpreter, RenderOptions};
+use wasm_bindgen::prelude::*;
+
+/// What a piece of code printed and reported
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalResult {
+    /// Output of the script, up to the error if execution failed
+    pub output: String,
+    /// Rendered errors and warnings, empty if there is none
+    pub diagnostics: String,
+    /// Whether code is executed without error
+    pub ok: bool,
+}
+
+/// An interpreter whose globals are kept between evaluations
+#[wasm_bindgen]
+pub struct Playground {
+    interpreter: Interpreter<Vec<u8>>,
+}
+
+#[wasm_bindgen]
+impl Playground {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter.set_render_options(RenderOptions::default().color(ColorChoice::Never));
+        interpreter.replace_input(Box::new(io::empty()));
+        Self { interpreter }
+    }
+
+    /// Execute `source` and collect its output and diagnostics
+    pub fn eval(&mut self, source: &str) -> EvalResult {
+        let result = self.interpreter.exec(source, "<playground>", true);
+        let output = self.interpreter.replace_buffer(vec![]);
+        let output = String::from_utf8_lossy(&output).into_owned();
+        match result {
+            Ok(()) => {
+                let mut diagnostics = String::new();
+                // Writing to a `String` never fails
+                let _ = self.interpreter.write_diagnostics(&mut diagnostics);
+                EvalResult {
+                    output,
+                    diagnostics,
+                    ok: true,
+                }
+            }
+            Err(diagnostics) => EvalResult {
+                output,
+                diagnostics,
+                ok: false,
+            },
+        }
+    }
+}
+
+impl Default for Playground {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Execute `source` in a new interpreter
+///
+/// Named `eval_source` since `eval` is reserved in JavaScript modules.
+#[wasm_bindgen]
+pub fn eval_source(source: &str) -> EvalResult {
+    Playground::new().eval(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        let result = eval_source("print('Hello,', 1 + 1)");
+        assert_eq!(result.output, "Hello, 2");
+        assert_eq!(result.diagnostics, "");
+        assert!(result.ok);
+
+        let result = eval_source("print('before')\nx = 1 + true");
+        assert_eq!(result.output, "before");
+        assert!(result.diagnostics.contains("error"), "{result:?}");
+        assert!(!result.diagnostics.contains("\u{1b}["), "{result:?}");
+        assert!(!result.ok);
+
+        let result = eval_source("x = input('Name? ')\nprint(x is Option::None)");
+        assert_eq!(result.output, "Name? true");
+    }
+
+    #[test]
+    fn test_playground() {
+        let mut playground = Playground::new();
+        assert!(playground.eval("x = 1").ok);
+        assert_eq!(playground.eval("print(x + 1)").output, "2");
+        assert!(!playground.eval("y = z").ok);
+        assert_eq!(playground.eval("print(x)").output, "1");
+    }
+}
@@ -0,0 +1,80 @@
+//! Benchmarks of representative programs
+//!
+//! Run with `cargo bench -p diatom --bench suite`. To compare a change with the code it is based
+//! on, run `cargo bench -p diatom --bench suite -- --save-baseline base` before the change and
+//! `cargo bench -p diatom --bench suite -- --baseline base` after it.
+//!
+//! Instructions executed and objects allocated by each program are printed before it is
+//! measured, they do not depend on the machine and thus can be compared across runs.
+use std::{fs, time::Duration};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use diatom::Interpreter;
+
+const PROGRAMS: &[(&str, &str)] = &[
+    ("fib", include_str!("programs/fib.dm")),
+    ("nbody", include_str!("programs/nbody.dm")),
+    ("string", include_str!("programs/string.dm")),
+    ("table", include_str!("programs/table.dm")),
+    ("closure", include_str!("programs/closure.dm")),
+    ("print", include_str!("programs/print.dm")),
+];
+
+fn programs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("programs");
+    for (name, code) in PROGRAMS {
+        let mut interpreter = Interpreter::new(vec![]);
+        if let Err(err) = interpreter.exec(code, name, true) {
+            panic!("{err}");
+        }
+        let stats = interpreter.stats();
+        println!(
+            "{name}: {} instructions, {} allocations, {} collections",
+            stats.instructions, stats.allocations, stats.collections
+        );
+        group.bench_function(*name, |b| {
+            b.iter_batched(
+                || Interpreter::new(vec![]),
+                |mut interpreter| interpreter.exec(code, name, true).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Source with `n` functions of a few statements each
+fn generated_source(n: usize) -> String {
+    (0..n)
+        .map(|i| {
+            format!(
+                "def f{i} x y =\n    t = {{a = x, b = [y, {i}, 'item']}}\n    if x > {i} then\n        t.a + f{i}(x - 1, y)\n    else\n        y * 2 - 1\n    end\nend\n"
+            )
+        })
+        .collect()
+}
+
+fn parser(c: &mut Criterion) {
+    let source = generated_source(2000);
+    let path = std::env::temp_dir().join(format!("diatom-bench-{}.dm", std::process::id()));
+    fs::write(&path, &source).unwrap();
+    let interpreter = Interpreter::new(vec![]);
+    let mut group = c.benchmark_group("parser");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("parse", |b| {
+        b.iter(|| {
+            interpreter
+                .parse_files(std::slice::from_ref(&path))
+                .unwrap()
+        })
+    });
+    group.finish();
+    let _ = fs::remove_file(path);
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(10));
+    targets = programs, parser
+}
+criterion_main!(benches);
@@ -0,0 +1,285 @@
+//! Run benchmarks written with `std.bench` and compare them with a baseline
+//!
+//! # Example
+//! ```
+//! use diatom::{benchmark, Interpreter};
+//!
+//! let mut interpreter = Interpreter::new(Vec::<u8>::new());
+//! let results = benchmark::run_benches(
+//!     &mut interpreter,
+//!     r#"
+//!     import {run_with} from std.bench
+//!     run_with("sum", fn = (1..100).sum(), 1, 20)
+//!     "#,
+//!     "<bench>",
+//!     true,
+//! )
+//! .unwrap();
+//! assert_eq!(results[0].name, "sum");
+//! assert_eq!(results[0].iterations, 20);
+//!
+//! // Saved results can be loaded as baseline of a later run
+//! let baseline = benchmark::from_json(&benchmark::to_json(&results)).unwrap();
+//! let comparisons = benchmark::compare(&results, &baseline);
+//! assert_eq!(comparisons[0].change(), Some(0.0));
+//! ```
+
+use std::{
+    ffi::OsStr,
+    fmt::Write,
+    io,
+    iter::Peekable,
+    path::{Path, PathBuf},
+    str::Chars,
+};
+
+use diatom_std_core::BenchResults;
+pub use diatom_std_core::{format_duration, BenchStats};
+
+use crate::{testing::discover_files, Interpreter, IoWrite};
+
+/// Suffix of file names picked up by `discover`
+pub const BENCH_FILE_SUFFIX: &str = "_bench.dm";
+
+/// Execute a benchmark file and collect results of every `run` in order
+pub fn run_benches<Buffer: IoWrite>(
+    interpreter: &mut Interpreter<Buffer>,
+    code: impl AsRef<str>,
+    source: impl AsRef<OsStr>,
+    is_phony: bool,
+) -> Result<Vec<BenchStats>, String> {
+    interpreter.set_context(BenchResults::default());
+    let result = interpreter.exec(code, source, is_phony);
+    let results = interpreter
+        .take_context::<BenchResults>()
+        .unwrap_or_default();
+    result.map(|_| results.0)
+}
+
+/// Find benchmark files under `path`, sorted by path
+///
+/// A file given directly is always included. Directories are searched recursively for files
+/// whose names end with `_bench.dm`.
+pub fn discover(path: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    discover_files(path.as_ref(), BENCH_FILE_SUFFIX)
+}
+
+/// A benchmark compared with its baseline
+pub struct Comparison {
+    pub name: String,
+    /// Mean time of this run in nanoseconds
+    pub mean: f64,
+    /// Mean time of the baseline, `None` if the benchmark is new
+    pub baseline: Option<f64>,
+}
+
+impl Comparison {
+    /// Relative change of mean time, e.g. `0.1` if 10% slower than baseline
+    pub fn change(&self) -> Option<f64> {
+        self.baseline.map(|baseline| self.mean / baseline - 1.0)
+    }
+}
+
+/// Match results with baseline by name
+pub fn compare(results: &[BenchStats], baseline: &[BenchStats]) -> Vec<Comparison> {
+    results
+        .iter()
+        .map(|stats| Comparison {
+            name: stats.name.clone(),
+            mean: stats.mean,
+            baseline: baseline
+                .iter()
+                .find(|base| base.name == stats.name)
+                .map(|base| base.mean),
+        })
+        .collect()
+}
+
+/// Save results as a JSON array
+pub fn to_json(results: &[BenchStats]) -> String {
+    let mut json = "[\n".to_string();
+    for (i, stats) in results.iter().enumerate() {
+        json.push_str("  {\"name\": ");
+        write_json_str(&mut json, &stats.name);
+        write!(
+            json,
+            ", \"iterations\": {}, \"mean\": {:?}, \"median\": {:?}, \"p95\": {:?}}}",
+            stats.iterations, stats.mean, stats.median, stats.p95
+        )
+        .unwrap();
+        json.push_str(if i + 1 < results.len() { ",\n" } else { "\n" });
+    }
+    json.push(']');
+    json.push('\n');
+    json
+}
+
+fn write_json_str(json: &mut String, s: &str) {
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Load results saved by `to_json`
+pub fn from_json(json: &str) -> Result<Vec<BenchStats>, String> {
+    let mut chars = json.chars().peekable();
+    let value = parse_value(&mut chars)?;
+    skip_whitespace(&mut chars);
+    if chars.next().is_some() {
+        return Err("Unexpected trailing characters".to_string());
+    }
+    let Json::Array(items) = value else {
+        return Err("Expected an array of benchmarks".to_string());
+    };
+    items
+        .into_iter()
+        .map(|item| {
+            let Json::Object(fields) = item else {
+                return Err("Expected an object of benchmark".to_string());
+            };
+            let field = |name: &str| {
+                fields
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value)
+                    .ok_or_else(|| format!("Missing field `{name}`"))
+            };
+            let number = |name: &str| match field(name)? {
+                Json::Number(n) => Ok(*n),
+                _ => Err(format!("Expected a number as `{name}`")),
+            };
+            let name = match field("name")? {
+                Json::Str(s) => s.clone(),
+                _ => return Err("Expected a string as `name`".to_string()),
+            };
+            Ok(BenchStats {
+                name,
+                iterations: number("iterations")? as usize,
+                mean: number("mean")?,
+                median: number("median")?,
+                p95: number("p95")?,
+            })
+        })
+        .collect()
+}
+
+/// JSON values, only what is needed to read a baseline
+enum Json {
+    Null,
+    Bool,
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect_literal(chars: &mut Peekable<Chars>, literal: &str) -> Result<(), String> {
+    if literal.chars().all(|c| chars.next() == Some(c)) {
+        Ok(())
+    } else {
+        Err(format!("Expected `{literal}`"))
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('n') => expect_literal(chars, "null").map(|_| Json::Null),
+        Some('t') => expect_literal(chars, "true").map(|_| Json::Bool),
+        Some('f') => expect_literal(chars, "false").map(|_| Json::Bool),
+        Some('"') => parse_str(chars).map(Json::Str),
+        Some('[') => {
+            chars.next();
+            let mut items = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Ok(Json::Array(items));
+            }
+            loop {
+                items.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Json::Array(items)),
+                    _ => return Err("Expected `,` or `]`".to_string()),
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            let mut fields = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Ok(Json::Object(fields));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = parse_str(chars)?;
+                skip_whitespace(chars);
+                expect_literal(chars, ":")?;
+                fields.push((key, parse_value(chars)?));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Json::Object(fields)),
+                    _ => return Err("Expected `,` or `}`".to_string()),
+                }
+            }
+        }
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) =
+                chars.next_if(|c| matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            {
+                number.push(c);
+            }
+            number
+                .parse()
+                .map(Json::Number)
+                .map_err(|_| format!("Invalid number `{number}`"))
+        }
+        _ => Err("Expected a JSON value".to_string()),
+    }
+}
+
+fn parse_str(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    expect_literal(chars, "\"")?;
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => match chars.next() {
+                Some('n') => s.push('\n'),
+                Some('t') => s.push('\t'),
+                Some('r') => s.push('\r'),
+                Some('b') => s.push('\u{8}'),
+                Some('f') => s.push('\u{c}'),
+                Some('u') => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    let c = u32::from_str_radix(&code, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| format!("Invalid escape `\\u{code}`"))?;
+                    s.push(c);
+                }
+                Some(c @ ('"' | '\\' | '/')) => s.push(c),
+                _ => return Err("Invalid escape in string".to_string()),
+            },
+            Some(c) => s.push(c),
+            None => return Err("Unterminated string".to_string()),
+        }
+    }
+}
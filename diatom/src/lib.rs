@@ -78,16 +78,76 @@
 //! "#, "<test_code>", true).unwrap();
 //! assert_eq!(*value.lock().unwrap(), 5);
 //! ```
+//!
+//! ## 3. Run stages separately
+//! `Interpreter::exec` parses, compiles and executes code at once. `Parser`, `Compiler` and `Vm`
+//! run these stages one by one, e.g. to report syntax errors before any environment is set up
+//! or to run one program by many virtual machines.
+//! ```
+//! use diatom::{Compiler, Interpreter, Parser, Vm};
+//!
+//! let parsed = Parser::new()
+//!     .parse("output = input * 2", "<test_code>", true)
+//!     .unwrap();
+//! assert!(Parser::new().parse("output = (", "<test_code>", true).is_err());
+//!
+//! let mut interpreter = Interpreter::new(vec![]);
+//! interpreter.set_global("input", 0);
+//! let mut compiler = Compiler::new(interpreter);
+//! let program = compiler.compile(&parsed).unwrap();
+//!
+//! let mut vm = Vm::new(&program, vec![]);
+//! vm.interpreter_mut().set_global("input", 21);
+//! vm.run().unwrap();
+//! assert_eq!(vm.interpreter_mut().get_global_as::<i64>("output"), Ok(42));
+//! ```
+
+use std::{
+    any::Any,
+    ffi::OsStr,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+pub use diatom_core::{
+    bundle, doc, extension, ffi, format, highlight, host, impl_diatom_table, lint, manifest,
+    syntax, tokens, ColorChoice, FsPolicy, IoRead, IoWrite, KeywordAliases, ParsedSource, Parser,
+    PrintOptions, RenderOptions, SandboxPolicy, SharedFile, SourceMap, SourceMapping, WarningLevel,
+    WarningOptions,
+};
 
-use std::{ffi::OsStr, io, path::PathBuf};
+#[cfg(feature = "unstable-ast")]
+pub use diatom_core::ast;
+#[cfg(feature = "macros")]
+pub use diatom_macros::{include_script, script};
+// Paths emitted by the macros start with `::diatom`
+#[cfg(feature = "macros")]
+extern crate self as diatom;
+pub mod benchmark;
+pub mod output;
+/// Capture output of scripts, e.g. in tests or a web UI, see `output`
+pub mod capture {
+    pub use crate::output::{BufferedWriter, StringBuffer};
+}
+mod pipeline;
+pub mod pool;
+#[cfg(feature = "repl")]
+pub mod repl;
+pub mod testing;
 
-pub use diatom_core::{extension, ffi, IoWrite};
+pub use pipeline::{Compiler, Vm};
 
 /// The version of this build
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-use diatom_core::{extension::Extension, Interpreter as __Interpreter};
-use diatom_std_core::{std_lib, StdLibCore};
+use diatom_core::{
+    extension::Extension, lint::LintConfig, Interpreter as __Interpreter, Program as __Program,
+};
+use diatom_std_core::{std_lib, LogSinkContext, StdLibCore};
+pub use diatom_std_core::{Channel, LogLevel, LogRecord, LogSink, SharedValue};
+#[cfg(feature = "std-env")]
+pub use diatom_std_os::{ScriptArgs, ENV_CAPABILITY};
 
 /// # The Diatom Interpreter
 ///
@@ -101,7 +161,7 @@ impl<Buffer: IoWrite> Interpreter<Buffer> {
     fn load_std(&mut self) {
         #[allow(unused_mut)]
         let mut std_lib_exts = std_lib();
-        #[cfg(feature = "std-os")]
+        #[cfg(feature = "diatom-std-os")]
         std_lib_exts.push(diatom_std_os::os_extension());
 
         let std = Extension {
@@ -129,6 +189,42 @@ impl<Buffer: IoWrite> Interpreter<Buffer> {
         self.0.with_search_path(path)
     }
 
+    /// Search paths added so far, in the order they are searched
+    pub fn search_paths(&self) -> &[PathBuf] {
+        self.0.search_paths()
+    }
+
+    /// Add search paths in environment variable `DIATOM_PATH`, see `manifest::SEARCH_PATH_ENV`
+    pub fn with_env_search_path(&mut self) -> &mut Self {
+        self.0.with_env_search_path();
+        self
+    }
+
+    /// Import modules of a project described by `manifest`
+    ///
+    /// Its module directory is added as a search path and each dependency is imported by its
+    /// name, e.g. `import util.strings` for dependency `util`.
+    ///
+    /// ```
+    /// # use diatom::{manifest::Manifest, Interpreter};
+    /// let dir = std::env::temp_dir().join(format!("diatom-manifest-doc-{}", std::process::id()));
+    /// std::fs::create_dir_all(dir.join("src")).unwrap();
+    /// std::fs::write(dir.join("diatom.toml"), "[package]\nsrc = 'src'").unwrap();
+    /// std::fs::write(dir.join("src/answer.dm"), "{answer = 42}").unwrap();
+    ///
+    /// let manifest = Manifest::load(&Manifest::find(&dir).unwrap()).unwrap();
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.with_manifest(&manifest).unwrap();
+    /// interpreter
+    ///     .exec("import {answer} from answer", "<test_code>", true)
+    ///     .unwrap();
+    /// assert_eq!(interpreter.get_global_as::<i64>("answer").unwrap(), 42);
+    /// # std::fs::remove_dir_all(dir).unwrap();
+    /// ```
+    pub fn with_manifest(&mut self, manifest: &manifest::Manifest) -> Result<(), io::Error> {
+        self.0.with_manifest(manifest)
+    }
+
     /// Enable ansi colored error message
     pub fn with_color(buffer: Buffer) -> Self {
         let mut interpreter = Self(__Interpreter::with_color(buffer));
@@ -136,6 +232,814 @@ impl<Buffer: IoWrite> Interpreter<Buffer> {
         interpreter
     }
 
+    /// Create an interpreter under `policy` instead of the default one
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{Interpreter, SandboxPolicy};
+    ///
+    /// let mut policy = SandboxPolicy::deny_all();
+    /// policy.max_allocations = Some(1_000);
+    /// let mut interpreter = Interpreter::with_policy(vec![], policy);
+    /// assert!(interpreter
+    ///     .exec("x = []\nloop x = [x] end", "<test_code>", true)
+    ///     .is_err());
+    /// ```
+    pub fn with_policy(buffer: Buffer, policy: SandboxPolicy) -> Self {
+        let mut interpreter = Self(__Interpreter::with_policy(buffer, policy));
+        interpreter.load_std();
+        interpreter
+    }
+
+    /// Set how diagnostics are rendered and return the old options
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{ColorChoice, Interpreter, RenderOptions};
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.set_render_options(
+    ///     RenderOptions::default()
+    ///         .color(ColorChoice::Never)
+    ///         .width(Some(60)),
+    /// );
+    /// let error = interpreter.exec("undefined_name", "<test>", true).unwrap_err();
+    /// assert!(!error.contains('\x1b'));
+    /// ```
+    pub fn set_render_options(&mut self, options: RenderOptions) -> RenderOptions {
+        self.0.set_render_options(options)
+    }
+
+    pub fn render_options(&self) -> &RenderOptions {
+        self.0.render_options()
+    }
+
+    /// Set limits of how values are shown by `print` and `inspect`, return the old options
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{Interpreter, PrintOptions};
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.set_print_options(PrintOptions::default().max_depth(Some(1)).max_items(Some(3)));
+    /// interpreter.exec("print([[1], 2, 3, 4])", "<test>", true).unwrap();
+    /// let output = interpreter.replace_buffer(vec![]);
+    /// assert_eq!(String::from_utf8(output).unwrap(), "[[...], 2, 3, ...]");
+    /// ```
+    pub fn set_print_options(&mut self, options: PrintOptions) -> PrintOptions {
+        self.0.set_print_options(options)
+    }
+
+    pub fn print_options(&self) -> PrintOptions {
+        self.0.print_options()
+    }
+
+    /// Set which warnings are reported and how, return the old options
+    ///
+    /// Warnings found while compiling are kept with other diagnostics, see `emit_diagnostics`.
+    /// They fail compilation if the level is `WarningLevel::Deny`.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{Interpreter, WarningLevel, WarningOptions};
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// let code = "if (true) then 1 end";
+    /// interpreter.exec(code, "<test>", true).unwrap();
+    /// assert_eq!(interpreter.warning_count(), 1);
+    ///
+    /// interpreter.set_warning_options(WarningOptions::default().suppress("unnecessary"));
+    /// interpreter.exec(code, "<test>", true).unwrap();
+    /// assert_eq!(interpreter.warning_count(), 0);
+    ///
+    /// interpreter.set_warning_options(WarningOptions::default().level(WarningLevel::Deny));
+    /// assert!(interpreter.exec(code, "<test>", true).is_err());
+    /// ```
+    pub fn set_warning_options(&mut self, options: WarningOptions) -> WarningOptions {
+        self.0.set_warning_options(options)
+    }
+
+    pub fn warning_options(&self) -> &WarningOptions {
+        self.0.warning_options()
+    }
+
+    /// Set names recognized as keywords in code compiled later, return the old aliases
+    ///
+    /// Each use of an alias is reported by warning `W1002`, suggesting the keyword instead.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{Interpreter, KeywordAliases};
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// let aliases = KeywordAliases::default()
+    ///     .alias("function", "def")
+    ///     .unwrap()
+    ///     .alias("elseif", "elsif")
+    ///     .unwrap();
+    /// interpreter.set_keyword_aliases(aliases);
+    /// let code = "function sign x =\n  if x < 0 then -1 elseif x > 0 then 1 else 0 end\nend";
+    /// interpreter.exec(code, "<test>", true).unwrap();
+    /// assert_eq!(interpreter.warning_count(), 2);
+    /// let mut diagnostics = vec![];
+    /// interpreter.emit_diagnostics(&mut diagnostics).unwrap();
+    /// assert!(String::from_utf8(diagnostics).unwrap().contains("Use `def` instead"));
+    ///
+    /// assert!(KeywordAliases::default().alias("function", "func").is_err());
+    /// ```
+    pub fn set_keyword_aliases(&mut self, aliases: KeywordAliases) -> KeywordAliases {
+        self.0.set_keyword_aliases(aliases)
+    }
+
+    pub fn keyword_aliases(&self) -> &KeywordAliases {
+        self.0.keyword_aliases()
+    }
+
+    /// Enable or disable strict mode for code compiled later, return the old setting
+    ///
+    /// In strict mode, misspelled names are errors instead of leftover or unit values:
+    /// - Reading a variable in the assignment that defines it is error `E2010`. Functions
+    ///   defined in the assignment may still refer to the variable.
+    /// - Only assignments at top level of a file or a function declare variables, assigning an
+    ///   undeclared name in a nested block is error `E2013`.
+    /// - Reading a global whose assignment failed, e.g. in a previous input of a REPL, is
+    ///   runtime error `E3029` until it is assigned.
+    ///
+    /// A single file can also opt in by a line `--! strict` before any code.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.set_strict(true);
+    /// let code = "count = 0\ndef inc = cuont = cuont + 1 end";
+    /// let err = interpreter.exec(code, "<test>", true).unwrap_err();
+    /// assert!(err.contains("E2010"));
+    /// assert!(err.contains("Did you mean `count`?"));
+    /// let code = "fib = fn n = if n < 2 then n else fib(n - 1) + fib(n - 2) end";
+    /// interpreter.exec(code, "<test>", true).unwrap();
+    ///
+    /// let err = interpreter.exec("if fib(1) == 1 then n = 1 end", "<test>", true);
+    /// assert!(err.unwrap_err().contains("E2013"));
+    /// assert!(interpreter.exec("fibs = [][0]", "<test>", true).is_err());
+    /// let err = interpreter.exec("fibs(3)", "<test>", true).unwrap_err();
+    /// assert!(err.contains("E3029"));
+    /// assert!(err.contains("Did you mean `fib`?"));
+    /// ```
+    pub fn set_strict(&mut self, strict: bool) -> bool {
+        self.0.set_strict(strict)
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.0.is_strict()
+    }
+
+    /// Check lint rules on code compiled later, or stop checking them if `None`
+    ///
+    /// Violated rules are reported with other diagnostics of compilation, filtered by warning
+    /// options. Rules set to `LintLevel::Deny` fail compilation. Return the old config.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{
+    ///     lint::{Lint, LintConfig, LintLevel},
+    ///     Interpreter,
+    /// };
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.set_lint(Some(LintConfig::default()));
+    /// let code = "def f x =\n    y = x\n    x\nend";
+    /// interpreter.check(code, "<test>", true).unwrap();
+    /// assert_eq!(interpreter.warning_count(), 1);
+    ///
+    /// let config = LintConfig::default().set(Lint::UnusedVariable, LintLevel::Deny);
+    /// interpreter.set_lint(Some(config));
+    /// let err = interpreter.check(code, "<test>", true).unwrap_err();
+    /// assert!(err.contains("Variable `y` is never used"));
+    /// ```
+    pub fn set_lint(&mut self, config: Option<LintConfig>) -> Option<LintConfig> {
+        self.0.set_lint(config)
+    }
+
+    pub fn lint_config(&self) -> Option<&LintConfig> {
+        self.0.lint_config()
+    }
+
+    /// Set source map of code executed as `source`, remove it if `None`, return the old one
+    ///
+    /// Diagnostics in mapped spans get a note pointing to the original file, and locations of
+    /// functions defined there are reported in the original file.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{Interpreter, SourceMap};
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// // Line 2 is copied from line 10 of `page.tpl`, starting at column 3
+    /// let code = "x = 1\ny = z\n";
+    /// interpreter.set_source_map("<page>", Some(SourceMap::new().map(6..12, "page.tpl", 10, 3)));
+    /// let err = interpreter.exec(code, "<page>", true).unwrap_err();
+    /// assert!(err.contains("Generated from `page.tpl:10:7`"));
+    /// ```
+    pub fn set_source_map(
+        &mut self,
+        source: impl AsRef<OsStr>,
+        map: Option<SourceMap>,
+    ) -> Option<SourceMap> {
+        self.0.set_source_map(source, map)
+    }
+
+    /// Number of warnings reported by the last call
+    pub fn warning_count(&self) -> usize {
+        self.0.warning_count()
+    }
+
+    /// Write diagnostics of the last call to `writer` instead of rendering them to a string
+    ///
+    /// Diagnostics are kept until the next call that compiles or executes code.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// let error = interpreter.exec("1 +", "<test>", true).unwrap_err();
+    /// let mut log = vec![];
+    /// interpreter.emit_diagnostics(&mut log).unwrap();
+    /// assert_eq!(String::from_utf8(log).unwrap(), error);
+    /// ```
+    pub fn emit_diagnostics(&self, writer: impl IoWrite) -> io::Result<()> {
+        self.0.emit_diagnostics(writer)
+    }
+
+    /// Write diagnostics of the last call to a `fmt::Write`
+    pub fn write_diagnostics(&self, writer: &mut impl std::fmt::Write) -> std::fmt::Result {
+        self.0.write_diagnostics(writer)
+    }
+
+    /// Set what scripts are allowed to do and return the old policy
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{Interpreter, SandboxPolicy};
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// let mut policy = SandboxPolicy::deny_all();
+    /// policy.max_fuel = Some(10_000);
+    /// interpreter.set_policy(policy);
+    /// assert!(interpreter.exec("loop end", "<test_code>", true).is_err());
+    /// ```
+    pub fn set_policy(&mut self, policy: SandboxPolicy) -> SandboxPolicy {
+        self.0.set_policy(policy)
+    }
+
+    pub fn policy(&self) -> &SandboxPolicy {
+        self.0.policy()
+    }
+
+    /// Set clock used for timeouts and `std.bench`, None for no clock, return the old one
+    ///
+    /// The clock of the standard library is used by default where it is available. Without a
+    /// clock, `SandboxPolicy::timeout` is ignored.
+    pub fn set_clock(
+        &mut self,
+        clock: Option<Arc<dyn host::Clock>>,
+    ) -> Option<Arc<dyn host::Clock>> {
+        self.0.set_clock(clock)
+    }
+
+    /// Set file system that modules are imported from and return the old one
+    ///
+    /// The file system of the standard library is used by default.
+    ///
+    /// # Example
+    /// ```
+    /// use std::{io, path::Path, sync::Arc};
+    /// use diatom::{host::FileSystem, Interpreter};
+    ///
+    /// struct Modules;
+    /// impl FileSystem for Modules {
+    ///     fn read_to_string(&self, path: &Path) -> io::Result<String> {
+    ///         match path.file_stem().and_then(|name| name.to_str()) {
+    ///             Some("greeting") => Ok("{text = 'hello'}".to_string()),
+    ///             _ => Err(io::ErrorKind::NotFound.into()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.with_search_path(std::env::temp_dir()).unwrap();
+    /// interpreter.set_file_system(Arc::new(Modules));
+    /// interpreter
+    ///     .exec("import {text} from greeting", "<test_code>", true)
+    ///     .unwrap();
+    /// assert_eq!(interpreter.get_global_as::<String>("text").unwrap(), "hello");
+    /// ```
+    pub fn set_file_system(
+        &mut self,
+        file_system: Arc<dyn host::FileSystem>,
+    ) -> Arc<dyn host::FileSystem> {
+        self.0.set_file_system(file_system)
+    }
+
+    /// Set sink of records logged by `std.log` and return the old one
+    ///
+    /// Without a sink, records are printed to output.
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use diatom::{Interpreter, LogLevel, LogRecord};
+    ///
+    /// let records = Arc::new(Mutex::new(vec![]));
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// let sink = records.clone();
+    /// interpreter.set_log_sink(move |record: LogRecord| sink.lock().unwrap().push(record));
+    /// interpreter
+    ///     .exec("import std.log\nlog::warn('disk is full', {free = 0})", "<test_code>", true)
+    ///     .unwrap();
+    /// let records = records.lock().unwrap();
+    /// assert_eq!(records[0].level, LogLevel::Warn);
+    /// assert_eq!(records[0].message, "disk is full");
+    /// ```
+    pub fn set_log_sink(&mut self, sink: impl LogSink + 'static) -> Option<Box<dyn LogSink>> {
+        self.0
+            .set_context(LogSinkContext(Box::new(sink)))
+            .map(|sink| sink.0)
+    }
+
+    /// Set loader that modules are imported from before files, None for no loader, return the
+    /// old one
+    ///
+    /// Modules embedded in the host binary can be served by a map from module name to source.
+    ///
+    /// # Example
+    /// ```
+    /// # use diatom::Interpreter;
+    /// # use std::{collections::HashMap, sync::Arc};
+    /// let modules = HashMap::from([
+    ///     ("util".to_string(), "import {twice} from util.math\n{twice = twice}".to_string()),
+    ///     ("util.math".to_string(), "{twice = fn x = x * 2}".to_string()),
+    /// ]);
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.set_module_loader(Some(Arc::new(modules)));
+    /// interpreter
+    ///     .exec("import {twice} from util\nx = twice(21)", "<test_code>", true)
+    ///     .unwrap();
+    /// assert_eq!(interpreter.get_global_as::<i64>("x").unwrap(), 42);
+    /// ```
+    pub fn set_module_loader(
+        &mut self,
+        loader: Option<Arc<dyn host::ModuleLoader>>,
+    ) -> Option<Arc<dyn host::ModuleLoader>> {
+        self.0.set_module_loader(loader)
+    }
+
+    /// Set hooks observing calls, writes of globals and errors, None for no hooks, return the
+    /// old ones
+    ///
+    /// Hooks are not copied by `fork_env` or `Program::spawn`. Execution is slower with hooks.
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use diatom::{ffi::{DiatomValue, EventHooks, State}, Interpreter};
+    ///
+    /// struct Writes(Arc<Mutex<Vec<String>>>);
+    /// impl EventHooks<Vec<u8>> for Writes {
+    ///     fn on_global_write(&mut self, _: &State<Vec<u8>>, name: &str, _: &DiatomValue) {
+    ///         self.0.lock().unwrap().push(name.to_string());
+    ///     }
+    /// }
+    ///
+    /// let writes = Arc::new(Mutex::new(vec![]));
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.set_hooks(Some(Box::new(Writes(writes.clone()))));
+    /// interpreter.exec("x = 1\ndef f = x = 2 end\nf()", "<test_code>", true).unwrap();
+    /// assert_eq!(*writes.lock().unwrap(), ["x", "f", "x"]);
+    /// ```
+    pub fn set_hooks(
+        &mut self,
+        hooks: Option<Box<dyn ffi::EventHooks<Buffer>>>,
+    ) -> Option<Box<dyn ffi::EventHooks<Buffer>>> {
+        self.0.set_hooks(hooks)
+    }
+
+    /// Grant a capability to external functions
+    ///
+    /// Some standard library functions (e.g. `std.os.process`) refuse to run unless the host has
+    /// explicitly granted the corresponding capability. See `SandboxPolicy::grant`.
+    pub fn enable_capability(&mut self, capability: impl Into<String>) -> &mut Self {
+        self.0.enable_capability(capability);
+        self
+    }
+
+    /// Revoke a previously granted capability
+    pub fn disable_capability(&mut self, capability: impl AsRef<str>) -> &mut Self {
+        self.0.disable_capability(capability);
+        self
+    }
+
+    /// Declare a table of external functions and values as variable
+    ///
+    /// Only the module name is declared so that host APIs do not pollute the global scope.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{ffi::DiatomValue, Interpreter};
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.add_module("math", |m| {
+    ///     m.value("version", 2_i64);
+    ///     m.func("double", |_, parameters, _| match parameters {
+    ///         [DiatomValue::Int(i)] => Ok(DiatomValue::Int(i * 2)),
+    ///         _ => Err("Expected an `Int`".to_string()),
+    ///     });
+    ///     m.module("v2", |m| {
+    ///         m.value("name", "math v2");
+    ///     });
+    /// });
+    /// interpreter
+    ///     .exec(
+    ///         "assert(math::double(math.version) == 4)\nassert(math.v2.name == 'math v2')",
+    ///         "<test_code>",
+    ///         true,
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn add_module(
+        &mut self,
+        name: impl Into<String>,
+        build: impl FnOnce(&mut ffi::ModuleBuilder<Buffer>),
+    ) {
+        self.0.add_module(name, build)
+    }
+
+    /// Declare a frozen table of named integer constants, e.g. variants of a rust enum
+    ///
+    /// Each variant becomes a field of the table. Function `name_of` looks up the name of a
+    /// value and returns an `Option`, e.g. `Color::name_of(1)`. Setting fields of the table is a
+    /// runtime error and a misspelled variant is reported as a missing key.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.add_enum("Color", &[("Red", 0), ("Green", 1)]);
+    /// interpreter
+    ///     .exec(
+    ///         "assert(Color.Green == 1)\nassert(Color::name_of(0).value == 'Red')",
+    ///         "<test_code>",
+    ///         true,
+    ///     )
+    ///     .unwrap();
+    /// assert!(interpreter.exec("Color.Blue", "<test_code>", true).is_err());
+    /// assert!(interpreter.exec("Color.Red = 2", "<test_code>", true).is_err());
+    /// ```
+    ///
+    /// # Panics
+    /// Panic if a variant is named `name_of`.
+    pub fn add_enum(&mut self, name: impl Into<String>, variants: &[(&str, i64)]) {
+        self.0.add_enum(name, variants)
+    }
+
+    /// Register methods and getters of a rust type used as user data
+    ///
+    /// Scripts can then use opaque handles created by `State::create_user_data` with method call
+    /// syntax.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{ffi::DiatomValue, Interpreter};
+    ///
+    /// struct Counter(i64);
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter
+    ///     .register_type::<Counter>()
+    ///     .method("add", |counter, _, parameters| match parameters {
+    ///         [DiatomValue::Int(i)] => {
+    ///             counter.0 += i;
+    ///             Ok(DiatomValue::Unit)
+    ///         }
+    ///         _ => Err("Expected an `Int`".to_string()),
+    ///     })
+    ///     .getter("value", |counter| counter.0);
+    /// interpreter.add_module("counter", |m| {
+    ///     m.func("new", |state, _, _| {
+    ///         Ok(DiatomValue::Ref(state.create_user_data(Box::new(Counter(0)))))
+    ///     });
+    /// });
+    /// interpreter
+    ///     .exec(
+    ///         "c = counter::new()\nc.add(3)\nc.add(4)\nassert(c.value == 7)",
+    ///         "<test_code>",
+    ///         true,
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn register_type<T: Any + Send>(&mut self) -> ffi::UserTypeBuilder<'_, T, Buffer> {
+        self.0.register_type()
+    }
+
+    /// Call a function stored in global variable `name`
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{ffi::DiatomValue, Interpreter};
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.exec("def add a b = a + b end", "<test_code>", true).unwrap();
+    /// let ret = interpreter.call("add", &[DiatomValue::Int(1), DiatomValue::Int(2)]);
+    /// assert!(matches!(ret, Ok(DiatomValue::Int(3))));
+    /// ```
+    ///
+    /// # Return
+    /// * Return value of the function. If it is a reference, it is only guaranteed to be valid
+    ///   until next call to the interpreter unless it is rooted by `root`.
+    /// * If `name` is not defined, parameters contain an invalid id or error occurs during
+    ///   execution, an `Err(String)` that illustrates the error is returned.
+    pub fn call(
+        &mut self,
+        name: impl AsRef<str>,
+        parameters: &[ffi::DiatomValue],
+    ) -> Result<ffi::DiatomValue, String> {
+        self.0.call(name, parameters)
+    }
+
+    /// Keep a value alive across calls to the interpreter while the returned handle is held
+    ///
+    /// Return None if value contains an invalid id.
+    pub fn root(&mut self, value: ffi::DiatomValue) -> Option<ffi::RootedValue> {
+        self.0.root(value)
+    }
+
+    /// Get a function stored in global variable `name` and keep it alive
+    ///
+    /// Return None if `name` is not defined or is neither a closure nor an external function.
+    pub fn get_function(&mut self, name: impl AsRef<str>) -> Option<ffi::FunctionHandle> {
+        self.0.get_function(name)
+    }
+
+    /// Call a function held by host
+    ///
+    /// Same as `call` except that function is given by a handle, which can be obtained by
+    /// `get_function` or `State::function_handle`.
+    pub fn call_function(
+        &mut self,
+        f: &ffi::FunctionHandle,
+        parameters: &[ffi::DiatomValue],
+    ) -> Result<ffi::DiatomValue, String> {
+        self.0.call_function(f, parameters)
+    }
+
+    /// Set global variable `name` to a value converted from rust
+    ///
+    /// The variable is declared if it does not exist.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.set_global("names", vec!["Alice", "Bob"]);
+    /// interpreter
+    ///     .exec("greeting = 'Hello, ' + names[1]", "<test_code>", true)
+    ///     .unwrap();
+    /// let greeting: String = interpreter.get_global_as("greeting").unwrap();
+    /// assert_eq!(greeting, "Hello, Bob");
+    /// ```
+    ///
+    /// # Panics
+    /// Panic if value is a `DiatomValue` that contains an invalid id.
+    pub fn set_global(&mut self, name: impl Into<String>, value: impl ffi::IntoDiatom) {
+        self.0.set_global(name, value)
+    }
+
+    /// Get value of global variable `name`
+    ///
+    /// Return None if `name` is not defined. If value is a reference, it is only guaranteed to be
+    /// valid until next call to the interpreter unless it is rooted by `root`.
+    pub fn get_global(&self, name: impl AsRef<str>) -> Option<ffi::DiatomValue> {
+        self.0.get_global(name)
+    }
+
+    /// Make the collection held by global variable `name` deeply immutable, as `freeze` does
+    ///
+    /// Useful for data handed to untrusted scripts, e.g. configurations shared by plugins.
+    /// Collections stay frozen in interpreters copied by `fork_env`. Return false if `name` is
+    /// not defined.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.exec("config = {plugins = ['log']}", "<config>", false).unwrap();
+    /// assert!(interpreter.freeze_global("config"));
+    /// assert!(interpreter.exec("config.plugins.append('evil')", "<plugin>", false).is_err());
+    /// ```
+    pub fn freeze_global(&mut self, name: impl AsRef<str>) -> bool {
+        self.0.freeze_global(name)
+    }
+
+    /// Names and kinds of all global variables, sorted by name
+    ///
+    /// Names imported from prelude are included.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{ffi::DiatomValueKind, Interpreter};
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter
+    ///     .exec("limit = 10\ndef clamp x = x end", "<test_code>", true)
+    ///     .unwrap();
+    /// let globals: Vec<_> = interpreter.globals().collect();
+    /// assert!(globals.contains(&("limit", DiatomValueKind::Int)));
+    /// assert!(globals.contains(&("clamp", DiatomValueKind::Function)));
+    /// assert!(globals.contains(&("print", DiatomValueKind::ExternFunction)));
+    /// ```
+    pub fn globals(&self) -> impl Iterator<Item = (&str, ffi::DiatomValueKind)> {
+        self.0.globals()
+    }
+
+    /// Metadata of function stored in global variable `name`
+    ///
+    /// Return None if `name` is not defined or is not a function defined in script.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter
+    ///     .exec("def clamp x low high =\n  x\nend", "<test_code>", true)
+    ///     .unwrap();
+    /// let info = interpreter.function_info("clamp").unwrap();
+    /// assert_eq!(info.name.as_deref(), Some("clamp"));
+    /// assert_eq!(info.arity, 3);
+    /// assert_eq!(info.parameters, vec!["x", "low", "high"]);
+    /// assert_eq!(info.location.unwrap().line, 1);
+    /// ```
+    pub fn function_info(&self, name: impl AsRef<str>) -> Option<ffi::FunctionInfo> {
+        self.0.function_info(name)
+    }
+
+    /// Counters of work done since the interpreter is created or `reset_stats` is called
+    ///
+    /// Counters accumulate over calls, so that the cost of a script can be measured by
+    /// resetting them before running it. Heap sizes are those of now.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.exec("l = [1, 2, 3]", "<test_code>", true).unwrap();
+    /// assert!(interpreter.stats().instructions > 0);
+    /// interpreter.reset_stats();
+    /// interpreter
+    ///     .exec("for i in 0..100 do l = [i] end", "<test_code>", true)
+    ///     .unwrap();
+    /// assert!(interpreter.stats().allocations >= 100);
+    /// ```
+    pub fn stats(&self) -> ffi::Stats {
+        self.0.stats()
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.0.reset_stats()
+    }
+
+    /// Collect garbage before every allocation and verify the heap after each collection
+    ///
+    /// Very slow, meant for testing external functions that hold values, which panic once a
+    /// value they use is collected instead of reading a reused slot.
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.0.set_gc_stress(stress)
+    }
+
+    /// Get value of global variable `name` and convert it into a rust value
+    ///
+    /// Return `Err` if `name` is not defined or value can not be converted.
+    pub fn get_global_as<T: ffi::FromDiatom>(
+        &mut self,
+        name: impl AsRef<str>,
+    ) -> Result<T, String> {
+        self.0.get_global_as(name)
+    }
+
+    /// Convert plain data into a diatom value and keep it alive while the handle is held
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{ffi::DiatomData, Interpreter};
+    ///
+    /// let mut interpreter = Interpreter::new(std::io::stdout());
+    /// let config = DiatomData::Table([
+    ///     ("name".to_string(), DiatomData::Str("diatom".to_string())),
+    ///     ("retry".to_string(), DiatomData::Int(3)),
+    /// ].into_iter().collect());
+    /// let config = interpreter.to_value(config);
+    /// interpreter.set_global("config", config.value());
+    /// interpreter.exec("config.retry = config.retry + 1", "<test>", true).unwrap();
+    ///
+    /// let config = interpreter.get_global("config").unwrap();
+    /// let config = interpreter.from_value(&config).unwrap();
+    /// match config {
+    ///     DiatomData::Table(t) => assert_eq!(t["retry"], DiatomData::Int(4)),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn to_value(&mut self, data: ffi::DiatomData) -> ffi::RootedValue {
+        self.0.to_value(data)
+    }
+
+    /// Convert a diatom value into plain data
+    ///
+    /// Return `Err` if value contains functions, user data or itself.
+    pub fn from_value(&mut self, value: &ffi::DiatomValue) -> Result<ffi::DiatomData, String> {
+        self.0.from_value(value)
+    }
+
+    /// Directly declare external function as variable
+    pub fn impl_extern_function<F>(&mut self, name: impl Into<String>, f: F)
+    where
+        F: Fn(
+                &mut ffi::State<Buffer>,
+                &[ffi::DiatomValue],
+                &mut Buffer,
+            ) -> Result<ffi::DiatomValue, String>
+            + 'static
+            + Send
+            + Sync,
+    {
+        self.0.impl_extern_function(name, f)
+    }
+
+    /// Directly declare external function that has access to host state of type `T`
+    ///
+    /// The state is set by `set_context`. Calling the function fails if the state is not set.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{ffi::DiatomValue, Interpreter};
+    ///
+    /// struct Log(Vec<String>);
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.set_context(Log(vec![]));
+    /// interpreter.impl_extern_function_with_state("log", |log: &mut Log, state, parameters, _| {
+    ///     match parameters {
+    ///         [DiatomValue::Str(sid)] => {
+    ///             log.0.push(state.get_string_by_id(*sid).unwrap().to_string());
+    ///             Ok(DiatomValue::Unit)
+    ///         }
+    ///         _ => Err("Expected a `String`".to_string()),
+    ///     }
+    /// });
+    /// interpreter.exec("log('started')", "<test_code>", true).unwrap();
+    /// assert_eq!(interpreter.context::<Log>().unwrap().0, vec!["started"]);
+    /// ```
+    pub fn impl_extern_function_with_state<T, F>(&mut self, name: impl Into<String>, f: F)
+    where
+        T: Any + Send,
+        F: Fn(
+                &mut T,
+                &mut ffi::State<Buffer>,
+                &[ffi::DiatomValue],
+                &mut Buffer,
+            ) -> Result<ffi::DiatomValue, String>
+            + 'static
+            + Send
+            + Sync,
+    {
+        self.0.impl_extern_function_with_state(name, f)
+    }
+
+    /// Set host state of type `T` and return the old one
+    ///
+    /// There is at most one state for each type.
+    pub fn set_context<T: Any + Send>(&mut self, context: T) -> Option<T> {
+        self.0.set_context(context)
+    }
+
+    /// Get host state of type `T`
+    pub fn context<T: Any + Send>(&self) -> Option<&T> {
+        self.0.context()
+    }
+
+    /// Get host state of type `T` mutably
+    pub fn context_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.0.context_mut()
+    }
+
+    /// Remove host state of type `T` from the interpreter
+    pub fn take_context<T: Any + Send>(&mut self) -> Option<T> {
+        self.0.take_context()
+    }
+
     /// Check if input is completeness
     ///
     /// Incomplete input usually contains unclosed parentheses, quotes or open expression.
@@ -148,6 +1052,26 @@ impl<Buffer: IoWrite> Interpreter<Buffer> {
         self.0.replace_buffer(buffer)
     }
 
+    /// Replace input source and get the old one
+    ///
+    /// Standard input is used by default.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.replace_input(Box::new("Alice\n".as_bytes()));
+    /// interpreter
+    ///     .exec("name = input('Name? ').value", "<test_code>", true)
+    ///     .unwrap();
+    /// let name: String = interpreter.get_global_as("name").unwrap();
+    /// assert_eq!(name, "Alice");
+    /// ```
+    pub fn replace_input(&mut self, input: Box<dyn IoRead + Send>) -> Box<dyn IoRead + Send> {
+        self.0.replace_input(input)
+    }
+
     /// Run a piece of diatom source code
     ///
     /// # Parameters
@@ -168,37 +1092,428 @@ impl<Buffer: IoWrite> Interpreter<Buffer> {
         self.0.exec(code, source, is_phony)
     }
 
-    /// Show decompiled byte code for given source code.
+    /// Run a piece of diatom source code and return the value of its last expression
     ///
-    /// If compilation failed, `Err` will be returned.
-    pub fn decompile(
+    /// Source is a place holder like `exec` with `is_phony`. Unit is returned if code does not
+    /// end with an expression. If the value is a reference, it is only guaranteed to be valid
+    /// until next call to the interpreter unless it is rooted by `root`.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{ffi::DiatomData, Interpreter};
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// let value = interpreter.eval("x = 20\nx * 2 + 2", "<test_code>").unwrap();
+    /// let data = interpreter.from_value(&value).unwrap();
+    /// assert_eq!(data, DiatomData::Int(42));
+    /// ```
+    pub fn eval(
         &mut self,
         code: impl AsRef<str>,
         source: impl AsRef<OsStr>,
-        is_phony: bool,
-    ) -> Result<String, String> {
-        self.0.decompile(code, source, is_phony)
+    ) -> Result<ffi::DiatomValue, String> {
+        self.0.eval(code, source)
     }
 
-    /// Load an rust extension.
+    /// Run diatom source code read from `reader` piece by piece
     ///
-    /// Return the extension if its namespace is already occupied
-    pub fn load_ext(
+    /// Code is executed in pieces of about 64 KiB cut between statements, so that a large
+    /// generated script is never wholly in memory. Diagnostics report lines of the whole source
+    /// named `source`. Execution stops at the first error, code before it has been executed.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// let data = (0..10000).map(|i| format!("x = {i}\n")).collect::<String>();
+    /// interpreter.exec_reader(data.as_bytes(), "data.dm").unwrap();
+    /// assert_eq!(interpreter.get_global_as::<i64>("x"), Ok(9999));
+    /// ```
+    pub fn exec_reader(
         &mut self,
-        extension: extension::Extension<Buffer>,
-    ) -> Result<(), extension::Extension<Buffer>> {
-        self.0.load_ext(extension)
+        reader: impl io::Read,
+        source: impl AsRef<OsStr>,
+    ) -> Result<(), String> {
+        self.0.exec_reader(reader, source)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use std::{fs, path::PathBuf};
-
-    use crate::Interpreter;
 
-    #[test]
+    /// Structured form of the last `Err` returned by the interpreter
+    ///
+    /// Errors reported by `State::raise` keep their code and payload. Other errors carry the
+    /// diagnostic code if there is one.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{ffi::DiatomData, Interpreter};
+    ///
+    /// let mut interpreter = Interpreter::new(std::io::stdout());
+    /// interpreter.impl_extern_function("withdraw", |state, _, _| {
+    ///     Err(state.raise("insufficient_funds", "Balance is too low", DiatomData::Int(42)))
+    /// });
+    /// assert!(interpreter.exec("withdraw()", "<test>", true).is_err());
+    ///
+    /// let error = interpreter.last_error().unwrap();
+    /// assert_eq!(error.code.as_deref(), Some("insufficient_funds"));
+    /// assert_eq!(error.payload, Some(DiatomData::Int(42)));
+    /// ```
+    pub fn last_error(&self) -> Option<&ffi::ScriptError> {
+        self.0.last_error()
+    }
+
+    /// Resources used by the last execution of code, by `exec`, `run`, `call` or `call_function`
+    ///
+    /// Unlike `stats`, counters are of one execution only, so that hosts can bill or limit each
+    /// script run. Code read by `exec_reader` is executed piece by piece, the report is of the
+    /// last piece.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter
+    ///     .exec("l = []\nfor i in 0..100 do l.append(i) end", "<test_code>", true)
+    ///     .unwrap();
+    /// let report = interpreter.last_report().unwrap();
+    /// assert!(report.instructions > 100);
+    /// assert!(report.peak_gc_objects > 0);
+    /// ```
+    pub fn last_report(&self) -> Option<&ffi::ExecutionReport> {
+        self.0.last_report()
+    }
+
+    /// Compile a piece of diatom source code into a program without running it
+    ///
+    /// The program is a snapshot of this interpreter, including globals, loaded extensions and
+    /// byte code. Interpreters spawned from it are independent of each other and share the
+    /// compiled byte code, which avoids loading the standard library and parsing the source for
+    /// each of them.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(std::io::stdout());
+    /// interpreter.set_global("input", 0);
+    /// let program = interpreter
+    ///     .compile_program("output = input * input", "<program>", true)
+    ///     .unwrap();
+    ///
+    /// for i in 0..10 {
+    ///     let mut context = program.spawn(std::io::stdout());
+    ///     context.set_global("input", i);
+    ///     context.run().unwrap();
+    ///     assert_eq!(context.get_global_as::<i64>("output"), Ok(i64::from(i * i)));
+    /// }
+    /// ```
+    pub fn compile_program(
+        &mut self,
+        code: impl AsRef<str>,
+        source: impl AsRef<OsStr>,
+        is_phony: bool,
+    ) -> Result<Program<Buffer>, String> {
+        self.0.compile_program(code, source, is_phony).map(Program)
+    }
+
+    /// Copy the interpreter with its globals, so that code run by the copy never changes them
+    ///
+    /// Byte code and pages of the heap are shared until either interpreter writes to them, thus
+    /// each request can run in a pristine environment. Host states set by `set_context` and input
+    /// source are not copied, user data is copied only if its type is registered with
+    /// `ffi::UserTypeBuilder::cloneable`.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let templates = "visits = 0\ndef render name = visits = visits + 1; 'Hi ' + name end";
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter.exec(templates, "<templates>", true).unwrap();
+    ///
+    /// for name in ["Alice", "Bob"] {
+    ///     let mut env = interpreter.fork_env(vec![]);
+    ///     env.exec(format!("page = render('{name}')"), "<request>", true)
+    ///         .unwrap();
+    ///     assert_eq!(env.get_global_as::<String>("page"), Ok(format!("Hi {name}")));
+    ///     assert_eq!(env.get_global_as::<i64>("visits"), Ok(1));
+    /// }
+    /// assert_eq!(interpreter.get_global_as::<i64>("visits"), Ok(0));
+    /// ```
+    pub fn fork_env(&self, buffer: Buffer) -> Self {
+        Self(self.0.fork_env(buffer))
+    }
+
+    /// Reload function definitions from a changed file while keeping global state
+    ///
+    /// Only top level `def` statements are executed, thus globals assigned elsewhere keep their
+    /// current values. Nothing changes if the file fails to compile. `on_change` is called with
+    /// names of functions whose definitions are new or changed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(std::io::stdout());
+    /// let code = std::fs::read_to_string("game.dm").unwrap();
+    /// interpreter.exec(code, "game.dm", false).unwrap();
+    /// // `game.dm` is edited
+    /// interpreter
+    ///     .reload("game.dm", |changed| println!("Reloaded: {}", changed.join(", ")))
+    ///     .unwrap();
+    /// ```
+    pub fn reload(
+        &mut self,
+        path: impl AsRef<Path>,
+        on_change: impl FnOnce(&[String]),
+    ) -> Result<(), String> {
+        self.0.reload(path, on_change)
+    }
+
+    /// Run the last compiled code again
+    ///
+    /// Mostly used with interpreters spawned by `Program::spawn`. Globals keep the values left
+    /// by the previous run unless they are assigned again by the code.
+    pub fn run(&mut self) -> Result<(), String> {
+        self.0.run()
+    }
+
+    /// Compile code and report diagnostics without executing it
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// assert!(interpreter.check("x = 1", "<test_code>", true).is_ok());
+    /// assert!(interpreter.check("y = undefined_name", "<test_code>", true).is_err());
+    /// ```
+    pub fn check(
+        &mut self,
+        code: impl AsRef<str>,
+        source: impl AsRef<OsStr>,
+        is_phony: bool,
+    ) -> Result<(), String> {
+        self.0.check(code, source, is_phony)
+    }
+
+    /// Check code against lint rules without executing it
+    ///
+    /// Imported modules are neither resolved nor checked. Return rendered warnings, which is empty
+    /// if there is none. If code has syntax errors or a rule set to `LintLevel::Deny` is
+    /// violated, all diagnostics are returned as error.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{
+    ///     lint::{Lint, LintConfig, LintLevel},
+    ///     Interpreter,
+    /// };
+    ///
+    /// let interpreter = Interpreter::new(vec![]);
+    /// let code = "def f x =\n    y = x\n    x\nend";
+    /// let warnings = interpreter.lint(code, "<test_code>", &LintConfig::default()).unwrap();
+    /// assert!(warnings.contains("Variable `y` is never used"));
+    ///
+    /// let config = LintConfig::default().set(Lint::UnusedVariable, LintLevel::Deny);
+    /// assert!(interpreter.lint(code, "<test_code>", &config).is_err());
+    /// ```
+    pub fn lint(
+        &self,
+        code: impl AsRef<str>,
+        source: impl AsRef<OsStr>,
+        config: &LintConfig,
+    ) -> Result<String, String> {
+        self.0.lint(code, source, config)
+    }
+
+    /// Parse files and modules they import without compiling them
+    ///
+    /// Files are parsed in parallel with feature `parallel`. Return rendered warnings, which is
+    /// empty if there is none. If any file can not be read or has a syntax error, the error is
+    /// returned instead.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::Interpreter;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("diatom_parse_files_{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("a.dm"), "x = 1").unwrap();
+    /// std::fs::write(dir.join("b.dm"), "y = (").unwrap();
+    ///
+    /// let interpreter = Interpreter::new(vec![]);
+    /// assert!(interpreter.parse_files(&[dir.join("a.dm")]).is_ok());
+    /// assert!(interpreter
+    ///     .parse_files(&[dir.join("a.dm"), dir.join("b.dm")])
+    ///     .is_err());
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn parse_files(&self, paths: &[PathBuf]) -> Result<String, String> {
+        self.0.parse_files(paths)
+    }
+
+    /// Pack source of code and files it imports into a bundle run by `exec_bundle`
+    ///
+    /// The bundle is compiled by this interpreter as `check` does, reading files from the bundle,
+    /// thus compile errors such as unresolved names are reported when the bundle is made instead
+    /// of when it is run. Modules of extensions, e.g. the standard library, are not bundled.
+    ///
+    /// # Example
+    /// ```
+    /// use diatom::{bundle::Bundle, Interpreter};
+    ///
+    /// let dir = std::env::temp_dir().join(format!("diatom-bundle-doc-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("greeting.dm"), "{text = 'hello'}").unwrap();
+    /// let main = dir.join("main.dm");
+    /// let code = "import {text} from greeting\nprint(text)";
+    ///
+    /// let bytes = Interpreter::new(vec![]).bundle(code, &main).unwrap().to_bytes();
+    /// std::fs::remove_dir_all(&dir).unwrap();
+    ///
+    /// let mut interpreter = Interpreter::new(vec![]);
+    /// interpreter
+    ///     .exec_bundle(&Bundle::from_bytes(&bytes).unwrap())
+    ///     .unwrap();
+    /// assert_eq!(interpreter.replace_buffer(vec![]), b"hello");
+    /// ```
+    pub fn bundle(
+        &mut self,
+        code: impl AsRef<str>,
+        source: impl AsRef<OsStr>,
+    ) -> Result<bundle::Bundle, String> {
+        self.0.bundle(code, source)
+    }
+
+    /// Run a bundle made by `bundle`
+    ///
+    /// The bundle is compiled as a script named by its main path. Its imports are resolved with
+    /// search paths and packages of the bundle, reading files from the bundle instead of the file
+    /// system.
+    pub fn exec_bundle(&mut self, bundle: &bundle::Bundle) -> Result<(), String> {
+        self.0.exec_bundle(bundle)
+    }
+
+    /// Show decompiled byte code for given source code.
+    ///
+    /// If compilation failed, `Err` will be returned.
+    pub fn decompile(
+        &mut self,
+        code: impl AsRef<str>,
+        source: impl AsRef<OsStr>,
+        is_phony: bool,
+    ) -> Result<String, String> {
+        self.0.decompile(code, source, is_phony)
+    }
+
+    /// Load an rust extension.
+    ///
+    /// Return the extension if its namespace is already occupied
+    pub fn load_ext(
+        &mut self,
+        extension: extension::Extension<Buffer>,
+    ) -> Result<(), extension::Extension<Buffer>> {
+        self.0.load_ext(extension)
+    }
+}
+
+/// Compiled code that can be executed by many independent interpreters
+///
+/// Created by `Interpreter::compile_program`. Host states set by `Interpreter::set_context` and
+/// input source are not part of a program, user data is handled as by `Interpreter::fork_env`.
+/// Clone the program to use it in another thread.
+pub struct Program<Buffer: IoWrite>(__Program<Buffer, StdLibCore>);
+
+impl<Buffer: IoWrite> Clone for Program<Buffer> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<Buffer: IoWrite> Program<Buffer> {
+    /// Create an interpreter ready to run this program with `Interpreter::run`
+    pub fn spawn(&self, buffer: Buffer) -> Interpreter<Buffer> {
+        Interpreter(self.0.spawn(buffer))
+    }
+}
+
+/// Source code embedded in host by `script!` or `include_script!`
+///
+/// Syntax of the script is checked when host is compiled, with the default keywords. Other
+/// errors, e.g. undefined variables, depend on globals and extensions of an interpreter and are
+/// reported when the script is compiled by it.
+///
+/// # Example
+/// ```
+/// use diatom::{script, Interpreter, EmbeddedScript};
+///
+/// const SQUARE: EmbeddedScript = script!("output = input * input");
+///
+/// let mut interpreter = Interpreter::new(std::io::stdout());
+/// interpreter.set_global("input", 0);
+/// let program = SQUARE.compile(&mut interpreter).unwrap();
+/// let mut context = program.spawn(std::io::stdout());
+/// context.set_global("input", 12);
+/// context.run().unwrap();
+/// assert_eq!(context.get_global_as::<i64>("output"), Ok(144));
+/// ```
+///
+/// A syntax error fails the build of host:
+/// ```compile_fail
+/// let script = diatom::script!("x = (1 + ");
+/// ```
+#[cfg(feature = "macros")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmbeddedScript {
+    code: &'static str,
+    source: &'static str,
+}
+
+#[cfg(feature = "macros")]
+impl EmbeddedScript {
+    #[doc(hidden)]
+    pub const fn __new(code: &'static str, source: &'static str) -> Self {
+        Self { code, source }
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Name of the script shown in error messages, location of `script!` or path given to
+    /// `include_script!`
+    pub fn source(&self) -> &'static str {
+        self.source
+    }
+
+    /// Compile this script into a program, see `Interpreter::compile_program`
+    pub fn compile<Buffer: IoWrite>(
+        &self,
+        interpreter: &mut Interpreter<Buffer>,
+    ) -> Result<Program<Buffer>, String> {
+        interpreter.compile_program(self.code, self.source, true)
+    }
+
+    /// Execute this script once, see `Interpreter::exec`
+    pub fn exec<Buffer: IoWrite>(
+        &self,
+        interpreter: &mut Interpreter<Buffer>,
+    ) -> Result<(), String> {
+        interpreter.exec(self.code, self.source, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Interpreter;
+
+    // Examples use `std.os.time`
+    #[cfg(feature = "std-time")]
+    #[test]
     fn test_examples() {
+        use std::{fs, path::PathBuf};
+
         let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         let mut path = path.parent().unwrap().to_path_buf();
         path.push("examples");
@@ -231,6 +1546,518 @@ mod tests {
             .expect("Test failed");
     }
 
+    #[test]
+    fn test_input() {
+        let code = r#"
+            name = input("Name? ")
+            assert(name.value == "Alice")
+            assert(input().value == "")
+            assert(input() is Option::None)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter.replace_input(Box::new("Alice\r\n\n".as_bytes()));
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(String::from_utf8(output).unwrap(), "Name? ");
+    }
+
+    #[cfg(all(feature = "std-process", unix))]
+    #[test]
+    fn test_process_capability() {
+        let code = r#"
+            import Process from std.os.process
+            result = Process::run("sh", ["-c", "read x; echo $x $FOO; exit 3"], {stdin = "hi", env = {FOO = "bar"}})
+            assert(result.code == 3)
+            assert(result.stdout == "hi bar\n")
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        assert!(interpreter.exec(code, "test", true).is_err());
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter.enable_capability(diatom_std_os::PROCESS_CAPABILITY);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+    }
+
+    #[cfg(feature = "std-io")]
+    #[test]
+    fn test_fs_capability() {
+        use crate::{FsPolicy, SandboxPolicy};
+
+        let dir = std::env::temp_dir().join(format!("diatom_fs_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let code = format!(
+            r#"
+            import {{read, write, append, exists, list_dir, remove}} from std.os.fs
+            path = '{}/a.txt'
+            write(path, 'hello')
+            append(path, ' world')
+            assert(read(path) == 'hello world')
+            assert(exists(path))
+            assert(list_dir('{}')[0] == 'a.txt')
+            remove(path)
+            assert(not exists(path))
+            "#,
+            dir.display(),
+            dir.display()
+        );
+        let mut interpreter = Interpreter::new(vec![]);
+        let err = interpreter.exec(&code, "test", true).unwrap_err();
+        assert!(
+            err.contains("Capability `fs` is not granted by host"),
+            "{err}"
+        );
+
+        let mut interpreter = Interpreter::new(vec![]);
+        let mut policy = SandboxPolicy::new();
+        policy.fs = FsPolicy::ReadOnly;
+        interpreter.set_policy(policy);
+        let err = interpreter.exec(&code, "test", true).unwrap_err();
+        assert!(
+            err.contains("Writing files is not allowed by host"),
+            "{err}"
+        );
+
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter.enable_capability(diatom_std_os::FS_CAPABILITY);
+        interpreter
+            .exec(&code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "std-net")]
+    #[test]
+    fn test_net_capability() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            stream.read_to_string(&mut request).unwrap();
+            stream
+                .write_all(format!("echo {request}").as_bytes())
+                .unwrap();
+        });
+        let code = format!(
+            r#"
+            import {{resolve, request}} from std.os.net
+            assert(resolve('127.0.0.1')[0] == '127.0.0.1')
+            assert(request('{addr}', 'ping') == 'echo ping')
+            "#
+        );
+        let mut interpreter = Interpreter::new(vec![]);
+        let err = interpreter.exec(&code, "test", true).unwrap_err();
+        assert!(
+            err.contains("Capability `net` is not granted by host"),
+            "{err}"
+        );
+
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter.enable_capability(diatom_std_os::NET_CAPABILITY);
+        interpreter
+            .exec(&code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_range() {
+        let code = r#"
+            r = 1..10
+            println(r.collect())
+            println(r.step(2).collect())
+            println(r.step(3).rev().collect())
+            println(r.rev().step(4).collect())
+            println((10..1).rev().collect())
+            println(((Int::MAX - 3)..).step(2).collect())
+            assert(r.sum() == 45)
+            assert(len(r) == 9)
+            assert(r.contains(5))
+            assert(r.contains(9.0))
+            assert(not r.contains(10))
+            assert(not r.step(2).contains(4))
+            assert(len(10..1) == 0)
+            assert(len(r.step(-1)) == 0)
+            assert(len(Int::MIN..Int::MAX) == Int::MAX)
+            n = 0
+            for i in r.rev() do
+                n = n + i
+            end
+            assert(n == 45)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[1, 2, 3, 4, 5, 6, 7, 8, 9]\n\
+             [1, 3, 5, 7, 9]\n\
+             [7, 4, 1]\n\
+             [9, 5, 1]\n\
+             []\n\
+             [9223372036854775804, 9223372036854775806]\n"
+        );
+        assert!(interpreter.exec("(1..3).step(0)", "test", true).is_err());
+    }
+
+    #[test]
+    fn test_membership() {
+        let code = r#"
+            primes = [2, 3, 5, 7]
+            for n in 1..8 do
+                if n in primes then print(n, '') end
+            end
+            println()
+            println(primes.iter().filter(_ not in [2, 5]).collect())
+            assert('a' in {a = 1})
+            assert('x' not in {})
+            assert(primes is primes)
+            assert(not ([] is []))
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(String::from_utf8(output).unwrap(), "2 3 5 7 \n[3, 7]\n");
+    }
+
+    #[test]
+    fn test_freeze() {
+        let code = r#"
+            config = freeze({name = 'app', ports = [80, 443], limits = (1, 2)})
+            assert(is_frozen(config) and is_frozen(config.ports) and is_frozen(config.limits))
+            assert(not is_frozen([]) and not is_frozen(1))
+            copy = [config.ports...]
+            copy.append(8080)
+            println(copy)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(String::from_utf8(output).unwrap(), "[80, 443, 8080]\n");
+
+        for (code, error) in [
+            ("config.name = 'x'", "E3024"),
+            ("config.ports[0] = 1", "E3028"),
+            ("config.ports.append(1)", "E3028"),
+            ("config.limits.0 = 3", "E3028"),
+        ] {
+            let mut env = interpreter.fork_env(vec![]);
+            let err = env.exec(code, "test", true).unwrap_err();
+            assert!(err.contains(error), "{err}");
+            assert!(err.contains("Frozen here"), "{err}");
+        }
+
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter.set_global("plugins", vec!["log".to_string()]);
+        assert!(interpreter.freeze_global("plugins"));
+        assert!(!interpreter.freeze_global("missing"));
+        assert!(interpreter.exec("plugins.clear()", "test", true).is_err());
+        assert!(interpreter.exec("freeze(1)", "test", true).is_err());
+    }
+
+    #[test]
+    fn test_gc_stress() {
+        let code = r#"
+            config = freeze({ports = [80, 443], name = 'app'})
+            squares = (0..20).map(fn x = x * x).filter(fn x = x.is_even()).collect()
+            ch = channel()
+            ch.send(share(config))
+            ports = ch.recv().value.get('ports').copy()
+            ports.append(squares[-1])
+            println(ports, str(len(squares)))
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter.set_gc_stress(true);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(String::from_utf8(output).unwrap(), "[80, 443, 324] 10\n");
+    }
+
+    #[test]
+    fn test_number_methods() {
+        let code = r#"
+            println((255).to_hex(), (-255).to_hex(), (8).to_oct(), (5).to_bin())
+            n = -7
+            assert(n.abs() == 7 and n.sign() == -1 and n.is_odd())
+            assert((2).pow(10) == 1024 and (15).clamp(0, 10) == 10 and (3).min(4) == 3)
+            assert((3.7).floor() > 2.9 and (3.7).floor() < 3.1)
+            assert((-3.7).trunc() > -3.1 and (2.5).fract() > 0.4 and (2.5).fract() < 0.6)
+            assert(Float::NAN.is_nan() and not (1.0).is_nan())
+            assert(not Float::INF.is_finite() and (1.5).clamp(0.0, 1.0) > 0.9)
+            assert(Int::to_hex(16) == '10')
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(String::from_utf8(output).unwrap(), "ff -ff 10 101\n");
+        assert!(interpreter.exec("(2).pow(-1)", "test", true).is_err());
+        assert!(interpreter.exec("(1).clamp(2, 0)", "test", true).is_err());
+    }
+
+    #[test]
+    fn test_bool() {
+        let code = r#"
+            falsy = [false, (), 0, 0.0, Float::NAN, '', [], (), None]
+            truthy = [true, 1, -0.5, 'a', [0], {}, Some(false), print]
+            assert(falsy.iter().all(fn x = not bool(x)))
+            assert(truthy.iter().all(bool))
+            xs = []
+            if not bool(xs) then println('empty') end
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(String::from_utf8(output).unwrap(), "empty\n");
+        assert!(interpreter.exec("if [] then 1 end", "test", true).is_err());
+    }
+
+    #[test]
+    fn test_float_format() {
+        let code = r#"
+            println(0.1 + 0.2, 1.0, -2.5, 1e100, 1.5e-7)
+            inf = float('1e999').unwrap()
+            println(inf, -inf, float('-1e-999').unwrap())
+            x = 0.1 + 0.2
+            assert(not (float(str(x)).unwrap() < x or float(str(x)).unwrap() > x))
+            assert(str(3.0) == '3.0')
+            write(Int::MIN)
+            write_line((false, ()))
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "0.30000000000000004 1.0 -2.5 1e100 1.5e-7\ninf -inf -0.0\n-9223372036854775808(false, ())\n"
+        );
+    }
+
+    #[test]
+    fn test_runtime_warning() {
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec("x = float('1e999').unwrap()", "test", true)
+            .unwrap();
+        assert_eq!(interpreter.warning_count(), 1);
+        let mut diagnostics = vec![];
+        interpreter.emit_diagnostics(&mut diagnostics).unwrap();
+        let diagnostics = String::from_utf8(diagnostics).unwrap();
+        assert!(diagnostics.contains("W3000"), "{diagnostics}");
+        assert!(diagnostics.contains("`1e999`"), "{diagnostics}");
+
+        interpreter
+            .exec(
+                "x = float('-inf').unwrap() + float('1.5').unwrap()",
+                "test",
+                true,
+            )
+            .unwrap();
+        assert_eq!(interpreter.warning_count(), 0);
+
+        interpreter
+            .set_warning_options(crate::WarningOptions::default().suppress("float-overflow"));
+        interpreter
+            .exec("x = float('1e999').unwrap()", "test", true)
+            .unwrap();
+        assert_eq!(interpreter.warning_count(), 0);
+    }
+
+    #[test]
+    fn test_print_values() {
+        let code = "def add x y = x + y end\nt = {f = fn x = x}\na = [1]\n\
+            println(add, t.f, fn = ())\nprintln([a, a], {x = a, y = a})\n\
+            a.append(a)\nprintln(a)";
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = String::from_utf8(interpreter.replace_buffer(vec![])).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "<fn add at test:1:1> <bound fn f at test:2:10> <fn at test:4:19>"
+        );
+        assert_eq!(lines[1], "[[1], [1]] {x = [1], y = [1]}");
+        assert!(lines[2].starts_with("[1, <Recursive ref@"), "{output}");
+
+        let code = "enum Shape Circle(r), Rect(w, h), Empty end\n\
+            println(Some(1), None, Ok([Err('no')]))\n\
+            println([Shape::Rect(1, 'a'), Shape::Circle(2), Shape.Empty])\n\
+            assert(Some(1) is Some and Ok(1).is_ok() and not (None is Some))";
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = String::from_utf8(interpreter.replace_buffer(vec![])).unwrap();
+        assert_eq!(
+            output,
+            "Some(1) None Ok([Err(no)])\n[Shape::Rect(w = 1, h = a), Shape::Circle(r = 2), Shape::Empty]\n"
+        );
+    }
+
+    #[test]
+    fn test_constructor() {
+        let code = r#"
+            Port = {
+                __init = fn self n =
+                    if n < 0 then
+                        Err('negative port ' + str(n))
+                    else
+                        self.n = n
+                        Ok(())
+                    end
+            }
+            assert(Port(80).unwrap().n == 80)
+            err = Port(-1)
+            assert(err.is_err())
+            println(err.error)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(String::from_utf8(output).unwrap(), "negative port -1\n");
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let code = r#"
+            xs = [1, 5, 2, 7]
+            println(xs |> Iter::filter(_ > 3) |> Iter::map(_ * 2) |> Iter::collect)
+            println(xs.iter().map(-_).filter(_ < -1).collect())
+            println((1..4) |> Iter::map((_, _ ** 2)) |> Iter::collect)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[10, 14]\n[-5, -2, -7]\n[(1, 1.0), (2, 4.0), (3, 9.0)]\n"
+        );
+    }
+
+    #[test]
+    fn test_defer() {
+        let code = r#"
+            log = []
+            def f x =
+                defer log.append("first")
+                for i in 1..3 do
+                    defer log.append("loop")
+                end
+                defer begin
+                    log.append("last")
+                end
+                if x then return log.len() end
+                assert(false)
+            end
+            println(f(true))
+            println(log)
+            log = []
+            def g =
+                defer [][0]
+                defer println(log)
+                f(false)
+            end
+            g()
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        // The first error is reported, even if a deferred closure fails as well
+        let err = interpreter.exec(code, "test", true).unwrap_err();
+        assert!(err.contains("Assert failed"), "{err}");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "0\n[last, loop, loop, first]\n[last, loop, loop, first]\n"
+        );
+        assert!(interpreter.exec("defer println(1)", "test", true).is_err());
+
+        // Loop variables are copied when deferred
+        let code = r#"
+            def g =
+                for i in 0..3 do defer println("loop", i) end
+                println("after")
+            end
+            g()
+        "#;
+        interpreter.exec(code, "test", true).unwrap();
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "after\nloop 2\nloop 1\nloop 0\n"
+        );
+    }
+
+    #[test]
+    fn test_strict_std() {
+        let code = r#"
+            import std.math
+            import std.functools
+            import {case} from std.test
+            import {run} from std.bench
+            total = 0
+            for i in 1..4 do
+                total = total + i
+            end
+            assert(total == 6)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter.set_strict(true);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+    }
+
+    #[test]
+    fn test_functools_arity() {
+        let mut interpreter = Interpreter::new(vec![]);
+        let err = interpreter
+            .exec(
+                "import partial from std.functools\ndef f a b c d e f g = a end\npartial(f, [1])",
+                "test",
+                true,
+            )
+            .unwrap_err();
+        assert!(err.contains("more than 6 parameters"), "{err}");
+    }
+
     #[test]
     fn test_for_macro() {
         let mut interpreter = Interpreter::new(vec![]);
@@ -239,4 +2066,533 @@ mod tests {
             .map_err(|err| println!("{err}"))
             .expect("Test failed");
     }
+
+    #[test]
+    fn test_script_pool() {
+        use crate::{
+            ffi::{DiatomData, DiatomValue},
+            pool::{Job, JobError, ScriptPool},
+        };
+
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter.impl_extern_function("boom", |_, parameters, _| match parameters {
+            [DiatomValue::Int(0)] => panic!("boom"),
+            _ => Ok(DiatomValue::Unit),
+        });
+        interpreter.set_global("x", 0);
+        let program = interpreter
+            .compile_program("boom(x)\nprint(x)\ny = [x, x + 1]", "test", true)
+            .unwrap();
+
+        let pool = ScriptPool::new(2);
+        let results: Vec<_> = (0..3)
+            .map(|x| {
+                pool.submit(
+                    Job::program(program.clone())
+                        .input("x", DiatomData::Int(x))
+                        .output("y"),
+                )
+            })
+            .collect();
+        let mut results = results.into_iter().map(|result| result.recv().unwrap());
+        match results.next().unwrap() {
+            Err(JobError::Script {
+                error: Some(error), ..
+            }) => {
+                assert_eq!(error.code.as_deref(), Some("E3022"));
+                assert_eq!(error.message, "boom");
+            }
+            _ => panic!("Expected a panic of external function"),
+        }
+        for x in 1..3 {
+            let output = results.next().unwrap().unwrap();
+            assert_eq!(
+                output.globals["y"],
+                DiatomData::List(vec![DiatomData::Int(x), DiatomData::Int(x + 1)])
+            );
+            assert_eq!(output.stdout, x.to_string().into_bytes());
+        }
+
+        // Workers are still usable after a panic
+        let result = pool
+            .submit(Job::source("t = {}\nt.missing"))
+            .recv()
+            .unwrap();
+        match result {
+            Err(JobError::Script {
+                error: Some(error), ..
+            }) => {
+                assert_eq!(error.code.as_deref(), Some("E3010"))
+            }
+            _ => panic!("Expected a script error"),
+        }
+        let result = pool
+            .submit(Job::source("y = 1").output("z"))
+            .recv()
+            .unwrap();
+        assert!(matches!(result, Err(JobError::Script { error: None, .. })));
+    }
+
+    #[test]
+    fn test_channel() {
+        use std::thread;
+
+        use crate::{ffi::DiatomData, Channel};
+
+        let code = r#"
+            ch = channel()
+            ch.send(1)
+            ch.send([2, {a = 3}])
+            assert(ch.recv().value == 1)
+            l = ch.recv().value
+            assert(l[1].a == 3)
+            assert(ch.try_recv() is Option::None)
+            ch.close()
+            assert(ch.recv() is Option::None)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+
+        let requests = Channel::new();
+        let replies = Channel::new();
+        let worker = {
+            let (requests, replies) = (requests.clone(), replies.clone());
+            thread::spawn(move || {
+                let mut interpreter = Interpreter::new(vec![]);
+                interpreter.set_global("requests", requests);
+                interpreter.set_global("replies", replies);
+                let code = r#"
+                    loop
+                        x = requests.recv()
+                        if x is Option::None then break end
+                        replies.send(x.value * 2)
+                    end
+                "#;
+                interpreter.exec(code, "worker", true)
+            })
+        };
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter.set_global("requests", requests.clone());
+        interpreter
+            .exec("requests.send(21)", "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        assert_eq!(replies.recv(), Some(DiatomData::Int(42)));
+        requests.close();
+        worker.join().unwrap().expect("Worker failed");
+        assert!(interpreter.exec("requests.send(1)", "test", true).is_err());
+        let endpoint = interpreter.get_global_as::<Channel>("requests").unwrap();
+        assert!(endpoint.send(DiatomData::Nil).is_err());
+    }
+
+    #[test]
+    fn test_share() {
+        use std::{sync::Arc, thread};
+
+        use crate::{ffi::DiatomData, Channel, SharedValue};
+
+        let code = r#"
+            config = freeze({name = 'app', ports = [80, 443]})
+            shared = share(config)
+            ch = channel()
+            ch.send(shared)
+            received = ch.recv().value
+            ports = received.get('ports')
+            assert(ports.len() == 2 and ports.get(-1) == 443 and received.get('name') == 'app')
+            copy = ports.copy()
+            copy.append(8080)
+            println(copy)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(String::from_utf8(output).unwrap(), "[80, 443, 8080]\n");
+        let shared = interpreter.get_global_as::<SharedValue>("shared").unwrap();
+        let received = interpreter
+            .get_global_as::<SharedValue>("received")
+            .unwrap();
+        match (&shared, &received) {
+            (SharedValue::Table(t1), SharedValue::Table(t2)) => assert!(Arc::ptr_eq(t1, t2)),
+            _ => panic!("Expected shared tables"),
+        }
+        assert!(interpreter.exec("share([1])", "test", true).is_err());
+        assert!(interpreter
+            .exec("shared.get('missing')", "test", true)
+            .is_err());
+
+        let requests = Channel::new();
+        let replies = Channel::new();
+        let worker = {
+            let (requests, replies) = (requests.clone(), replies.clone());
+            thread::spawn(move || {
+                let mut interpreter = Interpreter::new(vec![]);
+                interpreter.set_global("requests", requests);
+                interpreter.set_global("replies", replies);
+                let code = "replies.send(requests.recv().value.get('ports').get(0))";
+                interpreter.exec(code, "worker", true)
+            })
+        };
+        requests.send_shared(shared).unwrap();
+        worker.join().unwrap().expect("Worker failed");
+        assert_eq!(replies.recv(), Some(DiatomData::Int(80)));
+    }
+
+    #[test]
+    fn test_log() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::{ffi::DiatomData, LogLevel, LogRecord};
+
+        let code = r#"
+            import std.log
+            log::info('started')
+            log::error('failed', {code = 3, path = 'a.dm'})
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[info] started\n[error] failed {code = 3, path = a.dm}\n"
+        );
+
+        let records = Arc::new(Mutex::new(vec![]));
+        let sink = records.clone();
+        interpreter.set_log_sink(move |record: LogRecord| sink.lock().unwrap().push(record));
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        assert!(interpreter.replace_buffer(vec![]).is_empty());
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].level, LogLevel::Info);
+        assert!(records[0].fields.is_empty());
+        assert_eq!(records[1].level, LogLevel::Error);
+        assert_eq!(records[1].message, "failed");
+        assert_eq!(records[1].fields["code"], DiatomData::Int(3));
+        assert_eq!(
+            records[1].fields["path"],
+            DiatomData::Str("a.dm".to_string())
+        );
+
+        assert!(interpreter
+            .exec("import std.log\nlog::info('x', 1)", "test", true)
+            .is_err());
+        assert!(interpreter
+            .exec("import std.log\nlog::info('x', {f = fn = 1})", "test", true)
+            .is_err());
+    }
+
+    #[test]
+    fn test_deadline() {
+        use std::time::Duration;
+
+        use crate::SandboxPolicy;
+
+        let mut interpreter = Interpreter::new(vec![]);
+        let mut policy = SandboxPolicy::allow_all();
+        policy.timeout = Some(Duration::from_millis(100));
+        interpreter.set_policy(policy);
+        let err = interpreter
+            .exec("ch = channel()\nch.recv()", "test", true)
+            .unwrap_err();
+        assert!(err.contains("E3027"), "{err}");
+        interpreter
+            .exec(
+                "ch = channel()\nch.send(1)\nassert(ch.recv().value == 1)",
+                "test",
+                true,
+            )
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        #[cfg(all(feature = "std-process", unix))]
+        {
+            let err = interpreter
+                .exec(
+                    "import Process from std.os.process\nProcess::run('sleep', ['10'], {})",
+                    "test",
+                    true,
+                )
+                .unwrap_err();
+            assert!(err.contains("E3027"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_string_builder() {
+        let code = r#"
+            s = string_builder()
+            assert(s.len() == 0)
+            expected = ''
+            for i in 0..1000 do
+                s.push('ab')
+                expected = expected + 'ab'
+            end
+            s.push(1).push([2])
+            assert(s.len() == 2004)
+            t = s.to_string()
+            s.clear().push('c')
+            assert(t == expected + '1[2]')
+            assert(s.to_string() == 'c')
+            push = s.push
+            push('d')
+            assert(s.to_string() == 'cd')
+            xs = [1]
+            append = xs.append
+            append(2)
+            assert(xs.len() == 2)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+    }
+
+    #[test]
+    fn test_flush_points() {
+        use std::io::{self, Write};
+
+        /// Record content seen at each flush
+        #[derive(Default)]
+        struct Console {
+            pending: Vec<u8>,
+            flushed: Vec<String>,
+        }
+
+        impl Write for Console {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.pending.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                let text = String::from_utf8(std::mem::take(&mut self.pending)).unwrap();
+                self.flushed.push(text);
+                Ok(())
+            }
+        }
+
+        let mut interpreter = Interpreter::new(Console::default());
+        // Skip flushes of loading prelude
+        interpreter.replace_buffer(Console::default());
+        interpreter
+            .exec(
+                "write('a')\nwrite(1)\nflush()\nprint('b', 2)\nprintln('c')\nwrite_line([3])\nwrite('d')",
+                "test",
+                true,
+            )
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let console = interpreter.replace_buffer(Console::default());
+        assert_eq!(console.flushed, vec!["a1", "b 2c\n", "[3]\n", "d"]);
+        assert!(interpreter.exec("write(1, 2)", "test", true).is_err());
+    }
+
+    #[cfg(feature = "repl")]
+    #[test]
+    fn test_repl_continuation() {
+        use crate::repl::Repl;
+
+        let mut repl = Repl::new(Interpreter::new(vec![]));
+        assert_eq!(repl.feed("def add x y ="), None);
+        assert_eq!(repl.feed("    x + y"), None);
+        assert_eq!(
+            repl.feed("end").as_deref(),
+            Some("def add x y =\n    x + y\nend")
+        );
+        assert_eq!(repl.feed("  "), None);
+        assert!(repl.feed("add(1, 2)").is_some());
+        let output = repl.interpreter().replace_buffer(vec![]);
+        assert_eq!(String::from_utf8(output).unwrap(), "3\n");
+    }
+
+    #[cfg(feature = "std-env")]
+    #[test]
+    fn test_script_args() {
+        use crate::{ScriptArgs, ENV_CAPABILITY};
+
+        let code = r#"
+            import {args} from std.os.env
+            assert(args().len() == 0)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        assert!(interpreter.exec(code, "test", true).is_err());
+        interpreter.enable_capability(ENV_CAPABILITY);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        interpreter.set_context(ScriptArgs(vec!["-v".to_string(), "a b".to_string()]));
+        let code = r#"
+            import {args} from std.os.env
+            argv = args()
+            assert(argv.len() == 2)
+            assert(argv[1] == "a b")
+            import std.os
+            assert(os.args()[1] == "a b")
+        "#;
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+    }
+
+    #[test]
+    fn test_testing() {
+        use crate::testing::run_tests;
+
+        let code = r#"
+            import {case, expect} from std.test
+            case("passing", fn = begin
+                expect(1 + 1).to_equal(2)
+                expect({a = [1, "x"], b = (1, 2)}).to_equal({b = (1, 2), a = [1, "x"]})
+                expect([1]).not_to_equal([2])
+                expect(1 < 2).to_be_true()
+                expect(0.1 + 0.2).to_be_close(0.3, 1e-9)
+                expect(None).to_be_none()
+                expect(Ok(1)).to_be_ok()
+                expect(Err(1)).to_be_err()
+                expect([1, 2, 3]).to_contain(2)
+            end)
+            case("unequal", fn = expect([1, 2]).to_equal([1, 3]))
+            case("missing", fn = expect([1, 2]).to_contain(3))
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        let results = run_tests(&mut interpreter, code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let names: Vec<_> = results.iter().map(|result| result.name.as_str()).collect();
+        assert_eq!(names, ["passing", "unequal", "missing"]);
+        assert!(results[0].passed(), "{:?}", results[0].error);
+        let error = results[1].error.as_ref().unwrap();
+        assert!(error.contains("expected: [1, 3]"), "{error}");
+        assert!(error.contains("actual: [1, 2]"), "{error}");
+        assert!(!results[2].passed());
+
+        // Cases can not be registered without a runner
+        let code = r#"
+            import {case} from std.test
+            case("x", fn = ())
+        "#;
+        assert!(interpreter.exec(code, "test", true).is_err());
+    }
+
+    #[test]
+    fn test_bench() {
+        use crate::benchmark::{compare, from_json, to_json, BenchStats};
+
+        // Results are printed without a runner
+        let code = r#"
+            import {run} from std.bench
+            run("sum", fn = (1..10).sum())
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = String::from_utf8(interpreter.replace_buffer(vec![])).unwrap();
+        assert!(output.starts_with("sum: mean "), "{output}");
+        assert!(output.ends_with("(100 iterations)\n"), "{output}");
+
+        let stats = BenchStats::from_samples("a \"b\"", &[4, 1, 3, 2]).unwrap();
+        assert_eq!((stats.mean, stats.median, stats.p95), (2.5, 2.5, 4.0));
+        let baseline = from_json(&to_json(std::slice::from_ref(&stats))).unwrap();
+        assert_eq!(baseline[0], stats);
+        let faster = BenchStats {
+            mean: 1.25,
+            ..stats
+        };
+        let comparison = &compare(&[faster], &baseline)[0];
+        assert_eq!(comparison.change(), Some(-0.5));
+        assert!(from_json("[{\"name\": \"a\"}]").is_err());
+    }
+
+    #[test]
+    fn test_eval() {
+        use diatom_std_core::MAX_EVAL_DEPTH;
+
+        let code = r#"
+            assert(eval("x * y", {x = 6, y = 7}).unwrap() == 42)
+            assert(eval("[1, {a = 'b'}]").unwrap()[1].a == 'b')
+            assert(eval("import {sqrt} from std.math\nsqrt(16.0)").unwrap().int() == 4)
+            assert(eval("1 +").is_err())
+            -- Globals of the caller are not seen
+            y = 1
+            assert(eval("y").is_err())
+            assert(eval("fn x = x").is_err())
+            eval("println('in eval')")
+            -- Results nested inside are converted into tables
+            err = inspect(eval("eval(c, {c = c})", {c = "eval(c, {c = c})"}), 64)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+        let output = interpreter.replace_buffer(vec![]);
+        assert_eq!(String::from_utf8(output).unwrap(), "in eval\n");
+        let err: String = interpreter.get_global_as("err").unwrap();
+        assert!(err.contains(&MAX_EVAL_DEPTH.to_string()), "{err}");
+
+        interpreter.disable_capability(diatom_std_core::EVAL_CAPABILITY);
+        let err = interpreter.exec("eval('1')", "test", true).unwrap_err();
+        assert!(err.contains("not allowed"), "{err}");
+    }
+
+    #[test]
+    fn test_fn_info() {
+        let code = r#"
+            def route path handler = fn_info(handler).value end
+            Api = {}
+            def Api.get self id = id end
+            api = {} <- Api
+
+            info = route("/", fn request = request)
+            assert(info.name is None)
+            assert(info.parameters.len() == 1 and info.parameters[0] == "request")
+            assert(info.file == "test" and info.line == 7)
+            info = fn_info(route).value
+            assert(info.name.value == "route" and info.arity == 2)
+            -- Receiver of a bound method is not a parameter
+            info = fn_info(api.get).value
+            assert(info.name.value == "get" and info.arity == 1)
+            assert(fn_info(println) is None)
+            assert(fn_info(1) is None)
+        "#;
+        let mut interpreter = Interpreter::new(vec![]);
+        interpreter
+            .exec(code, "test", true)
+            .map_err(|err| println!("{err}"))
+            .expect("Test failed");
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn test_include_script() {
+        use crate::{include_script, script, EmbeddedScript};
+
+        const FIB: EmbeddedScript = include_script!("benches/programs/fib.dm");
+        assert_eq!(FIB.source(), "benches/programs/fib.dm");
+        let mut interpreter = Interpreter::new(vec![]);
+        FIB.exec(&mut interpreter).unwrap();
+        assert_eq!(interpreter.get_global_as::<i64>("result"), Ok(6765));
+
+        // Names are resolved by the interpreter
+        let script = script!("y = undefined_name");
+        assert!(script.source().contains("lib.rs:"), "{}", script.source());
+        let err = script.exec(&mut interpreter).unwrap_err();
+        assert!(err.contains("undefined_name"), "{err}");
+    }
 }
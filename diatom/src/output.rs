@@ -0,0 +1,274 @@
+//! Output adapters for embedders
+//!
+//! The interpreter writes to any `IoWrite` and flushes it at flush points: after `println`,
+//! `write_line`, `flush()`, an `input` prompt and at the end of each execution. Implementing
+//! `IoWrite::flush` is thus a hook to update a console incrementally during long loops.
+//!
+//! - `LineBuffered` forwards output to a callback line by line.
+//! - `StringBuffer` captures output as a string, e.g. in tests or a web UI.
+//! - `BufferedWriter` batches writes to a slow sink such as a file.
+//!
+//! # Example
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use diatom::{output::LineBuffered, Interpreter};
+//!
+//! let console = Arc::new(Mutex::new(vec![]));
+//! let sink = console.clone();
+//! let mut interpreter = Interpreter::new(LineBuffered::new(move |text: &str| {
+//!     sink.lock().unwrap().push(text.to_string())
+//! }));
+//! interpreter
+//!     .exec("for i in 0..2 do write_line(i) end\nwrite('done')", "<test_code>", true)
+//!     .unwrap();
+//! assert_eq!(*console.lock().unwrap(), vec!["0\n", "1\n", "done"]);
+//! ```
+
+use std::{borrow::Cow, io};
+
+use crate::IoWrite;
+
+/// Forward output to a callback line by line
+///
+/// The callback is called with each complete line including its line break, and with pending
+/// text of an incomplete line at flush points, e.g. a prompt of `input`. Invalid UTF-8 is
+/// replaced by `U+FFFD`.
+pub struct LineBuffered<F: FnMut(&str)> {
+    pending: Vec<u8>,
+    callback: F,
+}
+
+impl<F: FnMut(&str)> LineBuffered<F> {
+    pub fn new(callback: F) -> Self {
+        Self {
+            pending: vec![],
+            callback,
+        }
+    }
+
+    /// Get the callback back, pending text is discarded
+    pub fn into_inner(self) -> F {
+        self.callback
+    }
+
+    fn emit(&mut self, end: usize) {
+        let text: Vec<u8> = self.pending.drain(..end).collect();
+        (self.callback)(&String::from_utf8_lossy(&text));
+    }
+}
+
+impl<F: FnMut(&str)> IoWrite for LineBuffered<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while let Some(i) = self.pending.iter().position(|b| *b == b'\n') {
+            self.emit(i + 1);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.emit(self.pending.len());
+        }
+        Ok(())
+    }
+}
+
+/// Capture output as a string
+///
+/// Writing beyond the size limit fails with an IO error, which stops the script, and only the
+/// part within the limit is kept. The line callback is called with each complete line without
+/// its line break, and with the incomplete last line when output is taken. Invalid UTF-8 is
+/// replaced by `U+FFFD` when text is read.
+///
+/// # Example
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use diatom::{capture::StringBuffer, Interpreter};
+///
+/// let lines = Arc::new(Mutex::new(vec![]));
+/// let sink = lines.clone();
+/// let buffer = StringBuffer::new()
+///     .limit(16)
+///     .on_line(move |line| sink.lock().unwrap().push(line.to_string()));
+/// let mut interpreter = Interpreter::new(buffer);
+/// interpreter.exec("println('Hello!')", "<test_code>", true).unwrap();
+/// assert!(interpreter.exec("println('Hello again!')", "<test_code>", true).is_err());
+///
+/// let mut output = interpreter.replace_buffer(StringBuffer::new());
+/// assert!(output.exceeded());
+/// assert_eq!(output.take(), "Hello!\nHello aga");
+/// assert_eq!(*lines.lock().unwrap(), vec!["Hello!", "Hello aga"]);
+/// ```
+#[derive(Default)]
+pub struct StringBuffer {
+    bytes: Vec<u8>,
+    limit: Option<usize>,
+    exceeded: bool,
+    /// Start of the line not passed to callback yet
+    line_start: usize,
+    on_line: Option<LineCallback>,
+}
+
+type LineCallback = Box<dyn FnMut(&str) + Send>;
+
+impl StringBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most `bytes` bytes of output
+    pub fn limit(mut self, bytes: usize) -> Self {
+        self.limit = Some(bytes);
+        self
+    }
+
+    /// Call `callback` with each line written
+    pub fn on_line(mut self, callback: impl FnMut(&str) + Send + 'static) -> Self {
+        self.on_line = Some(Box::new(callback));
+        self
+    }
+
+    /// Output captured so far
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.bytes)
+    }
+
+    /// Take output captured so far and start over, the limit applies to new output
+    ///
+    /// An incomplete last line is passed to the line callback first.
+    pub fn take(&mut self) -> String {
+        if let Some(callback) = self.on_line.as_mut() {
+            if self.line_start < self.bytes.len() {
+                callback(&String::from_utf8_lossy(&self.bytes[self.line_start..]));
+            }
+        }
+        self.line_start = 0;
+        self.exceeded = false;
+        match String::from_utf8(std::mem::take(&mut self.bytes)) {
+            Ok(text) => text,
+            Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+        }
+    }
+
+    /// Whether any output is dropped due to the size limit
+    pub fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
+impl IoWrite for StringBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let available = self
+            .limit
+            .map_or(buf.len(), |limit| limit.saturating_sub(self.bytes.len()));
+        self.bytes
+            .extend_from_slice(&buf[..buf.len().min(available)]);
+        if let Some(callback) = self.on_line.as_mut() {
+            while let Some(i) = self.bytes[self.line_start..]
+                .iter()
+                .position(|b| *b == b'\n')
+            {
+                let line = &self.bytes[self.line_start..self.line_start + i];
+                callback(&String::from_utf8_lossy(line));
+                self.line_start += i + 1;
+            }
+        }
+        if available < buf.len() {
+            self.exceeded = true;
+            let limit = self.limit.unwrap_or_default();
+            return Err(io::Error::other(format!(
+                "Output exceeds the limit of {limit} bytes"
+            )));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Batch writes to another writer
+///
+/// Unlike `std::io::BufWriter`, flush points of the interpreter do not write buffered output
+/// through, since `println` would otherwise reach the writer once per line. Output is written
+/// when the buffer is full, by `flush_buffer`, `into_inner` and when dropped. Thus it does not
+/// suit scripts prompting for input.
+///
+/// # Example
+/// ```
+/// use diatom::{output::BufferedWriter, Interpreter};
+///
+/// let mut interpreter = Interpreter::new(BufferedWriter::new(vec![]));
+/// interpreter.exec("for i in 0..3 do println(i) end", "<test_code>", true).unwrap();
+/// let output = interpreter.replace_buffer(BufferedWriter::new(vec![]));
+/// assert!(output.get_ref().is_empty());
+/// assert_eq!(output.into_inner().unwrap(), b"0\n1\n2\n");
+/// ```
+pub struct BufferedWriter<W: IoWrite> {
+    /// Only `None` after `into_inner`
+    inner: Option<W>,
+    buffer: Vec<u8>,
+    capacity: usize,
+}
+
+impl<W: IoWrite> BufferedWriter<W> {
+    /// Buffer up to 64 KiB
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(1 << 16, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        Self {
+            inner: Some(inner),
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Write buffered output through and flush the writer
+    pub fn flush_buffer(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        inner.write_all(&self.buffer)?;
+        self.buffer.clear();
+        inner.flush()
+    }
+
+    /// Write buffered output through and get the writer back
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.flush_buffer()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: IoWrite> IoWrite for BufferedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() + buf.len() > self.capacity {
+            self.flush_buffer()?;
+        }
+        if buf.len() >= self.capacity {
+            self.inner.as_mut().unwrap().write(buf)
+        } else {
+            self.buffer.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: IoWrite> Drop for BufferedWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            // Errors can not be reported while dropping, same as `std::io::BufWriter`
+            let _ = self.flush_buffer();
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Stages of running a script with the standard library, see [`Parser`], [`Compiler`] and [`Vm`]
+
+use crate::{Interpreter, IoWrite, ParsedSource, Program};
+
+/// Compile parsed source into programs in the environment of an interpreter
+///
+/// Globals and extensions of the interpreter are visible to the compiled code, and globals
+/// assigned by one program are visible to programs compiled later.
+///
+/// [`Parser`]: crate::Parser
+pub struct Compiler<Buffer: IoWrite>(Interpreter<Buffer>);
+
+impl<Buffer: IoWrite> Compiler<Buffer> {
+    pub fn new(interpreter: Interpreter<Buffer>) -> Self {
+        Self(interpreter)
+    }
+
+    /// Compile `parsed` into a program, see `Interpreter::compile_program`
+    ///
+    /// Source is parsed again by the compiler with imports resolved, thus errors of imported
+    /// modules are reported here.
+    pub fn compile(&mut self, parsed: &ParsedSource) -> Result<Program<Buffer>, String> {
+        let aliases = self.0.set_keyword_aliases(parsed.keyword_aliases().clone());
+        let program = self
+            .0
+            .compile_program(parsed.code(), parsed.source(), parsed.is_phony());
+        self.0.set_keyword_aliases(aliases);
+        program
+    }
+
+    pub fn interpreter(&self) -> &Interpreter<Buffer> {
+        &self.0
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter<Buffer> {
+        &mut self.0
+    }
+
+    pub fn into_interpreter(self) -> Interpreter<Buffer> {
+        self.0
+    }
+}
+
+/// Execute a compiled program with its own globals and heap
+///
+/// Byte code is shared with the program, thus creating a virtual machine is cheap.
+pub struct Vm<Buffer: IoWrite>(Interpreter<Buffer>);
+
+impl<Buffer: IoWrite> Vm<Buffer> {
+    /// Create a virtual machine ready to run `program`, writing output to `buffer`
+    pub fn new(program: &Program<Buffer>, buffer: Buffer) -> Self {
+        Self(program.spawn(buffer))
+    }
+
+    /// Run the program, globals keep the values left by the previous run
+    pub fn run(&mut self) -> Result<(), String> {
+        self.0.run()
+    }
+
+    /// Interpreter of this virtual machine, e.g. to read and set globals
+    pub fn interpreter(&self) -> &Interpreter<Buffer> {
+        &self.0
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter<Buffer> {
+        &mut self.0
+    }
+
+    pub fn into_interpreter(self) -> Interpreter<Buffer> {
+        self.0
+    }
+}
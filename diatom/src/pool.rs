@@ -0,0 +1,250 @@
+//! Run scripts on a pool of worker threads
+//!
+//! # Example
+//! ```
+//! use std::time::Duration;
+//! use diatom::{
+//!     ffi::DiatomData,
+//!     pool::{Job, JobError, ScriptPool},
+//! };
+//!
+//! let pool = ScriptPool::new(2);
+//! let results: Vec<_> = (0..4)
+//!     .map(|i| {
+//!         let job = Job::source("y = x * x")
+//!             .input("x", DiatomData::Int(i))
+//!             .output("y");
+//!         pool.submit(job)
+//!     })
+//!     .collect();
+//! for (i, result) in results.into_iter().enumerate() {
+//!     let output = result.recv().unwrap().unwrap();
+//!     assert_eq!(output.globals["y"], DiatomData::Int((i * i) as i64));
+//! }
+//!
+//! let job = Job::source("loop end").timeout(Duration::from_millis(10));
+//! assert!(matches!(pool.submit(job).recv().unwrap(), Err(JobError::Timeout)));
+//! ```
+
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    ffi::{DiatomData, ScriptError},
+    Interpreter, Program, SandboxPolicy,
+};
+
+/// Code run by a job
+pub enum Script {
+    /// Source code compiled by a fresh interpreter
+    Source(String),
+    /// Compiled program, each job runs in its own interpreter spawned from it
+    Program(Box<Program<Vec<u8>>>),
+}
+
+/// A script with its inputs and wanted outputs
+pub struct Job {
+    script: Script,
+    inputs: Vec<(String, DiatomData)>,
+    outputs: Vec<String>,
+    policy: Option<SandboxPolicy>,
+}
+
+impl Job {
+    pub fn source(code: impl Into<String>) -> Self {
+        Self::new(Script::Source(code.into()))
+    }
+
+    pub fn program(program: Program<Vec<u8>>) -> Self {
+        Self::new(Script::Program(Box::new(program)))
+    }
+
+    fn new(script: Script) -> Self {
+        Self {
+            script,
+            inputs: vec![],
+            outputs: vec![],
+            policy: None,
+        }
+    }
+
+    /// Set global variable `name` before running
+    pub fn input(mut self, name: impl Into<String>, value: DiatomData) -> Self {
+        self.inputs.push((name.into(), value));
+        self
+    }
+
+    /// Read global variable `name` after running
+    pub fn output(mut self, name: impl Into<String>) -> Self {
+        self.outputs.push(name.into());
+        self
+    }
+
+    /// Run with a sandbox policy instead of the default one
+    pub fn policy(mut self, policy: SandboxPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Abort the job once it has run longer than `timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.policy.get_or_insert_with(SandboxPolicy::new).timeout = Some(timeout);
+        self
+    }
+}
+
+/// Result of a successful job
+#[derive(Debug)]
+pub struct JobOutput {
+    /// Values of global variables requested by `Job::output`
+    pub globals: BTreeMap<String, DiatomData>,
+    /// Everything printed by the script
+    pub stdout: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum JobError {
+    /// Compilation or execution failed, or an output can not be read
+    Script {
+        /// Rendered diagnostics
+        message: String,
+        error: Option<ScriptError>,
+    },
+    /// Time limit of the job is reached
+    Timeout,
+    /// Interpreter panicked, the worker thread is still usable
+    Panic(String),
+}
+
+type Task = (Job, mpsc::Sender<Result<JobOutput, JobError>>);
+
+/// A fixed number of worker threads running jobs
+///
+/// Every job runs in its own interpreter, so jobs never share state. Dropping the pool waits for
+/// all submitted jobs to finish.
+pub struct ScriptPool {
+    sender: Option<mpsc::Sender<Task>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ScriptPool {
+    /// Create a pool with `workers` threads
+    ///
+    /// # Panics
+    /// Panic if `workers` is 0.
+    pub fn new(workers: usize) -> Self {
+        assert!(workers > 0, "A pool needs at least one worker");
+        let (sender, receiver) = mpsc::channel::<Task>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..workers)
+            .map(|_| {
+                let receiver = receiver.clone();
+                thread::spawn(move || loop {
+                    // Lock is released before running the job
+                    let task = receiver.lock().unwrap().recv();
+                    match task {
+                        Ok((job, result)) => {
+                            // Nobody is waiting if the receiver is dropped
+                            let _ = result.send(run_job(job));
+                        }
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// Queue a job and get a receiver of its result
+    pub fn submit(&self, job: Job) -> mpsc::Receiver<Result<JobOutput, JobError>> {
+        let (sender, receiver) = mpsc::channel();
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send((job, sender))
+            .expect("Worker threads never exit before the pool is dropped");
+        receiver
+    }
+}
+
+impl Drop for ScriptPool {
+    fn drop(&mut self) {
+        // Workers exit once the queue is closed and empty
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_job(job: Job) -> Result<JobOutput, JobError> {
+    panic::catch_unwind(AssertUnwindSafe(|| run_job_unchecked(job)))
+        .unwrap_or_else(|err| Err(JobError::Panic(panic_message(err))))
+}
+
+fn run_job_unchecked(job: Job) -> Result<JobOutput, JobError> {
+    let Job {
+        script,
+        inputs,
+        outputs,
+        policy,
+    } = job;
+    let mut interpreter = match &script {
+        Script::Source(_) => Interpreter::new(vec![]),
+        Script::Program(program) => program.spawn(vec![]),
+    };
+    if let Some(policy) = policy {
+        interpreter.set_policy(policy);
+    }
+    for (name, value) in inputs {
+        interpreter.set_global(name, value);
+    }
+    let result = match &script {
+        Script::Source(code) => interpreter.exec(code, "<job>", true),
+        Script::Program(_) => interpreter.run(),
+    };
+    if let Err(message) = result {
+        let error = interpreter.last_error().cloned();
+        return match error {
+            Some(ScriptError {
+                code: Some(code), ..
+            }) if code == "E3021" => Err(JobError::Timeout),
+            error => Err(JobError::Script { message, error }),
+        };
+    }
+    let globals = outputs
+        .into_iter()
+        .map(|name| {
+            interpreter
+                .get_global_as::<DiatomData>(&name)
+                .map(|value| (name, value))
+                .map_err(|message| JobError::Script {
+                    message,
+                    error: None,
+                })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(JobOutput {
+        globals,
+        stdout: interpreter.replace_buffer(vec![]),
+    })
+}
+
+fn panic_message(err: Box<dyn Any + Send>) -> String {
+    match err.downcast::<String>() {
+        Ok(message) => *message,
+        Err(err) => match err.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "Unknown panic".to_string(),
+        },
+    }
+}
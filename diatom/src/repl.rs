@@ -0,0 +1,107 @@
+//! Interactive console with line editing, requires feature `repl`
+//!
+//! # Example
+//! ```no_run
+//! use diatom::{repl::Repl, Interpreter};
+//!
+//! let mut repl = Repl::new(Interpreter::new(std::io::stdout())).with_history(".diatom_history");
+//! repl.run().unwrap();
+//! ```
+
+use std::path::PathBuf;
+
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use crate::{Interpreter, IoWrite};
+
+const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+
+/// Read, evaluate and print loop
+///
+/// * Input that can continue, e.g. with an unclosed parenthesis, is continued on the next line.
+/// * Value of the last expression is printed to output of the interpreter.
+/// * `Ctrl-C` cancels current input and `Ctrl-D` exits.
+/// * History is saved after each input if a history file is set.
+pub struct Repl<Buffer: IoWrite> {
+    interpreter: Interpreter<Buffer>,
+    history: Option<PathBuf>,
+    pending: String,
+}
+
+impl<Buffer: IoWrite> Repl<Buffer> {
+    /// Enable REPL mode of the interpreter and wrap it
+    pub fn new(mut interpreter: Interpreter<Buffer>) -> Self {
+        interpreter.repl(true);
+        Self {
+            interpreter,
+            history: None,
+            pending: String::new(),
+        }
+    }
+
+    /// Load history from and save history to `path`, the file is created if it does not exist
+    pub fn with_history(mut self, path: impl Into<PathBuf>) -> Self {
+        self.history = Some(path.into());
+        self
+    }
+
+    pub fn interpreter(&mut self) -> &mut Interpreter<Buffer> {
+        &mut self.interpreter
+    }
+
+    /// Run until `Ctrl-D` is received
+    ///
+    /// Errors of scripts are printed to stderr, only errors of terminal or history file are
+    /// returned.
+    pub fn run(&mut self) -> Result<(), String> {
+        let mut editor = DefaultEditor::new().map_err(|err| err.to_string())?;
+        if let Some(path) = &self.history {
+            // History file does not exist at first run
+            let _ = editor.load_history(path);
+        }
+        loop {
+            let prompt = if self.pending.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    if let Some(input) = self.feed(&line) {
+                        editor
+                            .add_history_entry(input)
+                            .map_err(|err| err.to_string())?;
+                        if let Some(path) = &self.history {
+                            editor.save_history(path).map_err(|err| err.to_string())?;
+                        }
+                    }
+                }
+                Err(ReadlineError::Interrupted) => self.pending.clear(),
+                Err(ReadlineError::Eof) => return Ok(()),
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+    }
+
+    /// Add a line to pending input and execute it once complete
+    ///
+    /// Return the executed input, None if input can continue or is empty.
+    pub(crate) fn feed(&mut self, line: &str) -> Option<String> {
+        if !self.pending.is_empty() {
+            self.pending.push('\n');
+        }
+        self.pending.push_str(line);
+        if !self.interpreter.verify_input_completeness(&self.pending) {
+            return None;
+        }
+        let input = std::mem::take(&mut self.pending);
+        if input.trim().is_empty() {
+            return None;
+        }
+        if let Err(err) = self.interpreter.exec(&input, "<interactive>", true) {
+            eprint!("{err}");
+        }
+        Some(input)
+    }
+}
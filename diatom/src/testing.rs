@@ -0,0 +1,110 @@
+//! Run test cases written with `std.test`
+//!
+//! # Example
+//! ```
+//! use diatom::{testing, Interpreter};
+//!
+//! let mut interpreter = Interpreter::new(Vec::<u8>::new());
+//! let results = testing::run_tests(
+//!     &mut interpreter,
+//!     r#"
+//!     import {case, expect} from std.test
+//!     case("sum", fn = expect([1, 2].len()).to_equal(2))
+//!     case("wrong", fn = expect([1, 2]).to_equal([1, 3]))
+//!     "#,
+//!     "<test>",
+//!     true,
+//! )
+//! .unwrap();
+//! assert!(results[0].passed());
+//! assert!(!results[1].passed());
+//! ```
+
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use diatom_std_core::TestCases;
+
+use crate::{Interpreter, IoWrite};
+
+/// Suffix of file names picked up by `discover`
+pub const TEST_FILE_SUFFIX: &str = "_test.dm";
+
+/// Outcome of a test case
+pub struct CaseResult {
+    /// Name given to `case`
+    pub name: String,
+    /// Rendered error if the case failed
+    pub error: Option<String>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Execute a test file then run every case it registers, in order of registration
+///
+/// # Return
+/// * Results of all cases
+/// * `Err(String)` if the file itself fails to compile or execute
+pub fn run_tests<Buffer: IoWrite>(
+    interpreter: &mut Interpreter<Buffer>,
+    code: impl AsRef<str>,
+    source: impl AsRef<OsStr>,
+    is_phony: bool,
+) -> Result<Vec<CaseResult>, String> {
+    interpreter.set_context(TestCases::default());
+    let result = interpreter.exec(code, source, is_phony);
+    let cases = interpreter.take_context::<TestCases>().unwrap_or_default();
+    result?;
+    Ok(cases
+        .0
+        .into_iter()
+        .map(|(name, f)| CaseResult {
+            error: interpreter.call_function(&f, &[]).err(),
+            name,
+        })
+        .collect())
+}
+
+/// Find test files under `path`, sorted by path
+///
+/// A file given directly is always included. Directories are searched recursively for files
+/// whose names end with `_test.dm`.
+pub fn discover(path: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    discover_files(path.as_ref(), TEST_FILE_SUFFIX)
+}
+
+/// Find files under `path` whose names end with `suffix`, sorted by path
+pub(crate) fn discover_files(path: &Path, suffix: &str) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    if path.is_dir() {
+        discover_dir(path, suffix, &mut files)?;
+        files.sort();
+    } else {
+        fs::metadata(path)?;
+        files.push(path.to_path_buf());
+    }
+    Ok(files)
+}
+
+fn discover_dir(dir: &Path, suffix: &str, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            discover_dir(&path, suffix, files)?;
+        } else if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(suffix))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}